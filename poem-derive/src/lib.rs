@@ -12,7 +12,8 @@ mod utils;
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{
-    parse_macro_input, AttributeArgs, FnArg, GenericParam, ItemFn, Member, Meta, NestedMeta, Result,
+    parse_macro_input, AttributeArgs, DeriveInput, Error, FnArg, GenericParam, ItemFn, Lit, Member,
+    Meta, MetaNameValue, NestedMeta, Result, Type,
 };
 
 /// Wrap an asynchronous function as an `Endpoint`.
@@ -120,6 +121,72 @@ fn generate_handler(internal: bool, input: TokenStream) -> Result<TokenStream> {
     Ok(expanded.into())
 }
 
+/// Implement `ExtensionKey` for a marker type, with the type of the value
+/// specified by the `extension` attribute.
+///
+/// # Example
+///
+/// ```ignore
+/// #[derive(ExtensionKey)]
+/// #[extension(value = "String")]
+/// struct RequestId;
+/// ```
+#[proc_macro_derive(ExtensionKey, attributes(extension))]
+pub fn derive_extension_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match generate_extension_key(input) {
+        Ok(stream) => stream,
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+fn generate_extension_key(input: DeriveInput) -> Result<TokenStream> {
+    let mut internal = false;
+    let mut value = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("extension"))
+    {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected `#[extension(...)]`")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(p)) if p.is_ident("internal") => internal = true,
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("value") => value = Some(lit.parse::<Type>()?),
+                nested => return Err(Error::new_spanned(nested, "unknown extension attribute")),
+            }
+        }
+    }
+
+    let value = value.ok_or_else(|| {
+        Error::new_spanned(
+            &input.ident,
+            "the value type is required: `#[extension(value = \"...\")]`",
+        )
+    })?;
+    let crate_name = utils::get_crate_name(internal);
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let expanded = quote! {
+        impl #impl_generics #crate_name::web::ExtensionKey for #ident #type_generics #where_clause {
+            type Value = #value;
+            const NAME: &'static str = #name;
+        }
+    };
+
+    Ok(expanded.into())
+}
+
 #[doc(hidden)]
 #[proc_macro]
 pub fn generate_implement_middlewares(_: TokenStream) -> TokenStream {
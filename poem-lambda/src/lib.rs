@@ -7,13 +7,21 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
 
-use std::{io::ErrorKind, ops::Deref, sync::Arc};
+use std::{
+    net::{IpAddr, SocketAddr},
+    ops::Deref,
+};
 
 pub use lambda_http::lambda_runtime::Error;
 use lambda_http::{
-    lambda_runtime, service_fn, Body as LambdaBody, Request as LambdaRequest, RequestExt,
+    lambda_runtime, request::RequestContext, service_fn, Body as LambdaBody,
+    Request as LambdaRequest,
+};
+use poem::{
+    endpoint::HyperService,
+    http::{uri::Scheme, Response},
+    Endpoint, EndpointExt, FromRequest, IntoEndpoint, Request, RequestBody, Result,
 };
-use poem::{Body, Endpoint, EndpointExt, FromRequest, IntoEndpoint, Request, RequestBody, Result};
 
 /// The Lambda function execution context.
 ///
@@ -42,6 +50,15 @@ impl Deref for Context {
 
 /// Starts the AWS Lambda runtime.
 ///
+/// The API Gateway (REST, HTTP and WebSocket APIs) and Application Load
+/// Balancer events are converted to requests, whose remote address is the
+/// source IP of the event with the port `0`. The base64 encoded request bodies
+/// are decoded, and the response bodies are base64 encoded unless their
+/// content type is textual.
+///
+/// The Lambda runtime does not support streaming the responses, so the
+/// response bodies are buffered.
+///
 /// # Example
 ///
 /// ```no_run
@@ -59,54 +76,86 @@ impl Deref for Context {
 /// }
 /// ```
 pub async fn run(ep: impl IntoEndpoint) -> Result<(), Error> {
-    let ep = Arc::new(ep.map_to_response().into_endpoint());
+    let svc = ep.into_endpoint().into_hyper_service();
     lambda_http::run(service_fn(move |req: LambdaRequest| {
-        let ctx = req.lambda_context();
-        let ep = ep.clone();
-        async move {
-            let mut req: Request = from_lambda_request(req);
-            req.extensions_mut().insert(Context(ctx));
-
-            let resp = ep.get_response(req).await;
-            let (parts, body) = resp.into_parts();
-            let data = body
-                .into_vec()
-                .await
-                .map_err(|_| std::io::Error::new(ErrorKind::Other, "invalid request"))?;
-            let mut lambda_resp = poem::http::Response::new(if data.is_empty() {
-                LambdaBody::Empty
-            } else {
-                match String::from_utf8(data) {
-                    Ok(data) => LambdaBody::Text(data),
-                    Err(err) => LambdaBody::Binary(err.into_bytes()),
-                }
-            });
-            *lambda_resp.status_mut() = parts.status;
-            *lambda_resp.version_mut() = parts.version;
-            *lambda_resp.headers_mut() = parts.headers;
-            *lambda_resp.extensions_mut() = parts.extensions;
-
-            Ok::<_, Error>(lambda_resp)
-        }
+        handle(svc.clone(), req)
     }))
     .await
 }
 
-fn from_lambda_request(req: LambdaRequest) -> Request {
+/// Returns `true` if the process is running in the AWS Lambda environment, to
+/// run the same application on Lambda and on a listener.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{handler, listener::TcpListener, Server};
+/// use poem_lambda::Error;
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Error> {
+///     if poem_lambda::is_lambda() {
+///         poem_lambda::run(index).await
+///     } else {
+///         Ok(Server::new(TcpListener::bind("127.0.0.1:3000"))
+///             .run(index)
+///             .await?)
+///     }
+/// }
+/// ```
+pub fn is_lambda() -> bool {
+    std::env::var_os("AWS_LAMBDA_RUNTIME_API").is_some()
+}
+
+async fn handle<E: Endpoint>(
+    svc: HyperService<E>,
+    req: LambdaRequest,
+) -> Result<Response<LambdaBody>, Error> {
+    let scheme = req.uri().scheme().cloned().unwrap_or(Scheme::HTTPS);
+    let mut svc = svc.scheme(scheme);
+    if let Some(ip) = source_ip(&req) {
+        svc = svc.remote_addr(SocketAddr::new(ip, 0));
+    }
+
+    let mut req = from_lambda_request(req);
+    if let Some(ctx) = req.extensions().get::<lambda_runtime::Context>().cloned() {
+        req.extensions_mut().insert(Context(ctx));
+    }
+
+    let (parts, body) = svc.handle(req).await.into_parts();
+    let data = hyper::body::to_bytes(body).await?;
+    let body = if data.is_empty() {
+        LambdaBody::Empty
+    } else {
+        // converted to text by the runtime if the content type is textual
+        LambdaBody::Binary(data.to_vec())
+    };
+    Ok(Response::from_parts(parts, body))
+}
+
+fn source_ip(req: &LambdaRequest) -> Option<IpAddr> {
+    let source_ip = match req.extensions().get::<RequestContext>()? {
+        RequestContext::ApiGatewayV1(ctx) => ctx.identity.source_ip.as_deref(),
+        RequestContext::ApiGatewayV2(ctx) => ctx.http.source_ip.as_deref(),
+        RequestContext::WebSocket(ctx) => ctx.identity.source_ip.as_deref(),
+        RequestContext::Alb(_) => None,
+    };
+    source_ip?.parse().ok()
+}
+
+fn from_lambda_request(req: LambdaRequest) -> hyper::Request<hyper::Body> {
     let (parts, lambda_body) = req.into_parts();
     let body = match lambda_body {
-        LambdaBody::Empty => Body::empty(),
-        LambdaBody::Text(data) => Body::from_string(data),
-        LambdaBody::Binary(data) => Body::from_vec(data),
+        LambdaBody::Empty => hyper::Body::empty(),
+        LambdaBody::Text(data) => data.into(),
+        LambdaBody::Binary(data) => data.into(),
     };
-    let mut req = Request::builder()
-        .method(parts.method)
-        .uri(parts.uri)
-        .version(parts.version)
-        .body(body);
-    *req.headers_mut() = parts.headers;
-    *req.extensions_mut() = parts.extensions;
-    req
+    hyper::Request::from_parts(parts, body)
 }
 
 #[poem::async_trait]
@@ -119,3 +168,71 @@ impl<'a> FromRequest<'a> for &'a Context {
         Ok(ctx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use lambda_http::RequestExt;
+    use poem::{handler, web::RemoteAddr};
+
+    use super::*;
+
+    #[handler]
+    fn echo(req: &Request, remote_addr: &RemoteAddr, ctx: &Context, body: Vec<u8>) -> String {
+        format!(
+            "{} {} {} {} {}",
+            req.uri(),
+            req.scheme(),
+            remote_addr,
+            ctx.request_id,
+            String::from_utf8(body).unwrap()
+        )
+    }
+
+    #[tokio::test]
+    async fn api_gateway_v2() {
+        let req = lambda_http::request::from_str(
+            r#"{
+                "version": "2.0",
+                "routeKey": "$default",
+                "rawPath": "/echo",
+                "rawQueryString": "a=1",
+                "headers": {
+                    "host": "xxx.execute-api.us-east-1.amazonaws.com",
+                    "x-forwarded-proto": "https"
+                },
+                "requestContext": {
+                    "accountId": "123456789012",
+                    "apiId": "xxx",
+                    "domainName": "xxx.execute-api.us-east-1.amazonaws.com",
+                    "http": {
+                        "method": "POST",
+                        "path": "/echo",
+                        "protocol": "HTTP/1.1",
+                        "sourceIp": "65.78.31.245"
+                    },
+                    "requestId": "MIZRNhJtIAMEMDw=",
+                    "routeKey": "$default",
+                    "stage": "$default",
+                    "timeEpoch": 1588804615616
+                },
+                "body": "aGVsbG8=",
+                "isBase64Encoded": true
+            }"#,
+        )
+        .unwrap();
+        let mut ctx = lambda_runtime::Context::default();
+        ctx.request_id = "abc".to_string();
+        let req = req.with_lambda_context(ctx);
+
+        let resp = handle(echo.into_hyper_service(), req).await.unwrap();
+        assert!(resp.status().is_success());
+        match resp.into_body() {
+            LambdaBody::Binary(data) => assert_eq!(
+                String::from_utf8(data).unwrap(),
+                "https://xxx.execute-api.us-east-1.amazonaws.com/echo?a=1 https \
+                 socket://65.78.31.245:0 abc hello"
+            ),
+            _ => panic!("unexpected body"),
+        }
+    }
+}
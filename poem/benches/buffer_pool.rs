@@ -0,0 +1,54 @@
+//! Benchmarks of the buffer pool, run with `cargo bench -p poem --bench
+//! buffer_pool`, and with `--features buffer-pool` to compare with the pool
+//! enabled.
+//!
+//! Each case serializes a JSON response with the specified number of items in
+//! a loop, and prints the average time per response of the fastest round. The
+//! `in_flight` cases keep the last responses alive, like a server sending
+//! them concurrently.
+
+use std::{collections::VecDeque, time::Instant};
+
+use poem::{web::Json, IntoResponse, Response};
+use serde_json::{json, Value};
+
+fn payload(items: usize) -> Value {
+    json!({
+        "items": (0..items)
+            .map(|id| json!({ "id": id, "name": format!("item {id}") }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn bench(name: &str, value: &Value, in_flight: usize) {
+    const ROUNDS: usize = 10;
+    const ITERATIONS: u32 = 20_000;
+
+    // the fastest round is the least disturbed by the other processes
+    let elapsed = (0..ROUNDS)
+        .map(|_| {
+            let mut responses = VecDeque::<Response>::with_capacity(in_flight + 1);
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                responses.push_back(Json(value).into_response());
+                if responses.len() > in_flight {
+                    responses.pop_front();
+                }
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    println!(
+        "{name:<24} {:>8.0} ns/iter",
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    for items in [1, 10, 100, 1000] {
+        let value = payload(items);
+        bench(&format!("json/{items}"), &value, 0);
+        bench(&format!("json/{items}/in_flight"), &value, 64);
+    }
+}
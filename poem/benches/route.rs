@@ -0,0 +1,95 @@
+//! Benchmarks of the routing, run with `cargo bench -p poem --bench route`.
+//!
+//! Each case calls a routing table of the specified size with the same
+//! request in a loop, and prints the average time per request of the fastest
+//! round.
+
+use std::time::Instant;
+
+use poem::{handler, Endpoint, Request, Route};
+
+#[handler]
+fn index() {}
+
+fn table(size: usize) -> Route {
+    (0..size).fold(Route::new(), |route, i| {
+        route
+            .at(format!("/static/{i}/items"), index)
+            .at(format!("/param/{i}/items/:id/comments/:comment"), index)
+            .at(format!("/regex/{i}/:id<\\d+>"), index)
+            .at(format!("/catch_all/{i}/*path"), index)
+    })
+}
+
+fn nested(size: usize) -> Route {
+    (0..size).fold(Route::new(), |route, i| {
+        route.nest(format!("/nest/{i}"), Route::new().at("/users/:id", index))
+    })
+}
+
+fn bench(rt: &tokio::runtime::Runtime, name: &str, route: &impl Endpoint, path: &str) {
+    const ROUNDS: usize = 10;
+    const ITERATIONS: u32 = 100_000;
+
+    // the fastest round is the least disturbed by the other processes
+    let elapsed = (0..ROUNDS)
+        .map(|_| {
+            rt.block_on(async {
+                let start = Instant::now();
+                for _ in 0..ITERATIONS {
+                    let req = Request::builder().uri_str(path).finish();
+                    assert!(route.call(req).await.is_ok());
+                }
+                start.elapsed()
+            })
+        })
+        .min()
+        .unwrap();
+    println!(
+        "{name:<24} {:>8.0} ns/iter",
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    bench(&rt, "no_routing", &index, "/");
+
+    for size in [10, 1000] {
+        let route = table(size);
+        let last = size - 1;
+        bench(
+            &rt,
+            &format!("static/{size}"),
+            &route,
+            &format!("/static/{last}/items"),
+        );
+        bench(
+            &rt,
+            &format!("param/{size}"),
+            &route,
+            &format!("/param/{last}/items/42/comments/7"),
+        );
+        bench(
+            &rt,
+            &format!("regex/{size}"),
+            &route,
+            &format!("/regex/{last}/42"),
+        );
+        bench(
+            &rt,
+            &format!("catch_all/{size}"),
+            &route,
+            &format!("/catch_all/{last}/a/b/c"),
+        );
+        bench(
+            &rt,
+            &format!("nested/{size}"),
+            &nested(size),
+            &format!("/nest/{last}/users/42"),
+        );
+    }
+}
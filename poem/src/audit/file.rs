@@ -0,0 +1,88 @@
+use std::{fs::File, io::Write, path::Path};
+
+use parking_lot::Mutex;
+
+use super::{AuditEvent, AuditSink};
+use crate::{error::InternalServerError, Result};
+
+/// An audit sink appending the events to a file, one JSON object per line.
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    /// Opens the file at `path` for appending the events, creating it if it
+    /// does not exist.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditSink for FileAuditSink {
+    async fn record(&self, event: &AuditEvent) -> Result<()> {
+        let mut line = serde_json::to_vec(event).map_err(InternalServerError)?;
+        line.push(b'\n');
+        self.file
+            .lock()
+            .write_all(&line)
+            .map_err(InternalServerError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::audit::AuditOutcome;
+
+    #[tokio::test]
+    async fn append() {
+        let path = std::env::temp_dir().join(format!("poem-audit-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let event = AuditEvent {
+            at: UNIX_EPOCH + Duration::from_millis(1500),
+            action: "order.delete".to_string(),
+            principal: Some("sunli".to_string()),
+            client_ip: None,
+            method: "DELETE".to_string(),
+            route: "/orders/:id".to_string(),
+            params: [("id".to_string(), "42".to_string())].into_iter().collect(),
+            status: 200,
+            outcome: AuditOutcome::Success,
+        };
+        for _ in 0..2 {
+            let sink = FileAuditSink::open(&path).unwrap();
+            sink.record(&event).await.unwrap();
+        }
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(lines[1]).unwrap(),
+            serde_json::json!({
+                "at": 1500,
+                "action": "order.delete",
+                "principal": "sunli",
+                "client_ip": null,
+                "method": "DELETE",
+                "route": "/orders/:id",
+                "params": { "id": "42" },
+                "status": 200,
+                "outcome": "success",
+            })
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::{AuditEvent, AuditSink};
+use crate::Result;
+
+/// An audit sink keeping the events in memory, for the tests.
+///
+/// Cloning a `MemoryAuditSink` returns a handle to the same events.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAuditSink {
+    events: Arc<Mutex<Vec<AuditEvent>>>,
+}
+
+impl MemoryAuditSink {
+    /// Create a `MemoryAuditSink`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the recorded events, the oldest first.
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events.lock().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditSink for MemoryAuditSink {
+    async fn record(&self, event: &AuditEvent) -> Result<()> {
+        self.events.lock().push(event.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+use std::{borrow::Cow, collections::BTreeMap, net::IpAddr, sync::Arc};
+
+use super::{AuditEvent, AuditOutcome, AuditSink};
+use crate::{
+    redact::{Redactor, REDACTED},
+    route::PathPattern,
+    web::{Clock, ForwardedInfo, Principal},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// The action of an auditable route, added to the data of the response for
+/// the [`AuditLog`] middleware.
+#[derive(Debug, Clone)]
+struct AuditTag {
+    action: Arc<str>,
+    principal: Option<Principal>,
+    client_ip: Option<IpAddr>,
+    params: BTreeMap<String, String>,
+}
+
+/// Middleware for tagging the routes whose requests are recorded by the
+/// [`AuditLog`] middleware.
+///
+/// The parameters of the path and the query string are only recorded if
+/// they are allowed with [`Auditable::param`], so that the secrets and the
+/// personal data are not written to the audit log by mistake. The
+/// [`Principal`] is read when the route is called, so the authentication
+/// middlewares must be applied outside of `Auditable`.
+pub struct Auditable {
+    action: Arc<str>,
+    params: Arc<[String]>,
+}
+
+impl Auditable {
+    /// Create an `Auditable` middleware for the action named `action`, such
+    /// as `order.delete`.
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            action: action.into().into(),
+            params: Arc::new([]),
+        }
+    }
+
+    /// Records the parameter `name` of the path or of the query string.
+    #[must_use]
+    pub fn param(self, name: impl Into<String>) -> Self {
+        let mut params = self.params.to_vec();
+        params.push(name.into());
+        Self {
+            params: params.into(),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Auditable {
+    type Output = AuditableEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        AuditableEndpoint {
+            inner: ep,
+            action: self.action.clone(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+/// Endpoint for Auditable middleware.
+pub struct AuditableEndpoint<E> {
+    inner: E,
+    action: Arc<str>,
+    params: Arc<[String]>,
+}
+
+impl<E> AuditableEndpoint<E> {
+    fn tag(&self, req: &Request) -> AuditTag {
+        let mut params = BTreeMap::new();
+        if !self.params.is_empty() {
+            let query = serde_urlencoded::from_str::<Vec<(String, String)>>(
+                req.uri().query().unwrap_or_default(),
+            )
+            .unwrap_or_default();
+            for name in self.params.iter() {
+                let value = req.raw_path_param(name).map(Cow::into_owned).or_else(|| {
+                    query
+                        .iter()
+                        .find(|(param, _)| param == name)
+                        .map(|(_, value)| value.clone())
+                });
+                if let Some(value) = value {
+                    params.insert(name.clone(), value);
+                }
+            }
+        }
+
+        AuditTag {
+            action: self.action.clone(),
+            principal: Principal::of(req),
+            client_ip: match req.extensions().get::<ForwardedInfo>() {
+                Some(info) => info.client_ip,
+                None => req.remote_addr().as_socket_addr().map(|addr| addr.ip()),
+            },
+            params,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for AuditableEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let tag = self.tag(&req);
+        match self.inner.call(req).await {
+            Ok(resp) => {
+                let mut resp = resp.into_response();
+                resp.set_data(tag);
+                Ok(resp)
+            }
+            Err(mut err) => {
+                err.set_data(tag);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Middleware for recording the requests to the [`Auditable`] routes into
+/// an [`AuditSink`].
+///
+/// The event is recorded once the endpoint has returned, before the
+/// response is sent. The errors of the sink are logged, and don't change the
+/// response. The requests rejected outside of the `Auditable` routes, such
+/// as by an authentication middleware, are not recorded.
+pub struct AuditLog<T> {
+    sink: Arc<T>,
+    redactor: Arc<Redactor>,
+}
+
+impl<T: AuditSink> AuditLog<T> {
+    /// Create an `AuditLog` middleware recording the events into `sink`.
+    pub fn new(sink: T) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            redactor: Arc::new(Redactor::new()),
+        }
+    }
+
+    /// Sets the redactor of the parameters, whose values are replaced with
+    /// [`REDACTED`] if they are redacted query keys, even if they are
+    /// allowed by the [`Auditable`] middleware.
+    #[must_use]
+    pub fn redactor(self, redactor: Redactor) -> Self {
+        Self {
+            redactor: Arc::new(redactor),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint, T: AuditSink> Middleware<E> for AuditLog<T> {
+    type Output = AuditLogEndpoint<E, T>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        AuditLogEndpoint {
+            inner: ep,
+            sink: self.sink.clone(),
+            redactor: self.redactor.clone(),
+        }
+    }
+}
+
+/// Endpoint for AuditLog middleware.
+pub struct AuditLogEndpoint<E, T> {
+    inner: E,
+    sink: Arc<T>,
+    redactor: Arc<Redactor>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint, T: AuditSink> Endpoint for AuditLogEndpoint<E, T> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let at = Clock::of(&req).now();
+        let method = req.method().to_string();
+        let path = req.original_uri().path().to_string();
+
+        let res = self.inner.call(req).await.map(IntoResponse::into_response);
+        let (tag, pattern, status) = match &res {
+            Ok(resp) => (
+                resp.data::<AuditTag>(),
+                resp.data::<PathPattern>(),
+                resp.status(),
+            ),
+            Err(err) => (
+                err.data::<AuditTag>(),
+                err.data::<PathPattern>(),
+                err.status(),
+            ),
+        };
+
+        if let Some(tag) = tag {
+            let mut params = tag.params.clone();
+            for (name, value) in &mut params {
+                if self.redactor.is_redacted_query_key(name) {
+                    *value = REDACTED.to_string();
+                }
+            }
+            let event = AuditEvent {
+                at,
+                action: tag.action.to_string(),
+                principal: tag.principal.as_ref().map(|principal| principal.0.clone()),
+                client_ip: tag.client_ip,
+                method,
+                route: pattern.map_or(path, |pattern| pattern.0.to_string()),
+                params,
+                status: status.as_u16(),
+                outcome: AuditOutcome::from_status(status),
+            };
+            if let Err(err) = self.sink.record(&event).await {
+                tracing::error!(
+                    action = %event.action,
+                    error = %err,
+                    "failed to record the audit event"
+                );
+            }
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::{
+        audit::MemoryAuditSink,
+        error::NotFoundError,
+        guard::RequireScope,
+        handler,
+        http::StatusCode,
+        post,
+        test::TestClient,
+        web::{MockClock, Path, Permissions},
+        EndpointExt, Route,
+    };
+
+    #[handler(internal)]
+    fn update(Path(id): Path<u32>) -> Result<String> {
+        match id {
+            0 => Err(NotFoundError.into()),
+            id => Ok(id.to_string()),
+        }
+    }
+
+    #[handler(internal)]
+    fn health() -> &'static str {
+        "ok"
+    }
+
+    #[tokio::test]
+    async fn record() {
+        let sink = MemoryAuditSink::new();
+        let app = Route::new()
+            .at(
+                "/orders/:id",
+                post(update.guard(RequireScope::new("orders:write"))).with(
+                    Auditable::new("order.update")
+                        .param("id")
+                        .param("reason")
+                        .param("token"),
+                ),
+            )
+            .at("/health", health)
+            .with(AuditLog::new(sink.clone()).redactor(Redactor::new().query_key("token")))
+            .before(|mut req: Request| async move {
+                if let Some(scopes) = req.header("x-scopes").map(ToString::to_string) {
+                    req.extensions_mut()
+                        .insert(scopes.split(',').collect::<Permissions>());
+                    req.extensions_mut().insert(Principal("sunli".to_string()));
+                }
+                Ok(req)
+            })
+            .data(Clock::from(MockClock::at(
+                UNIX_EPOCH + Duration::from_secs(1000),
+            )));
+        let cli = TestClient::new(app);
+
+        cli.post("/orders/1")
+            .query("reason", &"typo")
+            .query("token", &"secret")
+            .header("x-scopes", "orders:write")
+            .send()
+            .await
+            .assert_status_is_ok();
+        cli.post("/orders/0")
+            .header("x-scopes", "orders:write")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        cli.post("/orders/2")
+            .header("x-scopes", "orders:read")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        cli.get("/health").send().await.assert_status_is_ok();
+
+        let events = sink.events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            AuditEvent {
+                at: UNIX_EPOCH + Duration::from_secs(1000),
+                action: "order.update".to_string(),
+                principal: Some("sunli".to_string()),
+                client_ip: None,
+                method: "POST".to_string(),
+                route: "/orders/:id".to_string(),
+                params: [
+                    ("id".to_string(), "1".to_string()),
+                    ("reason".to_string(), "typo".to_string()),
+                    ("token".to_string(), REDACTED.to_string()),
+                ]
+                .into_iter()
+                .collect(),
+                status: 200,
+                outcome: AuditOutcome::Success,
+            }
+        );
+        assert_eq!(
+            (events[1].status, events[1].outcome),
+            (404, AuditOutcome::Failure)
+        );
+        assert_eq!(
+            (events[2].status, events[2].outcome),
+            (403, AuditOutcome::Denied)
+        );
+    }
+}
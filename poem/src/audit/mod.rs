@@ -0,0 +1,197 @@
+//! Audit logging of the security-relevant actions.
+//!
+//! The routes performing such actions are tagged with the [`Auditable`]
+//! middleware, which names the action and lists the parameters of the
+//! request worth recording. The [`AuditLog`] middleware, applied to the
+//! whole application, records an [`AuditEvent`] for each request to an
+//! auditable route into an [`AuditSink`]: who made the request, which route
+//! was called with which parameters, and the outcome.
+//!
+//! The events can be appended to a file with a [`FileAuditSink`], sent to
+//! the receivers of a [`WebhookDispatcher`](crate::webhook::WebhookDispatcher)
+//! with the `webhook` feature, or stored in a database by implementing
+//! [`AuditSink`].
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     audit::{AuditLog, AuditOutcome, Auditable, MemoryAuditSink},
+//!     delete, handler,
+//!     test::TestClient,
+//!     web::{Path, Principal},
+//!     EndpointExt, Request, Route,
+//! };
+//!
+//! #[handler]
+//! fn delete_order(Path(id): Path<u64>) -> String {
+//!     format!("deleted {id}")
+//! }
+//!
+//! let sink = MemoryAuditSink::new();
+//! let app = Route::new()
+//!     .at(
+//!         "/orders/:id",
+//!         delete(delete_order).with(Auditable::new("order.delete").param("id")),
+//!     )
+//!     .with(AuditLog::new(sink.clone()))
+//!     .before(|mut req: Request| async move {
+//!         req.extensions_mut().insert(Principal("sunli".to_string()));
+//!         Ok(req)
+//!     });
+//! let cli = TestClient::new(app);
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! cli.delete("/orders/42").send().await.assert_status_is_ok();
+//!
+//! let events = sink.events();
+//! assert_eq!(events[0].action, "order.delete");
+//! assert_eq!(events[0].principal.as_deref(), Some("sunli"));
+//! assert_eq!(events[0].route, "/orders/:id");
+//! assert_eq!(events[0].params["id"], "42");
+//! assert_eq!(events[0].outcome, AuditOutcome::Success);
+//! # });
+//! ```
+
+mod file;
+mod memory;
+mod middleware;
+
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc, time::SystemTime};
+
+pub use file::FileAuditSink;
+pub use memory::MemoryAuditSink;
+pub use middleware::{AuditLog, AuditLogEndpoint, Auditable, AuditableEndpoint};
+use serde::Serialize;
+
+use crate::{http::StatusCode, middleware::recorder::serialize_unix_ms, Result};
+
+/// The outcome of an audited request.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// The action succeeded.
+    Success,
+    /// The client is not allowed to perform the action, the response has the
+    /// `401 Unauthorized` or `403 Forbidden` status code.
+    Denied,
+    /// The action failed.
+    Failure,
+}
+
+impl AuditOutcome {
+    /// Returns the outcome of a response with the status code `status`.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AuditOutcome::Denied,
+            _ if status.is_success() || status.is_redirection() => AuditOutcome::Success,
+            _ => AuditOutcome::Failure,
+        }
+    }
+}
+
+/// A request to an [`Auditable`] route, recorded by the [`AuditLog`]
+/// middleware.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct AuditEvent {
+    /// The time the request was received, serialized as milliseconds since
+    /// the Unix epoch.
+    #[serde(serialize_with = "serialize_unix_ms")]
+    pub at: SystemTime,
+    /// The name of the action.
+    pub action: String,
+    /// The [`Principal`](crate::web::Principal) of the request, or `None` if
+    /// it is not authenticated.
+    pub principal: Option<String>,
+    /// The ip address of the client, if known.
+    pub client_ip: Option<IpAddr>,
+    /// The method of the request.
+    pub method: String,
+    /// The pattern of the route, or the path of the request if the route has
+    /// no pattern.
+    pub route: String,
+    /// The parameters of the path and the query string allowed by the
+    /// [`Auditable`] middleware.
+    pub params: BTreeMap<String, String>,
+    /// The status code of the response.
+    pub status: u16,
+    /// The outcome of the request.
+    pub outcome: AuditOutcome,
+}
+
+/// Represents a destination of the [`AuditEvent`]s.
+///
+/// # Example
+///
+/// A sink storing the events in a database:
+///
+/// ```ignore
+/// use poem::{
+///     audit::{AuditEvent, AuditSink},
+///     error::InternalServerError,
+///     Result,
+/// };
+///
+/// struct DbAuditSink(sqlx::PgPool);
+///
+/// #[poem::async_trait]
+/// impl AuditSink for DbAuditSink {
+///     async fn record(&self, event: &AuditEvent) -> Result<()> {
+///         sqlx::query("insert into audit_events (action, principal, event) values ($1, $2, $3)")
+///             .bind(&event.action)
+///             .bind(&event.principal)
+///             .bind(sqlx::types::Json(event))
+///             .execute(&self.0)
+///             .await
+///             .map_err(InternalServerError)?;
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Records an event.
+    async fn record(&self, event: &AuditEvent) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: AuditSink + ?Sized> AuditSink for Arc<T> {
+    async fn record(&self, event: &AuditEvent) -> Result<()> {
+        self.as_ref().record(event).await
+    }
+}
+
+/// Sends the events to the registered receivers, as the `audit` event.
+#[cfg(all(feature = "webhook", feature = "server"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "webhook", feature = "server"))))]
+#[async_trait::async_trait]
+impl AuditSink for crate::webhook::WebhookDispatcher {
+    async fn record(&self, event: &AuditEvent) -> Result<()> {
+        self.dispatch("audit", event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcome() {
+        assert_eq!(
+            AuditOutcome::from_status(StatusCode::CREATED),
+            AuditOutcome::Success
+        );
+        assert_eq!(
+            AuditOutcome::from_status(StatusCode::SEE_OTHER),
+            AuditOutcome::Success
+        );
+        assert_eq!(
+            AuditOutcome::from_status(StatusCode::FORBIDDEN),
+            AuditOutcome::Denied
+        );
+        assert_eq!(
+            AuditOutcome::from_status(StatusCode::NOT_FOUND),
+            AuditOutcome::Failure
+        );
+    }
+}
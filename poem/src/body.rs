@@ -1,21 +1,53 @@
 use std::{
     fmt::{Debug, Display, Formatter},
+    future::Future,
     io::{Error as IoError, ErrorKind},
     pin::Pin,
     task::{Context, Poll},
 };
 
 use bytes::{Bytes, BytesMut};
-use futures_util::{Stream, TryStreamExt};
+use futures_util::{ready, Stream, TryStreamExt};
+use http::HeaderMap;
 use hyper::body::HttpBody;
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::{
     error::{ParseJsonError, ReadBodyError},
+    web::PooledBuffer,
     Result,
 };
 
+/// A transformation applied to the data of a [`Body`] while it is streamed,
+/// see [`Body::transform`].
+///
+/// A transformer which needs to look ahead, for example to match a pattern
+/// split across two chunks, should only hold back a bounded part of the data
+/// instead of the whole body.
+pub trait BodyTransformer: Send + 'static {
+    /// Transforms a chunk of the body, and returns the data to send.
+    fn transform(&mut self, chunk: Bytes) -> Result<Bytes, IoError>;
+
+    /// Called at the end of the body, returns the data held back by the
+    /// transformer.
+    fn finish(&mut self) -> Result<Bytes, IoError> {
+        Ok(Bytes::new())
+    }
+}
+
+impl BodyTransformer for Box<dyn BodyTransformer> {
+    #[inline]
+    fn transform(&mut self, chunk: Bytes) -> Result<Bytes, IoError> {
+        (**self).transform(chunk)
+    }
+
+    #[inline]
+    fn finish(&mut self) -> Result<Bytes, IoError> {
+        (**self).finish()
+    }
+}
+
 /// A body object for requests and responses.
 #[derive(Default)]
 pub struct Body(pub(crate) hyper::Body);
@@ -119,7 +151,105 @@ impl Body {
 
     /// Create a body object from JSON.
     pub fn from_json(body: impl Serialize) -> serde_json::Result<Self> {
-        Ok(serde_json::to_vec(&body)?.into())
+        let mut data = PooledBuffer::acquire();
+        serde_json::to_writer(&mut *data, &body)?;
+        Ok(data.into_bytes().into())
+    }
+
+    /// Sends the trailer headers returned by `trailers` after the data of this
+    /// body.
+    ///
+    /// The future is awaited once all the data has been sent, so it can
+    /// observe the whole body, for example to compute a checksum. The names
+    /// of the trailers should be declared with the `Trailer` header of the
+    /// response.
+    ///
+    /// NOTE: Trailers are only sent over HTTP/2, they are discarded by
+    /// HTTP/1.1 connections.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{handler, http::HeaderMap, test::TestClient, Body, Response};
+    ///
+    /// #[handler]
+    /// fn index() -> Response {
+    ///     Response::builder()
+    ///         .header("trailer", "x-checksum")
+    ///         .body(Body::from("hello").with_trailers(async {
+    ///             let mut trailers = HeaderMap::new();
+    ///             trailers.insert("x-checksum", "5d41402a".parse().unwrap());
+    ///             Some(trailers)
+    ///         }))
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let resp = TestClient::new(index).get("/").send().await;
+    /// let (data, trailers) = resp.0.into_body().into_bytes_with_trailers().await.unwrap();
+    /// assert_eq!(data, "hello");
+    /// assert_eq!(trailers.unwrap()["x-checksum"], "5d41402a");
+    /// # });
+    /// ```
+    pub fn with_trailers<F>(self, trailers: F) -> Self
+    where
+        F: Future<Output = Option<HeaderMap>> + Send + 'static,
+    {
+        let (mut sender, body) = hyper::Body::channel();
+        let mut inner = self.0;
+
+        tokio::spawn(async move {
+            while let Some(data) = inner.data().await {
+                match data {
+                    Ok(data) => {
+                        if sender.send_data(data).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => {
+                        sender.abort();
+                        return;
+                    }
+                }
+            }
+            if let Some(trailers) = trailers.await {
+                let _ = sender.send_trailers(trailers).await;
+            }
+        });
+
+        Self(body)
+    }
+
+    /// Applies `transformer` to the data of this body while it is streamed.
+    ///
+    /// NOTE: The trailers of the body are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Error;
+    ///
+    /// use bytes::Bytes;
+    /// use poem::{Body, BodyTransformer};
+    ///
+    /// struct Uppercase;
+    ///
+    /// impl BodyTransformer for Uppercase {
+    ///     fn transform(&mut self, chunk: Bytes) -> Result<Bytes, Error> {
+    ///         Ok(chunk.to_ascii_uppercase().into())
+    ///     }
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let body = Body::from("hello").transform(Uppercase);
+    /// assert_eq!(body.into_string().await.unwrap(), "HELLO");
+    /// # });
+    /// ```
+    pub fn transform(self, transformer: impl BodyTransformer) -> Self {
+        Self::from_bytes_stream(TransformStream {
+            inner: self.into_bytes_stream(),
+            transformer,
+            finished: false,
+        })
     }
 
     /// Create an empty body.
@@ -141,6 +271,25 @@ impl Body {
             .map_err(|err| ReadBodyError::Io(IoError::new(ErrorKind::Other, err)))
     }
 
+    /// Consumes this body object to return a [`Bytes`] that contains all data,
+    /// and the trailer headers sent after it.
+    pub async fn into_bytes_with_trailers(
+        self,
+    ) -> Result<(Bytes, Option<HeaderMap>), ReadBodyError> {
+        let mut body = self.0;
+        let mut data = BytesMut::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(
+                &chunk.map_err(|err| ReadBodyError::Io(IoError::new(ErrorKind::Other, err)))?,
+            );
+        }
+        let trailers = body
+            .trailers()
+            .await
+            .map_err(|err| ReadBodyError::Io(IoError::new(ErrorKind::Other, err)))?;
+        Ok((data.freeze(), trailers))
+    }
+
     /// Consumes this body object to return a [`Vec<u8>`] that contains all
     /// data.
     pub async fn into_vec(self) -> Result<Vec<u8>, ReadBodyError> {
@@ -261,6 +410,49 @@ where
     }
 }
 
+pin_project_lite::pin_project! {
+    struct TransformStream<S, T> {
+        #[pin] inner: S,
+        transformer: T,
+        finished: bool,
+    }
+}
+
+impl<S, T> Stream for TransformStream<S, T>
+where
+    S: Stream<Item = Result<Bytes, IoError>>,
+    T: BodyTransformer,
+{
+    type Item = Result<Bytes, IoError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            if *this.finished {
+                return Poll::Ready(None);
+            }
+
+            let res = match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => this.transformer.transform(chunk),
+                Some(Err(err)) => Err(err),
+                None => {
+                    *this.finished = true;
+                    this.transformer.finish()
+                }
+            };
+            match res {
+                Ok(data) if data.is_empty() => continue,
+                Ok(data) => return Poll::Ready(Some(Ok(data))),
+                Err(err) => {
+                    *this.finished = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,4 +502,47 @@ mod tests {
         let body = Body::from_json("abc").unwrap();
         assert_eq!(body.into_json::<String>().await.unwrap(), "abc");
     }
+
+    #[tokio::test]
+    async fn trailers() {
+        let body = Body::from_bytes_stream(futures_util::stream::iter(
+            ["abc", "def"].map(|s| Ok::<_, std::io::Error>(Bytes::from_static(s.as_bytes()))),
+        ))
+        .with_trailers(async {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", "0".parse().unwrap());
+            Some(trailers)
+        });
+        let (data, trailers) = body.into_bytes_with_trailers().await.unwrap();
+        assert_eq!(data, "abcdef");
+        assert_eq!(trailers.unwrap()["grpc-status"], "0");
+
+        let body = Body::from("abc").with_trailers(async { None });
+        let (data, trailers) = body.into_bytes_with_trailers().await.unwrap();
+        assert_eq!(data, "abc");
+        assert!(trailers.is_none());
+    }
+
+    #[tokio::test]
+    async fn transform() {
+        struct Reverse(Vec<u8>);
+
+        impl BodyTransformer for Reverse {
+            fn transform(&mut self, chunk: Bytes) -> Result<Bytes, IoError> {
+                self.0.extend_from_slice(&chunk);
+                Ok(Bytes::new())
+            }
+
+            fn finish(&mut self) -> Result<Bytes, IoError> {
+                self.0.reverse();
+                Ok(std::mem::take(&mut self.0).into())
+            }
+        }
+
+        let body = Body::from_bytes_stream(futures_util::stream::iter(
+            ["abc", "def"].map(|s| Ok::<_, std::io::Error>(Bytes::from_static(s.as_bytes()))),
+        ))
+        .transform(Reverse(Vec::new()));
+        assert_eq!(body.into_string().await.unwrap(), "fedcba");
+    }
 }
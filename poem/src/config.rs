@@ -0,0 +1,340 @@
+//! Configuration which can be changed without restarting the server.
+//!
+//! A [`Config`] holds a value which is loaded from a file or from the
+//! environment variables, and can be reloaded while the server is running.
+//! It is shared with the handlers with the [`Data`](crate::web::Data)
+//! extractor, and middleware can [subscribe](Config::subscribe) to be
+//! notified when it changes.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use poem::{
+//!     config::Config, handler, listener::TcpListener, web::Data, EndpointExt, Route, Server,
+//! };
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Settings {
+//!     maintenance: bool,
+//! }
+//!
+//! #[handler]
+//! fn index(settings: Data<&Config<Settings>>) -> &'static str {
+//!     if settings.get().maintenance {
+//!         "down for maintenance"
+//!     } else {
+//!         "hello"
+//!     }
+//! }
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let server = Server::new(TcpListener::bind("127.0.0.1:3000"));
+//! let settings = Config::<Settings>::from_json_file("settings.json").unwrap();
+//! settings.watch(&server.tasks(), Duration::from_secs(5));
+//!
+//! server.run(Route::new().at("/", index).data(settings)).await
+//! # });
+//! ```
+
+use std::{path::PathBuf, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::error::ConfigError;
+
+type Parser<T> = Box<dyn Fn(&str) -> Result<T, String> + Send + Sync>;
+
+enum Source {
+    File(PathBuf),
+    Env(String),
+}
+
+impl Source {
+    fn read(&self) -> Result<String, ConfigError> {
+        match self {
+            Source::File(path) => Ok(std::fs::read_to_string(path)?),
+            Source::Env(prefix) => Ok(env_to_json(prefix, std::env::vars()).to_string()),
+        }
+    }
+}
+
+/// Converts the variables starting with `prefix` to a JSON object, with the
+/// names in lowercase and `__` separating the nested objects.
+fn env_to_json(prefix: &str, vars: impl Iterator<Item = (String, String)>) -> Value {
+    let mut vars = vars
+        .filter_map(|(name, value)| Some((name.strip_prefix(prefix)?.to_lowercase(), value)))
+        .collect::<Vec<_>>();
+    vars.sort();
+
+    let mut root = Value::Object(Default::default());
+    for (name, value) in vars {
+        let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+        let mut obj = &mut root;
+        let mut keys = name.split("__").peekable();
+        while let Some(key) = keys.next() {
+            let map = match obj {
+                Value::Object(map) => map,
+                _ => break,
+            };
+            if keys.peek().is_none() {
+                map.insert(key.to_string(), value);
+                break;
+            }
+            obj = map
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Object(Default::default()));
+        }
+    }
+    root
+}
+
+struct Loader<T> {
+    source: Source,
+    parse: Parser<T>,
+    last: Mutex<String>,
+}
+
+/// A value which can be reloaded from its source, and notifies the
+/// subscribers when it changes.
+///
+/// Cloning a `Config` returns a handle to the same value.
+pub struct Config<T> {
+    tx: Arc<watch::Sender<Arc<T>>>,
+    loader: Option<Arc<Loader<T>>>,
+}
+
+impl<T> Clone for Config<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            loader: self.loader.clone(),
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> Config<T> {
+    /// Create a config with the specified value, which can only be changed
+    /// with [`Config::set`].
+    pub fn new(value: T) -> Self {
+        Self {
+            tx: Arc::new(watch::channel(Arc::new(value)).0),
+            loader: None,
+        }
+    }
+
+    fn load(source: Source, parse: Parser<T>) -> Result<Self, ConfigError> {
+        let content = source.read()?;
+        let value = parse(&content).map_err(ConfigError::Parse)?;
+        let mut config = Self::new(value);
+        config.loader = Some(Arc::new(Loader {
+            source,
+            parse,
+            last: Mutex::new(content),
+        }));
+        Ok(config)
+    }
+
+    /// Loads the config from the file at `path`, with a function parsing its
+    /// content such as `|s| serde_yaml::from_str(s)`.
+    pub fn from_file<F, E>(path: impl Into<PathBuf>, parse: F) -> Result<Self, ConfigError>
+    where
+        F: Fn(&str) -> Result<T, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+    {
+        Self::load(
+            Source::File(path.into()),
+            Box::new(move |s| parse(s).map_err(|err| err.to_string())),
+        )
+    }
+
+    /// Loads the config from the JSON file at `path`.
+    pub fn from_json_file(path: impl Into<PathBuf>) -> Result<Self, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        Self::from_file(path, |s| serde_json::from_str(s))
+    }
+
+    /// Loads the config from the environment variables starting with
+    /// `prefix`.
+    ///
+    /// The names are converted to lowercase without the prefix, and `__`
+    /// separates the fields of the nested structs. For example with the
+    /// prefix `APP_`, `APP_DB__MAX_CONNECTIONS=10` is the `max_connections`
+    /// field of the `db` field. The values which are valid JSON are parsed as
+    /// JSON, so a string field containing a number must be quoted.
+    pub fn from_env(prefix: impl Into<String>) -> Result<Self, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        Self::load(
+            Source::Env(prefix.into()),
+            Box::new(|s| serde_json::from_str(s).map_err(|err| err.to_string())),
+        )
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> Arc<T> {
+        self.tx.borrow().clone()
+    }
+
+    /// Replaces the value and notifies the subscribers.
+    pub fn set(&self, value: T) {
+        self.tx.send_replace(Arc::new(value));
+    }
+
+    /// Reads the source of the config again, and replaces the value if the
+    /// source has changed.
+    ///
+    /// Returns `true` if the value was replaced. If the new source cannot be
+    /// parsed, the current value is kept.
+    pub fn reload(&self) -> Result<bool, ConfigError> {
+        let loader = match &self.loader {
+            Some(loader) => loader,
+            None => return Ok(false),
+        };
+
+        let content = loader.source.read()?;
+        let mut last = loader.last.lock();
+        if *last == content {
+            return Ok(false);
+        }
+        let value = (loader.parse)(&content).map_err(ConfigError::Parse)?;
+        *last = content;
+        self.set(value);
+        Ok(true)
+    }
+
+    /// Returns a subscriber which is notified when the value changes.
+    pub fn subscribe(&self) -> ConfigSubscriber<T> {
+        ConfigSubscriber {
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    /// Spawns a task which [reloads](Config::reload) the config at the
+    /// specified interval, until the server shuts down.
+    ///
+    /// The errors are logged, and the current value is kept.
+    #[cfg(feature = "server")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+    pub fn watch(&self, tasks: &crate::tasks::Tasks, interval: std::time::Duration) {
+        let config = self.clone();
+        tasks.spawn("config", move |signal| async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = signal.wait() => return Ok::<_, std::convert::Infallible>(()),
+                }
+                let config = config.clone();
+                match tokio::task::spawn_blocking(move || config.reload()).await {
+                    Ok(Ok(true)) => tracing::info!("config reloaded"),
+                    Ok(Ok(false)) => {}
+                    Ok(Err(err)) => tracing::error!(error = %err, "failed to reload the config"),
+                    Err(_) => tracing::error!("failed to reload the config"),
+                }
+            }
+        });
+    }
+}
+
+/// A subscriber to the changes of a [`Config`], returned by
+/// [`Config::subscribe`].
+pub struct ConfigSubscriber<T> {
+    rx: watch::Receiver<Arc<T>>,
+}
+
+impl<T> ConfigSubscriber<T> {
+    /// Returns the current value.
+    pub fn get(&self) -> Arc<T> {
+        self.rx.borrow().clone()
+    }
+
+    /// Waits for the value to change and returns it, or returns `None` if
+    /// the config has been dropped.
+    pub async fn changed(&mut self) -> Option<Arc<T>> {
+        self.rx.changed().await.ok()?;
+        Some(self.rx.borrow_and_update().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        rate_limit: u32,
+    }
+
+    #[tokio::test]
+    async fn set_and_subscribe() {
+        let config = Config::new(1);
+        let mut subscriber = config.subscribe();
+        assert_eq!(*subscriber.get(), 1);
+
+        config.clone().set(2);
+        assert_eq!(*config.get(), 2);
+        assert_eq!(subscriber.changed().await.as_deref(), Some(&2));
+        assert!(!config.reload().unwrap());
+
+        drop(config);
+        assert_eq!(subscriber.changed().await, None);
+    }
+
+    #[tokio::test]
+    async fn reload_file() {
+        let path = std::env::temp_dir().join(format!("poem-config-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"rate_limit": 10}"#).unwrap();
+
+        let config = Config::<Settings>::from_json_file(&path).unwrap();
+        let mut subscriber = config.subscribe();
+        assert_eq!(config.get().rate_limit, 10);
+        assert!(!config.reload().unwrap());
+
+        std::fs::write(&path, r#"{"rate_limit": 20}"#).unwrap();
+        assert!(config.reload().unwrap());
+        assert_eq!(subscriber.changed().await.unwrap().rate_limit, 20);
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(matches!(config.reload(), Err(ConfigError::Parse(_))));
+        assert_eq!(config.get().rate_limit, 20);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(config.reload(), Err(ConfigError::Io(_))));
+    }
+
+    #[test]
+    fn env() {
+        let vars = [
+            ("APP_RATE_LIMIT", "10"),
+            ("APP_NAME", "poem"),
+            ("APP_DB__URL", "postgres://localhost"),
+            ("APP_DB__POOL__SIZE", "5"),
+            ("OTHER", "1"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        assert_eq!(
+            env_to_json("APP_", vars),
+            json!({
+                "rate_limit": 10,
+                "name": "poem",
+                "db": {"url": "postgres://localhost", "pool": {"size": 5}},
+            })
+        );
+
+        std::env::set_var("POEM_CONFIG_TEST_RATE_LIMIT", "5");
+        let config = Config::<Settings>::from_env("POEM_CONFIG_TEST_").unwrap();
+        assert_eq!(*config.get(), Settings { rate_limit: 5 });
+    }
+}
@@ -0,0 +1,592 @@
+use std::{sync::Arc, time::SystemTime};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{header, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use tera::{Context, Tera};
+
+use crate::{
+    error::{InternalServerError, NotFoundError},
+    middleware::{Maintenance, Recorder},
+    tera::TeraReloader,
+    web::{Form, Html, Redirect},
+    Endpoint, FromRequest, IntoResponse, Request, Response, Result,
+};
+
+type AuthorizeFn = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+type MetricFn = Arc<dyn Fn() -> String + Send + Sync>;
+type SessionsFn = Arc<dyn Fn() -> usize + Send + Sync>;
+
+const TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("templates/base.html")),
+    ("index.html", include_str!("templates/index.html")),
+    ("routes.html", include_str!("templates/routes.html")),
+    ("errors.html", include_str!("templates/errors.html")),
+    ("metrics.html", include_str!("templates/metrics.html")),
+    ("templates.html", include_str!("templates/templates.html")),
+];
+
+/// An endpoint serving an introspection console for the operators, usually
+/// nested at `/_admin`.
+///
+/// The pages are:
+///
+/// - `/`: an overview, with the buttons for toggling the [`Maintenance`]
+///   mode and reloading the templates of
+///   [`TeraTemplating`](crate::tera::TeraTemplating).
+/// - `/routes`: the paths of the routing table.
+/// - `/errors`: the exchanges recorded by a [`Recorder`] which failed or
+///   responded with a server error.
+/// - `/metrics`: the values of the registered metrics.
+/// - `/templates`: the templates which each template extends, includes and
+///   imports, and the templates affected by an edit of it.
+///
+/// All the requests are rejected unless they are allowed by
+/// [`basic_auth`](AdminDashboard::basic_auth) or
+/// [`authorize`](AdminDashboard::authorize), and the forms only accept the
+/// requests from the same origin.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     endpoint::AdminDashboard,
+///     handler,
+///     middleware::{Maintenance, Recorder},
+///     test::TestClient,
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let maintenance = Maintenance::new().allow_path("/_admin");
+/// let recorder = Recorder::new(100);
+/// let app = Route::new().at("/", index).at("/users/:id", index);
+/// let dashboard = AdminDashboard::new()
+///     .basic_auth("admin", "secret")
+///     .routes(app.paths())
+///     .recorder(recorder.clone())
+///     .maintenance(maintenance.clone());
+/// let app = app
+///     .nest("/_admin", dashboard)
+///     .with(recorder)
+///     .with(maintenance);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let cli = TestClient::new(app);
+/// cli.get("/_admin/routes")
+///     .send()
+///     .await
+///     .assert_status(poem::http::StatusCode::UNAUTHORIZED);
+/// # });
+/// ```
+pub struct AdminDashboard {
+    tera: Tera,
+    authorize: Option<AuthorizeFn>,
+    basic_auth: bool,
+    routes: Vec<String>,
+    recorder: Option<Recorder>,
+    metrics: Vec<(String, MetricFn)>,
+    sessions: Option<SessionsFn>,
+    templates: Option<TeraReloader>,
+    maintenance: Option<Maintenance>,
+}
+
+impl Default for AdminDashboard {
+    fn default() -> Self {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(TEMPLATES.iter().copied())
+            .expect("valid admin dashboard templates");
+        Self {
+            tera,
+            authorize: None,
+            basic_auth: false,
+            routes: Vec::new(),
+            recorder: None,
+            metrics: Vec::new(),
+            sessions: None,
+            templates: None,
+            maintenance: None,
+        }
+    }
+}
+
+impl AdminDashboard {
+    /// Create an `AdminDashboard` endpoint, which rejects all the requests
+    /// until an authorization is configured.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Allows the requests with the HTTP basic authentication credentials,
+    /// and asks the browsers for them.
+    #[must_use]
+    pub fn basic_auth(self, username: impl AsRef<str>, password: impl AsRef<str>) -> Self {
+        let expected = format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", username.as_ref(), password.as_ref()))
+        );
+        Self {
+            basic_auth: true,
+            ..self.authorize(move |req| {
+                req.header(header::AUTHORIZATION)
+                    .map(|value| constant_time_eq(value.as_bytes(), expected.as_bytes()))
+                    .unwrap_or_default()
+            })
+        }
+    }
+
+    /// Allows the requests for which `f` returns `true`, for example the
+    /// requests of an administrator session.
+    #[must_use]
+    pub fn authorize(self, f: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            authorize: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Sets the paths listed on the routes page, usually from
+    /// [`Route::paths`](crate::Route::paths).
+    #[must_use]
+    pub fn routes<I, T>(self, routes: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            routes: routes.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sets the recorder whose failed exchanges are listed on the errors page.
+    #[must_use]
+    pub fn recorder(self, recorder: Recorder) -> Self {
+        Self {
+            recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    /// Adds a metric to the metrics page, whose value is read each time the
+    /// page is displayed.
+    #[must_use]
+    pub fn metric<F, T>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+        T: ToString,
+    {
+        self.metrics
+            .push((name.into(), Arc::new(move || f().to_string())));
+        self
+    }
+
+    /// Sets a function returning the number of sessions, such as
+    /// [`MemoryStorage::len`](crate::session::MemoryStorage::len).
+    #[must_use]
+    pub fn sessions(self, f: impl Fn() -> usize + Send + Sync + 'static) -> Self {
+        Self {
+            sessions: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Sets the templates reloaded by the reload button and listed on the
+    /// templates page.
+    #[must_use]
+    pub fn templates(self, reloader: TeraReloader) -> Self {
+        Self {
+            templates: Some(reloader),
+            ..self
+        }
+    }
+
+    /// Sets the maintenance mode toggled by the maintenance button.
+    #[must_use]
+    pub fn maintenance(self, maintenance: Maintenance) -> Self {
+        Self {
+            maintenance: Some(maintenance),
+            ..self
+        }
+    }
+
+    fn render(&self, page: &str, base: &str, notice: Option<(String, bool)>) -> Result<Response> {
+        let mut context = Context::new();
+        context.insert("page", page);
+        context.insert("base", base);
+        context.insert("routes", &self.routes);
+        context.insert("recorder", &self.recorder.is_some());
+        context.insert("errors", &self.errors());
+        context.insert("templates", &self.templates.is_some());
+        if let Some(sessions) = &self.sessions {
+            context.insert("sessions", &sessions());
+        }
+        if let Some(maintenance) = &self.maintenance {
+            context.insert("maintenance", &maintenance.is_enabled());
+        }
+        if page == "metrics" {
+            let metrics = self
+                .metrics
+                .iter()
+                .map(|(name, f)| Metric { name, value: f() })
+                .collect::<Vec<_>>();
+            context.insert("metrics", &metrics);
+        }
+        if page == "templates" {
+            context.insert("graph", &self.graph());
+        }
+        if let Some((notice, is_error)) = notice {
+            context.insert("notice", &notice);
+            context.insert("notice_is_error", &is_error);
+        }
+
+        let html = crate::tera::render(&self.tera, &format!("{page}.html"), &context)
+            .map_err(InternalServerError)?;
+        Ok(Html(html).into_response())
+    }
+
+    fn errors(&self) -> Vec<ErrorEntry> {
+        let now = SystemTime::now();
+        self.recorder
+            .iter()
+            .flat_map(Recorder::exchanges)
+            .filter_map(|exchange| {
+                let status = exchange.response.as_ref().map(|resp| resp.status);
+                if exchange.error.is_none() && status.unwrap_or_default() < 500 {
+                    return None;
+                }
+                Some(ErrorEntry {
+                    id: exchange.id,
+                    age: now
+                        .duration_since(exchange.started_at)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    method: exchange.request.method,
+                    uri: exchange.request.uri,
+                    status,
+                    duration: exchange.duration.as_secs_f64() * 1000.0,
+                    error: exchange.error,
+                })
+            })
+            .collect()
+    }
+
+    fn graph(&self) -> Vec<TemplateEntry> {
+        let graph = match &self.templates {
+            Some(templates) => templates.graph(),
+            None => return Vec::new(),
+        };
+        graph
+            .iter()
+            .map(|(name, dependencies)| {
+                let mut affected = graph.affected([name]);
+                affected.remove(name);
+                TemplateEntry {
+                    name: name.to_string(),
+                    extends: dependencies.extends.clone(),
+                    includes: dependencies.includes.iter().cloned().collect(),
+                    imports: dependencies.imports.iter().cloned().collect(),
+                    affected: affected.into_iter().collect(),
+                }
+            })
+            .collect()
+    }
+
+    async fn post(&self, req: Request, path: &str, base: &str) -> Result<Response> {
+        match path {
+            "/maintenance" => {
+                let maintenance = self.maintenance.as_ref().ok_or(NotFoundError)?;
+                let (req, mut body) = req.split();
+                let Form(form) = Form::<MaintenanceForm>::from_request(&req, &mut body).await?;
+                maintenance.set_enabled(form.enabled);
+                tracing::info!(enabled = form.enabled, "maintenance mode toggled");
+                Ok(Redirect::see_other(format!("{base}/")).into_response())
+            }
+            "/templates/reload" => {
+                let templates = self.templates.as_ref().ok_or(NotFoundError)?;
+                let notice = match templates.reload() {
+                    Ok(()) => ("The templates were reloaded.".to_string(), false),
+                    Err(err) => (format!("Failed to reload the templates: {err}"), true),
+                };
+                self.render("index", base, Some(notice))
+            }
+            _ => Err(NotFoundError.into()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Metric<'a> {
+    name: &'a str,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct ErrorEntry {
+    id: u64,
+    age: u64,
+    method: String,
+    uri: String,
+    status: Option<u16>,
+    duration: f64,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TemplateEntry {
+    name: String,
+    extends: Option<String>,
+    includes: Vec<String>,
+    imports: Vec<String>,
+    affected: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceForm {
+    enabled: bool,
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Returns `true` if the `Origin` header, which the browsers send with the
+/// forms, is missing or has the same host as the request.
+fn is_same_origin(req: &Request) -> bool {
+    let origin = match req.header(header::ORIGIN) {
+        Some(origin) => origin,
+        None => return true,
+    };
+    let origin_host = origin.split_once("://").map(|(_, host)| host);
+    origin_host.is_some() && origin_host == req.header(header::HOST)
+}
+
+#[async_trait::async_trait]
+impl Endpoint for AdminDashboard {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let authorized = self.authorize.as_ref().map(|f| f(&req)).unwrap_or_default();
+        if !authorized {
+            let mut resp = StatusCode::UNAUTHORIZED.into_response();
+            if self.basic_auth {
+                resp.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    header::HeaderValue::from_static("Basic realm=\"admin\""),
+                );
+            }
+            return Ok(resp);
+        }
+
+        // the prefix stripped by the routes this endpoint is nested in
+        let path = req.uri().path().to_string();
+        let original_path = req.original_uri().path();
+        let base = original_path
+            .strip_suffix(path.as_str())
+            .or_else(|| (path == "/").then_some(original_path))
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_string();
+
+        match *req.method() {
+            Method::GET | Method::HEAD => match path.as_str() {
+                "" | "/" => self.render("index", &base, None),
+                "/routes" => self.render("routes", &base, None),
+                "/errors" => self.render("errors", &base, None),
+                "/metrics" => self.render("metrics", &base, None),
+                "/templates" if self.templates.is_some() => self.render("templates", &base, None),
+                _ => Err(NotFoundError.into()),
+            },
+            Method::POST if !is_same_origin(&req) => Ok(StatusCode::FORBIDDEN.into_response()),
+            Method::POST => self.post(req, &path, &base).await,
+            _ => Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt, Route};
+
+    #[handler(internal)]
+    fn index() -> &'static str {
+        "hello"
+    }
+
+    #[handler(internal)]
+    fn fail() -> Result<()> {
+        Err(InternalServerError(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "database is down",
+        )))
+    }
+
+    const AUTH: &str = "Basic YWRtaW46c2VjcmV0";
+
+    #[tokio::test]
+    async fn dashboard() {
+        let maintenance = Maintenance::new().allow_path("/_admin");
+        let recorder = Recorder::new(10);
+        let app = Route::new().at("/", index).at("/fail", fail);
+        let dashboard = AdminDashboard::new()
+            .basic_auth("admin", "secret")
+            .routes(app.paths())
+            .recorder(recorder.clone())
+            .metric("requests", || 42)
+            .sessions(|| 3)
+            .maintenance(maintenance.clone());
+        let cli = TestClient::new(
+            app.nest("/_admin", dashboard)
+                .with(recorder)
+                .with(maintenance.clone()),
+        );
+
+        let resp = cli.get("/_admin").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        resp.assert_header(header::WWW_AUTHENTICATE, "Basic realm=\"admin\"");
+
+        let resp = cli
+            .get("/_admin")
+            .header(header::AUTHORIZATION, AUTH)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains("<strong>3</strong>sessions"), "{html}");
+        assert!(html.contains("href=\"&#x2F;_admin/routes\""), "{html}");
+
+        let resp = cli
+            .get("/_admin/routes")
+            .header(header::AUTHORIZATION, AUTH)
+            .send()
+            .await;
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains("<code>&#x2F;fail</code>"), "{html}");
+
+        cli.get("/fail")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let resp = cli
+            .get("/_admin/errors")
+            .header(header::AUTHORIZATION, AUTH)
+            .send()
+            .await;
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains("<code>GET &#x2F;fail</code>"), "{html}");
+        assert!(html.contains("database is down"), "{html}");
+
+        let resp = cli
+            .get("/_admin/metrics")
+            .header(header::AUTHORIZATION, AUTH)
+            .send()
+            .await;
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains("<td>42</td>"), "{html}");
+
+        let resp = cli
+            .post("/_admin/maintenance")
+            .header(header::AUTHORIZATION, AUTH)
+            .content_type("application/x-www-form-urlencoded")
+            .body("enabled=true")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::SEE_OTHER);
+        resp.assert_header(header::LOCATION, "/_admin/");
+        assert!(maintenance.is_enabled());
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn forms() {
+        let ep = AdminDashboard::new()
+            .authorize(|_| true)
+            .templates(crate::tera::TeraTemplating::custom(Tera::default()).reloader());
+        let cli = TestClient::new(ep);
+
+        cli.post("/templates/reload")
+            .header(header::ORIGIN, "https://evil.example")
+            .header(header::HOST, "localhost")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+
+        let resp = cli
+            .post("/templates/reload")
+            .header(header::ORIGIN, "http://localhost")
+            .header(header::HOST, "localhost")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains("Failed to reload the templates"), "{html}");
+
+        cli.post("/maintenance")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        cli.delete("/")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn templates() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base.html", "{% block content %}{% endblock %}"),
+            ("nav.html", "nav"),
+            (
+                "index.html",
+                "{% extends \"base.html\" %}{% block content %}{% include \"nav.html\" %}{% \
+                 endblock %}",
+            ),
+        ])
+        .unwrap();
+        let cli = TestClient::new(
+            AdminDashboard::new()
+                .authorize(|_| true)
+                .templates(crate::tera::TeraTemplating::custom(tera).reloader()),
+        );
+
+        let resp = cli.get("/templates").send().await;
+        resp.assert_status_is_ok();
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(
+            html.contains(
+                "<td><code>nav.html</code></td>\n    <td></td>\n    <td></td>\n    \
+                 <td></td>\n    <td><code>index.html</code> </td>"
+            ),
+            "{html}"
+        );
+        assert!(
+            html.contains(
+                "<td><code>index.html</code></td>\n    <td><code>base.html</code></td>\n    \
+                 <td><code>nav.html</code> </td>"
+            ),
+            "{html}"
+        );
+
+        TestClient::new(AdminDashboard::new().authorize(|_| true))
+            .get("/templates")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_by_default() {
+        let resp = TestClient::new(AdminDashboard::new()).get("/").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        assert!(!resp.0.headers().contains_key(header::WWW_AUTHENTICATE));
+    }
+}
@@ -1,11 +1,12 @@
 use std::{future::Future, marker::PhantomData, sync::Arc};
 
 use super::{
-    After, AndThen, Around, Before, CatchAllError, CatchError, InspectAllError, InspectError, Map,
-    MapToResponse, ToResponse,
+    After, AndThen, Around, Before, CatchAllError, CatchError, HyperService, InspectAllError,
+    InspectError, Map, MapToResponse, ToResponse,
 };
 use crate::{
     error::IntoResult,
+    guard::{Guard, GuardEndpoint},
     middleware::{AddData, AddDataEndpoint},
     Error, IntoResponse, Middleware, Request, Response, Result,
 };
@@ -485,6 +486,17 @@ pub trait EndpointExt: IntoEndpoint {
         MapToResponse::new(self.into_endpoint())
     }
 
+    /// Converts this endpoint to a [`hyper::service::Service`], to mount it
+    /// inside another hyper server.
+    ///
+    /// See [`HyperService`] for more details.
+    fn into_hyper_service(self) -> HyperService<Self::Endpoint>
+    where
+        Self: Sized,
+    {
+        HyperService::new(self.into_endpoint())
+    }
+
     /// Convert the output of this endpoint into a response.
     /// [`Response`](crate::Response).
     ///
@@ -699,6 +711,38 @@ pub trait EndpointExt: IntoEndpoint {
         InspectAllError::new(self, f)
     }
 
+    /// Rejects the requests which don't satisfy the `guard`, before calling
+    /// this endpoint.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{guard::ContentType, handler, http::StatusCode, test::TestClient, EndpointExt};
+    ///
+    /// #[handler]
+    /// fn index(body: String) -> String {
+    ///     body
+    /// }
+    ///
+    /// let cli = TestClient::new(index.guard(ContentType::new("text/plain")));
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// cli.post("/")
+    ///     .content_type("application/json")
+    ///     .body("{}")
+    ///     .send()
+    ///     .await
+    ///     .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    /// # });
+    /// ```
+    fn guard<G>(self, guard: G) -> GuardEndpoint<Self, G>
+    where
+        G: Guard,
+        Self: Sized,
+    {
+        GuardEndpoint::new(self, guard)
+    }
+
     /// Does something with each specified error type.
     ///
     /// # Example
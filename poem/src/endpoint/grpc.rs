@@ -0,0 +1,207 @@
+use std::{collections::HashMap, convert::Infallible, error::Error as StdError, future::Future};
+
+use bytes::Bytes;
+use hyper::body::HttpBody;
+use tonic::server::NamedService;
+use tower::Service;
+
+use crate::{
+    endpoint::{BoxEndpoint, TowerCompatExt},
+    http::{header, HeaderMap},
+    Endpoint, EndpointExt, IntoResponse, Request, Response, Result,
+};
+
+/// The `grpc-status` of the requests to a service which is not mounted.
+const UNIMPLEMENTED: &str = "12";
+
+/// An endpoint serving the [`tonic`](https://crates.io/crates/tonic) services
+/// alongside the other routes of an application, on the same listener.
+///
+/// The gRPC requests, whose content type is `application/grpc`, are
+/// dispatched to the service named by the first segment of their path, and
+/// the other requests to the inner endpoint. Since the services are served
+/// by this endpoint, the middlewares applied to it, such as the
+/// authentication, the metrics or the tracing, apply to both.
+///
+/// The gRPC clients use HTTP/2, which the server accepts over TLS with ALPN,
+/// or over cleartext with prior knowledge.
+///
+/// # Example
+///
+/// ```no_compile
+/// use poem::{endpoint::Grpc, get, listener::TcpListener, middleware::Tracing, EndpointExt, Route, Server};
+///
+/// let app = Grpc::new(Route::new().at("/", get(index)))
+///     .add_service(GreeterServer::new(MyGreeter))
+///     .with(Tracing);
+///
+/// Server::new(TcpListener::bind("127.0.0.1:3000")).run(app).await?;
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "grpc")))]
+pub struct Grpc<E> {
+    inner: E,
+    services: HashMap<&'static str, BoxEndpoint<'static>>,
+}
+
+impl<E> Grpc<E> {
+    /// Create a `Grpc` endpoint, dispatching the requests which are not gRPC
+    /// requests to `inner`.
+    pub fn new(inner: E) -> Self {
+        Self {
+            inner,
+            services: HashMap::new(),
+        }
+    }
+
+    /// Mounts a service generated by `tonic-build`, such as
+    /// `GreeterServer::new(MyGreeter)`, at the path of its name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a service of the same name is already mounted.
+    #[must_use]
+    pub fn add_service<S, ResBody, Fut>(mut self, service: S) -> Self
+    where
+        S: Service<
+                http::Request<hyper::Body>,
+                Response = http::Response<ResBody>,
+                Error = Infallible,
+                Future = Fut,
+            > + NamedService
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        ResBody: HttpBody + Send + 'static,
+        ResBody::Data: Into<Bytes> + Send + 'static,
+        ResBody::Error: StdError + Send + Sync + 'static,
+        Fut: Future<Output = Result<http::Response<ResBody>, Infallible>> + Send + 'static,
+    {
+        assert!(
+            !self.services.contains_key(S::NAME),
+            "duplicate gRPC service: {}",
+            S::NAME
+        );
+        self.services.insert(S::NAME, service.compat().boxed());
+        self
+    }
+}
+
+fn is_grpc(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("application/grpc"))
+        .map_or(false, |suffix| {
+            suffix.is_empty() || suffix.starts_with('+') || suffix.starts_with(';')
+        })
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for Grpc<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if !is_grpc(req.headers()) {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let name = req
+            .uri()
+            .path()
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        match self.services.get(name) {
+            Some(service) => service.call(req).await,
+            None => Ok(Response::builder()
+                .content_type("application/grpc")
+                .header("grpc-status", UNIMPLEMENTED)
+                .header("grpc-message", format!("service `{name}` not found"))
+                .finish()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use futures_util::future::Ready;
+
+    use super::*;
+    use crate::{handler, test::TestClient};
+
+    #[derive(Clone)]
+    struct EchoServer;
+
+    impl NamedService for EchoServer {
+        const NAME: &'static str = "echo.Echo";
+    }
+
+    impl Service<http::Request<hyper::Body>> for EchoServer {
+        type Response = http::Response<hyper::Body>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<hyper::Body>) -> Self::Future {
+            let resp = http::Response::builder()
+                .header(header::CONTENT_TYPE, "application/grpc")
+                .header("grpc-status", "0")
+                .body(req.into_body())
+                .unwrap();
+            futures_util::future::ready(Ok(resp))
+        }
+    }
+
+    #[handler(internal)]
+    fn index() -> &'static str {
+        "index"
+    }
+
+    #[test]
+    fn grpc_content_type() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, value.parse().unwrap());
+            headers
+        };
+        assert!(is_grpc(&headers("application/grpc")));
+        assert!(is_grpc(&headers("application/grpc+proto")));
+        assert!(!is_grpc(&headers("application/grpc-web")));
+        assert!(!is_grpc(&headers("application/json")));
+        assert!(!is_grpc(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn dispatch() {
+        let cli = TestClient::new(Grpc::new(index).add_service(EchoServer));
+
+        cli.post("/echo.Echo/Echo")
+            .body("abc")
+            .send()
+            .await
+            .assert_text("index")
+            .await;
+
+        let resp = cli
+            .post("/echo.Echo/Echo")
+            .content_type("application/grpc")
+            .body("abc")
+            .send()
+            .await;
+        resp.assert_header("grpc-status", "0");
+        resp.assert_text("abc").await;
+
+        cli.post("/other.Other/Call")
+            .content_type("application/grpc")
+            .send()
+            .await
+            .assert_header("grpc-status", UNIMPLEMENTED);
+    }
+}
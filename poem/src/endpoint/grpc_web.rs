@@ -0,0 +1,238 @@
+//! Translation between gRPC-web and native gRPC for the
+//! [`Proxy`](crate::endpoint::Proxy) endpoint.
+//!
+//! Reference: <https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md>
+
+use base64::{engine::general_purpose::STANDARD, DecodeError, Engine};
+use bytes::{BufMut, Bytes, BytesMut};
+use http::{header, HeaderMap, HeaderValue};
+
+/// The flag of the frame carrying the trailers at the end of a gRPC-web
+/// response body.
+const TRAILERS_FLAG: u8 = 0x80;
+
+/// A gRPC-web request, with a `application/grpc-web[-text][+format]` content
+/// type.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct GrpcWeb {
+    /// The body is base64 encoded.
+    pub(crate) text: bool,
+    /// The message format, such as `+proto`, or an empty string.
+    suffix: String,
+}
+
+impl GrpcWeb {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        let content_type = content_type.split(';').next()?.trim();
+        let rest = content_type.strip_prefix("application/grpc-web")?;
+        let (text, suffix) = match rest.strip_prefix("-text") {
+            Some(suffix) => (true, suffix),
+            None => (false, rest),
+        };
+        if !suffix.is_empty() && !suffix.starts_with('+') {
+            return None;
+        }
+        Some(Self {
+            text,
+            suffix: suffix.to_string(),
+        })
+    }
+
+    /// The content type of the request forwarded to the upstream.
+    pub(crate) fn upstream_content_type(&self) -> Option<HeaderValue> {
+        HeaderValue::from_str(&format!("application/grpc{}", self.suffix)).ok()
+    }
+
+    /// Converts the content type of a native gRPC response, or returns `None`
+    /// if the response is not a gRPC response.
+    pub(crate) fn response_content_type(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        let suffix = content_type.strip_prefix("application/grpc")?;
+        if !suffix.is_empty() && !suffix.starts_with('+') {
+            return None;
+        }
+        let text = if self.text { "-text" } else { "" };
+        HeaderValue::from_str(&format!("application/grpc-web{text}{suffix}")).ok()
+    }
+}
+
+/// Decodes the base64 body of a `grpc-web-text` request, which arrives in
+/// chunks of any size and may contain padding between the messages.
+#[derive(Default)]
+pub(crate) struct Base64Decoder {
+    pending: Vec<u8>,
+}
+
+impl Base64Decoder {
+    pub(crate) fn decode(&mut self, chunk: &[u8]) -> Result<Bytes, DecodeError> {
+        self.pending
+            .extend(chunk.iter().filter(|b| !b.is_ascii_whitespace()));
+        let complete = self.pending.len() / 4 * 4;
+
+        let mut data = Vec::with_capacity(complete / 4 * 3);
+        let mut start = 0;
+        for end in (4..=complete).step_by(4) {
+            if end == complete || self.pending[end - 1] == b'=' {
+                STANDARD.decode_vec(&self.pending[start..end], &mut data)?;
+                start = end;
+            }
+        }
+        self.pending.drain(..complete);
+        Ok(data.into())
+    }
+
+    pub(crate) fn finish(self) -> Result<(), DecodeError> {
+        match self.pending.len() {
+            0 => Ok(()),
+            _ => Err(DecodeError::InvalidLength),
+        }
+    }
+}
+
+/// Encodes the body of a gRPC-web response, appending the trailers as the
+/// last frame.
+pub(crate) struct Encoder {
+    text: bool,
+    // the bytes not encoded yet, shorter than a base64 group
+    pending: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn new(text: bool) -> Self {
+        Self {
+            text,
+            pending: Vec::new(),
+        }
+    }
+
+    pub(crate) fn encode(&mut self, data: Bytes) -> Bytes {
+        if !self.text {
+            return data;
+        }
+        self.pending.extend_from_slice(&data);
+        let complete = self.pending.len() / 3 * 3;
+        let encoded = STANDARD.encode(&self.pending[..complete]);
+        self.pending.drain(..complete);
+        encoded.into()
+    }
+
+    /// Encodes the trailers frame and the rest of the body.
+    pub(crate) fn finish(&mut self, trailers: Option<&HeaderMap>) -> Bytes {
+        let mut data = match trailers {
+            Some(trailers) => self.encode(encode_trailers(trailers)).to_vec(),
+            None => Vec::new(),
+        };
+        if self.text && !self.pending.is_empty() {
+            data.extend(STANDARD.encode(&self.pending).into_bytes());
+            self.pending.clear();
+        }
+        data.into()
+    }
+}
+
+fn encode_trailers(trailers: &HeaderMap) -> Bytes {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.extend_from_slice(b": ");
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+
+    let mut frame = BytesMut::with_capacity(5 + block.len());
+    frame.put_u8(TRAILERS_FLAG);
+    frame.put_u32(block.len() as u32);
+    frame.extend_from_slice(&block);
+    frame.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn content_types() {
+        let grpc_web = GrpcWeb::from_headers(&headers("application/grpc-web+proto")).unwrap();
+        assert!(!grpc_web.text);
+        assert_eq!(
+            grpc_web.upstream_content_type().unwrap(),
+            "application/grpc+proto"
+        );
+        assert_eq!(
+            grpc_web
+                .response_content_type(&headers("application/grpc"))
+                .unwrap(),
+            "application/grpc-web"
+        );
+
+        let grpc_web = GrpcWeb::from_headers(&headers("application/grpc-web-text")).unwrap();
+        assert!(grpc_web.text);
+        assert_eq!(
+            grpc_web.upstream_content_type().unwrap(),
+            "application/grpc"
+        );
+        assert_eq!(
+            grpc_web
+                .response_content_type(&headers("application/grpc+proto"))
+                .unwrap(),
+            "application/grpc-web-text+proto"
+        );
+        assert_eq!(grpc_web.response_content_type(&headers("text/plain")), None);
+
+        assert_eq!(GrpcWeb::from_headers(&headers("application/grpc")), None);
+        assert_eq!(
+            GrpcWeb::from_headers(&headers("application/grpc-webfoo")),
+            None
+        );
+    }
+
+    #[test]
+    fn decode_base64_chunks() {
+        let mut decoder = Base64Decoder::default();
+        // "hello" and "world" encoded separately, split at random places
+        let mut data = decoder.decode(b"aGVs").unwrap().to_vec();
+        data.extend_from_slice(&decoder.decode(b"bG8=d2").unwrap());
+        data.extend_from_slice(&decoder.decode(b"9ybGQ=").unwrap());
+        assert_eq!(data, b"helloworld");
+        decoder.finish().unwrap();
+
+        let mut decoder = Base64Decoder::default();
+        decoder.decode(b"aGV").unwrap();
+        assert!(decoder.finish().is_err());
+        assert!(Base64Decoder::default().decode(b"a!==").is_err());
+    }
+
+    #[test]
+    fn encode_text() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+
+        let mut encoder = Encoder::new(true);
+        let mut data = encoder.encode(Bytes::from_static(b"\0\0")).to_vec();
+        data.extend_from_slice(&encoder.encode(Bytes::from_static(b"\0\0\x01a")));
+        data.extend_from_slice(&encoder.finish(Some(&trailers)));
+
+        let decoded = STANDARD.decode(data).unwrap();
+        assert_eq!(&decoded[..6], b"\0\0\0\0\x01a");
+        assert_eq!(&decoded[6..11], b"\x80\0\0\0\x10");
+        assert_eq!(&decoded[11..], b"grpc-status: 0\r\n");
+    }
+
+    #[test]
+    fn encode_binary() {
+        let mut encoder = Encoder::new(false);
+        assert_eq!(encoder.encode(Bytes::from_static(b"abc")), "abc");
+        assert_eq!(encoder.finish(None), "");
+        assert_eq!(
+            encoder.finish(Some(&HeaderMap::new())),
+            Bytes::from_static(b"\x80\0\0\0\0")
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::{future::BoxFuture, FutureExt};
+use http::uri::Scheme;
+
+use crate::{
+    web::{LocalAddr, RemoteAddr},
+    Addr, Endpoint, Request,
+};
+
+/// A [`hyper::service::Service`] serving the requests with an endpoint,
+/// returned by [`EndpointExt::into_hyper_service`](crate::EndpointExt::into_hyper_service).
+///
+/// It can be used to mount a poem application inside an existing hyper or
+/// axum server, or to call it from a serverless adapter with
+/// [`HyperService::handle`]. The errors returned by the endpoint are
+/// converted to responses, so the service never fails.
+///
+/// Since the service does not know the connection, the addresses and the
+/// scheme of the requests are set with [`HyperService::remote_addr`],
+/// [`HyperService::local_addr`] and [`HyperService::scheme`].
+///
+/// # Example
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use poem::{handler, EndpointExt};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let svc = index.into_hyper_service();
+///
+/// // a hyper handler function
+/// let handler = hyper::service::service_fn(move |req| {
+///     let svc = svc.clone();
+///     async move { Ok::<_, Infallible>(svc.handle(req).await) }
+/// });
+/// # });
+/// ```
+pub struct HyperService<E> {
+    ep: Arc<E>,
+    local_addr: LocalAddr,
+    remote_addr: RemoteAddr,
+    scheme: Scheme,
+}
+
+impl<E> Clone for HyperService<E> {
+    fn clone(&self) -> Self {
+        Self {
+            ep: self.ep.clone(),
+            local_addr: self.local_addr.clone(),
+            remote_addr: self.remote_addr.clone(),
+            scheme: self.scheme.clone(),
+        }
+    }
+}
+
+impl<E: Endpoint> HyperService<E> {
+    pub(crate) fn new(ep: E) -> Self {
+        Self {
+            ep: Arc::new(ep),
+            local_addr: LocalAddr(Default::default()),
+            remote_addr: RemoteAddr(Default::default()),
+            scheme: Scheme::HTTP,
+        }
+    }
+
+    /// Sets the local address of the requests.
+    #[must_use]
+    pub fn local_addr(self, addr: impl Into<Addr>) -> Self {
+        Self {
+            local_addr: LocalAddr(addr.into()),
+            ..self
+        }
+    }
+
+    /// Sets the remote address of the requests.
+    #[must_use]
+    pub fn remote_addr(self, addr: impl Into<Addr>) -> Self {
+        Self {
+            remote_addr: RemoteAddr(addr.into()),
+            ..self
+        }
+    }
+
+    /// Sets the scheme of the requests.
+    ///
+    /// Default is `http`.
+    #[must_use]
+    pub fn scheme(self, scheme: Scheme) -> Self {
+        Self { scheme, ..self }
+    }
+
+    /// Serves a request.
+    pub async fn handle(&self, req: hyper::Request<hyper::Body>) -> hyper::Response<hyper::Body> {
+        let req = Request::from((
+            req,
+            self.local_addr.clone(),
+            self.remote_addr.clone(),
+            self.scheme.clone(),
+        ));
+        self.ep.get_response(req).await.into()
+    }
+}
+
+impl<E: Endpoint + 'static> hyper::service::Service<hyper::Request<hyper::Body>>
+    for HyperService<E>
+{
+    type Response = hyper::Response<hyper::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: hyper::Request<hyper::Body>) -> Self::Future {
+        let svc = self.clone();
+        async move { Ok(svc.handle(req).await) }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use http::StatusCode;
+    use hyper::service::Service;
+
+    use super::*;
+    use crate::{error::NotFoundError, handler, EndpointExt};
+
+    #[handler(internal)]
+    fn index(req: &Request, remote_addr: &RemoteAddr) -> crate::Result<String> {
+        if req.uri().path() != "/" {
+            return Err(NotFoundError.into());
+        }
+        Ok(format!("{} {}", req.scheme(), remote_addr))
+    }
+
+    #[tokio::test]
+    async fn hyper_service() {
+        let addr: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        let mut svc = index
+            .into_hyper_service()
+            .remote_addr(addr)
+            .scheme(Scheme::HTTPS);
+
+        let resp = svc
+            .call(hyper::Request::new(hyper::Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, "https socket://10.0.0.1:1234");
+
+        let resp = svc
+            .handle(
+                hyper::Request::builder()
+                    .uri("/a")
+                    .body(hyper::Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,807 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytes::Bytes;
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        png::PngEncoder,
+        webp::{WebPEncoder, WebPQuality},
+    },
+    error::{EncodingError, ImageFormatHint},
+    imageops::FilterType,
+    ColorType, DynamicImage, ImageEncoder, ImageError, ImageResult,
+};
+use parking_lot::RwLock;
+use percent_encoding::{percent_decode_str, utf8_percent_encode};
+use ring::{digest, hmac};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ImageProxyError, InternalServerError},
+    http::{header, HeaderMap, Method, StatusCode},
+    single_flight::SingleFlight,
+    storage::{Storage, KEY_ENCODE_SET},
+    Body, Endpoint, Request, Response, Result,
+};
+
+const FILTER: FilterType = FilterType::Lanczos3;
+
+/// How an image is resized to the width and the height of the
+/// [`ImageOptions`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image-proxy")))]
+pub enum ImageFit {
+    /// Resizes the image to fit in the dimensions, preserving its aspect
+    /// ratio. The images smaller than the dimensions are not enlarged.
+    #[default]
+    Contain,
+    /// Resizes the image to fill the dimensions, preserving its aspect ratio,
+    /// and crops the sides which overflow.
+    Cover,
+    /// Resizes the image to the dimensions, without preserving its aspect
+    /// ratio.
+    Fill,
+}
+
+/// The output format of the [`ImageProxy`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image-proxy")))]
+pub enum ImageFormat {
+    /// JPEG
+    Jpeg,
+    /// PNG
+    Png,
+    /// WebP
+    WebP,
+    /// AVIF
+    Avif,
+}
+
+impl ImageFormat {
+    const ALL: [ImageFormat; 4] = [
+        ImageFormat::Jpeg,
+        ImageFormat::Png,
+        ImageFormat::WebP,
+        ImageFormat::Avif,
+    ];
+
+    /// Returns the content type of the format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Avif => "image/avif",
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Png => "png",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Avif => "avif",
+        }
+    }
+}
+
+/// The transformations of an image, sent as the query parameters of the
+/// requests to the [`ImageProxy`].
+///
+/// | Parameter | Description |
+/// |-----------|-------------|
+/// | `w` | The width in pixels. |
+/// | `h` | The height in pixels. |
+/// | `fit` | How the image is resized, `contain`, `cover` or `fill`. Default is `contain`. |
+/// | `format` | The output format, `jpeg`, `png`, `webp` or `avif`. Default is the best format accepted by the client. |
+/// | `q` | The quality of the lossy formats, from 1 to 100. |
+/// | `s` | The signature of the URL. |
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "image-proxy")))]
+pub struct ImageOptions {
+    // the fields are sorted by name, so that the query is canonical
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fit: Option<ImageFit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<ImageFormat>,
+    #[serde(rename = "h", skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    #[serde(rename = "q", skip_serializing_if = "Option::is_none")]
+    quality: Option<u8>,
+    #[serde(rename = "w", skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+}
+
+impl ImageOptions {
+    /// Create an `ImageOptions` returning the original dimensions.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the width in pixels.
+    #[must_use]
+    pub fn width(self, width: u32) -> Self {
+        Self {
+            width: Some(width),
+            ..self
+        }
+    }
+
+    /// Sets the height in pixels.
+    #[must_use]
+    pub fn height(self, height: u32) -> Self {
+        Self {
+            height: Some(height),
+            ..self
+        }
+    }
+
+    /// Sets how the image is resized.
+    #[must_use]
+    pub fn fit(self, fit: ImageFit) -> Self {
+        Self {
+            fit: Some(fit),
+            ..self
+        }
+    }
+
+    /// Sets the output format, instead of negotiating it with the client.
+    #[must_use]
+    pub fn format(self, format: ImageFormat) -> Self {
+        Self {
+            format: Some(format),
+            ..self
+        }
+    }
+
+    /// Sets the quality of the lossy formats, from 1 to 100.
+    #[must_use]
+    pub fn quality(self, quality: u8) -> Self {
+        Self {
+            quality: Some(quality),
+            ..self
+        }
+    }
+
+    /// Returns the URL of the image with the specified key, transformed with
+    /// these options by an [`ImageProxy`] nested at `base_url` and signed
+    /// with `secret`.
+    pub fn signed_url(&self, base_url: &str, key: &str, secret: impl AsRef<[u8]>) -> String {
+        let secret = hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref());
+        let query = self.query();
+        format!(
+            "{}/{}?{query}{}s={}",
+            base_url.trim_end_matches('/'),
+            utf8_percent_encode(key, KEY_ENCODE_SET),
+            if query.is_empty() { "" } else { "&" },
+            sign(&secret, key, &query)
+        )
+    }
+
+    fn query(&self) -> String {
+        serde_urlencoded::to_string(self).unwrap_or_default()
+    }
+}
+
+fn sign(secret: &hmac::Key, key: &str, query: &str) -> String {
+    let tag = hmac::sign(secret, format!("{key}?{query}").as_bytes());
+    URL_SAFE_NO_PAD.encode(tag.as_ref())
+}
+
+fn verify(secret: &hmac::Key, key: &str, query: &str, signature: &str) -> bool {
+    match URL_SAFE_NO_PAD.decode(signature) {
+        Ok(signature) => {
+            hmac::verify(secret, format!("{key}?{query}").as_bytes(), &signature).is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+#[derive(Deserialize)]
+struct Signature {
+    s: Option<String>,
+}
+
+struct Thumbnail {
+    format: ImageFormat,
+    data: Bytes,
+}
+
+/// An endpoint resizing and cropping the images of a [`Storage`] on demand,
+/// as specified by the [`ImageOptions`] in the query parameters.
+///
+/// When the format is not specified, the images are converted to AVIF or
+/// WebP if the `Accept` header of the client lists it, or else to PNG for
+/// the images with an alpha channel and to JPEG for the others.
+///
+/// The resized images are cached in memory, and on the disk with
+/// [`persist`](ImageProxy::persist). They are identified by the entity tag
+/// of the original image, so that they are resized again when it changes.
+/// The concurrent requests for an image which is not cached are coalesced,
+/// so that it is resized only once.
+///
+/// With [`signed`](ImageProxy::signed), which is strongly recommended, only
+/// the URLs returned by [`ImageOptions::signed_url`] with the same secret are
+/// served, so that the clients cannot make the server resize the images to
+/// arbitrary dimensions.
+///
+/// # Errors
+///
+/// - [`ImageProxyError`]
+/// - [`StorageError`](crate::error::StorageError)
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     endpoint::{ImageFit, ImageOptions, ImageProxy},
+///     storage::LocalStorage,
+///     Route,
+/// };
+///
+/// let app = Route::new().nest(
+///     "/images",
+///     ImageProxy::new(LocalStorage::new("uploads"))
+///         .signed(b"secret")
+///         .persist(std::env::temp_dir().join("image-cache")),
+/// );
+///
+/// let url = ImageOptions::new()
+///     .width(200)
+///     .height(200)
+///     .fit(ImageFit::Cover)
+///     .signed_url("/images", "avatars/1.png", b"secret");
+/// assert!(url.starts_with("/images/avatars/1.png?fit=cover&h=200&w=200&s="));
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "image-proxy")))]
+pub struct ImageProxy<S> {
+    storage: S,
+    secret: Option<hmac::Key>,
+    max_dimension: u32,
+    quality: u8,
+    avif: bool,
+    cache_control: String,
+    entries: RwLock<HashMap<String, Arc<Thumbnail>>>,
+    max_entries: usize,
+    dir: Option<PathBuf>,
+    flights: SingleFlight<Option<Arc<Thumbnail>>>,
+}
+
+impl<S: Storage> ImageProxy<S> {
+    /// Create an `ImageProxy` serving the images of `storage`.
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            secret: None,
+            max_dimension: 2048,
+            quality: 80,
+            avif: true,
+            cache_control: "public, max-age=86400".to_string(),
+            entries: Default::default(),
+            max_entries: 256,
+            dir: None,
+            flights: Default::default(),
+        }
+    }
+
+    /// Requires the URLs to be signed with `secret`.
+    #[must_use]
+    pub fn signed(self, secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: Some(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref())),
+            ..self
+        }
+    }
+
+    /// Sets the maximum width and height of the resized images.
+    ///
+    /// Default is `2048`.
+    #[must_use]
+    pub fn max_dimension(self, max_dimension: u32) -> Self {
+        Self {
+            max_dimension,
+            ..self
+        }
+    }
+
+    /// Sets the quality of the lossy formats, when it is not specified by the
+    /// options.
+    ///
+    /// Default is `80`.
+    #[must_use]
+    pub fn quality(self, quality: u8) -> Self {
+        Self { quality, ..self }
+    }
+
+    /// Sets whether the images are converted to AVIF for the clients
+    /// accepting it, which is slower to encode than WebP.
+    ///
+    /// Default is `true`.
+    #[must_use]
+    pub fn avif(self, avif: bool) -> Self {
+        Self { avif, ..self }
+    }
+
+    /// Sets the `Cache-Control` header of the responses.
+    ///
+    /// Default is `public, max-age=86400`.
+    #[must_use]
+    pub fn cache_control(self, cache_control: impl Into<String>) -> Self {
+        Self {
+            cache_control: cache_control.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of images cached in memory, the images are not
+    /// cached in memory anymore when it is reached.
+    ///
+    /// Default is `256`.
+    #[must_use]
+    pub fn max_entries(self, max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            ..self
+        }
+    }
+
+    /// Persists the resized images in the directory `dir`.
+    #[must_use]
+    pub fn persist(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    fn validate(&self, options: &ImageOptions) -> Result<(), ImageProxyError> {
+        let dimensions = [options.width, options.height];
+        if dimensions
+            .iter()
+            .flatten()
+            .any(|dimension| *dimension == 0 || *dimension > self.max_dimension)
+        {
+            return Err(ImageProxyError::InvalidOptions(format!(
+                "the dimensions must be between 1 and {}",
+                self.max_dimension
+            )));
+        }
+        if matches!(options.quality, Some(quality) if quality == 0 || quality > 100) {
+            return Err(ImageProxyError::InvalidOptions(
+                "the quality must be between 1 and 100".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn negotiate(&self, headers: &HeaderMap) -> Option<ImageFormat> {
+        if self.avif && accepts(headers, "image/avif") {
+            Some(ImageFormat::Avif)
+        } else if accepts(headers, "image/webp") {
+            Some(ImageFormat::WebP)
+        } else {
+            None
+        }
+    }
+
+    async fn load(&self, hash: &str) -> Option<Thumbnail> {
+        let mut data = Bytes::from(tokio::fs::read(self.dir.as_ref()?.join(hash)).await.ok()?);
+        if data.is_empty() {
+            return None;
+        }
+        let format = *ImageFormat::ALL.get(data.split_to(1)[0] as usize)?;
+        Some(Thumbnail { format, data })
+    }
+
+    async fn store(&self, hash: &str, thumbnail: &Thumbnail) -> std::io::Result<()> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        tokio::fs::create_dir_all(dir).await?;
+
+        // the images are stored as the index of the format followed by the
+        // data, written to a temporary file first so that a concurrent load
+        // never reads a partial image
+        let mut data = Vec::with_capacity(1 + thumbnail.data.len());
+        data.push(thumbnail.format as u8);
+        data.extend_from_slice(&thumbnail.data);
+        let tmp = dir.join(format!("{hash}.tmp"));
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, dir.join(hash)).await
+    }
+
+    async fn thumbnail(
+        &self,
+        key: &str,
+        hash: &str,
+        options: &ImageOptions,
+        format: Option<ImageFormat>,
+    ) -> Result<Arc<Thumbnail>> {
+        let thumbnail = self.entries.read().get(hash).cloned();
+        if let Some(thumbnail) = thumbnail {
+            return Ok(thumbnail);
+        }
+
+        // the concurrent requests wait for the first one, and resize the image
+        // themselves if it fails
+        let mut error = None;
+        let thumbnail = {
+            let error = &mut error;
+            self.flights
+                .run(hash, move || async move {
+                    match self.render(key, hash, options, format).await {
+                        Ok(thumbnail) => Some(thumbnail),
+                        Err(err) => {
+                            *error = Some(err);
+                            None
+                        }
+                    }
+                })
+                .await
+        };
+        match (thumbnail, error) {
+            (Some(thumbnail), _) => Ok(thumbnail),
+            (None, Some(err)) => Err(err),
+            (None, None) => self.render(key, hash, options, format).await,
+        }
+    }
+
+    async fn render(
+        &self,
+        key: &str,
+        hash: &str,
+        options: &ImageOptions,
+        format: Option<ImageFormat>,
+    ) -> Result<Arc<Thumbnail>> {
+        let thumbnail = self.entries.read().get(hash).cloned();
+        if let Some(thumbnail) = thumbnail {
+            return Ok(thumbnail);
+        }
+
+        let thumbnail = match self.load(hash).await {
+            Some(thumbnail) => Arc::new(thumbnail),
+            None => {
+                let data = self
+                    .storage
+                    .get(key)
+                    .await?
+                    .ok_or(ImageProxyError::NotFound)?;
+                let options = options.clone();
+                let quality = options.quality.unwrap_or(self.quality);
+                let thumbnail =
+                    tokio::task::spawn_blocking(move || process(&data, &options, format, quality))
+                        .await
+                        .map_err(InternalServerError)??;
+                if let Err(err) = self.store(hash, &thumbnail).await {
+                    tracing::warn!(error = %err, "failed to persist the image");
+                }
+                Arc::new(thumbnail)
+            }
+        };
+
+        let mut entries = self.entries.write();
+        if entries.len() < self.max_entries {
+            entries.insert(hash.to_string(), thumbnail.clone());
+        }
+        Ok(thumbnail)
+    }
+}
+
+/// Returns `true` if the `Accept` header lists the media type, without
+/// `q=0`.
+fn accepts(headers: &HeaderMap, media_type: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|item| {
+            let mut params = item.split(';').map(str::trim);
+            params
+                .next()
+                .map_or(false, |ty| ty.eq_ignore_ascii_case(media_type))
+                && params.all(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .map_or(true, |q| q > 0.0)
+                })
+        })
+}
+
+fn resize(image: DynamicImage, options: &ImageOptions) -> DynamicImage {
+    match (
+        options.width,
+        options.height,
+        options.fit.unwrap_or_default(),
+    ) {
+        (None, None, _) => image,
+        (Some(width), Some(height), ImageFit::Cover) => image.resize_to_fill(width, height, FILTER),
+        (Some(width), Some(height), ImageFit::Fill) => image.resize_exact(width, height, FILTER),
+        (width, height, _) => {
+            let width = width.unwrap_or(u32::MAX);
+            let height = height.unwrap_or(u32::MAX);
+            if width >= image.width() && height >= image.height() {
+                image
+            } else {
+                image.resize(width, height, FILTER)
+            }
+        }
+    }
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> ImageResult<Vec<u8>> {
+    let mut data = Vec::new();
+    let (width, height) = (image.width(), image.height());
+    let (pixels, color) = if image.color().has_alpha() && format != ImageFormat::Jpeg {
+        (image.to_rgba8().into_raw(), ColorType::Rgba8)
+    } else {
+        (image.to_rgb8().into_raw(), ColorType::Rgb8)
+    };
+
+    match format {
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut data, quality)
+            .write_image(&pixels, width, height, color)?,
+        ImageFormat::Png => {
+            PngEncoder::new(&mut data).write_image(&pixels, width, height, color)?
+        }
+        // the lossy encoding is deprecated by `image`, but the lossless one
+        // makes the thumbnails larger than the originals
+        #[allow(deprecated)]
+        ImageFormat::WebP => WebPEncoder::new_with_quality(&mut data, WebPQuality::lossy(quality))
+            .write_image(&pixels, width, height, color)?,
+        ImageFormat::Avif => data = encode_avif(&pixels, width, height, color, quality)?,
+    }
+    Ok(data)
+}
+
+/// Encodes an AVIF image with `ravif` without the assembly of `rav1e`, which
+/// the AVIF encoder of `image` enables and which requires `nasm` to build.
+fn encode_avif(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color: ColorType,
+    quality: u8,
+) -> ImageResult<Vec<u8>> {
+    let encoder = ravif::Encoder::new()
+        .with_quality(f32::from(quality.clamp(1, 100)))
+        .with_speed(8);
+    let (width, height) = (width as usize, height as usize);
+    let encoded = if color == ColorType::Rgba8 {
+        let pixels = pixels
+            .chunks_exact(4)
+            .map(|px| ravif::RGBA8::new(px[0], px[1], px[2], px[3]))
+            .collect::<Vec<_>>();
+        encoder.encode_rgba(ravif::Img::new(&pixels[..], width, height))
+    } else {
+        let pixels = pixels
+            .chunks_exact(3)
+            .map(|px| ravif::RGB8::new(px[0], px[1], px[2]))
+            .collect::<Vec<_>>();
+        encoder.encode_rgb(ravif::Img::new(&pixels[..], width, height))
+    }
+    .map_err(|err| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(image::ImageFormat::Avif),
+            err,
+        ))
+    })?;
+    Ok(encoded.avif_file)
+}
+
+fn process(
+    data: &[u8],
+    options: &ImageOptions,
+    format: Option<ImageFormat>,
+    quality: u8,
+) -> Result<Thumbnail, ImageProxyError> {
+    let image =
+        image::load_from_memory(data).map_err(|err| ImageProxyError::Decode(err.to_string()))?;
+    let image = resize(image, options);
+    let format = format.unwrap_or(if image.color().has_alpha() {
+        ImageFormat::Png
+    } else {
+        ImageFormat::Jpeg
+    });
+    let data =
+        encode(&image, format, quality).map_err(|err| ImageProxyError::Encode(err.to_string()))?;
+    Ok(Thumbnail {
+        format,
+        data: data.into(),
+    })
+}
+
+#[async_trait::async_trait]
+impl<S: Storage> Endpoint for ImageProxy<S> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Err(ImageProxyError::MethodNotAllowed.into());
+        }
+
+        let key = percent_decode_str(req.uri().path().trim_start_matches('/'))
+            .decode_utf8()
+            .map_err(|_| ImageProxyError::NotFound)?
+            .into_owned();
+        let query = req.uri().query().unwrap_or_default();
+        let options = serde_urlencoded::from_str::<ImageOptions>(query)
+            .map_err(|err| ImageProxyError::InvalidOptions(err.to_string()))?;
+        if let Some(secret) = &self.secret {
+            let signature = serde_urlencoded::from_str::<Signature>(query)
+                .ok()
+                .and_then(|signature| signature.s);
+            match signature {
+                Some(signature) if verify(secret, &key, &options.query(), &signature) => {}
+                _ => return Err(ImageProxyError::InvalidSignature.into()),
+            }
+        }
+        self.validate(&options)?;
+
+        let meta = self
+            .storage
+            .head(&key)
+            .await?
+            .ok_or(ImageProxyError::NotFound)?;
+        let format = options.format.or_else(|| self.negotiate(req.headers()));
+        let version = meta
+            .etag
+            .clone()
+            .unwrap_or_else(|| format!("{}:{:?}", meta.size, meta.last_modified));
+        let cache_key = format!(
+            "{key}\n{version}\n{}\n{}",
+            options.query(),
+            format.map_or("auto", |format| format.as_str())
+        );
+        let hash =
+            hex::encode(&digest::digest(&digest::SHA256, cache_key.as_bytes()).as_ref()[..16]);
+        let etag = format!("\"{hash}\"");
+
+        let mut resp = Response::builder()
+            .header(header::ETAG, &etag)
+            .header(header::CACHE_CONTROL, &self.cache_control);
+        if options.format.is_none() {
+            resp = resp.header(header::VARY, "accept");
+        }
+
+        let not_modified = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+            });
+        if not_modified {
+            return Ok(resp.status(StatusCode::NOT_MODIFIED).finish());
+        }
+
+        let thumbnail = self.thumbnail(&key, &hash, &options, format).await?;
+        Ok(resp
+            .header(header::CONTENT_TYPE, thumbnail.format.content_type())
+            .body(Body::from_bytes(thumbnail.data.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{storage::LocalStorage, test::TestClient, Route};
+
+    async fn storage(name: &str) -> (PathBuf, LocalStorage) {
+        let dir = std::env::temp_dir().join(format!("poem-{name}-{}", std::process::id()));
+        let storage = LocalStorage::new(dir.join("images"));
+        let mut data = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(100, 50)
+            .write_to(&mut data, image::ImageOutputFormat::Png)
+            .unwrap();
+        storage
+            .put("a.png", Body::from(data.into_inner()), None)
+            .await
+            .unwrap();
+        (dir, storage)
+    }
+
+    #[tokio::test]
+    async fn resize() {
+        let (dir, storage) = storage("image-proxy").await;
+        let cli = TestClient::new(Route::new().nest(
+            "/images",
+            ImageProxy::new(storage).persist(dir.join("cache")),
+        ));
+
+        let resp = cli.get("/images/a.png?w=50").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("image/jpeg");
+        resp.assert_header(header::VARY, "accept");
+        let etag = resp.0.header(header::ETAG).unwrap().to_string();
+        let image = image::load_from_memory(&resp.0.into_body().into_vec().await.unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (50, 25));
+
+        cli.get("/images/a.png?w=50")
+            .header(header::IF_NONE_MATCH, &etag)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+
+        let resp = cli
+            .get("/images/a.png?w=40&h=40&fit=cover")
+            .header(header::ACCEPT, "image/webp,image/*;q=0.8")
+            .send()
+            .await;
+        resp.assert_content_type("image/webp");
+        let image = image::load_from_memory(&resp.0.into_body().into_vec().await.unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (40, 40));
+
+        let resp = cli.get("/images/a.png?format=png").send().await;
+        resp.assert_content_type("image/png");
+        assert!(resp.0.header(header::VARY).is_none());
+
+        cli.get("/images/a.png?w=5000")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.get("/images/b.png")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        assert_eq!(std::fs::read_dir(dir.join("cache")).unwrap().count(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn signed() {
+        let (dir, storage) = storage("image-proxy-signed").await;
+        let cli = TestClient::new(
+            Route::new().nest("/images", ImageProxy::new(storage).signed(b"secret")),
+        );
+
+        let url = ImageOptions::new()
+            .width(20)
+            .signed_url("/images", "a.png", b"secret");
+        cli.get(&url).send().await.assert_status_is_ok();
+        cli.get(url.replace("w=20", "w=30"))
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        cli.get("/images/a.png?w=20")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn avif() {
+        for image in [
+            DynamicImage::new_rgb8(16, 8),
+            DynamicImage::new_rgba8(16, 8),
+        ] {
+            let data = encode(&image, ImageFormat::Avif, 80).unwrap();
+            assert_eq!(&data[4..12], b"ftypavif");
+        }
+    }
+
+    #[test]
+    fn accept() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            "image/avif;q=0, image/webp".parse().unwrap(),
+        );
+        assert!(accepts(&headers, "image/webp"));
+        assert!(!accepts(&headers, "image/avif"));
+        assert!(!accepts(&headers, "image/png"));
+    }
+}
@@ -1,5 +1,7 @@
 //! Endpoint related types.
 
+#[cfg(feature = "admin-dashboard")]
+mod admin_dashboard;
 mod after;
 mod and_then;
 mod around;
@@ -10,18 +12,38 @@ mod catch_error;
 mod embed;
 #[allow(clippy::module_inception)]
 mod endpoint;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "proxy")]
+mod grpc_web;
+mod hyper_service;
+#[cfg(feature = "image-proxy")]
+mod image_proxy;
 mod inspect_all_err;
 mod inspect_err;
 mod map;
 mod map_to_response;
 #[cfg(feature = "prometheus")]
 mod prometheus_exporter;
+#[cfg(feature = "proxy")]
+pub(crate) mod proxy;
+mod robots_txt;
+mod security_txt;
+mod site_icons;
 #[cfg(feature = "static-files")]
 mod static_files;
+#[cfg(feature = "storage")]
+mod storage_files;
 mod to_response;
 #[cfg(feature = "tower-compat")]
-mod tower_compat;
+pub(crate) mod tower_compat;
+#[cfg(feature = "tus")]
+mod tus;
+#[cfg(feature = "proxy")]
+mod upstream_pool;
 
+#[cfg(feature = "admin-dashboard")]
+pub use admin_dashboard::AdminDashboard;
 pub use after::After;
 pub use and_then::AndThen;
 pub use around::Around;
@@ -31,14 +53,32 @@ pub use catch_error::CatchError;
 #[cfg(feature = "embed")]
 pub use embed::{EmbeddedFileEndpoint, EmbeddedFilesEndpoint};
 pub use endpoint::{make, make_sync, BoxEndpoint, Endpoint, EndpointExt, IntoEndpoint};
+#[cfg(feature = "grpc")]
+pub use grpc::Grpc;
+pub use hyper_service::HyperService;
+#[cfg(feature = "image-proxy")]
+pub use image_proxy::{ImageFit, ImageFormat, ImageOptions, ImageProxy};
 pub use inspect_all_err::InspectAllError;
 pub use inspect_err::InspectError;
 pub use map::Map;
 pub use map_to_response::MapToResponse;
 #[cfg(feature = "prometheus")]
 pub use prometheus_exporter::PrometheusExporter;
+#[cfg(feature = "proxy")]
+pub use proxy::Proxy;
+pub use robots_txt::RobotsTxt;
+pub use security_txt::SecurityTxt;
+pub use site_icons::SiteIcons;
 #[cfg(feature = "static-files")]
 pub use static_files::{StaticFileEndpoint, StaticFilesEndpoint};
+#[cfg(feature = "storage")]
+pub use storage_files::StorageFilesEndpoint;
 pub use to_response::ToResponse;
 #[cfg(feature = "tower-compat")]
-pub use tower_compat::TowerCompatExt;
+pub use tower_compat::{EndpointService, TowerCompatExt};
+#[cfg(all(feature = "tus", feature = "storage"))]
+pub use tus::StorageTusStore;
+#[cfg(feature = "tus")]
+pub use tus::{FileTusStore, MemoryTusStore, TusEndpoint, TusStore, TusUpload};
+#[cfg(feature = "proxy")]
+pub use upstream_pool::{LoadBalance, UpstreamPool};
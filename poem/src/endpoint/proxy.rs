@@ -0,0 +1,797 @@
+use http::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    StatusCode, Uri,
+};
+use hyper::{body::HttpBody, client::HttpConnector};
+
+use crate::{
+    endpoint::{
+        grpc_web::{Base64Decoder, Encoder, GrpcWeb},
+        UpstreamPool,
+    },
+    error::ProxyError,
+    web::{deadline::REQUEST_TIMEOUT, Deadline},
+    Endpoint, Request, Response, Result,
+};
+
+/// The headers that only apply to a single connection and must not be
+/// forwarded.
+///
+/// Reference: <https://www.rfc-editor.org/rfc/rfc9110#section-7.6.1>
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+type RewritePath = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+/// An endpoint that forwards the requests to an upstream server, so that poem
+/// can be used as a reverse proxy or an API gateway.
+///
+/// The request and response bodies are streamed without being buffered. The
+/// hop-by-hop headers are removed, the address of the client is appended to
+/// the `X-Forwarded-For` header, and the `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` headers sent by the client are replaced with the values
+/// of the request. WebSocket and other upgraded connections are passed
+/// through to the upstream, and the trailers of the upstream responses are
+/// forwarded after their bodies.
+///
+/// With [`Proxy::grpc_web`], the gRPC-web requests of the browsers are
+/// translated to native gRPC, so that the proxy can front gRPC servers such
+/// as [`tonic`](https://crates.io/crates/tonic) services.
+///
+/// The path of the request is appended to the path of the upstream uri. When
+/// the endpoint is nested in a [`Route`](crate::Route), the nesting prefix has
+/// already been removed from it, and [`Proxy::rewrite_path`] can be used to
+/// rewrite it further.
+///
+/// The upstream requests are cancelled when the [`Deadline`] of the request
+/// expires, and the remaining time is forwarded in the `X-Request-Timeout`
+/// header.
+///
+/// # Errors
+///
+/// - [`ProxyError`]
+/// - [`DeadlineExceededError`](crate::error::DeadlineExceededError)
+///
+/// # Example
+///
+/// ```
+/// use poem::{endpoint::Proxy, Route};
+///
+/// // `/api/users?page=2` is forwarded to `http://127.0.0.1:8080/v1/users?page=2`
+/// let app = Route::new().nest("/api", Proxy::new("http://127.0.0.1:8080/v1"));
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy")))]
+pub struct Proxy {
+    client: hyper::Client<HttpConnector>,
+    pool: UpstreamPool,
+    preserve_host: bool,
+    rewrite_path: Option<RewritePath>,
+    grpc_web: bool,
+}
+
+impl Proxy {
+    /// Create a `Proxy` that forwards the requests to the specified upstream
+    /// uri.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `upstream` is not a valid `http` uri.
+    pub fn new(upstream: impl AsRef<str>) -> Self {
+        Self::with_pool(UpstreamPool::new().upstream(upstream))
+    }
+
+    /// Create a `Proxy` that balances the requests between the upstreams of
+    /// the specified pool.
+    pub fn with_pool(pool: UpstreamPool) -> Self {
+        Self {
+            client: hyper::Client::new(),
+            pool,
+            preserve_host: false,
+            rewrite_path: None,
+            grpc_web: false,
+        }
+    }
+
+    /// Forward the `Host` header of the requests instead of replacing it with
+    /// the authority of the upstream uri.
+    #[must_use]
+    pub fn preserve_host(self, preserve_host: bool) -> Self {
+        Self {
+            preserve_host,
+            ..self
+        }
+    }
+
+    /// Connect to the upstreams with HTTP/2 without negotiation, as required
+    /// by gRPC servers and to receive the trailers of the responses, which
+    /// are not supported over HTTP/1.1.
+    #[must_use]
+    pub fn http2_only(self, http2_only: bool) -> Self {
+        Self {
+            client: hyper::Client::builder().http2_only(http2_only).build_http(),
+            ..self
+        }
+    }
+
+    /// Rewrite the path of the requests before it is appended to the path of
+    /// the upstream uri.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::endpoint::Proxy;
+    ///
+    /// // `/users` is forwarded to `http://127.0.0.1:8080/users.json`
+    /// let ep = Proxy::new("http://127.0.0.1:8080").rewrite_path(|path| format!("{path}.json"));
+    /// ```
+    #[must_use]
+    pub fn rewrite_path<F>(self, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Self {
+            rewrite_path: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    /// Translate the gRPC-web requests, with a `application/grpc-web` or
+    /// `application/grpc-web-text` content type, to native gRPC requests,
+    /// and their responses back to gRPC-web, with the trailers encoded at the
+    /// end of the response bodies.
+    ///
+    /// The upstreams must be gRPC servers, which requires
+    /// [`Proxy::http2_only`]. The other requests are forwarded unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{endpoint::Proxy, middleware::Cors, EndpointExt, Route};
+    ///
+    /// let app = Route::new().nest(
+    ///     "/grpc",
+    ///     Proxy::new("http://127.0.0.1:50051")
+    ///         .http2_only(true)
+    ///         .grpc_web(true)
+    ///         .with(Cors::new().expose_headers(["grpc-status", "grpc-message"])),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn grpc_web(self, grpc_web: bool) -> Self {
+        Self { grpc_web, ..self }
+    }
+
+    fn path_and_query(&self, uri: &Uri) -> String {
+        let mut path_and_query = match &self.rewrite_path {
+            Some(rewrite_path) => rewrite_path(uri.path()),
+            None => uri.path().to_string(),
+        };
+        if let Some(query) = uri.query() {
+            path_and_query.push('?');
+            path_and_query.push_str(query);
+        }
+        path_and_query
+    }
+}
+
+/// Decodes the body of a `grpc-web-text` request.
+fn decode_base64_body(mut body: hyper::Body) -> hyper::Body {
+    let (mut sender, decoded) = hyper::Body::channel();
+    tokio::spawn(async move {
+        let mut decoder = Base64Decoder::default();
+        while let Some(data) = body.data().await {
+            match data.map(|data| decoder.decode(&data)) {
+                Ok(Ok(data)) => {
+                    if sender.send_data(data).await.is_err() {
+                        return;
+                    }
+                }
+                _ => {
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+        if decoder.finish().is_err() {
+            sender.abort();
+        }
+    });
+    decoded
+}
+
+fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::UPGRADE)
+        && headers
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+}
+
+pub(crate) fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let names = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+        .collect::<Vec<_>>();
+    for name in names.into_iter().chain(HOP_BY_HOP_HEADERS) {
+        headers.remove(name);
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for Proxy {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let path_and_query = self.path_and_query(req.uri());
+        let on_upgrade = if is_upgrade(req.headers()) {
+            req.take_upgrade().ok()
+        } else {
+            None
+        };
+        let client_ip = req
+            .remote_addr()
+            .as_socket_addr()
+            .map(|addr| addr.ip().to_string());
+        let proto = req.scheme().to_string();
+        let host = req.headers().get(header::HOST).cloned().or_else(|| {
+            req.original_uri()
+                .authority()
+                .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+        });
+
+        let deadline = Deadline::of(&req).cloned();
+
+        let (parts, body) = req.into_parts();
+        let mut headers = parts.headers;
+        let grpc_web = match self.grpc_web {
+            true => GrpcWeb::from_headers(&headers),
+            false => None,
+        };
+        let upgrade = headers.get(header::UPGRADE).cloned();
+        let accept_trailers = headers
+            .get_all(header::TE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("trailers"));
+        remove_hop_by_hop_headers(&mut headers);
+        headers.remove(header::HOST);
+
+        if accept_trailers || grpc_web.is_some() {
+            headers.insert(header::TE, HeaderValue::from_static("trailers"));
+        }
+        if let Some(grpc_web) = &grpc_web {
+            if let Some(content_type) = grpc_web.upstream_content_type() {
+                headers.insert(header::CONTENT_TYPE, content_type);
+            }
+            if grpc_web.text {
+                headers.remove(header::CONTENT_LENGTH);
+            }
+        }
+        if let (Some(upgrade), true) = (upgrade, on_upgrade.is_some()) {
+            headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+            headers.insert(header::UPGRADE, upgrade);
+        }
+        if let Some(host) = host {
+            if self.preserve_host {
+                headers.insert(header::HOST, host.clone());
+            }
+            headers.insert(HeaderName::from_static("x-forwarded-host"), host);
+        } else {
+            headers.remove("x-forwarded-host");
+        }
+        match HeaderValue::from_str(&proto) {
+            Ok(proto) => {
+                headers.insert(HeaderName::from_static("x-forwarded-proto"), proto);
+            }
+            Err(_) => {
+                headers.remove("x-forwarded-proto");
+            }
+        }
+        if let Some(client_ip) = client_ip {
+            let forwarded_for = headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .chain(std::iter::once(client_ip.as_str()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+                headers.insert(HeaderName::from_static("x-forwarded-for"), value);
+            }
+        }
+
+        let mut body = Some(match &grpc_web {
+            Some(grpc_web) if grpc_web.text => decode_base64_body(body.into()),
+            _ => hyper::Body::from(body),
+        });
+        let retryable = body.as_ref().map_or(false, HttpBody::is_end_stream);
+        let mut tried = Vec::new();
+        let mut last_err = None;
+
+        let (idx, mut resp, active) = loop {
+            let idx = match self.pool.select(&tried) {
+                Some(idx) => idx,
+                None => {
+                    return Err(last_err
+                        .map_or(ProxyError::NoAvailableUpstream, ProxyError::Upstream)
+                        .into())
+                }
+            };
+            let upstream = self.pool.get(idx);
+            let uri: Uri = format!(
+                "{}://{}{}{}",
+                upstream.scheme, upstream.authority, upstream.base_path, path_and_query
+            )
+            .parse()
+            .map_err(ProxyError::InvalidUri)?;
+
+            let mut upstream_req = hyper::Request::new(body.take().unwrap_or_default());
+            *upstream_req.method_mut() = parts.method.clone();
+            *upstream_req.uri_mut() = uri;
+            *upstream_req.headers_mut() = headers.clone();
+            if let Some(deadline) = &deadline {
+                let remaining = deadline.remaining().as_millis().max(1);
+                upstream_req.headers_mut().insert(
+                    REQUEST_TIMEOUT,
+                    HeaderValue::from(u64::try_from(remaining).unwrap_or(u64::MAX)),
+                );
+            }
+
+            let active = self.pool.begin(idx);
+            let res = match &deadline {
+                Some(deadline) => match deadline.run(self.client.request(upstream_req)).await {
+                    Ok(res) => res,
+                    Err(err) => {
+                        self.pool.record_failure(idx);
+                        return Err(err.into());
+                    }
+                },
+                None => self.client.request(upstream_req).await,
+            };
+            match res {
+                Ok(resp) => break (idx, resp, active),
+                Err(err) => {
+                    self.pool.record_failure(idx);
+                    tried.push(idx);
+                    if !(err.is_connect() && retryable && tried.len() <= self.pool.max_retries) {
+                        return Err(ProxyError::Upstream(err).into());
+                    }
+                    last_err = Some(err);
+                }
+            }
+        };
+
+        if matches!(
+            resp.status(),
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        ) {
+            self.pool.record_failure(idx);
+        } else {
+            self.pool.record_success(idx);
+        }
+
+        match on_upgrade {
+            Some(on_upgrade) if resp.status() == StatusCode::SWITCHING_PROTOCOLS => {
+                let upstream_upgrade = hyper::upgrade::on(&mut resp);
+                tokio::spawn(async move {
+                    if let (Ok(mut client), Ok(mut upstream)) =
+                        tokio::join!(on_upgrade, upstream_upgrade)
+                    {
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                    }
+                });
+            }
+            _ => remove_hop_by_hop_headers(resp.headers_mut()),
+        }
+
+        let mut encoder = grpc_web.and_then(|grpc_web| {
+            let content_type = grpc_web.response_content_type(resp.headers())?;
+            resp.headers_mut()
+                .insert(header::CONTENT_TYPE, content_type);
+            resp.headers_mut().remove(header::CONTENT_LENGTH);
+            Some(Encoder::new(grpc_web.text))
+        });
+
+        // the request is in progress until the response body has been sent
+        Ok(resp
+            .map(|mut body| {
+                let (mut sender, forwarded) = hyper::Body::channel();
+                tokio::spawn(async move {
+                    let _active = active;
+                    while let Some(data) = body.data().await {
+                        match data {
+                            Ok(data) => {
+                                let data = match &mut encoder {
+                                    Some(encoder) => encoder.encode(data),
+                                    None => data,
+                                };
+                                if sender.send_data(data).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Err(_) => {
+                                sender.abort();
+                                return;
+                            }
+                        }
+                    }
+                    match (body.trailers().await, &mut encoder) {
+                        // the gRPC-web trailers are sent in the body
+                        (Ok(trailers), Some(encoder)) => {
+                            let _ = sender.send_data(encoder.finish(trailers.as_ref())).await;
+                        }
+                        (Ok(Some(trailers)), None) => {
+                            let _ = sender.send_trailers(trailers).await;
+                        }
+                        (Ok(None), None) => {}
+                        (Err(_), _) => sender.abort(),
+                    }
+                });
+                forwarded
+            })
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    use super::*;
+    use crate::{
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        test::TestClient,
+        Body, Route, Server,
+    };
+
+    async fn serve<E>(ep: E) -> SocketAddr
+    where
+        E: Endpoint + 'static,
+    {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(ep));
+        addr
+    }
+
+    #[tokio::test]
+    async fn forward() {
+        use crate::{web::RemoteAddr, Addr, EndpointExt};
+
+        #[handler(internal)]
+        fn upstream(req: &Request, body: String) -> Response {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            Response::builder()
+                .header(header::CONNECTION, "x-hop")
+                .header("x-hop", "1")
+                .body(format!(
+                    "{} {} host={} for={} proto={} fwd-host={} {}",
+                    req.method(),
+                    req.uri(),
+                    header("host"),
+                    header("x-forwarded-for"),
+                    header("x-forwarded-proto"),
+                    header("x-forwarded-host"),
+                    body
+                ))
+        }
+
+        let addr = serve(upstream).await;
+        let app = Route::new().nest(
+            "/api",
+            Proxy::new(format!("http://{addr}/v1/")).rewrite_path(|path| format!("{path}.json")),
+        );
+        let cli = TestClient::new(app.before(|mut req| async move {
+            req.state_mut().remote_addr =
+                RemoteAddr(Addr::SocketAddr("10.0.0.3:1234".parse().unwrap()));
+            Ok(req)
+        }));
+
+        let resp = cli
+            .post("/api/users?page=2")
+            .header(header::HOST, "example.com")
+            .header("x-forwarded-for", "10.0.0.1")
+            .header("x-forwarded-for", "10.0.0.2")
+            .header("x-forwarded-host", "evil.com")
+            .header("x-forwarded-proto", "https")
+            .header("keep-alive", "timeout=5")
+            .body("hello")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_header_is_not_exist("x-hop");
+        resp.assert_text(format!(
+            "POST /v1/users.json?page=2 host={addr} for=10.0.0.1, 10.0.0.2, 10.0.0.3 proto=http fwd-host=example.com \
+             hello"
+        ))
+        .await;
+    }
+
+    #[tokio::test]
+    async fn deadline() {
+        use std::time::Duration;
+
+        use crate::{middleware::RequestDeadline, EndpointExt};
+
+        #[handler(internal)]
+        async fn upstream(req: &Request) -> String {
+            if req.uri().path() == "/slow" {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            req.header("x-request-timeout").unwrap().to_string()
+        }
+
+        let addr = serve(upstream).await;
+        let cli = TestClient::new(
+            Proxy::new(format!("http://{addr}"))
+                .with(RequestDeadline::new(Duration::from_secs(10))),
+        );
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        let remaining: u64 = resp
+            .0
+            .into_body()
+            .into_string()
+            .await
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(remaining > 9000 && remaining <= 10000);
+
+        cli.get("/slow")
+            .header("x-request-timeout", "50")
+            .send()
+            .await
+            .assert_status(StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn trailers() {
+        #[handler(internal)]
+        fn upstream(req: &Request) -> Response {
+            let te = req.headers().get(header::TE).cloned();
+            Response::builder()
+                .header(header::TRAILER, "grpc-status")
+                .body(Body::from("hello").with_trailers(async move {
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                    if let Some(te) = te {
+                        trailers.insert("x-te", te);
+                    }
+                    Some(trailers)
+                }))
+        }
+
+        let addr = serve(upstream).await;
+        let cli = TestClient::new(Proxy::new(format!("http://{addr}")).http2_only(true));
+        let resp = cli.get("/").header(header::TE, "trailers").send().await;
+        resp.assert_status_is_ok();
+        let (data, trailers) = resp.0.into_body().into_bytes_with_trailers().await.unwrap();
+        assert_eq!(data, "hello");
+        let trailers = trailers.unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+        assert_eq!(trailers["x-te"], "trailers");
+    }
+
+    #[tokio::test]
+    async fn grpc_web() {
+        #[handler(internal)]
+        fn upstream(req: &Request, body: Vec<u8>) -> Response {
+            let content_type = req.headers()[header::CONTENT_TYPE].clone();
+            let te = req.headers()[header::TE].clone();
+            Response::builder()
+                .content_type("application/grpc+proto")
+                .header(header::TRAILER, "grpc-status")
+                .body(Body::from(body).with_trailers(async move {
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                    trailers.insert("x-content-type", content_type);
+                    trailers.insert("x-te", te);
+                    Some(trailers)
+                }))
+        }
+
+        let addr = serve(upstream).await;
+        let cli = TestClient::new(
+            Proxy::new(format!("http://{addr}"))
+                .http2_only(true)
+                .grpc_web(true),
+        );
+        let message = b"\0\0\0\0\x02hi";
+        let trailers =
+            b"grpc-status: 0\r\nx-content-type: application/grpc+proto\r\nx-te: trailers\r\n";
+        let mut expected = message.to_vec();
+        expected.push(0x80);
+        expected.extend_from_slice(&(trailers.len() as u32).to_be_bytes());
+        expected.extend_from_slice(trailers);
+
+        let resp = cli
+            .post("/")
+            .content_type("application/grpc-web+proto")
+            .body(&message[..])
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("application/grpc-web+proto");
+        let (data, trailers) = resp.0.into_body().into_bytes_with_trailers().await.unwrap();
+        assert_eq!(data, expected);
+        assert!(trailers.is_none());
+
+        let resp = cli
+            .post("/")
+            .content_type("application/grpc-web-text+proto")
+            .body(STANDARD.encode(message))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("application/grpc-web-text+proto");
+        let data = resp.0.into_body().into_string().await.unwrap();
+        assert_eq!(STANDARD.decode(data).unwrap(), expected);
+
+        // other requests are forwarded unchanged
+        let resp = cli
+            .post("/")
+            .content_type("application/grpc")
+            .header(header::TE, "trailers")
+            .body(&message[..])
+            .send()
+            .await;
+        resp.assert_content_type("application/grpc+proto");
+        let (data, trailers) = resp.0.into_body().into_bytes_with_trailers().await.unwrap();
+        assert_eq!(data, &message[..]);
+        assert_eq!(trailers.unwrap()["grpc-status"], "0");
+    }
+
+    #[tokio::test]
+    async fn preserve_host() {
+        #[handler(internal)]
+        fn upstream(req: &Request) -> String {
+            format!("{:?}", req.headers().get(header::HOST))
+        }
+
+        let addr = serve(upstream).await;
+        let cli = TestClient::new(Proxy::new(format!("http://{addr}")).preserve_host(true));
+        cli.get("/")
+            .header(header::HOST, "example.com")
+            .send()
+            .await
+            .assert_text("Some(\"example.com\")")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn bad_gateway() {
+        let addr = {
+            let acceptor = TcpListener::bind("127.0.0.1:0")
+                .into_acceptor()
+                .await
+                .unwrap();
+            *acceptor.local_addr()[0].as_socket_addr().unwrap()
+        };
+        let cli = TestClient::new(Proxy::new(format!("http://{addr}")));
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn retry_on_connect_failure() {
+        #[handler(internal)]
+        fn upstream() -> &'static str {
+            "hello"
+        }
+
+        let dead_addr = {
+            let acceptor = TcpListener::bind("127.0.0.1:0")
+                .into_acceptor()
+                .await
+                .unwrap();
+            *acceptor.local_addr()[0].as_socket_addr().unwrap()
+        };
+        let addr = serve(upstream).await;
+        let pool = || {
+            UpstreamPool::new()
+                .upstream(format!("http://{dead_addr}"))
+                .upstream(format!("http://{addr}"))
+        };
+
+        // the first request selects the dead upstream and is retried
+        let cli = TestClient::new(Proxy::with_pool(pool()));
+        for _ in 0..3 {
+            cli.get("/").send().await.assert_text("hello").await;
+        }
+
+        // requests with a body are not retried
+        let cli = TestClient::new(Proxy::with_pool(pool()));
+        cli.post("/")
+            .body("data")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+
+        let cli = TestClient::new(Proxy::with_pool(pool().max_retries(0)));
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+
+        let cli = TestClient::new(Proxy::with_pool(
+            UpstreamPool::new()
+                .upstream(format!("http://{dead_addr}"))
+                .failure_threshold(1),
+        ));
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[cfg(feature = "websocket")]
+    #[tokio::test]
+    async fn websocket() {
+        use futures_util::{SinkExt, StreamExt};
+
+        use crate::{
+            web::websocket::{Message, WebSocket},
+            IntoResponse,
+        };
+
+        #[handler(internal)]
+        fn upstream(ws: WebSocket) -> impl IntoResponse {
+            ws.on_upgrade(|mut socket| async move {
+                while let Some(Ok(Message::Text(text))) = socket.next().await {
+                    let _ = socket.send(Message::Text(text.to_uppercase())).await;
+                }
+            })
+        }
+
+        let upstream_addr = serve(upstream).await;
+        let addr = serve(Proxy::new(format!("http://{upstream_addr}"))).await;
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}"))
+            .await
+            .unwrap();
+        client
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                "hello".to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            tokio_tungstenite::tungstenite::Message::Text("HELLO".to_string())
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use crate::{
+    http::{Method, StatusCode},
+    Endpoint, Request, Response, Result,
+};
+
+#[derive(Debug, Clone, Serialize)]
+struct Rule {
+    allow: bool,
+    path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<u32>,
+}
+
+/// An endpoint that serves a `robots.txt` file.
+///
+/// The rules added before any [`user_agent`](RobotsTxt::user_agent) apply to
+/// all the crawlers.
+///
+/// It implements [`Display`] to render the file, and [`Serialize`] so that it
+/// can also be passed to a template.
+///
+/// # Example
+///
+/// ```
+/// use poem::{endpoint::RobotsTxt, test::TestClient, Route};
+///
+/// let robots = RobotsTxt::new()
+///     .allow("/")
+///     .disallow("/admin")
+///     .user_agent("BadBot")
+///     .disallow("/")
+///     .sitemap("https://example.com/sitemap.xml");
+/// let app = Route::new().at("/robots.txt", robots);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(app).get("/robots.txt").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text(
+///     "User-agent: *\nAllow: /\nDisallow: /admin\n\nUser-agent: BadBot\nDisallow: /\n\nSitemap: https://example.com/sitemap.xml\n",
+/// )
+/// .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+    sitemaps: Vec<String>,
+}
+
+impl RobotsTxt {
+    /// Create an empty `RobotsTxt` endpoint.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts the rules for the crawler named `user_agent`.
+    ///
+    /// Consecutive calls add the crawlers to the same group of rules.
+    #[must_use]
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        match self.groups.last_mut() {
+            Some(group) if group.rules.is_empty() && group.crawl_delay.is_none() => {
+                group.user_agents.push(user_agent.into())
+            }
+            _ => self.groups.push(Group {
+                user_agents: vec![user_agent.into()],
+                rules: Vec::new(),
+                crawl_delay: None,
+            }),
+        }
+        self
+    }
+
+    /// Allows the crawlers to visit `path`.
+    #[must_use]
+    pub fn allow(self, path: impl Into<String>) -> Self {
+        self.rule(true, path.into())
+    }
+
+    /// Disallows the crawlers to visit `path`.
+    #[must_use]
+    pub fn disallow(self, path: impl Into<String>) -> Self {
+        self.rule(false, path.into())
+    }
+
+    /// Sets the number of seconds the crawlers should wait between two
+    /// requests.
+    #[must_use]
+    pub fn crawl_delay(mut self, seconds: u32) -> Self {
+        self.current_group().crawl_delay = Some(seconds);
+        self
+    }
+
+    /// Adds the URL of a sitemap.
+    #[must_use]
+    pub fn sitemap(mut self, url: impl Into<String>) -> Self {
+        self.sitemaps.push(url.into());
+        self
+    }
+
+    fn rule(mut self, allow: bool, path: String) -> Self {
+        self.current_group().rules.push(Rule { allow, path });
+        self
+    }
+
+    fn current_group(&mut self) -> &mut Group {
+        if self.groups.is_empty() {
+            self.groups.push(Group {
+                user_agents: vec!["*".to_string()],
+                rules: Vec::new(),
+                crawl_delay: None,
+            });
+        }
+        self.groups.last_mut().unwrap()
+    }
+}
+
+impl Display for RobotsTxt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (idx, group) in self.groups.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            for user_agent in &group.user_agents {
+                writeln!(f, "User-agent: {user_agent}")?;
+            }
+            for rule in &group.rules {
+                let directive = if rule.allow { "Allow" } else { "Disallow" };
+                writeln!(f, "{directive}: {}", rule.path)?;
+            }
+            if let Some(crawl_delay) = group.crawl_delay {
+                writeln!(f, "Crawl-delay: {crawl_delay}")?;
+            }
+        }
+
+        if !self.sitemaps.is_empty() {
+            if !self.groups.is_empty() {
+                writeln!(f)?;
+            }
+            for sitemap in &self.sitemaps {
+                writeln!(f, "Sitemap: {sitemap}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for RobotsTxt {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+
+        Ok(Response::builder()
+            .content_type("text/plain; charset=utf-8")
+            .body(self.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TestClient;
+
+    #[test]
+    fn render() {
+        assert_eq!(RobotsTxt::new().to_string(), "");
+        assert_eq!(
+            RobotsTxt::new()
+                .user_agent("a")
+                .user_agent("b")
+                .disallow("/private")
+                .crawl_delay(5)
+                .user_agent("*")
+                .allow("/")
+                .to_string(),
+            "User-agent: a\nUser-agent: b\nDisallow: /private\nCrawl-delay: 5\n\nUser-agent: *\nAllow: /\n"
+        );
+        assert_eq!(
+            RobotsTxt::new().sitemap("/sitemap.xml").to_string(),
+            "Sitemap: /sitemap.xml\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint() {
+        let cli = TestClient::new(RobotsTxt::new().disallow("/"));
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("text/plain; charset=utf-8");
+        resp.assert_text("User-agent: *\nDisallow: /\n").await;
+
+        cli.post("/")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    time::SystemTime,
+};
+
+use serde::Serialize;
+
+use crate::{
+    http::{Method, StatusCode},
+    middleware::recorder::har::format_iso8601,
+    Endpoint, Request, Response, Result,
+};
+
+/// An endpoint that serves a `security.txt` file, as defined by
+/// [RFC 9116](https://www.rfc-editor.org/rfc/rfc9116).
+///
+/// It should be mounted at `/.well-known/security.txt`. The RFC requires at
+/// least one [`contact`](SecurityTxt::contact) and an
+/// [`expires`](SecurityTxt::expires) field.
+///
+/// It implements [`Display`] to render the file, and [`Serialize`] so that it
+/// can also be passed to a template.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// use poem::{endpoint::SecurityTxt, test::TestClient, Route};
+///
+/// let security = SecurityTxt::new()
+///     .contact("mailto:security@example.com")
+///     .expires(UNIX_EPOCH + Duration::from_secs(1_893_456_000))
+///     .preferred_language("en")
+///     .preferred_language("fr");
+/// let app = Route::new().at("/.well-known/security.txt", security);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(app)
+///     .get("/.well-known/security.txt")
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text(
+///     "Contact: mailto:security@example.com\nExpires: 2030-01-01T00:00:00.000Z\nPreferred-Languages: en, fr\n",
+/// )
+/// .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SecurityTxt {
+    contacts: Vec<String>,
+    expires: Option<String>,
+    encryption: Vec<String>,
+    acknowledgments: Vec<String>,
+    preferred_languages: Vec<String>,
+    canonical: Vec<String>,
+    policy: Vec<String>,
+    hiring: Vec<String>,
+}
+
+impl SecurityTxt {
+    /// Create an empty `SecurityTxt` endpoint.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a URI to report the vulnerabilities to, such as
+    /// `mailto:security@example.com`.
+    #[must_use]
+    pub fn contact(mut self, uri: impl Into<String>) -> Self {
+        self.contacts.push(uri.into());
+        self
+    }
+
+    /// Sets the date after which the file should be considered stale.
+    #[must_use]
+    pub fn expires(self, time: SystemTime) -> Self {
+        Self {
+            expires: Some(format_iso8601(time)),
+            ..self
+        }
+    }
+
+    /// Adds a URI of a key to use for the encrypted communications.
+    #[must_use]
+    pub fn encryption(mut self, uri: impl Into<String>) -> Self {
+        self.encryption.push(uri.into());
+        self
+    }
+
+    /// Adds a URI of a page recognizing the security researchers.
+    #[must_use]
+    pub fn acknowledgments(mut self, uri: impl Into<String>) -> Self {
+        self.acknowledgments.push(uri.into());
+        self
+    }
+
+    /// Adds a language in which the reports are preferred, such as `en`.
+    #[must_use]
+    pub fn preferred_language(mut self, language: impl Into<String>) -> Self {
+        self.preferred_languages.push(language.into());
+        self
+    }
+
+    /// Adds a URI where this file is located.
+    #[must_use]
+    pub fn canonical(mut self, uri: impl Into<String>) -> Self {
+        self.canonical.push(uri.into());
+        self
+    }
+
+    /// Adds a URI of the vulnerability disclosure policy.
+    #[must_use]
+    pub fn policy(mut self, uri: impl Into<String>) -> Self {
+        self.policy.push(uri.into());
+        self
+    }
+
+    /// Adds a URI of the security-related job positions.
+    #[must_use]
+    pub fn hiring(mut self, uri: impl Into<String>) -> Self {
+        self.hiring.push(uri.into());
+        self
+    }
+}
+
+impl Display for SecurityTxt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for contact in &self.contacts {
+            writeln!(f, "Contact: {contact}")?;
+        }
+        if let Some(expires) = &self.expires {
+            writeln!(f, "Expires: {expires}")?;
+        }
+        for encryption in &self.encryption {
+            writeln!(f, "Encryption: {encryption}")?;
+        }
+        for acknowledgments in &self.acknowledgments {
+            writeln!(f, "Acknowledgments: {acknowledgments}")?;
+        }
+        if !self.preferred_languages.is_empty() {
+            writeln!(
+                f,
+                "Preferred-Languages: {}",
+                self.preferred_languages.join(", ")
+            )?;
+        }
+        for canonical in &self.canonical {
+            writeln!(f, "Canonical: {canonical}")?;
+        }
+        for policy in &self.policy {
+            writeln!(f, "Policy: {policy}")?;
+        }
+        for hiring in &self.hiring {
+            writeln!(f, "Hiring: {hiring}")?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for SecurityTxt {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+
+        Ok(Response::builder()
+            .content_type("text/plain; charset=utf-8")
+            .body(self.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    #[test]
+    fn render() {
+        let security = SecurityTxt::new()
+            .contact("mailto:security@example.com")
+            .contact("https://example.com/report")
+            .expires(UNIX_EPOCH)
+            .encryption("https://example.com/pgp.asc")
+            .acknowledgments("https://example.com/thanks")
+            .preferred_language("en")
+            .canonical("https://example.com/.well-known/security.txt")
+            .policy("https://example.com/policy")
+            .hiring("https://example.com/jobs");
+        assert_eq!(
+            security.to_string(),
+            "Contact: mailto:security@example.com\n\
+             Contact: https://example.com/report\n\
+             Expires: 1970-01-01T00:00:00.000Z\n\
+             Encryption: https://example.com/pgp.asc\n\
+             Acknowledgments: https://example.com/thanks\n\
+             Preferred-Languages: en\n\
+             Canonical: https://example.com/.well-known/security.txt\n\
+             Policy: https://example.com/policy\n\
+             Hiring: https://example.com/jobs\n"
+        );
+
+        let value = serde_json::to_value(&security).unwrap();
+        assert_eq!(value["contacts"][1], "https://example.com/report");
+        assert_eq!(value["expires"], "1970-01-01T00:00:00.000Z");
+    }
+}
@@ -0,0 +1,393 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use serde_json::{json, Value};
+
+use crate::{
+    http::{header, Method, StatusCode},
+    middleware::render_error::html_escape,
+    Endpoint, Request, Response, Result,
+};
+
+#[derive(Debug, Clone)]
+struct Icon {
+    path: String,
+    content_type: &'static str,
+    sizes: String,
+    data: Bytes,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Config {
+    prefix: String,
+    favicon: Option<Bytes>,
+    apple_touch_icon: Option<Bytes>,
+    icons: Vec<Icon>,
+    name: Option<String>,
+    short_name: Option<String>,
+    start_url: Option<String>,
+    display: Option<String>,
+    theme_color: Option<String>,
+    background_color: Option<String>,
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+    {
+        Some(ext) if ext == "png" => "image/png",
+        Some(ext) if ext == "svg" => "image/svg+xml",
+        Some(ext) if ext == "ico" => "image/x-icon",
+        Some(ext) if ext == "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// An endpoint that serves the icons of a site and a generated
+/// `site.webmanifest`.
+///
+/// It serves `favicon.ico`, `apple-touch-icon.png`, the icons added with
+/// [`icon`](SiteIcons::icon), and `site.webmanifest`, relative to the path it
+/// is mounted at, which must be set with [`prefix`](SiteIcons::prefix) when it
+/// is not the root. The icons are cached by the clients for
+/// [`max_age`](SiteIcons::max_age), and the manifest for a day.
+///
+/// The `<link>` tags referencing these files are returned by
+/// [`link_tags`](SiteIcons::link_tags), or emitted in the templates by the
+/// [`site_icons`](crate::tera::functions::site_icons) Tera function.
+///
+/// # Example
+///
+/// ```
+/// use poem::{endpoint::SiteIcons, test::TestClient, Route};
+///
+/// let icons = SiteIcons::new()
+///     .favicon(&b"ico"[..])
+///     .icon("icon-192.png", "192x192", &b"png"[..])
+///     .name("Poem")
+///     .theme_color("#ffffff");
+/// let app = Route::new().nest("/", icons);
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/favicon.ico").send().await;
+/// resp.assert_content_type("image/x-icon");
+/// resp.assert_text("ico").await;
+///
+/// let resp = cli.get("/site.webmanifest").send().await;
+/// resp.assert_content_type("application/manifest+json");
+/// resp.assert_json(serde_json::json!({
+///     "name": "Poem",
+///     "theme_color": "#ffffff",
+///     "icons": [{ "src": "/icon-192.png", "sizes": "192x192", "type": "image/png" }],
+/// }))
+/// .await;
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct SiteIcons {
+    config: Arc<Config>,
+    max_age: Duration,
+}
+
+impl Default for SiteIcons {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SiteIcons {
+    /// Create a `SiteIcons` endpoint without any icon.
+    pub fn new() -> Self {
+        Self {
+            config: Default::default(),
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+
+    fn update(mut self, f: impl FnOnce(&mut Config)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    /// Sets the path this endpoint is mounted at, used by the URLs of the
+    /// manifest and the `<link>` tags.
+    ///
+    /// Default is `/`.
+    #[must_use]
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.update(|config| config.prefix = prefix.trim_end_matches('/').to_string())
+    }
+
+    /// Sets the content of `favicon.ico`.
+    #[must_use]
+    pub fn favicon(self, data: impl Into<Bytes>) -> Self {
+        let data = data.into();
+        self.update(|config| config.favicon = Some(data))
+    }
+
+    /// Sets the content of `apple-touch-icon.png`.
+    #[must_use]
+    pub fn apple_touch_icon(self, data: impl Into<Bytes>) -> Self {
+        let data = data.into();
+        self.update(|config| config.apple_touch_icon = Some(data))
+    }
+
+    /// Adds an icon served at `path`, whose dimensions are `sizes`, such as
+    /// `192x192`, or `any` for the vector images.
+    ///
+    /// The icons are listed in the manifest.
+    #[must_use]
+    pub fn icon(
+        self,
+        path: impl Into<String>,
+        sizes: impl Into<String>,
+        data: impl Into<Bytes>,
+    ) -> Self {
+        let path = path.into().trim_start_matches('/').to_string();
+        let icon = Icon {
+            content_type: content_type(&path),
+            path,
+            sizes: sizes.into(),
+            data: data.into(),
+        };
+        self.update(|config| config.icons.push(icon))
+    }
+
+    /// Sets the name of the application in the manifest.
+    #[must_use]
+    pub fn name(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.update(|config| config.name = Some(name))
+    }
+
+    /// Sets the short name of the application in the manifest.
+    #[must_use]
+    pub fn short_name(self, short_name: impl Into<String>) -> Self {
+        let short_name = short_name.into();
+        self.update(|config| config.short_name = Some(short_name))
+    }
+
+    /// Sets the URL opened when the application is launched.
+    #[must_use]
+    pub fn start_url(self, start_url: impl Into<String>) -> Self {
+        let start_url = start_url.into();
+        self.update(|config| config.start_url = Some(start_url))
+    }
+
+    /// Sets the display mode of the application, such as `standalone`.
+    #[must_use]
+    pub fn display(self, display: impl Into<String>) -> Self {
+        let display = display.into();
+        self.update(|config| config.display = Some(display))
+    }
+
+    /// Sets the theme color, also emitted as a `<meta name="theme-color">`
+    /// tag.
+    #[must_use]
+    pub fn theme_color(self, color: impl Into<String>) -> Self {
+        let color = color.into();
+        self.update(|config| config.theme_color = Some(color))
+    }
+
+    /// Sets the background color of the splash screen.
+    #[must_use]
+    pub fn background_color(self, color: impl Into<String>) -> Self {
+        let color = color.into();
+        self.update(|config| config.background_color = Some(color))
+    }
+
+    /// Sets how long the clients cache the icons.
+    ///
+    /// Default is `7 days`.
+    #[must_use]
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self { max_age, ..self }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.config.prefix, path)
+    }
+
+    /// Returns the manifest.
+    pub fn manifest(&self) -> Value {
+        let config = &self.config;
+        let mut manifest = serde_json::Map::new();
+        for (key, value) in [
+            ("name", &config.name),
+            ("short_name", &config.short_name),
+            ("start_url", &config.start_url),
+            ("display", &config.display),
+            ("theme_color", &config.theme_color),
+            ("background_color", &config.background_color),
+        ] {
+            if let Some(value) = value {
+                manifest.insert(key.to_string(), Value::String(value.clone()));
+            }
+        }
+        manifest.insert(
+            "icons".to_string(),
+            config
+                .icons
+                .iter()
+                .map(|icon| {
+                    json!({
+                        "src": self.url(&icon.path),
+                        "sizes": icon.sizes,
+                        "type": icon.content_type,
+                    })
+                })
+                .collect(),
+        );
+        Value::Object(manifest)
+    }
+
+    /// Returns the `<link>` tags referencing the icons and the manifest, to
+    /// insert in the `<head>` of the pages.
+    pub fn link_tags(&self) -> String {
+        let config = &self.config;
+        let mut tags = Vec::new();
+        if config.favicon.is_some() {
+            tags.push(format!(
+                r#"<link rel="icon" href="{}" sizes="any">"#,
+                html_escape(&self.url("favicon.ico"))
+            ));
+        }
+        for icon in &config.icons {
+            tags.push(format!(
+                r#"<link rel="icon" type="{}" sizes="{}" href="{}">"#,
+                icon.content_type,
+                html_escape(&icon.sizes),
+                html_escape(&self.url(&icon.path))
+            ));
+        }
+        if config.apple_touch_icon.is_some() {
+            tags.push(format!(
+                r#"<link rel="apple-touch-icon" href="{}">"#,
+                html_escape(&self.url("apple-touch-icon.png"))
+            ));
+        }
+        tags.push(format!(
+            r#"<link rel="manifest" href="{}">"#,
+            html_escape(&self.url("site.webmanifest"))
+        ));
+        if let Some(color) = &config.theme_color {
+            tags.push(format!(
+                r#"<meta name="theme-color" content="{}">"#,
+                html_escape(color)
+            ));
+        }
+        tags.join("\n")
+    }
+
+    fn file(&self, path: &str) -> Option<(&'static str, Bytes)> {
+        let config = &self.config;
+        match path {
+            "favicon.ico" => config.favicon.clone().map(|data| ("image/x-icon", data)),
+            "apple-touch-icon.png" => config
+                .apple_touch_icon
+                .clone()
+                .map(|data| ("image/png", data)),
+            _ => config
+                .icons
+                .iter()
+                .find(|icon| icon.path == path)
+                .map(|icon| (icon.content_type, icon.data.clone())),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for SiteIcons {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+
+        let path = req.uri().path().trim_start_matches('/');
+        if path == "site.webmanifest" {
+            return Ok(Response::builder()
+                .content_type("application/manifest+json")
+                .header(header::CACHE_CONTROL, "public, max-age=86400")
+                .body(self.manifest().to_string()));
+        }
+
+        match self.file(path) {
+            Some((content_type, data)) => Ok(Response::builder()
+                .content_type(content_type)
+                .header(
+                    header::CACHE_CONTROL,
+                    format!("public, max-age={}", self.max_age.as_secs()),
+                )
+                .body(data)),
+            None => Ok(StatusCode::NOT_FOUND.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test::TestClient, Route};
+
+    fn icons() -> SiteIcons {
+        SiteIcons::new()
+            .prefix("/icons/")
+            .favicon(&b"ico"[..])
+            .apple_touch_icon(&b"apple"[..])
+            .icon("/icon.svg", "any", &b"svg"[..])
+            .theme_color("#000")
+            .max_age(Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let cli = TestClient::new(Route::new().nest("/icons", icons()));
+
+        let resp = cli.get("/icons/icon.svg").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("image/svg+xml");
+        resp.assert_header(header::CACHE_CONTROL, "public, max-age=60");
+        resp.assert_text("svg").await;
+
+        let resp = cli.get("/icons/apple-touch-icon.png").send().await;
+        resp.assert_content_type("image/png");
+        resp.assert_text("apple").await;
+
+        let resp = cli.get("/icons/site.webmanifest").send().await;
+        resp.assert_header(header::CACHE_CONTROL, "public, max-age=86400");
+        resp.assert_json(json!({
+            "theme_color": "#000",
+            "icons": [{ "src": "/icons/icon.svg", "sizes": "any", "type": "image/svg+xml" }],
+        }))
+        .await;
+
+        cli.get("/icons/missing.png")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        cli.post("/icons/favicon.ico")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn link_tags() {
+        assert_eq!(
+            icons().link_tags(),
+            [
+                r#"<link rel="icon" href="/icons/favicon.ico" sizes="any">"#,
+                r#"<link rel="icon" type="image/svg+xml" sizes="any" href="/icons/icon.svg">"#,
+                r#"<link rel="apple-touch-icon" href="/icons/apple-touch-icon.png">"#,
+                r#"<link rel="manifest" href="/icons/site.webmanifest">"#,
+                r##"<meta name="theme-color" content="#000">"##,
+            ]
+            .join("\n")
+        );
+    }
+}
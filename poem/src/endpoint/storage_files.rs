@@ -0,0 +1,237 @@
+use std::time::UNIX_EPOCH;
+
+use percent_encoding::percent_decode_str;
+use ring::hmac;
+
+use crate::{
+    error::{StaticFileError, StorageError},
+    http::{Method, StatusCode},
+    storage::{verify_url, Storage},
+    web::{Clock, StaticFileRequest},
+    Endpoint, FromRequest, IntoResponse, Request, Response, Result,
+};
+
+/// An endpoint serving the objects of a [`Storage`], with the support of
+/// the range requests and the conditional requests.
+///
+/// The key of an object is the path of the request, relative to the path the
+/// endpoint is nested at.
+///
+/// With [`signed`](StorageFilesEndpoint::signed), the requests must be sent
+/// to the URLs presigned by a [`LocalStorage`](crate::storage::LocalStorage)
+/// with the same secret, and the objects can be uploaded with the `PUT`
+/// requests to the URLs presigned for this method.
+///
+/// # Errors
+///
+/// - [`StaticFileError`]
+/// - [`StorageError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{endpoint::StorageFilesEndpoint, storage::LocalStorage, Route};
+///
+/// let app = Route::new().nest(
+///     "/files",
+///     StorageFilesEndpoint::new(LocalStorage::new("uploads")),
+/// );
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "storage")))]
+pub struct StorageFilesEndpoint<S> {
+    storage: S,
+    secret: Option<hmac::Key>,
+}
+
+impl<S: Storage> StorageFilesEndpoint<S> {
+    /// Create a `StorageFilesEndpoint` serving the objects of `storage`.
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            secret: None,
+        }
+    }
+
+    /// Requires the requests to be sent to the URLs presigned with `secret`.
+    #[must_use]
+    pub fn signed(self, secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: Some(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref())),
+            ..self
+        }
+    }
+
+    fn verify(&self, req: &Request, secret: &hmac::Key, key: &str) -> Result<()> {
+        let query = serde_urlencoded::from_str::<Vec<(String, String)>>(
+            req.uri().query().unwrap_or_default(),
+        )
+        .unwrap_or_default();
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(param, _)| param == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let now = Clock::of(req)
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // the URLs presigned for `GET` also accept `HEAD`
+        let method = match *req.method() {
+            Method::HEAD => Method::GET,
+            ref method => method.clone(),
+        };
+        let expires = param("expires").and_then(|expires| expires.parse::<u64>().ok());
+        match (expires, param("signature")) {
+            (Some(expires), Some(signature))
+                if expires > now && verify_url(secret, &method, key, expires, signature) =>
+            {
+                Ok(())
+            }
+            _ => Err(StorageError::InvalidSignature.into()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Storage> Endpoint for StorageFilesEndpoint<S> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let key = percent_decode_str(req.uri().path().trim_start_matches('/'))
+            .decode_utf8()
+            .map_err(|_| StaticFileError::InvalidPath)?
+            .into_owned();
+        if key.is_empty() {
+            return Err(StaticFileError::NotFound.into());
+        }
+        if let Some(secret) = &self.secret {
+            self.verify(&req, secret, &key)?;
+        }
+
+        match *req.method() {
+            Method::GET | Method::HEAD => Ok(StaticFileRequest::from_request_without_body(&req)
+                .await?
+                .create_response_from_storage(&self.storage, &key)
+                .await?
+                .into_response()),
+            Method::PUT if self.secret.is_some() => {
+                let content_type = req.content_type().map(ToString::to_string);
+                self.storage
+                    .put(&key, req.take_body(), content_type.as_deref())
+                    .await?;
+                Ok(StatusCode::CREATED.into_response())
+            }
+            _ => Err(StaticFileError::MethodNotAllowed(req.method().clone()).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{http::header, storage::LocalStorage, test::TestClient, Body, Route};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("poem-{name}-{}", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let dir = temp_dir("storage-files");
+        let storage = LocalStorage::new(&dir);
+        storage
+            .put(
+                "docs/a.txt",
+                Body::from_string("0123456789".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+        let cli = TestClient::new(Route::new().nest("/files", StorageFilesEndpoint::new(storage)));
+
+        let resp = cli.get("/files/docs/a.txt").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("text/plain");
+        resp.assert_header(header::ACCEPT_RANGES, "bytes");
+        let etag = resp.0.header(header::ETAG).unwrap().to_string();
+        resp.assert_text("0123456789").await;
+
+        cli.get("/files/docs/a.txt")
+            .header(header::IF_NONE_MATCH, &etag)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+
+        let resp = cli
+            .get("/files/docs/a.txt")
+            .header(header::RANGE, "bytes=2-4")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::PARTIAL_CONTENT);
+        resp.assert_header(header::CONTENT_RANGE, "bytes 2-4/10");
+        resp.assert_text("234").await;
+
+        cli.get("/files/docs/a.txt")
+            .header(header::RANGE, "bytes=20-30")
+            .send()
+            .await
+            .assert_status(StatusCode::RANGE_NOT_SATISFIABLE);
+        cli.get("/files/docs/b.txt")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        cli.get("/files/docs/%2e%2e/a.txt")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.put("/files/docs/a.txt")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn signed() {
+        let dir = temp_dir("storage-files-signed");
+        let storage = LocalStorage::new(&dir).presign("/files", b"secret");
+        let cli = TestClient::new(Route::new().nest(
+            "/files",
+            StorageFilesEndpoint::new(storage.clone()).signed(b"secret"),
+        ));
+
+        let put_url = storage
+            .presigned_url("a.txt", Method::PUT, Duration::from_secs(60))
+            .await
+            .unwrap();
+        let get_url = storage
+            .presigned_url("a.txt", Method::GET, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        cli.put(&get_url)
+            .body("hello")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        cli.put(&put_url)
+            .body("hello")
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED);
+
+        cli.get("/files/a.txt")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        cli.get(&get_url).send().await.assert_text("hello").await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
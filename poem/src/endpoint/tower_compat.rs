@@ -1,10 +1,22 @@
-use std::{error::Error as StdError, future::Future};
+use std::{
+    convert::Infallible,
+    error::Error as StdError,
+    future::Future,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 use bytes::Bytes;
+use futures_util::{future::BoxFuture, FutureExt};
+use http::uri::Scheme;
 use hyper::body::{HttpBody, Sender};
-use tower::{Service, ServiceExt};
+use tower::{BoxError, Service, ServiceExt};
 
-use crate::{Endpoint, Error, Request, Response, Result};
+use crate::{
+    request::RequestState,
+    web::{LocalAddr, RemoteAddr},
+    Endpoint, Error, Request, Response, Result,
+};
 
 /// Extension trait for tower service compat.
 #[cfg_attr(docsrs, doc(cfg(feature = "tower-compat")))]
@@ -30,6 +42,39 @@ pub trait TowerCompatExt {
     {
         TowerCompatEndpoint(self)
     }
+
+    /// Converts a poem endpoint to a tower service, which can be served by
+    /// hyper or wrapped by the tower middleware.
+    ///
+    /// The errors returned by the endpoint are converted to responses, so
+    /// the service never fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use poem::{endpoint::TowerCompatExt, handler};
+    /// use tower::ServiceExt;
+    ///
+    /// #[handler]
+    /// fn index() -> &'static str {
+    ///     "hello"
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let svc = index.into_tower_service();
+    /// let resp = svc
+    ///     .oneshot(http::Request::new(hyper::Body::empty()))
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(resp.status(), http::StatusCode::OK);
+    /// # });
+    /// ```
+    fn into_tower_service(self) -> EndpointService<Self>
+    where
+        Self: Endpoint + Sized + 'static,
+    {
+        EndpointService::new(self)
+    }
 }
 
 impl<T> TowerCompatExt for T {}
@@ -69,17 +114,84 @@ where
             .await
             .map_err(Into::into)?;
 
-        if !hyper_resp.body().is_end_stream() {
-            Ok(hyper_resp
-                .map(|body| {
-                    let (sender, new_body) = hyper::Body::channel();
-                    tokio::spawn(copy_body(body, sender));
-                    new_body
-                })
-                .into())
-        } else {
-            Ok(hyper_resp.map(|_| hyper::Body::empty()).into())
+        Ok(to_poem_response(hyper_resp))
+    }
+}
+
+/// Converts the response of a tower service, copying its body in a task
+/// unless it is empty.
+pub(crate) fn to_poem_response<B>(resp: http::Response<B>) -> Response
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Into<Bytes> + Send + 'static,
+    B::Error: StdError + Send + Sync + 'static,
+{
+    if !resp.body().is_end_stream() {
+        resp.map(|body| {
+            let (sender, new_body) = hyper::Body::channel();
+            tokio::spawn(copy_body(body, sender));
+            new_body
+        })
+        .into()
+    } else {
+        resp.map(|_| hyper::Body::empty()).into()
+    }
+}
+
+/// A poem endpoint to tower service adapter, returned by
+/// [`TowerCompatExt::into_tower_service`].
+#[cfg_attr(docsrs, doc(cfg(feature = "tower-compat")))]
+pub struct EndpointService<E>(Arc<E>);
+
+impl<E> EndpointService<E> {
+    pub(crate) fn new(ep: E) -> Self {
+        Self(Arc::new(ep))
+    }
+}
+
+impl<E> Clone for EndpointService<E> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<E, B> Service<http::Request<B>> for EndpointService<E>
+where
+    E: Endpoint + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Response = hyper::Response<hyper::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        let ep = self.0.clone();
+        let scheme = req.uri().scheme().cloned().unwrap_or(Scheme::HTTP);
+        // the state is kept by `TowerHttpCompatMiddleware`
+        let state = req.extensions_mut().remove::<RequestState>();
+        let req = req.map(|body| {
+            let data = futures_util::stream::unfold(Box::pin(body), |mut body| async move {
+                let data = body.data().await?;
+                Some((data.map(Into::into), body))
+            });
+            hyper::Body::wrap_stream(data)
+        });
+        let mut req = Request::from((
+            req,
+            LocalAddr(Default::default()),
+            RemoteAddr(Default::default()),
+            scheme,
+        ));
+        if let Some(state) = state {
+            *req.state_mut() = state;
         }
+        async move { Ok(ep.get_response(req).await.into()) }.boxed()
     }
 }
 
@@ -120,15 +232,11 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::{
-        convert::Infallible,
-        task::{Context, Poll},
-    };
-
     use futures_util::future::Ready;
+    use http::StatusCode;
 
     use super::*;
-    use crate::test::TestClient;
+    use crate::{error::NotFoundError, handler, test::TestClient};
 
     #[tokio::test]
     async fn test_tower_compat() {
@@ -154,4 +262,44 @@ mod tests {
         resp.assert_status_is_ok();
         resp.assert_text("abc").await;
     }
+
+    #[tokio::test]
+    async fn test_into_tower_service() {
+        #[handler(internal)]
+        fn index(req: &Request, body: String) -> Result<String> {
+            if body.is_empty() {
+                return Err(NotFoundError.into());
+            }
+            Ok(format!("{} {}", req.uri().path(), body))
+        }
+
+        let svc = index.into_tower_service();
+        let resp = svc
+            .clone()
+            .oneshot(
+                http::Request::builder()
+                    .uri("/a")
+                    .body(hyper::Body::from("abc"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, "/a abc");
+
+        let resp = svc
+            .oneshot(http::Request::new(hyper::Body::empty()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // round trip
+        let resp = TestClient::new(index.into_tower_service().compat())
+            .get("/b")
+            .body("abc")
+            .send()
+            .await;
+        resp.assert_text("/b abc").await;
+    }
 }
@@ -0,0 +1,1050 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    io::{Error as IoError, ErrorKind, SeekFrom},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::BytesMut;
+#[cfg(feature = "storage")]
+use futures_util::TryStreamExt;
+use futures_util::{future::BoxFuture, FutureExt, StreamExt};
+use parking_lot::Mutex;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+#[cfg(feature = "storage")]
+use crate::storage::Storage;
+use crate::{
+    error::{InternalServerError, ReadBodyError, TusError},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    web::Clock,
+    Body, Endpoint, IntoResponse, Request, Response, Result,
+};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,expiration,termination";
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// An upload of the [`TusEndpoint`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+pub struct TusUpload {
+    /// The id of the upload.
+    pub id: String,
+    /// The total size of the upload in bytes.
+    pub length: u64,
+    /// The number of bytes received.
+    pub offset: u64,
+    /// The metadata sent by the client with the `Upload-Metadata` header,
+    /// such as the name of the file.
+    pub metadata: BTreeMap<String, String>,
+    /// The time after which the upload can no longer be resumed.
+    pub expires: SystemTime,
+}
+
+impl TusUpload {
+    /// Returns `true` if all the bytes of the upload are received.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.offset >= self.length
+    }
+}
+
+/// Represents a back-end storage for the [`TusEndpoint`].
+///
+/// The endpoint never appends to the same upload concurrently.
+#[async_trait::async_trait]
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+pub trait TusStore: Send + Sync {
+    /// Creates an empty upload.
+    async fn create(&self, upload: &TusUpload) -> Result<()>;
+
+    /// Returns the upload with the specified id, or `None` if it does not
+    /// exist.
+    async fn get(&self, id: &str) -> Result<Option<TusUpload>>;
+
+    /// Appends the data of `body` to the upload, and returns its new offset.
+    ///
+    /// The body returns an error if the client sends more data than the
+    /// length of the upload. The data received before an error should be
+    /// kept if possible, so that the client can resume the upload from
+    /// there.
+    async fn append(&self, id: &str, body: Body) -> Result<u64>;
+
+    /// Returns the data received for the upload.
+    async fn read(&self, id: &str) -> Result<Body>;
+
+    /// Deletes the upload.
+    async fn delete(&self, id: &str) -> Result<()>;
+
+    /// Deletes the uploads which are expired at `now`.
+    async fn delete_expired(&self, now: SystemTime) -> Result<()>;
+}
+
+/// A tus store using memory, for the tests and the small uploads.
+#[derive(Default)]
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+pub struct MemoryTusStore {
+    uploads: Mutex<HashMap<String, (TusUpload, BytesMut)>>,
+}
+
+impl MemoryTusStore {
+    /// Create a `MemoryTusStore`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl TusStore for MemoryTusStore {
+    async fn create(&self, upload: &TusUpload) -> Result<()> {
+        self.uploads
+            .lock()
+            .insert(upload.id.clone(), (upload.clone(), BytesMut::new()));
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<TusUpload>> {
+        Ok(self
+            .uploads
+            .lock()
+            .get(id)
+            .map(|(upload, _)| upload.clone()))
+    }
+
+    async fn append(&self, id: &str, body: Body) -> Result<u64> {
+        let mut body = body.into_bytes_stream();
+        while let Some(data) = body.next().await {
+            let data = data.map_err(ReadBodyError::Io)?;
+            let mut uploads = self.uploads.lock();
+            let (upload, buf) = uploads.get_mut(id).ok_or(TusError::NotFound)?;
+            buf.extend_from_slice(&data);
+            upload.offset += data.len() as u64;
+        }
+        Ok(self.get(id).await?.ok_or(TusError::NotFound)?.offset)
+    }
+
+    async fn read(&self, id: &str) -> Result<Body> {
+        let uploads = self.uploads.lock();
+        let (_, buf) = uploads.get(id).ok_or(TusError::NotFound)?;
+        Ok(Body::from_bytes(buf.clone().freeze()))
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        self.uploads.lock().remove(id);
+        Ok(())
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<()> {
+        self.uploads
+            .lock()
+            .retain(|_, (upload, _)| upload.expires > now);
+        Ok(())
+    }
+}
+
+/// A tus store writing the uploads to a directory.
+///
+/// The data of an upload is written to a file named by its id, and its
+/// information to the file of the same name with the `.info` extension.
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+pub struct FileTusStore {
+    dir: PathBuf,
+}
+
+impl FileTusStore {
+    /// Create a `FileTusStore` writing the uploads to `dir`, which is created
+    /// if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the path of the file the data of the upload is written to.
+    pub fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn info_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.info"))
+    }
+
+    async fn write_info(&self, upload: &TusUpload) -> Result<()> {
+        let data = serde_json::to_vec(upload).map_err(InternalServerError)?;
+        tokio::fs::write(self.info_path(&upload.id), data)
+            .await
+            .map_err(InternalServerError)
+    }
+}
+
+async fn remove_file(path: PathBuf) -> Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(InternalServerError(err)),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait::async_trait]
+impl TusStore for FileTusStore {
+    async fn create(&self, upload: &TusUpload) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(InternalServerError)?;
+        tokio::fs::File::create(self.path(&upload.id))
+            .await
+            .map_err(InternalServerError)?;
+        self.write_info(upload).await
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<TusUpload>> {
+        match tokio::fs::read(self.info_path(id)).await {
+            Ok(data) => Ok(Some(
+                serde_json::from_slice(&data).map_err(InternalServerError)?,
+            )),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(InternalServerError(err)),
+        }
+    }
+
+    async fn append(&self, id: &str, body: Body) -> Result<u64> {
+        let mut upload = self.get(id).await?.ok_or(TusError::NotFound)?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(self.path(id))
+            .await
+            .map_err(InternalServerError)?;
+        // discards the data partially written by a failed request
+        file.set_len(upload.offset)
+            .await
+            .map_err(InternalServerError)?;
+        file.seek(SeekFrom::Start(upload.offset))
+            .await
+            .map_err(InternalServerError)?;
+
+        let mut body = body.into_bytes_stream();
+        let res = async {
+            while let Some(data) = body.next().await {
+                let data = data.map_err(ReadBodyError::Io)?;
+                file.write_all(&data).await.map_err(InternalServerError)?;
+                upload.offset += data.len() as u64;
+            }
+            file.flush().await.map_err(InternalServerError)
+        }
+        .await;
+        self.write_info(&upload).await?;
+        res.map(|_| upload.offset)
+    }
+
+    async fn read(&self, id: &str) -> Result<Body> {
+        let file = tokio::fs::File::open(self.path(id))
+            .await
+            .map_err(InternalServerError)?;
+        Ok(Body::from_async_read(file))
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        remove_file(self.info_path(id)).await?;
+        remove_file(self.path(id)).await
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<()> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(InternalServerError(err)),
+        };
+        while let Some(entry) = entries.next_entry().await.map_err(InternalServerError)? {
+            let name = entry.file_name();
+            let id = match name.to_str().and_then(|name| name.strip_suffix(".info")) {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(upload) = self.get(id).await? {
+                if upload.expires <= now {
+                    self.delete(id).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A tus store writing the uploads to a [`Storage`].
+///
+/// The data of each `PATCH` request is stored as a separate object, and the
+/// information of an upload in an object named `info.json`, under the key
+/// `{prefix}{id}/`.
+///
+/// The [`Storage`] cannot list its objects, so the expired uploads are only
+/// deleted when they are accessed, or by the store which created them. A
+/// lifecycle rule of the bucket should delete the uploads abandoned before a
+/// restart.
+#[cfg(feature = "storage")]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "tus", feature = "storage"))))]
+pub struct StorageTusStore<S> {
+    storage: Arc<S>,
+    prefix: String,
+    expirations: Mutex<HashMap<String, SystemTime>>,
+}
+
+#[cfg(feature = "storage")]
+#[derive(Serialize, Deserialize)]
+struct StorageTusInfo {
+    upload: TusUpload,
+    /// The offsets of the objects of the upload.
+    parts: Vec<u64>,
+}
+
+#[cfg(feature = "storage")]
+impl<S: Storage> StorageTusStore<S> {
+    /// Create a `StorageTusStore` writing the uploads to `storage`, with the
+    /// `tus/` prefix.
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage: Arc::new(storage),
+            prefix: "tus/".to_string(),
+            expirations: Default::default(),
+        }
+    }
+
+    /// Sets the prefix of the keys of the uploads.
+    #[must_use]
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    fn info_key(&self, id: &str) -> String {
+        format!("{}{id}/info.json", self.prefix)
+    }
+
+    fn part_key(&self, id: &str, offset: u64) -> String {
+        format!("{}{id}/{offset:020}", self.prefix)
+    }
+
+    async fn get_info(&self, id: &str) -> Result<Option<StorageTusInfo>> {
+        match self.storage.get(&self.info_key(id)).await? {
+            Some(data) => Ok(Some(
+                serde_json::from_slice(&data).map_err(InternalServerError)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_info(&self, info: &StorageTusInfo) -> Result<()> {
+        let data = serde_json::to_vec(info).map_err(InternalServerError)?;
+        self.storage
+            .put(
+                &self.info_key(&info.upload.id),
+                Body::from(data),
+                Some("application/json"),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "storage")]
+#[async_trait::async_trait]
+impl<S: Storage + 'static> TusStore for StorageTusStore<S> {
+    async fn create(&self, upload: &TusUpload) -> Result<()> {
+        self.put_info(&StorageTusInfo {
+            upload: upload.clone(),
+            parts: Vec::new(),
+        })
+        .await?;
+        self.expirations
+            .lock()
+            .insert(upload.id.clone(), upload.expires);
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<TusUpload>> {
+        Ok(self.get_info(id).await?.map(|info| info.upload))
+    }
+
+    async fn append(&self, id: &str, body: Body) -> Result<u64> {
+        let mut info = self.get_info(id).await?.ok_or(TusError::NotFound)?;
+        let offset = info.upload.offset;
+        // the storages do not keep the objects which are partially written,
+        // so the data of a failed request is discarded
+        let meta = self
+            .storage
+            .put(&self.part_key(id, offset), body, None)
+            .await?;
+        if meta.size > 0 {
+            info.parts.push(offset);
+            info.upload.offset += meta.size;
+            self.put_info(&info).await?;
+        }
+        Ok(info.upload.offset)
+    }
+
+    async fn read(&self, id: &str) -> Result<Body> {
+        let info = self.get_info(id).await?.ok_or(TusError::NotFound)?;
+        let keys = info
+            .parts
+            .iter()
+            .map(|offset| self.part_key(id, *offset))
+            .collect::<Vec<_>>();
+        let storage = self.storage.clone();
+        let stream = futures_util::stream::iter(keys)
+            .then(move |key| {
+                let storage = storage.clone();
+                async move {
+                    match storage.stream(&key, None).await {
+                        Ok(Some(object)) => Ok(object.body.into_bytes_stream()),
+                        Ok(None) => Err(IoError::new(ErrorKind::NotFound, key)),
+                        Err(err) => Err(IoError::new(ErrorKind::Other, err.to_string())),
+                    }
+                }
+            })
+            .try_flatten();
+        Ok(Body::from_bytes_stream(stream))
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        self.expirations.lock().remove(id);
+        if let Some(info) = self.get_info(id).await? {
+            for offset in info.parts {
+                self.storage.delete(&self.part_key(id, offset)).await?;
+            }
+            self.storage.delete(&self.info_key(id)).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_expired(&self, now: SystemTime) -> Result<()> {
+        let expired = self
+            .expirations
+            .lock()
+            .iter()
+            .filter(|(_, expires)| **expires <= now)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for id in expired {
+            self.delete(&id).await?;
+        }
+        Ok(())
+    }
+}
+
+type CompleteFn = Box<dyn Fn(TusUpload, Body) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// An endpoint implementing the [tus](https://tus.io/protocols/resumable-upload)
+/// resumable upload protocol, with the `creation`, `expiration` and
+/// `termination` extensions.
+///
+/// The clients create an upload with a `POST` request to the endpoint, and
+/// append the data with `PATCH` requests to the URL returned in the
+/// `Location` header. After a failure, they resume the upload at the offset
+/// returned by a `HEAD` request. The `X-HTTP-Method-Override` header is
+/// supported for the clients which cannot send `PATCH` and `DELETE`
+/// requests.
+///
+/// The uploads which are not completed before they expire are deleted.
+///
+/// # Errors
+///
+/// - [`TusError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     endpoint::{MemoryTusStore, TusEndpoint, TusUpload},
+///     Body, Route,
+/// };
+///
+/// let app = Route::new().nest(
+///     "/files",
+///     TusEndpoint::new(MemoryTusStore::new())
+///         .max_size(1024 * 1024 * 1024)
+///         .on_complete(|upload: TusUpload, body: Body| async move {
+///             let data = body.into_bytes().await?;
+///             println!("{:?}: {} bytes", upload.metadata.get("filename"), data.len());
+///             Ok(())
+///         }),
+/// );
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+pub struct TusEndpoint<T> {
+    store: Arc<T>,
+    max_size: Option<u64>,
+    expiration: Duration,
+    on_complete: Option<CompleteFn>,
+    locks: Mutex<HashSet<String>>,
+}
+
+impl<T: TusStore> TusEndpoint<T> {
+    /// Create a `TusEndpoint` with the specified store.
+    pub fn new(store: T) -> Self {
+        Self {
+            store: Arc::new(store),
+            max_size: None,
+            expiration: Duration::from_secs(60 * 60 * 24),
+            on_complete: None,
+            locks: Default::default(),
+        }
+    }
+
+    /// Sets the maximum size of an upload in bytes.
+    #[must_use]
+    pub fn max_size(self, max_size: u64) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+
+    /// Sets how long an upload can be resumed after its creation. Default is
+    /// 24 hours.
+    #[must_use]
+    pub fn expiration(self, expiration: Duration) -> Self {
+        Self { expiration, ..self }
+    }
+
+    /// Sets a function called with the data of an upload when it is
+    /// completed, before replying to the last request.
+    ///
+    /// The upload is kept in the store after the function returns, until it
+    /// expires.
+    #[must_use]
+    pub fn on_complete<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(TusUpload, Body) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Self {
+            on_complete: Some(Box::new(move |upload, body| f(upload, body).boxed())),
+            ..self
+        }
+    }
+
+    async fn complete(&self, upload: TusUpload) -> Result<()> {
+        if let Some(on_complete) = &self.on_complete {
+            let body = self.store.read(&upload.id).await?;
+            on_complete(upload, body).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_upload(&self, id: &str, now: SystemTime) -> Result<TusUpload> {
+        let valid_id = id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid_id || id.is_empty() {
+            return Err(TusError::NotFound.into());
+        }
+
+        let upload = self.store.get(id).await?.ok_or(TusError::NotFound)?;
+        if upload.expires <= now {
+            self.store.delete(id).await?;
+            return Err(TusError::Expired.into());
+        }
+        Ok(upload)
+    }
+
+    async fn create(&self, req: &Request, now: SystemTime) -> Result<Response> {
+        let length = parse_u64(req.headers(), "upload-length").ok_or(TusError::InvalidLength)?;
+        if self.max_size.map_or(false, |max_size| length > max_size) {
+            return Err(TusError::TooLarge.into());
+        }
+        let metadata = match req.headers().get("upload-metadata") {
+            Some(value) => parse_metadata(value.to_str().map_err(|_| TusError::InvalidMetadata)?)?,
+            None => BTreeMap::new(),
+        };
+
+        self.store.delete_expired(now).await?;
+        let upload = TusUpload {
+            id: generate_id(),
+            length,
+            offset: 0,
+            metadata,
+            expires: now + self.expiration,
+        };
+        self.store.create(&upload).await?;
+
+        let location = format!(
+            "{}/{}",
+            req.original_uri().path().trim_end_matches('/'),
+            upload.id
+        );
+        let expires = httpdate::fmt_http_date(upload.expires);
+        if upload.is_complete() {
+            self.complete(upload).await?;
+        }
+        Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .header(header::LOCATION, location)
+            .header("upload-expires", expires)
+            .finish())
+    }
+
+    async fn head(&self, id: &str, now: SystemTime) -> Result<Response> {
+        let upload = self.get_upload(id, now).await?;
+        let mut resp = Response::builder()
+            .header(header::CACHE_CONTROL, "no-store")
+            .header("upload-offset", upload.offset)
+            .header("upload-length", upload.length)
+            .header("upload-expires", httpdate::fmt_http_date(upload.expires));
+        if !upload.metadata.is_empty() {
+            resp = resp.header("upload-metadata", encode_metadata(&upload.metadata));
+        }
+        Ok(resp.finish())
+    }
+
+    async fn patch(&self, mut req: Request, id: &str, now: SystemTime) -> Result<Response> {
+        let is_offset_stream = req.content_type() == Some(OFFSET_OCTET_STREAM);
+        if !is_offset_stream {
+            return Err(TusError::UnsupportedContentType.into());
+        }
+        let offset = parse_u64(req.headers(), "upload-offset").ok_or(TusError::InvalidOffset)?;
+
+        let _lock = UploadLock::acquire(&self.locks, id)?;
+        let upload = self.get_upload(id, now).await?;
+        if offset != upload.offset {
+            return Err(TusError::OffsetMismatch(upload.offset).into());
+        }
+
+        let exceeded = Arc::new(AtomicBool::new(false));
+        let body = limit_body(
+            req.take_body(),
+            upload.length - upload.offset,
+            exceeded.clone(),
+        );
+        let res = self.store.append(id, body).await;
+        if exceeded.load(Ordering::Relaxed) {
+            return Err(TusError::TooLarge.into());
+        }
+        let offset = res?;
+
+        let expires = httpdate::fmt_http_date(upload.expires);
+        if offset == upload.length && upload.offset < upload.length {
+            self.complete(TusUpload { offset, ..upload }).await?;
+        }
+        Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header("upload-offset", offset)
+            .header("upload-expires", expires)
+            .finish())
+    }
+
+    async fn delete(&self, id: &str, now: SystemTime) -> Result<Response> {
+        let _lock = UploadLock::acquire(&self.locks, id)?;
+        self.get_upload(id, now).await?;
+        self.store.delete(id).await?;
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+
+    async fn handle(&self, req: Request) -> Result<Response> {
+        let method = req
+            .headers()
+            .get("x-http-method-override")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Method>().ok())
+            .unwrap_or_else(|| req.method().clone());
+
+        if method == Method::OPTIONS {
+            let mut resp = Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header("tus-version", TUS_VERSION)
+                .header("tus-extension", TUS_EXTENSIONS);
+            if let Some(max_size) = self.max_size {
+                resp = resp.header("tus-max-size", max_size);
+            }
+            return Ok(resp.finish());
+        }
+
+        let version = req
+            .headers()
+            .get("tus-resumable")
+            .and_then(|value| value.to_str().ok());
+        if version != Some(TUS_VERSION) {
+            return Err(TusError::UnsupportedVersion.into());
+        }
+
+        let now = Clock::of(&req).now();
+        let id = req.uri().path().trim_matches('/').to_string();
+        match (method, id.is_empty()) {
+            (Method::POST, true) => self.create(&req, now).await,
+            (Method::HEAD, false) => self.head(&id, now).await,
+            (Method::PATCH, false) => self.patch(req, &id, now).await,
+            (Method::DELETE, false) => self.delete(&id, now).await,
+            _ => Err(TusError::MethodNotAllowed.into()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: TusStore> Endpoint for TusEndpoint<T> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let mut resp = match self.handle(req).await {
+            Ok(resp) => resp,
+            Err(err) => {
+                if let Some(TusError::OffsetMismatch(offset)) = err.downcast_ref::<TusError>() {
+                    let offset = *offset;
+                    let mut resp = err.into_response();
+                    resp.headers_mut().insert("upload-offset", offset.into());
+                    resp
+                } else {
+                    err.into_response()
+                }
+            }
+        };
+
+        if resp.status() == StatusCode::PRECONDITION_FAILED {
+            resp.headers_mut()
+                .insert("tus-version", HeaderValue::from_static(TUS_VERSION));
+        }
+        resp.headers_mut()
+            .insert("tus-resumable", HeaderValue::from_static(TUS_VERSION));
+        Ok(resp)
+    }
+}
+
+/// Prevents the concurrent requests from modifying the same upload.
+struct UploadLock<'a> {
+    locks: &'a Mutex<HashSet<String>>,
+    id: String,
+}
+
+impl<'a> UploadLock<'a> {
+    fn acquire(locks: &'a Mutex<HashSet<String>>, id: &str) -> Result<Self> {
+        if !locks.lock().insert(id.to_string()) {
+            return Err(TusError::Locked.into());
+        }
+        Ok(Self {
+            locks,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl Drop for UploadLock<'_> {
+    fn drop(&mut self) {
+        self.locks.lock().remove(&self.id);
+    }
+}
+
+fn generate_id() -> String {
+    hex::encode(thread_rng().gen::<[u8; 16]>())
+}
+
+/// Returns a body failing when more than `limit` bytes are read, and setting
+/// `exceeded`.
+fn limit_body(body: Body, limit: u64, exceeded: Arc<AtomicBool>) -> Body {
+    let mut received = 0;
+    Body::from_bytes_stream(body.into_bytes_stream().map(move |data| {
+        let data = data?;
+        received += data.len() as u64;
+        if received > limit {
+            exceeded.store(true, Ordering::Relaxed);
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "the data exceeds the length of the upload",
+            ));
+        }
+        Ok(data)
+    }))
+}
+
+fn parse_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// Parses the `Upload-Metadata` header, a comma separated list of keys and
+/// base64 encoded values.
+fn parse_metadata(value: &str) -> Result<BTreeMap<String, String>, TusError> {
+    let mut metadata = BTreeMap::new();
+    for pair in value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = match pair.split_once(' ') {
+            Some((key, value)) => {
+                let value = STANDARD
+                    .decode(value.trim())
+                    .map_err(|_| TusError::InvalidMetadata)?;
+                (
+                    key,
+                    String::from_utf8(value).map_err(|_| TusError::InvalidMetadata)?,
+                )
+            }
+            None => (pair, String::new()),
+        };
+        if metadata.insert(key.to_string(), value).is_some() {
+            return Err(TusError::InvalidMetadata);
+        }
+    }
+    Ok(metadata)
+}
+
+fn encode_metadata(metadata: &BTreeMap<String, String>) -> String {
+    metadata
+        .iter()
+        .map(|(key, value)| {
+            if value.is_empty() {
+                key.clone()
+            } else {
+                format!("{key} {}", STANDARD.encode(value))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::{test::TestClient, web::MockClock, EndpointExt, Route};
+
+    #[test]
+    fn metadata() {
+        let metadata =
+            parse_metadata("filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential")
+                .unwrap();
+        assert_eq!(
+            metadata,
+            BTreeMap::from([
+                (
+                    "filename".to_string(),
+                    "world_domination_plan.pdf".to_string()
+                ),
+                ("is_confidential".to_string(), String::new()),
+            ])
+        );
+        assert_eq!(
+            parse_metadata(&encode_metadata(&metadata)).unwrap(),
+            metadata
+        );
+        assert_eq!(parse_metadata("a !!"), Err(TusError::InvalidMetadata));
+        assert_eq!(parse_metadata("a,a"), Err(TusError::InvalidMetadata));
+    }
+
+    #[tokio::test]
+    async fn upload() {
+        let completed = Arc::new(Mutex::new(Vec::new()));
+        let clock = MockClock::at(UNIX_EPOCH + Duration::from_secs(1000));
+        let ep = TusEndpoint::new(MemoryTusStore::new())
+            .max_size(100)
+            .on_complete({
+                let completed = completed.clone();
+                move |upload: TusUpload, body: Body| {
+                    let completed = completed.clone();
+                    async move {
+                        let data = body.into_string().await?;
+                        completed
+                            .lock()
+                            .push((upload.metadata["name"].clone(), data));
+                        Ok(())
+                    }
+                }
+            });
+        let cli = TestClient::new(
+            Route::new()
+                .nest("/files", ep)
+                .data(Clock::from(clock.clone())),
+        );
+
+        let resp = cli.options("/files").send().await;
+        resp.assert_status(StatusCode::NO_CONTENT);
+        resp.assert_header("tus-version", TUS_VERSION);
+        resp.assert_header("tus-max-size", "100");
+
+        cli.post("/files")
+            .header("upload-length", "10")
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+        cli.post("/files")
+            .header("tus-resumable", TUS_VERSION)
+            .header("upload-length", "101")
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        let resp = cli
+            .post("/files")
+            .header("tus-resumable", TUS_VERSION)
+            .header("upload-length", "10")
+            .header("upload-metadata", "name YS50eHQ=")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::CREATED);
+        resp.assert_header("tus-resumable", TUS_VERSION);
+        let location = resp.0.header(header::LOCATION).unwrap().to_string();
+        assert!(location.starts_with("/files/"));
+
+        let patch = |offset: &'static str, data: &'static str| {
+            cli.patch(&location)
+                .header("tus-resumable", TUS_VERSION)
+                .header("upload-offset", offset)
+                .content_type(OFFSET_OCTET_STREAM)
+                .body(data)
+                .send()
+        };
+
+        let resp = patch("0", "hello").await;
+        resp.assert_status(StatusCode::NO_CONTENT);
+        resp.assert_header("upload-offset", "5");
+
+        let resp = patch("0", "hello").await;
+        resp.assert_status(StatusCode::CONFLICT);
+        resp.assert_header("upload-offset", "5");
+
+        let resp = cli
+            .head(&location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_header("upload-offset", "5");
+        resp.assert_header("upload-length", "10");
+        resp.assert_header("upload-metadata", "name YS50eHQ=");
+
+        patch("5", "world!")
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(completed.lock().is_empty());
+        patch("5", "world")
+            .await
+            .assert_header("upload-offset", "10");
+        assert_eq!(
+            *completed.lock(),
+            [("a.txt".to_string(), "helloworld".to_string())]
+        );
+
+        clock.advance(Duration::from_secs(60 * 60 * 24));
+        cli.head(&location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await
+            .assert_status(StatusCode::GONE);
+        cli.head(&location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn file_store() {
+        let dir = std::env::temp_dir().join(format!("poem-tus-{}", std::process::id()));
+        let store = FileTusStore::new(&dir);
+        let upload = TusUpload {
+            id: generate_id(),
+            length: 6,
+            offset: 0,
+            metadata: BTreeMap::new(),
+            expires: UNIX_EPOCH + Duration::from_secs(100),
+        };
+
+        store.create(&upload).await.unwrap();
+        assert_eq!(
+            store.append(&upload.id, Body::from("abc")).await.unwrap(),
+            3
+        );
+        assert_eq!(
+            store.append(&upload.id, Body::from("def")).await.unwrap(),
+            6
+        );
+        assert_eq!(store.get(&upload.id).await.unwrap().unwrap().offset, 6);
+        let body = store.read(&upload.id).await.unwrap();
+        assert_eq!(body.into_string().await.unwrap(), "abcdef");
+
+        store
+            .delete_expired(UNIX_EPOCH + Duration::from_secs(50))
+            .await
+            .unwrap();
+        assert!(store.get(&upload.id).await.unwrap().is_some());
+        store
+            .delete_expired(UNIX_EPOCH + Duration::from_secs(100))
+            .await
+            .unwrap();
+        assert!(store.get(&upload.id).await.unwrap().is_none());
+        assert!(!store.path(&upload.id).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "storage")]
+    #[tokio::test]
+    async fn storage_store() {
+        let dir = std::env::temp_dir().join(format!("poem-tus-storage-{}", std::process::id()));
+        let store = StorageTusStore::new(crate::storage::LocalStorage::new(&dir));
+        let upload = TusUpload {
+            id: generate_id(),
+            length: 6,
+            offset: 0,
+            metadata: BTreeMap::new(),
+            expires: UNIX_EPOCH + Duration::from_secs(100),
+        };
+
+        store.create(&upload).await.unwrap();
+        assert_eq!(
+            store.append(&upload.id, Body::from("abc")).await.unwrap(),
+            3
+        );
+        assert_eq!(store.append(&upload.id, Body::empty()).await.unwrap(), 3);
+        assert_eq!(
+            store.append(&upload.id, Body::from("def")).await.unwrap(),
+            6
+        );
+        assert_eq!(store.get(&upload.id).await.unwrap().unwrap().offset, 6);
+        let body = store.read(&upload.id).await.unwrap();
+        assert_eq!(body.into_string().await.unwrap(), "abcdef");
+
+        store
+            .delete_expired(UNIX_EPOCH + Duration::from_secs(100))
+            .await
+            .unwrap();
+        assert!(store.get(&upload.id).await.unwrap().is_none());
+        assert_eq!(
+            std::fs::read_dir(dir.join("tus").join(&upload.id))
+                .unwrap()
+                .count(),
+            0
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn terminate() {
+        let cli = TestClient::new(TusEndpoint::new(MemoryTusStore::new()));
+        let resp = cli
+            .post("/")
+            .header("tus-resumable", TUS_VERSION)
+            .header("upload-length", "10")
+            .send()
+            .await;
+        let location = resp.0.header(header::LOCATION).unwrap().to_string();
+
+        cli.post(&location)
+            .header("tus-resumable", TUS_VERSION)
+            .header("x-http-method-override", "DELETE")
+            .send()
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        cli.head(&location)
+            .header("tus-resumable", TUS_VERSION)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use http::{
+    uri::{Authority, Scheme},
+    Uri,
+};
+use parking_lot::Mutex;
+
+/// The strategy used by an [`UpstreamPool`] to select an upstream.
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy")))]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum LoadBalance {
+    /// Select the upstreams in turn.
+    #[default]
+    RoundRobin,
+
+    /// Select the upstream with the fewest requests in progress.
+    LeastConnections,
+}
+
+#[derive(Default)]
+struct Health {
+    failures: usize,
+    open_until: Option<Instant>,
+}
+
+pub(crate) struct Upstream {
+    pub(crate) scheme: Scheme,
+    pub(crate) authority: Authority,
+    pub(crate) base_path: String,
+    active: Arc<AtomicUsize>,
+    health: Mutex<Health>,
+}
+
+/// Tracks a request in progress for [`LoadBalance::LeastConnections`] until
+/// it is dropped.
+pub(crate) struct ActiveGuard(Arc<AtomicUsize>);
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A pool of upstreams used by the [`Proxy`](crate::endpoint::Proxy)
+/// endpoint.
+///
+/// The failures of the forwarded requests are tracked for each upstream
+/// (passive health checks): a connection error or a `502`, `503` or `504`
+/// response counts as a failure, and any other response resets the count.
+/// After [`UpstreamPool::failure_threshold`] consecutive failures, the circuit
+/// of the upstream is opened and it is not selected for
+/// [`UpstreamPool::open_duration`]. After that, requests are sent to it again,
+/// and another failure opens the circuit immediately.
+///
+/// If connecting to an upstream fails, the request is retried on another one,
+/// up to [`UpstreamPool::max_retries`] times. Because the request bodies are
+/// streamed, only the requests without a body are retried.
+///
+/// # Example
+///
+/// ```
+/// use poem::endpoint::{LoadBalance, Proxy, UpstreamPool};
+///
+/// let ep = Proxy::with_pool(
+///     UpstreamPool::new()
+///         .upstream("http://10.0.0.1:8080")
+///         .upstream("http://10.0.0.2:8080")
+///         .load_balance(LoadBalance::LeastConnections),
+/// );
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy")))]
+pub struct UpstreamPool {
+    upstreams: Vec<Upstream>,
+    load_balance: LoadBalance,
+    next: AtomicUsize,
+    pub(crate) max_retries: usize,
+    failure_threshold: usize,
+    open_duration: Duration,
+}
+
+impl Default for UpstreamPool {
+    fn default() -> Self {
+        Self {
+            upstreams: Vec::new(),
+            load_balance: LoadBalance::default(),
+            next: AtomicUsize::new(0),
+            max_retries: 1,
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl UpstreamPool {
+    /// Create an empty `UpstreamPool`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add an upstream to the pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `uri` is not a valid `http` uri.
+    #[must_use]
+    pub fn upstream(mut self, uri: impl AsRef<str>) -> Self {
+        let uri: Uri = uri.as_ref().parse().expect("invalid upstream uri");
+        assert_eq!(
+            uri.scheme(),
+            Some(&Scheme::HTTP),
+            "the scheme of the upstream uri must be `http`"
+        );
+
+        self.upstreams.push(Upstream {
+            scheme: Scheme::HTTP,
+            authority: uri.authority().cloned().expect("invalid upstream uri"),
+            base_path: uri.path().trim_end_matches('/').to_string(),
+            active: Default::default(),
+            health: Default::default(),
+        });
+        self
+    }
+
+    /// Sets the strategy used to select an upstream. Defaults to
+    /// [`LoadBalance::RoundRobin`].
+    #[must_use]
+    pub fn load_balance(self, load_balance: LoadBalance) -> Self {
+        Self {
+            load_balance,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of times a request is retried on another
+    /// upstream when the connection fails. Defaults to `1`.
+    #[must_use]
+    pub fn max_retries(self, max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+
+    /// Sets the number of consecutive failures after which the circuit of an
+    /// upstream is opened. Defaults to `5`.
+    #[must_use]
+    pub fn failure_threshold(self, failure_threshold: usize) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            ..self
+        }
+    }
+
+    /// Sets how long an upstream is not selected after its circuit has been
+    /// opened. Defaults to 30 seconds.
+    #[must_use]
+    pub fn open_duration(self, open_duration: Duration) -> Self {
+        Self {
+            open_duration,
+            ..self
+        }
+    }
+
+    /// Selects an available upstream that is not in `exclude`.
+    pub(crate) fn select(&self, exclude: &[usize]) -> Option<usize> {
+        if self.upstreams.is_empty() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let candidates = (0..self.upstreams.len())
+            .map(|offset| (start + offset) % self.upstreams.len())
+            .filter(|idx| !exclude.contains(idx))
+            .filter(|idx| {
+                let health = self.upstreams[*idx].health.lock();
+                health
+                    .open_until
+                    .map_or(true, |open_until| open_until <= now)
+            });
+
+        match self.load_balance {
+            LoadBalance::RoundRobin => candidates.take(1).next(),
+            LoadBalance::LeastConnections => {
+                candidates.min_by_key(|idx| self.upstreams[*idx].active.load(Ordering::Relaxed))
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, idx: usize) -> &Upstream {
+        &self.upstreams[idx]
+    }
+
+    pub(crate) fn begin(&self, idx: usize) -> ActiveGuard {
+        let active = self.upstreams[idx].active.clone();
+        active.fetch_add(1, Ordering::Relaxed);
+        ActiveGuard(active)
+    }
+
+    pub(crate) fn record_success(&self, idx: usize) {
+        *self.upstreams[idx].health.lock() = Health::default();
+    }
+
+    pub(crate) fn record_failure(&self, idx: usize) {
+        let mut health = self.upstreams[idx].health.lock();
+        health.failures += 1;
+        if health.failures >= self.failure_threshold {
+            health.open_until = Some(Instant::now() + self.open_duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> UpstreamPool {
+        UpstreamPool::new()
+            .upstream("http://127.0.0.1:8001")
+            .upstream("http://127.0.0.1:8002/a/")
+            .upstream("http://127.0.0.1:8003")
+    }
+
+    #[test]
+    fn round_robin() {
+        let pool = pool();
+        assert_eq!(pool.get(1).base_path, "/a");
+        let selected = (0..6)
+            .map(|_| pool.select(&[]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec![0, 1, 2, 0, 1, 2]);
+        assert_eq!(pool.select(&[1, 2]), Some(0));
+        assert_eq!(pool.select(&[0, 1, 2]), None);
+    }
+
+    #[test]
+    fn least_connections() {
+        let pool = pool().load_balance(LoadBalance::LeastConnections);
+        let _a = pool.begin(0);
+        let _b = pool.begin(1);
+        assert_eq!(pool.select(&[]), Some(2));
+        let c = pool.begin(2);
+        let d = pool.begin(2);
+        let _e = pool.begin(1);
+        assert_eq!(pool.select(&[]), Some(0));
+        drop((c, d));
+        assert_eq!(pool.select(&[]), Some(2));
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let pool = pool()
+            .failure_threshold(2)
+            .open_duration(Duration::from_secs(60));
+        pool.record_failure(0);
+        pool.record_success(0);
+        pool.record_failure(0);
+        assert_eq!(pool.select(&[1, 2]), Some(0));
+        pool.record_failure(0);
+        assert_eq!(pool.select(&[1, 2]), None);
+
+        let pool = pool.open_duration(Duration::ZERO);
+        pool.record_failure(1);
+        pool.record_failure(1);
+        assert_eq!(pool.select(&[0, 2]), Some(1));
+    }
+}
@@ -643,6 +643,20 @@ define_simple_errors!(
     (MethodNotAllowedError, METHOD_NOT_ALLOWED, "method not allowed");
 );
 
+/// A possible error value when the client did not present a TLS certificate.
+#[cfg(feature = "rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+#[derive(Debug, thiserror::Error, Copy, Clone, Eq, PartialEq)]
+#[error("client certificate required")]
+pub struct ClientCertRequiredError;
+
+#[cfg(feature = "rustls")]
+impl ResponseError for ClientCertRequiredError {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
 /// A possible error value when reading the body.
 #[derive(Debug, thiserror::Error)]
 pub enum ReadBodyError {
@@ -710,6 +724,112 @@ impl ResponseError for GetDataError {
     }
 }
 
+/// A possible error value when the [`Deadline`](crate::web::Deadline) of a
+/// request expires.
+#[derive(Debug, Copy, Clone, thiserror::Error, Eq, PartialEq)]
+#[error("the deadline of the request expired")]
+pub struct DeadlineExceededError;
+
+impl ResponseError for DeadlineExceededError {
+    fn status(&self) -> StatusCode {
+        StatusCode::GATEWAY_TIMEOUT
+    }
+}
+
+/// A possible error value when sending an event to an
+/// [`EventStream`](crate::web::sse::EventStream) whose client disconnected.
+#[cfg(feature = "sse")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+#[derive(Debug, Copy, Clone, thiserror::Error, Eq, PartialEq)]
+#[error("the event stream is closed")]
+pub struct StreamClosedError;
+
+#[cfg(feature = "sse")]
+impl ResponseError for StreamClosedError {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// A possible error value when an extractor requires a middleware which is
+/// not applied to the endpoint.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+#[error("to use the `{extractor}` extractor, the `{middleware}` middleware is required")]
+pub struct MissingMiddlewareError {
+    /// The name of the extractor.
+    pub extractor: &'static str,
+    /// The name of the missing middleware.
+    pub middleware: &'static str,
+}
+
+impl ResponseError for MissingMiddlewareError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value when a [`Guard`](crate::guard::Guard) rejects a
+/// request.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum GuardError {
+    /// Invalid content type.
+    #[error("invalid content type `{0}`")]
+    InvalidContentType(String),
+
+    /// `Content-Type` header is required.
+    #[error("expect a `Content-Type` header")]
+    ContentTypeRequired,
+
+    /// The feature is not enabled for the request.
+    #[error("feature `{0}` is not enabled")]
+    FeatureDisabled(String),
+
+    /// The request is not authenticated.
+    #[error("authentication is required")]
+    Unauthenticated,
+
+    /// The scopes granted to the client do not satisfy the requirement.
+    #[error("insufficient scope, requires {0}")]
+    InsufficientScope(String),
+}
+
+impl ResponseError for GuardError {
+    fn status(&self) -> StatusCode {
+        match self {
+            GuardError::InvalidContentType(_) | GuardError::ContentTypeRequired => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+            GuardError::FeatureDisabled(_) => StatusCode::NOT_FOUND,
+            GuardError::Unauthenticated => StatusCode::UNAUTHORIZED,
+            GuardError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// A possible error value when injecting a service with the
+/// [`Inject`](crate::web::Inject) extractor.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum InjectError {
+    /// The [`Services`](crate::middleware::Services) middleware is not
+    /// applied.
+    #[error("the `Services` middleware is required to inject `{0}`")]
+    MissingMiddleware(&'static str),
+
+    /// The service is not registered.
+    #[error("service of type `{0}` is not registered")]
+    NotRegistered(&'static str),
+
+    /// The service depends on itself.
+    #[error("service of type `{0}` depends on itself")]
+    Cycle(&'static str),
+}
+
+impl ResponseError for InjectError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
 /// A possible error value when parsing form.
 #[derive(Debug, thiserror::Error)]
 pub enum ParseFormError {
@@ -818,6 +938,90 @@ impl ResponseError for ParseYamlError {
     }
 }
 
+/// A possible error value when parsing CSV.
+#[cfg(feature = "csv")]
+#[derive(Debug, thiserror::Error)]
+pub enum ParseCsvError {
+    /// Invalid content type.
+    #[error("invalid content type `{0}`, expect: `text/csv`")]
+    InvalidContentType(String),
+
+    /// `Content-Type` header is required.
+    #[error("expect content type `text/csv`")]
+    ContentTypeRequired,
+
+    /// Csv decode error.
+    #[error("parse error: {0}")]
+    Parse(#[from] csv::Error),
+}
+
+#[cfg(feature = "csv")]
+impl ResponseError for ParseCsvError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ParseCsvError::InvalidContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ParseCsvError::ContentTypeRequired => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ParseCsvError::Parse(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// A possible error value when parsing MessagePack.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, thiserror::Error)]
+pub enum ParseMsgpackError {
+    /// Invalid content type.
+    #[error("invalid content type `{0}`, expect: `application/msgpack`")]
+    InvalidContentType(String),
+
+    /// `Content-Type` header is required.
+    #[error("expect content type `application/msgpack`")]
+    ContentTypeRequired,
+
+    /// MessagePack decode error.
+    #[error("parse error: {0}")]
+    Parse(#[from] rmp_serde::decode::Error),
+}
+
+#[cfg(feature = "msgpack")]
+impl ResponseError for ParseMsgpackError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ParseMsgpackError::InvalidContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ParseMsgpackError::ContentTypeRequired => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ParseMsgpackError::Parse(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// A possible error value when parsing CBOR.
+#[cfg(feature = "cbor")]
+#[derive(Debug, thiserror::Error)]
+pub enum ParseCborError {
+    /// Invalid content type.
+    #[error("invalid content type `{0}`, expect: `application/cbor`")]
+    InvalidContentType(String),
+
+    /// `Content-Type` header is required.
+    #[error("expect content type `application/cbor`")]
+    ContentTypeRequired,
+
+    /// CBOR decode error.
+    #[error("parse error: {0}")]
+    Parse(#[from] ciborium::de::Error<std::io::Error>),
+}
+
+#[cfg(feature = "cbor")]
+impl ResponseError for ParseCborError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ParseCborError::InvalidContentType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ParseCborError::ContentTypeRequired => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ParseCborError::Parse(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
 /// A possible error value when parsing query.
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
@@ -829,6 +1033,61 @@ impl ResponseError for ParseQueryError {
     }
 }
 
+/// A possible error value when parsing the parameters of a list.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum ParseListParamsError {
+    /// The query string is not valid.
+    #[error("invalid query string")]
+    InvalidQuery,
+
+    /// The `sort` parameter is not valid.
+    #[error("invalid sort `{0}`")]
+    InvalidSort(String),
+
+    /// The field cannot be sorted on.
+    #[error("cannot sort by `{0}`")]
+    UnknownSortField(String),
+
+    /// The field cannot be filtered on.
+    #[error("cannot filter by `{0}`")]
+    UnknownFilterField(String),
+
+    /// The `page` parameter is not a positive integer.
+    #[error("invalid page `{0}`")]
+    InvalidPage(String),
+
+    /// The `per_page` parameter is not a positive integer within the maximum.
+    #[error("invalid page size `{0}`")]
+    InvalidPageSize(String),
+}
+
+impl ResponseError for ParseListParamsError {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+/// A possible error value when parsing a structured query.
+#[cfg(feature = "qs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "qs")))]
+#[derive(Debug, thiserror::Error)]
+pub enum ParseStructuredQueryError {
+    /// Failed to parse the query string.
+    #[error(transparent)]
+    Parse(#[from] serde_qs::Error),
+
+    /// The query string has keys which are not deserialized, in strict mode.
+    #[error("unknown query keys: {}", .0.join(", "))]
+    UnknownKeys(Vec<String>),
+}
+
+#[cfg(feature = "qs")]
+impl ResponseError for ParseStructuredQueryError {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+}
+
 /// A possible error value when parsing multipart.
 #[cfg(feature = "multipart")]
 #[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
@@ -1023,6 +1282,10 @@ pub enum RouteError {
     #[error("duplicate path: {0}")]
     Duplicate(String),
 
+    /// Duplicate route name
+    #[error("duplicate route name: {0}")]
+    DuplicateName(String),
+
     /// Invalid regex in path
     #[error("invalid regex in path: {path}")]
     InvalidRegex {
@@ -1062,6 +1325,96 @@ impl ResponseError for CorsError {
     }
 }
 
+/// A possible error value when verifying a webhook.
+#[cfg(feature = "webhook")]
+#[cfg_attr(docsrs, doc(cfg(feature = "webhook")))]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum WebhookError {
+    /// The signature header is missing.
+    #[error("missing webhook signature header `{0}`")]
+    MissingSignature(&'static str),
+
+    /// The signature or timestamp header is malformed.
+    #[error("malformed webhook signature")]
+    MalformedSignature,
+
+    /// The signature doesn't match the body.
+    #[error("invalid webhook signature")]
+    InvalidSignature,
+
+    /// The signed timestamp is outside of the replay window.
+    #[error("webhook timestamp outside of the tolerance")]
+    Expired,
+}
+
+#[cfg(feature = "webhook")]
+impl ResponseError for WebhookError {
+    fn status(&self) -> StatusCode {
+        match self {
+            WebhookError::MalformedSignature => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+/// A possible error value occurred in the
+/// [`RequestSignature`](crate::middleware::RequestSignature) middleware.
+#[cfg(feature = "request-signing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "request-signing")))]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum RequestSignatureError {
+    /// A signature header is missing.
+    #[error("missing request signature header `{0}`")]
+    MissingHeader(&'static str),
+
+    /// The signature or the timestamp is malformed.
+    #[error("malformed request signature")]
+    Malformed,
+
+    /// The key is not accepted.
+    #[error("unknown signing key `{0}`")]
+    UnknownKey(String),
+
+    /// The signed timestamp is outside of the clock skew window.
+    #[error("request timestamp outside of the clock skew window")]
+    Expired,
+
+    /// The signature doesn't match the request.
+    #[error("invalid request signature")]
+    Invalid,
+}
+
+#[cfg(feature = "request-signing")]
+impl ResponseError for RequestSignatureError {
+    fn status(&self) -> StatusCode {
+        match self {
+            RequestSignatureError::Malformed => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+/// A possible error value when decoding a request body.
+#[cfg(feature = "request-decoding")]
+#[cfg_attr(docsrs, doc(cfg(feature = "request-decoding")))]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum RequestDecodingError {
+    /// The `Content-Encoding` of the body is not supported.
+    #[error("unsupported content encoding `{0}`")]
+    UnsupportedEncoding(String),
+
+    /// The charset of the body is not supported.
+    #[error("unsupported charset `{0}`")]
+    UnsupportedCharset(String),
+}
+
+#[cfg(feature = "request-decoding")]
+impl ResponseError for RequestDecodingError {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    }
+}
+
 /// A possible error value occurred when loading i18n resources.
 #[cfg(feature = "i18n")]
 #[derive(Debug, thiserror::Error)]
@@ -1101,6 +1454,79 @@ impl ResponseError for I18NError {
     }
 }
 
+/// A possible error value occurred when the quota of a client is exceeded in
+/// the [`RateLimited`](crate::rate_limit::RateLimited) extractor.
+///
+/// The response is a problem details object, with the `Retry-After` and the
+/// `RateLimit-*` headers.
+#[derive(Debug, Copy, Clone, thiserror::Error, Eq, PartialEq)]
+#[error("the quota of the policy `{policy}` is exceeded")]
+pub struct RateLimitError {
+    /// The name of the policy.
+    pub policy: &'static str,
+    /// The status of the quota of the client.
+    pub status: crate::rate_limit::RateLimitStatus,
+}
+
+impl ResponseError for RateLimitError {
+    fn status(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn as_response(&self) -> Response {
+        let reset_after = self.status.reset_after;
+        let retry_after = reset_after.as_secs() + u64::from(reset_after.subsec_nanos() > 0);
+        let mut resp = ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS)
+            .detail(self.to_string())
+            .extension("policy", self.policy)
+            .extension("limit", self.status.limit)
+            .extension("retry_after", retry_after)
+            .as_response();
+        let headers = resp.headers_mut();
+        headers.insert(http::header::RETRY_AFTER, retry_after.into());
+        headers.insert("ratelimit-limit", self.status.limit.into());
+        headers.insert("ratelimit-remaining", self.status.remaining.into());
+        headers.insert("ratelimit-reset", retry_after.into());
+        resp
+    }
+}
+
+/// A possible error value occurred in a
+/// [`SessionCollection`](crate::session::SessionCollection).
+#[cfg(feature = "session")]
+#[cfg_attr(docsrs, doc(cfg(feature = "session")))]
+#[derive(Debug, thiserror::Error)]
+pub enum SessionCollectionError {
+    /// The collection is full.
+    #[error("the collection is full, the maximum length is {max_len}")]
+    Full {
+        /// The maximum number of items.
+        max_len: usize,
+    },
+
+    /// The item is too large.
+    #[error("the item is too large, the maximum size is {max_item_size} bytes")]
+    ItemTooLarge {
+        /// The maximum size in bytes of an item.
+        max_item_size: usize,
+    },
+
+    /// Failed to serialize the item.
+    #[error("serialize: {0}")]
+    Serialize(serde_json::Error),
+}
+
+#[cfg(feature = "session")]
+impl ResponseError for SessionCollectionError {
+    fn status(&self) -> StatusCode {
+        match self {
+            SessionCollectionError::Full { .. } => StatusCode::BAD_REQUEST,
+            SessionCollectionError::ItemTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            SessionCollectionError::Serialize(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 /// A possible error value occurred when deal with redis session.
 #[cfg(feature = "redis-session")]
 #[derive(Debug, thiserror::Error)]
@@ -1117,6 +1543,584 @@ impl ResponseError for RedisSessionError {
     }
 }
 
+/// A possible error value occurred in a [`RedisLock`](crate::lock::RedisLock).
+#[cfg(feature = "redis-lock")]
+#[cfg_attr(docsrs, doc(cfg(feature = "redis-lock")))]
+#[derive(Debug, thiserror::Error)]
+pub enum RedisLockError {
+    /// Redis error.
+    #[error("redis: {0}")]
+    Redis(redis::RedisError),
+}
+
+#[cfg(feature = "redis-lock")]
+impl ResponseError for RedisLockError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value occurred in the `Proxy` endpoint.
+#[cfg(feature = "proxy")]
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyError {
+    /// The rewritten uri is invalid.
+    #[error("invalid upstream uri: {0}")]
+    InvalidUri(#[from] http::uri::InvalidUri),
+
+    /// Failed to send the request to the upstream.
+    #[error("upstream: {0}")]
+    Upstream(#[from] hyper::Error),
+
+    /// All the upstreams are unavailable.
+    #[error("no available upstream")]
+    NoAvailableUpstream,
+}
+
+#[cfg(feature = "proxy")]
+impl ResponseError for ProxyError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ProxyError::InvalidUri(_) => StatusCode::BAD_REQUEST,
+            ProxyError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ProxyError::NoAvailableUpstream => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+/// A possible error value occurred when sending a request with the
+/// [`HttpClient`](crate::http_client::HttpClient).
+#[cfg(feature = "http-client")]
+#[derive(Debug, thiserror::Error)]
+pub enum HttpClientError {
+    /// The uri of the request is invalid.
+    #[error("invalid uri: {0}")]
+    InvalidUri(http::uri::InvalidUri),
+
+    /// Failed to serialize the body of the request.
+    #[error("failed to serialize the body: {0}")]
+    Serialize(serde_json::Error),
+
+    /// Failed to send the request.
+    #[error("request: {0}")]
+    Request(hyper::Error),
+
+    /// The request timed out.
+    #[error("the request timed out")]
+    Timeout,
+}
+
+#[cfg(feature = "http-client")]
+impl ResponseError for HttpClientError {
+    fn status(&self) -> StatusCode {
+        match self {
+            HttpClientError::InvalidUri(_) | HttpClientError::Serialize(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            HttpClientError::Request(_) => StatusCode::BAD_GATEWAY,
+            HttpClientError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+/// A possible error value occurred when sending a job to a
+/// [`TaskQueue`](crate::tasks::TaskQueue).
+#[cfg(feature = "server")]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum TaskQueueError {
+    /// The queue is full.
+    #[error("the task queue is full")]
+    Full,
+
+    /// The task has stopped.
+    #[error("the task queue is closed")]
+    Closed,
+}
+
+#[cfg(feature = "server")]
+impl ResponseError for TaskQueueError {
+    fn status(&self) -> StatusCode {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// A possible error value when parsing a cron expression.
+#[cfg(feature = "cron")]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+#[error("invalid cron expression: `{0}`")]
+pub struct ParseCronError(pub String);
+
+#[cfg(feature = "cron")]
+impl ResponseError for ParseCronError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value when loading a [`Config`](crate::config::Config).
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// Failed to read the source of the config.
+    #[error("failed to read the config: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Failed to parse the config.
+    #[error("failed to parse the config: {0}")]
+    Parse(String),
+}
+
+impl ResponseError for ConfigError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value occurred in the
+/// [`ApiKeyAuth`](crate::middleware::ApiKeyAuth) middleware.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum ApiKeyError {
+    /// The request has no API key.
+    #[error("missing api key")]
+    Missing,
+
+    /// The API key is not valid.
+    #[error("invalid api key")]
+    Invalid,
+}
+
+impl ResponseError for ApiKeyError {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+/// A possible error value occurred in the
+/// [`Transactional`](crate::middleware::Transactional) middleware or the
+/// [`Tx`](crate::web::Tx) extractor.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum TransactionError {
+    /// The `Transactional` middleware is not applied to the endpoint, or has
+    /// a transaction of another type.
+    #[error("the `Transactional` middleware is not applied")]
+    Missing,
+
+    /// The transaction is already extracted, or is still used after the
+    /// endpoint returned.
+    #[error("the transaction is in use")]
+    InUse,
+}
+
+impl ResponseError for TransactionError {
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// A possible error value occurred in the `IdempotencyKey` middleware.
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum IdempotencyKeyError {
+    /// The `Idempotency-Key` header is required.
+    #[error("the `Idempotency-Key` header is required")]
+    Required,
+
+    /// The `Idempotency-Key` header is not a valid key.
+    #[error("invalid idempotency key")]
+    Invalid,
+
+    /// A request with the same key is still in progress.
+    #[error("a request with the same idempotency key is in progress")]
+    InProgress,
+
+    /// The key was used by a request with another body.
+    #[error("the idempotency key was used by a request with another body")]
+    BodyMismatch,
+}
+
+impl ResponseError for IdempotencyKeyError {
+    fn status(&self) -> StatusCode {
+        match self {
+            IdempotencyKeyError::Required | IdempotencyKeyError::Invalid => StatusCode::BAD_REQUEST,
+            IdempotencyKeyError::InProgress => StatusCode::CONFLICT,
+            IdempotencyKeyError::BodyMismatch => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+/// A possible error value occurred when the monthly cap of a client is reached
+/// in the [`UsageMeter`](crate::middleware::UsageMeter) middleware.
+///
+/// The response is a problem details object, with the `Retry-After` header.
+#[cfg(feature = "usage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+#[derive(Debug, Copy, Clone, thiserror::Error, Eq, PartialEq)]
+#[error("the monthly usage cap is reached")]
+pub struct UsageCapError {
+    /// The usage of the client in the current month.
+    pub usage: crate::middleware::Usage,
+    /// The monthly cap of the client.
+    pub cap: crate::middleware::UsageCap,
+    /// The time until the next month.
+    pub reset_after: std::time::Duration,
+}
+
+#[cfg(feature = "usage")]
+impl ResponseError for UsageCapError {
+    fn status(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn as_response(&self) -> Response {
+        let retry_after = self.reset_after.as_secs();
+        let mut resp = ProblemDetails::new(StatusCode::TOO_MANY_REQUESTS)
+            .detail(self.to_string())
+            .extension("usage", self.usage)
+            .extension("cap", self.cap)
+            .as_response();
+        resp.headers_mut()
+            .insert(http::header::RETRY_AFTER, retry_after.into());
+        resp
+    }
+}
+
+/// A possible error value occurred in the
+/// [`TusEndpoint`](crate::endpoint::TusEndpoint).
+#[cfg(feature = "tus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tus")))]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum TusError {
+    /// The `Tus-Resumable` header is missing, or is not a supported version.
+    #[error("unsupported tus version")]
+    UnsupportedVersion,
+
+    /// The `Upload-Length` header is missing or invalid.
+    #[error("invalid `Upload-Length` header")]
+    InvalidLength,
+
+    /// The `Upload-Offset` header is missing or invalid.
+    #[error("invalid `Upload-Offset` header")]
+    InvalidOffset,
+
+    /// The `Upload-Metadata` header is invalid.
+    #[error("invalid `Upload-Metadata` header")]
+    InvalidMetadata,
+
+    /// The content type of a `PATCH` request is not
+    /// `application/offset+octet-stream`.
+    #[error("the content type must be `application/offset+octet-stream`")]
+    UnsupportedContentType,
+
+    /// The `Upload-Offset` header does not match the offset of the upload.
+    #[error("the offset of the upload is {0}")]
+    OffsetMismatch(u64),
+
+    /// The upload exceeds its length or the maximum size.
+    #[error("the upload is too large")]
+    TooLarge,
+
+    /// Another request is appending to the upload.
+    #[error("the upload is locked")]
+    Locked,
+
+    /// The upload does not exist.
+    #[error("upload not found")]
+    NotFound,
+
+    /// The upload has expired.
+    #[error("the upload has expired")]
+    Expired,
+
+    /// The method is not supported.
+    #[error("method not allowed")]
+    MethodNotAllowed,
+}
+
+#[cfg(feature = "tus")]
+impl ResponseError for TusError {
+    fn status(&self) -> StatusCode {
+        match self {
+            TusError::UnsupportedVersion => StatusCode::PRECONDITION_FAILED,
+            TusError::InvalidLength | TusError::InvalidOffset | TusError::InvalidMetadata => {
+                StatusCode::BAD_REQUEST
+            }
+            TusError::UnsupportedContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            TusError::OffsetMismatch(_) => StatusCode::CONFLICT,
+            TusError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            TusError::Locked => StatusCode::LOCKED,
+            TusError::NotFound => StatusCode::NOT_FOUND,
+            TusError::Expired => StatusCode::GONE,
+            TusError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+        }
+    }
+}
+
+/// A possible error value occurred in a [`Storage`](crate::storage::Storage).
+#[cfg(feature = "storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage")))]
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// The key is not a valid key, such as a key containing `..`.
+    #[error("invalid key: `{0}`")]
+    InvalidKey(String),
+
+    /// The storage cannot generate presigned URLs.
+    #[error("presigned URLs are not supported")]
+    PresignUnsupported,
+
+    /// The presigned URL is invalid or expired.
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    /// Io error.
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Failed to send the request to the storage service.
+    #[error("http: {0}")]
+    Http(#[from] hyper::Error),
+
+    /// The storage service replied with an unexpected status.
+    #[error("unexpected status: {0}")]
+    UnexpectedStatus(StatusCode),
+}
+
+#[cfg(feature = "storage")]
+impl ResponseError for StorageError {
+    fn status(&self) -> StatusCode {
+        match self {
+            StorageError::InvalidKey(_) => StatusCode::BAD_REQUEST,
+            StorageError::InvalidSignature => StatusCode::FORBIDDEN,
+            StorageError::PresignUnsupported | StorageError::Io(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            StorageError::Http(_) | StorageError::UnexpectedStatus(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+/// A possible error value occurred in the
+/// [`ImageProxy`](crate::endpoint::ImageProxy).
+#[cfg(feature = "image-proxy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "image-proxy")))]
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+pub enum ImageProxyError {
+    /// The query parameters are invalid.
+    #[error("invalid image options: {0}")]
+    InvalidOptions(String),
+
+    /// The signature of the URL is missing or invalid.
+    #[error("invalid signature")]
+    InvalidSignature,
+
+    /// The image does not exist.
+    #[error("image not found")]
+    NotFound,
+
+    /// The source image cannot be decoded.
+    #[error("failed to decode the image: {0}")]
+    Decode(String),
+
+    /// The image cannot be encoded in the requested format.
+    #[error("failed to encode the image: {0}")]
+    Encode(String),
+
+    /// The method is not supported.
+    #[error("method not allowed")]
+    MethodNotAllowed,
+}
+
+#[cfg(feature = "image-proxy")]
+impl ResponseError for ImageProxyError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ImageProxyError::InvalidOptions(_) => StatusCode::BAD_REQUEST,
+            ImageProxyError::InvalidSignature => StatusCode::FORBIDDEN,
+            ImageProxyError::NotFound => StatusCode::NOT_FOUND,
+            ImageProxyError::Decode(_) | ImageProxyError::Encode(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            ImageProxyError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+        }
+    }
+}
+
+/// An error in the
+/// [Problem Details](https://www.rfc-editor.org/rfc/rfc7807) format, which is
+/// converted to an `application/problem+json` response.
+///
+/// The `title` defaults to the reason phrase of the status code, as is
+/// recommended when the `type` is not specified.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     error::ProblemDetails, handler, http::StatusCode, test::TestClient, Result,
+/// };
+///
+/// #[handler]
+/// fn transfer() -> Result<()> {
+///     Err(ProblemDetails::new(StatusCode::FORBIDDEN)
+///         .ty("https://example.com/probs/out-of-credit")
+///         .title("You do not have enough credit.")
+///         .detail("Your current balance is 30, but that costs 50.")
+///         .instance("/account/12345/msgs/abc")
+///         .extension("balance", 30)
+///         .into())
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(transfer).get("/").send().await;
+/// resp.assert_status(StatusCode::FORBIDDEN);
+/// resp.assert_content_type("application/problem+json");
+/// resp.assert_json(serde_json::json!({
+///     "type": "https://example.com/probs/out-of-credit",
+///     "title": "You do not have enough credit.",
+///     "status": 403,
+///     "detail": "Your current balance is 30, but that costs 50.",
+///     "instance": "/account/12345/msgs/abc",
+///     "balance": 30,
+/// }))
+/// .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProblemDetails {
+    /// A URI reference that identifies the problem type.
+    pub ty: Option<String>,
+    /// A short, human-readable summary of the problem type.
+    pub title: Option<String>,
+    /// The HTTP status code.
+    pub status: StatusCode,
+    /// A human-readable explanation specific to this occurrence of the
+    /// problem.
+    pub detail: Option<String>,
+    /// A URI reference that identifies the specific occurrence of the
+    /// problem.
+    pub instance: Option<String>,
+    /// Additional members of the problem details object.
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ProblemDetails {
+    /// Create a `ProblemDetails` with the specified status code.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            ty: None,
+            title: status.canonical_reason().map(ToString::to_string),
+            status,
+            detail: None,
+            instance: None,
+            extensions: Default::default(),
+        }
+    }
+
+    /// Create a `ProblemDetails` describing an [`Error`].
+    ///
+    /// If the error was created from a `ProblemDetails`, it is returned
+    /// unchanged, otherwise the message of the error is used as the `detail`.
+    pub fn from_error(err: &Error) -> Self {
+        if let Some(problem) = err.downcast_ref::<ProblemDetails>() {
+            return problem.clone();
+        }
+
+        let status = err.status();
+        let mut problem = Self::new(status);
+        if !err.is_from_response() {
+            let msg = err.to_string();
+            if msg != status.to_string() {
+                problem.detail = Some(msg);
+            }
+        }
+        problem
+    }
+
+    /// Sets the URI reference that identifies the problem type.
+    #[must_use]
+    pub fn ty(mut self, ty: impl Into<String>) -> Self {
+        self.ty = Some(ty.into());
+        self
+    }
+
+    /// Sets the summary of the problem type.
+    #[must_use]
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the explanation of this occurrence of the problem.
+    #[must_use]
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the URI reference that identifies this occurrence of the problem.
+    #[must_use]
+    pub fn instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Adds an extension member.
+    ///
+    /// The value is ignored if it cannot be serialized to JSON.
+    #[must_use]
+    pub fn extension(mut self, name: impl Into<String>, value: impl serde::Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.extensions.insert(name.into(), value);
+        }
+        self
+    }
+
+    /// Returns the problem details object.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut obj = serde_json::Map::new();
+        if let Some(ty) = &self.ty {
+            obj.insert("type".to_string(), ty.clone().into());
+        }
+        if let Some(title) = &self.title {
+            obj.insert("title".to_string(), title.clone().into());
+        }
+        obj.insert("status".to_string(), self.status.as_u16().into());
+        if let Some(detail) = &self.detail {
+            obj.insert("detail".to_string(), detail.clone().into());
+        }
+        if let Some(instance) = &self.instance {
+            obj.insert("instance".to_string(), instance.clone().into());
+        }
+        for (name, value) in &self.extensions {
+            obj.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        obj.into()
+    }
+}
+
+impl Display for ProblemDetails {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.title, &self.detail) {
+            (Some(title), Some(detail)) => write!(f, "{title}: {detail}"),
+            (Some(msg), None) | (None, Some(msg)) => write!(f, "{msg}"),
+            (None, None) => write!(f, "{}", self.status),
+        }
+    }
+}
+
+impl StdError for ProblemDetails {}
+
+impl ResponseError for ProblemDetails {
+    fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    fn as_response(&self) -> Response {
+        Response::builder()
+            .status(self.status)
+            .content_type("application/problem+json")
+            .body(self.to_json().to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::{Error as IoError, ErrorKind};
@@ -1217,4 +2221,37 @@ mod tests {
             "my error message"
         );
     }
+
+    #[test]
+    fn problem_details() {
+        let problem = ProblemDetails::new(StatusCode::BAD_REQUEST)
+            .detail("missing field")
+            .extension("field", "name")
+            .extension("status", 200);
+        assert_eq!(problem.to_string(), "Bad Request: missing field");
+        assert_eq!(
+            problem.to_json(),
+            serde_json::json!({
+                "title": "Bad Request",
+                "status": 400,
+                "detail": "missing field",
+                "field": "name",
+            })
+        );
+
+        let err: Error = problem.clone().into();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(ProblemDetails::from_error(&err), problem);
+
+        let err = Error::from_string("boom", StatusCode::CONFLICT);
+        assert_eq!(
+            ProblemDetails::from_error(&err),
+            ProblemDetails::new(StatusCode::CONFLICT).detail("boom")
+        );
+        let err = Error::from_status(StatusCode::NOT_FOUND);
+        assert_eq!(
+            ProblemDetails::from_error(&err),
+            ProblemDetails::new(StatusCode::NOT_FOUND)
+        );
+    }
 }
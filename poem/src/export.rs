@@ -0,0 +1,344 @@
+//! Static site export.
+//!
+//! [`Export`] requests the pages of an application and writes the responses
+//! to a directory, which can be served by any static hosting. The pages go
+//! through all the middlewares of the application, so they are rendered
+//! exactly as they are served.
+//!
+//! # Example
+//!
+//! ```no_compile
+//! use poem::{export::Export, get, tera::TeraTemplating, EndpointExt, Route};
+//!
+//! let route = Route::new()
+//!     .at_named("home", "/", get(index))
+//!     .at_named("post", "/posts/:slug", get(post))
+//!     .at("/feed.xml", get(feed));
+//! let export = Export::new()
+//!     .routes(&route)
+//!     .params("post", [("slug", "hello")])
+//!     .params("post", [("slug", "world")])
+//!     .path("/feed.xml");
+//!
+//! let app = route.with(TeraTemplating::from_directory("templates"));
+//! let report = export.run(&app, "dist").await?;
+//! for page in &report.pages {
+//!     println!("{} {}", page.status, page.path);
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    path::{Path, PathBuf},
+};
+
+use percent_encoding::percent_decode_str;
+
+use crate::{
+    http::{header, Method, StatusCode},
+    web::NamedRedirect,
+    Endpoint, Request, Route,
+};
+
+/// A page requested by [`Export::run`].
+#[derive(Debug, Clone)]
+pub struct ExportedPage {
+    /// The path of the page.
+    pub path: String,
+    /// The status of the response.
+    pub status: StatusCode,
+    /// The file the page is written to, or `None` if the response is not
+    /// successful.
+    pub file: Option<PathBuf>,
+}
+
+/// The result of [`Export::run`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    /// The pages requested, in the order they were added.
+    pub pages: Vec<ExportedPage>,
+    /// The names of the routes which were not exported, because they have
+    /// parameters and none were added with [`Export::params`].
+    pub unresolved: Vec<String>,
+}
+
+/// Exports the pages of an application to a directory.
+///
+/// The paths of the pages are the named routes added with
+/// [`routes`](Export::routes), whose parameters are added with
+/// [`params`](Export::params), and the paths added with
+/// [`path`](Export::path).
+///
+/// The successful responses to the `GET` requests are written to the file of
+/// the same path, the HTML pages being written to an `index.html` file in
+/// the directory of the path, unless it has an extension. For instance,
+/// `/posts/hello` is written to `posts/hello/index.html`, and `/feed.xml` to
+/// `feed.xml`.
+///
+/// See the [module documentation](crate::export) for an example.
+#[derive(Debug, Clone, Default)]
+pub struct Export {
+    routes: Vec<(String, String)>,
+    params: HashMap<String, Vec<Vec<(String, String)>>>,
+    paths: Vec<String>,
+    host: Option<String>,
+}
+
+impl Export {
+    /// Create an empty `Export`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the named routes of `route`.
+    ///
+    /// The routes of the nested routing objects must be added with
+    /// [`path`](Export::path).
+    #[must_use]
+    pub fn routes(mut self, route: &Route) -> Self {
+        let mut routes = route
+            .named_paths()
+            .map(|(name, path)| (name.to_string(), path.to_string()))
+            .collect::<Vec<_>>();
+        // the names are not ordered in the routing table
+        routes.sort_by(|(_, a), (_, b)| a.cmp(b));
+        self.routes.extend(routes);
+        self
+    }
+
+    /// Adds a set of parameters of the named route `name`, which is exported
+    /// once for each set.
+    #[must_use]
+    pub fn params<K, V>(
+        mut self,
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = (K, V)>,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.params.entry(name.into()).or_default().push(
+            params
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Adds a path to export, such as `/feed.xml`.
+    #[must_use]
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Sets the `Host` header of the requests, for the pages generating
+    /// absolute URLs.
+    #[must_use]
+    pub fn host(self, host: impl Into<String>) -> Self {
+        Self {
+            host: Some(host.into()),
+            ..self
+        }
+    }
+
+    /// Returns the paths to export, and the names of the routes which cannot
+    /// be resolved.
+    fn resolve(&self) -> (Vec<String>, Vec<String>) {
+        let mut paths = Vec::new();
+        let mut unresolved = Vec::new();
+
+        for (name, pattern) in &self.routes {
+            let no_params = NamedRedirect {
+                name: name.clone(),
+                params: Vec::new(),
+            };
+            match (self.params.get(name), no_params.resolve(pattern)) {
+                (None, Some(path)) => paths.push(path),
+                (None, None) => unresolved.push(name.clone()),
+                (Some(sets), _) => {
+                    for params in sets {
+                        let named = NamedRedirect {
+                            name: name.clone(),
+                            params: params.clone(),
+                        };
+                        match named.resolve(pattern) {
+                            Some(path) => paths.push(path),
+                            None => {
+                                tracing::warn!(name = %name, "invalid parameters for named route")
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        paths.extend(self.paths.iter().cloned());
+        (paths, unresolved)
+    }
+
+    /// Requests the pages from `ep` and writes them to `dir`.
+    ///
+    /// The unsuccessful responses are reported without failing the export,
+    /// which only fails on the I/O errors.
+    pub async fn run(&self, ep: impl Endpoint, dir: impl AsRef<Path>) -> IoResult<ExportReport> {
+        let dir = dir.as_ref();
+        let (paths, unresolved) = self.resolve();
+        let mut report = ExportReport {
+            pages: Vec::new(),
+            unresolved,
+        };
+
+        for path in paths {
+            let uri = path
+                .parse()
+                .map_err(|err| IoError::new(ErrorKind::InvalidInput, format!("{path}: {err}")))?;
+            let mut req = Request::builder().method(Method::GET).uri(uri);
+            if let Some(host) = &self.host {
+                req = req.header(header::HOST, host);
+            }
+            let resp = ep.get_response(req.finish()).await;
+            let status = resp.status();
+            if !status.is_success() {
+                tracing::warn!(path = %path, status = %status, "page not exported");
+                report.pages.push(ExportedPage {
+                    path,
+                    status,
+                    file: None,
+                });
+                continue;
+            }
+
+            let is_html = resp
+                .content_type()
+                .map_or(false, |content_type| content_type.starts_with("text/html"));
+            let file = dir.join(file_path(&path, is_html)?);
+            let data = resp
+                .into_body()
+                .into_bytes()
+                .await
+                .map_err(|err| IoError::new(ErrorKind::Other, err))?;
+            if let Some(parent) = file.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&file, data).await?;
+            report.pages.push(ExportedPage {
+                path,
+                status,
+                file: Some(file),
+            });
+        }
+
+        Ok(report)
+    }
+}
+
+/// Returns the file of the page at `path`, relative to the export directory.
+fn file_path(path: &str, is_html: bool) -> IoResult<PathBuf> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let mut file = PathBuf::new();
+    let mut has_extension = false;
+
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let segment = percent_decode_str(segment).decode_utf8_lossy();
+        if segment == "." || segment == ".." || segment.contains(['/', '\\']) {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid path: {path}"),
+            ));
+        }
+        has_extension = segment.contains('.');
+        file.push(&*segment);
+    }
+
+    if file.as_os_str().is_empty() || (is_html && !has_extension) {
+        file.push("index.html");
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        get, handler,
+        web::{Html, Path as PathParam},
+    };
+
+    #[handler(internal)]
+    fn index() -> Html<&'static str> {
+        Html("home")
+    }
+
+    #[handler(internal)]
+    fn post(PathParam(slug): PathParam<String>) -> Html<String> {
+        Html(format!("post {slug}"))
+    }
+
+    #[handler(internal)]
+    fn feed() -> &'static str {
+        "feed"
+    }
+
+    #[test]
+    fn file_paths() {
+        assert_eq!(file_path("/", true).unwrap(), PathBuf::from("index.html"));
+        assert_eq!(
+            file_path("/posts/a%20b?page=1", true).unwrap(),
+            PathBuf::from("posts/a b/index.html")
+        );
+        assert_eq!(
+            file_path("/feed.xml", false).unwrap(),
+            PathBuf::from("feed.xml")
+        );
+        assert_eq!(file_path("/raw", false).unwrap(), PathBuf::from("raw"));
+        assert!(file_path("/a/../b", true).is_err());
+        assert!(file_path("/a/%2e%2e/b", true).is_err());
+    }
+
+    #[tokio::test]
+    async fn export() {
+        let route = Route::new()
+            .at_named("home", "/", get(index))
+            .at_named("post", "/posts/:slug", get(post))
+            .at_named("draft", "/drafts/:id", get(post))
+            .at("/feed.xml", get(feed));
+        let export = Export::new()
+            .routes(&route)
+            .params("post", [("slug", "hello")])
+            .params("post", [("slug", "hello world")])
+            .path("/feed.xml")
+            .path("/missing");
+
+        let dir = std::env::temp_dir().join(format!("poem-export-{}", std::process::id()));
+        let report = export.run(route, &dir).await.unwrap();
+
+        assert_eq!(
+            report
+                .pages
+                .iter()
+                .map(|page| (page.path.as_str(), page.status))
+                .collect::<Vec<_>>(),
+            [
+                ("/", StatusCode::OK),
+                ("/posts/hello", StatusCode::OK),
+                ("/posts/hello%20world", StatusCode::OK),
+                ("/feed.xml", StatusCode::OK),
+                ("/missing", StatusCode::NOT_FOUND),
+            ]
+        );
+        assert_eq!(report.unresolved, ["draft"]);
+        assert!(report.pages[4].file.is_none());
+
+        let read = |path: &str| std::fs::read_to_string(dir.join(path)).unwrap();
+        assert_eq!(read("index.html"), "home");
+        assert_eq!(read("posts/hello/index.html"), "post hello");
+        assert_eq!(read("posts/hello world/index.html"), "post hello world");
+        assert_eq!(read("feed.xml"), "feed");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,657 @@
+//! The FastCGI protocol, served by [`Server::fastcgi`](crate::Server::fastcgi).
+//!
+//! Reference: <https://fastcgi-archives.github.io/FastCGI_Specification.html>
+
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind},
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use http::{
+    header::HeaderName, uri::Scheme, HeaderMap, HeaderValue, Method, StatusCode, Uri, Version,
+};
+use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Result as IoResult};
+
+use crate::{
+    listener::connection_info::ConnectionInfo,
+    web::{LocalAddr, RemoteAddr},
+    Body, Endpoint, IntoResponse, Request, Response, ShutdownSignal,
+};
+
+const VERSION_1: u8 = 1;
+
+const BEGIN_REQUEST: u8 = 1;
+const ABORT_REQUEST: u8 = 2;
+const END_REQUEST: u8 = 3;
+const PARAMS: u8 = 4;
+const STDIN: u8 = 5;
+const STDOUT: u8 = 6;
+const GET_VALUES: u8 = 9;
+const GET_VALUES_RESULT: u8 = 10;
+const UNKNOWN_TYPE: u8 = 11;
+
+const RESPONDER: u16 = 1;
+const KEEP_CONN: u8 = 1;
+
+const REQUEST_COMPLETE: u8 = 0;
+const CANT_MPX_CONN: u8 = 1;
+const UNKNOWN_ROLE: u8 = 3;
+
+const MAX_CONTENT_LENGTH: usize = 0xffff;
+
+struct Record {
+    ty: u8,
+    request_id: u16,
+    content: Vec<u8>,
+}
+
+fn invalid_data(msg: &'static str) -> IoError {
+    IoError::new(ErrorKind::InvalidData, msg)
+}
+
+async fn read_record(reader: &mut (impl AsyncRead + Unpin)) -> IoResult<Option<Record>> {
+    let mut header = [0; 8];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    if header[0] != VERSION_1 {
+        return Err(invalid_data("unsupported FastCGI version"));
+    }
+
+    let request_id = u16::from_be_bytes([header[2], header[3]]);
+    let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let mut content = vec![0; len + header[6] as usize];
+    reader.read_exact(&mut content).await?;
+    content.truncate(len);
+    Ok(Some(Record {
+        ty: header[1],
+        request_id,
+        content,
+    }))
+}
+
+async fn write_record(
+    writer: &mut (impl AsyncWrite + Unpin),
+    ty: u8,
+    request_id: u16,
+    content: &[u8],
+) -> IoResult<()> {
+    let mut data = Vec::with_capacity(8 + content.len());
+    data.extend_from_slice(&[VERSION_1, ty]);
+    data.extend_from_slice(&request_id.to_be_bytes());
+    data.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(content);
+    writer.write_all(&data).await
+}
+
+/// Writes `data` in as many `STDOUT` records as required.
+async fn write_stdout(
+    writer: &mut (impl AsyncWrite + Unpin),
+    request_id: u16,
+    data: &[u8],
+) -> IoResult<()> {
+    for chunk in data.chunks(MAX_CONTENT_LENGTH) {
+        write_record(writer, STDOUT, request_id, chunk).await?;
+    }
+    Ok(())
+}
+
+async fn write_end_request(
+    writer: &mut (impl AsyncWrite + Unpin),
+    request_id: u16,
+    protocol_status: u8,
+) -> IoResult<()> {
+    write_record(
+        writer,
+        END_REQUEST,
+        request_id,
+        &[0, 0, 0, 0, protocol_status, 0, 0, 0],
+    )
+    .await
+}
+
+fn read_length(data: &mut &[u8]) -> Option<usize> {
+    let first = *data.first()?;
+    if first & 0x80 == 0 {
+        *data = &data[1..];
+        return Some(first as usize);
+    }
+    if data.len() < 4 {
+        return None;
+    }
+    let len = u32::from_be_bytes([first & 0x7f, data[1], data[2], data[3]]);
+    *data = &data[4..];
+    Some(len as usize)
+}
+
+fn parse_params(mut data: &[u8]) -> IoResult<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    while !data.is_empty() {
+        let name_len = read_length(&mut data).ok_or_else(|| invalid_data("invalid params"))?;
+        let value_len = read_length(&mut data).ok_or_else(|| invalid_data("invalid params"))?;
+        if data.len() < name_len + value_len {
+            return Err(invalid_data("invalid params"));
+        }
+        let name = String::from_utf8_lossy(&data[..name_len]).into_owned();
+        let value = String::from_utf8_lossy(&data[name_len..name_len + value_len]).into_owned();
+        params.insert(name, value);
+        data = &data[name_len + value_len..];
+    }
+    Ok(params)
+}
+
+fn encode_param(data: &mut Vec<u8>, name: &str, value: &str) {
+    for len in [name.len(), value.len()] {
+        if len < 0x80 {
+            data.push(len as u8);
+        } else {
+            data.extend_from_slice(&(len as u32 | 0x8000_0000).to_be_bytes());
+        }
+    }
+    data.extend_from_slice(name.as_bytes());
+    data.extend_from_slice(value.as_bytes());
+}
+
+/// Answers the management records, and rejects the requests received while
+/// another one is in progress since the connections are not multiplexed.
+async fn handle_other_record(
+    writer: &mut (impl AsyncWrite + Unpin),
+    record: Record,
+) -> IoResult<()> {
+    match (record.request_id, record.ty) {
+        (0, GET_VALUES) => {
+            let mut values = Vec::new();
+            for (name, _) in parse_params(&record.content)? {
+                match name.as_str() {
+                    "FCGI_MAX_REQS" => encode_param(&mut values, &name, "1"),
+                    "FCGI_MPXS_CONNS" => encode_param(&mut values, &name, "0"),
+                    _ => {}
+                }
+            }
+            write_record(writer, GET_VALUES_RESULT, 0, &values).await
+        }
+        (0, ty) => write_record(writer, UNKNOWN_TYPE, 0, &[ty, 0, 0, 0, 0, 0, 0, 0]).await,
+        (request_id, BEGIN_REQUEST) => write_end_request(writer, request_id, CANT_MPX_CONN).await,
+        // the records of an aborted or rejected request
+        _ => Ok(()),
+    }
+}
+
+/// Reads the records until the beginning of a request, and returns its id and
+/// whether the connection must be kept open after the response.
+async fn read_begin_request(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> IoResult<Option<(u16, bool)>> {
+    loop {
+        let record = match read_record(reader).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        if record.ty != BEGIN_REQUEST || record.request_id == 0 {
+            handle_other_record(writer, record).await?;
+            continue;
+        }
+        if record.content.len() < 8 {
+            return Err(invalid_data("invalid begin request record"));
+        }
+
+        let role = u16::from_be_bytes([record.content[0], record.content[1]]);
+        if role != RESPONDER {
+            write_end_request(writer, record.request_id, UNKNOWN_ROLE).await?;
+            continue;
+        }
+        return Ok(Some((
+            record.request_id,
+            record.content[2] & KEEP_CONN != 0,
+        )));
+    }
+}
+
+fn parse_addr(ip: Option<&String>, port: Option<&String>) -> Option<SocketAddr> {
+    let ip = ip?.parse::<IpAddr>().ok()?;
+    let port = port.and_then(|port| port.parse().ok()).unwrap_or_default();
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Creates a request from the CGI variables of the `PARAMS` records.
+///
+/// The address of the client sent by the web server takes precedence over
+/// the address of the connection, or the one of its PROXY protocol header.
+fn build_request(
+    params: &HashMap<String, String>,
+    local_addr: &LocalAddr,
+    remote_addr: &RemoteAddr,
+    scheme: &Scheme,
+    conn_info: &ConnectionInfo,
+    body: Body,
+) -> Option<Request> {
+    let method = match params.get("REQUEST_METHOD") {
+        Some(method) => Method::from_bytes(method.as_bytes()).ok()?,
+        None => Method::GET,
+    };
+    let uri = match params.get("REQUEST_URI") {
+        Some(uri) => uri.clone(),
+        None => {
+            let mut uri = params.get("SCRIPT_NAME").cloned().unwrap_or_default();
+            uri.push_str(
+                params
+                    .get("PATH_INFO")
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+            );
+            if uri.is_empty() {
+                uri.push('/');
+            }
+            match params.get("QUERY_STRING") {
+                Some(query) if !query.is_empty() => {
+                    uri.push('?');
+                    uri.push_str(query);
+                }
+                _ => {}
+            }
+            uri
+        }
+    };
+    let version = match params.get("SERVER_PROTOCOL").map(String::as_str) {
+        Some("HTTP/1.0") => Version::HTTP_10,
+        Some("HTTP/2" | "HTTP/2.0") => Version::HTTP_2,
+        _ => Version::HTTP_11,
+    };
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in params {
+        let name = match name.as_str() {
+            "CONTENT_TYPE" | "CONTENT_LENGTH" if !value.is_empty() => name.as_str(),
+            // duplicates of the variables above
+            "HTTP_CONTENT_TYPE" | "HTTP_CONTENT_LENGTH" => continue,
+            _ => match name.strip_prefix("HTTP_") {
+                Some(name) => name,
+                None => continue,
+            },
+        };
+        let name = HeaderName::from_bytes(name.replace('_', "-").to_ascii_lowercase().as_bytes());
+        if let (Ok(name), Ok(value)) = (name, HeaderValue::from_str(value)) {
+            headers.append(name, value);
+        }
+    }
+
+    let https = params
+        .get("HTTPS")
+        .map(|https| https.eq_ignore_ascii_case("on") || https == "1")
+        .unwrap_or_default()
+        || params.get("REQUEST_SCHEME").map(String::as_str) == Some("https");
+
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri.parse::<Uri>().ok()?)
+        .version(version)
+        .body(body);
+    *req.headers_mut() = headers;
+
+    let state = req.state_mut();
+    state.local_addr = parse_addr(params.get("SERVER_ADDR"), params.get("SERVER_PORT"))
+        .map(|addr| LocalAddr(addr.into()))
+        .unwrap_or_else(|| local_addr.clone());
+    state.remote_addr = remote_addr.clone();
+    state.scheme = if https {
+        Scheme::HTTPS
+    } else if params.contains_key("REQUEST_SCHEME") || params.contains_key("HTTPS") {
+        Scheme::HTTP
+    } else {
+        scheme.clone()
+    };
+    conn_info.apply(&mut req);
+    if let Some(addr) = parse_addr(params.get("REMOTE_ADDR"), params.get("REMOTE_PORT")) {
+        req.state_mut().remote_addr = RemoteAddr(addr.into());
+    }
+    Some(req)
+}
+
+/// Writes the response in the CGI format, in the `STDOUT` records.
+async fn write_response(
+    writer: &mut (impl AsyncWrite + Unpin),
+    request_id: u16,
+    resp: Response,
+) -> IoResult<()> {
+    let (parts, body) = resp.into_parts();
+    let mut head = format!(
+        "Status: {} {}\r\n",
+        parts.status.as_u16(),
+        parts.status.canonical_reason().unwrap_or_default()
+    )
+    .into_bytes();
+    for (name, value) in &parts.headers {
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    write_stdout(writer, request_id, &head).await?;
+
+    let mut stream = body.into_bytes_stream();
+    while let Some(data) = stream.next().await {
+        match data {
+            Ok(data) => write_stdout(writer, request_id, &data).await?,
+            Err(err) => {
+                tracing::debug!(error = %err, "failed to read the response body");
+                break;
+            }
+        }
+    }
+    write_record(writer, STDOUT, request_id, &[]).await?;
+    write_end_request(writer, request_id, REQUEST_COMPLETE).await?;
+    writer.flush().await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn serve_request(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+    request_id: u16,
+    local_addr: &LocalAddr,
+    remote_addr: &RemoteAddr,
+    scheme: &Scheme,
+    ep: &dyn Endpoint<Output = Response>,
+    signal: &ShutdownSignal,
+    conn_info: &Mutex<ConnectionInfo>,
+) -> IoResult<()> {
+    let mut params = Vec::new();
+    loop {
+        let record = read_record(reader)
+            .await?
+            .ok_or_else(|| IoError::from(ErrorKind::UnexpectedEof))?;
+        match (record.ty, record.request_id == request_id) {
+            (PARAMS, true) if record.content.is_empty() => break,
+            (PARAMS, true) => params.extend_from_slice(&record.content),
+            (ABORT_REQUEST, true) => {
+                return write_end_request(writer, request_id, REQUEST_COMPLETE).await
+            }
+            _ => handle_other_record(writer, record).await?,
+        }
+    }
+    let params = parse_params(&params)?;
+
+    // the body is streamed to the endpoint while the response is written, and
+    // the other records are answered afterwards
+    let (sender, body) = hyper::Body::channel();
+    let read_body = async move {
+        let mut other_records = Vec::new();
+        let mut sender = Some(sender);
+        loop {
+            let record = read_record(reader)
+                .await?
+                .ok_or_else(|| IoError::from(ErrorKind::UnexpectedEof))?;
+            match (record.ty, record.request_id == request_id) {
+                (STDIN, true) if record.content.is_empty() => break,
+                (STDIN, true) => {
+                    if let Some(tx) = &mut sender {
+                        if tx.send_data(Bytes::from(record.content)).await.is_err() {
+                            // the endpoint does not read the body
+                            sender = None;
+                        }
+                    }
+                }
+                (ABORT_REQUEST, true) => {
+                    if let Some(tx) = sender.take() {
+                        tx.abort();
+                    }
+                    break;
+                }
+                _ => other_records.push(record),
+            }
+        }
+        Ok::<_, IoError>(other_records)
+    };
+
+    let respond = async {
+        let req = build_request(
+            &params,
+            local_addr,
+            remote_addr,
+            scheme,
+            &conn_info.lock(),
+            body.into(),
+        );
+        let resp = match req {
+            Some(mut req) => {
+                req.extensions_mut().insert(signal.clone());
+                ep.get_response(req).await
+            }
+            None => StatusCode::BAD_REQUEST.into_response(),
+        };
+        write_response(&mut *writer, request_id, resp).await
+    };
+
+    let (other_records, res) = tokio::join!(read_body, respond);
+    res?;
+    for record in other_records? {
+        handle_other_record(writer, record).await?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn serve_connection(
+    socket: impl AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    local_addr: LocalAddr,
+    remote_addr: RemoteAddr,
+    scheme: Scheme,
+    ep: Arc<dyn Endpoint<Output = Response>>,
+    signal: ShutdownSignal,
+    conn_info: Arc<Mutex<ConnectionInfo>>,
+    max_requests: Option<usize>,
+) {
+    let (mut reader, mut writer) = tokio::io::split(socket);
+    let mut num_requests = 0;
+
+    loop {
+        let begin = tokio::select! {
+            res = read_begin_request(&mut reader, &mut writer) => res,
+            _ = signal.wait() => break,
+        };
+        let (request_id, keep_conn) = match begin {
+            Ok(Some(begin)) => begin,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::debug!(error = %err, "FastCGI connection error");
+                break;
+            }
+        };
+
+        let res = serve_request(
+            &mut reader,
+            &mut writer,
+            request_id,
+            &local_addr,
+            &remote_addr,
+            &scheme,
+            &*ep,
+            &signal,
+            &conn_info,
+        )
+        .await;
+        if let Err(err) = res {
+            tracing::debug!(error = %err, "FastCGI connection error");
+            break;
+        }
+
+        num_requests += 1;
+        let last_request = max_requests.map_or(false, |max| num_requests >= max);
+        if !keep_conn || last_request || signal.is_shutting_down() {
+            break;
+        }
+    }
+
+    let _ = writer.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::{
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        Server,
+    };
+
+    #[handler(internal)]
+    fn echo(req: &Request, body: String) -> String {
+        format!(
+            "{} {} {} {} {}",
+            req.method(),
+            req.uri(),
+            req.scheme(),
+            req.remote_addr(),
+            body
+        )
+    }
+
+    async fn connect_to(listener: impl Listener + 'static) -> TcpStream {
+        let acceptor = listener.into_acceptor().await.unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).fastcgi().run(echo));
+        TcpStream::connect(addr).await.unwrap()
+    }
+
+    async fn connect() -> TcpStream {
+        connect_to(TcpListener::bind("127.0.0.1:0")).await
+    }
+
+    async fn send_request(stream: &mut TcpStream, request_id: u16, params: &[(&str, &str)]) {
+        write_record(
+            stream,
+            BEGIN_REQUEST,
+            request_id,
+            &[0, 1, KEEP_CONN, 0, 0, 0, 0, 0],
+        )
+        .await
+        .unwrap();
+        let mut data = Vec::new();
+        for (name, value) in params {
+            encode_param(&mut data, name, value);
+        }
+        write_record(stream, PARAMS, request_id, &data)
+            .await
+            .unwrap();
+        write_record(stream, PARAMS, request_id, &[]).await.unwrap();
+    }
+
+    /// Reads the `STDOUT` records until the end of the request.
+    async fn read_response(stream: &mut TcpStream) -> (u8, String) {
+        let mut stdout = Vec::new();
+        loop {
+            let record = read_record(stream).await.unwrap().unwrap();
+            match record.ty {
+                STDOUT => stdout.extend(record.content),
+                END_REQUEST => {
+                    return (record.content[4], String::from_utf8(stdout).unwrap());
+                }
+                ty => panic!("unexpected record type {ty}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn fastcgi() {
+        let mut stream = connect().await;
+
+        send_request(
+            &mut stream,
+            1,
+            &[
+                ("REQUEST_METHOD", "POST"),
+                ("REQUEST_URI", "/a?b=1"),
+                ("REMOTE_ADDR", "10.0.0.1"),
+                ("REMOTE_PORT", "1234"),
+                ("HTTPS", "on"),
+                ("CONTENT_LENGTH", "5"),
+            ],
+        )
+        .await;
+        write_record(&mut stream, STDIN, 1, b"hel").await.unwrap();
+        write_record(&mut stream, STDIN, 1, b"lo").await.unwrap();
+        write_record(&mut stream, STDIN, 1, &[]).await.unwrap();
+        let (status, stdout) = read_response(&mut stream).await;
+        assert_eq!(status, REQUEST_COMPLETE);
+        assert!(stdout.starts_with("Status: 200 OK\r\n"));
+        assert!(stdout.ends_with("\r\n\r\nPOST /a?b=1 https socket://10.0.0.1:1234 hello"));
+
+        // the connection is kept open
+        send_request(
+            &mut stream,
+            2,
+            &[("SCRIPT_NAME", "/index"), ("QUERY_STRING", "x=2")],
+        )
+        .await;
+        write_record(&mut stream, STDIN, 2, &[]).await.unwrap();
+        let (_, stdout) = read_response(&mut stream).await;
+        assert!(stdout.contains("\r\n\r\nGET /index?x=2 http socket://127.0.0.1:"));
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol() {
+        let mut stream = connect_to(TcpListener::bind("127.0.0.1:0").proxy_protocol()).await;
+        stream
+            .write_all(b"PROXY TCP4 203.0.113.7 127.0.0.1 40000 9000\r\n")
+            .await
+            .unwrap();
+
+        send_request(&mut stream, 1, &[("REQUEST_URI", "/")]).await;
+        write_record(&mut stream, STDIN, 1, &[]).await.unwrap();
+        let (_, stdout) = read_response(&mut stream).await;
+        assert!(stdout.ends_with("\r\n\r\nGET / http socket://203.0.113.7:40000 "));
+
+        // the address sent by the web server takes precedence
+        send_request(
+            &mut stream,
+            2,
+            &[("REQUEST_URI", "/"), ("REMOTE_ADDR", "10.0.0.1")],
+        )
+        .await;
+        write_record(&mut stream, STDIN, 2, &[]).await.unwrap();
+        let (_, stdout) = read_response(&mut stream).await;
+        assert!(stdout.ends_with("\r\n\r\nGET / http socket://10.0.0.1:0 "));
+    }
+
+    #[tokio::test]
+    async fn management_records() {
+        let mut stream = connect().await;
+
+        let mut data = Vec::new();
+        encode_param(&mut data, "FCGI_MPXS_CONNS", "");
+        encode_param(&mut data, "FCGI_UNKNOWN", "");
+        write_record(&mut stream, GET_VALUES, 0, &data)
+            .await
+            .unwrap();
+        let record = read_record(&mut stream).await.unwrap().unwrap();
+        assert_eq!(record.ty, GET_VALUES_RESULT);
+        assert_eq!(
+            parse_params(&record.content).unwrap(),
+            HashMap::from([("FCGI_MPXS_CONNS".to_string(), "0".to_string())])
+        );
+
+        // a second request while the first one is in progress
+        send_request(&mut stream, 1, &[]).await;
+        write_record(&mut stream, BEGIN_REQUEST, 2, &[0, 1, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+        write_record(&mut stream, STDIN, 1, &[]).await.unwrap();
+        let (status, _) = read_response(&mut stream).await;
+        assert_eq!(status, REQUEST_COMPLETE);
+        let record = read_record(&mut stream).await.unwrap().unwrap();
+        assert_eq!((record.ty, record.request_id), (END_REQUEST, 2));
+        assert_eq!(record.content[4], CANT_MPX_CONN);
+    }
+}
@@ -0,0 +1,479 @@
+//! GraphQL integration with [`async-graphql`](https://crates.io/crates/async-graphql).
+//!
+//! - [`GraphQL`] executes the queries and the mutations sent by `GET` or
+//!   `POST` requests, including the batches and the file uploads of the
+//!   [GraphQL multipart request](https://github.com/jaydenseric/graphql-multipart-request-spec).
+//! - [`GraphQLSubscription`] executes the subscriptions over a WebSocket,
+//!   with the `graphql-ws` or the `graphql-transport-ws` protocol.
+//! - [`GraphiQL`] serves the GraphiQL IDE, whose page can be overridden by a
+//!   template of the [`TeraTemplating`](crate::tera::TeraTemplating)
+//!   middleware.
+//!
+//! # Example
+//!
+//! ```
+//! use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+//! use poem::{
+//!     get,
+//!     graphql::{GraphQL, GraphQLSubscription, GraphiQL},
+//!     test::TestClient,
+//!     Route,
+//! };
+//!
+//! struct Query;
+//!
+//! #[Object]
+//! impl Query {
+//!     async fn add(&self, a: i32, b: i32) -> i32 {
+//!         a + b
+//!     }
+//! }
+//!
+//! let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+//! let app = Route::new()
+//!     .at("/", get(GraphiQL::new("/graphql").subscription_endpoint("/ws")))
+//!     .at("/graphql", GraphQL::new(schema.clone()))
+//!     .at("/ws", get(GraphQLSubscription::new(schema)));
+//! let cli = TestClient::new(app);
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! cli.post("/graphql")
+//!     .body_json(&serde_json::json!({ "query": "{ add(a: 10, b: 20) }" }))
+//!     .send()
+//!     .await
+//!     .assert_json(serde_json::json!({ "data": { "add": 30 } }))
+//!     .await;
+//! # });
+//! ```
+
+use std::str::FromStr;
+
+use async_graphql::{
+    http::{WebSocketProtocols, WsMessage, ALL_WEBSOCKET_PROTOCOLS},
+    BatchRequest, BatchResponse, ObjectType, Schema, SubscriptionType,
+};
+use futures_util::{future, SinkExt, StreamExt};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use crate::{
+    error::{BadRequest, IntoResult},
+    http::{header, HeaderValue, Method, StatusCode},
+    tera::{Context, Tera},
+    web::{
+        websocket::{Message, WebSocket},
+        Html, Json,
+    },
+    Endpoint, FromRequest, IntoResponse, Request, RequestBody, Response, Result,
+};
+
+/// The name of the template overriding the page of the GraphiQL IDE.
+const GRAPHIQL_TEMPLATE: &str = "poem/graphiql.html";
+
+const GRAPHIQL_SOURCE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="robots" content="noindex">
+  <title>{{ title }}</title>
+  <link rel="stylesheet" href="https://unpkg.com/graphiql@2/graphiql.min.css">
+</head>
+<body style="margin: 0">
+  <div id="graphiql" style="height: 100vh"></div>
+  <script crossorigin src="https://unpkg.com/react@17/umd/react.production.min.js"></script>
+  <script crossorigin src="https://unpkg.com/react-dom@17/umd/react-dom.production.min.js"></script>
+  <script crossorigin src="https://unpkg.com/graphiql@2/graphiql.min.js"></script>
+  <script>
+    const wsUrl = (path) => {
+      const url = new URL(path, window.location.href);
+      url.protocol = url.protocol === "https:" ? "wss:" : "ws:";
+      return url.href;
+    };
+    const fetcher = GraphiQL.createFetcher({
+      url: {{ endpoint | json_encode() | safe }},
+      {% if subscription_endpoint -%}
+      subscriptionUrl: wsUrl({{ subscription_endpoint | json_encode() | safe }}),
+      {%- endif %}
+    });
+    ReactDOM.render(
+      React.createElement(GraphiQL, { fetcher }),
+      document.getElementById("graphiql"),
+    );
+  </script>
+</body>
+</html>
+"#;
+
+/// An extractor for a GraphQL batch request.
+///
+/// The request is read from the query string of the `GET` requests, and from
+/// the body of the other requests, either as JSON or as a multipart form
+/// with the uploaded files.
+///
+/// # Errors
+///
+/// - [`BadRequest`] if the request is not valid.
+pub struct GraphQLBatchRequest(pub BatchRequest);
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for GraphQLBatchRequest {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        if req.method() == Method::GET {
+            let req =
+                async_graphql::http::parse_query_string(req.uri().query().unwrap_or_default())
+                    .map_err(BadRequest)?;
+            return Ok(Self(BatchRequest::Single(req)));
+        }
+
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+        let body = body.take()?.into_async_read().compat();
+        Ok(Self(
+            async_graphql::http::receive_batch_body(content_type, body, Default::default())
+                .await
+                .map_err(BadRequest)?,
+        ))
+    }
+}
+
+/// A response of a GraphQL batch request.
+///
+/// The `Cache-Control` header is set from the cache control of the response,
+/// and the HTTP headers inserted by the resolvers are added.
+pub struct GraphQLBatchResponse(pub BatchResponse);
+
+impl IntoResponse for GraphQLBatchResponse {
+    fn into_response(self) -> Response {
+        let mut resp = Json(&self.0).into_response();
+        if self.0.is_ok() {
+            if let Some(cache_control) = self
+                .0
+                .cache_control()
+                .value()
+                .and_then(|value| HeaderValue::from_str(&value).ok())
+            {
+                resp.headers_mut()
+                    .insert(header::CACHE_CONTROL, cache_control);
+            }
+        }
+        resp.headers_mut().extend(self.0.http_headers_iter());
+        resp
+    }
+}
+
+/// An endpoint executing the GraphQL queries and mutations with a
+/// [`Schema`].
+///
+/// See the [module documentation](crate::graphql) for an example.
+pub struct GraphQL<Query, Mutation, Subscription> {
+    schema: Schema<Query, Mutation, Subscription>,
+}
+
+impl<Query, Mutation, Subscription> GraphQL<Query, Mutation, Subscription> {
+    /// Create a `GraphQL` endpoint executing the requests with `schema`.
+    pub fn new(schema: Schema<Query, Mutation, Subscription>) -> Self {
+        Self { schema }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Query, Mutation, Subscription> Endpoint for GraphQL<Query, Mutation, Subscription>
+where
+    Query: ObjectType + 'static,
+    Mutation: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
+{
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let (req, mut body) = req.split();
+        let GraphQLBatchRequest(batch) = GraphQLBatchRequest::from_request(&req, &mut body).await?;
+        Ok(GraphQLBatchResponse(self.schema.execute_batch(batch).await).into_response())
+    }
+}
+
+/// An endpoint executing the GraphQL subscriptions over a WebSocket.
+///
+/// The protocol is negotiated with the `Sec-WebSocket-Protocol` header,
+/// `graphql-ws` being used when the client does not request any.
+///
+/// See the [module documentation](crate::graphql) for an example.
+pub struct GraphQLSubscription<Query, Mutation, Subscription> {
+    schema: Schema<Query, Mutation, Subscription>,
+}
+
+impl<Query, Mutation, Subscription> GraphQLSubscription<Query, Mutation, Subscription> {
+    /// Create a `GraphQLSubscription` endpoint executing the subscriptions
+    /// with `schema`.
+    pub fn new(schema: Schema<Query, Mutation, Subscription>) -> Self {
+        Self { schema }
+    }
+}
+
+fn protocol(req: &Request) -> WebSocketProtocols {
+    req.headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|protocols| {
+            protocols
+                .split(',')
+                .find_map(|protocol| WebSocketProtocols::from_str(protocol.trim()).ok())
+        })
+        .unwrap_or(WebSocketProtocols::SubscriptionsTransportWS)
+}
+
+#[async_trait::async_trait]
+impl<Query, Mutation, Subscription> Endpoint for GraphQLSubscription<Query, Mutation, Subscription>
+where
+    Query: ObjectType + 'static,
+    Mutation: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
+{
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let (req, mut body) = req.split();
+        let websocket = WebSocket::from_request(&req, &mut body).await?;
+        let protocol = protocol(&req);
+        let schema = self.schema.clone();
+
+        Ok(websocket
+            .protocols(ALL_WEBSOCKET_PROTOCOLS)
+            .on_upgrade(move |socket| async move {
+                let (mut sink, stream) = socket.split();
+                let stream = stream
+                    .take_while(|msg| future::ready(msg.is_ok()))
+                    .filter_map(|msg| match msg {
+                        Ok(msg @ (Message::Text(_) | Message::Binary(_))) => {
+                            future::ready(Some(msg.into_bytes()))
+                        }
+                        _ => future::ready(None),
+                    });
+                let mut stream =
+                    async_graphql::http::WebSocket::new(schema, stream, protocol).map(|msg| {
+                        match msg {
+                            WsMessage::Text(text) => Message::text(text),
+                            WsMessage::Close(code, reason) => Message::close_with(code, reason),
+                        }
+                    });
+
+                while let Some(msg) = stream.next().await {
+                    if sink.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            })
+            .into_response())
+    }
+}
+
+/// An endpoint serving the [GraphiQL](https://github.com/graphql/graphiql)
+/// IDE.
+///
+/// The page can be overridden by a `poem/graphiql.html` template of the
+/// [`TeraTemplating`](crate::tera::TeraTemplating) middleware, which is
+/// rendered with the `title`, `endpoint` and `subscription_endpoint`
+/// variables.
+///
+/// See the [module documentation](crate::graphql) for an example.
+#[derive(Debug, Clone)]
+pub struct GraphiQL {
+    title: String,
+    endpoint: String,
+    subscription_endpoint: Option<String>,
+}
+
+impl GraphiQL {
+    /// Create a `GraphiQL` endpoint sending the requests to `endpoint`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            title: "GraphiQL".to_string(),
+            endpoint: endpoint.into(),
+            subscription_endpoint: None,
+        }
+    }
+
+    /// Sets the path of the WebSocket endpoint of the subscriptions.
+    #[must_use]
+    pub fn subscription_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            subscription_endpoint: Some(endpoint.into()),
+            ..self
+        }
+    }
+
+    /// Sets the title of the page.
+    ///
+    /// Default is `GraphiQL`.
+    #[must_use]
+    pub fn title(self, title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for GraphiQL {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+
+        let mut context = Context::new();
+        context.insert("title", &self.title);
+        context.insert("endpoint", &self.endpoint);
+        context.insert("subscription_endpoint", &self.subscription_endpoint);
+        let html: Html<String> = match req.extensions().get::<Tera>().filter(|tera| {
+            tera.get_template_names()
+                .any(|name| name == GRAPHIQL_TEMPLATE)
+        }) {
+            Some(tera) => crate::tera::render(tera, GRAPHIQL_TEMPLATE, &context),
+            None => Tera::one_off(GRAPHIQL_SOURCE, &context, true),
+        }
+        .into_result()?;
+        Ok(html.into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptySubscription, Object, Schema, SimpleObject};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{tera::TeraTemplating, test::TestClient, EndpointExt};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn add(&self, a: i32, b: i32) -> i32 {
+            a + b
+        }
+
+        #[graphql(cache_control(max_age = 60))]
+        async fn cached(&self) -> i32 {
+            1
+        }
+    }
+
+    #[derive(SimpleObject)]
+    struct Uploaded {
+        name: String,
+        size: usize,
+    }
+
+    struct Mutation;
+
+    #[Object]
+    impl Mutation {
+        async fn upload(
+            &self,
+            ctx: &async_graphql::Context<'_>,
+            file: async_graphql::Upload,
+        ) -> async_graphql::Result<Uploaded> {
+            let file = file.value(ctx)?;
+            Ok(Uploaded {
+                name: file.filename,
+                size: file.content.metadata()?.len() as usize,
+            })
+        }
+    }
+
+    fn schema() -> Schema<Query, Mutation, EmptySubscription> {
+        Schema::new(Query, Mutation, EmptySubscription)
+    }
+
+    #[tokio::test]
+    async fn queries() {
+        let cli = TestClient::new(GraphQL::new(schema()));
+
+        cli.post("/")
+            .body_json(&json!({ "query": "{ add(a: 1, b: 2) }" }))
+            .send()
+            .await
+            .assert_json(json!({ "data": { "add": 3 } }))
+            .await;
+
+        let resp = cli.get("/").query("query", &"{ cached }").send().await;
+        resp.assert_header(header::CACHE_CONTROL, "max-age=60");
+        resp.assert_json(json!({ "data": { "cached": 1 } })).await;
+
+        cli.post("/")
+            .body_json(&json!([
+                { "query": "{ add(a: 1, b: 2) }" },
+                { "query": "{ add(a: 3, b: 4) }" },
+            ]))
+            .send()
+            .await
+            .assert_json(json!([{ "data": { "add": 3 } }, { "data": { "add": 7 } }]))
+            .await;
+
+        cli.post("/")
+            .content_type("application/json")
+            .body("{")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn upload() {
+        let boundary = "poem-boundary";
+        let body = format!(
+            "--{boundary}\r\n\
+             Content-Disposition: form-data; name=\"operations\"\r\n\r\n\
+             {{\"query\": \"mutation($file: Upload!) {{ upload(file: $file) {{ name size }} }}\", \"variables\": {{\"file\": null}}}}\r\n\
+             --{boundary}\r\n\
+             Content-Disposition: form-data; name=\"map\"\r\n\r\n\
+             {{\"0\": [\"variables.file\"]}}\r\n\
+             --{boundary}\r\n\
+             Content-Disposition: form-data; name=\"0\"; filename=\"a.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             hello\r\n\
+             --{boundary}--\r\n"
+        );
+
+        TestClient::new(GraphQL::new(schema()))
+            .post("/")
+            .content_type(format!("multipart/form-data; boundary={boundary}"))
+            .body(body)
+            .send()
+            .await
+            .assert_json(json!({ "data": { "upload": { "name": "a.txt", "size": 5 } } }))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn graphiql() {
+        let ep = GraphiQL::new("/graphql")
+            .subscription_endpoint("/ws")
+            .title("API");
+        let cli = TestClient::new(ep.clone());
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("text/html; charset=utf-8");
+        let html = resp.0.into_body().into_string().await.unwrap();
+        assert!(html.contains("<title>API</title>"));
+        assert!(html.contains(r#"url: "/graphql","#));
+        assert!(html.contains(r#"subscriptionUrl: wsUrl("/ws"),"#));
+
+        // the template can be overridden
+        let mut tera = Tera::default();
+        tera.add_raw_template(GRAPHIQL_TEMPLATE, "custom {{ endpoint }}")
+            .unwrap();
+        TestClient::new(ep.with(TeraTemplating::custom(tera)))
+            .get("/")
+            .send()
+            .await
+            .assert_text("custom &#x2F;graphql")
+            .await;
+    }
+}
@@ -0,0 +1,649 @@
+//! Guards for declarative route preconditions.
+//!
+//! A [`Guard`] checks a request before it reaches the endpoint, and rejects
+//! it with an error otherwise. Guards are attached to the endpoints with
+//! [`EndpointExt::guard`](crate::EndpointExt::guard), or to all the methods
+//! of a route with [`RouteMethod::guard`](crate::RouteMethod::guard).
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     get, guard::Guard, handler, http::StatusCode, test::TestClient, Error, Request, Result,
+//!     Route,
+//! };
+//!
+//! enum RequireRole {
+//!     Admin,
+//! }
+//!
+//! #[poem::async_trait]
+//! impl Guard for RequireRole {
+//!     async fn check(&self, req: &Request) -> Result<()> {
+//!         match (self, req.header("x-role")) {
+//!             (RequireRole::Admin, Some("admin")) => Ok(()),
+//!             _ => Err(Error::from_status(StatusCode::FORBIDDEN)),
+//!         }
+//!     }
+//! }
+//!
+//! #[handler]
+//! fn index() -> &'static str {
+//!     "hello"
+//! }
+//!
+//! let app = Route::new()
+//!     .at("/admin", get(index).guard(RequireRole::Admin))
+//!     .at(
+//!         "/dashboard",
+//!         get(index).guard(RequireRole::Admin.or_redirect("/login")),
+//!     );
+//! let cli = TestClient::new(app);
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! cli.get("/admin")
+//!     .header("x-role", "admin")
+//!     .send()
+//!     .await
+//!     .assert_text("hello")
+//!     .await;
+//! cli.get("/admin")
+//!     .send()
+//!     .await
+//!     .assert_status(StatusCode::FORBIDDEN);
+//!
+//! let resp = cli.get("/dashboard").send().await;
+//! resp.assert_status(StatusCode::SEE_OTHER);
+//! resp.assert_header("location", "/login");
+//! # });
+//! ```
+
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use crate::{
+    error::GuardError,
+    web::{Experiments, Permissions, Principal, Redirect},
+    Endpoint, Error, IntoResponse, Request, Result,
+};
+
+/// Represents a precondition of an endpoint.
+#[async_trait::async_trait]
+pub trait Guard: Send + Sync {
+    /// Checks the request, returning an error to reject it.
+    async fn check(&self, req: &Request) -> Result<()>;
+
+    /// Maps the error rejecting the requests.
+    fn map_err<F>(self, f: F) -> MapErr<Self, F>
+    where
+        F: Fn(Error) -> Error + Send + Sync,
+        Self: Sized,
+    {
+        MapErr { inner: self, f }
+    }
+
+    /// Redirects the rejected requests to `uri` with the `303 See Other`
+    /// status code.
+    fn or_redirect(self, uri: impl Into<String>) -> OrRedirect<Self>
+    where
+        Self: Sized,
+    {
+        OrRedirect {
+            inner: self,
+            uri: uri.into(),
+        }
+    }
+
+    /// Renders the Tera template `name` for the rejected requests whose
+    /// client prefers HTML, such as the browsers, keeping the status code of
+    /// the error.
+    ///
+    /// The members of the [`ProblemDetails`](crate::error::ProblemDetails)
+    /// describing the error, such as `status`, `title` and `detail`, are the
+    /// context of the template. The templates are those of the
+    /// [`TeraTemplating`](crate::tera::TeraTemplating) middleware, which must
+    /// be applied outside of the guard. The other requests are rejected with
+    /// the error unchanged.
+    #[cfg(feature = "tera")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tera")))]
+    fn or_template(self, name: impl Into<String>) -> OrTemplate<Self>
+    where
+        Self: Sized,
+    {
+        OrTemplate {
+            inner: self,
+            name: name.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<F> Guard for F
+where
+    F: Fn(&Request) -> Result<()> + Send + Sync,
+{
+    async fn check(&self, req: &Request) -> Result<()> {
+        self(req)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Guard + ?Sized> Guard for Arc<T> {
+    async fn check(&self, req: &Request) -> Result<()> {
+        self.as_ref().check(req).await
+    }
+}
+
+/// Guard for the [`map_err`](Guard::map_err) method.
+pub struct MapErr<G, F> {
+    inner: G,
+    f: F,
+}
+
+#[async_trait::async_trait]
+impl<G, F> Guard for MapErr<G, F>
+where
+    G: Guard,
+    F: Fn(Error) -> Error + Send + Sync,
+{
+    async fn check(&self, req: &Request) -> Result<()> {
+        self.inner.check(req).await.map_err(&self.f)
+    }
+}
+
+/// Guard for the [`or_redirect`](Guard::or_redirect) method.
+pub struct OrRedirect<G> {
+    inner: G,
+    uri: String,
+}
+
+#[async_trait::async_trait]
+impl<G: Guard> Guard for OrRedirect<G> {
+    async fn check(&self, req: &Request) -> Result<()> {
+        self.inner
+            .check(req)
+            .await
+            .map_err(|_| Error::from_response(Redirect::see_other(&self.uri).into_response()))
+    }
+}
+
+/// Guard for the [`or_template`](Guard::or_template) method.
+#[cfg(feature = "tera")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tera")))]
+pub struct OrTemplate<G> {
+    inner: G,
+    name: String,
+}
+
+#[cfg(feature = "tera")]
+#[async_trait::async_trait]
+impl<G: Guard> Guard for OrTemplate<G> {
+    async fn check(&self, req: &Request) -> Result<()> {
+        use crate::{
+            error::ProblemDetails,
+            middleware::render_error::{prefer_html, Rendered},
+            Response,
+        };
+
+        let err = match self.inner.check(req).await {
+            Ok(()) => return Ok(()),
+            Err(err) if prefer_html(req) => err,
+            Err(err) => return Err(err),
+        };
+        let tera = match req.extensions().get::<tera::Tera>() {
+            Some(tera) => tera,
+            None => {
+                tracing::error!(
+                    template = %self.name,
+                    "the `TeraTemplating` middleware is required to render the template"
+                );
+                return Err(err);
+            }
+        };
+
+        let mut problem = ProblemDetails::from_error(&err);
+        if problem.instance.is_none() {
+            problem.instance = Some(req.original_uri().path().to_string());
+        }
+        let html = match tera::Context::from_serialize(problem.to_json())
+            .and_then(|context| tera.render(&self.name, &context))
+        {
+            Ok(html) => html,
+            Err(render_err) => {
+                tracing::error!(
+                    template = %self.name,
+                    error = %render_err,
+                    "failed to render the template"
+                );
+                return Err(err);
+            }
+        };
+
+        let mut err = Error::from_response(
+            Response::builder()
+                .status(problem.status)
+                .content_type("text/html; charset=utf-8")
+                .body(html),
+        );
+        err.set_data(Rendered);
+        Err(err)
+    }
+}
+
+/// Guard requiring the media type of the request body.
+///
+/// # Errors
+///
+/// - [`GuardError::ContentTypeRequired`]
+/// - [`GuardError::InvalidContentType`]
+#[derive(Debug, Clone)]
+pub struct ContentType {
+    essence: String,
+}
+
+impl ContentType {
+    /// Create a guard requiring the content type `essence`, such as
+    /// `application/json`, ignoring the parameters of the header.
+    pub fn new(essence: impl Into<String>) -> Self {
+        Self {
+            essence: essence.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for ContentType {
+    async fn check(&self, req: &Request) -> Result<()> {
+        let content_type = req.content_type().ok_or(GuardError::ContentTypeRequired)?;
+        match content_type.parse::<mime::Mime>() {
+            Ok(mime) if mime.essence_str().eq_ignore_ascii_case(&self.essence) => Ok(()),
+            _ => Err(GuardError::InvalidContentType(content_type.to_string()).into()),
+        }
+    }
+}
+
+/// Guard for the routes behind a feature flag, requiring the variant
+/// assigned by the [`Experiment`](crate::middleware::Experiment) middleware.
+///
+/// # Errors
+///
+/// - [`GuardError::FeatureDisabled`]
+#[derive(Debug, Clone)]
+pub struct Feature {
+    experiment: String,
+    variant: String,
+}
+
+impl Feature {
+    /// Create a guard requiring the `variant` of `experiment`.
+    pub fn new(experiment: impl Into<String>, variant: impl Into<String>) -> Self {
+        Self {
+            experiment: experiment.into(),
+            variant: variant.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for Feature {
+    async fn check(&self, req: &Request) -> Result<()> {
+        let enabled = req
+            .extensions()
+            .get::<Experiments>()
+            .and_then(|experiments| experiments.variant(&self.experiment))
+            == Some(self.variant.as_str());
+        if enabled {
+            Ok(())
+        } else {
+            Err(GuardError::FeatureDisabled(self.experiment.clone()).into())
+        }
+    }
+}
+
+/// Guard requiring scopes granted to the client of the request, as returned
+/// by the [`Permissions`] extractor.
+///
+/// The requirements are composed with [`RequireScope::any_of`] and
+/// [`RequireScope::all_of`].
+///
+/// # Errors
+///
+/// - [`GuardError::Unauthenticated`] if the request has neither a
+///   [`Principal`] nor scopes.
+/// - [`GuardError::InsufficientScope`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, guard::RequireScope, handler, http::StatusCode, post, test::TestClient,
+///     web::Permissions, EndpointExt, Request, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let app = Route::new()
+///     .at(
+///         "/orders",
+///         get(index.guard(RequireScope::any_of(["orders:read", "admin"])))
+///             .post(index.guard(RequireScope::new("orders:write"))),
+///     )
+///     .before(|mut req: Request| async move {
+///         let scopes = req.header("x-scopes").unwrap_or_default().to_string();
+///         req.extensions_mut()
+///             .insert(scopes.split(',').collect::<Permissions>());
+///         Ok(req)
+///     });
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/orders")
+///     .header("x-scopes", "orders:read")
+///     .send()
+///     .await
+///     .assert_text("hello")
+///     .await;
+/// cli.post("/orders")
+///     .header("x-scopes", "orders:read")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::FORBIDDEN);
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RequireScope(Requirement);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Requirement {
+    Scope(String),
+    AnyOf(Vec<RequireScope>),
+    AllOf(Vec<RequireScope>),
+}
+
+impl RequireScope {
+    /// Create a guard requiring the `scope`.
+    pub fn new(scope: impl Into<String>) -> Self {
+        Self(Requirement::Scope(scope.into()))
+    }
+
+    /// Create a guard requiring at least one of the `requirements`, which
+    /// are scopes or other `RequireScope`.
+    ///
+    /// An empty list is never satisfied.
+    pub fn any_of<I>(requirements: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<RequireScope>,
+    {
+        Self(Requirement::AnyOf(
+            requirements.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    /// Create a guard requiring all the `requirements`, which are scopes or
+    /// other `RequireScope`.
+    ///
+    /// An empty list is always satisfied.
+    pub fn all_of<I>(requirements: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<RequireScope>,
+    {
+        Self(Requirement::AllOf(
+            requirements.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    /// Returns `true` if the `permissions` satisfy the requirement.
+    pub fn is_satisfied_by(&self, permissions: &Permissions) -> bool {
+        match &self.0 {
+            Requirement::Scope(scope) => permissions.contains(scope),
+            Requirement::AnyOf(requirements) => requirements
+                .iter()
+                .any(|requirement| requirement.is_satisfied_by(permissions)),
+            Requirement::AllOf(requirements) => requirements
+                .iter()
+                .all(|requirement| requirement.is_satisfied_by(permissions)),
+        }
+    }
+}
+
+impl From<&str> for RequireScope {
+    fn from(scope: &str) -> Self {
+        Self::new(scope)
+    }
+}
+
+impl From<String> for RequireScope {
+    fn from(scope: String) -> Self {
+        Self::new(scope)
+    }
+}
+
+impl Display for RequireScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (requirements, separator) = match &self.0 {
+            Requirement::Scope(scope) => return write!(f, "`{}`", scope),
+            Requirement::AnyOf(requirements) => (requirements, " or "),
+            Requirement::AllOf(requirements) => (requirements, " and "),
+        };
+        for (idx, requirement) in requirements.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(separator)?;
+            }
+            match requirement.0 {
+                Requirement::Scope(_) => write!(f, "{}", requirement)?,
+                _ => write!(f, "({})", requirement)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for RequireScope {
+    async fn check(&self, req: &Request) -> Result<()> {
+        let permissions = Permissions::of(req);
+        if self.is_satisfied_by(&permissions) {
+            Ok(())
+        } else if permissions.is_empty() && Principal::of(req).is_none() {
+            Err(GuardError::Unauthenticated.into())
+        } else {
+            Err(GuardError::InsufficientScope(self.to_string()).into())
+        }
+    }
+}
+
+/// Endpoint for the [`guard`](crate::EndpointExt::guard) method.
+pub struct GuardEndpoint<E, G> {
+    inner: E,
+    guard: G,
+}
+
+impl<E, G> GuardEndpoint<E, G> {
+    #[inline]
+    pub(crate) fn new(inner: E, guard: G) -> GuardEndpoint<E, G> {
+        Self { inner, guard }
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint, G: Guard> Endpoint for GuardEndpoint<E, G> {
+    type Output = E::Output;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        self.guard.check(&req).await?;
+        self.inner.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, post, test::TestClient, EndpointExt, Route};
+
+    #[handler(internal)]
+    fn index() -> &'static str {
+        "hello"
+    }
+
+    #[tokio::test]
+    async fn content_type() {
+        let cli = TestClient::new(post(index).guard(ContentType::new("application/json")));
+        cli.post("/")
+            .content_type("application/json; charset=utf-8")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+        cli.post("/")
+            .content_type("text/plain")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        cli.post("/")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[cfg(feature = "cookie")]
+    #[tokio::test]
+    async fn feature() {
+        use crate::middleware::Experiment;
+
+        let ep = |variant| {
+            index
+                .guard(Feature::new("new-ui", "on"))
+                .with(Experiment::new("new-ui").variant(variant, 1))
+        };
+        TestClient::new(ep("on"))
+            .get("/")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+        TestClient::new(ep("off"))
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn combinators() {
+        let require_token = |req: &Request| match req.header("x-token") {
+            Some(_) => Ok(()),
+            None => Err(Error::from_status(StatusCode::UNAUTHORIZED)),
+        };
+        let cli = TestClient::new(
+            Route::new()
+                .at("/a", index.guard(require_token))
+                .at(
+                    "/b",
+                    index.guard(
+                        require_token.map_err(|_| Error::from_status(StatusCode::FORBIDDEN)),
+                    ),
+                )
+                .at("/c", index.guard(require_token.or_redirect("/login"))),
+        );
+
+        cli.get("/a")
+            .header("x-token", "1")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+        cli.get("/a")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        cli.get("/b")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        let resp = cli.get("/c").send().await;
+        resp.assert_status(StatusCode::SEE_OTHER);
+        resp.assert_header("location", "/login");
+    }
+
+    #[tokio::test]
+    async fn require_scope() {
+        let require =
+            RequireScope::all_of([RequireScope::new("a"), RequireScope::any_of(["b", "c"])]);
+        assert_eq!(require.to_string(), "`a` and (`b` or `c`)");
+        assert!(require.is_satisfied_by(&Permissions::from_iter(["a", "c"])));
+        assert!(!require.is_satisfied_by(&Permissions::from_iter(["a"])));
+        assert!(!RequireScope::any_of(Vec::<String>::new()).is_satisfied_by(&Permissions::new()));
+
+        let cli = TestClient::new(index.guard(require).before(|mut req: Request| async move {
+            if let Some(scopes) = req.header("x-scopes").map(ToString::to_string) {
+                req.extensions_mut()
+                    .insert(scopes.split(',').collect::<Permissions>());
+            }
+            if let Some(user) = req.header("x-user").map(ToString::to_string) {
+                req.extensions_mut().insert(Principal(user));
+            }
+            Ok(req)
+        }));
+
+        cli.get("/")
+            .header("x-scopes", "a,b")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+        cli.get("/")
+            .header("x-scopes", "b,c")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        cli.get("/")
+            .header("x-user", "sunli")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[cfg(feature = "tera")]
+    #[tokio::test]
+    async fn or_template() {
+        use crate::tera::{Tera, TeraTemplating};
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("403.html", "<h1>{{ status }}</h1><p>{{ detail }}</p>")
+            .unwrap();
+        let cli = TestClient::new(
+            index
+                .guard(RequireScope::new("admin").or_template("403.html"))
+                .with(TeraTemplating::custom(tera))
+                .data(Principal("sunli".to_string())),
+        );
+
+        let resp = cli.get("/").header("accept", "text/html").send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        resp.assert_content_type("text/html; charset=utf-8");
+        resp.assert_text("<h1>403</h1><p>insufficient scope, requires `admin`</p>")
+            .await;
+
+        let resp = cli
+            .get("/")
+            .header("accept", "application/json")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        resp.assert_text("insufficient scope, requires `admin`")
+            .await;
+    }
+}
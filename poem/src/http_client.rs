@@ -0,0 +1,706 @@
+//! An HTTP client for calling the other services from the handlers.
+//!
+//! The [`HttpClient`] is added to the application with
+//! [`EndpointExt::data`](crate::EndpointExt::data), and extracted by the
+//! handlers bound to the current request. The requests it sends then carry:
+//!
+//! - the `X-Request-Id` header of the current request, so that the logs of
+//!   the services can be correlated,
+//! - the W3C trace context, in the `traceparent` and `tracestate` headers. With
+//!   the `opentelemetry` feature, the context of the span of the
+//!   [`OpenTelemetryTracing`](crate::middleware::OpenTelemetryTracing)
+//!   middleware is injected, otherwise the headers of the current request are
+//!   forwarded,
+//! - the remaining time of the [`Deadline`] of the current request, in the
+//!   `X-Request-Timeout` header, and they are cancelled when it expires.
+//!
+//! Each destination, identified by its host, has its own timeout and
+//! retries, see [`Destination`], and its own [`DestinationMetrics`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use poem::{
+//!     handler,
+//!     http_client::{Destination, HttpClient},
+//!     EndpointExt, Result, Route,
+//! };
+//!
+//! #[handler]
+//! async fn index(client: HttpClient) -> Result<String> {
+//!     Ok(client
+//!         .get("http://users.internal/users/1")
+//!         .send()
+//!         .await?
+//!         .into_body()
+//!         .into_string()
+//!         .await?)
+//! }
+//!
+//! let client = HttpClient::new().timeout(Duration::from_secs(5)).destination(
+//!     "users.internal",
+//!     Destination::new()
+//!         .timeout(Duration::from_millis(500))
+//!         .max_retries(3),
+//! );
+//! let app = Route::new().at("/", index).data(client);
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use hyper::client::connect::Connect;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::{
+    error::{DeadlineExceededError, GetDataError, HttpClientError},
+    web::{deadline::REQUEST_TIMEOUT, Deadline},
+    FromRequest, Request, RequestBody, Response, Result,
+};
+
+type HttpSender = Arc<
+    dyn Fn(
+            http::Request<hyper::Body>,
+        ) -> BoxFuture<'static, Result<http::Response<hyper::Body>, hyper::Error>>
+        + Send
+        + Sync,
+>;
+
+/// The headers of the W3C trace context.
+///
+/// Reference: <https://www.w3.org/TR/trace-context/>
+const TRACE_CONTEXT_HEADERS: [HeaderName; 2] = [
+    HeaderName::from_static("traceparent"),
+    HeaderName::from_static("tracestate"),
+];
+
+/// The timeout and the retries of the requests sent to a destination.
+///
+/// The requests with an idempotent method are retried with an exponential
+/// backoff when they fail to be sent, time out, or receive a
+/// `502 Bad Gateway`, `503 Service Unavailable` or `504 Gateway Timeout`
+/// response.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Destination {
+    timeout: Duration,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl Default for Destination {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_retries: 2,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl Destination {
+    /// Create a `Destination` with the default settings.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the timeout of an attempt.
+    ///
+    /// Default is `30s`.
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Sets the number of retries of a request.
+    ///
+    /// Default is `2`.
+    #[must_use]
+    pub fn max_retries(self, max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+
+    /// Sets the delay before the first retry, which doubles on each retry.
+    ///
+    /// Default is `100ms`.
+    #[must_use]
+    pub fn backoff(self, backoff: Duration) -> Self {
+        Self { backoff, ..self }
+    }
+}
+
+/// The metrics of the requests sent to a destination by a [`HttpClient`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DestinationMetrics {
+    /// The number of requests, not counting the retries.
+    pub requests: u64,
+    /// The number of retries.
+    pub retries: u64,
+    /// The number of requests which failed on their last attempt, with an
+    /// error or a `5xx` status code.
+    pub failures: u64,
+    /// The number of requests whose last attempt timed out.
+    pub timeouts: u64,
+    /// The total time spent on the requests, including the retries.
+    pub latency: Duration,
+}
+
+impl DestinationMetrics {
+    /// Returns the mean time spent on a request.
+    pub fn mean_latency(&self) -> Duration {
+        self.latency / u32::try_from(self.requests.max(1)).unwrap_or(u32::MAX)
+    }
+}
+
+/// An HTTP client propagating the request id, the trace context and the
+/// deadline of the current request, see the [module level
+/// documentation](self).
+///
+/// The client extracted by a handler is bound to the current request, a
+/// client used outside of a request, such as in a background task, sends
+/// the requests without these headers. The clones share the connections and
+/// the metrics.
+///
+/// The default client only supports the `http` URLs, a client with a TLS
+/// connector is set with [`HttpClient::client`].
+///
+/// # Errors
+///
+/// - [`GetDataError`] if the `HttpClient` is not added to the application.
+#[derive(Clone)]
+pub struct HttpClient {
+    sender: HttpSender,
+    default: Destination,
+    destinations: Arc<HashMap<String, Destination>>,
+    request_id_header: HeaderName,
+    metrics: Arc<Mutex<HashMap<String, DestinationMetrics>>>,
+    context: HeaderMap,
+    deadline: Option<Deadline>,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            sender: sender(hyper::Client::new()),
+            default: Destination::default(),
+            destinations: Default::default(),
+            request_id_header: HeaderName::from_static("x-request-id"),
+            metrics: Default::default(),
+            context: HeaderMap::new(),
+            deadline: None,
+        }
+    }
+}
+
+impl HttpClient {
+    /// Create an `HttpClient`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the client sending the requests, such as one with a TLS
+    /// connector.
+    #[must_use]
+    pub fn client<C>(self, client: hyper::Client<C>) -> Self
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        Self {
+            sender: sender(client),
+            ..self
+        }
+    }
+
+    /// Sets the timeout of an attempt to the destinations without their own
+    /// settings.
+    ///
+    /// Default is `30s`.
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            default: self.default.clone().timeout(timeout),
+            ..self
+        }
+    }
+
+    /// Sets the number of retries of the requests to the destinations
+    /// without their own settings.
+    ///
+    /// Default is `2`.
+    #[must_use]
+    pub fn max_retries(self, max_retries: u32) -> Self {
+        Self {
+            default: self.default.clone().max_retries(max_retries),
+            ..self
+        }
+    }
+
+    /// Sets the settings of the requests to the host `host`.
+    #[must_use]
+    pub fn destination(self, host: impl Into<String>, destination: Destination) -> Self {
+        let mut destinations = (*self.destinations).clone();
+        destinations.insert(host.into(), destination);
+        Self {
+            destinations: Arc::new(destinations),
+            ..self
+        }
+    }
+
+    /// Sets the header of the request id propagated from the current
+    /// request.
+    ///
+    /// Default is `X-Request-Id`.
+    #[must_use]
+    pub fn request_id_header(self, name: impl TryInto<HeaderName>) -> Self {
+        Self {
+            request_id_header: name.try_into().map_err(|_| ()).expect("valid header name"),
+            ..self
+        }
+    }
+
+    /// Returns a client bound to `req`, which propagates its request id,
+    /// trace context and deadline.
+    ///
+    /// This is what the [`FromRequest`] implementation does, and is useful
+    /// with a client extracted with [`Data`](crate::web::Data).
+    #[must_use]
+    pub fn bind(&self, req: &Request) -> Self {
+        let mut context = HeaderMap::new();
+        if let Some(value) = req.headers().get(&self.request_id_header) {
+            context.insert(self.request_id_header.clone(), value.clone());
+        }
+        trace_context(req, &mut context);
+
+        Self {
+            context,
+            deadline: Deadline::of(req).cloned(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the metrics of the destinations, by host.
+    pub fn metrics(&self) -> BTreeMap<String, DestinationMetrics> {
+        self.metrics
+            .lock()
+            .iter()
+            .map(|(host, metrics)| (host.clone(), metrics.clone()))
+            .collect()
+    }
+
+    /// Create a request with the method `method` to `uri`.
+    pub fn request(&self, method: Method, uri: impl Into<String>) -> HttpClientRequest {
+        HttpClientRequest {
+            client: self.clone(),
+            method,
+            uri: uri.into(),
+            headers: HeaderMap::new(),
+            body: Ok(Bytes::new()),
+        }
+    }
+
+    /// Create a `GET` request to `uri`.
+    pub fn get(&self, uri: impl Into<String>) -> HttpClientRequest {
+        self.request(Method::GET, uri)
+    }
+
+    /// Create a `POST` request to `uri`.
+    pub fn post(&self, uri: impl Into<String>) -> HttpClientRequest {
+        self.request(Method::POST, uri)
+    }
+
+    /// Create a `PUT` request to `uri`.
+    pub fn put(&self, uri: impl Into<String>) -> HttpClientRequest {
+        self.request(Method::PUT, uri)
+    }
+
+    /// Create a `PATCH` request to `uri`.
+    pub fn patch(&self, uri: impl Into<String>) -> HttpClientRequest {
+        self.request(Method::PATCH, uri)
+    }
+
+    /// Create a `DELETE` request to `uri`.
+    pub fn delete(&self, uri: impl Into<String>) -> HttpClientRequest {
+        self.request(Method::DELETE, uri)
+    }
+
+    fn record(&self, host: String, retries: u32, latency: Duration, res: &Result<Response>) {
+        let mut metrics = self.metrics.lock();
+        let metrics = metrics.entry(host).or_default();
+        metrics.requests += 1;
+        metrics.retries += u64::from(retries);
+        metrics.latency += latency;
+        match res {
+            Ok(resp) if !resp.status().is_server_error() => {}
+            Ok(_) => metrics.failures += 1,
+            Err(err) => {
+                metrics.failures += 1;
+                if matches!(err.downcast_ref(), Some(HttpClientError::Timeout))
+                    || err.is::<DeadlineExceededError>()
+                {
+                    metrics.timeouts += 1;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for HttpClient {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .data::<HttpClient>()
+            .ok_or_else(|| GetDataError(std::any::type_name::<HttpClient>()))?
+            .bind(req))
+    }
+}
+
+/// A request sent by a [`HttpClient`].
+pub struct HttpClientRequest {
+    client: HttpClient,
+    method: Method,
+    uri: String,
+    headers: HeaderMap,
+    body: Result<Bytes, serde_json::Error>,
+}
+
+impl HttpClientRequest {
+    /// Appends a header to the request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or the value of the header is invalid.
+    #[must_use]
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+    {
+        let key = key.try_into().map_err(|_| ()).expect("valid header name");
+        let value = value
+            .try_into()
+            .map_err(|_| ())
+            .expect("valid header value");
+        self.headers.append(key, value);
+        self
+    }
+
+    /// Sets the body of the request.
+    #[must_use]
+    pub fn body(self, body: impl Into<Bytes>) -> Self {
+        Self {
+            body: Ok(body.into()),
+            ..self
+        }
+    }
+
+    /// Sets the JSON body of the request with the `application/json` content
+    /// type.
+    #[must_use]
+    pub fn body_json(mut self, body: &impl Serialize) -> Self {
+        self.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        Self {
+            body: serde_json::to_vec(body).map(Bytes::from),
+            ..self
+        }
+    }
+
+    /// Sends the request, retrying it according to the [`Destination`] of
+    /// its host.
+    ///
+    /// # Errors
+    ///
+    /// - [`HttpClientError`]
+    /// - [`DeadlineExceededError`] if the deadline of the bound request
+    ///   expires.
+    pub async fn send(self) -> Result<Response> {
+        let uri = self
+            .uri
+            .parse::<Uri>()
+            .map_err(HttpClientError::InvalidUri)?;
+        let body = self.body.map_err(HttpClientError::Serialize)?;
+        let client = self.client;
+        let host = uri.host().unwrap_or_default().to_string();
+        let destination = client
+            .destinations
+            .get(&host)
+            .unwrap_or(&client.default)
+            .clone();
+        let idempotent = matches!(
+            self.method,
+            Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+        );
+
+        let start = Instant::now();
+        let mut retries = 0;
+        let res = loop {
+            let mut req = http::Request::new(hyper::Body::from(body.clone()));
+            *req.method_mut() = self.method.clone();
+            *req.uri_mut() = uri.clone();
+            *req.headers_mut() = self.headers.clone();
+            for (name, value) in &client.context {
+                req.headers_mut()
+                    .entry(name)
+                    .or_insert_with(|| value.clone());
+            }
+
+            let mut timeout = destination.timeout;
+            if let Some(deadline) = &client.deadline {
+                if deadline.is_expired() {
+                    break Err(DeadlineExceededError.into());
+                }
+                timeout = timeout.min(deadline.remaining());
+                let remaining = timeout.as_millis().max(1);
+                req.headers_mut().insert(
+                    REQUEST_TIMEOUT,
+                    HeaderValue::from(u64::try_from(remaining).unwrap_or(u64::MAX)),
+                );
+            }
+
+            let res: Result<Response> =
+                match tokio::time::timeout(timeout, (client.sender)(req)).await {
+                    Ok(Ok(resp)) => Ok(Response::from(resp)),
+                    Ok(Err(err)) => Err(HttpClientError::Request(err).into()),
+                    Err(_) if client.deadline.as_ref().map_or(false, Deadline::is_expired) => {
+                        Err(DeadlineExceededError.into())
+                    }
+                    Err(_) => Err(HttpClientError::Timeout.into()),
+                };
+            let retry = match &res {
+                Ok(resp) => matches!(
+                    resp.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(err) => !err.is::<DeadlineExceededError>(),
+            };
+            if !(retry && idempotent && retries < destination.max_retries) {
+                break res;
+            }
+
+            let delay = destination.backoff * 2u32.saturating_pow(retries);
+            if let Some(deadline) = &client.deadline {
+                if deadline.remaining() <= delay {
+                    break res;
+                }
+            }
+            retries += 1;
+            tracing::debug!(
+                method = %self.method,
+                uri = %uri,
+                retry = retries,
+                "retrying the request"
+            );
+            tokio::time::sleep(delay).await;
+        };
+
+        let latency = start.elapsed();
+        match &res {
+            Ok(resp) => tracing::debug!(
+                method = %self.method,
+                uri = %uri,
+                status = %resp.status(),
+                retries = retries,
+                duration = ?latency,
+                "request sent"
+            ),
+            Err(err) => tracing::warn!(
+                method = %self.method,
+                uri = %uri,
+                error = %err,
+                retries = retries,
+                duration = ?latency,
+                "request failed"
+            ),
+        }
+        client.record(host, retries, latency, &res);
+        res
+    }
+}
+
+fn trace_context(req: &Request, headers: &mut HeaderMap) {
+    #[cfg(feature = "opentelemetry")]
+    {
+        use libopentelemetry::{global, trace::TraceContextExt, Context};
+
+        let cx = Context::current();
+        if cx.span().span_context().is_valid() {
+            global::get_text_map_propagator(|propagator| {
+                propagator.inject_context(&cx, &mut opentelemetry_http::HeaderInjector(headers))
+            });
+            return;
+        }
+    }
+
+    for name in TRACE_CONTEXT_HEADERS {
+        if let Some(value) = req.headers().get(&name) {
+            headers.insert(name, value.clone());
+        }
+    }
+}
+
+fn sender<C>(client: hyper::Client<C>) -> HttpSender
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    Arc::new(move |req| {
+        let client = client.clone();
+        Box::pin(async move { client.request(req).await })
+    })
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        middleware::RequestDeadline,
+        test::TestClient,
+        web::Data,
+        Endpoint, EndpointExt, Server,
+    };
+
+    async fn serve<E>(ep: E) -> SocketAddr
+    where
+        E: Endpoint + 'static,
+    {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(ep));
+        addr
+    }
+
+    #[tokio::test]
+    async fn propagate() {
+        #[handler(internal)]
+        fn upstream(req: &Request) -> String {
+            let header = |name: &str| req.header(name).unwrap_or("-").to_string();
+            format!(
+                "{} {} {}",
+                header("x-request-id"),
+                header("traceparent"),
+                req.header(REQUEST_TIMEOUT).is_some()
+            )
+        }
+
+        #[handler(internal)]
+        async fn index(client: HttpClient, addr: Data<&SocketAddr>) -> Result<String> {
+            Ok(client
+                .get(format!("http://{}/", *addr))
+                .send()
+                .await?
+                .into_body()
+                .into_string()
+                .await?)
+        }
+
+        let addr = serve(upstream).await;
+        let cli = TestClient::new(
+            index
+                .with(RequestDeadline::new(Duration::from_secs(10)))
+                .data(HttpClient::new())
+                .data(addr),
+        );
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        cli.get("/")
+            .header("x-request-id", "42")
+            .header("traceparent", traceparent)
+            .send()
+            .await
+            .assert_text(format!("42 {traceparent} true"))
+            .await;
+
+        let resp = HttpClient::new()
+            .get(format!("http://{addr}/"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.into_body().into_string().await.unwrap(), "- - false");
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        #[handler(internal)]
+        fn upstream(attempts: Data<&Arc<AtomicUsize>>) -> StatusCode {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::OK,
+            }
+        }
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let addr = serve(upstream.data(attempts.clone())).await;
+        let client = HttpClient::new().max_retries(1).destination(
+            addr.ip().to_string(),
+            Destination::new()
+                .max_retries(2)
+                .backoff(Duration::from_millis(1)),
+        );
+
+        let resp = client.get(format!("http://{addr}/")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        attempts.store(0, Ordering::SeqCst);
+        let resp = client.post(format!("http://{addr}/")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let metrics = client.metrics();
+        let metrics = &metrics[&addr.ip().to_string()];
+        assert_eq!(metrics.requests, 2);
+        assert_eq!(metrics.retries, 2);
+        assert_eq!(metrics.failures, 1);
+        assert_eq!(metrics.timeouts, 0);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        #[handler(internal)]
+        async fn upstream() {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+
+        let addr = serve(upstream).await;
+        let client = HttpClient::new()
+            .timeout(Duration::from_millis(20))
+            .max_retries(0);
+        let err = client
+            .get(format!("http://{addr}/"))
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(matches!(
+            err.downcast_ref::<HttpClientError>(),
+            Some(HttpClientError::Timeout)
+        ));
+        assert_eq!(client.metrics()[&addr.ip().to_string()].timeouts, 1);
+
+        let err = client.get("not a uri").send().await.unwrap_err();
+        assert!(err.is::<HttpClientError>());
+    }
+}
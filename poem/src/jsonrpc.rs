@@ -0,0 +1,585 @@
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) support.
+//!
+//! The typed methods are registered on a [`JsonRpc`] router, which parses
+//! the envelopes, dispatches the single and the batch requests, and replies
+//! with the structured [`RpcError`]s. It is an endpoint handling the
+//! requests sent by `POST`, and [`JsonRpc::websocket`] returns an endpoint
+//! handling the requests sent over a WebSocket.
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     jsonrpc::{JsonRpc, RpcError},
+//!     post,
+//!     test::TestClient,
+//!     Route,
+//! };
+//! use serde::Deserialize;
+//! use serde_json::json;
+//!
+//! #[derive(Deserialize)]
+//! struct Divide {
+//!     a: i32,
+//!     b: i32,
+//! }
+//!
+//! let rpc = JsonRpc::new()
+//!     .method("add", |(a, b): (i32, i32)| async move { Ok(a + b) })
+//!     .method("divide", |Divide { a, b }: Divide| async move {
+//!         match b {
+//!             0 => Err(RpcError::new(1, "division by zero")),
+//!             _ => Ok(a / b),
+//!         }
+//!     });
+//! let cli = TestClient::new(Route::new().at("/rpc", post(rpc)));
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! cli.post("/rpc")
+//!     .body_json(&json!([
+//!         { "jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 1 },
+//!         { "jsonrpc": "2.0", "method": "divide", "params": { "a": 1, "b": 0 }, "id": 2 },
+//!     ]))
+//!     .send()
+//!     .await
+//!     .assert_json(json!([
+//!         { "jsonrpc": "2.0", "result": 3, "id": 1 },
+//!         { "jsonrpc": "2.0", "error": { "code": 1, "message": "division by zero" }, "id": 2 },
+//!     ]))
+//!     .await;
+//! # });
+//! ```
+
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use futures_util::{future::BoxFuture, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    http::{Method, StatusCode},
+    Endpoint, IntoResponse, Request, Response, Result,
+};
+
+/// An error of a JSON-RPC call, replied in the `error` member of the
+/// response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[error("{message} ({code})")]
+pub struct RpcError {
+    /// The code of the error.
+    pub code: i64,
+    /// A short description of the error.
+    pub message: String,
+    /// Additional information about the error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// The request is not valid JSON.
+    pub const PARSE_ERROR: i64 = -32700;
+    /// The request is not a valid request object.
+    pub const INVALID_REQUEST: i64 = -32600;
+    /// The method does not exist.
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    /// The parameters of the method are not valid.
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// An internal error.
+    pub const INTERNAL_ERROR: i64 = -32603;
+
+    /// Create an error with the specified code and message.
+    ///
+    /// The codes from `-32768` to `-32000` are reserved by the
+    /// specification.
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Sets the additional information about the error.
+    #[must_use]
+    pub fn with_data(self, data: impl Serialize) -> Self {
+        Self {
+            data: serde_json::to_value(data).ok(),
+            ..self
+        }
+    }
+
+    /// Create a parse error.
+    pub fn parse_error() -> Self {
+        Self::new(Self::PARSE_ERROR, "Parse error")
+    }
+
+    /// Create an invalid request error.
+    pub fn invalid_request() -> Self {
+        Self::new(Self::INVALID_REQUEST, "Invalid Request")
+    }
+
+    /// Create a method not found error.
+    pub fn method_not_found() -> Self {
+        Self::new(Self::METHOD_NOT_FOUND, "Method not found")
+    }
+
+    /// Create an invalid params error, with the reason as its data.
+    pub fn invalid_params(reason: impl Into<String>) -> Self {
+        Self::new(Self::INVALID_PARAMS, "Invalid params").with_data(reason.into())
+    }
+
+    /// Create an internal error.
+    pub fn internal_error() -> Self {
+        Self::new(Self::INTERNAL_ERROR, "Internal error")
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Option<Value>,
+    // an explicit `null` id is not a notification
+    #[serde(default, deserialize_with = "deserialize_id")]
+    id: Option<Value>,
+}
+
+fn deserialize_id<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Value,
+}
+
+impl RpcResponse {
+    fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(err) => (None, Some(err)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+type BoxMethod = Box<dyn Fn(Value) -> BoxFuture<'static, Result<Value, RpcError>> + Send + Sync>;
+
+/// A router of JSON-RPC methods.
+///
+/// The requests without `id` are notifications, which are executed without
+/// replying. The requests of a batch are executed concurrently, up to the
+/// [`batch_concurrency`](JsonRpc::batch_concurrency) at a time, and a batch
+/// larger than the [`max_batch_size`](JsonRpc::max_batch_size) is rejected
+/// with an invalid request error. A request only containing notifications is
+/// replied with `204 No Content`.
+///
+/// See the [module documentation](crate::jsonrpc) for an example.
+#[derive(Clone)]
+pub struct JsonRpc {
+    methods: Arc<HashMap<String, BoxMethod>>,
+    max_batch_size: usize,
+    batch_concurrency: usize,
+}
+
+impl Default for JsonRpc {
+    fn default() -> Self {
+        Self {
+            methods: Default::default(),
+            max_batch_size: 100,
+            batch_concurrency: 16,
+        }
+    }
+}
+
+impl JsonRpc {
+    /// Create a router without methods.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum number of requests in a batch. Defaults to `100`.
+    #[must_use]
+    pub fn max_batch_size(self, max_batch_size: usize) -> Self {
+        Self {
+            max_batch_size,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of requests of a batch executed at the same
+    /// time. Defaults to `16`.
+    ///
+    /// The responses of a batch are replied in the order their requests
+    /// complete, and are matched to the requests by their `id`.
+    #[must_use]
+    pub fn batch_concurrency(self, batch_concurrency: usize) -> Self {
+        Self {
+            batch_concurrency: batch_concurrency.max(1),
+            ..self
+        }
+    }
+
+    /// Registers a method, whose parameters are deserialized from the
+    /// `params` member of the request, by position from an array or by name
+    /// from an object.
+    ///
+    /// A request without `params` is deserialized from `null`, which is valid
+    /// for `()` and `Option<T>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the router is cloned, or if the method is already
+    /// registered.
+    #[must_use]
+    pub fn method<F, Fut, P, R>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, RpcError>> + Send + 'static,
+        P: DeserializeOwned,
+        R: Serialize,
+    {
+        let name = name.into();
+        let methods = Arc::get_mut(&mut self.methods)
+            .expect("the methods must be registered before the router is cloned");
+        assert!(
+            !methods.contains_key(&name),
+            "duplicate JSON-RPC method: {name}"
+        );
+        methods.insert(
+            name,
+            Box::new(move |params| {
+                let fut = serde_json::from_value::<P>(params)
+                    .map(&f)
+                    .map_err(|err| RpcError::invalid_params(err.to_string()));
+                Box::pin(async move {
+                    let result = fut?.await?;
+                    serde_json::to_value(result).map_err(|err| {
+                        tracing::error!(error = %err, "failed to serialize the JSON-RPC result");
+                        RpcError::internal_error()
+                    })
+                })
+            }),
+        );
+        self
+    }
+
+    /// Executes a request object, returning `None` for the notifications.
+    async fn call_one(&self, value: Value) -> Option<RpcResponse> {
+        let req = match serde_json::from_value::<RpcRequest>(value) {
+            Ok(req) if req.jsonrpc == "2.0" => req,
+            _ => {
+                return Some(RpcResponse::new(
+                    Value::Null,
+                    Err(RpcError::invalid_request()),
+                ))
+            }
+        };
+
+        let result = match self.methods.get(&req.method) {
+            Some(method) => method(req.params.unwrap_or_default()).await,
+            None => Err(RpcError::method_not_found()),
+        };
+        if let Err(err) = &result {
+            tracing::debug!(method = %req.method, error = %err, "JSON-RPC call failed");
+        }
+        req.id.map(|id| RpcResponse::new(id, result))
+    }
+
+    /// Executes a single or a batch request, returning the serialized
+    /// response, or `None` if there is nothing to reply.
+    pub async fn call_str(&self, body: &str) -> Option<String> {
+        let value = match serde_json::from_str::<Value>(body) {
+            Ok(value) => value,
+            Err(_) => {
+                let resp = RpcResponse::new(Value::Null, Err(RpcError::parse_error()));
+                return serde_json::to_string(&resp).ok();
+            }
+        };
+
+        match value {
+            Value::Array(requests) if requests.len() > self.max_batch_size => {
+                let resp = RpcResponse::new(
+                    Value::Null,
+                    Err(RpcError::invalid_request()
+                        .with_data(format!("batch exceeds {} requests", self.max_batch_size))),
+                );
+                serde_json::to_string(&resp).ok()
+            }
+            Value::Array(requests) if !requests.is_empty() => {
+                let responses = futures_util::stream::iter(requests)
+                    .map(|req| self.call_one(req))
+                    .buffer_unordered(self.batch_concurrency)
+                    .filter_map(|resp| async move { resp })
+                    .collect::<Vec<_>>()
+                    .await;
+                if responses.is_empty() {
+                    None
+                } else {
+                    serde_json::to_string(&responses).ok()
+                }
+            }
+            value => {
+                let resp = self.call_one(value).await?;
+                serde_json::to_string(&resp).ok()
+            }
+        }
+    }
+
+    /// Returns an endpoint executing the requests received over a
+    /// WebSocket, each text message being a single or a batch request.
+    ///
+    /// ```
+    /// use poem::{get, jsonrpc::JsonRpc, post, Route};
+    ///
+    /// let rpc = JsonRpc::new().method("ping", |_: ()| async move { Ok("pong") });
+    /// let app = Route::new()
+    ///     .at("/rpc", post(rpc.clone()))
+    ///     .at("/ws", get(rpc.websocket()));
+    /// ```
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub fn websocket(&self) -> JsonRpcWebSocket {
+        JsonRpcWebSocket { rpc: self.clone() }
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for JsonRpc {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if req.method() != Method::POST {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+
+        let body = req.take_body().into_string().await?;
+        Ok(match self.call_str(&body).await {
+            Some(resp) => Response::builder()
+                .content_type("application/json")
+                .body(resp),
+            None => StatusCode::NO_CONTENT.into_response(),
+        })
+    }
+}
+
+/// An endpoint executing the JSON-RPC requests received over a WebSocket,
+/// returned by [`JsonRpc::websocket`].
+///
+/// The requests are executed in the order they are received.
+#[cfg(feature = "websocket")]
+#[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+pub struct JsonRpcWebSocket {
+    rpc: JsonRpc,
+}
+
+#[cfg(feature = "websocket")]
+#[async_trait::async_trait]
+impl Endpoint for JsonRpcWebSocket {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        use futures_util::SinkExt;
+
+        use crate::{
+            web::websocket::{Message, WebSocket},
+            FromRequest,
+        };
+
+        let (req, mut body) = req.split();
+        let websocket = WebSocket::from_request(&req, &mut body).await?;
+        let rpc = self.rpc.clone();
+
+        Ok(websocket
+            .on_upgrade(move |socket| async move {
+                let (mut sink, mut stream) = socket.split();
+                while let Some(Ok(msg)) = stream.next().await {
+                    let text = match msg {
+                        Message::Text(text) => text,
+                        Message::Close(_) => break,
+                        _ => continue,
+                    };
+                    if let Some(resp) = rpc.call_str(&text).await {
+                        if sink.send(Message::Text(resp)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            })
+            .into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test::TestClient;
+
+    fn rpc() -> JsonRpc {
+        JsonRpc::new()
+            .method("subtract", |(a, b): (i64, i64)| async move { Ok(a - b) })
+            .method("notify", |_: Value| async move { Ok(()) })
+            .method("fail", |_: ()| async move {
+                Err::<(), _>(RpcError::new(-1, "failed").with_data(json!({ "retry": true })))
+            })
+    }
+
+    async fn call(body: &str) -> Option<Value> {
+        rpc()
+            .call_str(body)
+            .await
+            .map(|resp| serde_json::from_str(&resp).unwrap())
+    }
+
+    #[tokio::test]
+    async fn single() {
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": 1}"#).await,
+            Some(json!({ "jsonrpc": "2.0", "result": 19, "id": 1 }))
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": null}"#)
+                .await,
+            Some(json!({ "jsonrpc": "2.0", "result": 19, "id": null }))
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "notify", "params": [1]}"#).await,
+            None
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "fail", "id": "a"}"#).await,
+            Some(json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -1, "message": "failed", "data": { "retry": true } },
+                "id": "a",
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "foobar", "id": "1"}"#).await,
+            Some(json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32601, "message": "Method not found" },
+                "id": "1",
+            }))
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method": "subtract", "params": ["a"], "id": 1}"#)
+                .await
+                .unwrap()["error"]["code"],
+            -32602
+        );
+        assert_eq!(
+            call(r#"{"jsonrpc": "2.0", "method""#).await,
+            Some(json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32700, "message": "Parse error" },
+                "id": null,
+            }))
+        );
+        for body in [
+            "[]",
+            "1",
+            r#"{"jsonrpc": "1.0", "method": "subtract", "id": 1}"#,
+        ] {
+            assert_eq!(
+                call(body).await,
+                Some(json!({
+                    "jsonrpc": "2.0",
+                    "error": { "code": -32600, "message": "Invalid Request" },
+                    "id": null,
+                }))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn batch() {
+        let batch = r#"[
+            {"jsonrpc": "2.0", "method": "subtract", "params": [1, 2], "id": 1},
+            {"jsonrpc": "2.0", "method": "notify"},
+            1
+        ]"#;
+        let mut responses = match call(batch).await {
+            Some(Value::Array(responses)) => responses,
+            resp => panic!("unexpected response: {resp:?}"),
+        };
+        responses.sort_by_key(|resp| resp["id"].is_null());
+        assert_eq!(
+            responses,
+            vec![
+                json!({ "jsonrpc": "2.0", "result": -1, "id": 1 }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "error": { "code": -32600, "message": "Invalid Request" },
+                    "id": null,
+                }),
+            ]
+        );
+        assert_eq!(
+            call(r#"[{"jsonrpc": "2.0", "method": "notify"}]"#).await,
+            None
+        );
+
+        let rpc = rpc().max_batch_size(2).batch_concurrency(1);
+        let resp = rpc.call_str(batch).await.unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&resp).unwrap(),
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32600,
+                    "message": "Invalid Request",
+                    "data": "batch exceeds 2 requests",
+                },
+                "id": null,
+            })
+        );
+        let resp = rpc.call_str(&batch.replace(",\n            1", "")).await;
+        assert_eq!(
+            serde_json::from_str::<Value>(&resp.unwrap()).unwrap(),
+            json!([{ "jsonrpc": "2.0", "result": -1, "id": 1 }])
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint() {
+        let cli = TestClient::new(rpc());
+        let resp = cli
+            .post("/")
+            .body(r#"{"jsonrpc": "2.0", "method": "subtract", "params": [2, 1], "id": 1}"#)
+            .send()
+            .await;
+        resp.assert_content_type("application/json");
+        resp.assert_json(json!({ "jsonrpc": "2.0", "result": 1, "id": 1 }))
+            .await;
+
+        cli.post("/")
+            .body(r#"{"jsonrpc": "2.0", "method": "notify"}"#)
+            .send()
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
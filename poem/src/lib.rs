@@ -232,6 +232,7 @@
 //! |------------------|--------------------------------|
 //! | server | Server and listener APIs(enable by default) |
 //! |compression  | Support decompress request body and compress response body |
+//! | render-cache | Support for caching the rendered pages with their brotli compressed bodies on the disk |
 //! |cookie            | Support for Cookie             |
 //! |csrf | Support for Cross-Site Request Forgery (CSRF) protection |
 //! |multipart         | Support for Multipart          |
@@ -256,6 +257,33 @@
 //! | xml | Integrate with [`quick-xml`](https://crates.io/crates/quick-xml) crate. |
 //! | yaml | Integrate with [`serde-yaml`](https://crates.io/crates/serde-yaml) crate.                   |
 //! | tera | Support for [`tera`](https://crates.io/crates/tera) templating. |
+//! | sentry | Integrate with [`sentry`](https://crates.io/crates/sentry) for error reporting. |
+//! | listenfd | Support for systemd socket activation with [`listenfd`](https://crates.io/crates/listenfd). |
+//! | quic | Support for HTTP/3 over QUIC with [`quinn`](https://crates.io/crates/quinn) and [`h3`](https://crates.io/crates/h3). |
+//! | proxy | Support for forwarding requests to an upstream server with the `Proxy` endpoint. |
+//! | csv | Integrate with [`csv`](https://crates.io/crates/csv) crate. |
+//! | msgpack | Integrate with [`rmp-serde`](https://crates.io/crates/rmp-serde) crate. |
+//! | cbor | Integrate with [`ciborium`](https://crates.io/crates/ciborium) crate. |
+//! | cron | Support for running background tasks on cron expressions with [`Schedule`](tasks::Schedule). |
+//! | admin-dashboard | Support for the [`AdminDashboard`](endpoint::AdminDashboard) introspection console. |
+//! | qs | Support for the nested query strings with [`serde_qs`](https://crates.io/crates/serde_qs), see [`StructuredQuery`](web::StructuredQuery). |
+//! | request-decoding | Support for decompressing the request bodies and converting their charset with the [`RequestDecoding`](middleware::RequestDecoding) middleware. |
+//! | html-rewrite | Support for rewriting the HTML responses with [`lol_html`](https://crates.io/crates/lol_html), see [`HtmlRewrite`](middleware::HtmlRewrite). |
+//! | export | Support for exporting the pages of an application to a static site, see the [`export`] module. |
+//! | preview | Support for previewing the unpublished content with the [`Preview`](middleware::Preview) middleware. |
+//! | graphql | Integrate with the [`async-graphql`](https://crates.io/crates/async-graphql) crate, see the [`graphql`] module. |
+//! | grpc | Support for serving the [`tonic`](https://crates.io/crates/tonic) services alongside the other routes, see [`Grpc`](endpoint::Grpc). |
+//! | tus | Support for the [tus](https://tus.io) resumable uploads with the [`TusEndpoint`](endpoint::TusEndpoint). |
+//! | storage | Support for storing the uploads and serving the files with an object storage, see the [`storage`] module. |
+//! | s3 | Support for the S3-compatible object storages with the [`S3Storage`](storage::S3Storage). |
+//! | image-proxy | Support for resizing the images on demand with the [`ImageProxy`](endpoint::ImageProxy). |
+//! | redis-lock | Support for the locks shared by the instances of an application with the [`RedisLock`](lock::RedisLock). |
+//! | usage | Support for metering the usage of an API and enforcing monthly caps with the [`UsageMeter`](middleware::UsageMeter) middleware. |
+//! | request-signing | Support for authenticating the calls between services with signed requests, see the [`RequestSignature`](middleware::RequestSignature) middleware. |
+//! | http-client | Support for calling the other services with the [`HttpClient`](http_client::HttpClient), which propagates the request id, the trace context and the deadline of the requests. |
+//! | sqlx | Support for running the requests in the transactions of a [`sqlx`](https://crates.io/crates/sqlx) pool with the [`Transactional`](middleware::Transactional) middleware. |
+//! | webhook | Support for verifying the signatures of the webhooks, see the [`webhook`] module. |
+//! | buffer-pool | Reuse the buffers serializing the small responses, see [`BufferPoolMetrics`](web::BufferPoolMetrics). |
 
 #![doc(html_favicon_url = "https://raw.githubusercontent.com/poem-web/poem/master/favicon.ico")]
 #![doc(html_logo_url = "https://raw.githubusercontent.com/poem-web/poem/master/logo.png")]
@@ -265,18 +293,40 @@
 #![warn(rustdoc::broken_intra_doc_links)]
 #![warn(missing_docs)]
 
+pub mod audit;
+pub mod config;
 pub mod endpoint;
 pub mod error;
+#[cfg(feature = "export")]
+#[cfg_attr(docsrs, doc(cfg(feature = "export")))]
+pub mod export;
+#[cfg(feature = "graphql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "graphql")))]
+pub mod graphql;
+pub mod guard;
+#[cfg(feature = "http-client")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http-client")))]
+pub mod http_client;
 #[cfg(feature = "i18n")]
 #[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
 pub mod i18n;
+pub mod jsonrpc;
 #[cfg(feature = "server")]
 #[cfg_attr(docsrs, doc(cfg(feature = "server")))]
 pub mod listener;
+pub mod lock;
 pub mod middleware;
+pub mod rate_limit;
+pub mod redact;
 #[cfg(feature = "session")]
 #[cfg_attr(docsrs, doc(cfg(feature = "session")))]
 pub mod session;
+#[cfg(feature = "storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage")))]
+pub mod storage;
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub mod tasks;
 #[cfg(feature = "tera")]
 #[cfg_attr(docsrs, doc(cfg(feature = "tera")))]
 pub mod tera;
@@ -284,21 +334,30 @@ pub mod tera;
 #[cfg_attr(docsrs, doc(cfg(feature = "test")))]
 pub mod test;
 pub mod web;
+#[cfg(feature = "webhook")]
+#[cfg_attr(docsrs, doc(cfg(feature = "webhook")))]
+pub mod webhook;
 
 #[doc(inline)]
 pub use http;
 
 mod addr;
 mod body;
+#[cfg(feature = "server")]
+mod fastcgi;
 mod request;
 mod response;
 mod route;
 #[cfg(feature = "server")]
 mod server;
+#[cfg(feature = "server")]
+mod sharded_server;
+#[cfg(any(feature = "render-cache", feature = "image-proxy"))]
+mod single_flight;
 
 pub use addr::Addr;
 pub use async_trait::async_trait;
-pub use body::Body;
+pub use body::{Body, BodyTransformer};
 pub use endpoint::{Endpoint, EndpointExt, IntoEndpoint};
 pub use error::{Error, Result};
 pub use middleware::Middleware;
@@ -306,9 +365,11 @@ pub use poem_derive::handler;
 pub use request::{OnUpgrade, Request, RequestBuilder, RequestParts, Upgraded};
 pub use response::{Response, ResponseBuilder, ResponseParts};
 pub use route::{
-    connect, delete, get, head, options, patch, post, put, trace, PathPattern, Route, RouteDomain,
-    RouteMethod, RouteScheme,
+    connect, delete, get, head, options, patch, post, put, trace, Breadcrumb, BreadcrumbItem,
+    Breadcrumbs, PathPattern, Route, RouteDomain, RouteMethod, RouteScheme,
 };
 #[cfg(feature = "server")]
-pub use server::Server;
+pub use server::{Readiness, Server, ShutdownSignal};
+#[cfg(feature = "server")]
+pub use sharded_server::{ShardMetrics, ShardedServer};
 pub use web::{FromRequest, IntoResponse, RequestBody};
@@ -3,6 +3,7 @@ use std::{
     fmt::{self, Debug, Formatter},
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 use http::Uri;
@@ -21,6 +22,7 @@ pub struct AutoCert {
     pub(crate) challenge_type: ChallengeType,
     pub(crate) keys_for_http01: Option<Arc<RwLock<HashMap<String, String>>>>,
     pub(crate) cache_path: Option<PathBuf>,
+    pub(crate) renew_before: Duration,
     pub(crate) cache_cert: Option<Vec<u8>>,
     pub(crate) cache_key: Option<Vec<u8>>,
 }
@@ -55,6 +57,7 @@ impl Debug for AutoCert {
             .field("directory_url", &self.directory_url)
             .field("domains", &self.domains)
             .field("cache_path", &self.cache_path)
+            .field("renew_before", &self.renew_before)
             .finish()
     }
 }
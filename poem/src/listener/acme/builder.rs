@@ -3,6 +3,7 @@ use std::{
     io::{Error as IoError, ErrorKind, Result as IoResult},
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 use crate::listener::acme::{keypair::KeyPair, AutoCert, ChallengeType, LETS_ENCRYPT_PRODUCTION};
@@ -14,6 +15,7 @@ pub struct AutoCertBuilder {
     contacts: HashSet<String>,
     challenge_type: ChallengeType,
     cache_path: Option<PathBuf>,
+    renew_before: Duration,
 }
 
 impl AutoCertBuilder {
@@ -24,6 +26,7 @@ impl AutoCertBuilder {
             contacts: Default::default(),
             challenge_type: ChallengeType::TlsAlpn01,
             cache_path: None,
+            renew_before: Duration::from_secs(60 * 60 * 12),
         }
     }
 
@@ -76,6 +79,21 @@ impl AutoCertBuilder {
         }
     }
 
+    /// Sets how long before the expiration of the current certificate a new
+    /// one should be requested.
+    ///
+    /// The new certificate replaces the old one for new TLS handshakes as soon
+    /// as it is obtained, without restarting the server.
+    ///
+    /// Defaults to 12 hours.
+    #[must_use]
+    pub fn renew_before(self, renew_before: Duration) -> Self {
+        Self {
+            renew_before,
+            ..self
+        }
+    }
+
     /// Consumes this builder and returns a [`AutoCert`] object.
     pub fn build(self) -> IoResult<AutoCert> {
         let directory_url = self.directory_url.parse().map_err(|err| {
@@ -116,6 +134,7 @@ impl AutoCertBuilder {
                 ChallengeType::TlsAlpn01 => None,
             },
             cache_path: self.cache_path,
+            renew_before: self.renew_before,
             cache_key,
             cache_cert,
         })
@@ -127,7 +127,7 @@ impl<T: Listener> Listener for AutoCertListener<T> {
 
         tokio::spawn(async move {
             while let Some(cert_resolver) = Weak::upgrade(&weak_cert_resolver) {
-                if cert_resolver.is_expired() {
+                if cert_resolver.is_expired(auto_cert.renew_before) {
                     if let Err(err) = issue_cert(&mut client, &auto_cert, &cert_resolver).await {
                         tracing::error!(error = %err, "failed to issue certificate");
                     }
@@ -1,7 +1,7 @@
 use std::{
     collections::HashMap,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use parking_lot::RwLock;
@@ -20,7 +20,7 @@ pub(crate) struct ResolveServerCert {
 }
 
 impl ResolveServerCert {
-    pub(crate) fn is_expired(&self) -> bool {
+    pub(crate) fn is_expired(&self, renew_before: Duration) -> bool {
         let cert = self.cert.read();
         match cert
             .as_ref()
@@ -31,7 +31,7 @@ impl ResolveServerCert {
             Some(valid_until) => {
                 let now = SystemTime::now();
                 let now = now.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
-                now + 60 * 60 * 12 > valid_until
+                now + renew_before.as_secs() as i64 > valid_until
             }
             None => true,
         }
@@ -63,3 +63,45 @@ impl ResolvesServerCert for ResolveServerCert {
         self.cert.read().as_ref().cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{Certificate, CertificateParams, PKCS_ECDSA_P256_SHA256};
+    use tokio_rustls::rustls::{sign::any_ecdsa_type, PrivateKey};
+
+    use super::*;
+    use crate::listener::acme::AutoCert;
+
+    #[test]
+    fn is_expired() {
+        let resolver = ResolveServerCert::default();
+        assert!(resolver.is_expired(Duration::ZERO));
+
+        let mut params = CertificateParams::new(vec!["example.com".to_string()]);
+        params.alg = &PKCS_ECDSA_P256_SHA256;
+        params.not_after = rcgen::date_time_ymd(2100, 1, 1);
+        let cert = Certificate::from_params(params).unwrap();
+        *resolver.cert.write() = Some(Arc::new(CertifiedKey::new(
+            vec![tokio_rustls::rustls::Certificate(
+                cert.serialize_der().unwrap(),
+            )],
+            any_ecdsa_type(&PrivateKey(cert.serialize_private_key_der())).unwrap(),
+        )));
+
+        // the certificate is renewed once the time until it expires is within
+        // the renewal window
+        let valid_until = UNIX_EPOCH + Duration::from_secs(4_102_444_800);
+        let remaining = valid_until.duration_since(SystemTime::now()).unwrap();
+        let hour = Duration::from_secs(60 * 60);
+        let renew_before = |renew_before| {
+            AutoCert::builder()
+                .domain("example.com")
+                .renew_before(renew_before)
+                .build()
+                .unwrap()
+                .renew_before
+        };
+        assert!(!resolver.is_expired(renew_before(remaining - hour)));
+        assert!(resolver.is_expired(renew_before(remaining + hour)));
+    }
+}
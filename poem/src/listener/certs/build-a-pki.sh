@@ -2,7 +2,7 @@
 
 set -xe
 
-rm key1.pem cert1.pem chain1.pem identity.p12
+rm -f key1.pem cert1.pem chain1.pem identity.p12 ca1.pem client_key1.pem client_chain1.pem
 mkdir -p rsa/
 
 openssl req -nodes \
@@ -88,6 +88,9 @@ openssl asn1parse -in rsa/ca.cert -out rsa/ca.der > /dev/null
 cp rsa/end.key key1.pem
 cp rsa/end.cert cert1.pem
 cp rsa/end.fullchain chain1.pem
+cp rsa/ca.cert ca1.pem
+cp rsa/client.key client_key1.pem
+cp rsa/client.fullchain client_chain1.pem
 openssl pkcs12 -export \
     -inkey rsa/inter.key \
     -in rsa/inter.cert \
@@ -3,6 +3,7 @@ use std::{
     task::{Context, Poll},
 };
 
+use futures_util::future::select_all;
 use http::uri::Scheme;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, Result as IoResult};
 
@@ -62,6 +63,54 @@ impl<A: Acceptor, B: Acceptor> Acceptor for Combined<A, B> {
     }
 }
 
+/// Combine any number of listeners of the same type.
+///
+/// Listeners of different types can be combined after converting them into
+/// [`BoxListener`](crate::listener::BoxListener) with
+/// [`Listener::boxed`](crate::listener::Listener::boxed).
+///
+/// # Example
+///
+/// ```
+/// use poem::listener::{Listener, TcpListener};
+///
+/// let listener = vec![
+///     TcpListener::bind("127.0.0.1:80").boxed(),
+///     TcpListener::bind("127.0.0.1:81").boxed(),
+///     TcpListener::bind("127.0.0.1:82").boxed(),
+/// ];
+/// ```
+#[async_trait::async_trait]
+impl<T: Listener> Listener for Vec<T> {
+    type Acceptor = Vec<T::Acceptor>;
+
+    async fn into_acceptor(self) -> IoResult<Self::Acceptor> {
+        let mut acceptors = Vec::with_capacity(self.len());
+        for listener in self {
+            acceptors.push(listener.into_acceptor().await?);
+        }
+        Ok(acceptors)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Acceptor> Acceptor for Vec<T> {
+    type Io = T::Io;
+
+    fn local_addr(&self) -> Vec<LocalAddr> {
+        self.iter().flat_map(Acceptor::local_addr).collect()
+    }
+
+    async fn accept(&mut self) -> IoResult<(Self::Io, LocalAddr, RemoteAddr, Scheme)> {
+        if self.is_empty() {
+            return futures_util::future::pending().await;
+        }
+        select_all(self.iter_mut().map(|acceptor| acceptor.accept()))
+            .await
+            .0
+    }
+}
+
 /// A IO stream for CombinedAcceptor.
 pub enum CombinedStream<A, B> {
     #[allow(missing_docs)]
@@ -152,4 +201,27 @@ mod tests {
         let (mut stream, _, _, _) = acceptor.accept().await.unwrap();
         assert_eq!(stream.read_i32().await.unwrap(), 20);
     }
+
+    #[tokio::test]
+    async fn combined_vec() {
+        let listener = vec![
+            TcpListener::bind("127.0.0.1:3003").boxed(),
+            TcpListener::bind("127.0.0.1:3004").boxed(),
+            TcpListener::bind("127.0.0.1:3005").boxed(),
+        ];
+        let mut acceptor = listener.into_acceptor().await.unwrap();
+        assert_eq!(acceptor.local_addr().len(), 3);
+
+        tokio::spawn(async move {
+            for (port, value) in [(3003, 10), (3004, 20), (3005, 30)] {
+                let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+                stream.write_i32(value).await.unwrap();
+            }
+        });
+
+        for value in [10, 20, 30] {
+            let (mut stream, _, _, _) = acceptor.accept().await.unwrap();
+            assert_eq!(stream.read_i32().await.unwrap(), value);
+        }
+    }
 }
@@ -0,0 +1,46 @@
+use std::{future::Future, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::{web::RemoteAddr, Request};
+
+tokio::task_local! {
+    static CONNECTION_INFO: Arc<Mutex<ConnectionInfo>>;
+}
+
+/// Information about a connection that only becomes available after it has
+/// been accepted, for example once the TLS handshake is completed or the PROXY
+/// protocol header has been read.
+///
+/// The server runs each connection within [`scope`], so the listeners can
+/// record this information with [`update`] while the connection is being
+/// driven, and it is copied into every request served on that connection.
+#[derive(Default)]
+pub(crate) struct ConnectionInfo {
+    pub(crate) remote_addr: Option<RemoteAddr>,
+    #[cfg(feature = "rustls")]
+    pub(crate) client_cert: Option<crate::web::ClientCert>,
+}
+
+impl ConnectionInfo {
+    pub(crate) fn apply(&self, req: &mut Request) {
+        if let Some(remote_addr) = &self.remote_addr {
+            req.state_mut().remote_addr = remote_addr.clone();
+        }
+
+        #[cfg(feature = "rustls")]
+        if let Some(client_cert) = &self.client_cert {
+            req.extensions_mut().insert(client_cert.clone());
+        }
+    }
+}
+
+pub(crate) async fn scope<F: Future>(info: Arc<Mutex<ConnectionInfo>>, fut: F) -> F::Output {
+    CONNECTION_INFO.scope(info, fut).await
+}
+
+/// Updates the information of the current connection, does nothing if called
+/// outside of [`scope`].
+pub(crate) fn update(f: impl FnOnce(&mut ConnectionInfo)) {
+    let _ = CONNECTION_INFO.try_with(|info| f(&mut info.lock()));
+}
@@ -14,7 +14,8 @@ enum State<S> {
     Error,
 }
 
-/// A handshake stream for tls.
+/// A stream that needs to complete a handshake, such as a TLS handshake or
+/// reading the PROXY protocol header, before it can be used.
 pub struct HandshakeStream<S> {
     state: State<S>,
 }
@@ -0,0 +1,131 @@
+use std::{
+    env,
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    sync::Mutex,
+};
+
+use listenfd::ListenFd;
+
+use crate::listener::{AcceptorExt, BoxAcceptor, Listener, TcpAcceptor, UnixAcceptor};
+
+/// The inherited sockets with their names, read from the environment the
+/// first time a [`ListenFdListener`] is used, so that several listeners can
+/// take different sockets.
+static INHERITED: Mutex<Option<(ListenFd, Vec<String>)>> = Mutex::new(None);
+
+/// A listener that accepts connections on the sockets inherited from
+/// [systemd socket activation](https://www.freedesktop.org/software/systemd/man/sd_listen_fds.html)
+/// or a supervisor implementing the same protocol, such as
+/// [`systemfd`](https://github.com/mitsuhiko/systemfd).
+///
+/// Because the sockets are created by the supervisor and outlive the process,
+/// the server can be restarted without refusing any connection. Both TCP and
+/// Unix domain sockets are supported, and all of the inherited sockets are
+/// used unless [`ListenFdListener::name`] is specified.
+///
+/// With [`Server::sd_notify`](crate::Server::sd_notify), the server notifies
+/// the service manager once it is ready to accept connections and when it
+/// starts shutting down, for the `Type=notify` services.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{handler, listener::ListenFdListener, Server};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// Server::new(ListenFdListener::new())
+///     .sd_notify()
+///     .run(index)
+///     .await
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "listenfd"))))]
+#[derive(Debug, Default)]
+pub struct ListenFdListener {
+    names: Vec<String>,
+}
+
+impl ListenFdListener {
+    /// Create a `ListenFdListener` that uses all the inherited sockets.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only use the inherited sockets with the specified name, which is set
+    /// with `FileDescriptorName=` in the systemd socket unit.
+    ///
+    /// You can call this function multiple times to use the sockets with
+    /// several names.
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.names.push(name.into());
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Listener for ListenFdListener {
+    type Acceptor = Vec<BoxAcceptor>;
+
+    async fn into_acceptor(self) -> IoResult<Self::Acceptor> {
+        let mut inherited = INHERITED.lock().unwrap();
+        let (listen_fd, fd_names) = inherited.get_or_insert_with(|| {
+            // `ListenFd::from_env` removes `LISTEN_FDS` from the environment
+            let fd_names = env::var("LISTEN_FDNAMES")
+                .map(|names| names.split(':').map(ToString::to_string).collect())
+                .unwrap_or_default();
+            (ListenFd::from_env(), fd_names)
+        });
+        let mut acceptors = Vec::new();
+
+        for idx in select_fds(listen_fd.len(), fd_names, &self.names) {
+            if let Ok(Some(listener)) = listen_fd.take_tcp_listener(idx) {
+                listener.set_nonblocking(true)?;
+                acceptors.push(TcpAcceptor::from_std(listener)?.boxed());
+            } else if let Some(listener) = listen_fd.take_unix_listener(idx)? {
+                listener.set_nonblocking(true)?;
+                acceptors.push(UnixAcceptor::from_std(listener)?.boxed());
+            }
+        }
+
+        if acceptors.is_empty() {
+            return Err(IoError::new(ErrorKind::Other, "no inherited sockets"));
+        }
+        Ok(acceptors)
+    }
+}
+
+/// Returns the indexes of the inherited sockets with the specified names, or
+/// of all of them if `names` is empty.
+fn select_fds(count: usize, fd_names: &[String], names: &[String]) -> Vec<usize> {
+    (0..count)
+        .filter(|idx| {
+            names.is_empty()
+                || fd_names
+                    .get(*idx)
+                    .map_or(false, |fd_name| names.contains(fd_name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_by_name() {
+        let fd_names = vec!["http".to_string(), "https".to_string()];
+        assert_eq!(select_fds(3, &fd_names, &[]), vec![0, 1, 2]);
+        assert_eq!(select_fds(3, &fd_names, &["https".to_string()]), vec![1]);
+        assert_eq!(
+            select_fds(3, &fd_names, &["http".to_string(), "https".to_string()]),
+            vec![0, 1]
+        );
+        assert!(select_fds(3, &fd_names, &["admin".to_string()]).is_empty());
+    }
+}
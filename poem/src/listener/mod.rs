@@ -4,12 +4,17 @@
 #[cfg_attr(docsrs, doc(cfg(feature = "acme-base")))]
 pub mod acme;
 mod combined;
-#[cfg(any(feature = "native-tls", feature = "rustls", feature = "openssl-tls"))]
+pub(crate) mod connection_info;
 mod handshake_stream;
+#[cfg(all(unix, feature = "listenfd"))]
+mod listen_fd;
 #[cfg(feature = "native-tls")]
 mod native_tls;
 #[cfg(feature = "openssl-tls")]
 mod openssl_tls;
+mod proxy_protocol;
+#[cfg(feature = "quic")]
+mod quic;
 #[cfg(feature = "rustls")]
 mod rustls;
 mod tcp;
@@ -31,20 +36,24 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, Result as IoResult};
 
 #[cfg(feature = "acme-base")]
 use self::acme::{AutoCert, AutoCertListener};
-#[cfg(any(feature = "native-tls", feature = "rustls", feature = "openssl-tls"))]
-pub use self::handshake_stream::HandshakeStream;
+#[cfg(all(unix, feature = "listenfd"))]
+pub use self::listen_fd::ListenFdListener;
 #[cfg(feature = "native-tls")]
 pub use self::native_tls::{NativeTlsAcceptor, NativeTlsConfig, NativeTlsListener};
 #[cfg(feature = "openssl-tls")]
 pub use self::openssl_tls::{OpensslTlsAcceptor, OpensslTlsConfig, OpensslTlsListener};
+#[cfg(feature = "quic")]
+pub use self::quic::QuicListener;
 #[cfg(feature = "rustls")]
 pub use self::rustls::{RustlsAcceptor, RustlsCertificate, RustlsConfig, RustlsListener};
 #[cfg(any(feature = "rustls", feature = "native-tls", feature = "openssl-tls"))]
-pub use self::tls::IntoTlsConfigStream;
+pub use self::tls::{IntoTlsConfigStream, TlsConfigHandle};
 #[cfg(unix)]
 pub use self::unix::{UnixAcceptor, UnixListener};
 pub use self::{
     combined::{Combined, CombinedStream},
+    handshake_stream::HandshakeStream,
+    proxy_protocol::{ProxyProtocolAcceptor, ProxyProtocolListener},
     tcp::{TcpAcceptor, TcpListener},
 };
 use crate::web::{LocalAddr, RemoteAddr};
@@ -81,6 +90,16 @@ pub trait AcceptorExt: Acceptor {
         Combined::new(self, other)
     }
 
+    /// Consume this acceptor and return a new acceptor which reads the PROXY
+    /// protocol header of each connection.
+    #[must_use]
+    fn proxy_protocol(self) -> ProxyProtocolAcceptor<Self>
+    where
+        Self: Sized,
+    {
+        ProxyProtocolAcceptor::new(self)
+    }
+
     /// Wrap the acceptor in a `Box`.
     fn boxed(self) -> BoxAcceptor
     where
@@ -136,7 +155,8 @@ pub trait Listener: Send {
 
     /// Combine two listeners.
     ///
-    /// You can call this function multiple times to combine more listeners.
+    /// You can call this function multiple times to combine more listeners,
+    /// or use a `Vec` of listeners to combine any number of them.
     ///
     /// # Example
     ///
@@ -153,6 +173,16 @@ pub trait Listener: Send {
         Combined::new(self, other)
     }
 
+    /// Consume this listener and return a new listener which reads the PROXY
+    /// protocol header of each connection, see [`ProxyProtocolListener`].
+    #[must_use]
+    fn proxy_protocol(self) -> ProxyProtocolListener<Self>
+    where
+        Self: Sized,
+    {
+        ProxyProtocolListener::new(self)
+    }
+
     /// Consume this listener and return a new TLS listener with [`rustls`](https://crates.io/crates/rustls).
     #[cfg(feature = "rustls")]
     #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
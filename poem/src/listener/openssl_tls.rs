@@ -196,6 +196,8 @@ where
     async fn accept(&mut self) -> IoResult<(Self::Io, LocalAddr, RemoteAddr, Scheme)> {
         loop {
             tokio::select! {
+                biased;
+
                 res = self.config_stream.next() => {
                     if let Some(tls_config) = res {
                         match tls_config.create_acceptor_builder() {
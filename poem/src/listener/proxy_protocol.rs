@@ -0,0 +1,253 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use http::uri::Scheme;
+use tokio::io::{AsyncRead, AsyncReadExt, Error as IoError, ErrorKind, Result as IoResult};
+
+use crate::{
+    listener::{connection_info, Acceptor, HandshakeStream, Listener},
+    web::{LocalAddr, RemoteAddr},
+    Addr,
+};
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+const V1_MAX_LENGTH: usize = 107;
+
+/// A wrapper around an underlying listener which reads the
+/// [PROXY protocol](https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt)
+/// header (version 1 or 2) sent by an L4 load balancer at the beginning of
+/// each connection.
+///
+/// The original client address in the header replaces the address of the
+/// load balancer as the [`RemoteAddr`] of the requests, so it is also used by
+/// the [`RealIp`](crate::web::RealIp) extractor. Connections that do not
+/// start with a valid header are closed, so only the load balancer should be
+/// able to connect to this listener.
+///
+/// NOTE: You cannot create it directly and should use the
+/// [`proxy_protocol`](crate::listener::Listener::proxy_protocol) method to
+/// create it, because it needs to wrap a underlying listener.
+///
+/// # Example
+///
+/// ```
+/// use poem::listener::{Listener, TcpListener};
+///
+/// let listener = TcpListener::bind("0.0.0.0:3000").proxy_protocol();
+/// ```
+pub struct ProxyProtocolListener<T> {
+    inner: T,
+}
+
+impl<T> ProxyProtocolListener<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Listener> Listener for ProxyProtocolListener<T> {
+    type Acceptor = ProxyProtocolAcceptor<T::Acceptor>;
+
+    async fn into_acceptor(self) -> IoResult<Self::Acceptor> {
+        Ok(ProxyProtocolAcceptor::new(
+            self.inner.into_acceptor().await?,
+        ))
+    }
+}
+
+/// An acceptor which reads the PROXY protocol header.
+pub struct ProxyProtocolAcceptor<T> {
+    inner: T,
+}
+
+impl<T> ProxyProtocolAcceptor<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Acceptor> Acceptor for ProxyProtocolAcceptor<T> {
+    type Io = HandshakeStream<T::Io>;
+
+    fn local_addr(&self) -> Vec<LocalAddr> {
+        self.inner.local_addr()
+    }
+
+    async fn accept(&mut self) -> IoResult<(Self::Io, LocalAddr, RemoteAddr, Scheme)> {
+        let (mut stream, local_addr, remote_addr, scheme) = self.inner.accept().await?;
+        let stream = HandshakeStream::new(async move {
+            if let Some(addr) = read_header(&mut stream).await? {
+                connection_info::update(|info| {
+                    info.remote_addr = Some(RemoteAddr(Addr::SocketAddr(addr)))
+                });
+            }
+            Ok(stream)
+        });
+        Ok((stream, local_addr, remote_addr, scheme))
+    }
+}
+
+fn invalid_header() -> IoError {
+    IoError::new(ErrorKind::InvalidData, "invalid proxy protocol header")
+}
+
+/// Reads the PROXY protocol header and returns the source address, or `None`
+/// if the connection was not proxied (`UNKNOWN` or `LOCAL`).
+async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> IoResult<Option<SocketAddr>> {
+    // the shortest v1 header, `PROXY UNKNOWN\r\n`, is longer than the v2
+    // signature
+    let mut header = vec![0; V2_SIGNATURE.len()];
+    stream.read_exact(&mut header).await?;
+
+    if header == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if header.starts_with(b"PROXY ") {
+        while !header.ends_with(b"\r\n") {
+            if header.len() >= V1_MAX_LENGTH {
+                return Err(invalid_header());
+            }
+            header.push(stream.read_u8().await?);
+        }
+        parse_v1(&header[..header.len() - 2])
+    } else {
+        Err(invalid_header())
+    }
+}
+
+fn parse_v1(header: &[u8]) -> IoResult<Option<SocketAddr>> {
+    let header = std::str::from_utf8(header).map_err(|_| invalid_header())?;
+    let mut parts = header.split(' ').skip(1);
+
+    match parts.next() {
+        Some("TCP4" | "TCP6") => {}
+        Some("UNKNOWN") => return Ok(None),
+        _ => return Err(invalid_header()),
+    }
+
+    let src_ip = parts
+        .next()
+        .and_then(|ip| ip.parse::<IpAddr>().ok())
+        .ok_or_else(invalid_header)?;
+    let _dst_ip = parts.next().ok_or_else(invalid_header)?;
+    let src_port = parts
+        .next()
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or_else(invalid_header)?;
+    Ok(Some(SocketAddr::new(src_ip, src_port)))
+}
+
+async fn read_v2<S: AsyncRead + Unpin>(stream: &mut S) -> IoResult<Option<SocketAddr>> {
+    let ver_cmd = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let len = stream.read_u16().await?;
+    let mut addrs = vec![0; len as usize];
+    stream.read_exact(&mut addrs).await?;
+
+    if ver_cmd >> 4 != 2 {
+        return Err(invalid_header());
+    }
+
+    match ver_cmd & 0x0f {
+        // LOCAL
+        0 => return Ok(None),
+        // PROXY
+        1 => {}
+        _ => return Err(invalid_header()),
+    }
+
+    match family >> 4 {
+        // AF_INET
+        1 if addrs.len() >= 12 => {
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addrs[0..4]).unwrap());
+            let port = u16::from_be_bytes([addrs[8], addrs[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // AF_INET6
+        2 if addrs.len() >= 36 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addrs[0..16]).unwrap());
+            let port = u16::from_be_bytes([addrs[32], addrs[33]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        1 | 2 => Err(invalid_header()),
+        // AF_UNSPEC, AF_UNIX
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+    use crate::{handler, listener::TcpListener, Server};
+
+    #[tokio::test]
+    async fn parse_header() {
+        async fn parse(mut data: &[u8]) -> IoResult<Option<SocketAddr>> {
+            let addr = read_header(&mut data).await?;
+            assert_eq!(data, b"GET");
+            Ok(addr)
+        }
+
+        assert_eq!(
+            parse(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET")
+                .await
+                .unwrap(),
+            Some("192.168.0.1:56324".parse().unwrap())
+        );
+        assert_eq!(
+            parse(b"PROXY TCP6 ::1 ::2 56324 443\r\nGET").await.unwrap(),
+            Some("[::1]:56324".parse().unwrap())
+        );
+        assert_eq!(parse(b"PROXY UNKNOWN\r\nGET").await.unwrap(), None);
+        assert!(parse(b"PROXY TCP4 a b c d\r\nGET").await.is_err());
+        assert!(parse(b"GET / HTTP/1.1\r\n\r\nGET").await.is_err());
+
+        let mut v2 = V2_SIGNATURE.to_vec();
+        v2.extend_from_slice(&[
+            0x21, 0x11, 0, 12, 10, 0, 0, 1, 10, 0, 0, 2, 0x1f, 0x90, 0, 80,
+        ]);
+        v2.extend_from_slice(b"GET");
+        assert_eq!(
+            parse(&v2).await.unwrap(),
+            Some("10.0.0.1:8080".parse().unwrap())
+        );
+
+        let mut v2 = V2_SIGNATURE.to_vec();
+        v2.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        v2.extend_from_slice(b"GET");
+        assert_eq!(parse(&v2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol_listener() {
+        #[handler(internal)]
+        fn index(remote_addr: &RemoteAddr) -> String {
+            remote_addr.to_string()
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .proxy_protocol()
+            .into_acceptor()
+            .await
+            .unwrap();
+        let local_addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(index));
+
+        let mut stream = TcpStream::connect(local_addr).await.unwrap();
+        stream
+            .write_all(
+                b"PROXY TCP4 203.0.113.7 127.0.0.1 40000 80\r\nGET / HTTP/1.1\r\nhost: \
+                  localhost\r\nconnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.ends_with("socket://203.0.113.7:40000"), "{resp}");
+    }
+}
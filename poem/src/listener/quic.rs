@@ -0,0 +1,358 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use bytes::{Buf, Bytes};
+use h3::{error::ErrorLevel, server::RequestStream};
+use http::{header, uri::Scheme};
+use hyper::body::HttpBody;
+use tokio::{
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    net::{lookup_host, ToSocketAddrs},
+    sync::watch,
+};
+
+use crate::{
+    listener::RustlsConfig,
+    web::{LocalAddr, RemoteAddr},
+    Addr, Body, Endpoint, EndpointExt, IntoEndpoint, Response, ShutdownSignal,
+};
+
+/// A listener that serves HTTP/3 over [QUIC](https://www.rfc-editor.org/rfc/rfc9000) with
+/// [`quinn`](https://crates.io/crates/quinn) and [`h3`](https://crates.io/crates/h3).
+///
+/// QUIC runs over UDP, so unlike the other listeners this one cannot be used
+/// with [`Server`](crate::Server) and serves the endpoint itself. It is
+/// usually bound to the same port as a TLS listener serving HTTP/1.1 and
+/// HTTP/2 with the [`AltSvc`](crate::middleware::AltSvc) middleware, which
+/// tells the clients that HTTP/3 is available.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{
+///     handler,
+///     listener::{Listener, QuicListener, RustlsCertificate, RustlsConfig, TcpListener},
+///     middleware::AltSvc,
+///     EndpointExt, Route, Server,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let config = || {
+///     RustlsConfig::new().fallback(
+///         RustlsCertificate::new()
+///             .cert(std::fs::read("cert.pem").unwrap())
+///             .key(std::fs::read("key.pem").unwrap()),
+///     )
+/// };
+/// let app = || Route::new().at("/", index).with(AltSvc::new().h3(443));
+///
+/// let _ = tokio::join!(
+///     Server::new(TcpListener::bind("0.0.0.0:443").rustls(config())).run(app()),
+///     QuicListener::bind("0.0.0.0:443", config()).run(app()),
+/// );
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "quic")))]
+pub struct QuicListener<T> {
+    addr: T,
+    config: RustlsConfig,
+}
+
+impl<T: ToSocketAddrs + Send> QuicListener<T> {
+    /// Binds to the provided UDP address and uses the specified tls config.
+    pub fn bind(addr: T, config: RustlsConfig) -> Self {
+        Self { addr, config }
+    }
+
+    /// Run this listener.
+    pub async fn run<E>(self, ep: E) -> IoResult<()>
+    where
+        E: IntoEndpoint,
+        E::Endpoint: 'static,
+    {
+        self.run_with_graceful_shutdown(ep, futures_util::future::pending(), None)
+            .await
+    }
+
+    /// Run this listener and a signal to initiate graceful shutdown.
+    ///
+    /// When the `signal` completes, the listener stops accepting new
+    /// connections and sends a `GOAWAY` frame to the open ones, which are
+    /// closed once their in-flight requests have been answered. If `timeout`
+    /// is specified, connections that are still open when it elapses are
+    /// closed forcibly.
+    pub async fn run_with_graceful_shutdown<E>(
+        self,
+        ep: E,
+        signal: impl Future<Output = ()>,
+        timeout: Option<Duration>,
+    ) -> IoResult<()>
+    where
+        E: IntoEndpoint,
+        E::Endpoint: 'static,
+    {
+        let ep: Arc<dyn Endpoint<Output = Response>> =
+            Arc::new(ep.into_endpoint().map_to_response());
+        let addr = lookup_host(self.addr)
+            .await?
+            .next()
+            .ok_or_else(|| IoError::new(ErrorKind::Other, "invalid address"))?;
+
+        let mut server_config = self.config.create_server_config()?;
+        server_config.alpn_protocols = vec![b"h3".to_vec()];
+        let endpoint = quinn::Endpoint::server(
+            quinn::ServerConfig::with_crypto(Arc::new(server_config)),
+            addr,
+        )?;
+        let local_addr = LocalAddr(Addr::SocketAddr(endpoint.local_addr()?));
+        let (shutdown, _) = watch::channel(false);
+
+        tokio::pin!(signal);
+
+        tracing::info!(addr = %local_addr, "listening");
+        tracing::info!("quic server started");
+
+        loop {
+            tokio::select! {
+                _ = &mut signal => break,
+                res = endpoint.accept() => {
+                    let connecting = match res {
+                        Some(connecting) => connecting,
+                        None => break,
+                    };
+                    tokio::spawn(serve_connection(
+                        connecting,
+                        local_addr.clone(),
+                        ep.clone(),
+                        ShutdownSignal {
+                            rx: shutdown.subscribe(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        shutdown.send_replace(true);
+        tracing::info!("initiate graceful shutdown");
+
+        match timeout {
+            Some(timeout) => {
+                if tokio::time::timeout(timeout, endpoint.wait_idle())
+                    .await
+                    .is_err()
+                {
+                    endpoint.close(0u32.into(), b"");
+                }
+            }
+            None => endpoint.wait_idle().await,
+        }
+
+        tracing::info!("quic server stopped");
+        Ok(())
+    }
+}
+
+async fn serve_connection(
+    connecting: quinn::Connecting,
+    local_addr: LocalAddr,
+    ep: Arc<dyn Endpoint<Output = Response>>,
+    signal: ShutdownSignal,
+) {
+    let conn = match connecting.await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::debug!(error = %err, "quic handshake failed");
+            return;
+        }
+    };
+    let remote_addr = RemoteAddr(Addr::SocketAddr(conn.remote_address()));
+    let mut conn = match h3::server::Connection::new(h3_quinn::Connection::new(conn)).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::debug!(error = %err, "failed to establish http3 connection");
+            return;
+        }
+    };
+    let mut shutting_down = false;
+
+    loop {
+        let res = tokio::select! {
+            res = conn.accept() => res,
+            _ = signal.wait(), if !shutting_down => {
+                shutting_down = true;
+                let _ = conn.shutdown(0).await;
+                continue;
+            }
+        };
+
+        match res {
+            Ok(Some((req, stream))) => {
+                tokio::spawn(serve_request(
+                    req,
+                    stream,
+                    local_addr.clone(),
+                    remote_addr.clone(),
+                    ep.clone(),
+                    signal.clone(),
+                ));
+            }
+            Ok(None) => break,
+            Err(err) => match err.get_error_level() {
+                ErrorLevel::ConnectionError => break,
+                ErrorLevel::StreamError => continue,
+            },
+        }
+    }
+}
+
+async fn serve_request(
+    req: http::Request<()>,
+    stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    local_addr: LocalAddr,
+    remote_addr: RemoteAddr,
+    ep: Arc<dyn Endpoint<Output = Response>>,
+    signal: ShutdownSignal,
+) {
+    let (mut send, recv) = stream.split();
+    let body = Body::from_bytes_stream(futures_util::stream::unfold(
+        Some(recv),
+        |recv| async move {
+            let mut recv = recv?;
+            match recv.recv_data().await {
+                Ok(Some(mut data)) => Some((Ok(data.copy_to_bytes(data.remaining())), Some(recv))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        },
+    ));
+
+    let (parts, _) = req.into_parts();
+    let mut req: crate::Request = (
+        http::Request::from_parts(parts, body.into()),
+        local_addr,
+        remote_addr,
+        Scheme::HTTPS,
+    )
+        .into();
+    req.extensions_mut().insert(signal);
+
+    let resp: http::Response<hyper::Body> = ep.get_response(req).await.into();
+    let (mut parts, mut body) = resp.into_parts();
+
+    // connection-specific headers are not allowed in HTTP/3
+    for name in [
+        header::CONNECTION,
+        header::TRANSFER_ENCODING,
+        header::UPGRADE,
+        header::HeaderName::from_static("keep-alive"),
+        header::HeaderName::from_static("proxy-connection"),
+    ] {
+        parts.headers.remove(name);
+    }
+
+    if let Err(err) = send
+        .send_response(http::Response::from_parts(parts, ()))
+        .await
+    {
+        tracing::debug!(error = %err, "failed to send http3 response");
+        return;
+    }
+
+    while let Some(data) = body.data().await {
+        let res = match data {
+            Ok(data) => send.send_data(data).await,
+            Err(err) => {
+                tracing::debug!(error = %err, "failed to read response body");
+                send.stop_stream(h3::error::Code::H3_INTERNAL_ERROR);
+                return;
+            }
+        };
+        if let Err(err) = res {
+            tracing::debug!(error = %err, "failed to send http3 response");
+            return;
+        }
+    }
+
+    if let Ok(Some(trailers)) = body.trailers().await {
+        if send.send_trailers(trailers).await.is_err() {
+            return;
+        }
+    }
+
+    let _ = send.finish().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_rustls::rustls::{Certificate, ClientConfig, RootCertStore};
+
+    use super::*;
+    use crate::{handler, listener::RustlsCertificate, Request};
+
+    #[tokio::test]
+    async fn quic_listener() {
+        #[handler(internal)]
+        fn index(req: &Request, body: String) -> String {
+            format!("{:?} {}", req.version(), body)
+        }
+
+        let config = RustlsConfig::new().fallback(
+            RustlsCertificate::new()
+                .cert(include_bytes!("certs/cert1.pem").as_ref())
+                .key(include_bytes!("certs/key1.pem").as_ref()),
+        );
+        tokio::spawn(QuicListener::bind("127.0.0.1:3010", config).run(index));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut roots = RootCertStore::empty();
+        for der in rustls_pemfile::certs(&mut include_bytes!("certs/chain1.pem").as_ref()).unwrap()
+        {
+            roots.add(&Certificate(der)).unwrap();
+        }
+        let mut client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h3".to_vec()];
+        let mut client = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+        client.set_default_client_config(quinn::ClientConfig::new(Arc::new(client_config)));
+
+        let conn = client
+            .connect("127.0.0.1:3010".parse().unwrap(), "testserver.com")
+            .unwrap()
+            .await
+            .unwrap();
+        let (mut driver, mut send_request) = h3::client::new(h3_quinn::Connection::new(conn))
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = futures_util::future::poll_fn(|cx| driver.poll_close(cx)).await;
+        });
+
+        let mut stream = send_request
+            .send_request(
+                http::Request::post("https://testserver.com/")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        stream
+            .send_data(Bytes::from_static(b"hello"))
+            .await
+            .unwrap();
+        stream.finish().await.unwrap();
+
+        let resp = stream.recv_response().await.unwrap();
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let mut body = Vec::new();
+        while let Some(mut data) = stream.recv_data().await.unwrap() {
+            body.extend_from_slice(&data.copy_to_bytes(data.remaining()));
+        }
+        assert_eq!(body, b"HTTP/3.0 hello");
+    }
+}
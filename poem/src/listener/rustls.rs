@@ -2,7 +2,7 @@ use std::{collections::HashMap, sync::Arc};
 
 use futures_util::{
     stream::{BoxStream, Chain, Pending},
-    Stream, StreamExt,
+    Stream, StreamExt, TryFutureExt,
 };
 use http::uri::Scheme;
 use rustls_pemfile::Item;
@@ -20,8 +20,8 @@ use tokio_rustls::{
 };
 
 use crate::{
-    listener::{Acceptor, HandshakeStream, IntoTlsConfigStream, Listener},
-    web::{LocalAddr, RemoteAddr},
+    listener::{connection_info, Acceptor, HandshakeStream, IntoTlsConfigStream, Listener},
+    web::{ClientCert, LocalAddr, RemoteAddr},
 };
 
 #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
@@ -210,6 +210,9 @@ impl RustlsConfig {
     }
 
     /// Sets the trust anchor for optional client authentication.
+    ///
+    /// The verified certificate of the client, if any, can be obtained with
+    /// the [`ClientCert`] extractor.
     #[must_use]
     pub fn client_auth_optional(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
         self.client_auth = TlsClientAuth::Optional(trust_anchor.into());
@@ -217,13 +220,16 @@ impl RustlsConfig {
     }
 
     /// Sets the trust anchor for required client authentication.
+    ///
+    /// The verified certificate of the client can be obtained with the
+    /// [`ClientCert`] extractor.
     #[must_use]
     pub fn client_auth_required(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
         self.client_auth = TlsClientAuth::Required(trust_anchor.into());
         self
     }
 
-    fn create_server_config(&self) -> IoResult<ServerConfig> {
+    pub(crate) fn create_server_config(&self) -> IoResult<ServerConfig> {
         let fallback = self
             .fallback
             .as_ref()
@@ -368,6 +374,8 @@ where
     async fn accept(&mut self) -> IoResult<(Self::Io, LocalAddr, RemoteAddr, Scheme)> {
         loop {
             tokio::select! {
+                biased;
+
                 res = self.config_stream.next() => {
                     if let Some(tls_config) = res {
                         match tls_config.create_server_config() {
@@ -393,7 +401,13 @@ where
                         None => return Err(IoError::new(ErrorKind::Other, "no valid tls config.")),
                     };
 
-                    let stream = HandshakeStream::new(tls_acceptor.accept(stream));
+                    let stream = HandshakeStream::new(tls_acceptor.accept(stream).map_ok(|stream| {
+                        if let Some(certs) = stream.get_ref().1.peer_certificates() {
+                            let chain = certs.iter().map(|cert| cert.0.clone()).collect();
+                            connection_info::update(|info| info.client_cert = ClientCert::from_chain(chain));
+                        }
+                        stream
+                    }));
                     return Ok((stream, local_addr, remote_addr, Scheme::HTTPS));
                 }
             }
@@ -424,7 +438,11 @@ mod tests {
     use tokio_rustls::rustls::{ClientConfig, ServerName};
 
     use super::*;
-    use crate::listener::TcpListener;
+    use crate::{
+        handler,
+        listener::{TcpListener, TlsConfigHandle},
+        Server,
+    };
 
     #[tokio::test]
     async fn tls_listener() {
@@ -458,4 +476,160 @@ mod tests {
         let (mut stream, _, _, _) = acceptor.accept().await.unwrap();
         assert_eq!(stream.read_i32().await.unwrap(), 10);
     }
+
+    #[tokio::test]
+    async fn client_cert() {
+        #[handler(internal)]
+        fn index(cert: Option<ClientCert>) -> String {
+            match cert {
+                Some(cert) => format!("{}|{}", cert.subject(), cert.chain().len()),
+                None => "anonymous".to_string(),
+            }
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .rustls(
+                RustlsConfig::new()
+                    .fallback(
+                        RustlsCertificate::new()
+                            .cert(include_bytes!("certs/cert1.pem").as_ref())
+                            .key(include_bytes!("certs/key1.pem").as_ref()),
+                    )
+                    .client_auth_optional(include_bytes!("certs/ca1.pem").as_ref()),
+            )
+            .into_acceptor()
+            .await
+            .unwrap();
+        let local_addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(index));
+
+        async fn request(local_addr: std::net::SocketAddr, with_cert: bool) -> String {
+            let config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(
+                    read_trust_anchor(include_bytes!("certs/chain1.pem")).unwrap(),
+                );
+            let config = if with_cert {
+                let certs =
+                    rustls_pemfile::certs(&mut include_bytes!("certs/client_chain1.pem").as_ref())
+                        .unwrap()
+                        .into_iter()
+                        .map(Certificate)
+                        .collect();
+                let key = rustls_pemfile::pkcs8_private_keys(
+                    &mut include_bytes!("certs/client_key1.pem").as_ref(),
+                )
+                .unwrap()
+                .remove(0);
+                config.with_single_cert(certs, PrivateKey(key)).unwrap()
+            } else {
+                config.with_no_client_auth()
+            };
+
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+            let domain = ServerName::try_from("testserver.com").unwrap();
+            let stream = TcpStream::connect(local_addr).await.unwrap();
+            let mut stream = connector.connect(domain, stream).await.unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nhost: testserver.com\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut resp = String::new();
+            let _ = stream.read_to_string(&mut resp).await;
+            resp
+        }
+
+        let resp = request(local_addr, true).await;
+        assert!(resp.ends_with("CN=ponytown client|3"), "{resp}");
+
+        let resp = request(local_addr, false).await;
+        assert!(resp.ends_with("anonymous"), "{resp}");
+    }
+
+    #[tokio::test]
+    async fn reload_config() {
+        #[handler(internal)]
+        fn index() -> &'static str {
+            "ok"
+        }
+
+        fn config() -> RustlsConfig {
+            RustlsConfig::new().fallback(
+                RustlsCertificate::new()
+                    .cert(include_bytes!("certs/cert1.pem").as_ref())
+                    .key(include_bytes!("certs/key1.pem").as_ref()),
+            )
+        }
+
+        let (handle, config_stream) = TlsConfigHandle::new(config());
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .rustls(config_stream)
+            .into_acceptor()
+            .await
+            .unwrap();
+        let local_addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(index));
+
+        async fn request(local_addr: std::net::SocketAddr) -> String {
+            let config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(
+                    read_trust_anchor(include_bytes!("certs/chain1.pem")).unwrap(),
+                )
+                .with_no_client_auth();
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+            let domain = ServerName::try_from("testserver.com").unwrap();
+            let stream = TcpStream::connect(local_addr).await.unwrap();
+            let mut resp = String::new();
+            if let Ok(mut stream) = connector.connect(domain, stream).await {
+                if stream
+                    .write_all(
+                        b"GET / HTTP/1.1\r\nhost: testserver.com\r\nconnection: close\r\n\r\n",
+                    )
+                    .await
+                    .is_ok()
+                {
+                    let _ = stream.read_to_string(&mut resp).await;
+                }
+            }
+            resp
+        }
+
+        // an established connection is not affected by the reload
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(read_trust_anchor(include_bytes!("certs/chain1.pem")).unwrap())
+            .with_no_client_auth();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+        let mut established = connector
+            .connect(
+                ServerName::try_from("testserver.com").unwrap(),
+                TcpStream::connect(local_addr).await.unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(request(local_addr).await.ends_with("ok"));
+
+        assert!(
+            handle.reload(config().client_auth_required(include_bytes!("certs/ca1.pem").as_ref()))
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!request(local_addr).await.ends_with("ok"));
+
+        established
+            .write_all(b"GET / HTTP/1.1\r\nhost: testserver.com\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut resp = String::new();
+        established.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.ends_with("ok"));
+
+        // the last config is kept after the handle is dropped
+        assert!(handle.reload(config()));
+        drop(handle);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(request(local_addr).await.ends_with("ok"));
+        assert!(request(local_addr).await.ends_with("ok"));
+    }
 }
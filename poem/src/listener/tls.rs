@@ -1,6 +1,7 @@
 use std::io::Result as IoResult;
 
-use futures_util::Stream;
+use futures_util::{stream::BoxStream, Stream, StreamExt};
+use tokio::sync::mpsc;
 
 /// Represents a type that can convert into tls config stream.
 #[cfg(any(feature = "rustls", feature = "native-tls", feature = "openssl-tls"))]
@@ -11,3 +12,83 @@ pub trait IntoTlsConfigStream<C>: Send + 'static {
     /// Consume itself and return tls config stream.
     fn into_stream(self) -> IoResult<Self::Stream>;
 }
+
+/// A handle that can be used to replace the tls config of a listener at
+/// runtime.
+///
+/// [`TlsConfigHandle::new`] returns the handle together with a config stream
+/// that should be passed to the listener. Each config sent with
+/// [`TlsConfigHandle::reload`] is used for the connections accepted
+/// afterwards, while the connections that are already established keep using
+/// the config they were accepted with.
+///
+/// Dropping all the handles doesn't stop the listener, which keeps using the
+/// last config.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use poem::listener::{Listener, RustlsCertificate, RustlsConfig, TcpListener, TlsConfigHandle};
+///
+/// fn load_config() -> RustlsConfig {
+///     RustlsConfig::new().fallback(
+///         RustlsCertificate::new()
+///             .cert(std::fs::read("cert.pem").unwrap())
+///             .key(std::fs::read("key.pem").unwrap()),
+///     )
+/// }
+///
+/// let (handle, config_stream) = TlsConfigHandle::new(load_config());
+/// let listener = TcpListener::bind("127.0.0.1:443").rustls(config_stream);
+///
+/// tokio::spawn(async move {
+///     loop {
+///         // reload the renewed certificate every day
+///         tokio::time::sleep(Duration::from_secs(60 * 60 * 24)).await;
+///         if !handle.reload(load_config()) {
+///             break;
+///         }
+///     }
+/// });
+/// ```
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(feature = "rustls", feature = "native-tls", feature = "openssl-tls")))
+)]
+pub struct TlsConfigHandle<C> {
+    tx: mpsc::UnboundedSender<C>,
+}
+
+impl<C> Clone for TlsConfigHandle<C> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<C: Send + 'static> TlsConfigHandle<C> {
+    /// Create a handle and the config stream to be passed to the listener,
+    /// which yields the `initial` config first.
+    pub fn new(initial: C) -> (Self, BoxStream<'static, C>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let stream = futures_util::stream::once(futures_util::future::ready(initial))
+            .chain(futures_util::stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|config| (config, rx))
+            }))
+            // keep the last config after all the handles are dropped
+            .chain(futures_util::stream::pending())
+            .boxed();
+        (Self { tx }, stream)
+    }
+
+    /// Replace the tls config of the listener.
+    ///
+    /// Returns `false` if the listener has been dropped. If the config is
+    /// invalid, an error is logged and the previous config remains in use.
+    pub fn reload(&self, config: C) -> bool {
+        self.tx.send(config).is_ok()
+    }
+}
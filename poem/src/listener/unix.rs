@@ -1,6 +1,12 @@
-use std::{io::Result, path::Path};
+use std::{
+    fs::Permissions,
+    io::{Error, ErrorKind, Result},
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
 
 use http::uri::Scheme;
+use nix::unistd::{Gid, Uid};
 use tokio::{
     io::Result as IoResult,
     net::{UnixListener as TokioUnixListener, UnixStream},
@@ -12,15 +18,82 @@ use crate::{
 };
 
 /// A Unix domain socket listener.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::listener::UnixListener;
+///
+/// let listener = UnixListener::bind("/run/poem/poem.sock")
+///     .permissions(0o660)
+///     .owner(None, Some(33))
+///     .remove_stale_socket(true)
+///     .remove_on_drop(true);
+/// ```
 #[cfg_attr(docsrs, doc(cfg(unix)))]
 pub struct UnixListener<T> {
     path: T,
+    permissions: Option<u32>,
+    owner: Option<(Option<u32>, Option<u32>)>,
+    remove_stale_socket: bool,
+    remove_on_drop: bool,
 }
 
 impl<T> UnixListener<T> {
     /// Binds to the provided address, and returns a [`UnixListener<T>`].
     pub fn bind(path: T) -> Self {
-        Self { path }
+        Self {
+            path,
+            permissions: None,
+            owner: None,
+            remove_stale_socket: false,
+            remove_on_drop: false,
+        }
+    }
+
+    /// Sets the permissions of the socket file after it is created, for
+    /// example `0o660` to allow a reverse proxy in the same group to
+    /// connect.
+    #[must_use]
+    pub fn permissions(self, mode: u32) -> Self {
+        Self {
+            permissions: Some(mode),
+            ..self
+        }
+    }
+
+    /// Sets the owner and the group of the socket file after it is created.
+    ///
+    /// `None` leaves the corresponding id unchanged.
+    #[must_use]
+    pub fn owner(self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        Self {
+            owner: Some((uid, gid)),
+            ..self
+        }
+    }
+
+    /// Specify whether a socket file left by a previous process should be
+    /// removed before binding. Defaults to `false`.
+    ///
+    /// The file is only removed if it is a socket that nobody is listening
+    /// on, so a running server is never replaced.
+    #[must_use]
+    pub fn remove_stale_socket(self, enable: bool) -> Self {
+        Self {
+            remove_stale_socket: enable,
+            ..self
+        }
+    }
+
+    /// Specify whether the socket file should be removed when the acceptor is
+    /// dropped, for example when the server shuts down. Defaults to `false`.
+    #[must_use]
+    pub fn remove_on_drop(self, enable: bool) -> Self {
+        Self {
+            remove_on_drop: enable,
+            ..self
+        }
     }
 }
 
@@ -29,20 +102,85 @@ impl<T: AsRef<Path> + Send> Listener for UnixListener<T> {
     type Acceptor = UnixAcceptor;
 
     async fn into_acceptor(self) -> IoResult<Self::Acceptor> {
-        let listener = TokioUnixListener::bind(self.path)?;
+        let path = self.path.as_ref();
+
+        if self.remove_stale_socket {
+            remove_stale_socket(path)?;
+        }
+
+        let listener = TokioUnixListener::bind(path)?;
+        let guard = self.remove_on_drop.then(|| RemoveOnDrop::new(path));
+
+        if let Some(mode) = self.permissions {
+            std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+        }
+
+        if let Some((uid, gid)) = self.owner {
+            nix::unistd::chown(path, uid.map(Uid::from_raw), gid.map(Gid::from_raw))?;
+        }
+
         let local_addr = listener.local_addr().map(|addr| LocalAddr(addr.into()))?;
         Ok(UnixAcceptor {
             local_addr,
             listener,
+            _remove_on_drop: guard.transpose()?,
+        })
+    }
+}
+
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            match std::os::unix::net::UnixStream::connect(path) {
+                Ok(_) => Err(Error::new(
+                    ErrorKind::AddrInUse,
+                    format!("`{}` is used by another server", path.display()),
+                )),
+                Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                    std::fs::remove_file(path)
+                }
+                Err(err) => Err(err),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Removes the socket file when dropped, unless it has been replaced by
+/// another one.
+struct RemoveOnDrop {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+}
+
+impl RemoveOnDrop {
+    fn new(path: &Path) -> Result<Self> {
+        let metadata = std::fs::symlink_metadata(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
         })
     }
 }
 
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        if let Ok(metadata) = std::fs::symlink_metadata(&self.path) {
+            if metadata.dev() == self.dev && metadata.ino() == self.ino {
+                let _ = std::fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
 /// A acceptor that accepts connections.
 #[cfg_attr(docsrs, doc(cfg(unix)))]
 pub struct UnixAcceptor {
     local_addr: LocalAddr,
     listener: TokioUnixListener,
+    _remove_on_drop: Option<RemoveOnDrop>,
 }
 
 impl UnixAcceptor {
@@ -53,6 +191,7 @@ impl UnixAcceptor {
         Ok(Self {
             local_addr,
             listener,
+            _remove_on_drop: None,
         })
     }
 }
@@ -104,4 +243,35 @@ mod tests {
         drop(acceptor);
         std::fs::remove_file("test-socket").unwrap();
     }
+
+    #[tokio::test]
+    async fn socket_file_options() {
+        let path = "test-socket-options";
+        std::os::unix::net::UnixDatagram::bind(path).unwrap();
+        assert!(UnixListener::bind(path).into_acceptor().await.is_err());
+
+        let acceptor = UnixListener::bind(path)
+            .permissions(0o600)
+            .remove_stale_socket(true)
+            .remove_on_drop(true)
+            .into_acceptor()
+            .await
+            .unwrap();
+        let metadata = std::fs::metadata(path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+        assert_eq!(
+            UnixListener::bind(path)
+                .remove_stale_socket(true)
+                .into_acceptor()
+                .await
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::AddrInUse
+        );
+
+        drop(acceptor);
+        assert!(!Path::new(path).exists());
+    }
 }
@@ -0,0 +1,104 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use super::Lock;
+use crate::{web::Clock, Result};
+
+/// A lock using memory, which coordinates the tasks of a single instance.
+#[derive(Default)]
+pub struct MemoryLock {
+    locks: Mutex<HashMap<String, (String, Instant)>>,
+    next_token: AtomicU64,
+    clock: Clock,
+}
+
+impl MemoryLock {
+    /// Create a `MemoryLock`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the clock used for the expiration of the locks.
+    ///
+    /// Default is the system clock.
+    #[must_use]
+    pub fn clock(self, clock: impl Into<Clock>) -> Self {
+        Self {
+            clock: clock.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Lock for MemoryLock {
+    async fn try_acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>> {
+        let now = self.clock.instant();
+        let mut locks = self.locks.lock();
+        locks.retain(|_, (_, expires_at)| *expires_at > now);
+        if locks.contains_key(key) {
+            return Ok(None);
+        }
+
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed).to_string();
+        locks.insert(key.to_string(), (token.clone(), now + ttl));
+        Ok(Some(token))
+    }
+
+    async fn release(&self, key: &str, token: &str) -> Result<()> {
+        let mut locks = self.locks.lock();
+        if matches!(locks.get(key), Some((current, _)) if current == token) {
+            locks.remove(key);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::MockClock;
+
+    #[tokio::test]
+    async fn expiration() {
+        let clock = MockClock::new();
+        let lock = MemoryLock::new().clock(clock.clone());
+
+        let token = lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_none());
+
+        clock.advance(Duration::from_secs(10));
+        let new_token = lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the expired token does not release the new lock
+        lock.release("a", &token).await.unwrap();
+        assert!(lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_none());
+        lock.release("a", &new_token).await.unwrap();
+        assert!(lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
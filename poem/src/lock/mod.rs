@@ -0,0 +1,184 @@
+//! Locks coordinating the concurrent tasks and the instances of an
+//! application.
+//!
+//! The [`Lock`] trait is implemented by [`MemoryLock`], which coordinates
+//! the tasks of a single instance, and by [`RedisLock`] with the
+//! `redis-lock` feature, which coordinates all the instances using the same
+//! redis server.
+//!
+//! The locks are acquired for a time to live, so that a lock held by an
+//! instance which crashed is eventually released. They are used by the
+//! [`RenderCache`](crate::middleware::RenderCache), the
+//! [`IdempotencyKey`](crate::middleware::IdempotencyKey) middleware and the
+//! [`Schedule`](crate::tasks::Schedule).
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use poem::lock::{with_lock, MemoryLock};
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let lock = MemoryLock::new();
+//! let report = with_lock(&lock, "report", Duration::from_secs(60), async {
+//!     // only one task generates the report at a time
+//!     "report".to_string()
+//! })
+//! .await
+//! .unwrap();
+//! assert_eq!(report, "report");
+//! # });
+//! ```
+
+mod memory;
+#[cfg(feature = "redis-lock")]
+mod redis_lock;
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+pub use memory::MemoryLock;
+#[cfg(feature = "redis-lock")]
+#[cfg_attr(docsrs, doc(cfg(feature = "redis-lock")))]
+pub use redis_lock::RedisLock;
+
+use crate::Result;
+
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Represents a set of locks identified by keys.
+#[async_trait::async_trait]
+pub trait Lock: Send + Sync {
+    /// Acquires the lock with the specified key for `ttl` and returns a token
+    /// for releasing it, or returns `None` if the lock is held.
+    async fn try_acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>>;
+
+    /// Releases the lock, if it is still held with the token.
+    async fn release(&self, key: &str, token: &str) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: Lock + ?Sized> Lock for Arc<T> {
+    async fn try_acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>> {
+        self.as_ref().try_acquire(key, ttl).await
+    }
+
+    async fn release(&self, key: &str, token: &str) -> Result<()> {
+        self.as_ref().release(key, token).await
+    }
+}
+
+/// Waits for the lock with the specified key, acquires it for `ttl`, and
+/// releases it after `fut` completes.
+///
+/// If `fut` is cancelled, or takes longer than `ttl`, the lock is released
+/// when it expires.
+pub async fn with_lock<L, F>(lock: &L, key: &str, ttl: Duration, fut: F) -> Result<F::Output>
+where
+    L: Lock + ?Sized,
+    F: Future,
+{
+    let mut delay = MIN_RETRY_DELAY;
+    let token = loop {
+        if let Some(token) = lock.try_acquire(key, ttl).await? {
+            break token;
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    };
+    Ok(run_locked(lock, key, &token, fut).await)
+}
+
+/// Acquires the lock with the specified key for `ttl` and releases it after
+/// `fut` completes, or returns `None` without running `fut` if the lock is
+/// held.
+pub async fn try_with_lock<L, F>(
+    lock: &L,
+    key: &str,
+    ttl: Duration,
+    fut: F,
+) -> Result<Option<F::Output>>
+where
+    L: Lock + ?Sized,
+    F: Future,
+{
+    match lock.try_acquire(key, ttl).await? {
+        Some(token) => Ok(Some(run_locked(lock, key, &token, fut).await)),
+        None => Ok(None),
+    }
+}
+
+async fn run_locked<L, F>(lock: &L, key: &str, token: &str, fut: F) -> F::Output
+where
+    L: Lock + ?Sized,
+    F: Future,
+{
+    let output = fut.await;
+    if let Err(err) = lock.release(key, token).await {
+        tracing::warn!(key = key, error = %err, "failed to release the lock");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn exclusive() {
+        let lock = Arc::new(MemoryLock::new());
+        let running = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..5)
+            .map(|_| {
+                let lock = lock.clone();
+                let running = running.clone();
+                tokio::spawn(async move {
+                    with_lock(&lock, "a", Duration::from_secs(10), async {
+                        assert_eq!(running.fetch_add(1, Ordering::SeqCst), 0);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                    .await
+                    .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn try_lock() {
+        let lock = MemoryLock::new();
+        let token = lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            try_with_lock(&lock, "a", Duration::from_secs(10), async { 1 })
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            try_with_lock(&lock, "b", Duration::from_secs(10), async { 2 })
+                .await
+                .unwrap(),
+            Some(2)
+        );
+
+        lock.release("a", &token).await.unwrap();
+        assert_eq!(
+            try_with_lock(&lock, "a", Duration::from_secs(10), async { 3 })
+                .await
+                .unwrap(),
+            Some(3)
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use redis::{aio::ConnectionLike, Script};
+
+use super::Lock;
+use crate::{error::RedisLockError, Result};
+
+/// Deletes the key only if it still holds the token, so that a lock which
+/// expired and was acquired by another instance is not released.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("get", KEYS[1]) == ARGV[1] then
+    return redis.call("del", KEYS[1])
+else
+    return 0
+end
+"#;
+
+/// A lock using redis, which coordinates all the instances using the same
+/// redis server.
+///
+/// # Errors
+///
+/// - [`RedisLockError`]
+#[cfg_attr(docsrs, doc(cfg(feature = "redis-lock")))]
+pub struct RedisLock<T> {
+    connection: T,
+    prefix: String,
+}
+
+impl<T> RedisLock<T> {
+    /// Create a `RedisLock` storing the locks in the keys prefixed with
+    /// `lock:`.
+    pub fn new(connection: T) -> Self {
+        Self {
+            connection,
+            prefix: "lock:".to_string(),
+        }
+    }
+
+    /// Sets the prefix of the keys of the locks.
+    #[must_use]
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: ConnectionLike + Clone + Sync + Send> Lock for RedisLock<T> {
+    async fn try_acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>> {
+        let token = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect::<String>();
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{key}", self.prefix))
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(RedisLockError::Redis)?;
+        Ok(reply.map(|_| token))
+    }
+
+    async fn release(&self, key: &str, token: &str) -> Result<()> {
+        Script::new(RELEASE_SCRIPT)
+            .key(format!("{}{key}", self.prefix))
+            .arg(token)
+            .invoke_async::<_, i64>(&mut self.connection.clone())
+            .await
+            .map_err(RedisLockError::Redis)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::{aio::ConnectionManager, Client, ConnectionLike};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn redis_lock() {
+        let mut client = match Client::open("redis://127.0.0.1/") {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+        if !client.check_connection() {
+            panic!("redis server is not running");
+        }
+
+        let lock = RedisLock::new(ConnectionManager::new(client).await.unwrap())
+            .prefix(format!("poem-test-lock-{}:", std::process::id()));
+        let token = lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_none());
+
+        lock.release("a", "other").await.unwrap();
+        assert!(lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_none());
+        lock.release("a", &token).await.unwrap();
+        let token = lock
+            .try_acquire("a", Duration::from_secs(10))
+            .await
+            .unwrap()
+            .unwrap();
+        lock.release("a", &token).await.unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use crate::{
+    http::{header::HeaderName, HeaderValue, Version},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// Middleware for advertising alternative services with the `Alt-Svc`
+/// header, so that clients can switch to HTTP/3 served by a
+/// [`QuicListener`](crate::listener::QuicListener).
+///
+/// The header is not added to the responses of HTTP/3 requests or to the
+/// responses that already contain it.
+///
+/// Reference: <https://www.rfc-editor.org/rfc/rfc7838>
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{
+///     handler, middleware::AltSvc, test::TestClient, Endpoint, EndpointExt, Request, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let app = Route::new()
+///     .at("/", index)
+///     .with(AltSvc::new().h3(443).max_age(Duration::from_secs(3600)));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(app).get("/").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_header("alt-svc", "h3=\":443\"; ma=3600");
+/// # });
+/// ```
+#[derive(Default)]
+pub struct AltSvc {
+    services: Vec<String>,
+    max_age: Option<Duration>,
+}
+
+impl AltSvc {
+    /// Create new `AltSvc` middleware.
+    #[must_use]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Advertises HTTP/3 on the specified UDP port of the same host.
+    #[must_use]
+    pub fn h3(self, port: u16) -> Self {
+        self.service("h3", format!(":{port}"))
+    }
+
+    /// Advertises an alternative service with the specified ALPN protocol id
+    /// and authority, for example `("h3", "alt.example.com:443")`.
+    #[must_use]
+    pub fn service(mut self, protocol_id: impl Into<String>, authority: impl AsRef<str>) -> Self {
+        self.services
+            .push(format!("{}=\"{}\"", protocol_id.into(), authority.as_ref()));
+        self
+    }
+
+    /// Sets how long the alternative services are considered fresh by
+    /// clients. If it is not set, clients use the default of 24 hours.
+    #[must_use]
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for AltSvc {
+    type Output = AltSvcEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        let value = self
+            .services
+            .iter()
+            .map(|service| match self.max_age {
+                Some(max_age) => format!("{}; ma={}", service, max_age.as_secs()),
+                None => service.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        AltSvcEndpoint {
+            inner: ep,
+            value: HeaderValue::from_str(&value).ok(),
+        }
+    }
+}
+
+/// Endpoint for AltSvc middleware.
+pub struct AltSvcEndpoint<E> {
+    inner: E,
+    value: Option<HeaderValue>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for AltSvcEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let version = req.version();
+        let mut resp = self.inner.call(req).await?.into_response();
+        let name = HeaderName::from_static("alt-svc");
+
+        if let Some(value) = &self.value {
+            if version != Version::HTTP_3 && !resp.headers().contains_key(&name) {
+                resp.headers_mut().insert(name, value.clone());
+            }
+        }
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[tokio::test]
+    async fn alt_svc() {
+        #[handler(internal)]
+        fn index() {}
+
+        let ep = index.with(
+            AltSvc::new()
+                .h3(443)
+                .service("h3", "alt.example.com:8443")
+                .max_age(Duration::from_secs(60)),
+        );
+        let cli = TestClient::new(&ep);
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header(
+            "alt-svc",
+            "h3=\":443\"; ma=60, h3=\"alt.example.com:8443\"; ma=60",
+        );
+
+        let resp = ep
+            .call(Request::builder().version(Version::HTTP_3).finish())
+            .await
+            .unwrap();
+        assert!(!resp.headers().contains_key("alt-svc"));
+    }
+}
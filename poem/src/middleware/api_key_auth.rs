@@ -0,0 +1,436 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap, VecDeque},
+    hash::{BuildHasher, Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use tracing::Instrument;
+
+use crate::{
+    error::ApiKeyError,
+    web::{ApiKey, Clock, Principal},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// Represents a resolver of the API keys for the [`ApiKeyAuth`] middleware,
+/// such as a lookup in a database.
+#[async_trait::async_trait]
+pub trait ApiKeyResolver: Send + Sync {
+    /// Returns the API key with the specified secret, or `None` if it is not
+    /// a valid key.
+    async fn resolve(&self, key: &str) -> Result<Option<ApiKey>>;
+}
+
+#[async_trait::async_trait]
+impl<T: ApiKeyResolver + ?Sized> ApiKeyResolver for Arc<T> {
+    async fn resolve(&self, key: &str) -> Result<Option<ApiKey>> {
+        self.as_ref().resolve(key).await
+    }
+}
+
+/// Resolves the keys of a map from the secret keys to the API keys.
+#[async_trait::async_trait]
+impl ApiKeyResolver for HashMap<String, ApiKey> {
+    async fn resolve(&self, key: &str) -> Result<Option<ApiKey>> {
+        Ok(self.get(key).cloned())
+    }
+}
+
+/// The valid keys, identified by a hash of their secret so that the secrets
+/// are not kept in memory.
+#[derive(Default)]
+struct Cache {
+    keys: HashMap<u128, (ApiKey, Instant)>,
+    /// The hashes in the order their validations expire, which is the order
+    /// they were added in since they have the same ttl.
+    expirations: VecDeque<(Instant, u128)>,
+    hashers: [RandomState; 2],
+}
+
+impl Cache {
+    fn hash(&self, key: &str) -> u128 {
+        let [high, low] = [&self.hashers[0], &self.hashers[1]].map(|hasher| {
+            let mut hasher = hasher.build_hasher();
+            key.hash(&mut hasher);
+            hasher.finish()
+        });
+        (u128::from(high) << 64) | u128::from(low)
+    }
+
+    fn get(&mut self, hash: u128, now: Instant) -> Option<ApiKey> {
+        while self
+            .expirations
+            .front()
+            .map_or(false, |(expires_at, _)| *expires_at <= now)
+        {
+            self.pop();
+        }
+        self.keys.get(&hash).map(|(api_key, _)| api_key.clone())
+    }
+
+    fn insert(&mut self, hash: u128, api_key: ApiKey, expires_at: Instant, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        while self.keys.len() >= capacity {
+            self.pop();
+        }
+        self.keys.insert(hash, (api_key, expires_at));
+        self.expirations.push_back((expires_at, hash));
+    }
+
+    fn pop(&mut self) {
+        if let Some((expires_at, hash)) = self.expirations.pop_front() {
+            // the key may have been validated again since
+            if self.keys.get(&hash).map(|(_, at)| *at) == Some(expires_at) {
+                self.keys.remove(&hash);
+            }
+        }
+    }
+}
+
+/// Middleware for authenticating the requests with an API key, sent in a
+/// header or in the query string.
+///
+/// The keys are validated with the [`ApiKeyResolver`], and the valid keys are
+/// cached for the [`cache_ttl`](ApiKeyAuth::cache_ttl), so that the resolver
+/// is not called on each request. The invalid keys are not cached, so that
+/// the requests with random keys do not fill the cache. The [`ApiKey`] is available
+/// to the handlers with the extractor of the same name, and its id is used
+/// as the [`Principal`] of the request, so that the
+/// [`RateLimited`](crate::rate_limit::RateLimited) extractor and the
+/// [`UsageMeter`](crate::middleware::UsageMeter) middleware count the
+/// requests per key. The `ApiKey` is also added to the data of the response,
+/// for the metrics middlewares.
+///
+/// # Errors
+///
+/// - [`ApiKeyError`]
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use poem::{
+///     handler, http::StatusCode, middleware::ApiKeyAuth, test::TestClient, web::ApiKey,
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index(key: ApiKey) -> String {
+///     format!("hello {}", key.owner)
+/// }
+///
+/// let mut keys = HashMap::new();
+/// keys.insert(
+///     "sk_4f2a9c".to_string(),
+///     ApiKey::new("key-1", "sunli").scope("orders:read"),
+/// );
+/// let cli = TestClient::new(index.with(ApiKeyAuth::new(keys)));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .header("x-api-key", "sk_4f2a9c")
+///     .send()
+///     .await
+///     .assert_text("hello sunli")
+///     .await;
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::UNAUTHORIZED);
+/// # });
+/// ```
+pub struct ApiKeyAuth<T> {
+    resolver: Arc<T>,
+    header: Option<String>,
+    query: Option<String>,
+    cache_ttl: Duration,
+    cache_capacity: usize,
+    optional: bool,
+}
+
+impl<T: ApiKeyResolver> ApiKeyAuth<T> {
+    /// Create an `ApiKeyAuth` middleware with the specified resolver.
+    pub fn new(resolver: T) -> Self {
+        Self {
+            resolver: Arc::new(resolver),
+            header: Some("x-api-key".to_string()),
+            query: None,
+            cache_ttl: Duration::from_secs(60),
+            cache_capacity: 10_000,
+            optional: false,
+        }
+    }
+
+    /// Sets the name of the header containing the key. Defaults to
+    /// `X-Api-Key`.
+    #[must_use]
+    pub fn header(self, name: impl Into<String>) -> Self {
+        Self {
+            header: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Reads the key from the query parameter with the specified name, when
+    /// the header is missing.
+    ///
+    /// The query strings are often logged, so the keys sent in them are more
+    /// likely to leak.
+    #[must_use]
+    pub fn query(self, name: impl Into<String>) -> Self {
+        Self {
+            query: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Sets how long the validations are cached. Defaults to 60 seconds.
+    ///
+    /// A revoked key is still accepted until its validation expires.
+    #[must_use]
+    pub fn cache_ttl(self, cache_ttl: Duration) -> Self {
+        Self { cache_ttl, ..self }
+    }
+
+    /// Sets the maximum number of cached keys, the oldest validations being
+    /// removed first. Defaults to `10000`.
+    #[must_use]
+    pub fn cache_capacity(self, cache_capacity: usize) -> Self {
+        Self {
+            cache_capacity,
+            ..self
+        }
+    }
+
+    /// Allows the requests without a key, which reach the endpoint without
+    /// an [`ApiKey`]. Defaults to `false`.
+    ///
+    /// The requests with an invalid key are still rejected.
+    #[must_use]
+    pub fn optional(self, optional: bool) -> Self {
+        Self { optional, ..self }
+    }
+}
+
+impl<E: Endpoint, T: ApiKeyResolver> Middleware<E> for ApiKeyAuth<T> {
+    type Output = ApiKeyAuthEndpoint<E, T>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ApiKeyAuthEndpoint {
+            inner: ep,
+            resolver: self.resolver.clone(),
+            header: self.header.clone(),
+            query: self.query.clone(),
+            cache_ttl: self.cache_ttl,
+            cache_capacity: self.cache_capacity,
+            optional: self.optional,
+            cache: Default::default(),
+        }
+    }
+}
+
+/// Endpoint for ApiKeyAuth middleware.
+pub struct ApiKeyAuthEndpoint<E, T> {
+    inner: E,
+    resolver: Arc<T>,
+    header: Option<String>,
+    query: Option<String>,
+    cache_ttl: Duration,
+    cache_capacity: usize,
+    optional: bool,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl<E, T: ApiKeyResolver> ApiKeyAuthEndpoint<E, T> {
+    fn key(&self, req: &Request) -> Option<String> {
+        if let Some(key) = self.header.as_deref().and_then(|name| req.header(name)) {
+            return Some(key.to_string());
+        }
+
+        let name = self.query.as_deref()?;
+        serde_urlencoded::from_str::<Vec<(String, String)>>(req.uri().query().unwrap_or_default())
+            .ok()?
+            .into_iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value)
+    }
+
+    async fn resolve(&self, key: &str, now: Instant) -> Result<Option<ApiKey>> {
+        let hash = {
+            let mut cache = self.cache.lock();
+            let hash = cache.hash(key);
+            if let Some(api_key) = cache.get(hash, now) {
+                return Ok(Some(api_key));
+            }
+            hash
+        };
+
+        let api_key = self.resolver.resolve(key).await?;
+        if let Some(api_key) = &api_key {
+            self.cache.lock().insert(
+                hash,
+                api_key.clone(),
+                now + self.cache_ttl,
+                self.cache_capacity,
+            );
+        }
+        Ok(api_key)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint, T: ApiKeyResolver> Endpoint for ApiKeyAuthEndpoint<E, T> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let key = match self.key(&req) {
+            Some(key) if !key.is_empty() => key,
+            _ if self.optional => {
+                return self.inner.call(req).await.map(IntoResponse::into_response)
+            }
+            _ => return Err(ApiKeyError::Missing.into()),
+        };
+        let api_key = self
+            .resolve(&key, Clock::of(&req).instant())
+            .await?
+            .ok_or(ApiKeyError::Invalid)?;
+
+        req.extensions_mut().insert(Principal(api_key.id.clone()));
+        req.extensions_mut().insert(api_key.clone());
+
+        let span = tracing::info_span!("api_key", api_key = %api_key.id, owner = %api_key.owner);
+        match self.inner.call(req).instrument(span).await {
+            Ok(resp) => {
+                let mut resp = resp.into_response();
+                resp.set_data(api_key);
+                Ok(resp)
+            }
+            Err(mut err) => {
+                err.set_data(api_key);
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient, web::MockClock, EndpointExt};
+
+    struct Counting {
+        keys: HashMap<String, ApiKey>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl ApiKeyResolver for Counting {
+        async fn resolve(&self, key: &str) -> Result<Option<ApiKey>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.keys.resolve(key).await
+        }
+    }
+
+    #[handler(internal)]
+    fn index(key: Option<ApiKey>, principal: Option<Principal>) -> String {
+        match (key, principal) {
+            (Some(key), Some(principal)) => format!("{} {}", key.owner, principal),
+            _ => "anonymous".to_string(),
+        }
+    }
+
+    fn resolver() -> Arc<Counting> {
+        let mut keys = HashMap::new();
+        keys.insert("secret".to_string(), ApiKey::new("key-1", "sunli"));
+        Arc::new(Counting {
+            keys,
+            calls: AtomicUsize::new(0),
+        })
+    }
+
+    #[tokio::test]
+    async fn auth() {
+        let resolver = resolver();
+        let clock = MockClock::new();
+        let cli = TestClient::new(
+            index
+                .with(ApiKeyAuth::new(resolver.clone()).query("api_key"))
+                .data(Clock::from(clock.clone())),
+        );
+
+        let resp = cli.get("/").header("x-api-key", "secret").send().await;
+        assert_eq!(
+            resp.0.data::<ApiKey>(),
+            Some(&ApiKey::new("key-1", "sunli"))
+        );
+        resp.assert_text("sunli key-1").await;
+        cli.get("/")
+            .query("api_key", &"secret")
+            .send()
+            .await
+            .assert_text("sunli key-1")
+            .await;
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        for _ in 0..2 {
+            cli.get("/")
+                .header("x-api-key", "other")
+                .send()
+                .await
+                .assert_status(StatusCode::UNAUTHORIZED);
+        }
+        // the invalid keys are not cached
+        assert_eq!(resolver.calls.load(Ordering::SeqCst), 3);
+
+        clock.advance(Duration::from_secs(60));
+        cli.get("/")
+            .header("x-api-key", "secret")
+            .send()
+            .await
+            .assert_status_is_ok();
+        assert_eq!(resolver.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn cache() {
+        let now = Instant::now();
+        let mut cache = Cache::default();
+        let (a, b, c) = (cache.hash("a"), cache.hash("b"), cache.hash("c"));
+        assert_ne!(a, b);
+        assert_eq!(a, cache.hash("a"));
+
+        let ttl = Duration::from_secs(60);
+        cache.insert(a, ApiKey::new("a", "sunli"), now + ttl, 2);
+        cache.insert(b, ApiKey::new("b", "sunli"), now + ttl * 2, 2);
+        assert_eq!(cache.get(a, now).unwrap().id, "a");
+
+        // the oldest validation is removed when the cache is full
+        cache.insert(c, ApiKey::new("c", "sunli"), now + ttl * 2, 2);
+        assert!(cache.get(a, now).is_none());
+        assert_eq!(cache.get(b, now).unwrap().id, "b");
+
+        assert!(cache.get(c, now + ttl * 2).is_none());
+        assert!(cache.keys.is_empty() && cache.expirations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn optional() {
+        let cli = TestClient::new(index.with(ApiKeyAuth::new(resolver()).optional(true)));
+
+        cli.get("/").send().await.assert_text("anonymous").await;
+        cli.get("/")
+            .header("x-api-key", "other")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+}
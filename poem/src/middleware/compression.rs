@@ -68,6 +68,16 @@ fn parse_accept_encoding(
         .map(|(coding, _)| coding)
 }
 
+/// Returns `true` if the client accepts the brotli encoding.
+#[cfg(feature = "render-cache")]
+pub(crate) fn accepts_brotli(headers: &HeaderMap) -> bool {
+    let algorithms = [CompressionAlgo::BR].into_iter().collect();
+    matches!(
+        parse_accept_encoding(headers, &algorithms),
+        Some(ContentCoding::Brotli | ContentCoding::Star)
+    )
+}
+
 /// Middleware for decompress request body and compress response body.
 ///
 /// It selects the decompression algorithm according to the request
@@ -162,8 +172,10 @@ impl<E: Endpoint> Endpoint for CompressionEndpoint<E> {
                 ContentCoding::Star | ContentCoding::Brotli => CompressionAlgo::BR,
             });
 
-        let resp = self.ep.call(req).await?;
+        let resp = self.ep.call(req).await?.into_response();
         match compress_algo {
+            // already compressed, such as by the `RenderCache` middleware
+            Some(_) if resp.headers().contains_key(header::CONTENT_ENCODING) => Ok(resp),
             Some(algo) => {
                 let mut compress = Compress::new(resp, algo);
                 if let Some(level) = self.level {
@@ -171,7 +183,7 @@ impl<E: Endpoint> Endpoint for CompressionEndpoint<E> {
                 }
                 Ok(compress.into_response())
             }
-            None => Ok(resp.into_response()),
+            None => Ok(resp),
         }
     }
 }
@@ -0,0 +1,306 @@
+use std::{collections::Bound, io::Error as IoError, ops::Range as StdRange};
+
+use futures_util::{StreamExt, TryStreamExt};
+use headers::{
+    ContentLength, ContentRange, ETag, HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch,
+    IfRange, IfUnmodifiedSince, LastModified, Range,
+};
+use http::{header, HeaderMap, Method, StatusCode};
+
+use crate::{Body, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+
+/// Middleware for answering conditional and `Range` requests from the
+/// validators of the responses.
+///
+/// For the successful responses to `GET` and `HEAD` requests:
+///
+/// - If the response has an `ETag` or a `Last-Modified` header, the
+///   `If-Match`, `If-Unmodified-Since`, `If-None-Match` and
+///   `If-Modified-Since` headers are evaluated, and the response is replaced
+///   by `412 Precondition Failed` or `304 Not Modified` when appropriate.
+/// - If the response is `200 OK` with `Accept-Ranges: bytes` and a
+///   `Content-Length`, such as a [`SizedBody`](crate::web::SizedBody), the
+///   first range of the `Range` header is served as `206 Partial Content`,
+///   unless an `If-Range` header does not match the validators.
+///
+/// The handler is still called, so it should only produce the validators
+/// cheaply and leave reading the body to the stream.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Conditional;
+
+impl<E: Endpoint> Middleware<E> for Conditional {
+    type Output = ConditionalEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ConditionalEndpoint { inner: ep }
+    }
+}
+
+/// Endpoint for Conditional middleware.
+pub struct ConditionalEndpoint<E> {
+    inner: E,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ConditionalEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let headers = req.headers().clone();
+        let resp = self.inner.call(req).await?.into_response();
+        if !resp.status().is_success() {
+            return Ok(resp);
+        }
+
+        if let Some(status) = check_preconditions(&headers, resp.headers()) {
+            let mut not_modified = Response::builder().status(status).finish();
+            if status == StatusCode::NOT_MODIFIED {
+                for name in [header::ETAG, header::LAST_MODIFIED, header::CACHE_CONTROL] {
+                    if let Some(value) = resp.headers().get(&name) {
+                        not_modified.headers_mut().insert(name, value.clone());
+                    }
+                }
+            }
+            return Ok(not_modified);
+        }
+
+        Ok(apply_range(&headers, resp))
+    }
+}
+
+/// Returns the status replacing the response if a precondition fails.
+fn check_preconditions(req: &HeaderMap, resp: &HeaderMap) -> Option<StatusCode> {
+    let etag = resp.typed_get::<ETag>();
+    let last_modified = resp.typed_get::<LastModified>().map(Into::into);
+
+    if let Some(if_match) = req.typed_get::<IfMatch>() {
+        if !etag
+            .as_ref()
+            .map_or(if_match.is_any(), |etag| if_match.precondition_passes(etag))
+        {
+            return Some(StatusCode::PRECONDITION_FAILED);
+        }
+    } else if let (Some(if_unmodified_since), Some(last_modified)) =
+        (req.typed_get::<IfUnmodifiedSince>(), last_modified)
+    {
+        if !if_unmodified_since.precondition_passes(last_modified) {
+            return Some(StatusCode::PRECONDITION_FAILED);
+        }
+    }
+
+    if let Some(if_none_match) = req.typed_get::<IfNoneMatch>() {
+        if let Some(etag) = &etag {
+            if !if_none_match.precondition_passes(etag) {
+                return Some(StatusCode::NOT_MODIFIED);
+            }
+        }
+    } else if let (Some(if_modified_since), Some(last_modified)) =
+        (req.typed_get::<IfModifiedSince>(), last_modified)
+    {
+        if !if_modified_since.is_modified(last_modified) {
+            return Some(StatusCode::NOT_MODIFIED);
+        }
+    }
+
+    None
+}
+
+fn apply_range(req: &HeaderMap, mut resp: Response) -> Response {
+    if resp.status() != StatusCode::OK
+        || resp
+            .headers()
+            .get(header::ACCEPT_RANGES)
+            .map(|v| v.as_bytes())
+            != Some(b"bytes")
+        || resp.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return resp;
+    }
+    let (size, range) = match (
+        resp.headers().typed_get::<ContentLength>(),
+        req.typed_get::<Range>(),
+    ) {
+        (Some(ContentLength(size)), Some(range)) => (size, range),
+        _ => return resp,
+    };
+    if let Some(if_range) = req.typed_get::<IfRange>() {
+        if if_range.is_modified(
+            resp.headers().typed_get::<ETag>().as_ref(),
+            resp.headers().typed_get::<LastModified>().as_ref(),
+        ) {
+            return resp;
+        }
+    }
+
+    let (start, end) = match range.iter().next() {
+        // a suffix range, which selects the last `n` bytes
+        Some((Bound::Unbounded, Bound::Included(n))) => (size.saturating_sub(n), size),
+        // a last byte position past the end selects the bytes until the end
+        Some((start, end)) => (
+            match start {
+                Bound::Included(n) => n,
+                Bound::Excluded(n) => n.saturating_add(1),
+                Bound::Unbounded => 0,
+            },
+            match end {
+                Bound::Included(n) => n.saturating_add(1).min(size),
+                Bound::Excluded(n) => n.min(size),
+                Bound::Unbounded => size,
+            },
+        ),
+        None => return resp,
+    };
+    if start >= end {
+        return Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .typed_header(ContentRange::unsatisfied_bytes(size))
+            .finish();
+    }
+
+    resp.set_status(StatusCode::PARTIAL_CONTENT);
+    resp.headers_mut().typed_insert(ContentLength(end - start));
+    resp.headers_mut()
+        .typed_insert(ContentRange::bytes(start..end, size).unwrap());
+    let body = resp.take_body();
+    resp.set_body(slice_body(body, start..end));
+    resp
+}
+
+/// Returns the bytes of `body` in `range`, stopping reading at its end.
+fn slice_body(body: Body, range: StdRange<u64>) -> Body {
+    let StdRange { start, end } = range;
+    let stream = futures_util::stream::try_unfold(
+        (body.into_bytes_stream().boxed(), 0),
+        move |(mut stream, mut pos)| async move {
+            while pos < end {
+                let data = match stream.try_next().await? {
+                    Some(data) => data,
+                    None => break,
+                };
+                let chunk_start = pos;
+                pos += data.len() as u64;
+                if pos <= start {
+                    continue;
+                }
+                let from = start.saturating_sub(chunk_start) as usize;
+                let to = (end.min(pos) - chunk_start) as usize;
+                return Ok::<_, IoError>(Some((data.slice(from..to), (stream, pos))));
+            }
+            Ok(None)
+        },
+    );
+    Body::from_bytes_stream(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{handler, test::TestClient, web::SizedBody, EndpointExt};
+
+    #[handler(internal)]
+    fn index() -> SizedBody {
+        let chunks =
+            ["0123", "4567", "89"].map(|s| Ok::<_, IoError>(Bytes::from_static(s.as_bytes())));
+        SizedBody::from_bytes_stream(futures_util::stream::iter(chunks), 10)
+            .etag("\"abc\"")
+            .last_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(100))
+    }
+
+    #[tokio::test]
+    async fn range() {
+        let cli = TestClient::new(index.with(Conditional));
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("0123456789").await;
+
+        for (range, content_range, text) in [
+            ("bytes=2-5", "bytes 2-5/10", "2345"),
+            ("bytes=4-7", "bytes 4-7/10", "4567"),
+            ("bytes=7-", "bytes 7-9/10", "789"),
+            ("bytes=-3", "bytes 7-9/10", "789"),
+            ("bytes=5-20", "bytes 5-9/10", "56789"),
+            ("bytes=0-18446744073709551615", "bytes 0-9/10", "0123456789"),
+        ] {
+            let resp = cli.get("/").header("range", range).send().await;
+            resp.assert_status(StatusCode::PARTIAL_CONTENT);
+            resp.assert_header("content-range", content_range);
+            resp.assert_header("content-length", text.len().to_string());
+            resp.assert_text(text).await;
+        }
+
+        let resp = cli.get("/").header("range", "bytes=10-20").send().await;
+        resp.assert_status(StatusCode::RANGE_NOT_SATISFIABLE);
+        resp.assert_header("content-range", "bytes */10");
+
+        let resp = cli
+            .get("/")
+            .header("range", "bytes=2-5")
+            .header("if-range", "\"old\"")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_text("0123456789").await;
+
+        let resp = cli
+            .get("/")
+            .header("range", "bytes=2-5")
+            .header("if-range", "\"abc\"")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::PARTIAL_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn preconditions() {
+        let cli = TestClient::new(index.with(Conditional));
+
+        let resp = cli.get("/").header("if-none-match", "\"abc\"").send().await;
+        resp.assert_status(StatusCode::NOT_MODIFIED);
+        resp.assert_header("etag", "\"abc\"");
+
+        cli.get("/")
+            .header("if-none-match", "\"def\"")
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        cli.get("/")
+            .header("if-modified-since", "Thu, 01 Jan 1970 00:01:40 GMT")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+
+        cli.get("/")
+            .header("if-modified-since", "Thu, 01 Jan 1970 00:01:00 GMT")
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        cli.get("/")
+            .header("if-match", "\"def\"")
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+
+        cli.get("/")
+            .header("if-unmodified-since", "Thu, 01 Jan 1970 00:01:00 GMT")
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+
+        cli.post("/")
+            .header("if-none-match", "\"abc\"")
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+}
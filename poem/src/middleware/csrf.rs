@@ -143,6 +143,10 @@ impl Csrf {
     /// valid.
     ///
     /// The default for this value is one day.
+    ///
+    /// The expiration time is authenticated inside the cookie, and checked
+    /// against the system time rather than the [`Clock`](crate::web::Clock)
+    /// of the request.
     #[must_use]
     pub fn ttl(self, ttl: Duration) -> Self {
         Self { ttl, ..self }
@@ -309,4 +313,48 @@ mod tests {
             "invalid token"
         );
     }
+
+    #[tokio::test]
+    async fn expired() {
+        #[handler(internal)]
+        fn login_ui(token: &CsrfToken) -> impl IntoResponse {
+            token.0.to_string()
+        }
+
+        #[handler(internal)]
+        fn login(verifier: &CsrfVerifier, req: &Request) -> &'static str {
+            match verifier.is_valid(req.header(CSRF_TOKEN_NAME).unwrap_or_default()) {
+                true => "ok",
+                false => "invalid",
+            }
+        }
+
+        for (ttl, expected) in [(60, "ok"), (0, "invalid")] {
+            let app = get(login_ui)
+                .post(login)
+                .with(Csrf::new().ttl(Duration::from_secs(ttl)));
+            let resp = app.call(Request::default()).await.unwrap();
+            let cookie = resp
+                .header(header::SET_COOKIE)
+                .map(|cookie| cookie.to_string())
+                .unwrap();
+            let token = resp.into_body().into_string().await.unwrap();
+
+            let resp = app
+                .call(
+                    Request::builder()
+                        .method(Method::POST)
+                        .header(CSRF_TOKEN_NAME, token)
+                        .header(header::COOKIE, cookie)
+                        .finish(),
+                )
+                .await
+                .unwrap()
+                .into_body()
+                .into_string()
+                .await
+                .unwrap();
+            assert_eq!(resp, expected);
+        }
+    }
 }
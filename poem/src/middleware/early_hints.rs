@@ -0,0 +1,178 @@
+use crate::{web::Preload, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+
+/// Middleware for sending the resource hints added with the
+/// [`Preload`] extractor.
+///
+/// The hints are sent as `Link` headers of the response. The server does not
+/// write `103 Early Hints` informational responses, which are not supported
+/// by the underlying HTTP implementation, but CDNs such as Cloudflare and
+/// Fastly learn the `Link` headers of a page and send them as early hints to
+/// the next clients while the page is still being rendered. HTTP/2 server
+/// push is not supported either, but the proxies which still push the
+/// resources do it from the same `Link: rel=preload` headers.
+///
+/// With the `tera` feature, the stylesheets and the scripts used by a
+/// template through the `asset` function are hinted automatically.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EarlyHints;
+
+impl<E: Endpoint> Middleware<E> for EarlyHints {
+    type Output = EarlyHintsEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        EarlyHintsEndpoint { inner: ep }
+    }
+}
+
+/// Endpoint for EarlyHints middleware.
+pub struct EarlyHintsEndpoint<E> {
+    inner: E,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for EarlyHintsEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if req.extensions().get::<Preload>().is_some() {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let preload = Preload::default();
+        req.extensions_mut().insert(preload.clone());
+        let mut resp = self.inner.call(req).await?.into_response();
+        preload.append_to_headers(resp.headers_mut());
+        Ok(resp)
+    }
+}
+
+/// Middleware for opting a route out of the hints of the
+/// [`EarlyHints`] middleware, such as the pages which are not worth
+/// preloading their assets.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     middleware::{EarlyHints, NoPreload},
+///     test::TestClient,
+///     web::Preload,
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index(preload: Preload) {
+///     preload.preload("/app.css", "style");
+/// }
+///
+/// let app = Route::new()
+///     .at("/", index)
+///     .at("/embed", index.with(NoPreload))
+///     .with(EarlyHints);
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/embed").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_header_is_not_exist("link");
+/// # });
+/// ```
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoPreload;
+
+impl<E: Endpoint> Middleware<E> for NoPreload {
+    type Output = NoPreloadEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        NoPreloadEndpoint { inner: ep }
+    }
+}
+
+/// Endpoint for NoPreload middleware.
+pub struct NoPreloadEndpoint<E> {
+    inner: E,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for NoPreloadEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if let Some(preload) = req.extensions().get::<Preload>() {
+            preload.disable();
+        }
+        self.inner.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt, Route};
+
+    #[tokio::test]
+    async fn early_hints() {
+        #[handler(internal)]
+        fn index(preload: Preload) {
+            preload
+                .preload("/app.css", "style")
+                .preload("/app.css", "style");
+        }
+
+        #[handler(internal)]
+        fn nested(preload: Preload) {
+            preload.preload("/nested.js", "script");
+        }
+
+        let app = Route::new()
+            .at("/", index)
+            .at("/nested", nested.with(EarlyHints))
+            .with(EarlyHints);
+        let cli = TestClient::new(app);
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header_all("link", ["</app.css>; rel=preload; as=style"]);
+
+        let resp = cli.get("/nested").send().await;
+        resp.assert_header_all("link", ["</nested.js>; rel=preload; as=script"]);
+    }
+
+    #[tokio::test]
+    async fn no_preload() {
+        #[handler(internal)]
+        fn index(preload: Preload) {
+            preload.preload("/app.css", "style");
+        }
+
+        let app = Route::new()
+            .at("/", index)
+            .at("/plain", index.with(NoPreload))
+            .with(EarlyHints);
+        let cli = TestClient::new(app);
+
+        cli.get("/")
+            .send()
+            .await
+            .assert_header("link", "</app.css>; rel=preload; as=style");
+        cli.get("/plain")
+            .send()
+            .await
+            .assert_header_is_not_exist("link");
+    }
+
+    #[tokio::test]
+    async fn missing_middleware() {
+        #[handler(internal)]
+        fn index(_preload: Preload) {}
+
+        TestClient::new(index)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
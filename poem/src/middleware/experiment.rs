@@ -0,0 +1,320 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use tracing::Instrument;
+
+use crate::{
+    middleware::{feature_flags::stable_hash, CookieJarManager, CookieJarManagerEndpoint},
+    web::{cookie::Cookie, Experiments},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+type UserIdFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Middleware for assigning the requests to the variants of an A/B
+/// experiment, which are read with the [`Experiments`] extractor.
+///
+/// The variant is kept in a cookie, so a client stays in the same variant.
+/// The new clients are assigned by hashing the id returned by the
+/// [`user_id`](Experiment::user_id) function, so a user gets the same variant
+/// on all their devices, or randomly if there is no user id. The variants are
+/// chosen in proportion to their weights.
+///
+/// The requests are processed in an `experiment` tracing span with the name
+/// of the experiment and the variant, and the
+/// [`OpenTelemetryMetrics`](crate::middleware::OpenTelemetryMetrics)
+/// middleware adds an `experiment.<name>` label to the metrics.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::Experiment, test::TestClient, web::Experiments, EndpointExt};
+///
+/// #[handler]
+/// fn checkout(experiments: Experiments) -> &'static str {
+///     match experiments.variant("checkout") {
+///         Some("one_page") => "one page checkout",
+///         _ => "checkout",
+///     }
+/// }
+///
+/// let app = checkout.with(
+///     Experiment::new("checkout")
+///         .variant("control", 1)
+///         .variant("one_page", 1),
+/// );
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").send().await;
+/// resp.assert_status_is_ok();
+/// assert!(resp.0.headers().contains_key("set-cookie"));
+/// # });
+/// ```
+pub struct Experiment {
+    name: String,
+    variants: Vec<(String, u32)>,
+    user_id: Option<UserIdFn>,
+    cookie_name: String,
+    max_age: Duration,
+}
+
+impl Experiment {
+    /// Create an `Experiment` middleware without variants.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            cookie_name: format!("poem-experiment-{}", name),
+            name,
+            variants: Vec::new(),
+            user_id: None,
+            max_age: Duration::from_secs(60 * 60 * 24 * 30),
+        }
+    }
+
+    /// Adds a variant with a weight.
+    #[must_use]
+    pub fn variant(mut self, name: impl Into<String>, weight: u32) -> Self {
+        self.variants.push((name.into(), weight));
+        self
+    }
+
+    /// Sets a function which returns the id of the user of a request, such
+    /// as the user id or the id of the session.
+    #[must_use]
+    pub fn user_id(self, f: impl Fn(&Request) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            user_id: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Sets the name of the cookie keeping the variant.
+    ///
+    /// Default is `poem-experiment-<name>`.
+    #[must_use]
+    pub fn cookie_name(self, cookie_name: impl Into<String>) -> Self {
+        Self {
+            cookie_name: cookie_name.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Max-Age` of the cookie.
+    ///
+    /// Default is 30 days.
+    #[must_use]
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self { max_age, ..self }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Experiment {
+    type Output = CookieJarManagerEndpoint<ExperimentEndpoint<E>>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        CookieJarManager::new().transform(ExperimentEndpoint {
+            inner: ep,
+            name: self.name.clone(),
+            variants: self
+                .variants
+                .iter()
+                .filter(|(_, weight)| *weight > 0)
+                .cloned()
+                .collect(),
+            user_id: self.user_id.clone(),
+            cookie_name: self.cookie_name.clone(),
+            max_age: self.max_age,
+        })
+    }
+}
+
+/// Endpoint for Experiment middleware.
+pub struct ExperimentEndpoint<E> {
+    inner: E,
+    name: String,
+    variants: Vec<(String, u32)>,
+    user_id: Option<UserIdFn>,
+    cookie_name: String,
+    max_age: Duration,
+}
+
+impl<E> ExperimentEndpoint<E> {
+    fn assign(&self, req: &Request) -> Option<String> {
+        let total = self
+            .variants
+            .iter()
+            .map(|(_, weight)| *weight as u64)
+            .sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+
+        let hash = match self.user_id.as_ref().and_then(|f| f(req)) {
+            Some(user_id) => stable_hash(format!("{}:{}", self.name, user_id)),
+            // a new random key for each hasher
+            None => RandomState::new().build_hasher().finish(),
+        };
+        let mut bucket = hash % total;
+        for (variant, weight) in &self.variants {
+            if bucket < *weight as u64 {
+                return Some(variant.clone());
+            }
+            bucket -= *weight as u64;
+        }
+        None
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ExperimentEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let existing = req
+            .cookie()
+            .get(&self.cookie_name)
+            .map(|cookie| cookie.value_str().to_string())
+            .filter(|value| self.variants.iter().any(|(variant, _)| variant == value));
+        let variant = match existing.or_else(|| self.assign(&req)) {
+            Some(variant) => variant,
+            None => return self.inner.call(req).await.map(IntoResponse::into_response),
+        };
+
+        let mut cookie = Cookie::new_with_str(&self.cookie_name, &variant);
+        cookie.set_path("/");
+        cookie.set_max_age(self.max_age);
+        req.cookie().add(cookie);
+
+        let mut experiments = req
+            .extensions()
+            .get::<Experiments>()
+            .map(|experiments| (*experiments.0).clone())
+            .unwrap_or_default();
+        experiments.insert(self.name.clone(), variant.clone());
+        let experiments = Experiments(Arc::new(experiments));
+        req.extensions_mut().insert(experiments.clone());
+
+        let span = tracing::info_span!("experiment", experiment = %self.name, variant = %variant);
+        // the innermost experiment middleware has all the variants
+        match self.inner.call(req).instrument(span).await {
+            Ok(resp) => {
+                let mut resp = resp.into_response();
+                if resp.data::<Experiments>().is_none() {
+                    resp.set_data(experiments);
+                }
+                Ok(resp)
+            }
+            Err(mut err) => {
+                if err.data::<Experiments>().is_none() {
+                    err.set_data(experiments);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[handler(internal)]
+    fn index(experiments: Experiments) -> String {
+        experiments
+            .iter()
+            .map(|(experiment, variant)| format!("{}={}", experiment, variant))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn user_id(req: &Request) -> Option<String> {
+        req.header("x-user-id").map(ToString::to_string)
+    }
+
+    #[tokio::test]
+    async fn sticky_cookie() {
+        let app = index.with(Experiment::new("exp").variant("a", 1).variant("b", 1));
+        let cli = TestClient::new(app);
+
+        let resp = cli.get("/").send().await;
+        let cookie = resp.0.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let cookie = Cookie::parse(cookie).unwrap();
+        assert_eq!(cookie.name(), "poem-experiment-exp");
+        let variant = cookie.value_str().to_string();
+        resp.assert_text(format!("exp={}", variant)).await;
+
+        for _ in 0..10 {
+            cli.get("/")
+                .header(header::COOKIE, format!("poem-experiment-exp={}", variant))
+                .send()
+                .await
+                .assert_text(format!("exp={}", variant))
+                .await;
+        }
+
+        // an unknown variant is reassigned
+        let resp = cli
+            .get("/")
+            .header(header::COOKIE, "poem-experiment-exp=c")
+            .send()
+            .await;
+        let text = resp.0.into_body().into_string().await.unwrap();
+        assert!(text == "exp=a" || text == "exp=b", "{text}");
+    }
+
+    #[tokio::test]
+    async fn deterministic_assignment() {
+        let app = index
+            .with(
+                Experiment::new("inner")
+                    .variant("x", 1)
+                    .variant("y", 0)
+                    .user_id(user_id),
+            )
+            .with(
+                Experiment::new("outer")
+                    .variant("a", 1)
+                    .variant("b", 3)
+                    .user_id(user_id),
+            );
+        let cli = TestClient::new(app);
+
+        let mut b = 0;
+        for user in 0..200 {
+            let text = |resp: crate::test::TestResponse| async move {
+                resp.0.into_body().into_string().await.unwrap()
+            };
+            let user = user.to_string();
+            let first = text(cli.get("/").header("x-user-id", &user).send().await).await;
+            let second = text(cli.get("/").header("x-user-id", &user).send().await).await;
+            assert_eq!(first, second);
+            match first.as_str() {
+                "inner=x,outer=a" => {}
+                "inner=x,outer=b" => b += 1,
+                text => panic!("unexpected variants: {text}"),
+            }
+        }
+        assert!((120..180).contains(&b), "{b}");
+    }
+
+    #[tokio::test]
+    async fn response_data() {
+        let ep = index.with(Experiment::new("exp").variant("a", 1));
+        let resp = ep.call(Request::default()).await.unwrap();
+        assert_eq!(
+            resp.data::<Experiments>().unwrap().variant("exp"),
+            Some("a")
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use http::{header, StatusCode};
+
+use crate::{Endpoint, Error, IntoResponse, Middleware, Request, Response, Result};
+
+/// Middleware for injecting faults in the responses, to test the resilience
+/// of the clients, such as their retries, timeouts and circuit breakers, in a
+/// staging environment.
+///
+/// No fault is injected until they are configured with
+/// [`latency`](FaultInjection::latency),
+/// [`abort_rate`](FaultInjection::abort_rate) and
+/// [`corrupt_rate`](FaultInjection::corrupt_rate). The faults only affect the
+/// requests whose path starts with one of the
+/// [`match_path`](FaultInjection::match_path) prefixes, if any, and are
+/// toggled at runtime with [`set_enabled`](FaultInjection::set_enabled) on
+/// any clone of the middleware.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{handler, middleware::FaultInjection, EndpointExt, Route};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// // delay half of the api requests by 200ms, and fail 5% of them
+/// let app = Route::new().at("/api", index).with(
+///     FaultInjection::new()
+///         .match_path("/api")
+///         .latency(Duration::from_millis(200), 0.5)
+///         .abort_rate(0.05),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct FaultInjection {
+    enabled: Arc<AtomicBool>,
+    paths: Vec<String>,
+    latency: Duration,
+    latency_rate: f64,
+    abort_rate: f64,
+    abort_status: StatusCode,
+    corrupt_rate: f64,
+}
+
+impl Default for FaultInjection {
+    fn default() -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            paths: Vec::new(),
+            latency: Duration::ZERO,
+            latency_rate: 0.0,
+            abort_rate: 0.0,
+            abort_status: StatusCode::INTERNAL_SERVER_ERROR,
+            corrupt_rate: 0.0,
+        }
+    }
+}
+
+impl FaultInjection {
+    /// Create a `FaultInjection` middleware, which injects no fault.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Only injects the faults in the requests whose path starts with
+    /// `prefix`.
+    ///
+    /// The faults are injected in all the requests if no prefix is set.
+    #[must_use]
+    pub fn match_path(mut self, prefix: impl Into<String>) -> Self {
+        self.paths.push(prefix.into());
+        self
+    }
+
+    /// Delays the fraction `rate` of the requests, between `0.0` and `1.0`, by
+    /// `latency`.
+    #[must_use]
+    pub fn latency(self, latency: Duration, rate: f64) -> Self {
+        Self {
+            latency,
+            latency_rate: rate.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Fails the fraction `rate` of the requests, between `0.0` and `1.0`,
+    /// without calling the inner endpoint.
+    #[must_use]
+    pub fn abort_rate(self, rate: f64) -> Self {
+        Self {
+            abort_rate: rate.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Sets the status code of the failed requests.
+    ///
+    /// Default is `500 Internal Server Error`.
+    #[must_use]
+    pub fn abort_status(self, status: StatusCode) -> Self {
+        Self {
+            abort_status: status,
+            ..self
+        }
+    }
+
+    /// Corrupts the responses to the fraction `rate` of the requests, between
+    /// `0.0` and `1.0`, by truncating their bodies to half of their length.
+    #[must_use]
+    pub fn corrupt_rate(self, rate: f64) -> Self {
+        Self {
+            corrupt_rate: rate.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Returns `true` if the faults are injected.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the injection of the faults.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for FaultInjection {
+    type Output = FaultInjectionEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        FaultInjectionEndpoint {
+            inner: ep,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Endpoint for FaultInjection middleware.
+pub struct FaultInjectionEndpoint<E> {
+    inner: E,
+    config: FaultInjection,
+}
+
+fn sampled(rate: f64) -> bool {
+    if rate <= 0.0 {
+        return false;
+    }
+    // a new random key for each hasher
+    let random = RandomState::new().build_hasher().finish();
+    (random as f64 / u64::MAX as f64) < rate
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for FaultInjectionEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let config = &self.config;
+        let matched = config.paths.is_empty()
+            || config
+                .paths
+                .iter()
+                .any(|prefix| req.uri().path().starts_with(prefix.as_str()));
+        if !config.is_enabled() || !matched {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        if sampled(config.latency_rate) {
+            tracing::debug!(latency = ?config.latency, "inject latency");
+            tokio::time::sleep(config.latency).await;
+        }
+
+        if sampled(config.abort_rate) {
+            tracing::debug!(status = %config.abort_status, "inject failure");
+            return Err(Error::from_status(config.abort_status));
+        }
+
+        let resp = self.inner.call(req).await?.into_response();
+        if !sampled(config.corrupt_rate) {
+            return Ok(resp);
+        }
+
+        tracing::debug!("inject corrupted response");
+        let (mut parts, body) = resp.into_parts();
+        let mut data = body.into_vec().await?;
+        data.truncate(data.len() / 2);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        Ok(Response::from_parts(parts, data.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt, Route};
+
+    #[handler(internal)]
+    fn index() -> &'static str {
+        "hello world!"
+    }
+
+    #[tokio::test]
+    async fn no_fault() {
+        let cli = TestClient::new(index.with(FaultInjection::new()));
+        cli.get("/").send().await.assert_text("hello world!").await;
+    }
+
+    #[tokio::test]
+    async fn abort() {
+        let faults = FaultInjection::new()
+            .match_path("/api")
+            .abort_rate(1.0)
+            .abort_status(StatusCode::BAD_GATEWAY);
+        let app = Route::new()
+            .at("/api", index)
+            .at("/health", index)
+            .with(faults.clone());
+        let cli = TestClient::new(app);
+
+        cli.get("/api")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+        cli.get("/health").send().await.assert_status_is_ok();
+
+        faults.set_enabled(false);
+        cli.get("/api").send().await.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn corrupt() {
+        let cli = TestClient::new(index.with(FaultInjection::new().corrupt_rate(1.0)));
+        cli.get("/").send().await.assert_text("hello ").await;
+    }
+
+    #[tokio::test]
+    async fn latency() {
+        let cli = TestClient::new(
+            index.with(FaultInjection::new().latency(Duration::from_millis(50), 1.0)),
+        );
+        let start = std::time::Instant::now();
+        cli.get("/").send().await.assert_text("hello world!").await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
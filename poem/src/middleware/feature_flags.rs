@@ -0,0 +1,328 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::{
+    error::NotFoundError, web::Flags, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+type UserIdFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Represents a back-end for the per-user flags of the [`FeatureFlags`]
+/// middleware.
+#[async_trait::async_trait]
+pub trait FlagOverrides: Send + Sync {
+    /// Returns the flags enabled or disabled for the user, which take
+    /// precedence over the defaults.
+    async fn overrides(&self, user_id: &str) -> Result<HashMap<String, bool>>;
+}
+
+#[async_trait::async_trait]
+impl FlagOverrides for HashMap<String, HashMap<String, bool>> {
+    async fn overrides(&self, user_id: &str) -> Result<HashMap<String, bool>> {
+        Ok(self.get(user_id).cloned().unwrap_or_default())
+    }
+}
+
+/// Returns a stable hash of `key`, which must not change between releases
+/// since it assigns users to buckets.
+pub(crate) fn stable_hash(key: impl AsRef<[u8]>) -> u64 {
+    // FNV-1a
+    key.as_ref().iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Middleware for evaluating the feature flags of each request, which are
+/// read with the [`Flags`] extractor.
+///
+/// A flag is enabled, disabled, or enabled for a percentage of the users
+/// identified by the [`user_id`](FeatureFlags::user_id) function, the same
+/// users always getting the same result. The flags of a user can be
+/// overridden with a [`FlagOverrides`] back-end.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     middleware::{FeatureFlags, RequireFlag},
+///     test::TestClient,
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn new_ui() -> &'static str {
+///     "new ui"
+/// }
+///
+/// let app = Route::new()
+///     .at("/new", new_ui.with(RequireFlag("new_ui")))
+///     .with(
+///         FeatureFlags::new()
+///             .flag("new_ui", false)
+///             .rollout("new_checkout", 20)
+///             .user_id(|req| req.header("x-user-id").map(ToString::to_string)),
+///     );
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/new")
+///     .send()
+///     .await
+///     .assert_status(poem::http::StatusCode::NOT_FOUND);
+/// # });
+/// ```
+#[derive(Default)]
+pub struct FeatureFlags {
+    flags: BTreeMap<String, u8>,
+    user_id: Option<UserIdFn>,
+    overrides: Option<Arc<dyn FlagOverrides>>,
+}
+
+impl FeatureFlags {
+    /// Create a `FeatureFlags` middleware without flags.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Enables or disables a flag for all the users.
+    #[must_use]
+    pub fn flag(self, name: impl Into<String>, enabled: bool) -> Self {
+        self.rollout(name, if enabled { 100 } else { 0 })
+    }
+
+    /// Enables a flag for `percent` of the users.
+    ///
+    /// The flag is disabled for the requests without a user id, unless
+    /// `percent` is `100`.
+    #[must_use]
+    pub fn rollout(mut self, name: impl Into<String>, percent: u8) -> Self {
+        self.flags.insert(name.into(), percent.min(100));
+        self
+    }
+
+    /// Sets a function which returns the id of the user of a request, such
+    /// as the user id stored in the session.
+    #[must_use]
+    pub fn user_id(self, f: impl Fn(&Request) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            user_id: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Sets the back-end for the per-user flags.
+    ///
+    /// If it returns an error, the error is logged and the defaults are used.
+    #[must_use]
+    pub fn overrides(self, overrides: impl FlagOverrides + 'static) -> Self {
+        Self {
+            overrides: Some(Arc::new(overrides)),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for FeatureFlags {
+    type Output = FeatureFlagsEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        FeatureFlagsEndpoint {
+            inner: ep,
+            flags: self.flags.clone(),
+            user_id: self.user_id.clone(),
+            overrides: self.overrides.clone(),
+        }
+    }
+}
+
+/// Endpoint for FeatureFlags middleware.
+pub struct FeatureFlagsEndpoint<E> {
+    inner: E,
+    flags: BTreeMap<String, u8>,
+    user_id: Option<UserIdFn>,
+    overrides: Option<Arc<dyn FlagOverrides>>,
+}
+
+impl<E> FeatureFlagsEndpoint<E> {
+    async fn evaluate(&self, req: &Request) -> BTreeMap<String, bool> {
+        let user_id = self.user_id.as_ref().and_then(|f| f(req));
+        let mut flags = self
+            .flags
+            .iter()
+            .map(|(name, percent)| {
+                let enabled = match &user_id {
+                    _ if *percent >= 100 => true,
+                    Some(user_id) if *percent > 0 => {
+                        stable_hash(format!("{}:{}", name, user_id)) % 100 < *percent as u64
+                    }
+                    _ => false,
+                };
+                (name.clone(), enabled)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        if let (Some(overrides), Some(user_id)) = (&self.overrides, &user_id) {
+            match overrides.overrides(user_id).await {
+                Ok(overrides) => flags.extend(overrides),
+                Err(err) => {
+                    tracing::error!(error = %err, "failed to load the feature flag overrides")
+                }
+            }
+        }
+
+        flags
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for FeatureFlagsEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if req.extensions().get::<Flags>().is_none() {
+            let flags = Flags(Arc::new(self.evaluate(&req).await));
+            req.extensions_mut().insert(flags);
+        }
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+/// Middleware for responding with `404 Not Found` unless a feature flag is
+/// enabled.
+///
+/// It must be applied inside the [`FeatureFlags`] middleware, otherwise all
+/// the flags are considered disabled.
+#[derive(Debug, Copy, Clone)]
+pub struct RequireFlag(pub &'static str);
+
+impl<E: Endpoint> Middleware<E> for RequireFlag {
+    type Output = RequireFlagEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequireFlagEndpoint {
+            inner: ep,
+            name: self.0,
+        }
+    }
+}
+
+/// Endpoint for RequireFlag middleware.
+pub struct RequireFlagEndpoint<E> {
+    inner: E,
+    name: &'static str,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RequireFlagEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let enabled = req
+            .extensions()
+            .get::<Flags>()
+            .map(|flags| flags.is_enabled(self.name))
+            .unwrap_or_default();
+        if !enabled {
+            return Err(NotFoundError.into());
+        }
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt, Route};
+
+    #[handler(internal)]
+    fn index(flags: Flags) -> String {
+        flags.enabled().collect::<Vec<_>>().join(",")
+    }
+
+    fn user_id(req: &Request) -> Option<String> {
+        req.header("x-user-id").map(ToString::to_string)
+    }
+
+    #[tokio::test]
+    async fn flags() {
+        let overrides = HashMap::from([(
+            "admin".to_string(),
+            HashMap::from([("beta".to_string(), true), ("new_ui".to_string(), false)]),
+        )]);
+        let app = Route::new()
+            .at("/", index)
+            .at("/beta", index.with(RequireFlag("beta")))
+            .with(
+                FeatureFlags::new()
+                    .flag("new_ui", true)
+                    .flag("beta", false)
+                    .rollout("half", 50)
+                    .user_id(user_id)
+                    .overrides(overrides),
+            );
+        let cli = TestClient::new(app);
+
+        cli.get("/").send().await.assert_text("new_ui").await;
+        cli.get("/beta")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        let resp = cli.get("/").header("x-user-id", "admin").send().await;
+        let text = resp.0.into_body().into_string().await.unwrap();
+        assert!(text == "beta" || text == "beta,half", "{text}");
+        cli.get("/beta")
+            .header("x-user-id", "admin")
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn rollout() {
+        let ep = index.with(
+            FeatureFlags::new()
+                .rollout("half", 50)
+                .rollout("none", 0)
+                .rollout("all", 100)
+                .user_id(user_id),
+        );
+        let cli = TestClient::new(ep);
+
+        let mut enabled = 0;
+        for user in 0..200 {
+            let user = user.to_string();
+            let text = |resp: crate::test::TestResponse| async move {
+                resp.0.into_body().into_string().await.unwrap()
+            };
+            let first = text(cli.get("/").header("x-user-id", &user).send().await).await;
+            let second = text(cli.get("/").header("x-user-id", &user).send().await).await;
+            assert_eq!(first, second);
+            match first.as_str() {
+                "all,half" => enabled += 1,
+                "all" => {}
+                text => panic!("unexpected flags: {text}"),
+            }
+        }
+        assert!((60..140).contains(&enabled), "{enabled}");
+    }
+
+    #[tokio::test]
+    async fn missing_middleware() {
+        TestClient::new(index)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        TestClient::new(index.with(RequireFlag("beta")))
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{net::IpAddr, sync::Arc};
+
+use crate::{web::ForwardedInfo, Endpoint, Middleware, Request, Result};
+
+#[derive(Debug, Copy, Clone)]
+struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr.parse().ok()?, Some(prefix_len.parse().ok()?)),
+            None => (s.parse().ok()?, None),
+        };
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = prefix_len.unwrap_or(max_len);
+        (prefix_len <= max_len).then_some(Self { addr, prefix_len })
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Middleware for using the `Forwarded` and `X-Forwarded-*` headers set by
+/// trusted reverse proxies.
+///
+/// When the request comes from a trusted proxy, the scheme, host and port
+/// requested by the client are read from the headers, skipping the other
+/// trusted proxies in the chain. They are then used for
+/// [`Request::scheme`] and the absolute [`Request::original_uri`], so that
+/// redirects and links generated from the request point to the external url.
+/// The information is also available with the
+/// [`ForwardedInfo`](crate::web::ForwardedInfo) extractor.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler, middleware::ForwardedHeaders, test::TestClient, EndpointExt, Request, Route,
+/// };
+///
+/// #[handler]
+/// fn index(req: &Request) -> String {
+///     req.original_uri().to_string()
+/// }
+///
+/// let app = Route::new()
+///     .at("/", get(index))
+///     .with(ForwardedHeaders::new().trusted_proxy("10.0.0.0/8"));
+/// ```
+#[derive(Default)]
+pub struct ForwardedHeaders {
+    trusted: Vec<IpNet>,
+    trust_all: bool,
+}
+
+impl ForwardedHeaders {
+    /// Create new `ForwardedHeaders` middleware, which does not trust any
+    /// proxy.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Trust the proxies with the specified ip address, or in the specified
+    /// network such as `10.0.0.0/8`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `proxy` is not a valid ip address or network.
+    #[must_use]
+    pub fn trusted_proxy(mut self, proxy: impl AsRef<str>) -> Self {
+        self.trusted
+            .push(IpNet::parse(proxy.as_ref()).expect("invalid trusted proxy"));
+        self
+    }
+
+    /// Trust all the proxies, which should only be used if the server cannot
+    /// be reached directly, for example through a Unix domain socket.
+    #[must_use]
+    pub fn trust_all(self) -> Self {
+        Self {
+            trust_all: true,
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ForwardedHeaders {
+    type Output = ForwardedHeadersEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ForwardedHeadersEndpoint {
+            inner: ep,
+            trusted: self.trusted.clone().into(),
+            trust_all: self.trust_all,
+        }
+    }
+}
+
+/// Endpoint for ForwardedHeaders middleware.
+pub struct ForwardedHeadersEndpoint<E> {
+    inner: E,
+    trusted: Arc<[IpNet]>,
+    trust_all: bool,
+}
+
+impl<E> ForwardedHeadersEndpoint<E> {
+    fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trust_all || self.trusted.iter().any(|net| net.contains(ip))
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ForwardedHeadersEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let peer_trusted = self.trust_all
+            || req
+                .remote_addr()
+                .as_socket_addr()
+                .map_or(false, |addr| self.is_trusted(&addr.ip()));
+        let info = if peer_trusted {
+            ForwardedInfo::from_headers(&req, |ip| self.is_trusted(ip))
+        } else {
+            ForwardedInfo::direct(&req)
+        };
+
+        if let Some(base_url) = info.base_url() {
+            let path_and_query = req
+                .original_uri()
+                .path_and_query()
+                .map_or("/", |path_and_query| path_and_query.as_str());
+            if let Ok(uri) = format!("{base_url}{path_and_query}").parse() {
+                req.state_mut().original_uri = uri;
+            }
+        }
+        req.state_mut().scheme = info.scheme.clone();
+        req.extensions_mut().insert(info);
+
+        self.inner.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, web::RemoteAddr, Addr, EndpointExt};
+
+    #[test]
+    fn ip_net() {
+        let net = IpNet::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(!net.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(!net.contains(&"::1".parse().unwrap()));
+
+        let net = IpNet::parse("::1").unwrap();
+        assert!(net.contains(&"::1".parse().unwrap()));
+        assert!(!net.contains(&"::2".parse().unwrap()));
+
+        assert!(IpNet::parse("0.0.0.0/0")
+            .unwrap()
+            .contains(&"1.2.3.4".parse().unwrap()));
+        assert!(IpNet::parse("10.0.0.0/33").is_none());
+        assert!(IpNet::parse("example.com").is_none());
+    }
+
+    async fn call(peer: &str, headers: &[(&'static str, &'static str)]) -> String {
+        #[handler(internal)]
+        fn index(req: &Request, info: ForwardedInfo) -> String {
+            format!(
+                "{} {} {:?}",
+                req.scheme(),
+                req.original_uri(),
+                info.client_ip
+            )
+        }
+
+        let ep = index.with(
+            ForwardedHeaders::new()
+                .trusted_proxy("10.0.0.0/8")
+                .trusted_proxy("::1"),
+        );
+        let mut req = Request::builder()
+            .uri_str("/a?b=1")
+            .header("host", "internal:3000");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let mut req = req.finish();
+        req.state_mut().remote_addr = RemoteAddr(Addr::SocketAddr(peer.parse().unwrap()));
+        ep.get_response(req)
+            .await
+            .into_body()
+            .into_string()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn forwarded_headers() {
+        let forwarded = [(
+            "forwarded",
+            "for=192.0.2.60;proto=https;host=example.com, for=10.0.0.2",
+        )];
+
+        // untrusted peer
+        assert_eq!(
+            call("192.0.2.1:1234", &forwarded).await,
+            "http http://internal:3000/a?b=1 Some(192.0.2.1)"
+        );
+
+        assert_eq!(
+            call("10.0.0.1:1234", &forwarded).await,
+            "https https://example.com/a?b=1 Some(192.0.2.60)"
+        );
+        assert_eq!(
+            call("[::1]:1234", &forwarded).await,
+            "https https://example.com/a?b=1 Some(192.0.2.60)"
+        );
+
+        // the client cannot spoof the headers set by the proxies
+        assert_eq!(
+            call(
+                "10.0.0.1:1234",
+                &[(
+                    "forwarded",
+                    "for=1.1.1.1;proto=http;host=evil.com, for=192.0.2.60;proto=https;host=example.com"
+                )]
+            )
+            .await,
+            "https https://example.com/a?b=1 Some(192.0.2.60)"
+        );
+
+        assert_eq!(
+            call(
+                "10.0.0.1:1234",
+                &[
+                    ("x-forwarded-for", "192.0.2.60, 10.0.0.2"),
+                    ("x-forwarded-proto", "https"),
+                    ("x-forwarded-host", "example.com"),
+                    ("x-forwarded-port", "8443"),
+                ]
+            )
+            .await,
+            "https https://example.com:8443/a?b=1 Some(192.0.2.60)"
+        );
+
+        // the values sent by the client are not used for the other hops
+        assert_eq!(
+            call(
+                "10.0.0.1:1234",
+                &[(
+                    "forwarded",
+                    "for=1.1.1.1;proto=https;host=evil.com, for=192.0.2.60"
+                )]
+            )
+            .await,
+            "http http://internal:3000/a?b=1 Some(192.0.2.60)"
+        );
+        assert_eq!(
+            call(
+                "10.0.0.1:1234",
+                &[
+                    ("x-forwarded-for", "1.1.1.1, 192.0.2.60, 10.0.0.2"),
+                    ("x-forwarded-proto", "http, https"),
+                    ("x-forwarded-host", "evil.com, example.com"),
+                    ("x-forwarded-port", "1, 8443"),
+                ]
+            )
+            .await,
+            "https https://example.com:8443/a?b=1 Some(192.0.2.60)"
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    borrow::Cow,
+    io::{Error as IoError, ErrorKind},
+    str::FromStr,
+    sync::Arc,
+};
+
+use bytes::{Bytes, BytesMut};
+use lol_html::{
+    html_content::ContentType,
+    send::{ElementContentHandlers, HtmlRewriter, Settings},
+    OutputSink, Selector,
+};
+use mime::Mime;
+use parking_lot::Mutex;
+
+use crate::{
+    http::header, web::CompressionAlgo, Body, BodyTransformer, Endpoint, IntoResponse, Middleware,
+    Request, Response, Result,
+};
+
+/// An HTML element matched by a selector of the [`HtmlRewrite`] middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "html-rewrite")))]
+pub use lol_html::send::Element as HtmlElement;
+
+type ElementHandler = Arc<dyn Fn(&mut HtmlElement<'_, '_>) + Send + Sync>;
+
+/// Middleware for rewriting the HTML responses with CSS selectors, built on
+/// [`lol_html`](https://crates.io/crates/lol_html).
+///
+/// The responses are rewritten while they are streamed, whether they are
+/// rendered from templates or forwarded from an upstream server. Only the
+/// responses with the `text/html` content type are rewritten, after being
+/// decompressed if needed, so this middleware should be applied before the
+/// [`Compression`](crate::middleware::Compression) middleware.
+///
+/// # Panics
+///
+/// The methods adding a handler panic if the selector is not a valid CSS
+/// selector, or is not supported by `lol_html`.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::HtmlRewrite, test::TestClient, web::Html, EndpointExt};
+///
+/// #[handler]
+/// fn index() -> Html<&'static str> {
+///     Html(r#"<html><head></head><body><img src="/logo.png"></body></html>"#)
+/// }
+///
+/// let ep = index.with(
+///     HtmlRewrite::new()
+///         .append_html("head", "<script src=\"/analytics.js\"></script>")
+///         .set_attribute("img:not([loading])", "loading", "lazy")
+///         .rewrite_attribute("img[src^='/']", "src", |src| {
+///             Some(format!("https://cdn.example.com{src}"))
+///         }),
+/// );
+/// let cli = TestClient::new(ep);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_text(concat!(
+///         r#"<html><head><script src="/analytics.js"></script></head>"#,
+///         r#"<body><img src="https://cdn.example.com/logo.png" loading="lazy"></body></html>"#
+///     ))
+///     .await;
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "html-rewrite")))]
+#[derive(Default, Clone)]
+pub struct HtmlRewrite {
+    handlers: Vec<(Arc<Selector>, ElementHandler)>,
+}
+
+impl HtmlRewrite {
+    /// Create new `HtmlRewrite` middleware.
+    #[must_use]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Calls `f` with each element matching `selector`.
+    #[must_use]
+    pub fn element<F>(mut self, selector: &str, f: F) -> Self
+    where
+        F: Fn(&mut HtmlElement<'_, '_>) + Send + Sync + 'static,
+    {
+        let selector = Selector::from_str(selector)
+            .unwrap_or_else(|err| panic!("invalid selector `{selector}`: {err}"));
+        self.handlers.push((Arc::new(selector), Arc::new(f)));
+        self
+    }
+
+    /// Inserts `html` at the end of the content of each element matching
+    /// `selector`, for example to inject a script into `head`.
+    #[must_use]
+    pub fn append_html(self, selector: &str, html: impl Into<String>) -> Self {
+        let html = html.into();
+        self.element(selector, move |element| {
+            element.append(&html, ContentType::Html)
+        })
+    }
+
+    /// Inserts `html` at the beginning of the content of each element matching
+    /// `selector`.
+    #[must_use]
+    pub fn prepend_html(self, selector: &str, html: impl Into<String>) -> Self {
+        let html = html.into();
+        self.element(selector, move |element| {
+            element.prepend(&html, ContentType::Html)
+        })
+    }
+
+    /// Sets the attribute `name` to `value` on each element matching
+    /// `selector`.
+    #[must_use]
+    pub fn set_attribute(
+        self,
+        selector: &str,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        let name = name.into();
+        let value = value.into();
+        self.element(selector, move |element| {
+            let _ = element.set_attribute(&name, &value);
+        })
+    }
+
+    /// Replaces the value of the attribute `name` with the value returned by
+    /// `f` on each element matching `selector`, `None` leaves it unchanged.
+    #[must_use]
+    pub fn rewrite_attribute<F>(self, selector: &str, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.element(selector, move |element| {
+            if let Some(value) = element.get_attribute(&name).and_then(|value| f(&value)) {
+                let _ = element.set_attribute(&name, &value);
+            }
+        })
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for HtmlRewrite {
+    type Output = HtmlRewriteEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        HtmlRewriteEndpoint {
+            inner: ep,
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+/// Endpoint for HtmlRewrite middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "html-rewrite")))]
+pub struct HtmlRewriteEndpoint<E> {
+    inner: E,
+    handlers: Vec<(Arc<Selector>, ElementHandler)>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for HtmlRewriteEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let mut resp = self.inner.call(req).await?.into_response();
+        let is_html = resp
+            .content_type()
+            .and_then(|content_type| content_type.parse::<Mime>().ok())
+            .map(|mime| mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+            .unwrap_or_default();
+        if !is_html || self.handlers.is_empty() {
+            return Ok(resp);
+        }
+
+        let mut body = resp.take_body();
+        if let Some(value) = resp.headers().get(header::CONTENT_ENCODING) {
+            match value
+                .to_str()
+                .ok()
+                .and_then(|value| CompressionAlgo::from_str(value).ok())
+            {
+                Some(algo) => {
+                    body = Body::from_async_read(algo.decompress(body.into_async_read()));
+                    resp.headers_mut().remove(header::CONTENT_ENCODING);
+                }
+                None => {
+                    resp.set_body(body);
+                    return Ok(resp);
+                }
+            }
+        }
+
+        resp.headers_mut().remove(header::CONTENT_LENGTH);
+        resp.set_body(body.transform(Rewriter::new(&self.handlers)));
+        Ok(resp)
+    }
+}
+
+#[derive(Clone, Default)]
+struct OutputBuffer(Arc<Mutex<BytesMut>>);
+
+impl OutputSink for OutputBuffer {
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        self.0.lock().extend_from_slice(chunk);
+    }
+}
+
+struct Rewriter {
+    rewriter: Option<HtmlRewriter<'static, OutputBuffer>>,
+    output: OutputBuffer,
+}
+
+impl Rewriter {
+    fn new(handlers: &[(Arc<Selector>, ElementHandler)]) -> Self {
+        let element_content_handlers = handlers
+            .iter()
+            .map(|(selector, handler)| {
+                let handler = handler.clone();
+                (
+                    Cow::Borrowed(&**selector),
+                    ElementContentHandlers::default().element(
+                        move |element: &mut HtmlElement<'_, '_>| {
+                            handler(element);
+                            Ok(())
+                        },
+                    ),
+                )
+            })
+            .collect();
+        let output = OutputBuffer::default();
+        let rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers,
+                ..Settings::new_send()
+            },
+            output.clone(),
+        );
+
+        Self {
+            rewriter: Some(rewriter),
+            output,
+        }
+    }
+
+    fn take_output(&self) -> Bytes {
+        self.output.0.lock().split().freeze()
+    }
+}
+
+impl BodyTransformer for Rewriter {
+    fn transform(&mut self, chunk: Bytes) -> Result<Bytes, IoError> {
+        if let Some(rewriter) = &mut self.rewriter {
+            rewriter
+                .write(&chunk)
+                .map_err(|err| IoError::new(ErrorKind::Other, err))?;
+        }
+        Ok(self.take_output())
+    }
+
+    fn finish(&mut self) -> Result<Bytes, IoError> {
+        if let Some(rewriter) = self.rewriter.take() {
+            rewriter
+                .end()
+                .map_err(|err| IoError::new(ErrorKind::Other, err))?;
+        }
+        Ok(self.take_output())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    const PAGE: &str = r#"<html><head><title>poem</title></head><body><a href="/a">a</a><img src="/b.png"></body></html>"#;
+
+    fn rewrite() -> HtmlRewrite {
+        HtmlRewrite::new()
+            .prepend_html("body", "<nav></nav>")
+            .set_attribute("img", "loading", "lazy")
+            .rewrite_attribute("a", "href", |href| Some(format!("/app{href}")))
+    }
+
+    const EXPECTED: &str = r#"<html><head><title>poem</title></head><body><nav></nav><a href="/app/a">a</a><img src="/b.png" loading="lazy"></body></html>"#;
+
+    #[tokio::test]
+    async fn rewrite_html() {
+        #[handler(internal)]
+        fn index() -> Response {
+            Response::builder()
+                .content_type("text/html")
+                .header(header::CONTENT_LENGTH, PAGE.len())
+                .body(PAGE)
+        }
+
+        let resp = TestClient::new(index.with(rewrite())).get("/").send().await;
+        resp.assert_header_is_not_exist(header::CONTENT_LENGTH);
+        resp.assert_text(EXPECTED).await;
+    }
+
+    #[tokio::test]
+    async fn not_html() {
+        #[handler(internal)]
+        fn index() -> &'static str {
+            PAGE
+        }
+
+        TestClient::new(index.with(rewrite()))
+            .get("/")
+            .send()
+            .await
+            .assert_text(PAGE)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn compressed() {
+        #[handler(internal)]
+        async fn index() -> Response {
+            let mut data = Vec::new();
+            CompressionAlgo::GZIP
+                .compress(PAGE.as_bytes(), None)
+                .read_to_end(&mut data)
+                .await
+                .unwrap();
+            Response::builder()
+                .content_type("text/html; charset=utf-8")
+                .header(header::CONTENT_ENCODING, "gzip")
+                .body(data)
+        }
+
+        let resp = TestClient::new(index.with(rewrite())).get("/").send().await;
+        resp.assert_header_is_not_exist(header::CONTENT_ENCODING);
+        resp.assert_text(EXPECTED).await;
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_selector() {
+        let _ = HtmlRewrite::new().set_attribute("img[", "loading", "lazy");
+    }
+}
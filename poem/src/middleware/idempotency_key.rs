@@ -0,0 +1,679 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use parking_lot::Mutex;
+
+use crate::{
+    error::IdempotencyKeyError,
+    lock::{with_lock, Lock},
+    middleware::feature_flags::stable_hash,
+    web::Clock,
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+/// How long the lock of a key is held while the request is processed.
+const LOCK_TTL: Duration = Duration::from_secs(60);
+
+type UserIdFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// A response saved by the [`IdempotencyKey`] middleware.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// A hash of the body of the request, which must be the same for the
+    /// response to be replayed
+    pub body_hash: u64,
+    /// Status code
+    pub status: StatusCode,
+    /// Headers, without the `Set-Cookie` headers
+    pub headers: HeaderMap,
+    /// Body
+    pub body: Bytes,
+}
+
+impl IntoResponse for CachedResponse {
+    fn into_response(self) -> Response {
+        let mut resp = Response::builder().status(self.status).body(self.body);
+        *resp.headers_mut() = self.headers;
+        resp
+    }
+}
+
+/// The state of an idempotency key, returned by [`IdempotencyStore::begin`].
+#[derive(Debug, Clone)]
+pub enum IdempotencyState {
+    /// The key was not used, and is now reserved for the current request.
+    Started,
+    /// A request with the same key is still in progress.
+    InProgress,
+    /// A request with the same key has completed with this response.
+    Completed(CachedResponse),
+}
+
+/// Represents a back-end storage for the [`IdempotencyKey`] middleware.
+///
+/// The operations on a key must be atomic, so that only one of the concurrent
+/// requests with the same key is processed.
+#[async_trait::async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Reserves the key for `ttl` if it is not used, or returns its state.
+    async fn begin(&self, key: &str, ttl: Duration) -> Result<IdempotencyState>;
+
+    /// Saves the response of the request which reserved the key, so that it
+    /// is replayed for `ttl`.
+    async fn complete(&self, key: &str, resp: &CachedResponse, ttl: Duration) -> Result<()>;
+
+    /// Releases the key, so that the request can be retried.
+    async fn abort(&self, key: &str) -> Result<()>;
+}
+
+enum Entry {
+    InProgress,
+    Completed(CachedResponse),
+}
+
+/// An idempotency store using memory.
+#[derive(Default)]
+pub struct MemoryIdempotencyStore {
+    entries: Mutex<HashMap<String, (Entry, Instant)>>,
+    clock: Clock,
+}
+
+impl MemoryIdempotencyStore {
+    /// Create a `MemoryIdempotencyStore`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the clock used for the expiration of the keys.
+    ///
+    /// Default is the system clock.
+    #[must_use]
+    pub fn clock(self, clock: impl Into<Clock>) -> Self {
+        Self {
+            clock: clock.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn begin(&self, key: &str, ttl: Duration) -> Result<IdempotencyState> {
+        let now = self.clock.instant();
+        let mut entries = self.entries.lock();
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+
+        match entries.get(key) {
+            Some((Entry::InProgress, _)) => Ok(IdempotencyState::InProgress),
+            Some((Entry::Completed(resp), _)) => Ok(IdempotencyState::Completed(resp.clone())),
+            None => {
+                entries.insert(key.to_string(), (Entry::InProgress, now + ttl));
+                Ok(IdempotencyState::Started)
+            }
+        }
+    }
+
+    async fn complete(&self, key: &str, resp: &CachedResponse, ttl: Duration) -> Result<()> {
+        self.entries.lock().insert(
+            key.to_string(),
+            (Entry::Completed(resp.clone()), self.clock.instant() + ttl),
+        );
+        Ok(())
+    }
+
+    async fn abort(&self, key: &str) -> Result<()> {
+        self.entries.lock().remove(key);
+        Ok(())
+    }
+}
+
+/// Middleware for making the `POST` and `PATCH` requests safe to retry with
+/// the `Idempotency-Key` header.
+///
+/// The response to the first request with a key is saved in the
+/// [`IdempotencyStore`], and replayed with the `Idempotent-Replayed: true`
+/// header to the requests of the same user with the same method, path and key
+/// during the [`window`](IdempotencyKey::window). A request with the key of a
+/// request which is still in progress fails with `409 Conflict`, or waits for
+/// it and replays its response with a [`lock`](IdempotencyKey::lock), and a
+/// request reusing a key with another body fails with
+/// `422 Unprocessable Entity`.
+///
+/// The keys are shared by all the clients unless the
+/// [`user_id`](IdempotencyKey::user_id) function is set, which should be done
+/// as soon as the clients are authenticated. The `Set-Cookie` headers are not
+/// replayed.
+///
+/// The errors and the responses with a `5xx` status code are not saved, so
+/// that the request can be retried. The other methods are not affected, as
+/// they are expected to be idempotent.
+///
+/// # Errors
+///
+/// - [`IdempotencyKeyError`]
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use poem::{
+///     handler,
+///     middleware::{IdempotencyKey, MemoryIdempotencyStore},
+///     post,
+///     test::TestClient,
+///     EndpointExt, Route,
+/// };
+///
+/// static CHARGES: AtomicUsize = AtomicUsize::new(0);
+///
+/// #[handler]
+/// fn charge() -> String {
+///     format!("charge {}", CHARGES.fetch_add(1, Ordering::SeqCst))
+/// }
+///
+/// let app = Route::new()
+///     .at("/charges", post(charge))
+///     .with(IdempotencyKey::new(MemoryIdempotencyStore::new()));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// for _ in 0..2 {
+///     let resp = cli
+///         .post("/charges")
+///         .header("idempotency-key", "8e03978e")
+///         .send()
+///         .await;
+///     resp.assert_text("charge 0").await;
+/// }
+/// # });
+/// ```
+pub struct IdempotencyKey<T> {
+    store: Arc<T>,
+    window: Duration,
+    required: bool,
+    lock: Option<Arc<dyn Lock>>,
+    user_id: Option<UserIdFn>,
+}
+
+impl<T: IdempotencyStore> IdempotencyKey<T> {
+    /// Create an `IdempotencyKey` middleware with the specified store.
+    pub fn new(store: T) -> Self {
+        Self {
+            store: Arc::new(store),
+            window: Duration::from_secs(60 * 60 * 24),
+            required: false,
+            lock: None,
+            user_id: None,
+        }
+    }
+
+    /// Sets how long the responses are replayed. Defaults to 24 hours.
+    #[must_use]
+    pub fn window(self, window: Duration) -> Self {
+        Self { window, ..self }
+    }
+
+    /// Rejects the `POST` and `PATCH` requests without an `Idempotency-Key`
+    /// header with `400 Bad Request`. Defaults to `false`.
+    #[must_use]
+    pub fn required(self, required: bool) -> Self {
+        Self { required, ..self }
+    }
+
+    /// Sets a function which returns the id of the user of a request, such
+    /// as the user id or the id of the session, so that the users can't
+    /// replay the responses of the other users.
+    #[must_use]
+    pub fn user_id(self, f: impl Fn(&Request) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            user_id: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Processes the requests while holding the lock with the key
+    /// `idempotency:{method} {path} {user id} {idempotency key}`, where the
+    /// user id is quoted, so that a request with
+    /// the key of a request in progress waits for it and replays its
+    /// response, instead of failing with `409 Conflict`.
+    #[must_use]
+    pub fn lock(self, lock: impl Lock + 'static) -> Self {
+        Self {
+            lock: Some(Arc::new(lock)),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint, T: IdempotencyStore + 'static> Middleware<E> for IdempotencyKey<T> {
+    type Output = IdempotencyKeyEndpoint<E, T>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        IdempotencyKeyEndpoint {
+            inner: ep,
+            store: self.store.clone(),
+            window: self.window,
+            required: self.required,
+            lock: self.lock.clone(),
+            user_id: self.user_id.clone(),
+        }
+    }
+}
+
+/// Endpoint for IdempotencyKey middleware.
+pub struct IdempotencyKeyEndpoint<E, T> {
+    inner: E,
+    store: Arc<T>,
+    window: Duration,
+    required: bool,
+    lock: Option<Arc<dyn Lock>>,
+    user_id: Option<UserIdFn>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint, T: IdempotencyStore + 'static> Endpoint for IdempotencyKeyEndpoint<E, T> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::POST && req.method() != Method::PATCH {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let key = match req.headers().get(IDEMPOTENCY_KEY) {
+            Some(value) => value
+                .to_str()
+                .ok()
+                .filter(|key| !key.is_empty() && key.len() <= 255)
+                .ok_or(IdempotencyKeyError::Invalid)?,
+            None if self.required => return Err(IdempotencyKeyError::Required.into()),
+            None => return self.inner.call(req).await.map(IntoResponse::into_response),
+        };
+        let user_id = self
+            .user_id
+            .as_ref()
+            .and_then(|user_id| user_id(&req))
+            .unwrap_or_default();
+        // the user id is quoted, since both the ids may contain spaces
+        let key = format!(
+            "{} {} {:?} {}",
+            req.method(),
+            req.original_uri().path(),
+            user_id,
+            key
+        );
+
+        match &self.lock {
+            Some(lock) => {
+                let lock_key = format!("idempotency:{key}");
+                with_lock(lock, &lock_key, LOCK_TTL, self.process(key, req)).await?
+            }
+            None => self.process(key, req).await,
+        }
+    }
+}
+
+impl<E: Endpoint, T: IdempotencyStore + 'static> IdempotencyKeyEndpoint<E, T> {
+    async fn process(&self, key: String, mut req: Request) -> Result<Response> {
+        let req_body = req.take_body().into_bytes().await?;
+        let body_hash = stable_hash(&req_body);
+        req.set_body(req_body);
+
+        match self.store.begin(&key, self.window).await? {
+            IdempotencyState::Started => {}
+            IdempotencyState::InProgress => return Err(IdempotencyKeyError::InProgress.into()),
+            IdempotencyState::Completed(cached) if cached.body_hash != body_hash => {
+                return Err(IdempotencyKeyError::BodyMismatch.into());
+            }
+            IdempotencyState::Completed(cached) => {
+                let mut resp = cached.into_response();
+                resp.headers_mut()
+                    .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+                return Ok(resp);
+            }
+        }
+
+        // releases the key if the request is cancelled
+        let mut guard = AbortGuard {
+            store: self.store.clone(),
+            key: Some(key),
+        };
+
+        let mut resp = match self.inner.call(req).await {
+            Ok(resp) => resp.into_response(),
+            Err(err) => {
+                self.store.abort(&guard.disarm()).await?;
+                return Err(err);
+            }
+        };
+        if resp.status().is_server_error() {
+            self.store.abort(&guard.disarm()).await?;
+            return Ok(resp);
+        }
+
+        let body = match resp.take_body().into_bytes().await {
+            Ok(body) => body,
+            Err(err) => {
+                self.store.abort(&guard.disarm()).await?;
+                return Err(err.into());
+            }
+        };
+        let mut headers = resp.headers().clone();
+        headers.remove(header::SET_COOKIE);
+        let cached = CachedResponse {
+            body_hash,
+            status: resp.status(),
+            headers,
+            body,
+        };
+        self.store
+            .complete(&guard.disarm(), &cached, self.window)
+            .await?;
+        resp.set_body(cached.body);
+        Ok(resp)
+    }
+}
+
+struct AbortGuard<T: IdempotencyStore + 'static> {
+    store: Arc<T>,
+    key: Option<String>,
+}
+
+impl<T: IdempotencyStore + 'static> AbortGuard<T> {
+    fn disarm(&mut self) -> String {
+        self.key.take().unwrap_or_default()
+    }
+}
+
+impl<T: IdempotencyStore + 'static> Drop for AbortGuard<T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let store = self.store.clone();
+            tokio::spawn(async move {
+                let _ = store.abort(&key).await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        handler,
+        test::TestClient,
+        web::{Data, MockClock},
+        EndpointExt, Error, Route,
+    };
+
+    #[tokio::test]
+    async fn replay() {
+        #[handler(internal)]
+        fn index(counter: Data<&Arc<AtomicUsize>>, body: String) -> Result<String> {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            if body == "fail" {
+                return Err(Error::from_status(StatusCode::BAD_REQUEST));
+            }
+            Ok(format!("{body} {n}"))
+        }
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = Route::new()
+            .at("/a", index)
+            .at("/b", index)
+            .with(IdempotencyKey::new(MemoryIdempotencyStore::new()))
+            .data(counter.clone());
+        let cli = TestClient::new(app);
+
+        let resp = cli
+            .post("/a")
+            .header(IDEMPOTENCY_KEY, "1")
+            .body("x")
+            .send()
+            .await;
+        resp.assert_header_is_not_exist(IDEMPOTENT_REPLAYED);
+        resp.assert_text("x 0").await;
+
+        let resp = cli
+            .post("/a")
+            .header(IDEMPOTENCY_KEY, "1")
+            .body("x")
+            .send()
+            .await;
+        resp.assert_header(IDEMPOTENT_REPLAYED, "true");
+        resp.assert_text("x 0").await;
+
+        // the key can't be reused with another body
+        cli.post("/a")
+            .header(IDEMPOTENCY_KEY, "1")
+            .body("y")
+            .send()
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+        // the key is scoped to the path
+        let resp = cli
+            .post("/b")
+            .header(IDEMPOTENCY_KEY, "1")
+            .body("x")
+            .send()
+            .await;
+        resp.assert_text("x 1").await;
+
+        // the other methods and requests without a key are not affected
+        cli.put("/a")
+            .header(IDEMPOTENCY_KEY, "1")
+            .body("x")
+            .send()
+            .await
+            .assert_text("x 2")
+            .await;
+        cli.post("/a")
+            .body("x")
+            .send()
+            .await
+            .assert_text("x 3")
+            .await;
+
+        // errors are not saved
+        for _ in 0..2 {
+            cli.post("/a")
+                .header(IDEMPOTENCY_KEY, "2")
+                .body("fail")
+                .send()
+                .await
+                .assert_status(StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn user_id() {
+        #[handler(internal)]
+        fn index(counter: Data<&Arc<AtomicUsize>>) -> Response {
+            Response::builder()
+                .header(header::SET_COOKIE, "session=1")
+                .body(counter.fetch_add(1, Ordering::SeqCst).to_string())
+        }
+
+        let app = index
+            .with(
+                IdempotencyKey::new(MemoryIdempotencyStore::new())
+                    .user_id(|req| req.header("x-user").map(ToString::to_string)),
+            )
+            .data(Arc::new(AtomicUsize::new(0)));
+        let cli = TestClient::new(app);
+
+        let send = |user: &'static str| {
+            cli.post("/")
+                .header("x-user", user)
+                .header(IDEMPOTENCY_KEY, "1")
+                .send()
+        };
+
+        let resp = send("a").await;
+        resp.assert_header(header::SET_COOKIE, "session=1");
+        resp.assert_text("0").await;
+
+        // the cookies are not replayed
+        let resp = send("a").await;
+        resp.assert_header(IDEMPOTENT_REPLAYED, "true");
+        resp.assert_header_is_not_exist(header::SET_COOKIE);
+        resp.assert_text("0").await;
+
+        // the key is scoped to the user
+        let resp = send("b").await;
+        resp.assert_header_is_not_exist(IDEMPOTENT_REPLAYED);
+        resp.assert_text("1").await;
+    }
+
+    #[tokio::test]
+    async fn expired() {
+        #[handler(internal)]
+        fn index(counter: Data<&Arc<AtomicUsize>>) -> String {
+            counter.fetch_add(1, Ordering::SeqCst).to_string()
+        }
+
+        let clock = MockClock::new();
+        let app = index
+            .with(
+                IdempotencyKey::new(MemoryIdempotencyStore::new().clock(clock.clone()))
+                    .window(Duration::from_secs(60)),
+            )
+            .data(Arc::new(AtomicUsize::new(0)));
+        let cli = TestClient::new(app);
+
+        for (advance, expected) in [(0, "0"), (59, "0"), (1, "1")] {
+            clock.advance(Duration::from_secs(advance));
+            cli.post("/")
+                .header(IDEMPOTENCY_KEY, "1")
+                .send()
+                .await
+                .assert_text(expected)
+                .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn in_progress() {
+        #[handler(internal)]
+        async fn index(notify: Data<&Arc<tokio::sync::Notify>>) -> &'static str {
+            notify.notified().await;
+            "done"
+        }
+
+        let notify = Arc::new(tokio::sync::Notify::new());
+        let app = index
+            .with(IdempotencyKey::new(MemoryIdempotencyStore::new()))
+            .data(notify.clone());
+        let cli = TestClient::new(app);
+
+        // the first request reserves the key before the second one is sent
+        tokio::join!(
+            async {
+                cli.post("/")
+                    .header(IDEMPOTENCY_KEY, "1")
+                    .send()
+                    .await
+                    .assert_text("done")
+                    .await;
+            },
+            async {
+                cli.post("/")
+                    .header(IDEMPOTENCY_KEY, "1")
+                    .send()
+                    .await
+                    .assert_status(StatusCode::CONFLICT);
+                notify.notify_one();
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn locked() {
+        #[handler(internal)]
+        async fn index(counter: Data<&Arc<AtomicUsize>>) -> String {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            n.to_string()
+        }
+
+        let app = index
+            .with(
+                IdempotencyKey::new(MemoryIdempotencyStore::new())
+                    .lock(crate::lock::MemoryLock::new()),
+            )
+            .data(Arc::new(AtomicUsize::new(0)));
+        let cli = TestClient::new(app);
+
+        // the second request waits for the first one and replays its response
+        let send = || cli.post("/").header(IDEMPOTENCY_KEY, "1").send();
+        let (first, second) = tokio::join!(send(), send());
+        first.assert_text("0").await;
+        second.assert_header(IDEMPOTENT_REPLAYED, "true");
+        second.assert_text("0").await;
+    }
+
+    #[tokio::test]
+    async fn cancelled() {
+        #[handler(internal)]
+        async fn index(body: String) -> &'static str {
+            if body == "hang" {
+                futures_util::future::pending::<()>().await;
+            }
+            "done"
+        }
+
+        let cli = TestClient::new(index.with(IdempotencyKey::new(MemoryIdempotencyStore::new())));
+        let req = cli
+            .post("/")
+            .header(IDEMPOTENCY_KEY, "1")
+            .body("hang")
+            .send();
+        assert!(tokio::time::timeout(Duration::from_millis(10), req)
+            .await
+            .is_err());
+        tokio::task::yield_now().await;
+
+        cli.post("/")
+            .header(IDEMPOTENCY_KEY, "1")
+            .send()
+            .await
+            .assert_text("done")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn invalid_key() {
+        #[handler(internal)]
+        fn index() {}
+
+        let cli = TestClient::new(
+            index.with(IdempotencyKey::new(MemoryIdempotencyStore::new()).required(true)),
+        );
+        cli.post("/")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.post("/")
+            .header(IDEMPOTENCY_KEY, "")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.post("/")
+            .header(IDEMPOTENCY_KEY, "1")
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+}
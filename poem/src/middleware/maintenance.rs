@@ -0,0 +1,192 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use http::{header, StatusCode};
+
+use crate::{Endpoint, IntoResponse, Middleware, Request, Response, Result};
+
+/// Middleware for responding with `503 Service Unavailable` while the
+/// maintenance mode is enabled.
+///
+/// The mode is toggled at runtime with [`set_enabled`](Maintenance::set_enabled)
+/// on any clone of the middleware, for instance from the
+/// [`AdminDashboard`](crate::endpoint::AdminDashboard).
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler, http::StatusCode, middleware::Maintenance, test::TestClient, EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let maintenance = Maintenance::new().allow_path("/health");
+/// let app = Route::new()
+///     .at("/", index)
+///     .at("/health", index)
+///     .with(maintenance.clone());
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// maintenance.set_enabled(true);
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+/// cli.get("/health").send().await.assert_status_is_ok();
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct Maintenance {
+    enabled: Arc<AtomicBool>,
+    message: String,
+    retry_after: Option<Duration>,
+    allowed_paths: Vec<String>,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            enabled: Default::default(),
+            message: "Service is under maintenance".to_string(),
+            retry_after: None,
+            allowed_paths: Vec::new(),
+        }
+    }
+}
+
+impl Maintenance {
+    /// Create a `Maintenance` middleware, which is disabled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the body of the responses.
+    ///
+    /// Default is `Service is under maintenance`.
+    #[must_use]
+    pub fn message(self, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Retry-After` header of the responses.
+    #[must_use]
+    pub fn retry_after(self, retry_after: Duration) -> Self {
+        Self {
+            retry_after: Some(retry_after),
+            ..self
+        }
+    }
+
+    /// Keeps serving the requests whose path starts with `prefix`, such as
+    /// the health checks.
+    #[must_use]
+    pub fn allow_path(mut self, prefix: impl Into<String>) -> Self {
+        self.allowed_paths.push(prefix.into());
+        self
+    }
+
+    /// Returns `true` if the maintenance mode is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the maintenance mode.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Maintenance {
+    type Output = MaintenanceEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        MaintenanceEndpoint {
+            inner: ep,
+            config: self.clone(),
+        }
+    }
+}
+
+/// Endpoint for Maintenance middleware.
+pub struct MaintenanceEndpoint<E> {
+    inner: E,
+    config: Maintenance,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for MaintenanceEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let allowed = self
+            .config
+            .allowed_paths
+            .iter()
+            .any(|prefix| req.uri().path().starts_with(prefix.as_str()));
+        if !self.config.is_enabled() || allowed {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let mut resp = self
+            .config
+            .message
+            .clone()
+            .with_status(StatusCode::SERVICE_UNAVAILABLE)
+            .into_response();
+        if let Some(retry_after) = self.config.retry_after {
+            resp.headers_mut()
+                .insert(header::RETRY_AFTER, retry_after.as_secs().into());
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[handler(internal)]
+    fn index() -> &'static str {
+        "hello"
+    }
+
+    #[tokio::test]
+    async fn maintenance() {
+        let maintenance = Maintenance::new()
+            .message("back soon")
+            .retry_after(Duration::from_secs(120))
+            .allow_path("/_admin");
+        let cli = TestClient::new(index.with(maintenance.clone()));
+
+        cli.get("/").send().await.assert_text("hello").await;
+
+        maintenance.set_enabled(true);
+        assert!(maintenance.is_enabled());
+        let resp = cli.get("/").send().await;
+        resp.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        resp.assert_header(header::RETRY_AFTER, "120");
+        resp.assert_text("back soon").await;
+        cli.get("/_admin/routes")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+
+        maintenance.set_enabled(false);
+        cli.get("/").send().await.assert_status_is_ok();
+    }
+}
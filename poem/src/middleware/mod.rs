@@ -1,29 +1,63 @@
 //! Commonly used middleware.
 
 mod add_data;
+mod alt_svc;
+mod api_key_auth;
 mod catch_panic;
 #[cfg(feature = "compression")]
 mod compression;
+mod conditional;
 #[cfg(feature = "cookie")]
 mod cookie_jar_manager;
 mod cors;
 #[cfg(feature = "csrf")]
 mod csrf;
+mod early_hints;
+#[cfg(feature = "cookie")]
+mod experiment;
+mod fault_injection;
+mod feature_flags;
 mod force_https;
+mod forwarded_headers;
+#[cfg(feature = "html-rewrite")]
+mod html_rewrite;
+mod idempotency_key;
+mod maintenance;
 mod normalize_path;
 #[cfg(feature = "opentelemetry")]
 mod opentelemetry_metrics;
 #[cfg(feature = "opentelemetry")]
 mod opentelemetry_tracing;
+#[cfg(feature = "preview")]
+mod preview;
+mod problem_json;
 mod propagate_header;
+pub(crate) mod recorder;
+#[cfg(feature = "render-cache")]
+mod render_cache;
+pub(crate) mod render_error;
+mod request_deadline;
+#[cfg(feature = "request-decoding")]
+mod request_decoding;
+#[cfg(feature = "request-signing")]
+mod request_signing;
 mod sensitive_header;
+#[cfg(feature = "sentry")]
+mod sentry_mw;
+mod services;
 mod set_header;
+#[cfg(feature = "proxy")]
+mod shadow;
 mod size_limit;
 #[cfg(feature = "tokio-metrics")]
 mod tokio_metrics_mw;
 #[cfg(feature = "tower-compat")]
 mod tower_compat;
 mod tracing_mw;
+mod transactional;
+mod transform_body;
+#[cfg(feature = "usage")]
+mod usage_meter;
 
 #[cfg(feature = "compression")]
 pub use self::compression::{Compression, CompressionEndpoint};
@@ -31,25 +65,71 @@ pub use self::compression::{Compression, CompressionEndpoint};
 pub use self::cookie_jar_manager::{CookieJarManager, CookieJarManagerEndpoint};
 #[cfg(feature = "csrf")]
 pub use self::csrf::{Csrf, CsrfEndpoint};
+#[cfg(feature = "cookie")]
+pub use self::experiment::{Experiment, ExperimentEndpoint};
+#[cfg(feature = "html-rewrite")]
+pub use self::html_rewrite::{HtmlElement, HtmlRewrite, HtmlRewriteEndpoint};
 #[cfg(feature = "opentelemetry")]
 pub use self::opentelemetry_metrics::{OpenTelemetryMetrics, OpenTelemetryMetricsEndpoint};
 #[cfg(feature = "opentelemetry")]
 pub use self::opentelemetry_tracing::{OpenTelemetryTracing, OpenTelemetryTracingEndpoint};
+#[cfg(feature = "preview")]
+pub use self::preview::{Preview, PreviewEndpoint};
+#[cfg(feature = "render-cache")]
+pub use self::render_cache::{RenderCache, RenderCacheEndpoint};
+#[cfg(feature = "request-decoding")]
+pub use self::request_decoding::{RequestDecoding, RequestDecodingEndpoint};
+#[cfg(feature = "request-signing")]
+pub use self::request_signing::{RequestSignature, RequestSignatureEndpoint, RequestSigner};
+#[cfg(feature = "sentry")]
+pub use self::sentry_mw::{Sentry, SentryEndpoint};
+#[cfg(feature = "proxy")]
+pub use self::shadow::{Shadow, ShadowEndpoint};
 #[cfg(feature = "tokio-metrics")]
 pub use self::tokio_metrics_mw::{TokioMetrics, TokioMetricsEndpoint};
 #[cfg(feature = "tower-compat")]
-pub use self::tower_compat::TowerLayerCompatExt;
+pub use self::tower_compat::{
+    TowerHttpCompatEndpoint, TowerHttpCompatMiddleware, TowerLayerCompatExt,
+};
+#[cfg(feature = "usage")]
+pub use self::usage_meter::{
+    MemoryUsageStore, Usage, UsageCap, UsageMeter, UsageMeterEndpoint, UsageStore,
+};
 pub use self::{
     add_data::{AddData, AddDataEndpoint},
+    alt_svc::{AltSvc, AltSvcEndpoint},
+    api_key_auth::{ApiKeyAuth, ApiKeyAuthEndpoint, ApiKeyResolver},
     catch_panic::{CatchPanic, CatchPanicEndpoint, PanicHandler},
+    conditional::{Conditional, ConditionalEndpoint},
     cors::{Cors, CorsEndpoint},
+    early_hints::{EarlyHints, EarlyHintsEndpoint, NoPreload, NoPreloadEndpoint},
+    fault_injection::{FaultInjection, FaultInjectionEndpoint},
+    feature_flags::{
+        FeatureFlags, FeatureFlagsEndpoint, FlagOverrides, RequireFlag, RequireFlagEndpoint,
+    },
     force_https::ForceHttps,
+    forwarded_headers::{ForwardedHeaders, ForwardedHeadersEndpoint},
+    idempotency_key::{
+        CachedResponse, IdempotencyKey, IdempotencyKeyEndpoint, IdempotencyState, IdempotencyStore,
+        MemoryIdempotencyStore,
+    },
+    maintenance::{Maintenance, MaintenanceEndpoint},
     normalize_path::{NormalizePath, NormalizePathEndpoint, TrailingSlash},
+    problem_json::{ProblemJson, ProblemJsonEndpoint},
     propagate_header::{PropagateHeader, PropagateHeaderEndpoint},
+    recorder::{
+        Exchange, RecordedBody, RecordedHeader, RecordedRequest, RecordedResponse, Recorder,
+        RecorderAdminEndpoint, RecorderEndpoint,
+    },
+    render_error::{RenderError, RenderErrorEndpoint},
+    request_deadline::{RequestDeadline, RequestDeadlineEndpoint},
     sensitive_header::{SensitiveHeader, SensitiveHeaderEndpoint},
+    services::{Services, ServicesEndpoint},
     set_header::{SetHeader, SetHeaderEndpoint},
     size_limit::{SizeLimit, SizeLimitEndpoint},
     tracing_mw::{Tracing, TracingEndpoint},
+    transactional::{TransactionManager, Transactional, TransactionalEndpoint},
+    transform_body::{Replace, TransformBody, TransformBodyEndpoint},
 };
 use crate::endpoint::Endpoint;
 
@@ -7,7 +7,11 @@ use libopentelemetry::{
 };
 use opentelemetry_semantic_conventions::trace;
 
-use crate::{route::PathPattern, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+use crate::{
+    route::PathPattern,
+    web::{ApiKey, Experiments},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
 
 /// Middleware for metrics with OpenTelemetry.
 #[cfg_attr(docsrs, doc(cfg(feature = "opentelemetry")))]
@@ -84,6 +88,27 @@ impl<E: Endpoint> Endpoint for OpenTelemetryMetricsEndpoint<E> {
         let res = self.inner.call(req).await.map(IntoResponse::into_response);
         let elapsed = s.elapsed();
 
+        let experiments = match &res {
+            Ok(resp) => resp.data::<Experiments>(),
+            Err(err) => err.data::<Experiments>(),
+        };
+        if let Some(experiments) = experiments {
+            for (experiment, variant) in experiments.iter() {
+                labels.push(
+                    Key::new(format!("experiment.{}", experiment)).string(variant.to_string()),
+                );
+            }
+        }
+
+        let api_key = match &res {
+            Ok(resp) => resp.data::<ApiKey>(),
+            Err(err) => err.data::<ApiKey>(),
+        };
+        if let Some(api_key) = api_key {
+            const API_KEY_ID: Key = Key::from_static_str("api_key.id");
+            labels.push(API_KEY_ID.string(api_key.id.clone()));
+        }
+
         match &res {
             Ok(resp) => {
                 if let Some(path_pattern) = resp.data::<PathPattern>() {
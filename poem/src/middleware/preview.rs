@@ -0,0 +1,272 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::hmac;
+
+use crate::{
+    http::{header, HeaderValue},
+    middleware::{CookieJarManager, CookieJarManagerEndpoint},
+    web::{
+        cookie::{Cookie, SameSite},
+        Clock, PreviewMode,
+    },
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// Middleware for previewing the unpublished content, such as the drafts of
+/// the articles.
+///
+/// The preview mode is enabled by a link with a token generated by
+/// [`token`](Preview::token), such as `/articles/1?preview=<token>`, and
+/// stays enabled with a cookie until the token expires. The handlers read it
+/// with the [`PreviewMode`] extractor, and the templates with the
+/// [`preview`](crate::tera::functions::preview) Tera function.
+///
+/// The responses in preview mode are marked `Cache-Control: private,
+/// no-store` and `X-Robots-Tag: noindex`, so that they are neither cached nor
+/// indexed, and the [`RenderCache`](crate::middleware::RenderCache)
+/// middleware applied inside this middleware is bypassed.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+///
+/// use poem::{
+///     handler, middleware::Preview, test::TestClient, web::PreviewMode, EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn article(preview: PreviewMode) -> &'static str {
+///     if preview.is_enabled() {
+///         "draft"
+///     } else {
+///         "published"
+///     }
+/// }
+///
+/// let preview = Preview::new(b"secret");
+/// let token = preview.token(SystemTime::now() + Duration::from_secs(3600));
+/// let cli = TestClient::new(Route::new().at("/article", article).with(preview));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/article")
+///     .send()
+///     .await
+///     .assert_text("published")
+///     .await;
+///
+/// let resp = cli.get("/article").query("preview", &token).send().await;
+/// resp.assert_header("x-robots-tag", "noindex");
+/// resp.assert_text("draft").await;
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "preview")))]
+pub struct Preview {
+    key: Arc<hmac::Key>,
+    query_param: String,
+    cookie_name: String,
+    secure: bool,
+}
+
+impl Preview {
+    /// Create a `Preview` middleware, whose tokens are signed with `secret`.
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: Arc::new(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref())),
+            query_param: "preview".to_string(),
+            cookie_name: "poem-preview".to_string(),
+            secure: true,
+        }
+    }
+
+    /// Sets the name of the query parameter of the token.
+    ///
+    /// Default is `preview`.
+    #[must_use]
+    pub fn query_param(self, name: impl Into<String>) -> Self {
+        Self {
+            query_param: name.into(),
+            ..self
+        }
+    }
+
+    /// Sets the name of the cookie keeping the preview mode enabled.
+    ///
+    /// Default is `poem-preview`.
+    #[must_use]
+    pub fn cookie_name(self, name: impl Into<String>) -> Self {
+        Self {
+            cookie_name: name.into(),
+            ..self
+        }
+    }
+
+    /// Sets the `Secure` to the cookie. Default is `true`.
+    #[must_use]
+    pub fn secure(self, secure: bool) -> Self {
+        Self { secure, ..self }
+    }
+
+    /// Returns a token enabling the preview mode until `expires`.
+    pub fn token(&self, expires: SystemTime) -> String {
+        token(&self.key, unix_secs(expires))
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn token(key: &hmac::Key, expires: u64) -> String {
+    let expires = expires.to_string();
+    let tag = hmac::sign(key, expires.as_bytes());
+    format!("{expires}.{}", URL_SAFE_NO_PAD.encode(tag.as_ref()))
+}
+
+/// Returns the expiration time of `token`, or `None` if it is not valid.
+fn verify(key: &hmac::Key, token: &str) -> Option<u64> {
+    let (expires, tag) = token.split_once('.')?;
+    let tag = URL_SAFE_NO_PAD.decode(tag).ok()?;
+    hmac::verify(key, expires.as_bytes(), &tag).ok()?;
+    expires.parse().ok()
+}
+
+impl<E: Endpoint> Middleware<E> for Preview {
+    type Output = CookieJarManagerEndpoint<PreviewEndpoint<E>>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        CookieJarManager::new().transform(PreviewEndpoint {
+            inner: ep,
+            key: self.key.clone(),
+            query_param: self.query_param.clone(),
+            cookie_name: self.cookie_name.clone(),
+            secure: self.secure,
+        })
+    }
+}
+
+/// Endpoint for the `Preview` middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "preview")))]
+pub struct PreviewEndpoint<E> {
+    inner: E,
+    key: Arc<hmac::Key>,
+    query_param: String,
+    cookie_name: String,
+    secure: bool,
+}
+
+impl<E> PreviewEndpoint<E> {
+    fn query_token(&self, req: &Request) -> Option<String> {
+        serde_urlencoded::from_str::<Vec<(String, String)>>(req.uri().query()?)
+            .ok()?
+            .into_iter()
+            .find(|(name, _)| name == &self.query_param)
+            .map(|(_, value)| value)
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for PreviewEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let now = unix_secs(Clock::of(&req).now());
+        let valid = |token: &str| verify(&self.key, token).filter(|expires| *expires > now);
+
+        let enabled = if let Some(expires) = self.query_token(&req).as_deref().and_then(valid) {
+            let mut cookie = Cookie::new_with_str(&self.cookie_name, token(&self.key, expires));
+            cookie.set_path("/");
+            cookie.set_secure(self.secure);
+            cookie.set_http_only(true);
+            cookie.set_same_site(SameSite::Lax);
+            cookie.set_max_age(Duration::from_secs(expires - now));
+            req.cookie().add(cookie);
+            true
+        } else if let Some(cookie) = req.cookie().get(&self.cookie_name) {
+            let enabled = valid(cookie.value_str()).is_some();
+            if !enabled {
+                req.cookie().remove(&self.cookie_name);
+            }
+            enabled
+        } else {
+            false
+        };
+
+        if !enabled {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        req.set_data(PreviewMode(true));
+        let mut resp = self.inner.call(req).await?.into_response();
+        let headers = resp.headers_mut();
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-store"),
+        );
+        headers.insert("x-robots-tag", HeaderValue::from_static("noindex"));
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient, web::MockClock, EndpointExt};
+
+    #[handler(internal)]
+    fn index(preview: PreviewMode) -> &'static str {
+        if preview.is_enabled() {
+            "draft"
+        } else {
+            "published"
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let other = hmac::Key::new(hmac::HMAC_SHA256, b"other");
+        assert_eq!(verify(&key, &token(&key, 100)), Some(100));
+        assert_eq!(verify(&other, &token(&key, 100)), None);
+        assert_eq!(verify(&key, &token(&key, 100).replace("100", "200")), None);
+        assert_eq!(verify(&key, "100"), None);
+    }
+
+    #[tokio::test]
+    async fn preview() {
+        let clock = MockClock::at(UNIX_EPOCH + Duration::from_secs(1000));
+        let preview = Preview::new(b"secret").secure(false);
+        let token = preview.token(UNIX_EPOCH + Duration::from_secs(1100));
+        let expired = preview.token(UNIX_EPOCH + Duration::from_secs(900));
+        let cli =
+            TestClient::new(index.with(preview).data(Clock::from(clock.clone()))).cookie_store();
+
+        let resp = cli.get("/").query("preview", &expired).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header_is_not_exist("x-robots-tag");
+        resp.assert_text("published").await;
+
+        let resp = cli.get("/").query("preview", &token).send().await;
+        resp.assert_header("x-robots-tag", "noindex");
+        resp.assert_header(header::CACHE_CONTROL, "private, no-store");
+        resp.assert_text("draft").await;
+
+        // the cookie keeps the preview mode enabled until the token expires
+        cli.get("/").send().await.assert_text("draft").await;
+        clock.advance(Duration::from_secs(200));
+        cli.get("/").send().await.assert_text("published").await;
+        assert!(cli.cookie_jar().unwrap().get("poem-preview").is_none());
+
+        cli.get("/")
+            .query("preview", &"invalid")
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{error::ProblemDetails, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+
+/// Middleware for converting the errors into
+/// [Problem Details](https://www.rfc-editor.org/rfc/rfc7807)
+/// (`application/problem+json`) responses.
+///
+/// The errors created from a [`ProblemDetails`] are rendered unchanged, other
+/// errors are described by their status code and message, and the `instance`
+/// member is set to the path of the request if it is missing.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     error::NotFoundError, handler, http::StatusCode, middleware::ProblemJson,
+///     test::TestClient, EndpointExt, Result, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> Result<()> {
+///     Err(NotFoundError.into())
+/// }
+///
+/// let app = Route::new().at("/users/1", index).with(ProblemJson);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(app).get("/users/1").send().await;
+/// resp.assert_status(StatusCode::NOT_FOUND);
+/// resp.assert_content_type("application/problem+json");
+/// resp.assert_json(serde_json::json!({
+///     "title": "Not Found",
+///     "status": 404,
+///     "detail": "not found",
+///     "instance": "/users/1",
+/// }))
+/// .await;
+/// # });
+/// ```
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ProblemJson;
+
+impl<E: Endpoint> Middleware<E> for ProblemJson {
+    type Output = ProblemJsonEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ProblemJsonEndpoint { inner: ep }
+    }
+}
+
+/// Endpoint for ProblemJson middleware.
+pub struct ProblemJsonEndpoint<E> {
+    inner: E,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ProblemJsonEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let path = req.original_uri().path().to_string();
+        match self.inner.call(req).await {
+            Ok(resp) => Ok(resp.into_response()),
+            Err(err) => {
+                let mut problem = ProblemDetails::from_error(&err);
+                if problem.instance.is_none() {
+                    problem.instance = Some(path);
+                }
+                Err(problem.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt, Error, Route};
+
+    #[tokio::test]
+    async fn problem_json() {
+        #[handler(internal)]
+        fn custom() -> Result<()> {
+            Err(ProblemDetails::new(StatusCode::CONFLICT)
+                .ty("https://example.com/probs/conflict")
+                .extension("id", 1)
+                .into())
+        }
+
+        #[handler(internal)]
+        fn string() -> Result<()> {
+            Err(Error::from_string("invalid name", StatusCode::BAD_REQUEST))
+        }
+
+        let app = Route::new()
+            .at("/custom", custom)
+            .at("/string", string)
+            .with(ProblemJson);
+        let cli = TestClient::new(app);
+
+        let resp = cli.get("/custom").send().await;
+        resp.assert_status(StatusCode::CONFLICT);
+        resp.assert_content_type("application/problem+json");
+        resp.assert_json(json!({
+            "type": "https://example.com/probs/conflict",
+            "title": "Conflict",
+            "status": 409,
+            "instance": "/custom",
+            "id": 1,
+        }))
+        .await;
+
+        let resp = cli.get("/string").send().await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+        resp.assert_json(json!({
+            "title": "Bad Request",
+            "status": 400,
+            "detail": "invalid name",
+            "instance": "/string",
+        }))
+        .await;
+
+        let resp = cli.get("/missing").send().await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+        resp.assert_json(json!({
+            "title": "Not Found",
+            "status": 404,
+            "detail": "not found",
+            "instance": "/missing",
+        }))
+        .await;
+    }
+}
@@ -0,0 +1,174 @@
+//! Conversion of the recorded exchanges to the HTTP Archive format.
+//!
+//! Reference: <http://www.softwareishard.com/blog/har-12-spec/>
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use super::{Exchange, RecordedBody, RecordedHeader};
+
+/// Returns the `(year, month, day)` of a number of days since the Unix epoch.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html>.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Formats a time as an ISO 8601 date in UTC, such as
+/// `2023-06-02T10:00:00.000Z`.
+pub(crate) fn format_iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let rem = secs % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn header_value<'a>(headers: &'a [RecordedHeader], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .map(|header| header.value.as_str())
+}
+
+fn headers(headers: &[RecordedHeader]) -> Value {
+    headers
+        .iter()
+        .map(|header| json!({ "name": header.name, "value": header.value }))
+        .collect()
+}
+
+fn exchange_to_entry(exchange: &Exchange) -> Value {
+    let req = &exchange.request;
+    let url = if req.uri.starts_with('/') {
+        let host = header_value(&req.headers, "host").unwrap_or("localhost");
+        format!("{}://{}{}", req.scheme, host, req.uri)
+    } else {
+        req.uri.clone()
+    };
+    let query = req
+        .uri
+        .split_once('?')
+        .and_then(|(_, query)| serde_urlencoded::from_str::<Vec<(String, String)>>(query).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect::<Value>();
+
+    let mut request = json!({
+        "method": req.method,
+        "url": url,
+        "httpVersion": req.version,
+        "cookies": [],
+        "headers": headers(&req.headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": req.body.size,
+    });
+    if req.body.size > 0 {
+        request["postData"] = json!({
+            "mimeType": header_value(&req.headers, "content-type").unwrap_or_default(),
+            "text": req.body.data,
+        });
+    }
+
+    let response = match &exchange.response {
+        Some(resp) => {
+            let RecordedBody { data, size, .. } = &resp.body;
+            json!({
+                "status": resp.status,
+                "statusText": http::StatusCode::from_u16(resp.status)
+                    .ok()
+                    .and_then(|status| status.canonical_reason())
+                    .unwrap_or_default(),
+                "httpVersion": req.version,
+                "cookies": [],
+                "headers": headers(&resp.headers),
+                "content": {
+                    "size": size,
+                    "mimeType": header_value(&resp.headers, "content-type").unwrap_or_default(),
+                    "text": data,
+                },
+                "redirectURL": header_value(&resp.headers, "location").unwrap_or_default(),
+                "headersSize": -1,
+                "bodySize": size,
+            })
+        }
+        // the endpoint returned an error, which is shown as a failed request
+        None => json!({
+            "status": 0,
+            "statusText": exchange.error.as_deref().unwrap_or_default(),
+            "httpVersion": req.version,
+            "cookies": [],
+            "headers": [],
+            "content": { "size": 0, "mimeType": "" },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": -1,
+        }),
+    };
+
+    let time = exchange.duration.as_secs_f64() * 1000.0;
+    json!({
+        "startedDateTime": format_iso8601(exchange.started_at),
+        "time": time,
+        "request": request,
+        "response": response,
+        "cache": {},
+        "timings": { "send": 0, "wait": time, "receive": 0 },
+    })
+}
+
+pub(super) fn exchanges_to_har<'a>(exchanges: impl Iterator<Item = &'a Exchange>) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": "poem", "version": env!("CARGO_PKG_VERSION") },
+            "entries": exchanges.map(exchange_to_entry).collect::<Vec<_>>(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn iso8601() {
+        assert_eq!(format_iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_iso8601(UNIX_EPOCH + Duration::from_millis(1_685_700_000_123)),
+            "2023-06-02T10:00:00.123Z"
+        );
+        assert_eq!(
+            format_iso8601(UNIX_EPOCH + Duration::from_secs(951_868_800)),
+            "2000-03-01T00:00:00.000Z"
+        );
+        assert_eq!(
+            format_iso8601(UNIX_EPOCH + Duration::from_secs(951_782_399)),
+            "2000-02-28T23:59:59.000Z"
+        );
+    }
+}
@@ -0,0 +1,742 @@
+pub(crate) mod har;
+
+use std::{
+    collections::VecDeque,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::TryStreamExt;
+use http::{header, HeaderMap, HeaderName, Method, StatusCode};
+use parking_lot::Mutex;
+use serde::{Serialize, Serializer};
+
+use crate::{
+    redact::Redactor, web::Json, Body, Endpoint, IntoResponse, Middleware, Request, Response,
+    Result,
+};
+
+/// A header recorded by the [`Recorder`] middleware.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct RecordedHeader {
+    /// The name of the header.
+    pub name: String,
+    /// The value of the header, or `[redacted]`.
+    pub value: String,
+}
+
+/// A body recorded by the [`Recorder`] middleware.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct RecordedBody {
+    /// The beginning of the body, the invalid UTF-8 sequences being replaced.
+    pub data: String,
+    /// The size of the body which has been read.
+    pub size: u64,
+    /// Whether the body is larger than the recorded data.
+    pub truncated: bool,
+}
+
+/// A request recorded by the [`Recorder`] middleware.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct RecordedRequest {
+    /// The method of the request.
+    pub method: String,
+    /// The scheme of the request.
+    pub scheme: String,
+    /// The URI of the request.
+    pub uri: String,
+    /// The HTTP version of the request, such as `HTTP/1.1`.
+    pub version: String,
+    /// The headers of the request.
+    pub headers: Vec<RecordedHeader>,
+    /// The body of the request, as far as it has been read by the endpoint.
+    pub body: RecordedBody,
+}
+
+/// A response recorded by the [`Recorder`] middleware.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct RecordedResponse {
+    /// The status code of the response.
+    pub status: u16,
+    /// The headers of the response.
+    pub headers: Vec<RecordedHeader>,
+    /// The body of the response, as far as it has been sent.
+    pub body: RecordedBody,
+}
+
+/// A request and its response recorded by the [`Recorder`] middleware.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Exchange {
+    /// The sequence number of the exchange.
+    pub id: u64,
+    /// The time the request was received.
+    #[serde(serialize_with = "serialize_unix_ms")]
+    pub started_at: SystemTime,
+    /// The time until the response body was sent.
+    #[serde(serialize_with = "serialize_ms")]
+    pub duration: Duration,
+    /// The request.
+    pub request: RecordedRequest,
+    /// The response, or `None` if the endpoint returned an error.
+    pub response: Option<RecordedResponse>,
+    /// The error returned by the endpoint.
+    pub error: Option<String>,
+}
+
+pub(crate) fn serialize_unix_ms<S: Serializer>(
+    time: &SystemTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let ms = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    serializer.serialize_u64(ms as u64)
+}
+
+fn serialize_ms<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+struct State {
+    enabled: AtomicBool,
+    next_id: AtomicU64,
+    exchanges: Mutex<VecDeque<Exchange>>,
+    file: Mutex<Option<std::fs::File>>,
+}
+
+/// Middleware for recording the requests and their responses, for
+/// debugging.
+///
+/// The most recent exchanges are kept in memory, and can be browsed with the
+/// [`endpoint`](Recorder::endpoint) of the recorder. They can also be
+/// appended to a file, one JSON object per line.
+///
+/// The bodies are recorded while they are streamed, up to
+/// [`max_body_size`](Recorder::max_body_size) bytes, and an exchange is
+/// recorded when its response body has been sent. The values of the
+/// `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie` headers,
+/// the other headers added with [`redact_header`](Recorder::redact_header),
+/// and the header values marked as sensitive are replaced with `[redacted]`,
+/// as are the query parameters and the JSON paths of the
+/// [`redactor`](Recorder::redactor).
+///
+/// Cloning a `Recorder` returns a handle to the same records, which can be
+/// used to [disable](Recorder::set_enabled) it at runtime.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::Recorder, test::TestClient, EndpointExt, Route};
+///
+/// #[handler]
+/// fn index(body: String) -> String {
+///     body
+/// }
+///
+/// let recorder = Recorder::new(100);
+/// let app = Route::new()
+///     .at("/", index.with(recorder.clone()))
+///     .at("/_debug/exchanges", recorder.endpoint());
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.post("/")
+///     .body("hello")
+///     .send()
+///     .await
+///     .assert_text("hello")
+///     .await;
+///
+/// let exchanges = recorder.exchanges();
+/// assert_eq!(exchanges[0].request.body.data, "hello");
+/// assert_eq!(exchanges[0].response.as_ref().unwrap().status, 200);
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct Recorder {
+    state: Arc<State>,
+    capacity: usize,
+    max_body_size: usize,
+    redactor: Arc<Redactor>,
+}
+
+impl Recorder {
+    /// Create a `Recorder` middleware keeping the last `capacity` exchanges
+    /// in memory.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(State {
+                enabled: AtomicBool::new(true),
+                next_id: AtomicU64::new(1),
+                exchanges: Mutex::new(VecDeque::with_capacity(capacity)),
+                file: Mutex::new(None),
+            }),
+            capacity,
+            max_body_size: 64 * 1024,
+            redactor: Arc::new(Redactor::new()),
+        }
+    }
+
+    /// Sets the maximum number of bytes recorded for each body.
+    ///
+    /// Default is `64KiB`.
+    #[must_use]
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Adds a header whose values are not recorded.
+    #[must_use]
+    pub fn redact_header<K>(self, name: K) -> Self
+    where
+        K: TryInto<HeaderName>,
+    {
+        Self {
+            redactor: Arc::new(self.redactor.as_ref().clone().header(name)),
+            ..self
+        }
+    }
+
+    /// Sets the redactor of the headers, the query parameters and the JSON
+    /// bodies.
+    ///
+    /// A JSON body which is truncated is not recorded if the redactor has
+    /// JSON paths, since the redacted values cannot be located in it.
+    #[must_use]
+    pub fn redactor(self, redactor: Redactor) -> Self {
+        Self {
+            redactor: Arc::new(redactor),
+            ..self
+        }
+    }
+
+    /// Also appends the exchanges to the file at `path`, one JSON object per
+    /// line.
+    pub fn file(self, path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.into())?;
+        *self.state.file.lock() = Some(file);
+        Ok(self)
+    }
+
+    /// Returns `true` if the exchanges are recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.state.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Starts or stops recording the exchanges.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the exchanges kept in memory, from the most recent.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.state.exchanges.lock().iter().rev().cloned().collect()
+    }
+
+    /// Returns the exchanges kept in memory in the
+    /// [HAR](http://www.softwareishard.com/blog/har-12-spec/) format, from the
+    /// oldest, which can be imported in the browser devtools and most load
+    /// testing tools.
+    ///
+    /// The redacted header values are exported as `[redacted]`, and the bodies
+    /// are truncated to the recorded data.
+    pub fn har(&self) -> serde_json::Value {
+        har::exchanges_to_har(self.state.exchanges.lock().iter())
+    }
+
+    /// Removes the exchanges kept in memory.
+    pub fn clear(&self) {
+        self.state.exchanges.lock().clear();
+    }
+
+    /// Returns an endpoint for browsing the exchanges kept in memory.
+    ///
+    /// `GET` responds with the exchanges as JSON, from the most recent, or as
+    /// a HAR file with the `format=har` query. `POST`
+    /// with a `enabled=true` or `enabled=false` query toggles the recording,
+    /// and `DELETE` removes them. This endpoint exposes the requests of other
+    /// users, so it must be protected.
+    pub fn endpoint(&self) -> RecorderAdminEndpoint {
+        RecorderAdminEndpoint {
+            recorder: self.clone(),
+        }
+    }
+
+    fn record(&self, exchange: Exchange) {
+        if let Some(file) = &mut *self.state.file.lock() {
+            let mut line = serde_json::to_vec(&exchange).unwrap_or_default();
+            line.push(b'\n');
+            if let Err(err) = file.write_all(&line) {
+                tracing::error!(error = %err, "failed to write the recorded exchange");
+            }
+        }
+
+        if self.capacity > 0 {
+            let mut exchanges = self.state.exchanges.lock();
+            if exchanges.len() == self.capacity {
+                exchanges.pop_front();
+            }
+            exchanges.push_back(exchange);
+        }
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> Vec<RecordedHeader> {
+        self.redactor
+            .headers(headers)
+            .into_iter()
+            .map(|(name, value)| RecordedHeader { name, value })
+            .collect()
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Recorder {
+    type Output = RecorderEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RecorderEndpoint {
+            inner: ep,
+            recorder: self.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Capture {
+    data: BytesMut,
+    size: u64,
+}
+
+impl Capture {
+    fn into_body(
+        self,
+        max: usize,
+        redactor: &Redactor,
+        headers: &[RecordedHeader],
+    ) -> RecordedBody {
+        let content_type = headers
+            .iter()
+            .find(|header| header.name == "content-type")
+            .map(|header| header.value.as_str());
+        RecordedBody {
+            data: redactor.body(content_type, &String::from_utf8_lossy(&self.data)),
+            size: self.size,
+            truncated: self.size > max as u64,
+        }
+    }
+}
+
+/// Records the chunks of `body` into `capture` while they are read.
+fn tee(body: Body, capture: Arc<Mutex<Capture>>, max: usize) -> Body {
+    Body::from_bytes_stream(body.into_bytes_stream().map_ok(move |chunk: Bytes| {
+        let mut capture = capture.lock();
+        capture.size += chunk.len() as u64;
+        let remaining = max.saturating_sub(capture.data.len());
+        capture
+            .data
+            .extend_from_slice(&chunk[..remaining.min(chunk.len())]);
+        chunk
+    }))
+}
+
+/// Records the exchange when it is dropped, with the response body.
+struct PendingExchange {
+    recorder: Recorder,
+    exchange: Option<Exchange>,
+    start: Instant,
+    request_body: Arc<Mutex<Capture>>,
+    response_body: Option<Arc<Mutex<Capture>>>,
+}
+
+impl Drop for PendingExchange {
+    fn drop(&mut self) {
+        if let Some(mut exchange) = self.exchange.take() {
+            let max = self.recorder.max_body_size;
+            let redactor = &self.recorder.redactor;
+            exchange.duration = self.start.elapsed();
+            exchange.request.body = std::mem::take(&mut *self.request_body.lock()).into_body(
+                max,
+                redactor,
+                &exchange.request.headers,
+            );
+            if let (Some(resp), Some(body)) = (&mut exchange.response, &self.response_body) {
+                resp.body =
+                    std::mem::take(&mut *body.lock()).into_body(max, redactor, &resp.headers);
+            }
+            self.recorder.record(exchange);
+        }
+    }
+}
+
+/// Endpoint for Recorder middleware.
+pub struct RecorderEndpoint<E> {
+    inner: E,
+    recorder: Recorder,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RecorderEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if !self.recorder.is_enabled() {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let recorder = &self.recorder;
+        let max = recorder.max_body_size;
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let request = RecordedRequest {
+            method: req.method().to_string(),
+            scheme: req.scheme().to_string(),
+            uri: recorder.redactor.uri(req.original_uri()),
+            version: format!("{:?}", req.version()),
+            headers: recorder.headers(req.headers()),
+            body: RecordedBody::default(),
+        };
+        let request_body = Arc::new(Mutex::new(Capture::default()));
+        let body = req.take_body();
+        if body.is_empty() {
+            req.set_body(body);
+        } else {
+            req.set_body(tee(body, request_body.clone(), max));
+        }
+
+        let res = self.inner.call(req).await.map(IntoResponse::into_response);
+        let mut pending = PendingExchange {
+            recorder: recorder.clone(),
+            exchange: Some(Exchange {
+                id: recorder.state.next_id.fetch_add(1, Ordering::Relaxed),
+                started_at,
+                duration: Duration::ZERO,
+                request,
+                response: None,
+                error: None,
+            }),
+            start,
+            request_body,
+            response_body: None,
+        };
+        let exchange = pending.exchange.as_mut().unwrap();
+
+        match res {
+            Ok(mut resp) => {
+                exchange.response = Some(RecordedResponse {
+                    status: resp.status().as_u16(),
+                    headers: recorder.headers(resp.headers()),
+                    body: RecordedBody::default(),
+                });
+                let body = resp.take_body();
+                if body.is_empty() {
+                    resp.set_body(body);
+                } else {
+                    let capture = Arc::new(Mutex::new(Capture::default()));
+                    pending.response_body = Some(capture.clone());
+                    let body = tee(body, capture, max);
+                    // the exchange is recorded when the body is dropped
+                    let body =
+                        Body::from_bytes_stream(body.into_bytes_stream().map_ok(move |chunk| {
+                            let _pending = &pending;
+                            chunk
+                        }));
+                    resp.set_body(body);
+                }
+                Ok(resp)
+            }
+            Err(err) => {
+                exchange.error = Some(err.to_string());
+                Err(err)
+            }
+        }
+    }
+}
+
+/// An endpoint for browsing the exchanges recorded by a [`Recorder`],
+/// returned by [`Recorder::endpoint`].
+pub struct RecorderAdminEndpoint {
+    recorder: Recorder,
+}
+
+#[async_trait::async_trait]
+impl Endpoint for RecorderAdminEndpoint {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        match *req.method() {
+            Method::GET | Method::HEAD if req.uri().query() == Some("format=har") => {
+                Ok(Json(self.recorder.har())
+                    .with_header(
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"exchanges.har\"",
+                    )
+                    .into_response())
+            }
+            Method::GET | Method::HEAD => Ok(Json(self.recorder.exchanges()).into_response()),
+            Method::POST => {
+                if let Some(enabled) = req.uri().query().and_then(|query| {
+                    serde_urlencoded::from_str::<Vec<(String, bool)>>(query)
+                        .ok()?
+                        .into_iter()
+                        .find(|(name, _)| name == "enabled")
+                }) {
+                    self.recorder.set_enabled(enabled.1);
+                }
+                Ok(
+                    Json(serde_json::json!({ "enabled": self.recorder.is_enabled() }))
+                        .into_response(),
+                )
+            }
+            Method::DELETE => {
+                self.recorder.clear();
+                Ok(StatusCode::NO_CONTENT.into_response())
+            }
+            _ => Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+    use http::header;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[handler(internal)]
+    fn echo(body: String) -> String {
+        body
+    }
+
+    #[tokio::test]
+    async fn record() {
+        let recorder = Recorder::new(2).max_body_size(4).redact_header("x-secret");
+        let cli = TestClient::new(echo.with(recorder.clone()));
+
+        cli.post("/a?x=1")
+            .header(header::AUTHORIZATION, "Basic abc")
+            .header("x-secret", "1")
+            .header("x-public", "2")
+            .body("hello")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+
+        let exchanges = recorder.exchanges();
+        assert_eq!(exchanges.len(), 1);
+        let exchange = &exchanges[0];
+        assert_eq!(exchange.id, 1);
+        assert_eq!(exchange.request.method, "POST");
+        assert_eq!(exchange.request.uri, "/a?x=1");
+        let headers = exchange
+            .request
+            .headers
+            .iter()
+            .map(|header| (header.name.as_str(), header.value.as_str()))
+            .collect::<Vec<_>>();
+        assert!(headers.contains(&("authorization", "[redacted]")));
+        assert!(headers.contains(&("x-secret", "[redacted]")));
+        assert!(headers.contains(&("x-public", "2")));
+        assert_eq!(
+            exchange.request.body,
+            RecordedBody {
+                data: "hell".to_string(),
+                size: 5,
+                truncated: true,
+            }
+        );
+        let resp = exchange.response.as_ref().unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body.data, "hell");
+        assert!(exchange.error.is_none());
+
+        // ring buffer
+        cli.post("/b").send().await.assert_status_is_ok();
+        cli.post("/c").send().await.assert_status_is_ok();
+        let uris = recorder
+            .exchanges()
+            .into_iter()
+            .map(|exchange| exchange.request.uri)
+            .collect::<Vec<_>>();
+        assert_eq!(uris, ["/c", "/b"]);
+
+        recorder.set_enabled(false);
+        cli.post("/d").send().await.assert_status_is_ok();
+        assert_eq!(recorder.exchanges()[0].request.uri, "/c");
+    }
+
+    #[tokio::test]
+    async fn error_and_streaming() {
+        #[handler(internal)]
+        fn index(req: &Request) -> Result<Body> {
+            if req.uri().query() == Some("fail") {
+                return Err(crate::error::NotFoundError.into());
+            }
+            Ok(Body::from_bytes_stream(stream::iter(vec![
+                Ok::<_, std::io::Error>(Bytes::from_static(b"a")),
+                Ok(Bytes::from_static(b"b")),
+            ])))
+        }
+
+        let recorder = Recorder::new(10);
+        let ep = index.with(recorder.clone());
+
+        let resp = ep
+            .call(Request::builder().uri_str("/").finish())
+            .await
+            .unwrap();
+        assert!(recorder.exchanges().is_empty());
+        assert_eq!(resp.into_body().into_string().await.unwrap(), "ab");
+        assert_eq!(
+            recorder.exchanges()[0].response.as_ref().unwrap().body.data,
+            "ab"
+        );
+
+        assert!(ep
+            .call(Request::builder().uri_str("/?fail").finish())
+            .await
+            .is_err());
+        let exchange = &recorder.exchanges()[0];
+        assert!(exchange.response.is_none());
+        assert_eq!(exchange.error.as_deref(), Some("not found"));
+    }
+
+    #[tokio::test]
+    async fn admin_endpoint() {
+        let recorder = Recorder::new(10);
+        let app = crate::Route::new()
+            .at("/", echo.with(recorder.clone()))
+            .at("/exchanges", recorder.endpoint());
+        let cli = TestClient::new(app);
+
+        cli.post("/")
+            .body("hi")
+            .send()
+            .await
+            .assert_text("hi")
+            .await;
+        let resp = cli.get("/exchanges").send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().array().assert_len(1);
+        json.value()
+            .array()
+            .get(0)
+            .object()
+            .get("request")
+            .object()
+            .get("body")
+            .object()
+            .get("data")
+            .assert_string("hi");
+
+        let resp = cli.get("/exchanges?format=har").send().await;
+        resp.assert_header(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"exchanges.har\"",
+        );
+        let har = resp.json().await;
+        let log = har.value().object().get("log").object();
+        log.get("version").assert_string("1.2");
+        let entry = log.get("entries").array().get(0).object();
+        let request = entry.get("request").object();
+        request.get("method").assert_string("POST");
+        request.get("url").assert_string("http://localhost/");
+        request.get("httpVersion").assert_string("HTTP/1.1");
+        request
+            .get("postData")
+            .object()
+            .get("text")
+            .assert_string("hi");
+        let response = entry.get("response").object();
+        response.get("status").assert_i64(200);
+        response.get("statusText").assert_string("OK");
+        response
+            .get("content")
+            .object()
+            .get("text")
+            .assert_string("hi");
+
+        cli.post("/exchanges?enabled=false")
+            .send()
+            .await
+            .assert_json(serde_json::json!({ "enabled": false }))
+            .await;
+        assert!(!recorder.is_enabled());
+
+        cli.delete("/exchanges")
+            .send()
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        assert!(recorder.exchanges().is_empty());
+    }
+
+    #[tokio::test]
+    async fn redact() {
+        #[handler(internal)]
+        fn echo_json(body: String) -> impl IntoResponse {
+            body.with_content_type("application/json")
+        }
+
+        let recorder =
+            Recorder::new(1).redactor(Redactor::new().query_key("token").json_path("password"));
+        let cli = TestClient::new(echo_json.with(recorder.clone()));
+
+        cli.post("/?token=abc&page=1")
+            .content_type("application/json")
+            .body(r#"{"user":"sunli","password":"secret"}"#)
+            .send()
+            .await
+            .assert_json(serde_json::json!({ "user": "sunli", "password": "secret" }))
+            .await;
+
+        let exchange = &recorder.exchanges()[0];
+        assert_eq!(exchange.request.uri, "/?token=[redacted]&page=1");
+        let expected = serde_json::json!({ "user": "sunli", "password": "[redacted]" });
+        for body in [
+            &exchange.request.body,
+            &exchange.response.as_ref().unwrap().body,
+        ] {
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body.data).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn file() {
+        let path =
+            std::env::temp_dir().join(format!("poem-recorder-{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let recorder = Recorder::new(0).file(&path).unwrap();
+        let cli = TestClient::new(echo.with(recorder.clone()));
+
+        cli.post("/").body("a").send().await.assert_status_is_ok();
+        cli.post("/").body("b").send().await.assert_status_is_ok();
+        assert!(recorder.exchanges().is_empty());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines = content
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["request"]["body"]["data"], "b");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,646 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    error::InternalServerError,
+    lock::{with_lock, Lock},
+    middleware::compression::accepts_brotli,
+    single_flight::SingleFlight,
+    web::{CompressionAlgo, CompressionLevel, PreviewMode},
+    Body, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+#[derive(Serialize, Deserialize)]
+struct EntryMeta {
+    key: String,
+    headers: Vec<(String, String)>,
+    body_len: usize,
+}
+
+/// How long the lock of a page is held while it is rendered.
+const LOCK_TTL: Duration = Duration::from_secs(30);
+
+/// The file marking the directories of the versions written by the cache, the
+/// only ones which are removed when the version changes.
+const VERSION_MARKER: &str = ".poem-render-cache";
+
+struct Entry {
+    headers: HeaderMap,
+    body: Bytes,
+    brotli: Bytes,
+}
+
+/// Middleware for caching the rendered pages, along with their brotli
+/// compressed bodies, optionally persisted to the disk so that a restarted
+/// server does not start with an empty cache.
+///
+/// Only the successful responses to the `GET` requests, without a
+/// `Set-Cookie` header and not marked `Cache-Control: no-store` or `private`,
+/// are cached, by path and query, unless the
+/// [`PreviewMode`] is enabled. The clients accepting the `br` encoding
+/// receive the precompressed body.
+///
+/// The concurrent requests for a page which is not cached are coalesced, so
+/// that the page is rendered only once. With a [`lock`](RenderCache::lock)
+/// shared by the instances of an application persisting the pages to the
+/// same directory, it is rendered by only one of them.
+///
+/// The cached pages are only valid for the current [`version`] of the
+/// templates, such as
+/// [`TeraTemplating::templates_hash`](crate::tera::TeraTemplating::templates_hash):
+/// the pages persisted by a previous version are ignored and removed.
+///
+/// [`version`]: RenderCache::version
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::RenderCache, EndpointExt, Route};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let app = Route::new().at("/", index).with(
+///     RenderCache::new()
+///         .persist(std::env::temp_dir().join("render-cache"))
+///         .version("v1"),
+/// );
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "render-cache")))]
+#[derive(Clone)]
+pub struct RenderCache {
+    entries: Arc<RwLock<HashMap<String, Arc<Entry>>>>,
+    dir: Option<PathBuf>,
+    version: String,
+    max_entries: usize,
+    stale_removed: Arc<AtomicBool>,
+    flights: Arc<SingleFlight<Option<Arc<Entry>>>>,
+    lock: Option<Arc<dyn Lock>>,
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderCache {
+    /// Create an in-memory `RenderCache` middleware.
+    pub fn new() -> Self {
+        Self {
+            entries: Default::default(),
+            dir: None,
+            version: String::new(),
+            max_entries: 1024,
+            stale_removed: Default::default(),
+            flights: Default::default(),
+            lock: None,
+        }
+    }
+
+    /// Persists the cached pages in the directory `dir`, and loads the pages
+    /// cached by a previous run from it.
+    ///
+    /// The pages are stored in a subdirectory named after the
+    /// [`version`](RenderCache::version), containing a
+    /// `.poem-render-cache` marker file. When the first page of a version is
+    /// stored, the subdirectories of `dir` with this marker file, written by
+    /// the other versions, are removed. The other files and directories in
+    /// `dir` are left untouched.
+    #[must_use]
+    pub fn persist(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Sets the version of the cached pages, such as a hash of the templates.
+    #[must_use]
+    pub fn version(self, version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of cached pages, the pages are not cached
+    /// anymore when it is reached.
+    ///
+    /// Default is `1024`.
+    #[must_use]
+    pub fn max_entries(self, max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            ..self
+        }
+    }
+
+    /// Renders a page which is not cached while holding the lock with the key
+    /// `render-cache:{path and query}`, and loads the page persisted by
+    /// another instance if it was rendered while waiting for it.
+    #[must_use]
+    pub fn lock(self, lock: impl Lock + 'static) -> Self {
+        Self {
+            lock: Some(Arc::new(lock)),
+            ..self
+        }
+    }
+
+    /// Returns the number of pages cached in memory.
+    pub fn len(&self) -> usize {
+        self.entries.read().len()
+    }
+
+    /// Returns `true` if no page is cached in memory.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the cached pages, in memory and on the disk.
+    pub async fn clear(&self) -> std::io::Result<()> {
+        self.entries.write().clear();
+        if let Some(dir) = self.version_dir() {
+            match tokio::fs::remove_dir_all(dir).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn version_dir(&self) -> Option<PathBuf> {
+        let version = if self.version.is_empty() {
+            "default"
+        } else {
+            &self.version
+        };
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(sanitize_file_name(version)))
+    }
+
+    fn entry_path(&self, key: &str) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.version_dir()
+            .map(|dir| dir.join(format!("{:016x}.bin", hasher.finish())))
+    }
+
+    async fn load(&self, key: &str) -> Option<Entry> {
+        let data = tokio::fs::read(self.entry_path(key)?).await.ok()?;
+        let (entry_key, entry) = decode_entry(Bytes::from(data))?;
+        (entry_key == key).then_some(entry)
+    }
+
+    async fn store(&self, key: &str, entry: &Entry) -> std::io::Result<()> {
+        let (root, dir, path) = match (&self.dir, self.version_dir(), self.entry_path(key)) {
+            (Some(root), Some(dir), Some(path)) => (root, dir, path),
+            _ => return Ok(()),
+        };
+        if !self.stale_removed.swap(true, Ordering::Relaxed) {
+            remove_stale_versions(root, &dir).await;
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        let marker = dir.join(VERSION_MARKER);
+        if tokio::fs::metadata(&marker).await.is_err() {
+            tokio::fs::write(&marker, b"").await?;
+        }
+
+        // write to a temporary file first, so that a concurrent load never
+        // reads a partial entry
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, encode_entry(key, entry)).await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+}
+
+async fn remove_stale_versions(root: &Path, current: &Path) {
+    let mut dirs = match tokio::fs::read_dir(root).await {
+        Ok(dirs) => dirs,
+        Err(_) => return,
+    };
+    while let Ok(Some(dir)) = dirs.next_entry().await {
+        // only the directories written by the cache are removed
+        if dir.path() != current && dir.path().join(VERSION_MARKER).is_file() {
+            tracing::debug!(path = %dir.path().display(), "remove stale render cache");
+            let _ = tokio::fs::remove_dir_all(dir.path()).await;
+        }
+    }
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The entries are stored as the length of the metadata, the JSON metadata,
+/// the body and the brotli compressed body.
+fn encode_entry(key: &str, entry: &Entry) -> Vec<u8> {
+    let meta = serde_json::to_vec(&EntryMeta {
+        key: key.to_string(),
+        headers: entry
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body_len: entry.body.len(),
+    })
+    .unwrap_or_default();
+
+    let mut data = Vec::with_capacity(4 + meta.len() + entry.body.len() + entry.brotli.len());
+    data.extend_from_slice(&(meta.len() as u32).to_be_bytes());
+    data.extend_from_slice(&meta);
+    data.extend_from_slice(&entry.body);
+    data.extend_from_slice(&entry.brotli);
+    data
+}
+
+fn decode_entry(mut data: Bytes) -> Option<(String, Entry)> {
+    if data.len() < 4 {
+        return None;
+    }
+    let meta_len = u32::from_be_bytes(data.split_to(4)[..].try_into().ok()?) as usize;
+    if data.len() < meta_len {
+        return None;
+    }
+    let meta: EntryMeta = serde_json::from_slice(&data.split_to(meta_len)).ok()?;
+    if data.len() < meta.body_len {
+        return None;
+    }
+    let body = data.split_to(meta.body_len);
+    Some((
+        meta.key,
+        Entry {
+            headers: meta
+                .headers
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    Some((
+                        HeaderName::from_bytes(name.as_bytes()).ok()?,
+                        HeaderValue::from_str(&value).ok()?,
+                    ))
+                })
+                .collect(),
+            body,
+            brotli: data,
+        },
+    ))
+}
+
+impl<E: Endpoint> Middleware<E> for RenderCache {
+    type Output = RenderCacheEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RenderCacheEndpoint {
+            inner: ep,
+            cache: self.clone(),
+        }
+    }
+}
+
+/// Endpoint for RenderCache middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "render-cache")))]
+pub struct RenderCacheEndpoint<E> {
+    inner: E,
+    cache: RenderCache,
+}
+
+fn is_cacheable(resp: &Response) -> bool {
+    let no_store = resp
+        .headers()
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| matches!(directive.trim(), "no-store" | "private"));
+    resp.status() == StatusCode::OK
+        && !no_store
+        && !resp.headers().contains_key(header::SET_COOKIE)
+        && !resp.headers().contains_key(header::CONTENT_ENCODING)
+}
+
+fn make_response(entry: &Entry, brotli: bool) -> Response {
+    let mut resp = if brotli {
+        Response::builder()
+            .header(header::CONTENT_ENCODING, "br")
+            .body(Body::from_bytes(entry.brotli.clone()))
+    } else {
+        Response::builder().body(Body::from_bytes(entry.body.clone()))
+    };
+    resp.headers_mut().extend(entry.headers.clone());
+    resp.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    resp
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RenderCacheEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET || PreviewMode::of(&req) {
+            return self.inner.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let key = req
+            .uri()
+            .path_and_query()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let brotli = accepts_brotli(req.headers());
+
+        let entry = self.cache.entries.read().get(&key).cloned();
+        if let Some(entry) = entry {
+            return Ok(make_response(&entry, brotli));
+        }
+
+        // only the first request renders the page, the concurrent requests
+        // wait for it, and render the page themselves if it is not cacheable
+        let mut req = Some(req);
+        let mut uncached = None;
+        let entry = {
+            let (key, req, uncached) = (key.as_str(), &mut req, &mut uncached);
+            self.cache
+                .flights
+                .run(key, move || async move {
+                    match self.render(key, req.take()?).await {
+                        Ok(Ok(entry)) => Some(entry),
+                        Ok(Err(resp)) => {
+                            *uncached = Some(Ok(resp));
+                            None
+                        }
+                        Err(err) => {
+                            *uncached = Some(Err(err));
+                            None
+                        }
+                    }
+                })
+                .await
+        };
+
+        match (entry, uncached, req) {
+            (_, Some(resp), _) => resp,
+            (Some(entry), _, _) => Ok(make_response(&entry, brotli)),
+            (None, None, Some(req)) => self.inner.call(req).await.map(IntoResponse::into_response),
+            (None, None, None) => unreachable!("the rendered page is returned"),
+        }
+    }
+}
+
+impl<E: Endpoint> RenderCacheEndpoint<E> {
+    /// Loads the cached page, or renders it and caches it if it is
+    /// cacheable, or else returns the response.
+    async fn render(&self, key: &str, req: Request) -> Result<Result<Arc<Entry>, Response>> {
+        let entry = self.cache.entries.read().get(key).cloned();
+        if let Some(entry) = entry {
+            return Ok(Ok(entry));
+        }
+        match &self.cache.lock {
+            Some(lock) => {
+                with_lock(
+                    lock,
+                    &format!("render-cache:{key}"),
+                    LOCK_TTL,
+                    self.load_or_render(key, req),
+                )
+                .await?
+            }
+            None => self.load_or_render(key, req).await,
+        }
+    }
+
+    async fn load_or_render(
+        &self,
+        key: &str,
+        req: Request,
+    ) -> Result<Result<Arc<Entry>, Response>> {
+        if let Some(entry) = self.cache.load(key).await {
+            let entry = Arc::new(entry);
+            self.cache
+                .entries
+                .write()
+                .insert(key.to_string(), entry.clone());
+            return Ok(Ok(entry));
+        }
+
+        let resp = self.inner.call(req).await?.into_response();
+        if !is_cacheable(&resp) || self.cache.entries.read().len() >= self.cache.max_entries {
+            return Ok(Err(resp));
+        }
+
+        let (mut parts, body) = resp.into_parts();
+        let body = body.into_bytes().await?;
+        let mut compressed = Vec::new();
+        CompressionAlgo::BR
+            .compress(&body[..], Some(CompressionLevel::Best))
+            .read_to_end(&mut compressed)
+            .await
+            .map_err(InternalServerError)?;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        let entry = Arc::new(Entry {
+            headers: parts.headers,
+            body,
+            brotli: compressed.into(),
+        });
+        if let Err(err) = self.cache.store(key, &entry).await {
+            tracing::warn!(error = %err, "failed to persist the render cache");
+        }
+        self.cache
+            .entries
+            .write()
+            .insert(key.to_string(), entry.clone());
+        Ok(Ok(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{handler, middleware::SetHeader, test::TestClient, web::Html, EndpointExt, Route};
+
+    static RENDERS: AtomicUsize = AtomicUsize::new(0);
+
+    #[handler(internal)]
+    fn index() -> Html<String> {
+        RENDERS.fetch_add(1, Ordering::SeqCst);
+        Html("<p>hello</p>".repeat(10))
+    }
+
+    #[handler(internal)]
+    fn private() -> &'static str {
+        "private"
+    }
+
+    fn app(cache: RenderCache) -> impl Endpoint {
+        Route::new()
+            .at("/", index)
+            .at(
+                "/private",
+                private.with(SetHeader::new().overriding(header::CACHE_CONTROL, "private")),
+            )
+            .with(cache)
+    }
+
+    #[tokio::test]
+    async fn render_cache() {
+        let dir = std::env::temp_dir().join(format!("poem-render-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = RenderCache::new().persist(&dir).version("v1");
+        let cli = TestClient::new(app(cache.clone()));
+
+        let renders = RENDERS.load(Ordering::SeqCst);
+        for _ in 0..2 {
+            let resp = cli.get("/").send().await;
+            resp.assert_content_type("text/html; charset=utf-8");
+            resp.assert_text("<p>hello</p>".repeat(10)).await;
+        }
+        assert_eq!(RENDERS.load(Ordering::SeqCst), renders + 1);
+
+        let resp = cli.get("/").header("accept-encoding", "br").send().await;
+        resp.assert_header("content-encoding", "br");
+        let mut data = String::new();
+        CompressionAlgo::BR
+            .decompress(&resp.0.into_body().into_vec().await.unwrap()[..])
+            .read_to_string(&mut data)
+            .await
+            .unwrap();
+        assert_eq!(data, "<p>hello</p>".repeat(10));
+
+        cli.get("/private")
+            .send()
+            .await
+            .assert_text("private")
+            .await;
+        assert_eq!(cache.len(), 1);
+
+        // a restarted server loads the persisted pages
+        let cli = TestClient::new(app(RenderCache::new().persist(&dir).version("v1")));
+        cli.get("/")
+            .send()
+            .await
+            .assert_text("<p>hello</p>".repeat(10))
+            .await;
+        assert_eq!(RENDERS.load(Ordering::SeqCst), renders + 1);
+
+        // a new version ignores and removes the previous pages, but not the
+        // directories it has not written
+        std::fs::create_dir(dir.join("data")).unwrap();
+        let cli = TestClient::new(app(RenderCache::new().persist(&dir).version("v2")));
+        cli.get("/").send().await.assert_status_is_ok();
+        assert_eq!(RENDERS.load(Ordering::SeqCst), renders + 2);
+        assert!(!dir.join("v1").exists());
+        assert!(dir.join("v2").exists());
+        assert!(dir.join("data").exists());
+
+        cache.clear().await.unwrap();
+        assert!(cache.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn coalesce() {
+        static SLOW_RENDERS: AtomicUsize = AtomicUsize::new(0);
+
+        #[handler(internal)]
+        async fn slow() -> &'static str {
+            SLOW_RENDERS.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            "slow"
+        }
+
+        let cli = TestClient::new(slow.with(RenderCache::new()));
+        let resps = futures_util::future::join_all((0..5).map(|_| cli.get("/").send())).await;
+        for resp in resps {
+            resp.assert_text("slow").await;
+        }
+        assert_eq!(SLOW_RENDERS.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn shared_lock() {
+        static LOCKED_RENDERS: AtomicUsize = AtomicUsize::new(0);
+
+        #[handler(internal)]
+        async fn slow() -> &'static str {
+            LOCKED_RENDERS.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            "slow"
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("poem-render-cache-lock-{}", std::process::id()));
+        let lock = Arc::new(crate::lock::MemoryLock::new());
+        let instance =
+            || TestClient::new(slow.with(RenderCache::new().persist(&dir).lock(lock.clone())));
+        let (a, b) = (instance(), instance());
+        let (resp_a, resp_b) =
+            futures_util::future::join(a.get("/").send(), b.get("/").send()).await;
+        resp_a.assert_text("slow").await;
+        resp_b.assert_text("slow").await;
+        assert_eq!(LOCKED_RENDERS.load(Ordering::SeqCst), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bypass_preview() {
+        static PREVIEW_RENDERS: AtomicUsize = AtomicUsize::new(0);
+
+        #[handler(internal)]
+        fn draft() -> Html<&'static str> {
+            PREVIEW_RENDERS.fetch_add(1, Ordering::SeqCst);
+            Html("draft")
+        }
+
+        let cache = RenderCache::new();
+        let cli = TestClient::new(draft.with(cache.clone()).data(PreviewMode(true)));
+        for _ in 0..2 {
+            cli.get("/").send().await.assert_text("draft").await;
+        }
+        assert_eq!(PREVIEW_RENDERS.load(Ordering::SeqCst), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        let entry = Entry {
+            headers,
+            body: Bytes::from_static(b"body"),
+            brotli: Bytes::from_static(b"br"),
+        };
+        let (key, decoded) = decode_entry(encode_entry("/a?b=1", &entry).into()).unwrap();
+        assert_eq!(key, "/a?b=1");
+        assert_eq!(decoded.headers, entry.headers);
+        assert_eq!(decoded.body, entry.body);
+        assert_eq!(decoded.brotli, entry.brotli);
+        assert!(decode_entry(Bytes::from_static(b"\0\0\0\x09{")).is_none());
+    }
+}
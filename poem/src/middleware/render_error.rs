@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use crate::{
+    error::ProblemDetails, web::parse_accept, Endpoint, Error, IntoResponse, Middleware, Request,
+    Response, Result,
+};
+
+type HtmlRenderer = Arc<dyn Fn(&ProblemDetails) -> String + Send + Sync>;
+
+/// Marks the errors which have already been rendered, so that the outer
+/// `RenderError` middleware leaves them unchanged.
+pub(crate) struct Rendered;
+
+/// Middleware for rendering the errors in the format preferred by the client.
+///
+/// Every error is described by a [`ProblemDetails`], as with the
+/// [`ProblemJson`](crate::middleware::ProblemJson) middleware. It is rendered
+/// as an HTML page if the `Accept` header prefers `text/html`, which is the
+/// case for browsers, and as `application/problem+json` otherwise.
+///
+/// The HTML page can be customized with [`RenderError::html`], for example to
+/// render a template with the members of [`ProblemDetails::to_json`]. The
+/// middleware can be applied to several route groups with different
+/// templates, an error rendered by an inner group is not rendered again by
+/// the outer one.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     error::NotFoundError, handler, http::StatusCode, middleware::RenderError, test::TestClient,
+///     EndpointExt, Result, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> Result<()> {
+///     Err(NotFoundError.into())
+/// }
+///
+/// let app = Route::new()
+///     .nest("/api", Route::new().at("/users", index))
+///     .nest(
+///         "/admin",
+///         Route::new().at("/users", index).with(
+///             RenderError::new()
+///                 .html(|problem| format!("<h1>Admin: {}</h1>", problem.status.as_u16())),
+///         ),
+///     )
+///     .with(RenderError::new());
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .get("/api/users")
+///     .header("accept", "application/json")
+///     .send()
+///     .await;
+/// resp.assert_status(StatusCode::NOT_FOUND);
+/// resp.assert_content_type("application/problem+json");
+///
+/// let resp = cli
+///     .get("/admin/users")
+///     .header("accept", "text/html")
+///     .send()
+///     .await;
+/// resp.assert_status(StatusCode::NOT_FOUND);
+/// resp.assert_content_type("text/html; charset=utf-8");
+/// resp.assert_text("<h1>Admin: 404</h1>").await;
+/// # });
+/// ```
+pub struct RenderError {
+    html: HtmlRenderer,
+}
+
+impl Default for RenderError {
+    fn default() -> Self {
+        Self {
+            html: Arc::new(default_html),
+        }
+    }
+}
+
+impl RenderError {
+    /// Create new `RenderError` middleware.
+    #[must_use]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the function that renders the HTML page of an error.
+    #[must_use]
+    pub fn html(self, f: impl Fn(&ProblemDetails) -> String + Send + Sync + 'static) -> Self {
+        Self { html: Arc::new(f) }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RenderError {
+    type Output = RenderErrorEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RenderErrorEndpoint {
+            inner: ep,
+            html: self.html.clone(),
+        }
+    }
+}
+
+/// Endpoint for RenderError middleware.
+pub struct RenderErrorEndpoint<E> {
+    inner: E,
+    html: HtmlRenderer,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RenderErrorEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let path = req.original_uri().path().to_string();
+        let prefer_html = prefer_html(&req);
+
+        let err = match self.inner.call(req).await {
+            Ok(resp) => return Ok(resp.into_response()),
+            Err(err) if err.data::<Rendered>().is_some() => return Err(err),
+            Err(err) => err,
+        };
+
+        let mut problem = ProblemDetails::from_error(&err);
+        if problem.instance.is_none() {
+            problem.instance = Some(path);
+        }
+        let resp = if prefer_html {
+            Response::builder()
+                .status(problem.status)
+                .content_type("text/html; charset=utf-8")
+                .body((self.html)(&problem))
+        } else {
+            Error::from(problem).into_response()
+        };
+
+        let mut err = Error::from_response(resp);
+        err.set_data(Rendered);
+        Err(err)
+    }
+}
+
+/// Returns `true` if the client prefers HTML to JSON.
+pub(crate) fn prefer_html(req: &Request) -> bool {
+    parse_accept(req.headers())
+        .iter()
+        .find_map(
+            |mime| match (mime.type_().as_str(), mime.subtype().as_str()) {
+                ("text", "html") | ("application", "xhtml") => Some(true),
+                ("application", "json") => Some(false),
+                _ if mime.suffix().map_or(false, |suffix| suffix == "json") => Some(false),
+                _ => None,
+            },
+        )
+        .unwrap_or_default()
+}
+
+fn default_html(problem: &ProblemDetails) -> String {
+    let title = html_escape(problem.title.as_deref().unwrap_or_default());
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{status} \
+         {title}</title></head><body><h1>{status} {title}</h1>",
+        status = problem.status.as_u16(),
+    );
+    if let Some(detail) = &problem.detail {
+        html.push_str(&format!("<p>{}</p>", html_escape(detail)));
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+pub(crate) fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, middleware::ProblemJson, test::TestClient, EndpointExt, Route};
+
+    #[handler(internal)]
+    fn index() -> Result<()> {
+        Err(Error::from_string("<script>", StatusCode::BAD_REQUEST))
+    }
+
+    #[tokio::test]
+    async fn negotiate() {
+        let cli = TestClient::new(index.with(RenderError::new()));
+
+        for accept in [None, Some("application/json"), Some("*/*")] {
+            let mut req = cli.get("/");
+            if let Some(accept) = accept {
+                req = req.header("accept", accept);
+            }
+            let resp = req.send().await;
+            resp.assert_status(StatusCode::BAD_REQUEST);
+            resp.assert_content_type("application/problem+json");
+            resp.assert_json(serde_json::json!({
+                "title": "Bad Request",
+                "status": 400,
+                "detail": "<script>",
+                "instance": "/",
+            }))
+            .await;
+        }
+
+        let resp = cli
+            .get("/")
+            .header(
+                "accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            )
+            .send()
+            .await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+        resp.assert_content_type("text/html; charset=utf-8");
+        resp.assert_text(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>400 Bad \
+             Request</title></head><body><h1>400 Bad Request</h1><p>&lt;script&gt;</p></body></html>",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        let app = Route::new()
+            .nest(
+                "/inner",
+                Route::new()
+                    .at("/", index)
+                    .with(RenderError::new().html(|_| "inner".to_string())),
+            )
+            .at("/problem", index.with(ProblemJson))
+            .with(RenderError::new().html(|_| "outer".to_string()));
+        let cli = TestClient::new(app);
+
+        let resp = cli.get("/inner").header("accept", "text/html").send().await;
+        resp.assert_text("inner").await;
+
+        let resp = cli
+            .get("/problem")
+            .header("accept", "text/html")
+            .send()
+            .await;
+        resp.assert_text("outer").await;
+
+        let resp = cli
+            .get("/missing")
+            .header("accept", "text/html")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+        resp.assert_text("outer").await;
+    }
+}
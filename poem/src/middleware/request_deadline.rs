@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use crate::{
+    web::{deadline::REQUEST_TIMEOUT, Clock, Deadline},
+    Endpoint, Middleware, Request, Result,
+};
+
+/// Middleware for setting the [`Deadline`] of the requests, failing them
+/// with [`DeadlineExceededError`](crate::error::DeadlineExceededError) once
+/// it expires.
+///
+/// The deadline is the configured timeout, shortened by the
+/// `X-Request-Timeout` header of the request, in milliseconds, so that the
+/// callers can propagate their own deadlines. A nested `RequestDeadline`
+/// only shortens the deadline of the outer one.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{
+///     handler, http::StatusCode, middleware::RequestDeadline, test::TestClient, web::Deadline,
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// async fn index(deadline: Deadline) -> String {
+///     tokio::time::sleep(Duration::from_millis(50)).await;
+///     format!("{}ms left", deadline.remaining().as_millis())
+/// }
+///
+/// let cli = TestClient::new(index.with(RequestDeadline::new(Duration::from_secs(10))));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .header("x-request-timeout", "10")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::GATEWAY_TIMEOUT);
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequestDeadline {
+    timeout: Duration,
+    from_header: bool,
+}
+
+impl RequestDeadline {
+    /// Create a `RequestDeadline` middleware expiring the requests after
+    /// `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            from_header: true,
+        }
+    }
+
+    /// Sets whether the `X-Request-Timeout` header shortens the deadline,
+    /// which should be disabled for the untrusted clients.
+    ///
+    /// Default is `true`.
+    #[must_use]
+    pub fn from_header(self, from_header: bool) -> Self {
+        Self {
+            from_header,
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestDeadline {
+    type Output = RequestDeadlineEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestDeadlineEndpoint {
+            inner: ep,
+            timeout: self.timeout,
+            from_header: self.from_header,
+        }
+    }
+}
+
+/// Endpoint for RequestDeadline middleware.
+pub struct RequestDeadlineEndpoint<E> {
+    inner: E,
+    timeout: Duration,
+    from_header: bool,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RequestDeadlineEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let requested = self
+            .from_header
+            .then(|| req.headers().get(REQUEST_TIMEOUT))
+            .flatten()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .map(Duration::from_millis);
+        let timeout = match requested {
+            Some(requested) => requested.min(self.timeout),
+            None => self.timeout,
+        };
+
+        let mut deadline = Deadline::after_with_clock(timeout, Clock::of(&req));
+        if let Some(outer) = Deadline::of(&req) {
+            deadline = deadline.min(outer.clone());
+        }
+        req.set_data(deadline.clone());
+
+        deadline.run(self.inner.call(req)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[handler(internal)]
+    async fn index(deadline: Deadline, req: &Request) -> String {
+        if let Some(delay) = req.header("x-delay") {
+            tokio::time::sleep(Duration::from_millis(delay.parse().unwrap())).await;
+        }
+        let remaining = deadline.remaining();
+        assert!(remaining > Duration::ZERO);
+        (remaining.as_secs_f64().ceil() as u64).to_string()
+    }
+
+    #[tokio::test]
+    async fn deadline() {
+        let cli = TestClient::new(index.with(RequestDeadline::new(Duration::from_secs(10))));
+        cli.get("/").send().await.assert_text("10").await;
+        cli.get("/")
+            .header("x-request-timeout", "2000")
+            .send()
+            .await
+            .assert_text("2")
+            .await;
+        cli.get("/")
+            .header("x-request-timeout", "20000")
+            .send()
+            .await
+            .assert_text("10")
+            .await;
+        cli.get("/")
+            .header("x-request-timeout", "10")
+            .header("x-delay", "100")
+            .send()
+            .await
+            .assert_status(StatusCode::GATEWAY_TIMEOUT);
+
+        let cli = TestClient::new(
+            index.with(RequestDeadline::new(Duration::from_secs(10)).from_header(false)),
+        );
+        cli.get("/")
+            .header("x-request-timeout", "2000")
+            .send()
+            .await
+            .assert_text("10")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        let cli = TestClient::new(
+            index
+                .with(RequestDeadline::new(Duration::from_secs(10)))
+                .with(RequestDeadline::new(Duration::from_secs(3))),
+        );
+        cli.get("/").send().await.assert_text("3").await;
+    }
+}
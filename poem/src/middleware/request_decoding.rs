@@ -0,0 +1,354 @@
+use std::str::FromStr;
+
+use encoding_rs::{Encoding, UTF_8};
+use mime::Mime;
+
+use crate::{
+    error::{ReadBodyError, RequestDecodingError},
+    http::{header, HeaderValue},
+    web::CompressionAlgo,
+    Body, Endpoint, Middleware, Request, Result,
+};
+
+/// Middleware for decoding the request bodies before the extractors run.
+///
+/// The bodies compressed with `gzip`, `deflate`, `br` or `zstd`, according to
+/// the `Content-Encoding` header, are decompressed, and the text bodies with
+/// another charset than UTF-8 in their `Content-Type` header, such as
+/// `text/plain; charset=iso-8859-1`, are converted to UTF-8. The headers are
+/// updated to describe the decoded body.
+///
+/// The decompressed size is limited, so that a small compressed body cannot
+/// expand to an unbounded amount of memory: the requests whose decoded body
+/// is larger than [`max_size`](RequestDecoding::max_size), or larger than
+/// [`max_ratio`](RequestDecoding::max_ratio) times the compressed body, are
+/// rejected with `413 Payload Too Large`.
+///
+/// # Errors
+///
+/// - [`RequestDecodingError`]
+/// - [`ReadBodyError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::RequestDecoding, test::TestClient, EndpointExt};
+///
+/// #[handler]
+/// fn index(body: String) -> String {
+///     body
+/// }
+///
+/// let cli = TestClient::new(index.with(RequestDecoding::new()));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.post("/")
+///     .content_type("text/plain; charset=iso-8859-1")
+///     .body(b"caf\xe9".to_vec())
+///     .send()
+///     .await
+///     .assert_text("café")
+///     .await;
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "request-decoding")))]
+#[derive(Debug, Clone, Copy)]
+pub struct RequestDecoding {
+    max_size: usize,
+    max_ratio: usize,
+    charset: bool,
+}
+
+impl Default for RequestDecoding {
+    fn default() -> Self {
+        Self {
+            max_size: 16 * 1024 * 1024,
+            max_ratio: 100,
+            charset: true,
+        }
+    }
+}
+
+impl RequestDecoding {
+    /// Create a `RequestDecoding` middleware.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum size of the decoded body, in bytes.
+    ///
+    /// Default is `16MiB`.
+    #[must_use]
+    pub fn max_size(self, max_size: usize) -> Self {
+        Self { max_size, ..self }
+    }
+
+    /// Sets the maximum ratio between the sizes of the decompressed and the
+    /// compressed bodies.
+    ///
+    /// Default is `100`.
+    #[must_use]
+    pub fn max_ratio(self, max_ratio: usize) -> Self {
+        Self { max_ratio, ..self }
+    }
+
+    /// Sets whether the text bodies are converted to UTF-8.
+    ///
+    /// Default is `true`.
+    #[must_use]
+    pub fn charset(self, charset: bool) -> Self {
+        Self { charset, ..self }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestDecoding {
+    type Output = RequestDecodingEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestDecodingEndpoint {
+            inner: ep,
+            config: *self,
+        }
+    }
+}
+
+/// Endpoint for RequestDecoding middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "request-decoding")))]
+pub struct RequestDecodingEndpoint<E> {
+    inner: E,
+    config: RequestDecoding,
+}
+
+impl<E> RequestDecodingEndpoint<E> {
+    /// Returns the compression algorithms of the body, in the order they were
+    /// applied.
+    fn encodings(req: &Request) -> Result<Vec<CompressionAlgo>, RequestDecodingError> {
+        req.headers()
+            .get_all(header::CONTENT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"))
+            .map(|coding| {
+                CompressionAlgo::from_str(&coding.to_ascii_lowercase())
+                    .map_err(|_| RequestDecodingError::UnsupportedEncoding(coding.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns the charset of a body which must be converted, and its content
+    /// type with the UTF-8 charset.
+    fn charset(
+        req: &Request,
+    ) -> Result<Option<(&'static Encoding, HeaderValue)>, RequestDecodingError> {
+        let mime = match req
+            .content_type()
+            .and_then(|content_type| content_type.parse::<Mime>().ok())
+        {
+            Some(mime) => mime,
+            None => return Ok(None),
+        };
+        let charset = match mime.get_param(mime::CHARSET) {
+            Some(charset) => charset,
+            None => return Ok(None),
+        };
+        let encoding = Encoding::for_label(charset.as_str().as_bytes())
+            .ok_or_else(|| RequestDecodingError::UnsupportedCharset(charset.to_string()))?;
+        if encoding == UTF_8 {
+            return Ok(None);
+        }
+
+        let mut content_type = mime.essence_str().to_string();
+        for (name, value) in mime.params().filter(|(name, _)| *name != mime::CHARSET) {
+            content_type.push_str(&format!("; {name}={value}"));
+        }
+        content_type.push_str("; charset=utf-8");
+        Ok(HeaderValue::try_from(content_type)
+            .ok()
+            .map(|content_type| (encoding, content_type)))
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RequestDecodingEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let encodings = Self::encodings(&req)?;
+        let charset = if self.config.charset {
+            Self::charset(&req)?
+        } else {
+            None
+        };
+        if encodings.is_empty() && charset.is_none() {
+            return self.inner.call(req).await;
+        }
+
+        let mut data = req
+            .take_body()
+            .into_bytes_limit(self.config.max_size)
+            .await?;
+        if !encodings.is_empty() {
+            let limit = self
+                .config
+                .max_size
+                .min(data.len().saturating_mul(self.config.max_ratio));
+            let mut body = Body::from(data);
+            for algo in encodings.iter().rev() {
+                body = Body::from_async_read(algo.decompress(body.into_async_read()));
+            }
+            data = body.into_bytes_limit(limit).await?;
+            req.headers_mut().remove(header::CONTENT_ENCODING);
+        }
+
+        if let Some((encoding, content_type)) = charset {
+            let (text, _) = encoding.decode_without_bom_handling(&data);
+            data = text.into_owned().into();
+            if data.len() > self.config.max_size {
+                return Err(ReadBodyError::PayloadTooLarge.into());
+            }
+            req.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+
+        req.headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(data.len()));
+        req.set_body(data);
+        self.inner.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[handler(internal)]
+    fn index(req: &Request, body: String) -> String {
+        format!(
+            "{} {:?} {:?}",
+            body,
+            req.content_type(),
+            req.header(header::CONTENT_ENCODING)
+        )
+    }
+
+    async fn compress(algo: CompressionAlgo, data: &[u8]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        algo.compress(data, None)
+            .read_to_end(&mut compressed)
+            .await
+            .unwrap();
+        compressed
+    }
+
+    #[tokio::test]
+    async fn decompress() {
+        let cli = TestClient::new(index.with(RequestDecoding::new()));
+        for algo in [
+            CompressionAlgo::GZIP,
+            CompressionAlgo::DEFLATE,
+            CompressionAlgo::BR,
+            CompressionAlgo::ZSTD,
+        ] {
+            cli.post("/")
+                .header(header::CONTENT_ENCODING, algo.as_str())
+                .body(compress(algo, b"hello").await)
+                .send()
+                .await
+                .assert_text("hello None None")
+                .await;
+        }
+
+        // the encodings are removed in the reverse order
+        let data = compress(CompressionAlgo::GZIP, b"hello").await;
+        cli.post("/")
+            .header(header::CONTENT_ENCODING, "gzip, br")
+            .body(compress(CompressionAlgo::BR, &data).await)
+            .send()
+            .await
+            .assert_text("hello None None")
+            .await;
+
+        cli.post("/")
+            .header(header::CONTENT_ENCODING, "compress")
+            .body("hello")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        cli.post("/")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body("hello")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let data = vec![b'a'; 100_000];
+        let compressed = compress(CompressionAlgo::GZIP, &data).await;
+        assert!(compressed.len() * 100 < data.len());
+
+        let cli = TestClient::new(index.with(RequestDecoding::new()));
+        cli.post("/")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(compressed.clone())
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+
+        let cli = TestClient::new(index.with(RequestDecoding::new().max_ratio(10_000)));
+        cli.post("/")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(compressed.clone())
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let cli =
+            TestClient::new(index.with(RequestDecoding::new().max_ratio(10_000).max_size(50_000)));
+        cli.post("/")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(compressed)
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn charset() {
+        let cli = TestClient::new(index.with(RequestDecoding::new()));
+        cli.post("/")
+            .content_type("text/plain; format=flowed; charset=windows-1252")
+            .body(b"\x93caf\xe9\x94".to_vec())
+            .send()
+            .await
+            .assert_text(r#"“café” Some("text/plain; format=flowed; charset=utf-8") None"#)
+            .await;
+        cli.post("/")
+            .content_type("text/plain; charset=utf-8")
+            .body("café")
+            .send()
+            .await
+            .assert_text(r#"café Some("text/plain; charset=utf-8") None"#)
+            .await;
+        cli.post("/")
+            .content_type("text/plain; charset=klingon")
+            .body("café")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let cli = TestClient::new(index.with(RequestDecoding::new().charset(false)));
+        cli.post("/")
+            .content_type("text/plain; charset=iso-8859-1")
+            .body(b"caf\xe9".to_vec())
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
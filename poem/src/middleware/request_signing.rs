@@ -0,0 +1,471 @@
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{HeaderName, Method};
+use ring::{
+    digest, hmac,
+    signature::{Ed25519KeyPair, KeyPair as _, UnparsedPublicKey, ED25519},
+};
+
+use crate::{
+    error::RequestSignatureError,
+    web::{Clock, Principal},
+    Endpoint, Middleware, Request, Result,
+};
+
+const KEY_ID: &str = "x-signature-key-id";
+const TIMESTAMP: &str = "x-signature-timestamp";
+const SIGNATURE: &str = "x-signature";
+
+/// Returns the signed message of a request.
+fn message(method: &Method, path: &str, timestamp: &str, body: &[u8]) -> Vec<u8> {
+    let digest = digest::digest(&digest::SHA256, body);
+    format!(
+        "{}\n{}\n{}\n{}",
+        method,
+        path,
+        timestamp,
+        STANDARD.encode(digest)
+    )
+    .into_bytes()
+}
+
+#[derive(Clone)]
+enum SigningKey {
+    Hmac(hmac::Key),
+    Ed25519(Arc<Ed25519KeyPair>),
+}
+
+/// Signs the requests sent to the services protected by the
+/// [`RequestSignature`] middleware.
+///
+/// The signature covers the method, the path with the query string, the
+/// timestamp and the SHA-256 digest of the body, and is sent with the key id
+/// and the timestamp in the `X-Signature`, `X-Signature-Key-Id` and
+/// `X-Signature-Timestamp` headers.
+///
+/// # Example
+///
+/// ```
+/// use poem::{http::Request, middleware::RequestSigner};
+///
+/// let signer = RequestSigner::hmac("billing", "secret");
+/// let mut req = Request::post("http://orders/orders?notify=1")
+///     .body(br#"{"id":1}"#.to_vec())
+///     .unwrap();
+/// signer.sign_request(&mut req);
+/// assert!(req.headers().contains_key("x-signature"));
+/// ```
+#[derive(Clone)]
+pub struct RequestSigner {
+    key_id: String,
+    key: SigningKey,
+}
+
+impl RequestSigner {
+    /// Create a signer with a HMAC-SHA256 `secret` shared with the services,
+    /// identified by `key_id`.
+    pub fn hmac(key_id: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key_id: key_id.into(),
+            key: SigningKey::Hmac(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref())),
+        }
+    }
+
+    /// Create a signer with an Ed25519 private key in the PKCS#8 format,
+    /// identified by `key_id`.
+    ///
+    /// The services verify the signatures with its
+    /// [`public_key`](RequestSigner::public_key).
+    pub fn ed25519(key_id: impl Into<String>, pkcs8: impl AsRef<[u8]>) -> IoResult<Self> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+            .map_err(|_| IoError::new(ErrorKind::InvalidInput, "invalid ed25519 key pair"))?;
+        Ok(Self {
+            key_id: key_id.into(),
+            key: SigningKey::Ed25519(Arc::new(key_pair)),
+        })
+    }
+
+    /// Returns the id of the key.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Returns the public key of an Ed25519 signer, or `None` for a HMAC
+    /// signer.
+    pub fn public_key(&self) -> Option<&[u8]> {
+        match &self.key {
+            SigningKey::Hmac(_) => None,
+            SigningKey::Ed25519(key_pair) => Some(key_pair.public_key().as_ref()),
+        }
+    }
+
+    /// Returns the headers signing a request, sent at the time `timestamp`.
+    ///
+    /// `path` is the path of the request with its query string, such as
+    /// `/orders?notify=1`.
+    pub fn sign(
+        &self,
+        method: &Method,
+        path: &str,
+        body: &[u8],
+        timestamp: SystemTime,
+    ) -> Vec<(HeaderName, String)> {
+        let timestamp = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let message = message(method, path, &timestamp, body);
+        let signature = match &self.key {
+            SigningKey::Hmac(key) => STANDARD.encode(hmac::sign(key, &message)),
+            SigningKey::Ed25519(key_pair) => STANDARD.encode(key_pair.sign(&message)),
+        };
+        vec![
+            (HeaderName::from_static(KEY_ID), self.key_id.clone()),
+            (HeaderName::from_static(TIMESTAMP), timestamp),
+            (HeaderName::from_static(SIGNATURE), signature),
+        ]
+    }
+
+    /// Adds the headers signing `req` at the current time.
+    pub fn sign_request<T: AsRef<[u8]>>(&self, req: &mut http::Request<T>) {
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/")
+            .to_string();
+        let headers = self.sign(req.method(), &path, req.body().as_ref(), SystemTime::now());
+        for (name, value) in headers {
+            if let Ok(value) = value.parse() {
+                req.headers_mut().insert(name, value);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+enum VerifyingKey {
+    Hmac(hmac::Key),
+    Ed25519(Arc<[u8]>),
+}
+
+/// Middleware for authenticating the calls of other services, with the
+/// signatures of the requests produced by a [`RequestSigner`].
+///
+/// The key is selected by the `X-Signature-Key-Id` header, and the signed
+/// timestamp must be within the [`skew`](RequestSignature::skew) of the
+/// current time, which rejects the replayed requests. The body is read to
+/// verify its digest, so the [`SizeLimit`](crate::middleware::SizeLimit)
+/// middleware should be applied outside of this middleware. The key id is
+/// the [`Principal`] of the authenticated requests.
+///
+/// # Errors
+///
+/// - [`RequestSignatureError`]
+///
+/// # Example
+///
+/// ```
+/// use std::time::SystemTime;
+///
+/// use poem::{
+///     handler,
+///     http::{Method, StatusCode},
+///     middleware::{RequestSignature, RequestSigner},
+///     test::TestClient,
+///     web::Principal,
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index(principal: Principal, body: String) -> String {
+///     format!("{principal}: {body}")
+/// }
+///
+/// let cli = TestClient::new(index.with(RequestSignature::new().hmac_key("billing", "secret")));
+/// let signer = RequestSigner::hmac("billing", "secret");
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut req = cli.post("/").body("hello");
+/// for (name, value) in signer.sign(&Method::POST, "/", b"hello", SystemTime::now()) {
+///     req = req.header(name, value);
+/// }
+/// req.send().await.assert_text("billing: hello").await;
+///
+/// cli.post("/")
+///     .body("hello")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::UNAUTHORIZED);
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct RequestSignature {
+    keys: HashMap<String, VerifyingKey>,
+    skew: Duration,
+}
+
+impl Default for RequestSignature {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            skew: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl RequestSignature {
+    /// Create a `RequestSignature` middleware without keys.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Accepts the signatures with the HMAC-SHA256 `secret` identified by
+    /// `key_id`.
+    #[must_use]
+    pub fn hmac_key(mut self, key_id: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        self.keys.insert(
+            key_id.into(),
+            VerifyingKey::Hmac(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref())),
+        );
+        self
+    }
+
+    /// Accepts the signatures with the Ed25519 `public_key` identified by
+    /// `key_id`.
+    #[must_use]
+    pub fn ed25519_key(mut self, key_id: impl Into<String>, public_key: impl AsRef<[u8]>) -> Self {
+        self.keys.insert(
+            key_id.into(),
+            VerifyingKey::Ed25519(public_key.as_ref().into()),
+        );
+        self
+    }
+
+    /// Sets the maximum difference between the signed timestamp and the
+    /// current time.
+    ///
+    /// Default is `5 minutes`.
+    #[must_use]
+    pub fn skew(self, skew: Duration) -> Self {
+        Self { skew, ..self }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestSignature {
+    type Output = RequestSignatureEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestSignatureEndpoint {
+            inner: ep,
+            keys: Arc::new(self.keys.clone()),
+            skew: self.skew,
+        }
+    }
+}
+
+/// Endpoint for RequestSignature middleware.
+pub struct RequestSignatureEndpoint<E> {
+    inner: E,
+    keys: Arc<HashMap<String, VerifyingKey>>,
+    skew: Duration,
+}
+
+fn header(req: &Request, name: &'static str) -> Result<String, RequestSignatureError> {
+    req.header(name)
+        .map(ToString::to_string)
+        .ok_or(RequestSignatureError::MissingHeader(name))
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for RequestSignatureEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let key_id = header(&req, KEY_ID)?;
+        let timestamp = header(&req, TIMESTAMP)?;
+        let signature = STANDARD
+            .decode(header(&req, SIGNATURE)?)
+            .map_err(|_| RequestSignatureError::Malformed)?;
+        let key = self
+            .keys
+            .get(&key_id)
+            .ok_or_else(|| RequestSignatureError::UnknownKey(key_id.clone()))?;
+
+        let signed_at = timestamp
+            .parse::<u64>()
+            .ok()
+            .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+            .ok_or(RequestSignatureError::Malformed)?;
+        let now = Clock::of(&req).now();
+        let skew = match now.duration_since(signed_at) {
+            Ok(elapsed) => elapsed,
+            Err(err) => err.duration(),
+        };
+        if skew > self.skew {
+            return Err(RequestSignatureError::Expired.into());
+        }
+
+        let body = req.take_body().into_bytes().await?;
+        let path = req
+            .original_uri()
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+        let message = message(req.method(), path, &timestamp, &body);
+        let verified = match key {
+            VerifyingKey::Hmac(key) => hmac::verify(key, &message, &signature).is_ok(),
+            VerifyingKey::Ed25519(public_key) => {
+                UnparsedPublicKey::new(&ED25519, public_key.as_ref())
+                    .verify(&message, &signature)
+                    .is_ok()
+            }
+        };
+        if !verified {
+            return Err(RequestSignatureError::Invalid.into());
+        }
+
+        req.set_body(body);
+        req.extensions_mut().insert(Principal(key_id));
+        self.inner.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::rand::SystemRandom;
+
+    use super::*;
+    use crate::{
+        handler,
+        http::StatusCode,
+        test::{TestClient, TestRequestBuilder},
+        web::MockClock,
+        EndpointExt,
+    };
+
+    #[handler(internal)]
+    fn index(principal: Principal, body: String) -> String {
+        format!("{principal}: {body}")
+    }
+
+    fn signed<'a, E: Endpoint>(
+        cli: &'a TestClient<E>,
+        signer: &RequestSigner,
+        path: &str,
+        body: &'static str,
+        timestamp: SystemTime,
+    ) -> TestRequestBuilder<'a, E> {
+        let mut req = cli.post(path).body(body);
+        for (name, value) in signer.sign(&Method::POST, path, body.as_bytes(), timestamp) {
+            req = req.header(name, value);
+        }
+        req
+    }
+
+    #[tokio::test]
+    async fn verify() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let ed25519 = RequestSigner::ed25519("orders", pkcs8.as_ref()).unwrap();
+        let hmac = RequestSigner::hmac("billing", "secret");
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let cli = TestClient::new(
+            index
+                .with(
+                    RequestSignature::new()
+                        .hmac_key("billing", "secret")
+                        .ed25519_key("orders", ed25519.public_key().unwrap())
+                        .skew(Duration::from_secs(30)),
+                )
+                .data(Clock::from(MockClock::at(now))),
+        );
+
+        for signer in [&hmac, &ed25519] {
+            signed(
+                &cli,
+                signer,
+                "/a?b=1",
+                "hello",
+                now - Duration::from_secs(30),
+            )
+            .send()
+            .await
+            .assert_text(format!("{}: hello", signer.key_id()))
+            .await;
+        }
+
+        // expired
+        signed(&cli, &hmac, "/a", "hello", now + Duration::from_secs(31))
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        // another path
+        let mut req = cli.post("/b").body("hello");
+        for (name, value) in hmac.sign(&Method::POST, "/a", b"hello", now) {
+            req = req.header(name, value);
+        }
+        req.send().await.assert_status(StatusCode::UNAUTHORIZED);
+        // another body
+        let mut req = cli.post("/a").body("hello!");
+        for (name, value) in ed25519.sign(&Method::POST, "/a", b"hello", now) {
+            req = req.header(name, value);
+        }
+        req.send().await.assert_status(StatusCode::UNAUTHORIZED);
+        // unknown key
+        signed(
+            &cli,
+            &RequestSigner::hmac("other", "secret"),
+            "/a",
+            "hello",
+            now,
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+        // timestamp out of range
+        let mut req = cli.post("/a").body("hello");
+        for (name, value) in hmac.sign(&Method::POST, "/a", b"hello", now) {
+            let value = match name.as_str() {
+                TIMESTAMP => u64::MAX.to_string(),
+                _ => value,
+            };
+            req = req.header(name, value);
+        }
+        req.send().await.assert_status(StatusCode::BAD_REQUEST);
+        // missing signature
+        cli.post("/a")
+            .body("hello")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn sign_request() {
+        let signer = RequestSigner::hmac("billing", "secret");
+        let mut req = http::Request::post("http://orders/a?b=1")
+            .body(b"hello".to_vec())
+            .unwrap();
+        signer.sign_request(&mut req);
+
+        let timestamp = req.headers()[TIMESTAMP].to_str().unwrap();
+        let message = message(&Method::POST, "/a?b=1", timestamp, b"hello");
+        let signature = STANDARD
+            .decode(req.headers()[SIGNATURE].to_str().unwrap())
+            .unwrap();
+        assert_eq!(req.headers()[KEY_ID], "billing");
+        assert!(hmac::verify(
+            &hmac::Key::new(hmac::HMAC_SHA256, b"secret"),
+            &message,
+            &signature
+        )
+        .is_ok());
+    }
+}
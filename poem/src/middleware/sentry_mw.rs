@@ -0,0 +1,368 @@
+use std::{any::Any, panic::AssertUnwindSafe, sync::Arc};
+
+use futures_util::FutureExt;
+use sentry_core::{
+    protocol::{Event, Level, Request as SentryRequest, User},
+    Hub, SentryFutureExt,
+};
+
+use crate::{route::PathPattern, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+
+/// Middleware for reporting errors to [Sentry](https://sentry.io).
+///
+/// Each request runs on its own [`Hub`] bound to the inner endpoint's future,
+/// so anything recorded during the request (breadcrumbs from
+/// `sentry-tracing`, a user set with [`sentry_core::configure_scope`] by an
+/// authentication middleware, ...) is attached to the events captured for
+/// that request.
+///
+/// Panics and responses with a `5xx` status code are captured as events,
+/// enriched with the request method, URL, headers and the matched path
+/// pattern. If a [`sentry_core::User`] is present in the request extensions,
+/// it is attached as the user of the event.
+///
+/// Panics are resumed after they have been captured, so use
+/// [`CatchPanic`](crate::middleware::CatchPanic) outside of this middleware
+/// to convert them into responses.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     middleware::{CatchPanic, Sentry},
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let app = Route::new()
+///     .at("/", index)
+///     .with(Sentry::new())
+///     .with(CatchPanic::new());
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "sentry")))]
+pub struct Sentry {
+    hub: Option<Arc<Hub>>,
+    capture_server_errors: bool,
+    send_headers: bool,
+}
+
+impl Sentry {
+    /// Create `Sentry` middleware.
+    pub fn new() -> Self {
+        Self {
+            hub: None,
+            capture_server_errors: true,
+            send_headers: true,
+        }
+    }
+
+    /// Use the specified hub as the parent hub of each request instead of
+    /// the current hub.
+    #[must_use]
+    pub fn hub(self, hub: Arc<Hub>) -> Self {
+        Self {
+            hub: Some(hub),
+            ..self
+        }
+    }
+
+    /// Specify whether responses with a `5xx` status code should be captured.
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn capture_server_errors(self, enable: bool) -> Self {
+        Self {
+            capture_server_errors: enable,
+            ..self
+        }
+    }
+
+    /// Specify whether the request headers should be attached to the events.
+    /// Defaults to `true`.
+    ///
+    /// Headers marked as sensitive, such as those affected by the
+    /// [`SensitiveHeader`](crate::middleware::SensitiveHeader) middleware, are
+    /// never sent.
+    #[must_use]
+    pub fn send_headers(self, enable: bool) -> Self {
+        Self {
+            send_headers: enable,
+            ..self
+        }
+    }
+}
+
+impl Default for Sentry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Sentry {
+    type Output = SentryEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        SentryEndpoint {
+            inner: ep,
+            hub: self.hub.clone(),
+            capture_server_errors: self.capture_server_errors,
+            send_headers: self.send_headers,
+        }
+    }
+}
+
+/// Endpoint for `Sentry` middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "sentry")))]
+pub struct SentryEndpoint<E> {
+    inner: E,
+    hub: Option<Arc<Hub>>,
+    capture_server_errors: bool,
+    send_headers: bool,
+}
+
+impl<E> SentryEndpoint<E> {
+    fn sentry_request(&self, req: &Request) -> SentryRequest {
+        let uri = req.original_uri();
+        let url = match uri.host() {
+            Some(_) => uri.to_string(),
+            None => format!(
+                "{}://{}{}",
+                req.scheme(),
+                req.headers()
+                    .get(http::header::HOST)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or("localhost"),
+                uri.path_and_query().map(|p| p.as_str()).unwrap_or("/"),
+            ),
+        };
+
+        SentryRequest {
+            url: url.parse().ok(),
+            method: Some(req.method().to_string()),
+            query_string: uri.query().map(ToString::to_string),
+            headers: if self.send_headers {
+                req.headers()
+                    .iter()
+                    .filter(|(_, value)| !value.is_sensitive())
+                    .filter_map(|(name, value)| {
+                        Some((name.to_string(), value.to_str().ok()?.to_string()))
+                    })
+                    .collect()
+            } else {
+                Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for SentryEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let parent_hub = self.hub.clone().unwrap_or_else(Hub::current);
+        let hub = Arc::new(Hub::new_from_top(parent_hub));
+
+        let sentry_req = self.sentry_request(&req);
+        let method = req.method().clone();
+        let user = req.extensions().get::<User>().cloned();
+
+        hub.configure_scope(|scope| {
+            if let Some(user) = user {
+                scope.set_user(Some(user));
+            }
+            scope.add_event_processor(move |mut event| {
+                if event.request.is_none() {
+                    event.request = Some(sentry_req.clone());
+                }
+                Some(event)
+            });
+        });
+
+        let res = AssertUnwindSafe(self.inner.call(req).bind_hub(hub.clone()))
+            .catch_unwind()
+            .await
+            .map(|res| res.map(IntoResponse::into_response));
+
+        // The path pattern is only known once the router has matched the
+        // request, and is attached to the response or the error.
+        let path_pattern = match &res {
+            Ok(Ok(resp)) => resp.data::<PathPattern>(),
+            Ok(Err(err)) => err.data::<PathPattern>(),
+            Err(_) => None,
+        };
+        if let Some(path_pattern) = path_pattern {
+            let transaction = format!("{} {}", method, path_pattern.0);
+            hub.configure_scope(|scope| scope.set_transaction(Some(&transaction)));
+        }
+
+        match res {
+            Ok(Ok(resp)) => {
+                if self.capture_server_errors && resp.status().is_server_error() {
+                    hub.capture_event(Event {
+                        message: Some(format!("HTTP {}", resp.status())),
+                        level: Level::Error,
+                        ..Default::default()
+                    });
+                }
+                Ok(resp)
+            }
+            Ok(Err(err)) => {
+                if self.capture_server_errors && err.status().is_server_error() {
+                    hub.capture_event(Event {
+                        message: Some(err.to_string()),
+                        level: Level::Error,
+                        ..Default::default()
+                    });
+                }
+                Err(err)
+            }
+            Err(panic) => {
+                hub.capture_event(Event {
+                    message: Some(panic_message(&*panic)),
+                    level: Level::Fatal,
+                    ..Default::default()
+                });
+                std::panic::resume_unwind(panic)
+            }
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use sentry_core::{test::TestTransport, ClientOptions};
+
+    use super::*;
+    use crate::{handler, middleware::CatchPanic, test::TestClient, EndpointExt, Error, Route};
+
+    fn test_hub() -> (Arc<Hub>, Arc<TestTransport>) {
+        let transport = TestTransport::new();
+        let options = ClientOptions {
+            dsn: Some("https://public@sentry.invalid/1".parse().unwrap()),
+            transport: Some(Arc::new(transport.clone())),
+            ..Default::default()
+        };
+        let hub = Arc::new(Hub::new(Some(Arc::new(options.into())), Default::default()));
+        (hub, transport)
+    }
+
+    #[tokio::test]
+    async fn captures_server_errors() {
+        #[handler(internal)]
+        fn ok() -> &'static str {
+            "ok"
+        }
+
+        #[handler(internal)]
+        fn fail() -> Result<()> {
+            Err(Error::from_string("boom", StatusCode::BAD_GATEWAY))
+        }
+
+        #[handler(internal)]
+        fn not_found() -> StatusCode {
+            StatusCode::NOT_FOUND
+        }
+
+        let (hub, transport) = test_hub();
+        let app = Route::new()
+            .at("/ok", ok)
+            .at("/fail", fail)
+            .at("/not_found", not_found)
+            .with(Sentry::new().hub(hub));
+        let cli = TestClient::new(app);
+
+        cli.get("/ok").send().await.assert_status_is_ok();
+        cli.get("/not_found")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+        assert!(transport.fetch_and_clear_events().is_empty());
+
+        cli.get("/fail")
+            .header("x-custom", "value")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+        let events = transport.fetch_and_clear_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message.as_deref(), Some("boom"));
+        assert_eq!(events[0].level, Level::Error);
+        assert_eq!(events[0].transaction.as_deref(), Some("GET /fail"));
+        let request = events[0].request.as_ref().unwrap();
+        assert_eq!(request.method.as_deref(), Some("GET"));
+        assert_eq!(request.headers.get("x-custom").unwrap(), "value");
+    }
+
+    #[tokio::test]
+    async fn captures_panics() {
+        #[handler(internal)]
+        fn index() {
+            panic!("oops");
+        }
+
+        let (hub, transport) = test_hub();
+        let app = Route::new()
+            .at("/", index)
+            .with(Sentry::new().hub(hub))
+            .with(CatchPanic::new());
+        let cli = TestClient::new(app);
+
+        cli.get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let events = transport.fetch_and_clear_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message.as_deref(), Some("oops"));
+        assert_eq!(events[0].level, Level::Fatal);
+    }
+
+    #[tokio::test]
+    async fn scope_is_per_request() {
+        #[handler(internal)]
+        fn index() -> StatusCode {
+            sentry_core::configure_scope(|scope| {
+                scope.set_user(Some(User {
+                    id: Some("42".to_string()),
+                    ..Default::default()
+                }))
+            });
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+
+        let (hub, transport) = test_hub();
+        let app = Route::new()
+            .at("/", index)
+            .with(Sentry::new().hub(hub.clone()));
+        let cli = TestClient::new(app);
+
+        cli.get("/").send().await;
+        let events = transport.fetch_and_clear_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].user.as_ref().and_then(|user| user.id.as_deref()),
+            Some("42")
+        );
+        hub.capture_message("outside", Level::Error);
+        let events = transport.fetch_and_clear_events();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].user.is_none());
+    }
+}
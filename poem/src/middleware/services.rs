@@ -0,0 +1,228 @@
+use std::{any::TypeId, sync::Arc};
+
+use crate::{
+    web::{
+        inject::{Injector, Registration, Registrations},
+        Resolver,
+    },
+    Endpoint, Middleware, Request, Result,
+};
+
+/// Middleware for registering the services which are extracted with
+/// [`Inject`](crate::web::Inject) in the handlers, instead of adding each
+/// of them to the requests with [`Data`](crate::web::Data).
+///
+/// A service is either a singleton, constructed once and shared by all the
+/// requests, or a per-request service, constructed at most once for each
+/// request which injects it. The factories resolve the other services they
+/// depend on with [`Resolver::get`].
+///
+/// Registering a service of the same type twice replaces the previous
+/// registration, and the services of a nested `Services` middleware take
+/// precedence over the outer ones.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use poem::{handler, middleware::Services, web::Inject, EndpointExt, Route};
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     hits: AtomicUsize,
+/// }
+///
+/// #[handler]
+/// fn index(counter: Inject<Counter>) -> String {
+///     counter.hits.fetch_add(1, Ordering::Relaxed).to_string()
+/// }
+///
+/// let app = Route::new()
+///     .at("/", index)
+///     .with(Services::new().singleton(Counter::default()));
+/// ```
+#[derive(Default, Clone)]
+pub struct Services {
+    registrations: Registrations,
+}
+
+impl Services {
+    /// Create a `Services` middleware without any service.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the singleton `service`.
+    #[must_use]
+    pub fn singleton<T: Send + Sync + 'static>(self, service: T) -> Self {
+        self.register::<T>(Registration::instance(service))
+    }
+
+    /// Registers a singleton constructed by `factory` the first time it is
+    /// injected.
+    #[must_use]
+    pub fn singleton_with<T, F>(self, factory: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Resolver<'_>) -> Result<T> + Send + Sync + 'static,
+    {
+        self.register::<T>(Registration::singleton(factory))
+    }
+
+    /// Registers a per-request service constructed by `factory`.
+    #[must_use]
+    pub fn per_request<T, F>(self, factory: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Resolver<'_>) -> Result<T> + Send + Sync + 'static,
+    {
+        self.register::<T>(Registration::per_request(factory))
+    }
+
+    fn register<T: 'static>(mut self, registration: Registration) -> Self {
+        self.registrations.insert(TypeId::of::<T>(), registration);
+        self
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Services {
+    type Output = ServicesEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ServicesEndpoint {
+            inner: ep,
+            registrations: Arc::new(self.registrations.clone()),
+        }
+    }
+}
+
+/// Endpoint for Services middleware.
+pub struct ServicesEndpoint<E> {
+    inner: E,
+    registrations: Arc<Registrations>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ServicesEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let parent = req.extensions_mut().remove::<Arc<Injector>>();
+        let injector = Injector::new(self.registrations.clone(), parent);
+        req.extensions_mut().insert(Arc::new(injector));
+        self.inner.call(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, web::Inject, EndpointExt, Route};
+
+    #[derive(Default)]
+    struct Counter {
+        count: AtomicUsize,
+    }
+
+    struct RequestId {
+        id: usize,
+    }
+
+    struct Greeting {
+        text: String,
+    }
+
+    #[handler(internal)]
+    fn index(a: Inject<RequestId>, b: Inject<RequestId>, greeting: Inject<Greeting>) -> String {
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        format!("{}:{}", greeting.text, a.id)
+    }
+
+    fn services() -> Services {
+        Services::new()
+            .singleton(Counter::default())
+            .per_request(|resolver| {
+                let counter = resolver.get::<Counter>()?;
+                Ok(RequestId {
+                    id: counter.count.fetch_add(1, Ordering::SeqCst),
+                })
+            })
+            .singleton_with(|resolver| {
+                let name = resolver.request().header("x-name").unwrap_or("world");
+                Ok(Greeting {
+                    text: format!("hello {name}"),
+                })
+            })
+    }
+
+    #[tokio::test]
+    async fn inject() {
+        let cli = TestClient::new(Route::new().at("/", index).with(services()));
+        cli.get("/")
+            .header("x-name", "poem")
+            .send()
+            .await
+            .assert_text("hello poem:0")
+            .await;
+        cli.get("/").send().await.assert_text("hello poem:1").await;
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        let app = Route::new()
+            .at(
+                "/",
+                index.with(Services::new().singleton(Greeting {
+                    text: "hi".to_string(),
+                })),
+            )
+            .with(services());
+        TestClient::new(app)
+            .get("/")
+            .send()
+            .await
+            .assert_text("hi:0")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        struct A;
+        struct B;
+
+        #[handler(internal)]
+        fn cycle(_a: Inject<A>) {}
+
+        #[handler(internal)]
+        fn missing(_b: Inject<Counter>) {}
+
+        let services = Services::new()
+            .per_request(|resolver| resolver.get::<B>().map(|_| A))
+            .per_request(|resolver| resolver.get::<A>().map(|_| B));
+        let cli = TestClient::new(
+            Route::new()
+                .at("/cycle", cycle)
+                .at("/missing", missing)
+                .with(services),
+        );
+        cli.get("/cycle")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        cli.get("/missing")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        TestClient::new(missing)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::StreamExt;
+use http::{
+    header,
+    uri::{Authority, Scheme},
+    Uri,
+};
+use hyper::{body::HttpBody, client::HttpConnector};
+use tokio::sync::Semaphore;
+
+use crate::{
+    endpoint::proxy::remove_hop_by_hop_headers, error::ReadBodyError, Body, Endpoint, IntoResponse,
+    Middleware, Request, Response, Result,
+};
+
+type FilterFn = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+/// Middleware for mirroring a sample of the requests to a second upstream,
+/// for instance to test a new version of a service with the production
+/// traffic.
+///
+/// The mirrored requests are sent in the background and their responses are
+/// discarded, so they never affect the responses to the clients. A request is
+/// only mirrored if its body is smaller than
+/// [`max_body_size`](Shadow::max_body_size), since the body is buffered to be
+/// sent twice, and the requests are no longer mirrored while
+/// [`max_in_flight`](Shadow::max_in_flight) of them are in progress.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::Shadow, EndpointExt, Route};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// // mirror 10% of the requests to the new version
+/// let app = Route::new()
+///     .at("/", index)
+///     .with(Shadow::new("http://10.0.0.2:8080").sample_rate(0.1));
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy")))]
+pub struct Shadow {
+    client: hyper::Client<HttpConnector>,
+    authority: Authority,
+    base_path: String,
+    sample_rate: f64,
+    max_body_size: usize,
+    timeout: Duration,
+    max_in_flight: usize,
+    filter: Option<FilterFn>,
+}
+
+impl Shadow {
+    /// Create a `Shadow` middleware that mirrors all the requests to the
+    /// specified upstream uri.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `upstream` is not a valid `http` uri.
+    pub fn new(upstream: impl AsRef<str>) -> Self {
+        let uri: Uri = upstream.as_ref().parse().expect("invalid upstream uri");
+        assert_eq!(
+            uri.scheme(),
+            Some(&Scheme::HTTP),
+            "the scheme of the upstream uri must be `http`"
+        );
+
+        Self {
+            client: hyper::Client::new(),
+            authority: uri.authority().cloned().expect("invalid upstream uri"),
+            base_path: uri.path().trim_end_matches('/').to_string(),
+            sample_rate: 1.0,
+            max_body_size: 64 * 1024,
+            timeout: Duration::from_secs(10),
+            max_in_flight: 100,
+            filter: None,
+        }
+    }
+
+    /// Sets the fraction of the requests which are mirrored, between `0.0`
+    /// and `1.0`.
+    ///
+    /// Default is `1.0`.
+    #[must_use]
+    pub fn sample_rate(self, sample_rate: f64) -> Self {
+        Self {
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of the bodies of the mirrored requests.
+    ///
+    /// Default is `64 KiB`.
+    #[must_use]
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Sets the time after which a mirrored request is cancelled.
+    ///
+    /// Default is `10s`.
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Sets the maximum number of mirrored requests in progress.
+    ///
+    /// Default is `100`.
+    #[must_use]
+    pub fn max_in_flight(self, max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight,
+            ..self
+        }
+    }
+
+    /// Uses a closure to determine if a request can be mirrored, for
+    /// instance to exclude the requests with side effects.
+    #[must_use]
+    pub fn filter(self, predicate: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            filter: Some(Arc::new(predicate)),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Shadow {
+    type Output = ShadowEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ShadowEndpoint {
+            inner: ep,
+            client: self.client.clone(),
+            authority: self.authority.clone(),
+            base_path: self.base_path.clone(),
+            sample_rate: self.sample_rate,
+            max_body_size: self.max_body_size,
+            timeout: self.timeout,
+            in_flight: Arc::new(Semaphore::new(self.max_in_flight)),
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+/// Endpoint for Shadow middleware.
+pub struct ShadowEndpoint<E> {
+    inner: E,
+    client: hyper::Client<HttpConnector>,
+    authority: Authority,
+    base_path: String,
+    sample_rate: f64,
+    max_body_size: usize,
+    timeout: Duration,
+    in_flight: Arc<Semaphore>,
+    filter: Option<FilterFn>,
+}
+
+impl<E> ShadowEndpoint<E> {
+    fn sampled(&self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        // a new random key for each hasher
+        let random = RandomState::new().build_hasher().finish();
+        (random as f64 / u64::MAX as f64) < self.sample_rate
+    }
+
+    /// Reads the body of the request unless it is larger than
+    /// `max_body_size`, in which case the part already read is put back.
+    async fn read_body(&self, req: &mut Request) -> Result<Option<Bytes>> {
+        let too_large = req
+            .header(header::CONTENT_LENGTH)
+            .and_then(|len| len.parse::<usize>().ok())
+            .map_or(false, |len| len > self.max_body_size);
+        if too_large {
+            return Ok(None);
+        }
+
+        let mut stream = req.take_body().into_bytes_stream().boxed();
+        let mut data = BytesMut::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk.map_err(ReadBodyError::Io)?);
+            if data.len() > self.max_body_size {
+                let read = futures_util::stream::once(async move { Ok(data.freeze()) });
+                req.set_body(Body::from_bytes_stream(read.chain(stream)));
+                return Ok(None);
+            }
+        }
+        let data = data.freeze();
+        req.set_body(data.clone());
+        Ok(Some(data))
+    }
+
+    async fn mirror(&self, req: &mut Request) -> Result<()> {
+        let permit = match self.in_flight.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                tracing::debug!("too many mirrored requests in progress");
+                return Ok(());
+            }
+        };
+
+        let body = match self.read_body(req).await? {
+            Some(body) => body,
+            None => {
+                tracing::debug!("the body of the request is too large to be mirrored");
+                return Ok(());
+            }
+        };
+
+        let mut path_and_query = format!("{}{}", self.base_path, req.uri().path());
+        if let Some(query) = req.uri().query() {
+            path_and_query.push('?');
+            path_and_query.push_str(query);
+        }
+        let uri = match Uri::builder()
+            .scheme(Scheme::HTTP)
+            .authority(self.authority.clone())
+            .path_and_query(path_and_query)
+            .build()
+        {
+            Ok(uri) => uri,
+            Err(err) => {
+                tracing::debug!(error = %err, "invalid uri for the mirrored request");
+                return Ok(());
+            }
+        };
+        let mut headers = req.headers().clone();
+        remove_hop_by_hop_headers(&mut headers);
+        headers.remove(header::HOST);
+
+        let mut shadow_req = hyper::Request::new(hyper::Body::from(body));
+        *shadow_req.method_mut() = req.method().clone();
+        *shadow_req.uri_mut() = uri;
+        *shadow_req.headers_mut() = headers;
+
+        let client = self.client.clone();
+        let timeout = self.timeout;
+        tokio::spawn(async move {
+            let _permit = permit;
+            let res = tokio::time::timeout(timeout, async move {
+                let mut resp = client.request(shadow_req).await?;
+                while let Some(data) = resp.body_mut().data().await {
+                    data?;
+                }
+                Ok::<_, hyper::Error>(resp.status())
+            })
+            .await;
+            match res {
+                Ok(Ok(status)) => tracing::debug!(status = %status, "mirrored request"),
+                Ok(Err(err)) => tracing::debug!(error = %err, "mirrored request failed"),
+                Err(_) => tracing::debug!("mirrored request timed out"),
+            }
+        });
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for ShadowEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if self.filter.as_ref().map(|f| f(&req)).unwrap_or(true) && self.sampled() {
+            self.mirror(&mut req).await?;
+        }
+        self.inner.call(req).await.map(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use http::Method;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        endpoint::make,
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        test::TestClient,
+        EndpointExt, Server,
+    };
+
+    #[handler(internal)]
+    fn echo(body: String) -> String {
+        body
+    }
+
+    async fn serve_shadow() -> (SocketAddr, mpsc::UnboundedReceiver<String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let ep = make(move |mut req: Request| {
+            let tx = tx.clone();
+            async move {
+                let body = req.take_body().into_string().await.unwrap();
+                tx.send(format!("{} {} {}", req.method(), req.uri(), body))
+                    .unwrap();
+                "shadow"
+            }
+        });
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(ep));
+        (addr, rx)
+    }
+
+    async fn recv(rx: &mut mpsc::UnboundedReceiver<String>) -> Option<String> {
+        tokio::time::timeout(Duration::from_millis(500), rx.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    #[tokio::test]
+    async fn mirror() {
+        let (addr, mut rx) = serve_shadow().await;
+        let cli = TestClient::new(
+            echo.with(
+                Shadow::new(format!("http://{addr}/v2"))
+                    .max_body_size(8)
+                    .filter(|req| req.method() != Method::DELETE),
+            ),
+        );
+
+        cli.post("/a?x=1")
+            .body("hello")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+        assert_eq!(recv(&mut rx).await.as_deref(), Some("POST /v2/a?x=1 hello"));
+
+        // too large
+        cli.post("/b")
+            .body("hello world")
+            .send()
+            .await
+            .assert_text("hello world")
+            .await;
+        // filtered out
+        cli.delete("/c").send().await.assert_status_is_ok();
+        assert_eq!(recv(&mut rx).await, None);
+    }
+
+    #[tokio::test]
+    async fn sample_rate() {
+        let (addr, mut rx) = serve_shadow().await;
+        let cli =
+            TestClient::new(echo.with(Shadow::new(format!("http://{addr}")).sample_rate(0.0)));
+        for _ in 0..10 {
+            cli.get("/").send().await.assert_status_is_ok();
+        }
+        assert_eq!(recv(&mut rx).await, None);
+    }
+
+    #[tokio::test]
+    async fn upstream_down() {
+        let cli = TestClient::new(echo.with(Shadow::new("http://127.0.0.1:1")));
+        cli.post("/")
+            .body("hello")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+    }
+}
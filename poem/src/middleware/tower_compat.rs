@@ -1,13 +1,19 @@
 use std::{
+    error::Error as StdError,
     sync::Arc,
     task::{Context, Poll},
 };
 
+use bytes::Bytes;
 use futures_util::{future::BoxFuture, FutureExt};
 use http::StatusCode;
+use hyper::body::HttpBody;
 use tower::{buffer::Buffer, BoxError, Layer, Service, ServiceExt};
 
-use crate::{Endpoint, Error, IntoResponse, Middleware, Request, Result};
+use crate::{
+    endpoint::{tower_compat::to_poem_response, EndpointService},
+    Endpoint, Error, IntoResponse, Middleware, Request, Response, Result,
+};
 
 #[doc(hidden)]
 #[derive(Debug, thiserror::Error)]
@@ -31,6 +37,37 @@ pub trait TowerLayerCompatExt {
     {
         TowerCompatMiddleware(self)
     }
+
+    /// Converts a tower layer wrapping the services of `http::Request`s, such
+    /// as the middleware of [`tower-http`](https://crates.io/crates/tower-http),
+    /// to a poem middleware.
+    ///
+    /// The state of the requests, such as the remote address and the path
+    /// parameters, is kept for the inner endpoint.
+    ///
+    /// ```ignore
+    /// use poem::{handler, middleware::TowerLayerCompatExt, EndpointExt};
+    /// use tower_http::set_header::SetResponseHeaderLayer;
+    ///
+    /// #[handler]
+    /// fn index() -> &'static str {
+    ///     "hello"
+    /// }
+    ///
+    /// let app = index.with(
+    ///     SetResponseHeaderLayer::overriding(
+    ///         http::header::SERVER,
+    ///         http::HeaderValue::from_static("poem"),
+    ///     )
+    ///     .http_compat(),
+    /// );
+    /// ```
+    fn http_compat(self) -> TowerHttpCompatMiddleware<Self>
+    where
+        Self: Sized,
+    {
+        TowerHttpCompatMiddleware(self)
+    }
 }
 
 impl<L> TowerLayerCompatExt for L {}
@@ -99,11 +136,66 @@ where
     }
 }
 
+/// A tower layer adapter for the layers wrapping the services of
+/// `http::Request`s.
+#[cfg_attr(docsrs, doc(cfg(feature = "tower-compat")))]
+pub struct TowerHttpCompatMiddleware<L>(L);
+
+impl<E, L, ResBody> Middleware<E> for TowerHttpCompatMiddleware<L>
+where
+    E: Endpoint + 'static,
+    L: Layer<EndpointService<E>>,
+    L::Service:
+        Service<http::Request<hyper::Body>, Response = http::Response<ResBody>> + Send + 'static,
+    <L::Service as Service<http::Request<hyper::Body>>>::Future: Send,
+    <L::Service as Service<http::Request<hyper::Body>>>::Error: Into<BoxError> + Send + Sync,
+    ResBody: HttpBody + Send + 'static,
+    ResBody::Data: Into<Bytes> + Send + 'static,
+    ResBody::Error: StdError + Send + Sync + 'static,
+{
+    type Output = TowerHttpCompatEndpoint<L::Service>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        let new_svc = self.0.layer(EndpointService::new(ep));
+        TowerHttpCompatEndpoint(Buffer::new(new_svc, 32))
+    }
+}
+
+/// Endpoint for TowerHttpCompatMiddleware.
+pub struct TowerHttpCompatEndpoint<Svc: Service<http::Request<hyper::Body>>>(
+    Buffer<Svc, http::Request<hyper::Body>>,
+);
+
+#[async_trait::async_trait]
+impl<Svc, ResBody> Endpoint for TowerHttpCompatEndpoint<Svc>
+where
+    Svc: Service<http::Request<hyper::Body>, Response = http::Response<ResBody>> + Send + 'static,
+    Svc::Future: Send,
+    Svc::Error: Into<BoxError> + Send + Sync,
+    ResBody: HttpBody + Send + 'static,
+    ResBody::Data: Into<Bytes> + Send + 'static,
+    ResBody::Error: StdError + Send + Sync + 'static,
+{
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let mut svc = self.0.clone();
+        svc.ready().await.map_err(boxed_err_to_poem_err)?;
+
+        let state = std::mem::take(req.state_mut());
+        let mut http_req: http::Request<hyper::Body> = req.into();
+        http_req.extensions_mut().insert(state);
+        let resp = svc.call(http_req).await.map_err(boxed_err_to_poem_err)?;
+        Ok(to_poem_response(resp))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use http::HeaderValue;
 
     use super::*;
-    use crate::{endpoint::make_sync, test::TestClient, EndpointExt};
+    use crate::{endpoint::make_sync, handler, test::TestClient, web::Path, EndpointExt, Route};
 
     #[tokio::test]
     async fn test_tower_layer() {
@@ -142,4 +234,29 @@ mod tests {
         let cli = TestClient::new(ep);
         cli.get("/").send().await.assert_status_is_ok();
     }
+
+    #[tokio::test]
+    async fn test_tower_http_layer() {
+        #[handler(internal)]
+        fn index(Path(name): Path<String>, req: &Request) -> String {
+            format!("{} {}", name, req.header("x-request").unwrap_or_default())
+        }
+
+        let layer = tower::ServiceBuilder::new()
+            .map_request(|mut req: http::Request<hyper::Body>| {
+                req.headers_mut()
+                    .insert("x-request", HeaderValue::from_static("1"));
+                req
+            })
+            .map_response(|mut resp: http::Response<hyper::Body>| {
+                resp.headers_mut()
+                    .insert("x-response", HeaderValue::from_static("2"));
+                resp
+            });
+        let app = Route::new().at("/:name", index.with(layer.http_compat()));
+        let resp = TestClient::new(app).get("/poem").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_header("x-response", "2");
+        resp.assert_text("poem 1").await;
+    }
 }
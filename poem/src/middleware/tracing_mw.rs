@@ -3,11 +3,14 @@ use std::time::Instant;
 use tracing::{Instrument, Level};
 
 use crate::{
-    route::PathPattern, web::RealIp, Endpoint, FromRequest, IntoResponse, Middleware, Request,
-    Response, Result,
+    redact::Redactor, route::PathPattern, web::RealIp, Endpoint, FromRequest, IntoResponse,
+    Middleware, Request, Response, Result,
 };
 
 /// Middleware for [`tracing`](https://crates.io/crates/tracing).
+///
+/// The query parameters of the URI are redacted with the [`Redactor`] added
+/// to the endpoint with [`EndpointExt::data`](crate::EndpointExt::data).
 #[derive(Default)]
 pub struct Tracing;
 
@@ -35,6 +38,10 @@ impl<E: Endpoint> Endpoint for TracingEndpoint<E> {
             .and_then(|real_ip| real_ip.0)
             .map(|addr| addr.to_string())
             .unwrap_or_else(|| req.remote_addr().to_string());
+        let uri = match req.data::<Redactor>() {
+            Some(redactor) => redactor.uri(req.original_uri()),
+            None => req.original_uri().to_string(),
+        };
 
         let span = tracing::span!(
             target: module_path!(),
@@ -43,7 +50,7 @@ impl<E: Endpoint> Endpoint for TracingEndpoint<E> {
             remote_addr = %remote_addr,
             version = ?req.version(),
             method = %req.method(),
-            uri = %req.original_uri(),
+            uri = %uri,
         );
 
         if let Some(path_pattern) = req.data::<PathPattern>() {
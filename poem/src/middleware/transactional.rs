@@ -0,0 +1,336 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::{
+    error::TransactionError, web::tx::TransactionSlot, Endpoint, IntoResponse, Middleware, Request,
+    Response, Result,
+};
+
+/// Represents a source of the transactions for the [`Transactional`]
+/// middleware, such as a connection pool.
+///
+/// It is implemented for [`sqlx::Pool`] with the `sqlx` feature.
+///
+/// # Example
+///
+/// ```
+/// use poem::{middleware::TransactionManager, Result};
+///
+/// struct Batch(Vec<String>);
+///
+/// struct BatchManager;
+///
+/// #[poem::async_trait]
+/// impl TransactionManager for BatchManager {
+///     type Transaction = Batch;
+///
+///     async fn begin(&self) -> Result<Batch> {
+///         Ok(Batch(Vec::new()))
+///     }
+///
+///     async fn commit(&self, batch: Batch) -> Result<()> {
+///         println!("applying {} operations", batch.0.len());
+///         Ok(())
+///     }
+///
+///     async fn rollback(&self, _batch: Batch) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait TransactionManager: Send + Sync {
+    /// The type of the transactions, extracted by the handlers with
+    /// [`Tx`](crate::web::Tx).
+    type Transaction: Send + 'static;
+
+    /// Begins a transaction.
+    async fn begin(&self) -> Result<Self::Transaction>;
+
+    /// Commits the transaction.
+    async fn commit(&self, tx: Self::Transaction) -> Result<()>;
+
+    /// Rolls back the transaction.
+    async fn rollback(&self, tx: Self::Transaction) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: TransactionManager + ?Sized> TransactionManager for Arc<T> {
+    type Transaction = T::Transaction;
+
+    async fn begin(&self) -> Result<Self::Transaction> {
+        self.as_ref().begin().await
+    }
+
+    async fn commit(&self, tx: Self::Transaction) -> Result<()> {
+        self.as_ref().commit(tx).await
+    }
+
+    async fn rollback(&self, tx: Self::Transaction) -> Result<()> {
+        self.as_ref().rollback(tx).await
+    }
+}
+
+#[cfg(feature = "sqlx")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sqlx")))]
+#[async_trait::async_trait]
+impl<DB: sqlx::Database> TransactionManager for sqlx::Pool<DB> {
+    type Transaction = sqlx::Transaction<'static, DB>;
+
+    async fn begin(&self) -> Result<Self::Transaction> {
+        Ok(sqlx::Pool::begin(self)
+            .await
+            .map_err(crate::error::InternalServerError)?)
+    }
+
+    async fn commit(&self, tx: Self::Transaction) -> Result<()> {
+        Ok(tx
+            .commit()
+            .await
+            .map_err(crate::error::InternalServerError)?)
+    }
+
+    async fn rollback(&self, tx: Self::Transaction) -> Result<()> {
+        Ok(tx
+            .rollback()
+            .await
+            .map_err(crate::error::InternalServerError)?)
+    }
+}
+
+/// Middleware for running each request in a transaction, extracted by the
+/// handlers with [`Tx`](crate::web::Tx).
+///
+/// The transaction is begun before the endpoint is called, and once it has
+/// returned, it is committed if the response is successful, or rolled back
+/// if the endpoint returned an error or a response with a `5xx` status code.
+/// The error of the commit replaces the response, while the error of the
+/// rollback is logged.
+///
+/// The transaction must not be kept by the handler after it returned, such
+/// as in a spawned task, otherwise the request fails with
+/// [`TransactionError::InUse`] and the transaction is dropped.
+///
+/// # Errors
+///
+/// - [`TransactionError`]
+/// - The errors of the [`TransactionManager`].
+///
+/// # Example
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use poem::{
+///     handler,
+///     http::StatusCode,
+///     middleware::{TransactionManager, Transactional},
+///     post,
+///     test::TestClient,
+///     web::{Path, Tx},
+///     EndpointExt, Result, Route,
+/// };
+///
+/// #[derive(Clone, Default)]
+/// struct Store(Arc<Mutex<Vec<String>>>);
+///
+/// #[poem::async_trait]
+/// impl TransactionManager for Store {
+///     type Transaction = Vec<String>;
+///
+///     async fn begin(&self) -> Result<Vec<String>> {
+///         Ok(Vec::new())
+///     }
+///
+///     async fn commit(&self, items: Vec<String>) -> Result<()> {
+///         self.0.lock().unwrap().extend(items);
+///         Ok(())
+///     }
+///
+///     async fn rollback(&self, _items: Vec<String>) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// #[handler]
+/// fn add(Path(item): Path<String>, mut tx: Tx<Vec<String>>) -> StatusCode {
+///     tx.push(item.clone());
+///     match item.as_str() {
+///         "poison" => StatusCode::INTERNAL_SERVER_ERROR,
+///         _ => StatusCode::CREATED,
+///     }
+/// }
+///
+/// let store = Store::default();
+/// let app = Route::new()
+///     .at("/items/:item", post(add))
+///     .with(Transactional::new(store.clone()));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.post("/items/apple").send().await.assert_status(StatusCode::CREATED);
+/// cli.post("/items/poison")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+/// assert_eq!(*store.0.lock().unwrap(), vec!["apple".to_string()]);
+/// # });
+/// ```
+pub struct Transactional<T> {
+    manager: Arc<T>,
+}
+
+impl<T: TransactionManager> Transactional<T> {
+    /// Create a `Transactional` middleware with the specified transaction
+    /// manager.
+    pub fn new(manager: T) -> Self {
+        Self {
+            manager: Arc::new(manager),
+        }
+    }
+}
+
+impl<E: Endpoint, T: TransactionManager> Middleware<E> for Transactional<T> {
+    type Output = TransactionalEndpoint<E, T>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        TransactionalEndpoint {
+            inner: ep,
+            manager: self.manager.clone(),
+        }
+    }
+}
+
+/// Endpoint for Transactional middleware.
+pub struct TransactionalEndpoint<E, T> {
+    inner: E,
+    manager: Arc<T>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint, T: TransactionManager> Endpoint for TransactionalEndpoint<E, T> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let slot = Arc::new(Mutex::new(Some(self.manager.begin().await?)));
+        req.extensions_mut()
+            .insert(TransactionSlot::<T::Transaction>(slot.clone()));
+
+        let res = self.inner.call(req).await.map(IntoResponse::into_response);
+        let tx = match slot.try_lock() {
+            Ok(mut tx) => tx.take(),
+            Err(_) => None,
+        };
+        let tx = match tx {
+            Some(tx) => tx,
+            None => return Err(TransactionError::InUse.into()),
+        };
+
+        match res {
+            Ok(resp) if !resp.status().is_server_error() => {
+                self.manager.commit(tx).await?;
+                Ok(resp)
+            }
+            res => {
+                if let Err(err) = self.manager.rollback(tx).await {
+                    tracing::error!(error = %err, "failed to roll back the transaction");
+                }
+                res
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::{
+        error::InternalServerError,
+        handler,
+        http::StatusCode,
+        test::TestClient,
+        web::{Path, Tx},
+        EndpointExt, Route,
+    };
+
+    #[derive(Default)]
+    struct Journal(Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl TransactionManager for Journal {
+        type Transaction = Vec<String>;
+
+        async fn begin(&self) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        async fn commit(&self, tx: Vec<String>) -> Result<()> {
+            if tx.iter().any(|op| op == "conflict") {
+                return Err(InternalServerError(std::io::Error::from(
+                    std::io::ErrorKind::Other,
+                )));
+            }
+            self.0.lock().push(format!("commit {}", tx.join(",")));
+            Ok(())
+        }
+
+        async fn rollback(&self, tx: Vec<String>) -> Result<()> {
+            self.0.lock().push(format!("rollback {}", tx.join(",")));
+            Ok(())
+        }
+    }
+
+    #[handler(internal)]
+    fn index(Path(op): Path<String>, mut tx: Tx<Vec<String>>) -> Result<StatusCode> {
+        tx.push(op.clone());
+        match op.as_str() {
+            "fail" => Err(crate::error::BadRequest(std::io::Error::from(
+                std::io::ErrorKind::InvalidInput,
+            ))),
+            "crash" => Ok(StatusCode::SERVICE_UNAVAILABLE),
+            "missing" => Ok(StatusCode::NOT_FOUND),
+            _ => Ok(StatusCode::OK),
+        }
+    }
+
+    #[tokio::test]
+    async fn commit_and_rollback() {
+        let journal = Arc::new(Journal::default());
+        let cli = TestClient::new(
+            Route::new()
+                .at("/:op", index)
+                .with(Transactional::new(journal.clone())),
+        );
+
+        for (op, status) in [
+            ("insert", StatusCode::OK),
+            ("fail", StatusCode::BAD_REQUEST),
+            ("crash", StatusCode::SERVICE_UNAVAILABLE),
+            ("missing", StatusCode::NOT_FOUND),
+            ("conflict", StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            cli.get(format!("/{op}")).send().await.assert_status(status);
+        }
+        assert_eq!(
+            *journal.0.lock(),
+            vec![
+                "commit insert",
+                "rollback fail",
+                "rollback crash",
+                "commit missing",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_middleware() {
+        let cli = TestClient::new(Route::new().at("/:op", index));
+        let resp = cli.get("/insert").send().await;
+        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        resp.assert_text("the `Transactional` middleware is not applied")
+            .await;
+    }
+}
@@ -0,0 +1,252 @@
+use std::{io::Error as IoError, sync::Arc};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    http::header, BodyTransformer, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+type TransformerFactory = Arc<dyn Fn(&Response) -> Option<Box<dyn BodyTransformer>> + Send + Sync>;
+
+/// Middleware for transforming the response bodies while they are streamed.
+///
+/// Each transformer is created by a function which receives the response, and
+/// can return `None` to leave it untouched, for example when its content type
+/// does not match. The transformers are applied in the order they were added,
+/// and the `Content-Length` header is removed from the transformed responses.
+///
+/// The responses with a `Content-Encoding` header are never transformed, so
+/// this middleware should be applied before the
+/// [`Compression`](crate::middleware::Compression) middleware.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     middleware::{Replace, TransformBody},
+///     test::TestClient,
+///     web::Html,
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index() -> Html<&'static str> {
+///     Html("<html><body>hello</body></html>")
+/// }
+///
+/// let ep = index.with(TransformBody::new().transformer(|resp| {
+///     (resp.content_type() == Some("text/html; charset=utf-8"))
+///         .then(|| Replace::new("</body>", "<p>staging</p></body>"))
+/// }));
+/// let cli = TestClient::new(ep);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_text("<html><body>hello<p>staging</p></body></html>")
+///     .await;
+/// # });
+/// ```
+#[derive(Default, Clone)]
+pub struct TransformBody {
+    transformers: Vec<TransformerFactory>,
+}
+
+impl TransformBody {
+    /// Create new `TransformBody` middleware.
+    #[must_use]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a transformer created by `f` for each response.
+    #[must_use]
+    pub fn transformer<F, T>(mut self, f: F) -> Self
+    where
+        F: Fn(&Response) -> Option<T> + Send + Sync + 'static,
+        T: BodyTransformer,
+    {
+        self.transformers.push(Arc::new(move |resp| {
+            f(resp).map(|transformer| Box::new(transformer) as Box<dyn BodyTransformer>)
+        }));
+        self
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for TransformBody {
+    type Output = TransformBodyEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        TransformBodyEndpoint {
+            inner: ep,
+            transformers: self.transformers.clone(),
+        }
+    }
+}
+
+/// Endpoint for TransformBody middleware.
+pub struct TransformBodyEndpoint<E> {
+    inner: E,
+    transformers: Vec<TransformerFactory>,
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint> Endpoint for TransformBodyEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let mut resp = self.inner.call(req).await?.into_response();
+        if resp.headers().contains_key(header::CONTENT_ENCODING) {
+            return Ok(resp);
+        }
+
+        let transformers = self
+            .transformers
+            .iter()
+            .filter_map(|f| f(&resp))
+            .collect::<Vec<_>>();
+        if transformers.is_empty() {
+            return Ok(resp);
+        }
+
+        let body = transformers
+            .into_iter()
+            .fold(resp.take_body(), |body, transformer| {
+                body.transform(transformer)
+            });
+        resp.headers_mut().remove(header::CONTENT_LENGTH);
+        resp.set_body(body);
+        Ok(resp)
+    }
+}
+
+/// A [`BodyTransformer`] which replaces all the occurrences of a pattern.
+///
+/// At most the length of the pattern is held back between two chunks.
+#[derive(Debug, Clone)]
+pub struct Replace {
+    pattern: Bytes,
+    replacement: Bytes,
+    pending: BytesMut,
+}
+
+impl Replace {
+    /// Create a `Replace` transformer replacing `pattern` with `replacement`.
+    pub fn new(pattern: impl Into<Bytes>, replacement: impl Into<Bytes>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            replacement: replacement.into(),
+            pending: BytesMut::new(),
+        }
+    }
+}
+
+impl BodyTransformer for Replace {
+    fn transform(&mut self, chunk: Bytes) -> Result<Bytes, IoError> {
+        if self.pattern.is_empty() {
+            return Ok(chunk);
+        }
+
+        self.pending.extend_from_slice(&chunk);
+        let data = self.pending.split().freeze();
+        let len = self.pattern.len();
+        let mut output = BytesMut::with_capacity(data.len());
+        let mut start = 0;
+        let mut idx = 0;
+
+        while idx + len <= data.len() {
+            if data[idx..idx + len] == self.pattern[..] {
+                output.extend_from_slice(&data[start..idx]);
+                output.extend_from_slice(&self.replacement);
+                idx += len;
+                start = idx;
+            } else {
+                idx += 1;
+            }
+        }
+
+        // the tail may be the beginning of an occurrence split across chunks
+        let keep = data.len().saturating_sub(len - 1).max(start);
+        output.extend_from_slice(&data[start..keep]);
+        self.pending.extend_from_slice(&data[keep..]);
+        Ok(output.freeze())
+    }
+
+    fn finish(&mut self) -> Result<Bytes, IoError> {
+        Ok(self.pending.split().freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+    use crate::{handler, test::TestClient, web::Html, Body, EndpointExt};
+
+    fn chunked(chunks: &'static [&'static str]) -> Body {
+        Body::from_bytes_stream(stream::iter(
+            chunks
+                .iter()
+                .map(|chunk| Ok::<_, IoError>(Bytes::from_static(chunk.as_bytes()))),
+        ))
+    }
+
+    #[tokio::test]
+    async fn replace() {
+        let body = chunked(&["a</bo", "dy>b</", "body", ">c</b"])
+            .transform(Replace::new("</body>", "<hr></body>"));
+        assert_eq!(
+            body.into_string().await.unwrap(),
+            "a<hr></body>b<hr></body>c</b"
+        );
+
+        let body = chunked(&["aaa", "a"]).transform(Replace::new("aa", "b"));
+        assert_eq!(body.into_string().await.unwrap(), "bb");
+    }
+
+    #[tokio::test]
+    async fn transform_body() {
+        #[handler(internal)]
+        fn index() -> Html<&'static str> {
+            Html("<body>hello</body>")
+        }
+
+        let ep = index.with(
+            TransformBody::new()
+                .transformer(|_| Some(Replace::new("hello", "hi")))
+                .transformer(|resp| {
+                    resp.content_type()
+                        .filter(|ty| ty.starts_with("text/html"))
+                        .map(|_| Replace::new("</body>", "!</body>"))
+                })
+                .transformer(|resp| {
+                    resp.content_type()
+                        .filter(|ty| ty.starts_with("application/json"))
+                        .map(|_| Replace::new("<body>", ""))
+                }),
+        );
+        let cli = TestClient::new(ep);
+        let resp = cli.get("/").send().await;
+        resp.assert_header_is_not_exist(header::CONTENT_LENGTH);
+        resp.assert_text("<body>hi!</body>").await;
+
+        #[handler(internal)]
+        fn compressed() -> Response {
+            Response::builder()
+                .header(header::CONTENT_ENCODING, "identity")
+                .body("hello")
+        }
+
+        let ep = compressed
+            .with(TransformBody::new().transformer(|_| Some(Replace::new("hello", "hi"))));
+        TestClient::new(ep)
+            .get("/")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+    }
+}
@@ -0,0 +1,582 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::{Add, AddAssign},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use hyper::body::HttpBody;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::UsageCapError,
+    web::{Clock, CurrentUsage, Principal},
+    Body, BodyTransformer, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+/// The usage of an API by a client.
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// The number of requests.
+    pub requests: u64,
+    /// The number of bytes of the request and response bodies.
+    pub bytes: u64,
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Usage {
+            requests: self.requests + rhs.requests,
+            bytes: self.bytes + rhs.bytes,
+        }
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// The maximum usage of an API by a client in a month.
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UsageCap {
+    /// The maximum number of requests.
+    pub requests: Option<u64>,
+    /// The maximum number of bytes of the request and response bodies.
+    pub bytes: Option<u64>,
+}
+
+impl UsageCap {
+    /// Create a `UsageCap` which does not limit the usage.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum number of requests.
+    #[must_use]
+    pub fn requests(self, requests: u64) -> Self {
+        Self {
+            requests: Some(requests),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of bytes of the request and response bodies.
+    #[must_use]
+    pub fn bytes(self, bytes: u64) -> Self {
+        Self {
+            bytes: Some(bytes),
+            ..self
+        }
+    }
+
+    /// Returns `true` if `usage` reaches this cap, so that no more requests
+    /// are allowed.
+    pub fn is_reached(&self, usage: &Usage) -> bool {
+        self.requests
+            .map_or(false, |requests| usage.requests >= requests)
+            || self.bytes.map_or(false, |bytes| usage.bytes >= bytes)
+    }
+}
+
+/// Represents a back-end storage for the [`UsageMeter`] middleware.
+///
+/// The usage is stored per client and per period, which is the month of the
+/// requests formatted as `YYYY-MM`.
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+#[async_trait::async_trait]
+pub trait UsageStore: Send + Sync {
+    /// Adds `usage` to the usage of the client in the period.
+    async fn add(&self, key: &str, period: &str, usage: Usage) -> Result<()>;
+
+    /// Returns the usage of the client in the period.
+    async fn get(&self, key: &str, period: &str) -> Result<Usage>;
+}
+
+#[async_trait::async_trait]
+impl<T: UsageStore + ?Sized> UsageStore for Arc<T> {
+    async fn add(&self, key: &str, period: &str, usage: Usage) -> Result<()> {
+        self.as_ref().add(key, period, usage).await
+    }
+
+    async fn get(&self, key: &str, period: &str) -> Result<Usage> {
+        self.as_ref().get(key, period).await
+    }
+}
+
+/// A usage store using memory.
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+#[derive(Default)]
+pub struct MemoryUsageStore {
+    usages: Mutex<HashMap<(String, String), Usage>>,
+}
+
+impl MemoryUsageStore {
+    /// Create a `MemoryUsageStore`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl UsageStore for MemoryUsageStore {
+    async fn add(&self, key: &str, period: &str, usage: Usage) -> Result<()> {
+        *self
+            .usages
+            .lock()
+            .entry((key.to_string(), period.to_string()))
+            .or_default() += usage;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str, period: &str) -> Result<Usage> {
+        Ok(self
+            .usages
+            .lock()
+            .get(&(key.to_string(), period.to_string()))
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+type UsageKey = (String, String);
+
+struct Meter<T> {
+    store: T,
+    /// The usage not flushed to the store yet.
+    pending: Mutex<HashMap<UsageKey, Usage>>,
+    /// The usage in the store, including the usage being flushed.
+    totals: Mutex<HashMap<UsageKey, Usage>>,
+    started: AtomicBool,
+}
+
+impl<T: UsageStore> Meter<T> {
+    async fn current(&self, key: &UsageKey) -> Result<Usage> {
+        let total = self.totals.lock().get(key).copied();
+        let total = match total {
+            Some(total) => total,
+            None => {
+                let total = self.store.get(&key.0, &key.1).await?;
+                *self.totals.lock().entry(key.clone()).or_insert(total)
+            }
+        };
+        let pending = self.pending.lock().get(key).copied().unwrap_or_default();
+        Ok(total + pending)
+    }
+
+    fn record(&self, key: &UsageKey, usage: Usage) {
+        *self.pending.lock().entry(key.clone()).or_default() += usage;
+    }
+
+    async fn flush(&self) {
+        let pending = {
+            let mut pending = self.pending.lock();
+            let mut totals = self.totals.lock();
+            let pending = std::mem::take(&mut *pending);
+            for (key, usage) in &pending {
+                *totals.entry(key.clone()).or_default() += *usage;
+            }
+            pending
+        };
+
+        let mut flushed = HashSet::new();
+        for (key, usage) in pending {
+            if let Err(err) = self.store.add(&key.0, &key.1, usage).await {
+                tracing::warn!(
+                    key = %key.0,
+                    period = %key.1,
+                    error = %err,
+                    "failed to flush the usage"
+                );
+                if let Some(total) = self.totals.lock().get_mut(&key) {
+                    total.requests -= usage.requests;
+                    total.bytes -= usage.bytes;
+                }
+                self.record(&key, usage);
+                continue;
+            }
+            // refreshes the total, which includes the usage of the other instances
+            if let Ok(total) = self.store.get(&key.0, &key.1).await {
+                self.totals.lock().insert(key.clone(), total);
+            }
+            flushed.insert(key);
+        }
+
+        // the totals of the idle clients are reloaded from the store
+        let pending = self.pending.lock();
+        self.totals
+            .lock()
+            .retain(|key, _| flushed.contains(key) || pending.contains_key(key));
+    }
+}
+
+/// Counts the bytes of a body, and records them when it is dropped.
+struct CountBytes<T: UsageStore + 'static> {
+    meter: Arc<Meter<T>>,
+    key: UsageKey,
+    bytes: u64,
+}
+
+impl<T: UsageStore + 'static> BodyTransformer for CountBytes<T> {
+    fn transform(&mut self, chunk: Bytes) -> std::io::Result<Bytes> {
+        self.bytes += chunk.len() as u64;
+        Ok(chunk)
+    }
+}
+
+impl<T: UsageStore + 'static> Drop for CountBytes<T> {
+    fn drop(&mut self) {
+        self.meter.record(
+            &self.key,
+            Usage {
+                requests: 0,
+                bytes: self.bytes,
+            },
+        );
+    }
+}
+
+/// Returns the period of the specified time, and the duration until the
+/// next period.
+fn period_of(now: SystemTime) -> (String, Duration) {
+    let now = DateTime::<Utc>::from(now);
+    let (year, month) = match now.month() {
+        12 => (now.year() + 1, 1),
+        month => (now.year(), month + 1),
+    };
+    let reset_after = Utc
+        .with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .and_then(|next| (next - now).to_std().ok())
+        .unwrap_or_default();
+    (now.format("%Y-%m").to_string(), reset_after)
+}
+
+/// Middleware for metering the usage of an API by the authenticated clients,
+/// and enforcing a monthly cap.
+///
+/// The requests and the bytes of the request and response bodies are counted
+/// per [`Principal`] and per month, and added to the [`UsageStore`] every
+/// [`flush_interval`](UsageMeter::flush_interval), so that the store is not
+/// written on each request. The usage of a client is read from the store on
+/// its first request, and after each flush, so that it includes the usage
+/// recorded by the other instances.
+///
+/// The current usage of the client is available with the [`CurrentUsage`]
+/// extractor. The requests without a principal are not metered.
+///
+/// # Errors
+///
+/// - [`UsageCapError`] if the cap of the client is reached.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     http::StatusCode,
+///     middleware::{MemoryUsageStore, UsageCap, UsageMeter},
+///     test::TestClient,
+///     web::{CurrentUsage, Principal},
+///     EndpointExt, Request,
+/// };
+///
+/// #[handler]
+/// fn index(usage: CurrentUsage) -> String {
+///     format!("request {}", usage.usage.requests + 1)
+/// }
+///
+/// let app = index
+///     .with(UsageMeter::new(MemoryUsageStore::new()).monthly_cap(UsageCap::new().requests(1)))
+///     .before(|mut req: Request| async move {
+///         req.extensions_mut().insert(Principal("sunli".to_string()));
+///         Ok(req)
+///     });
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/").send().await.assert_text("request 1").await;
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::TOO_MANY_REQUESTS);
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+pub struct UsageMeter<T> {
+    meter: Arc<Meter<T>>,
+    flush_interval: Duration,
+    monthly_cap: Option<UsageCap>,
+}
+
+impl<T: UsageStore + 'static> UsageMeter<T> {
+    /// Create a `UsageMeter` middleware with the specified store.
+    pub fn new(store: T) -> Self {
+        Self {
+            meter: Arc::new(Meter {
+                store,
+                pending: Default::default(),
+                totals: Default::default(),
+                started: AtomicBool::new(false),
+            }),
+            flush_interval: Duration::from_secs(10),
+            monthly_cap: None,
+        }
+    }
+
+    /// Sets how often the usage is added to the store. Defaults to 10
+    /// seconds.
+    #[must_use]
+    pub fn flush_interval(self, flush_interval: Duration) -> Self {
+        Self {
+            flush_interval,
+            ..self
+        }
+    }
+
+    /// Rejects the requests of the clients whose usage reaches `cap` in the
+    /// current month.
+    ///
+    /// Since the usage of the other instances is read after each flush, the
+    /// cap may be exceeded by the requests received by the other instances
+    /// in the meantime.
+    #[must_use]
+    pub fn monthly_cap(self, cap: UsageCap) -> Self {
+        Self {
+            monthly_cap: Some(cap),
+            ..self
+        }
+    }
+}
+
+impl<E: Endpoint, T: UsageStore + 'static> Middleware<E> for UsageMeter<T> {
+    type Output = UsageMeterEndpoint<E, T>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        UsageMeterEndpoint {
+            inner: ep,
+            meter: self.meter.clone(),
+            flush_interval: self.flush_interval,
+            monthly_cap: self.monthly_cap,
+        }
+    }
+}
+
+/// Endpoint for UsageMeter middleware.
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+pub struct UsageMeterEndpoint<E, T> {
+    inner: E,
+    meter: Arc<Meter<T>>,
+    flush_interval: Duration,
+    monthly_cap: Option<UsageCap>,
+}
+
+impl<E, T: UsageStore + 'static> UsageMeterEndpoint<E, T> {
+    fn start_flushing(&self) {
+        if self.meter.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let meter = Arc::downgrade(&self.meter);
+        let flush_interval = self.flush_interval;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(flush_interval).await;
+                match meter.upgrade() {
+                    Some(meter) => meter.flush().await,
+                    None => return,
+                }
+            }
+        });
+    }
+
+    fn count(&self, body: Body, key: &UsageKey) -> Body {
+        match HttpBody::size_hint(&body.0).exact() {
+            Some(bytes) => {
+                self.meter.record(key, Usage { requests: 0, bytes });
+                body
+            }
+            None => body.transform(CountBytes {
+                meter: self.meter.clone(),
+                key: key.clone(),
+                bytes: 0,
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Endpoint, T: UsageStore + 'static> Endpoint for UsageMeterEndpoint<E, T> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let principal = match Principal::of(&req) {
+            Some(principal) => principal,
+            None => return self.inner.call(req).await.map(IntoResponse::into_response),
+        };
+        self.start_flushing();
+
+        let (period, reset_after) = period_of(Clock::of(&req).now());
+        let key = (principal.0, period);
+        let usage = self.meter.current(&key).await?;
+        if let Some(cap) = self.monthly_cap {
+            if cap.is_reached(&usage) {
+                return Err(UsageCapError {
+                    usage,
+                    cap,
+                    reset_after,
+                }
+                .into());
+            }
+        }
+
+        self.meter.record(
+            &key,
+            Usage {
+                requests: 1,
+                bytes: 0,
+            },
+        );
+        let body = self.count(req.take_body(), &key);
+        req.set_body(body);
+        req.extensions_mut().insert(CurrentUsage {
+            period: key.1.clone(),
+            usage,
+            cap: self.monthly_cap,
+        });
+
+        let mut resp = self.inner.call(req).await?.into_response();
+        let body = self.count(resp.take_body(), &key);
+        resp.set_body(body);
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient, web::MockClock, EndpointExt};
+
+    fn clock() -> MockClock {
+        MockClock::at(Utc.with_ymd_and_hms(2023, 6, 15, 0, 0, 0).unwrap().into())
+    }
+
+    fn app(meter: UsageMeter<Arc<MemoryUsageStore>>, clock: MockClock) -> impl Endpoint {
+        #[handler(internal)]
+        fn index(usage: Option<CurrentUsage>, body: String) -> String {
+            match usage {
+                Some(usage) => format!("{} {}", usage.usage.requests, body),
+                None => body,
+            }
+        }
+
+        index
+            .with(meter)
+            .data(Clock::from(clock))
+            .before(|mut req: Request| async move {
+                if let Some(user) = req.header("x-user").map(ToString::to_string) {
+                    req.extensions_mut().insert(Principal(user));
+                }
+                Ok(req)
+            })
+    }
+
+    #[test]
+    fn period() {
+        let now = Utc.with_ymd_and_hms(2023, 12, 31, 23, 0, 0).unwrap();
+        assert_eq!(
+            period_of(now.into()),
+            ("2023-12".to_string(), Duration::from_secs(60 * 60))
+        );
+    }
+
+    #[tokio::test]
+    async fn meter() {
+        let store = Arc::new(MemoryUsageStore::new());
+        let meter = UsageMeter::new(store.clone()).flush_interval(Duration::from_secs(3600));
+        let inner = meter.meter.clone();
+        let cli = TestClient::new(app(meter, clock()));
+
+        for expected in ["0 abc", "1 de"] {
+            cli.post("/")
+                .header("x-user", "a")
+                .body(&expected[2..])
+                .send()
+                .await
+                .assert_text(expected)
+                .await;
+        }
+        cli.post("/")
+            .body("xyz")
+            .send()
+            .await
+            .assert_text("xyz")
+            .await;
+
+        assert_eq!(store.get("a", "2023-06").await.unwrap(), Usage::default());
+        inner.flush().await;
+        assert_eq!(
+            store.get("a", "2023-06").await.unwrap(),
+            Usage {
+                requests: 2,
+                bytes: 3 + 5 + 2 + 4,
+            }
+        );
+        assert_eq!(inner.pending.lock().len(), 0);
+        assert_eq!(inner.totals.lock().len(), 1);
+
+        // the idle clients are reloaded from the store
+        inner.flush().await;
+        assert_eq!(inner.totals.lock().len(), 0);
+        cli.post("/")
+            .header("x-user", "a")
+            .send()
+            .await
+            .assert_text("2 ")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn monthly_cap() {
+        let clock = clock();
+        let meter = UsageMeter::new(Arc::new(MemoryUsageStore::new()))
+            .monthly_cap(UsageCap::new().requests(10).bytes(4));
+        let cli = TestClient::new(app(meter, clock.clone()));
+
+        cli.post("/")
+            .header("x-user", "a")
+            .body("ab")
+            .send()
+            .await
+            .assert_text("0 ab")
+            .await;
+        let resp = cli.post("/").header("x-user", "a").send().await;
+        resp.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        resp.assert_content_type("application/problem+json");
+        cli.post("/")
+            .header("x-user", "b")
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        // the usage is reset in the next month
+        clock.advance(Duration::from_secs(60 * 60 * 24 * 31));
+        cli.post("/")
+            .header("x-user", "a")
+            .send()
+            .await
+            .assert_text("0 ")
+            .await;
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Instant,
+};
+
+use parking_lot::Mutex;
+
+use super::{Quota, RateLimitStatus, RateLimiter};
+use crate::{web::Clock, Result};
+
+/// A rate limiter using memory, which counts the requests of a single
+/// instance.
+///
+/// The requests are counted in fixed windows of the period of the quota,
+/// starting at the first request of each window. The windows are removed
+/// once they have ended, and at most
+/// [`max_keys`](MemoryRateLimiter::max_keys) windows are kept.
+pub struct MemoryRateLimiter {
+    windows: Mutex<Windows>,
+    max_keys: usize,
+    clock: Clock,
+}
+
+#[derive(Default)]
+struct Windows {
+    windows: HashMap<String, (Instant, u32)>,
+    /// The keys ordered by the end of their window, for removing the ended
+    /// windows without scanning all of them.
+    ends: BTreeSet<(Instant, String)>,
+}
+
+impl Windows {
+    /// Removes the window which ends first, only if it has ended at `now`
+    /// when `now` is set.
+    fn remove_first(&mut self, now: Option<Instant>) -> bool {
+        let first = match self.ends.iter().next() {
+            Some((ends_at, _)) if now.map_or(false, |now| *ends_at > now) => return false,
+            Some(first) => first.clone(),
+            None => return false,
+        };
+        self.ends.remove(&first);
+        self.windows.remove(&first.1);
+        true
+    }
+}
+
+impl Default for MemoryRateLimiter {
+    fn default() -> Self {
+        Self {
+            windows: Default::default(),
+            max_keys: 100_000,
+            clock: Clock::default(),
+        }
+    }
+}
+
+impl MemoryRateLimiter {
+    /// Create a `MemoryRateLimiter`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum number of keys whose requests are counted.
+    ///
+    /// When a key is hit while the limit is reached, the window ending first
+    /// is removed, which resets the count of its key.
+    ///
+    /// Default is `100000`.
+    #[must_use]
+    pub fn max_keys(self, max_keys: usize) -> Self {
+        Self {
+            max_keys: max_keys.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the clock used for the windows.
+    ///
+    /// Default is the system clock.
+    #[must_use]
+    pub fn clock(self, clock: impl Into<Clock>) -> Self {
+        Self {
+            clock: clock.into(),
+            ..self
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimiter for MemoryRateLimiter {
+    async fn hit(&self, key: &str, quota: Quota) -> Result<RateLimitStatus> {
+        let now = self.clock.instant();
+        let mut windows = self.windows.lock();
+        while windows.remove_first(Some(now)) {}
+
+        if !windows.windows.contains_key(key) {
+            while windows.windows.len() >= self.max_keys && windows.remove_first(None) {}
+            let ends_at = now + quota.period();
+            windows.ends.insert((ends_at, key.to_string()));
+            windows.windows.insert(key.to_string(), (ends_at, 0));
+        }
+        let (ends_at, count) = windows.windows.get_mut(key).expect("the window of the key");
+        let allowed = *count < quota.limit();
+        if allowed {
+            *count += 1;
+        }
+        Ok(RateLimitStatus {
+            allowed,
+            limit: quota.limit(),
+            remaining: quota.limit() - *count,
+            reset_after: *ends_at - now,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::web::MockClock;
+
+    #[tokio::test]
+    async fn windows() {
+        let clock = MockClock::new();
+        let limiter = MemoryRateLimiter::new().clock(clock.clone());
+        let quota = Quota::per_minute(2);
+
+        let status = limiter.hit("a", quota).await.unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 1);
+        assert_eq!(status.reset_after, Duration::from_secs(60));
+
+        clock.advance(Duration::from_secs(20));
+        assert!(limiter.hit("a", quota).await.unwrap().allowed);
+        let status = limiter.hit("a", quota).await.unwrap();
+        assert!(!status.allowed);
+        assert_eq!(status.remaining, 0);
+        assert_eq!(status.reset_after, Duration::from_secs(40));
+        assert!(limiter.hit("b", quota).await.unwrap().allowed);
+
+        clock.advance(Duration::from_secs(40));
+        let status = limiter.hit("a", quota).await.unwrap();
+        assert!(status.allowed);
+        assert_eq!(status.remaining, 1);
+        assert_eq!(limiter.windows.lock().windows.len(), 2);
+
+        // the window of "b" has ended
+        clock.advance(Duration::from_secs(20));
+        assert!(limiter.hit("a", quota).await.unwrap().allowed);
+        let windows = limiter.windows.lock();
+        assert_eq!(windows.windows.len(), 1);
+        assert_eq!(windows.ends.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn max_keys() {
+        let clock = MockClock::new();
+        let limiter = MemoryRateLimiter::new().clock(clock.clone()).max_keys(2);
+        let quota = Quota::per_minute(1);
+
+        assert!(limiter.hit("a", quota).await.unwrap().allowed);
+        clock.advance(Duration::from_secs(1));
+        assert!(limiter.hit("b", quota).await.unwrap().allowed);
+        assert!(!limiter.hit("a", quota).await.unwrap().allowed);
+
+        // the window of "a" ends first, so it is removed
+        assert!(limiter.hit("c", quota).await.unwrap().allowed);
+        assert!(!limiter.hit("b", quota).await.unwrap().allowed);
+        assert!(limiter.hit("a", quota).await.unwrap().allowed);
+        assert_eq!(limiter.windows.lock().windows.len(), 2);
+    }
+}
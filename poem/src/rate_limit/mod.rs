@@ -0,0 +1,420 @@
+//! Rate limiting of the requests per client.
+//!
+//! The [`RateLimited`] extractor counts the requests of the client with the
+//! [`RateLimiter`] added to the endpoint with a [`SharedRateLimiter`], and
+//! rejects them with a [`RateLimitError`] once the [`Quota`] of the
+//! [`RateLimitPolicy`] is exceeded. The authenticated clients are identified
+//! by their [`Principal`], and the anonymous clients by their ip address,
+//! which is read from the [`ForwardedInfo`](crate::web::ForwardedInfo) when
+//! the [`ForwardedHeaders`](crate::middleware::ForwardedHeaders) middleware
+//! is applied.
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     handler,
+//!     http::StatusCode,
+//!     rate_limit::{
+//!         MemoryRateLimiter, Quota, RateLimitPolicy, RateLimitSubject, RateLimited,
+//!         SharedRateLimiter,
+//!     },
+//!     test::TestClient,
+//!     EndpointExt,
+//! };
+//!
+//! struct Api;
+//!
+//! impl RateLimitPolicy for Api {
+//!     const NAME: &'static str = "api";
+//!
+//!     fn quota(subject: &RateLimitSubject) -> Option<Quota> {
+//!         match subject {
+//!             RateLimitSubject::Principal(_) => Some(Quota::per_minute(600)),
+//!             _ => Some(Quota::per_minute(1)),
+//!         }
+//!     }
+//! }
+//!
+//! #[handler]
+//! fn index(_: RateLimited<Api>) -> &'static str {
+//!     "hello"
+//! }
+//!
+//! let app = index.data(SharedRateLimiter::new(MemoryRateLimiter::new()));
+//! let cli = TestClient::new(app);
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! cli.get("/").send().await.assert_text("hello").await;
+//!
+//! let resp = cli.get("/").send().await;
+//! resp.assert_status(StatusCode::TOO_MANY_REQUESTS);
+//! resp.assert_header("ratelimit-remaining", "0");
+//! # });
+//! ```
+
+mod memory;
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+pub use memory::MemoryRateLimiter;
+
+use crate::{
+    error::{GetDataError, RateLimitError},
+    web::{ForwardedInfo, Principal},
+    FromRequest, Request, RequestBody, Result,
+};
+
+/// The maximum number of requests of a client in a period.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Quota {
+    limit: u32,
+    period: Duration,
+}
+
+impl Quota {
+    /// Create a `Quota` of `limit` requests per `period`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        assert!(!period.is_zero(), "the period of a quota must not be zero");
+        Self { limit, period }
+    }
+
+    /// Create a `Quota` of `limit` requests per second.
+    pub fn per_second(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    /// Create a `Quota` of `limit` requests per minute.
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    /// Create a `Quota` of `limit` requests per hour.
+    pub fn per_hour(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60 * 60))
+    }
+
+    /// Create a `Quota` of `limit` requests per day.
+    pub fn per_day(limit: u32) -> Self {
+        Self::new(limit, Duration::from_secs(60 * 60 * 24))
+    }
+
+    /// Returns the maximum number of requests in a period.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Returns the period.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// The status of the quota of a client after a request.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RateLimitStatus {
+    /// Whether the request is allowed.
+    pub allowed: bool,
+    /// The maximum number of requests in a period.
+    pub limit: u32,
+    /// The number of requests remaining in the current period.
+    pub remaining: u32,
+    /// The time until the quota is reset.
+    pub reset_after: Duration,
+}
+
+/// Represents a store counting the requests of the clients.
+#[async_trait::async_trait]
+pub trait RateLimiter: Send + Sync {
+    /// Counts a request of the client identified by `key`, unless its quota
+    /// is exceeded, and returns the status of the quota.
+    async fn hit(&self, key: &str, quota: Quota) -> Result<RateLimitStatus>;
+}
+
+#[async_trait::async_trait]
+impl<T: RateLimiter + ?Sized> RateLimiter for Arc<T> {
+    async fn hit(&self, key: &str, quota: Quota) -> Result<RateLimitStatus> {
+        self.as_ref().hit(key, quota).await
+    }
+}
+
+/// A [`RateLimiter`] added to the endpoints with
+/// [`EndpointExt::data`](crate::EndpointExt::data), and used by the
+/// [`RateLimited`] extractor.
+#[derive(Clone)]
+pub struct SharedRateLimiter(Arc<dyn RateLimiter>);
+
+impl SharedRateLimiter {
+    /// Create a `SharedRateLimiter`.
+    pub fn new(limiter: impl RateLimiter + 'static) -> Self {
+        Self(Arc::new(limiter))
+    }
+}
+
+impl Debug for SharedRateLimiter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedRateLimiter").finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimiter for SharedRateLimiter {
+    async fn hit(&self, key: &str, quota: Quota) -> Result<RateLimitStatus> {
+        self.0.hit(key, quota).await
+    }
+}
+
+/// The client whose requests are counted.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RateLimitSubject {
+    /// An authenticated client.
+    Principal(Principal),
+
+    /// An anonymous client, identified by its ip address.
+    Ip(IpAddr),
+
+    /// An anonymous client whose ip address is unknown, such as a client
+    /// connected through a Unix domain socket.
+    ///
+    /// All these clients share the same quota.
+    Unknown,
+}
+
+impl RateLimitSubject {
+    /// Returns the subject of the request.
+    pub fn of(req: &Request) -> Self {
+        if let Some(principal) = Principal::of(req) {
+            return RateLimitSubject::Principal(principal);
+        }
+
+        let ip = match req.extensions().get::<ForwardedInfo>() {
+            Some(info) => info.client_ip,
+            None => req.remote_addr().as_socket_addr().map(|addr| addr.ip()),
+        };
+        match ip {
+            Some(ip) => RateLimitSubject::Ip(ip),
+            None => RateLimitSubject::Unknown,
+        }
+    }
+
+    /// Returns the key identifying the subject in a [`RateLimiter`].
+    pub fn key(&self) -> String {
+        match self {
+            RateLimitSubject::Principal(principal) => format!("principal:{principal}"),
+            RateLimitSubject::Ip(ip) => format!("ip:{ip}"),
+            RateLimitSubject::Unknown => "unknown".to_string(),
+        }
+    }
+}
+
+/// A policy of the [`RateLimited`] extractor, which defines the quotas of
+/// the clients.
+pub trait RateLimitPolicy: Send + Sync + 'static {
+    /// The name of the policy.
+    ///
+    /// The requests are counted separately for each policy.
+    const NAME: &'static str;
+
+    /// Returns the quota of the client, or `None` if its requests are not
+    /// limited.
+    fn quota(subject: &RateLimitSubject) -> Option<Quota>;
+}
+
+/// An extractor counting the request with the [`RateLimitPolicy`] `T`, which
+/// rejects it if the quota of the client is exceeded.
+///
+/// The [`RateLimiter`] is added to the endpoint with a
+/// [`SharedRateLimiter`]. See the [module-level documentation](self) for an
+/// example.
+///
+/// # Errors
+///
+/// - [`RateLimitError`] if the quota of the client is exceeded.
+/// - [`GetDataError`] if the [`SharedRateLimiter`] is not added to the
+///   endpoint.
+pub struct RateLimited<T> {
+    subject: RateLimitSubject,
+    status: Option<RateLimitStatus>,
+    _mark: PhantomData<fn() -> T>,
+}
+
+impl<T> Debug for RateLimited<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimited")
+            .field("subject", &self.subject)
+            .field("status", &self.status)
+            .finish()
+    }
+}
+
+impl<T> RateLimited<T> {
+    /// Returns the client whose request is counted.
+    pub fn subject(&self) -> &RateLimitSubject {
+        &self.subject
+    }
+
+    /// Returns the status of the quota of the client, or `None` if its
+    /// requests are not limited.
+    pub fn status(&self) -> Option<&RateLimitStatus> {
+        self.status.as_ref()
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: RateLimitPolicy> FromRequest<'a> for RateLimited<T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        let limiter = req
+            .extensions()
+            .get::<SharedRateLimiter>()
+            .ok_or_else(|| GetDataError(std::any::type_name::<SharedRateLimiter>()))?;
+        let subject = RateLimitSubject::of(req);
+        let status = match T::quota(&subject) {
+            Some(quota) => {
+                let key = format!("rate-limit:{}:{}", T::NAME, subject.key());
+                let status = limiter.hit(&key, quota).await?;
+                if !status.allowed {
+                    return Err(RateLimitError {
+                        policy: T::NAME,
+                        status,
+                    }
+                    .into());
+                }
+                Some(status)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            subject,
+            status,
+            _mark: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        handler,
+        http::StatusCode,
+        test::TestClient,
+        web::{MockClock, RemoteAddr},
+        Addr, Endpoint, EndpointExt,
+    };
+
+    struct Api;
+
+    impl RateLimitPolicy for Api {
+        const NAME: &'static str = "api";
+
+        fn quota(subject: &RateLimitSubject) -> Option<Quota> {
+            match subject {
+                RateLimitSubject::Principal(principal) if principal.as_str() == "admin" => None,
+                RateLimitSubject::Principal(_) => Some(Quota::per_minute(2)),
+                _ => Some(Quota::per_minute(1)),
+            }
+        }
+    }
+
+    #[handler(internal)]
+    fn index(limited: RateLimited<Api>) -> String {
+        match limited.status() {
+            Some(status) => status.remaining.to_string(),
+            None => "unlimited".to_string(),
+        }
+    }
+
+    fn app(clock: MockClock) -> impl Endpoint {
+        index
+            .before(|mut req: Request| async move {
+                if let Some(user) = req.header("x-user").map(ToString::to_string) {
+                    req.extensions_mut().insert(Principal(user));
+                }
+                if let Some(ip) = req.header("x-peer").and_then(|ip| ip.parse().ok()) {
+                    req.state_mut().remote_addr = RemoteAddr(Addr::SocketAddr(ip));
+                }
+                Ok(req)
+            })
+            .data(SharedRateLimiter::new(
+                MemoryRateLimiter::new().clock(clock),
+            ))
+    }
+
+    #[tokio::test]
+    async fn principal() {
+        let cli = TestClient::new(app(MockClock::new()));
+
+        for remaining in ["1", "0"] {
+            cli.get("/")
+                .header("x-user", "a")
+                .send()
+                .await
+                .assert_text(remaining)
+                .await;
+        }
+        let resp = cli.get("/").header("x-user", "a").send().await;
+        resp.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        resp.assert_content_type("application/problem+json");
+        resp.assert_header("retry-after", "60");
+        resp.assert_header("ratelimit-limit", "2");
+        resp.assert_header("ratelimit-remaining", "0");
+        resp.assert_header("ratelimit-reset", "60");
+        resp.assert_json(json!({
+            "title": "Too Many Requests",
+            "status": 429,
+            "detail": "the quota of the policy `api` is exceeded",
+            "policy": "api",
+            "limit": 2,
+            "retry_after": 60,
+        }))
+        .await;
+
+        cli.get("/")
+            .header("x-user", "b")
+            .send()
+            .await
+            .assert_text("1")
+            .await;
+        cli.get("/")
+            .header("x-user", "admin")
+            .send()
+            .await
+            .assert_text("unlimited")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn ip() {
+        let clock = MockClock::new();
+        let cli = TestClient::new(app(clock.clone()));
+
+        let send = |peer: &'static str| cli.get("/").header("x-peer", peer).send();
+        send("1.2.3.4:1000").await.assert_text("0").await;
+        send("1.2.3.4:2000")
+            .await
+            .assert_status(StatusCode::TOO_MANY_REQUESTS);
+        send("5.6.7.8:1000").await.assert_text("0").await;
+
+        clock.advance(Duration::from_secs(60));
+        send("1.2.3.4:1000").await.assert_text("0").await;
+    }
+
+    #[tokio::test]
+    async fn missing_limiter() {
+        let resp = TestClient::new(index).get("/").send().await;
+        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
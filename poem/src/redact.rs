@@ -0,0 +1,348 @@
+//! Redaction of the sensitive data before it is logged or persisted.
+//!
+//! A [`Redactor`] masks the values of the configured headers, query keys and
+//! JSON paths with [`REDACTED`]. It is used by:
+//!
+//! - the [`Tracing`](crate::middleware::Tracing) middleware, for the URI of
+//!   the requests, with the `Redactor` added to the endpoint with
+//!   [`EndpointExt::data`](crate::EndpointExt::data),
+//! - the [`Recorder`](crate::middleware::Recorder) middleware, for the
+//!   headers, the URI and the JSON bodies of the exchanges, with
+//!   [`Recorder::redactor`](crate::middleware::Recorder::redactor),
+//! - the [`AuditLog`](crate::audit::AuditLog) middleware, for the parameters
+//!   of the events, with
+//!   [`AuditLog::redactor`](crate::audit::AuditLog::redactor).
+//!
+//! # Example
+//!
+//! ```
+//! use poem::redact::Redactor;
+//! use serde_json::json;
+//!
+//! let redactor = Redactor::new()
+//!     .query_key("token")
+//!     .json_path("user.password")
+//!     .json_path("card.number");
+//!
+//! assert_eq!(redactor.query("token=abc&page=2"), "token=[redacted]&page=2");
+//!
+//! let mut body = json!({
+//!     "user": { "name": "sunli", "password": "secret" },
+//!     "card": { "number": "4242424242424242", "expiry": "12/30" },
+//! });
+//! redactor.json(&mut body);
+//! assert_eq!(
+//!     body,
+//!     json!({
+//!         "user": { "name": "sunli", "password": "[redacted]" },
+//!         "card": { "number": "[redacted]", "expiry": "12/30" },
+//!     })
+//! );
+//! ```
+
+use std::collections::HashSet;
+
+use http::{header, HeaderMap, HeaderName, HeaderValue, Uri};
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+
+/// The value replacing the redacted data.
+pub const REDACTED: &str = "[redacted]";
+
+/// Masks the values of the configured headers, query keys and JSON paths.
+///
+/// The values of the `Authorization`, `Proxy-Authorization`, `Cookie` and
+/// `Set-Cookie` headers, and the header values marked as sensitive, are
+/// always redacted.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    headers: HashSet<HeaderName>,
+    query_keys: HashSet<String>,
+    json_paths: Vec<Vec<String>>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self {
+            headers: [
+                header::AUTHORIZATION,
+                header::PROXY_AUTHORIZATION,
+                header::COOKIE,
+                header::SET_COOKIE,
+            ]
+            .into_iter()
+            .collect(),
+            query_keys: HashSet::new(),
+            json_paths: Vec::new(),
+        }
+    }
+}
+
+impl Redactor {
+    /// Create a `Redactor`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Redacts the values of the header `name`.
+    #[must_use]
+    pub fn header<K>(mut self, name: K) -> Self
+    where
+        K: TryInto<HeaderName>,
+    {
+        if let Ok(name) = name.try_into() {
+            self.headers.insert(name);
+        }
+        self
+    }
+
+    /// Redacts the values of the query parameter `key`.
+    #[must_use]
+    pub fn query_key(mut self, key: impl Into<String>) -> Self {
+        self.query_keys.insert(key.into());
+        self
+    }
+
+    /// Redacts the values at the JSON path `path`, whose segments are
+    /// separated with dots, such as `card.number`.
+    ///
+    /// A segment `*` matches any member of an object or any element of an
+    /// array, and a numeric segment matches an element of an array. The
+    /// other segments are applied to all the elements of the arrays, so that
+    /// `password` also redacts `[{"password": "secret"}]`.
+    #[must_use]
+    pub fn json_path(mut self, path: impl AsRef<str>) -> Self {
+        self.json_paths
+            .push(path.as_ref().split('.').map(ToString::to_string).collect());
+        self
+    }
+
+    /// Returns `true` if the value of the header `name` is redacted.
+    pub fn is_redacted_header(&self, name: &HeaderName, value: &HeaderValue) -> bool {
+        value.is_sensitive() || self.headers.contains(name)
+    }
+
+    /// Returns the value of the header `name`, or [`REDACTED`].
+    ///
+    /// The invalid UTF-8 sequences are replaced.
+    pub fn header_value(&self, name: &HeaderName, value: &HeaderValue) -> String {
+        if self.is_redacted_header(name, value) {
+            REDACTED.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        }
+    }
+
+    /// Returns the names and the values of the headers, the redacted values
+    /// being replaced with [`REDACTED`].
+    pub fn headers(&self, headers: &HeaderMap) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), self.header_value(name, value)))
+            .collect()
+    }
+
+    /// Returns `true` if the values of the query parameter `key` are
+    /// redacted.
+    pub fn is_redacted_query_key(&self, key: &str) -> bool {
+        self.query_keys.contains(key)
+    }
+
+    /// Returns the query string with the values of the redacted keys
+    /// replaced with [`REDACTED`].
+    pub fn query(&self, query: &str) -> String {
+        if self.query_keys.is_empty() {
+            return query.to_string();
+        }
+
+        query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if self.is_redacted_query_key(&decode_query_key(key)) => {
+                    format!("{}={}", key, REDACTED)
+                }
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Returns the URI with the values of the redacted query keys replaced
+    /// with [`REDACTED`].
+    pub fn uri(&self, uri: &Uri) -> String {
+        match uri.query() {
+            Some(query) if !self.query_keys.is_empty() => {
+                let uri = uri.to_string();
+                let prefix = &uri[..uri.len() - query.len()];
+                format!("{}{}", prefix, self.query(query))
+            }
+            _ => uri.to_string(),
+        }
+    }
+
+    /// Replaces the values at the redacted JSON paths with [`REDACTED`].
+    pub fn json(&self, value: &mut Value) {
+        for path in &self.json_paths {
+            redact_json(value, path);
+        }
+    }
+
+    /// Returns the body with the values at the redacted JSON paths replaced
+    /// with [`REDACTED`], if its content type is JSON.
+    ///
+    /// A JSON body which cannot be parsed, such as a truncated one, is
+    /// replaced entirely with [`REDACTED`], since the redacted values cannot
+    /// be located in it.
+    pub fn body(&self, content_type: Option<&str>, body: &str) -> String {
+        let is_json = content_type
+            .and_then(|content_type| content_type.parse::<mime::Mime>().ok())
+            .map_or(false, |mime| {
+                mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+            });
+        if self.json_paths.is_empty() || !is_json || body.is_empty() {
+            return body.to_string();
+        }
+
+        match serde_json::from_str::<Value>(body) {
+            Ok(mut value) => {
+                self.json(&mut value);
+                value.to_string()
+            }
+            Err(_) => REDACTED.to_string(),
+        }
+    }
+}
+
+fn decode_query_key(key: &str) -> String {
+    percent_decode_str(&key.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+fn redact_json(value: &mut Value, path: &[String]) {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let redact = |value: &mut Value| {
+        if rest.is_empty() {
+            *value = Value::String(REDACTED.to_string());
+        } else {
+            redact_json(value, rest);
+        }
+    };
+
+    match value {
+        Value::Object(members) => {
+            for (name, member) in members.iter_mut() {
+                if segment == "*" || name == segment {
+                    redact(member);
+                }
+            }
+        }
+        Value::Array(elements) if segment == "*" => elements.iter_mut().for_each(redact),
+        Value::Array(elements) => match segment.parse::<usize>() {
+            Ok(idx) => {
+                if let Some(element) = elements.get_mut(idx) {
+                    redact(element);
+                }
+            }
+            Err(_) => {
+                for element in elements {
+                    redact_json(element, path);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn headers() {
+        let redactor = Redactor::new().header("x-secret");
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("a=1"));
+        headers.insert("x-secret", HeaderValue::from_static("1"));
+        headers.insert("x-public", HeaderValue::from_static("2"));
+        let mut sensitive = HeaderValue::from_static("3");
+        sensitive.set_sensitive(true);
+        headers.insert("x-sensitive", sensitive);
+
+        let headers = redactor.headers(&headers);
+        let value = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("cookie"), Some(REDACTED));
+        assert_eq!(value("x-secret"), Some(REDACTED));
+        assert_eq!(value("x-sensitive"), Some(REDACTED));
+        assert_eq!(value("x-public"), Some("2"));
+    }
+
+    #[test]
+    fn query() {
+        let redactor = Redactor::new()
+            .query_key("api_key")
+            .query_key("access token");
+        assert_eq!(
+            redactor.query("api_key=1&page=2&access+token=3&api_key"),
+            "api_key=[redacted]&page=2&access+token=[redacted]&api_key"
+        );
+        assert_eq!(
+            redactor.uri(&"http://localhost/a?api%5Fkey=1".parse().unwrap()),
+            "http://localhost/a?api%5Fkey=[redacted]"
+        );
+        assert_eq!(redactor.uri(&"/a".parse().unwrap()), "/a");
+        assert_eq!(Redactor::new().query("api_key=1"), "api_key=1");
+    }
+
+    #[test]
+    fn json() {
+        let redactor = Redactor::new()
+            .json_path("password")
+            .json_path("cards.*.number")
+            .json_path("tokens.0");
+        let mut value = json!({
+            "password": "a",
+            "users": [{ "password": "b" }, { "name": "c" }],
+            "cards": [{ "number": "1", "cvc": "2" }],
+            "tokens": ["d", "e"],
+        });
+        redactor.json(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "password": REDACTED,
+                "users": [{ "password": "b" }, { "name": "c" }],
+                "cards": [{ "number": REDACTED, "cvc": "2" }],
+                "tokens": [REDACTED, "e"],
+            })
+        );
+    }
+
+    #[test]
+    fn body() {
+        let redactor = Redactor::new().json_path("password");
+        assert_eq!(
+            redactor.body(Some("application/json"), r#"{"password":"a"}"#),
+            r#"{"password":"[redacted]"}"#
+        );
+        assert_eq!(
+            redactor.body(Some("application/problem+json"), r#"{"password":"#),
+            REDACTED
+        );
+        assert_eq!(
+            redactor.body(Some("text/plain"), "password=a"),
+            "password=a"
+        );
+        assert_eq!(redactor.body(None, "{}"), "{}");
+    }
+}
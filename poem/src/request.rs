@@ -1,5 +1,6 @@
 use std::{
     any::Any,
+    borrow::Cow,
     fmt::{self, Debug, Formatter},
     future::Future,
     io::Error,
@@ -10,7 +11,8 @@ use std::{
 
 use http::uri::Scheme;
 use parking_lot::Mutex;
-use serde::de::DeserializeOwned;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 #[cfg(feature = "cookie")]
@@ -24,8 +26,9 @@ use crate::{
     },
     route::PathParams,
     web::{
+        extension::Keyed,
         headers::{Header, HeaderMapExt},
-        LocalAddr, PathDeserializer, RemoteAddr,
+        ExtensionKey, LocalAddr, PathDeserializer, RemoteAddr,
     },
     RequestBody,
 };
@@ -122,7 +125,7 @@ impl From<(http::Request<hyper::Body>, LocalAddr, RemoteAddr, Scheme)> for Reque
             parts
                 .extensions
                 .remove::<hyper::upgrade::OnUpgrade>()
-                .map(|fut| OnUpgrade { fut }),
+                .map(OnUpgrade::new),
         );
 
         Self {
@@ -254,13 +257,16 @@ impl Request {
             .and_then(|value| value.to_str().ok())
     }
 
-    /// Returns the raw path parameter with the specified `name`.
-    pub fn raw_path_param(&self, name: &str) -> Option<&str> {
+    /// Returns the raw path parameter with the specified `name`,
+    /// percent-decoded.
+    ///
+    /// NOTE: Returns `None` if the decoded value is not a valid UTF8 string.
+    pub fn raw_path_param(&self, name: &str) -> Option<Cow<'_, str>> {
         self.state
             .match_params
             .iter()
-            .find(|(key, _)| key == name)
-            .map(|(_, value)| value.as_str())
+            .find(|(key, _)| &**key == name)
+            .and_then(|(_, value)| percent_decode_str(value).decode_utf8().ok())
     }
 
     /// Deserialize path parameters.
@@ -292,7 +298,7 @@ impl Request {
     /// resp.assert_text("100:abc").await;
     /// # });
     /// ```
-    pub fn path_params<T: DeserializeOwned>(&self) -> Result<T, ParsePathError> {
+    pub fn path_params<'a, T: Deserialize<'a>>(&'a self) -> Result<T, ParsePathError> {
         T::deserialize(PathDeserializer::new(&self.state().match_params))
             .map_err(|_| ParsePathError)
     }
@@ -338,7 +344,7 @@ impl Request {
     /// resp.assert_text("100:abc").await;
     /// # });
     /// ```
-    pub fn params<T: DeserializeOwned>(&self) -> Result<T, ParseQueryError> {
+    pub fn params<'a, T: Deserialize<'a>>(&'a self) -> Result<T, ParseQueryError> {
         Ok(serde_urlencoded::from_str(
             self.uri().query().unwrap_or_default(),
         )?)
@@ -376,6 +382,37 @@ impl Request {
         self.extensions.insert(data);
     }
 
+    /// Returns the value of the typed extension with the key `K`, or `None`
+    /// if it is not set.
+    #[inline]
+    pub fn try_ext<K: ExtensionKey>(&self) -> Option<&K::Value> {
+        self.extensions.get::<Keyed<K>>().map(|keyed| &keyed.value)
+    }
+
+    /// Returns the value of the typed extension with the key `K`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the extension is not set, use [`Request::try_ext`] for the
+    /// optional extensions.
+    #[track_caller]
+    pub fn ext<K: ExtensionKey>(&self) -> &K::Value {
+        match self.try_ext::<K>() {
+            Some(value) => value,
+            None => panic!(
+                "the extension `{}` is not set, it must be inserted with `Request::set_ext` by a \
+                 middleware applied to this endpoint",
+                K::NAME
+            ),
+        }
+    }
+
+    /// Sets the value of the typed extension with the key `K`.
+    #[inline]
+    pub fn set_ext<K: ExtensionKey>(&mut self, value: K::Value) {
+        self.extensions.insert(Keyed::<K>::new(value));
+    }
+
     /// Returns a reference to the remote address.
     #[inline]
     pub fn remote_addr(&self) -> &RemoteAddr {
@@ -456,10 +493,30 @@ impl Request {
     }
 }
 
-pin_project_lite::pin_project! {
-    /// A future for a possible HTTP upgrade.
-    pub struct OnUpgrade {
-        #[pin] fut: hyper::upgrade::OnUpgrade,
+/// A future for a possible HTTP upgrade.
+pub struct OnUpgrade {
+    inner: OnUpgradeInner,
+}
+
+enum OnUpgradeInner {
+    Hyper(hyper::upgrade::OnUpgrade),
+    // an in-process connection of the test client
+    #[cfg(all(feature = "test", feature = "websocket"))]
+    Duplex(Option<tokio::io::DuplexStream>),
+}
+
+impl OnUpgrade {
+    pub(crate) fn new(fut: hyper::upgrade::OnUpgrade) -> Self {
+        Self {
+            inner: OnUpgradeInner::Hyper(fut),
+        }
+    }
+
+    #[cfg(all(feature = "test", feature = "websocket"))]
+    pub(crate) fn duplex(stream: tokio::io::DuplexStream) -> Self {
+        Self {
+            inner: OnUpgradeInner::Duplex(Some(stream)),
+        }
     }
 }
 
@@ -467,20 +524,46 @@ impl Future for OnUpgrade {
     type Output = Result<Upgraded, UpgradeError>;
 
     #[inline]
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
-        this.fut
-            .poll(cx)
-            .map_ok(|stream| Upgraded { stream })
-            .map_err(|err| UpgradeError::Other(err.to_string()))
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.inner {
+            OnUpgradeInner::Hyper(fut) => Pin::new(fut)
+                .poll(cx)
+                .map_ok(|stream| Upgraded {
+                    stream: UpgradedStream::Hyper(stream),
+                })
+                .map_err(|err| UpgradeError::Other(err.to_string())),
+            #[cfg(all(feature = "test", feature = "websocket"))]
+            OnUpgradeInner::Duplex(stream) => Poll::Ready(
+                stream
+                    .take()
+                    .map(|stream| Upgraded {
+                        stream: UpgradedStream::Duplex(stream),
+                    })
+                    .ok_or(UpgradeError::NoUpgrade),
+            ),
+        }
     }
 }
 
-pin_project_lite::pin_project! {
-    /// An upgraded HTTP connection.
-    pub struct Upgraded {
-        #[pin] stream: hyper::upgrade::Upgraded,
-    }
+/// An upgraded HTTP connection.
+pub struct Upgraded {
+    stream: UpgradedStream,
+}
+
+enum UpgradedStream {
+    Hyper(hyper::upgrade::Upgraded),
+    #[cfg(all(feature = "test", feature = "websocket"))]
+    Duplex(tokio::io::DuplexStream),
+}
+
+macro_rules! poll_stream {
+    ($this:expr, $stream:ident => $poll:expr) => {
+        match &mut $this.get_mut().stream {
+            UpgradedStream::Hyper($stream) => $poll,
+            #[cfg(all(feature = "test", feature = "websocket"))]
+            UpgradedStream::Duplex($stream) => $poll,
+        }
+    };
 }
 
 impl AsyncRead for Upgraded {
@@ -490,7 +573,7 @@ impl AsyncRead for Upgraded {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<std::io::Result<()>> {
-        self.project().stream.poll_read(cx, buf)
+        poll_stream!(self, stream => Pin::new(stream).poll_read(cx, buf))
     }
 }
 
@@ -501,17 +584,17 @@ impl AsyncWrite for Upgraded {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, Error>> {
-        self.project().stream.poll_write(cx, buf)
+        poll_stream!(self, stream => Pin::new(stream).poll_write(cx, buf))
     }
 
     #[inline]
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        self.project().stream.poll_flush(cx)
+        poll_stream!(self, stream => Pin::new(stream).poll_flush(cx))
     }
 
     #[inline]
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        self.project().stream.poll_shutdown(cx)
+        poll_stream!(self, stream => Pin::new(stream).poll_shutdown(cx))
     }
 }
 
@@ -608,12 +691,15 @@ impl RequestBuilder {
     pub fn body(self, body: impl Into<Body>) -> Request {
         Request {
             method: self.method,
+            state: RequestState {
+                original_uri: self.uri.clone(),
+                ..Default::default()
+            },
             uri: self.uri,
             version: self.version,
             headers: self.headers,
             extensions: self.extensions,
             body: body.into(),
-            state: Default::default(),
         }
     }
 
@@ -0,0 +1,203 @@
+use std::{ops::Deref, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::{middleware::render_error::html_escape, FromRequest, Request, RequestBody, Result};
+
+/// The breadcrumb of a named route, declared with
+/// [`Route::breadcrumb`](crate::Route::breadcrumb).
+#[derive(Debug, Clone)]
+pub struct Breadcrumb {
+    pub(crate) label: String,
+    pub(crate) parent: Option<String>,
+}
+
+impl Breadcrumb {
+    /// Create a breadcrumb with the specified label.
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            parent: None,
+        }
+    }
+
+    /// Sets the name of the parent route, which precedes this route in the
+    /// trail.
+    #[must_use]
+    pub fn parent(self, name: impl Into<String>) -> Self {
+        Self {
+            parent: Some(name.into()),
+            ..self
+        }
+    }
+}
+
+/// An item of the [`Breadcrumbs`] trail.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct BreadcrumbItem {
+    /// The label of the route.
+    pub label: String,
+    /// The URL of the route, or `None` if it has parameters which are not
+    /// captured by the current route.
+    pub url: Option<String>,
+}
+
+/// The trail of breadcrumbs of the matched route, from the root to the
+/// current route.
+///
+/// The trail is empty if the matched route has no
+/// [`Breadcrumb`].
+///
+/// # Example
+///
+/// ```
+/// use poem::{get, handler, test::TestClient, Breadcrumb, Breadcrumbs, Route};
+///
+/// #[handler]
+/// fn index(breadcrumbs: Breadcrumbs) -> String {
+///     breadcrumbs
+///         .iter()
+///         .map(|item| format!("{} {:?}", item.label, item.url))
+///         .collect::<Vec<_>>()
+///         .join(", ")
+/// }
+///
+/// let app = Route::new()
+///     .at_named("home", "/", get(index))
+///     .at_named("users", "/users", get(index))
+///     .at_named("user", "/users/:id", get(index))
+///     .breadcrumb("home", Breadcrumb::new("Home"))
+///     .breadcrumb("users", Breadcrumb::new("Users").parent("home"))
+///     .breadcrumb("user", Breadcrumb::new("User").parent("users"));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// TestClient::new(app)
+///     .get("/users/1")
+///     .send()
+///     .await
+///     .assert_text(r#"Home Some("/"), Users Some("/users"), User Some("/users/1")"#)
+///     .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct Breadcrumbs(pub(crate) Vec<BreadcrumbItem>);
+
+impl Deref for Breadcrumbs {
+    type Target = [BreadcrumbItem];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Breadcrumbs {
+    /// Renders the trail as a `<nav>` element, the last item being the
+    /// current page.
+    pub fn to_html(&self) -> String {
+        if self.0.is_empty() {
+            return String::new();
+        }
+
+        let mut html = String::from(r#"<nav aria-label="breadcrumb"><ol>"#);
+        for (idx, item) in self.0.iter().enumerate() {
+            let label = html_escape(&item.label);
+            match &item.url {
+                _ if idx == self.0.len() - 1 => {
+                    html.push_str(&format!(r#"<li aria-current="page">{label}</li>"#))
+                }
+                Some(url) => html.push_str(&format!(
+                    r#"<li><a href="{}">{label}</a></li>"#,
+                    html_escape(url)
+                )),
+                None => html.push_str(&format!("<li>{label}</li>")),
+            }
+        }
+        html.push_str("</ol></nav>");
+        html
+    }
+}
+
+/// Receives the trail of the route matched after it is inserted, for the
+/// template functions registered before the routing.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BreadcrumbsSlot(pub(crate) Arc<Mutex<Breadcrumbs>>);
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Breadcrumbs {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<Breadcrumbs>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, test::TestClient, Route};
+
+    #[handler(internal)]
+    fn index(breadcrumbs: Breadcrumbs) -> String {
+        breadcrumbs.to_html()
+    }
+
+    fn app() -> Route {
+        Route::new()
+            .at_named("home", "/", index)
+            .at_named("repo", "/repos/:repo", index)
+            .at_named("issues", "/repos/:repo/issues", index)
+            .at_named("issue", "/repos/:repo/issues/:id", index)
+            .at_named("settings", "/settings", index)
+            .at("/about", index)
+            .breadcrumb("home", Breadcrumb::new("Home"))
+            .breadcrumb("repo", Breadcrumb::new("<Repo>").parent("home"))
+            .breadcrumb("issues", Breadcrumb::new("Issues").parent("repo"))
+            .breadcrumb("issue", Breadcrumb::new("Issue").parent("issues"))
+            .breadcrumb("settings", Breadcrumb::new("Settings").parent("missing"))
+    }
+
+    #[tokio::test]
+    async fn trail() {
+        let cli = TestClient::new(app());
+        cli.get("/repos/poem/issues/1")
+            .send()
+            .await
+            .assert_text(
+                "<nav aria-label=\"breadcrumb\"><ol>\
+                 <li><a href=\"/\">Home</a></li>\
+                 <li><a href=\"/repos/poem\">&lt;Repo&gt;</a></li>\
+                 <li><a href=\"/repos/poem/issues\">Issues</a></li>\
+                 <li aria-current=\"page\">Issue</li>\
+                 </ol></nav>",
+            )
+            .await;
+        cli.get("/settings")
+            .send()
+            .await
+            .assert_text(
+                "<nav aria-label=\"breadcrumb\"><ol>\
+                 <li aria-current=\"page\">Settings</li>\
+                 </ol></nav>",
+            )
+            .await;
+        cli.get("/about").send().await.assert_text("").await;
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        let cli = TestClient::new(Route::new().nest("/api", app()));
+        cli.get("/api/repos/poem")
+            .send()
+            .await
+            .assert_text(
+                "<nav aria-label=\"breadcrumb\"><ol>\
+                 <li><a href=\"/api/\">Home</a></li>\
+                 <li aria-current=\"page\">&lt;Repo&gt;</li>\
+                 </ol></nav>",
+            )
+            .await;
+    }
+}
@@ -27,6 +27,13 @@ enum Segment<'a> {
     Regex(Option<&'a [u8]>, PathRegex),
 }
 
+fn param_name(name: &[u8]) -> Option<Arc<str>> {
+    match name.is_empty() {
+        true => None,
+        false => std::str::from_utf8(name).ok().map(Into::into),
+    }
+}
+
 fn find_slash(path: &[u8]) -> Option<usize> {
     for (i, c) in path.iter().enumerate() {
         if *c == b'/' {
@@ -184,6 +191,9 @@ impl<T> NodeData<T> {
 struct Node<T> {
     node_type: NodeType,
     name: Vec<u8>,
+    // the name of the parameter captured by this node, converted once when it
+    // is inserted
+    param_name: Option<Arc<str>>,
     children: Vec<Node<T>>,
     indices: Vec<u8>,
     re: Option<PathRegex>,
@@ -232,6 +242,7 @@ impl<T> Node<T> {
                     let a = Node {
                         node_type: NodeType::Static,
                         name: child.name[n..].to_vec(),
+                        param_name: None,
                         children: ::std::mem::take(&mut child.children),
                         indices: std::mem::take(&mut child.indices),
                         re: None,
@@ -245,6 +256,7 @@ impl<T> Node<T> {
                         let b = Node {
                             node_type: NodeType::Static,
                             name: name[n..].to_vec(),
+                            param_name: None,
                             children: vec![],
                             indices: vec![],
                             re: None,
@@ -278,6 +290,7 @@ impl<T> Node<T> {
                 self.children.push(Node {
                     node_type: NodeType::Static,
                     name: name.to_vec(),
+                    param_name: None,
                     children: vec![],
                     indices: vec![],
                     re: None,
@@ -311,6 +324,7 @@ impl<T> Node<T> {
                 self.param_children.push(Box::new(Node {
                     node_type: NodeType::Param,
                     name: name.to_vec(),
+                    param_name: param_name(name),
                     children: vec![],
                     indices: vec![],
                     re: None,
@@ -331,6 +345,7 @@ impl<T> Node<T> {
             .replace(Box::new(Node {
                 node_type: NodeType::CatchAll,
                 name: name.unwrap_or_default().to_vec(),
+                param_name: param_name(name.unwrap_or_default()),
                 children: vec![],
                 indices: vec![],
                 re: None,
@@ -360,6 +375,7 @@ impl<T> Node<T> {
                 self.regex_children.push(Box::new(Node {
                     node_type: NodeType::Regex,
                     name: name.to_vec(),
+                    param_name: param_name(name),
                     children: vec![],
                     indices: vec![],
                     re: Some(re),
@@ -378,12 +394,12 @@ impl<T> Node<T> {
     fn matches<'a: 'b, 'b>(
         &'a self,
         path: &'b [u8],
-        params: &mut SmallVec<[(&'b [u8], &'b [u8]); 8]>,
+        params: &mut SmallVec<[(&'a Arc<str>, &'b [u8]); 8]>,
     ) -> Option<&'a NodeData<T>> {
         if path.is_empty() {
             return if let Some(catch_all_child) = &self.catch_all_child {
-                if !catch_all_child.name.is_empty() {
-                    params.push((&catch_all_child.name, path));
+                if let Some(name) = &catch_all_child.param_name {
+                    params.push((name, path));
                 }
                 catch_all_child.data.as_ref()
             } else {
@@ -407,8 +423,8 @@ impl<T> Node<T> {
 
             if let Some(captures) = regex_children.re.as_ref().unwrap().re.captures(path) {
                 let value = &path[..captures[0].len()];
-                if !regex_children.name.is_empty() {
-                    params.push((&regex_children.name, value));
+                if let Some(name) = &regex_children.param_name {
+                    params.push((name, value));
                 }
                 if let Some(data) = regex_children.matches(&path[value.len()..], params) {
                     return Some(data);
@@ -423,7 +439,9 @@ impl<T> Node<T> {
                 Some(pos) => &path[..pos],
                 None => path,
             };
-            params.push((&param_children.name, value));
+            if let Some(name) = &param_children.param_name {
+                params.push((name, value));
+            }
             if let Some(data) = param_children.matches(&path[value.len()..], params) {
                 return Some(data);
             }
@@ -431,7 +449,9 @@ impl<T> Node<T> {
 
         params.truncate(num_params);
         if let Some(catch_all_child) = &self.catch_all_child {
-            params.push((&catch_all_child.name, path));
+            if let Some(name) = &catch_all_child.param_name {
+                params.push((name, path));
+            }
             return catch_all_child.data.as_ref();
         }
 
@@ -439,7 +459,9 @@ impl<T> Node<T> {
     }
 }
 
-pub(crate) type PathParams = Vec<(String, String)>;
+/// The matched path parameters, which are kept percent-encoded until they are
+/// deserialized.
+pub(crate) type PathParams = Vec<(Arc<str>, String)>;
 
 #[derive(Debug, Eq, PartialEq)]
 pub(crate) struct Matches<'a, T> {
@@ -458,6 +480,7 @@ impl<T> Default for RadixTree<T> {
             root: Node {
                 node_type: NodeType::Root,
                 name: vec![],
+                param_name: None,
                 children: vec![],
                 indices: vec![],
                 re: None,
@@ -516,11 +539,8 @@ impl<T> RadixTree<T> {
             Some(data) => {
                 let mut params2 = Vec::with_capacity(params.len());
                 for (name, value) in params {
-                    if let (Ok(name), Ok(value)) = (
-                        std::str::from_utf8(name),
-                        percent_encoding::percent_decode(value).decode_utf8(),
-                    ) {
-                        params2.push((name.to_string(), value.into_owned()));
+                    if let Ok(value) = std::str::from_utf8(value) {
+                        params2.push((name.clone(), value.to_string()));
                     }
                 }
                 Some(Matches {
@@ -619,15 +639,19 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![Node {
                         node_type: NodeType::Static,
                         name: b"/abc".to_vec(),
+                        param_name: None,
                         children: vec![Node {
                             node_type: NodeType::Static,
                             name: b"def".to_vec(),
+                            param_name: None,
                             children: vec![Node {
                                 node_type: NodeType::Static,
                                 name: b"gh".to_vec(),
+                                param_name: None,
                                 children: vec![],
                                 indices: vec![],
                                 re: None,
@@ -675,13 +699,16 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![Node {
                         node_type: NodeType::Static,
                         name: b"/ab".to_vec(),
+                        param_name: None,
                         children: vec![
                             Node {
                                 node_type: NodeType::Static,
                                 name: b"cd".to_vec(),
+                                param_name: None,
                                 children: vec![],
                                 indices: vec![],
                                 re: None,
@@ -693,10 +720,12 @@ mod tests {
                             Node {
                                 node_type: NodeType::Static,
                                 name: b"12".to_vec(),
+                                param_name: None,
                                 children: vec![
                                     Node {
                                         node_type: NodeType::Static,
                                         name: b"34".to_vec(),
+                                        param_name: None,
                                         children: vec![],
                                         indices: vec![],
                                         re: None,
@@ -708,9 +737,11 @@ mod tests {
                                     Node {
                                         node_type: NodeType::Static,
                                         name: b"56".to_vec(),
+                                        param_name: None,
                                         children: vec![Node {
                                             node_type: NodeType::Static,
                                             name: b"78".to_vec(),
+                                            param_name: None,
                                             children: vec![],
                                             indices: vec![],
                                             re: None,
@@ -764,12 +795,15 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![Node {
                         node_type: NodeType::Static,
                         name: b"/ab".to_vec(),
+                        param_name: None,
                         children: vec![Node {
                             node_type: NodeType::Static,
                             name: b"c".to_vec(),
+                            param_name: None,
                             children: vec![],
                             indices: vec![],
                             re: None,
@@ -808,21 +842,26 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![Node {
                         node_type: NodeType::Static,
                         name: b"/abc/".to_vec(),
+                        param_name: None,
                         children: vec![],
                         indices: vec![],
                         re: None,
                         param_children: vec![Box::new(Node {
                             node_type: NodeType::Param,
                             name: b"p1".to_vec(),
+                            param_name: Some("p1".into()),
                             children: vec![Node {
                                 node_type: NodeType::Static,
                                 name: b"/".to_vec(),
+                                param_name: None,
                                 children: vec![Node {
                                     node_type: NodeType::Static,
                                     name: b"p2".to_vec(),
+                                    param_name: None,
                                     children: vec![],
                                     indices: vec![],
                                     re: None,
@@ -836,6 +875,7 @@ mod tests {
                                 param_children: vec![Box::new(Node {
                                     node_type: NodeType::Param,
                                     name: b"p3".to_vec(),
+                                    param_name: Some("p3".into()),
                                     children: vec![],
                                     indices: vec![],
                                     re: None,
@@ -881,13 +921,16 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![Node {
                         node_type: NodeType::Static,
                         name: b"/ab".to_vec(),
+                        param_name: None,
                         children: vec![
                             Node {
                                 node_type: NodeType::Static,
                                 name: b"c/".to_vec(),
+                                param_name: None,
                                 children: vec![],
                                 indices: vec![],
                                 re: None,
@@ -895,6 +938,7 @@ mod tests {
                                 catch_all_child: Some(Box::new(Node {
                                     node_type: NodeType::CatchAll,
                                     name: b"p1".to_vec(),
+                                    param_name: Some("p1".into()),
                                     children: vec![],
                                     indices: vec![],
                                     re: None,
@@ -909,6 +953,7 @@ mod tests {
                             Node {
                                 node_type: NodeType::Static,
                                 name: b"/de".to_vec(),
+                                param_name: None,
                                 children: vec![],
                                 indices: vec![],
                                 re: None,
@@ -946,6 +991,7 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![],
                     indices: vec![],
                     re: None,
@@ -953,6 +999,7 @@ mod tests {
                     catch_all_child: Some(Box::new(Node {
                         node_type: NodeType::CatchAll,
                         name: b"p1".to_vec(),
+                        param_name: Some("p1".into()),
                         children: vec![],
                         indices: vec![],
                         re: None,
@@ -980,12 +1027,15 @@ mod tests {
                 root: Node {
                     node_type: NodeType::Root,
                     name: vec![],
+                    param_name: None,
                     children: vec![Node {
                         node_type: NodeType::Static,
                         name: b"/abc/".to_vec(),
+                        param_name: None,
                         children: vec![Node {
                             node_type: NodeType::Static,
                             name: b"def/".to_vec(),
+                            param_name: None,
                             children: vec![],
                             indices: vec![],
                             re: None,
@@ -994,6 +1044,7 @@ mod tests {
                             regex_children: vec![Box::new(Node {
                                 node_type: NodeType::Regex,
                                 name: b"name".to_vec(),
+                                param_name: Some("name".into()),
                                 children: vec![],
                                 indices: vec![],
                                 re: Some(PathRegex::new(b"\\d+").unwrap()),
@@ -1011,9 +1062,11 @@ mod tests {
                         regex_children: vec![Box::new(Node {
                             node_type: NodeType::Regex,
                             name: vec![],
+                            param_name: None,
                             children: vec![Node {
                                 node_type: NodeType::Static,
                                 name: b"/def".to_vec(),
+                                param_name: None,
                                 children: vec![],
                                 indices: vec![],
                                 re: None,
@@ -1061,7 +1114,7 @@ mod tests {
     fn create_url_params<I, K, V>(values: I) -> PathParams
     where
         I: IntoIterator<Item = (K, V)>,
-        K: Into<String>,
+        K: Into<Arc<str>>,
         V: Into<String>,
     {
         values
@@ -1215,29 +1268,29 @@ mod tests {
         let matches = tree.matches("/abc/a").unwrap();
         assert_eq!(matches.data.data, 1);
         assert_eq!(matches.params.len(), 1);
-        assert_eq!(matches.params[0].0, "id1");
+        assert_eq!(&*matches.params[0].0, "id1");
         assert_eq!(matches.params[0].1, "abc");
 
         let matches = tree.matches("/def/b").unwrap();
         assert_eq!(matches.data.data, 2);
         assert_eq!(matches.params.len(), 1);
-        assert_eq!(matches.params[0].0, "id2");
+        assert_eq!(&*matches.params[0].0, "id2");
         assert_eq!(matches.params[0].1, "def");
     }
 
     #[test]
-    fn test_percent_decoded() {
+    fn test_percent_encoded() {
         let mut tree = RadixTree::default();
         tree.add("/a/:id", 1).unwrap();
 
         let matches = tree.matches("/a/abc").unwrap();
         assert_eq!(matches.data.data, 1);
-        assert_eq!(matches.params[0].0, "id");
+        assert_eq!(&*matches.params[0].0, "id");
         assert_eq!(matches.params[0].1, "abc");
 
         let matches = tree.matches("/a/%E4%BD%A0%E5%A5%BD").unwrap();
         assert_eq!(matches.data.data, 1);
-        assert_eq!(matches.params[0].0, "id");
-        assert_eq!(matches.params[0].1, "你好");
+        assert_eq!(&*matches.params[0].0, "id");
+        assert_eq!(matches.params[0].1, "%E4%BD%A0%E5%A5%BD");
     }
 }
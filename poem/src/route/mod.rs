@@ -1,11 +1,14 @@
 //! Route object and DSL
 
+mod breadcrumbs;
 mod internal;
 mod router;
 mod router_domain;
 mod router_method;
 mod router_scheme;
 
+pub(crate) use breadcrumbs::BreadcrumbsSlot;
+pub use breadcrumbs::{Breadcrumb, BreadcrumbItem, Breadcrumbs};
 pub(crate) use internal::radix_tree::PathParams;
 pub use router::{PathPattern, Route};
 #[allow(unreachable_pub)]
@@ -24,6 +27,7 @@ pub(crate) fn check_result<T>(res: Result<T, RouteError>) -> T {
         Ok(value) => value,
         Err(RouteError::InvalidPath(path)) => panic!("invalid path: {path}"),
         Err(RouteError::Duplicate(path)) => panic!("duplicate path: {path}"),
+        Err(RouteError::DuplicateName(name)) => panic!("duplicate route name: {name}"),
         Err(RouteError::InvalidRegex { path, regex }) => {
             panic!("invalid regex in path: {path} `{regex}`")
         }
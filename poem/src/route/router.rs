@@ -1,12 +1,17 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
+use percent_encoding::percent_decode_str;
 use regex::Regex;
 
 use crate::{
     endpoint::BoxEndpoint,
     error::{NotFoundError, RouteError},
-    http::{uri::PathAndQuery, Uri},
-    route::{check_result, internal::radix_tree::RadixTree},
+    http::{header, uri::PathAndQuery, StatusCode, Uri},
+    route::{
+        check_result, internal::radix_tree::RadixTree, Breadcrumb, BreadcrumbItem, Breadcrumbs,
+        BreadcrumbsSlot, PathParams,
+    },
+    web::NamedRedirect,
     Endpoint, EndpointExt, IntoEndpoint, IntoResponse, Request, Response, Result,
 };
 
@@ -131,6 +136,9 @@ struct PathPrefix(usize);
 #[derive(Default)]
 pub struct Route {
     tree: RadixTree<BoxEndpoint<'static>>,
+    names: HashMap<String, String>,
+    breadcrumbs: HashMap<String, Breadcrumb>,
+    paths: Vec<String>,
 }
 
 impl Route {
@@ -159,11 +167,63 @@ impl Route {
         E: IntoEndpoint,
         E::Endpoint: 'static,
     {
-        self.tree
-            .add(&normalize_path(path.as_ref()), ep.map_to_response().boxed())?;
+        let path = normalize_path(path.as_ref());
+        self.tree.add(&path, ep.map_to_response().boxed())?;
+        self.paths.push(path);
         Ok(self)
     }
 
+    /// Add an [Endpoint] to the specified path with a name, which can be used
+    /// to redirect to it with [`Redirect::to_named`](crate::web::Redirect::to_named).
+    ///
+    /// # Panics
+    ///
+    /// Panic when there are duplicates in the routing table or the name has
+    /// already been used.
+    #[must_use]
+    pub fn at_named<E>(self, name: impl Into<String>, path: impl AsRef<str>, ep: E) -> Self
+    where
+        E: IntoEndpoint,
+        E::Endpoint: 'static,
+    {
+        check_result(self.try_at_named(name, path, ep))
+    }
+
+    /// Attempts to add an [Endpoint] to the specified path with a name.
+    pub fn try_at_named<E>(
+        mut self,
+        name: impl Into<String>,
+        path: impl AsRef<str>,
+        ep: E,
+    ) -> Result<Self, RouteError>
+    where
+        E: IntoEndpoint,
+        E::Endpoint: 'static,
+    {
+        let name = name.into();
+        if self.names.contains_key(&name) {
+            return Err(RouteError::DuplicateName(name));
+        }
+        let path = normalize_path(path.as_ref());
+        self = self.try_at(&path, ep)?;
+        self.names.insert(name, path);
+        Ok(self)
+    }
+
+    /// Declares the breadcrumb of the route added with the specified name by
+    /// [`Route::at_named`], which can be read by the handlers with the
+    /// [`Breadcrumbs`] extractor, or rendered in the templates with the
+    /// [`breadcrumbs`](crate::tera::functions::breadcrumbs) Tera function.
+    ///
+    /// The URLs of the parent routes are filled with the path parameters of
+    /// the matched route. The parents must be named routes of the same
+    /// routing object.
+    #[must_use]
+    pub fn breadcrumb(mut self, name: impl Into<String>, breadcrumb: Breadcrumb) -> Self {
+        self.breadcrumbs.insert(name.into(), breadcrumb);
+        self
+    }
+
     /// Add an [Endpoint] to the `/` path.
     ///
     /// Same as `self.at("/", ep)`.
@@ -252,7 +312,7 @@ impl Route {
                 if !self.root {
                     let idx = req.state().match_params.len() - 1;
                     let (name, _) = req.state_mut().match_params.remove(idx);
-                    assert_eq!(name, "--poem-rest");
+                    assert_eq!(&*name, "--poem-rest");
                 }
 
                 let new_uri = {
@@ -316,8 +376,39 @@ impl Route {
             }),
         )?;
 
+        self.paths.push(format!("{path}*"));
         Ok(self)
     }
+
+    /// Returns the paths of the routing table in the order they were added,
+    /// the nested endpoints being listed as `<prefix>/*`.
+    ///
+    /// ```
+    /// use poem::{handler, Route};
+    ///
+    /// #[handler]
+    /// fn index() {}
+    ///
+    /// let app = Route::new()
+    ///     .at("/", index)
+    ///     .at("/users/:id", index)
+    ///     .nest("/api", Route::new().at("/a", index));
+    /// assert_eq!(
+    ///     app.paths().collect::<Vec<_>>(),
+    ///     ["/", "/users/:id", "/api/*"]
+    /// );
+    /// ```
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(String::as_str)
+    }
+
+    /// Returns the names and the paths of the routes added with
+    /// [`Route::at_named`], in no particular order.
+    pub fn named_paths(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_str()))
+    }
 }
 
 /// Container that can be used to obtain path pattern from the request.
@@ -329,6 +420,18 @@ impl Endpoint for Route {
     type Output = Response;
 
     async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        // the prefix stripped by the routes this one is nested in, only needed to
+        // resolve the named redirects
+        let prefix = match self.names.is_empty() {
+            true => String::new(),
+            false => req
+                .original_uri()
+                .path()
+                .strip_suffix(req.uri().path())
+                .unwrap_or_default()
+                .to_string(),
+        };
+
         match self.tree.matches(req.uri().path()) {
             Some(matches) => {
                 req.state_mut().match_params.extend(matches.params);
@@ -348,6 +451,19 @@ impl Endpoint for Route {
                 };
                 req.set_data(pattern.clone());
 
+                if !self.breadcrumbs.is_empty() {
+                    if let Some(breadcrumbs) = self.breadcrumb_trail(
+                        &matches.data.pattern,
+                        &req.state().match_params,
+                        &prefix,
+                    ) {
+                        if let Some(slot) = req.data::<BreadcrumbsSlot>() {
+                            *slot.0.lock() = breadcrumbs.clone();
+                        }
+                        req.set_data(breadcrumbs);
+                    }
+                }
+
                 let result = matches.data.data.call(req).await;
 
                 // Add PathPattern to the innermost response so that metrics instrumentation
@@ -357,6 +473,7 @@ impl Endpoint for Route {
                         if res.data::<PathPattern>().is_none() {
                             res.set_data(pattern);
                         }
+                        self.resolve_named_redirect(&mut res, &prefix);
                         Ok(res)
                     }
                     Err(mut err) => {
@@ -372,6 +489,79 @@ impl Endpoint for Route {
     }
 }
 
+impl Route {
+    fn breadcrumb_trail(
+        &self,
+        pattern: &str,
+        params: &PathParams,
+        prefix: &str,
+    ) -> Option<Breadcrumbs> {
+        let mut name = self
+            .names
+            .iter()
+            .find(|(_, path)| path.as_str() == pattern)
+            .map(|(name, _)| name)?;
+        let params = NamedRedirect {
+            name: String::new(),
+            params: params
+                .iter()
+                .map(|(name, value)| {
+                    let value = percent_decode_str(value).decode_utf8_lossy();
+                    (name.to_string(), value.into_owned())
+                })
+                .collect(),
+        };
+
+        let mut trail = Vec::new();
+        while let Some(breadcrumb) = self.breadcrumbs.get(name) {
+            trail.push(BreadcrumbItem {
+                label: breadcrumb.label.clone(),
+                url: self
+                    .names
+                    .get(name)
+                    .and_then(|pattern| params.resolve(pattern))
+                    .map(|path| format!("{prefix}{path}")),
+            });
+            // stops on the cycles
+            match &breadcrumb.parent {
+                Some(parent) if trail.len() <= self.breadcrumbs.len() => name = parent,
+                _ => break,
+            }
+        }
+
+        if trail.is_empty() {
+            return None;
+        }
+        trail.reverse();
+        Some(Breadcrumbs(trail))
+    }
+
+    fn resolve_named_redirect(&self, resp: &mut Response, prefix: &str) {
+        let location = match resp.data::<(StatusCode, NamedRedirect)>() {
+            Some((status, named)) => match self.names.get(&named.name) {
+                Some(pattern) => match named.resolve(pattern) {
+                    Some(path) => Some((*status, format!("{prefix}{path}"))),
+                    None => {
+                        tracing::error!(name = %named.name, "invalid parameters for named route");
+                        None
+                    }
+                },
+                None => return,
+            },
+            None => return,
+        };
+
+        resp.extensions_mut()
+            .remove::<(StatusCode, NamedRedirect)>();
+        if let Some((status, location)) = location {
+            if let Ok(location) = location.parse() {
+                resp.set_status(status);
+                resp.headers_mut().insert(header::LOCATION, location);
+            }
+        }
+    }
+}
+
 fn normalize_path(path: &str) -> String {
     let re = Regex::new("//+").unwrap();
     let mut path = re.replace_all(path, "/").to_string();
@@ -772,4 +962,49 @@ mod tests {
             "/nest_no_strip1/nest_no_strip2/:id"
         );
     }
+
+    #[tokio::test]
+    async fn named_redirect() {
+        #[handler(internal)]
+        fn redirect(crate::web::Path(name): crate::web::Path<String>) -> crate::web::Redirect {
+            crate::web::Redirect::to_named(name, [("id", 1)])
+        }
+
+        let app = Route::new()
+            .at_named("root", "/items/:id", h)
+            .nest(
+                "/a",
+                Route::new()
+                    .at_named("inner", "/items/:id", h)
+                    .at("/*path", redirect),
+            )
+            .nest_no_strip(
+                "/b",
+                Route::new()
+                    .at_named("no_strip", "/b/items/:id", h)
+                    .at("/b/*path", redirect),
+            );
+        let cli = TestClient::new(app);
+
+        for (path, location) in [
+            ("/a/inner", "/a/items/1"),
+            ("/a/root", "/items/1"),
+            ("/b/no_strip", "/b/items/1"),
+        ] {
+            let resp = cli.get(path).send().await;
+            resp.assert_status(StatusCode::SEE_OTHER);
+            resp.assert_header(header::LOCATION, location);
+        }
+
+        cli.get("/a/unknown")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_name() {
+        let _ = Route::new().at_named("a", "/a", h).at_named("a", "/b", h);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
-    endpoint::BoxEndpoint, error::MethodNotAllowedError, http::Method, Endpoint, EndpointExt,
-    IntoEndpoint, Request, Response, Result,
+    endpoint::BoxEndpoint, error::MethodNotAllowedError, guard::Guard, http::Method, Endpoint,
+    EndpointExt, IntoEndpoint, Request, Response, Result,
 };
 
 /// Routing object for HTTP methods
@@ -52,6 +52,7 @@ use crate::{
 #[derive(Default)]
 pub struct RouteMethod {
     methods: Vec<(Method, BoxEndpoint<'static>)>,
+    guards: Vec<Box<dyn Guard>>,
 }
 
 impl RouteMethod {
@@ -161,6 +162,17 @@ impl RouteMethod {
     {
         self.method(Method::TRACE, ep)
     }
+
+    /// Adds a [`Guard`] checked before calling the endpoints of all the
+    /// methods.
+    ///
+    /// The requests with a method without endpoint are rejected with
+    /// [`MethodNotAllowedError`] before checking the guards.
+    #[must_use]
+    pub fn guard(mut self, guard: impl Guard + 'static) -> Self {
+        self.guards.push(Box::new(guard));
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -174,7 +186,12 @@ impl Endpoint for RouteMethod {
             .find(|(method, _)| method == req.method())
             .map(|(_, ep)| ep)
         {
-            Some(ep) => ep.call(req).await,
+            Some(ep) => {
+                for guard in &self.guards {
+                    guard.check(&req).await?;
+                }
+                ep.call(req).await
+            }
             None => {
                 if req.method() == Method::HEAD {
                     req.set_method(Method::GET);
@@ -347,4 +364,38 @@ mod tests {
         resp.assert_status_is_ok();
         resp.assert_text("").await;
     }
+
+    #[tokio::test]
+    async fn guard() {
+        #[handler(internal)]
+        fn index() -> &'static str {
+            "hello"
+        }
+
+        let cli =
+            TestClient::new(get(index).post(index).guard(
+                |req: &Request| match req.header("x-token") {
+                    Some(_) => Ok(()),
+                    None => Err(StatusCode::UNAUTHORIZED.into()),
+                },
+            ));
+        cli.get("/")
+            .header("x-token", "1")
+            .send()
+            .await
+            .assert_text("hello")
+            .await;
+        cli.head("/")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        cli.post("/")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        cli.put("/")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
 }
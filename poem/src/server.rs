@@ -1,44 +1,210 @@
 use std::{
     convert::Infallible,
     future::Future,
+    io::{Error as IoError, ErrorKind, IoSlice},
+    pin::Pin,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    task::{Context, Poll},
 };
 
+use futures_util::future::BoxFuture;
 use http::uri::Scheme;
 use hyper::server::conn::Http;
+use parking_lot::Mutex;
 use tokio::{
-    io::{AsyncRead, AsyncWrite, Result as IoResult},
-    sync::Notify,
-    time::Duration,
+    io::{AsyncRead, AsyncWrite, ReadBuf, Result as IoResult},
+    sync::{watch, Notify, Semaphore},
+    time::{Duration, Sleep},
 };
 
 use crate::{
-    listener::{Acceptor, AcceptorExt, Listener},
-    web::{LocalAddr, RemoteAddr},
-    Endpoint, EndpointExt, IntoEndpoint, Response,
+    listener::{
+        connection_info::{self, ConnectionInfo},
+        Acceptor, AcceptorExt, Listener,
+    },
+    tasks::Tasks,
+    web::{client_disconnect::RequestGuard, ClientDisconnect, LocalAddr, RemoteAddr},
+    Endpoint, EndpointExt, IntoEndpoint, Response, Result,
 };
 
+type LifecycleHook = Box<dyn FnOnce() -> BoxFuture<'static, Result<()>> + Send>;
+
 enum Either<L, A> {
     Listener(L),
     Acceptor(A),
 }
 
+/// A handle that can be used to observe the graceful shutdown of a
+/// [`Server`].
+///
+/// It can be obtained with [`Server::shutdown_signal`] before the server is
+/// started, and is also inserted into the extensions of every request served,
+/// so handlers can extract it with `Data<&ShutdownSignal>`.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, web::Data, ShutdownSignal};
+///
+/// #[handler]
+/// async fn index(signal: Data<&ShutdownSignal>) -> &'static str {
+///     if signal.is_shutting_down() {
+///         "bye"
+///     } else {
+///         "hello"
+///     }
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    pub(crate) rx: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    /// Returns `true` if the server has started shutting down.
+    pub fn is_shutting_down(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Waits until the server starts shutting down.
+    ///
+    /// If the server has already stopped, this returns immediately.
+    pub async fn wait(&self) {
+        let mut rx = self.rx.clone();
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// The readiness of a [`Server`] to serve the traffic, which is also an
+/// endpoint for the readiness probes of the orchestrators, such as the
+/// `/readyz` route of Kubernetes.
+///
+/// It can be obtained with [`Server::readiness`] before the server is
+/// started. The server is ready once the [startup hooks](Server::on_startup)
+/// have completed and the [warm-up requests](Server::warm_up) have been
+/// answered, and is no longer ready when it starts shutting down. The
+/// endpoint responds with `200 OK` when the server is ready, and with `503
+/// Service Unavailable` otherwise.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{get, handler, listener::TcpListener, Request, Route, Server};
+///
+/// #[handler]
+/// fn report() -> &'static str {
+///     "report"
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let server = Server::new(TcpListener::bind("127.0.0.1:3000"))
+///     .warm_up(Request::builder().uri_str("/report").finish());
+/// let app = Route::new()
+///     .at("/report", get(report))
+///     .at("/readyz", server.readiness());
+/// server.run(app).await
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    ready: watch::Receiver<bool>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Readiness {
+    /// Returns `true` if the server is ready.
+    pub fn is_ready(&self) -> bool {
+        *self.ready.borrow() && !*self.shutdown.borrow()
+    }
+
+    /// Waits until the server is ready.
+    ///
+    /// If the server has stopped before being ready, this returns
+    /// immediately.
+    pub async fn wait(&self) {
+        let mut rx = self.ready.clone();
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Endpoint for Readiness {
+    type Output = Response;
+
+    async fn call(&self, _req: crate::Request) -> Result<Self::Output> {
+        Ok(match self.is_ready() {
+            true => Response::builder().body("ready"),
+            false => Response::builder()
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body("not ready"),
+        })
+    }
+}
+
 /// An HTTP Server.
 #[cfg_attr(docsrs, doc(cfg(feature = "server")))]
 pub struct Server<L, A> {
     listener: Either<L, A>,
     name: Option<String>,
+    shutdown: watch::Sender<bool>,
+    ready: watch::Sender<bool>,
+    tasks: Tasks,
+    max_connections: Option<usize>,
+    options: ConnectionOptions,
+    startup_hooks: Vec<LifecycleHook>,
+    shutdown_hooks: Vec<LifecycleHook>,
+    warm_up: Vec<crate::Request>,
+    #[cfg(unix)]
+    sd_notify: bool,
+}
+
+#[derive(Clone)]
+struct ConnectionOptions {
+    http: Http,
+    fastcgi: bool,
+    max_requests: Option<usize>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
 }
 
 impl<L: Listener> Server<L, Infallible> {
     /// Use the specified listener to create an HTTP server.
     pub fn new(listener: L) -> Self {
+        let shutdown = watch::channel(false).0;
         Self {
             listener: Either::Listener(listener),
             name: None,
+            tasks: Tasks::new(ShutdownSignal {
+                rx: shutdown.subscribe(),
+            }),
+            shutdown,
+            ready: watch::channel(false).0,
+            max_connections: None,
+            options: ConnectionOptions {
+                http: Http::new(),
+                fastcgi: false,
+                max_requests: None,
+                read_timeout: None,
+                write_timeout: None,
+            },
+            startup_hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            warm_up: Vec::new(),
+            #[cfg(unix)]
+            sd_notify: false,
         }
     }
 }
@@ -46,9 +212,28 @@ impl<L: Listener> Server<L, Infallible> {
 impl<A: Acceptor> Server<Infallible, A> {
     /// Use the specified acceptor to create an HTTP server.
     pub fn new_with_acceptor(acceptor: A) -> Self {
+        let shutdown = watch::channel(false).0;
         Self {
             listener: Either::Acceptor(acceptor),
             name: None,
+            tasks: Tasks::new(ShutdownSignal {
+                rx: shutdown.subscribe(),
+            }),
+            shutdown,
+            ready: watch::channel(false).0,
+            max_connections: None,
+            options: ConnectionOptions {
+                http: Http::new(),
+                fastcgi: false,
+                max_requests: None,
+                read_timeout: None,
+                write_timeout: None,
+            },
+            startup_hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            warm_up: Vec::new(),
+            #[cfg(unix)]
+            sd_notify: false,
         }
     }
 }
@@ -68,6 +253,261 @@ where
         }
     }
 
+    /// Sets the maximum number of connections that are served at the same
+    /// time.
+    ///
+    /// When the limit is reached, the server stops accepting connections
+    /// until one of them is closed, so the new ones wait in the backlog of
+    /// the listener.
+    #[must_use]
+    pub fn max_connections(self, max: usize) -> Self {
+        Self {
+            max_connections: Some(max),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of requests that are served on a connection,
+    /// after which the connection is closed gracefully.
+    #[must_use]
+    pub fn max_requests_per_connection(mut self, max: usize) -> Self {
+        self.options.max_requests = Some(max);
+        self
+    }
+
+    /// Serves the requests with the [FastCGI](https://fastcgi-archives.github.io/FastCGI_Specification.html)
+    /// protocol instead of HTTP, to run behind a web server such as nginx or
+    /// Apache on the hosts that only expose FastCGI.
+    ///
+    /// The method, uri, headers, addresses and scheme of the requests are
+    /// taken from the CGI variables sent by the web server. The connections are
+    /// not multiplexed, but they are reused for the next requests if the web
+    /// server asks for it (`fastcgi_keep_conn on` with nginx).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use poem::{handler, listener::TcpListener, Server};
+    ///
+    /// #[handler]
+    /// fn index() -> &'static str {
+    ///     "hello"
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// // location / {
+    /// //     include fastcgi_params;
+    /// //     fastcgi_param REQUEST_URI $request_uri;
+    /// //     fastcgi_pass 127.0.0.1:9000;
+    /// // }
+    /// Server::new(TcpListener::bind("127.0.0.1:9000"))
+    ///     .fastcgi()
+    ///     .run(index)
+    ///     .await
+    /// # });
+    /// ```
+    #[must_use]
+    pub fn fastcgi(mut self) -> Self {
+        self.options.fastcgi = true;
+        self
+    }
+
+    /// Sets the maximum size of the buffer used to read HTTP/1 requests, which
+    /// limits the size of the request line and headers. Defaults to about
+    /// 400kb.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is smaller than 8192.
+    #[must_use]
+    pub fn http1_max_buf_size(mut self, max: usize) -> Self {
+        self.options.http.max_buf_size(max);
+        self
+    }
+
+    /// Sets the maximum size of the headers of HTTP/2 requests. Defaults to
+    /// 16kb.
+    #[must_use]
+    pub fn http2_max_header_list_size(mut self, max: u32) -> Self {
+        self.options.http.http2_max_header_list_size(max);
+        self
+    }
+
+    /// Sets a timeout for reading the headers of HTTP/1 requests, after which
+    /// the connection is closed.
+    #[must_use]
+    pub fn http1_header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.http.http1_header_read_timeout(timeout);
+        self
+    }
+
+    /// Sets a timeout for reading from connections: a connection on which no
+    /// data is received for this duration, including idle keep-alive
+    /// connections and upgraded connections such as WebSocket, is closed.
+    #[must_use]
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a timeout for writing to connections: a connection that does not
+    /// accept any data for this duration, for example because the client
+    /// stopped reading the response, is closed.
+    #[must_use]
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.options.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Specify whether HTTP/1 connections are kept alive between requests.
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn http1_keep_alive(mut self, enable: bool) -> Self {
+        self.options.http.http1_keep_alive(enable);
+        self
+    }
+
+    /// Sets the interval of the HTTP/2 `PING` frames that are sent to keep
+    /// connections alive. Defaults to disabled.
+    #[must_use]
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.options.http.http2_keep_alive_interval(interval);
+        self
+    }
+
+    /// Sets how long to wait for the acknowledgement of an HTTP/2 keep-alive
+    /// `PING` before the connection is closed. Defaults to 20 seconds, and it
+    /// has no effect unless [`Server::http2_keep_alive_interval`] is set.
+    #[must_use]
+    pub fn http2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.options.http.http2_keep_alive_timeout(timeout);
+        self
+    }
+
+    /// Adds a function which is called before the server starts listening,
+    /// such as to run the migrations of a database, warm a cache or check a
+    /// connection pool.
+    ///
+    /// The startup hooks are called one after the other, in the order they
+    /// were added, and each one must complete before the next one is called.
+    /// If a hook fails, the next ones are not called, and the server returns
+    /// its error without listening nor calling the shutdown hooks.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use poem::{handler, listener::TcpListener, Server};
+    ///
+    /// #[handler]
+    /// fn index() -> &'static str {
+    ///     "hello"
+    /// }
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// Server::new(TcpListener::bind("127.0.0.1:3000"))
+    ///     .on_startup(|| async {
+    ///         tracing::info!("running the migrations");
+    ///         Ok(())
+    ///     })
+    ///     .on_startup(|| async {
+    ///         tracing::info!("warming the caches");
+    ///         Ok(())
+    ///     })
+    ///     .on_shutdown(|| async {
+    ///         tracing::info!("flushing the metrics");
+    ///         Ok(())
+    ///     })
+    ///     .run(index)
+    ///     .await
+    /// # });
+    /// ```
+    #[must_use]
+    pub fn on_startup<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.startup_hooks.push(Box::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Adds a function which is called once the server has stopped: after
+    /// the connections have been closed and the background
+    /// [`tasks`](Server::tasks) have returned.
+    ///
+    /// The shutdown hooks are called one after the other, in the reverse
+    /// order they were added, so that the resources set up by the first
+    /// hooks are released last. The errors are logged, and don't prevent the
+    /// next hooks from being called.
+    #[must_use]
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Adds a synthetic request which is sent to the endpoint once the
+    /// startup hooks have completed, such as to render the heaviest template
+    /// or to call a health check, so that the caches are filled before the
+    /// server is [ready](Server::readiness).
+    ///
+    /// The warm-up requests are sent one after the other, in the order they
+    /// were added, while the server is already listening. The failed requests,
+    /// answered with an error or a `5xx` status code, are logged and don't
+    /// prevent the server from being ready.
+    #[must_use]
+    pub fn warm_up(mut self, req: crate::Request) -> Self {
+        self.warm_up.push(req);
+        self
+    }
+
+    /// Notifies the service manager with `READY=1` once the server is
+    /// [ready](Server::readiness), and with `STOPPING=1` when it starts
+    /// shutting down, if the `NOTIFY_SOCKET` environment variable is set, as
+    /// for the systemd services with `Type=notify`.
+    ///
+    /// Each state is only sent once per process, so in a process running
+    /// several servers, it should be enabled on the server started last.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[must_use]
+    pub fn sd_notify(self) -> Self {
+        Self {
+            sd_notify: true,
+            ..self
+        }
+    }
+
+    /// Returns the [`Readiness`] of this server, which is also the endpoint
+    /// of its readiness probe.
+    pub fn readiness(&self) -> Readiness {
+        Readiness {
+            ready: self.ready.subscribe(),
+            shutdown: self.shutdown.subscribe(),
+        }
+    }
+
+    /// Returns a [`ShutdownSignal`] that is triggered when this server starts
+    /// shutting down, which can be handed to background tasks.
+    pub fn shutdown_signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            rx: self.shutdown.subscribe(),
+        }
+    }
+
+    /// Returns the [`Tasks`] of this server, whose tasks receive its
+    /// [`ShutdownSignal`].
+    ///
+    /// After the connections have been closed, the server waits for the tasks
+    /// to return, within the timeout of
+    /// [`run_with_graceful_shutdown`](Server::run_with_graceful_shutdown).
+    pub fn tasks(&self) -> Tasks {
+        self.tasks.clone()
+    }
+
     /// Run this server.
     pub async fn run<E>(self, ep: E) -> IoResult<()>
     where
@@ -79,6 +519,15 @@ where
     }
 
     /// Run this server and a signal to initiate graceful shutdown.
+    ///
+    /// When the `signal` completes, the server stops accepting new
+    /// connections and asks the open ones to close once their in-flight
+    /// requests have been answered (HTTP/1 responses are sent with
+    /// `Connection: close`, HTTP/2 connections receive a `GOAWAY` frame). The
+    /// [`ShutdownSignal`] of the server is triggered at the same time.
+    ///
+    /// If `timeout` is specified, connections that are still open when it
+    /// elapses are closed forcibly.
     pub async fn run_with_graceful_shutdown<E>(
         self,
         ep: E,
@@ -90,11 +539,33 @@ where
         E::Endpoint: 'static,
     {
         let ep = Arc::new(ep.into_endpoint().map_to_response());
-        let Server { listener, name } = self;
+        let Server {
+            listener,
+            name,
+            shutdown,
+            ready,
+            tasks,
+            max_connections,
+            options,
+            startup_hooks,
+            shutdown_hooks,
+            warm_up,
+            #[cfg(unix)]
+            sd_notify,
+        } = self;
+        let connections_limit = max_connections.map(|max| Arc::new(Semaphore::new(max)));
         let name = name.as_deref();
         let alive_connections = Arc::new(AtomicUsize::new(0));
         let notify = Arc::new(Notify::new());
         let timeout_notify = Arc::new(Notify::new());
+        let mut deadline = None;
+
+        for hook in startup_hooks {
+            if let Err(err) = hook().await {
+                tracing::error!(name = name, error = %err, "startup hook failed");
+                return Err(IoError::new(ErrorKind::Other, err.to_string()));
+            }
+        }
 
         let mut acceptor = match listener {
             Either::Listener(listener) => listener.into_acceptor().await?.boxed(),
@@ -108,10 +579,46 @@ where
         }
         tracing::info!(name = name, "server started");
 
+        let warm_up = tokio::spawn({
+            let ep = ep.clone();
+            let name = name.map(ToString::to_string);
+            let signal = ShutdownSignal {
+                rx: shutdown.subscribe(),
+            };
+            async move {
+                for mut req in warm_up {
+                    let (method, uri) = (req.method().clone(), req.uri().clone());
+                    req.extensions_mut().insert(signal.clone());
+                    let resp = ep.get_response(req).await;
+                    if resp.status().is_server_error() {
+                        tracing::warn!(
+                            name = name.as_deref(),
+                            method = %method,
+                            uri = %uri,
+                            status = %resp.status(),
+                            "warm-up request failed"
+                        );
+                    }
+                }
+                ready.send_replace(true);
+                tracing::info!(name = name.as_deref(), "server ready");
+                #[cfg(unix)]
+                if sd_notify {
+                    sd_notify_once(&READY_NOTIFIED, "READY=1");
+                }
+            }
+        });
+
         loop {
             tokio::select! {
                 _ = &mut signal => {
+                    shutdown.send_replace(true);
+                    #[cfg(unix)]
+                    if sd_notify {
+                        sd_notify_once(&STOPPING_NOTIFIED, "STOPPING=1");
+                    }
                     if let Some(timeout) = timeout {
+                        deadline = Some(tokio::time::Instant::now() + timeout);
                         tracing::info!(
                             name = name,
                             timeout_in_seconds = timeout.as_secs_f32(),
@@ -128,7 +635,13 @@ where
                     }
                     break;
                 },
-                res = acceptor.accept() => {
+                (res, permit) = async {
+                    let permit = match &connections_limit {
+                        Some(limit) => limit.clone().acquire_owned().await.ok(),
+                        None => None,
+                    };
+                    (acceptor.accept().await, permit)
+                } => {
                     if let Ok((socket, local_addr, remote_addr, scheme)) = res {
                         alive_connections.fetch_add(1, Ordering::Release);
 
@@ -136,17 +649,23 @@ where
                         let alive_connections = alive_connections.clone();
                         let notify = notify.clone();
                         let timeout_notify = timeout_notify.clone();
+                        let signal = ShutdownSignal {
+                            rx: shutdown.subscribe(),
+                        };
+                        let options = options.clone();
 
                         tokio::spawn(async move {
+                            let serve = serve_connection(socket, local_addr, remote_addr, scheme, ep, signal, options);
                             if timeout.is_some() {
                                 tokio::select! {
-                                    _ = serve_connection(socket, local_addr, remote_addr, scheme, ep) => {}
+                                    _ = serve => {}
                                     _ = timeout_notify.notified() => {}
                                 }
                             } else {
-                                serve_connection(socket, local_addr, remote_addr, scheme, ep).await;
+                                serve.await;
                             }
 
+                            drop(permit);
                             if alive_connections.fetch_sub(1, Ordering::Acquire) == 1 {
                                 notify.notify_one();
                             }
@@ -157,41 +676,622 @@ where
         }
 
         drop(acceptor);
+        warm_up.abort();
         if alive_connections.load(Ordering::Acquire) > 0 {
             tracing::info!(name = name, "wait for all connections to close.");
             notify.notified().await;
         }
 
+        if !tasks.status().is_empty() {
+            tracing::info!(name = name, "wait for all background tasks to return.");
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, tasks.join())
+                        .await
+                        .is_err()
+                    {
+                        tracing::warn!(name = name, "background tasks did not return in time");
+                    }
+                }
+                None => tasks.join().await,
+            }
+        }
+
+        for hook in shutdown_hooks.into_iter().rev() {
+            if let Err(err) = hook().await {
+                tracing::error!(name = name, error = %err, "shutdown hook failed");
+            }
+        }
+
         tracing::info!(name = name, "server stopped");
         Ok(())
     }
 }
 
+/// Whether `READY=1` has been sent to the service manager by a server of this
+/// process.
+#[cfg(unix)]
+static READY_NOTIFIED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Whether `STOPPING=1` has been sent to the service manager by a server of
+/// this process.
+#[cfg(unix)]
+static STOPPING_NOTIFIED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(unix)]
+fn sd_notify_once(notified: &std::sync::atomic::AtomicBool, state: &str) {
+    if !notified.swap(true, Ordering::SeqCst) {
+        sd_notify(state);
+    }
+}
+
+/// Sends a state notification to the service manager if the `NOTIFY_SOCKET`
+/// environment variable is set.
+///
+/// Reference: <https://www.freedesktop.org/software/systemd/man/sd_notify.html>
+#[cfg(unix)]
+fn sd_notify(state: &str) {
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(err) = send_notification(&path, state) {
+        tracing::warn!(error = %err, socket = ?path, "failed to notify the service manager");
+    }
+}
+
+/// Sends a state notification to the socket `path`, which is in the abstract
+/// namespace if it starts with `@`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn send_notification(path: &std::ffi::OsStr, state: &str) -> IoResult<()> {
+    use std::os::unix::{ffi::OsStrExt, io::AsRawFd};
+
+    use nix::sys::socket::{sendto, MsgFlags, UnixAddr};
+
+    let addr = match path.as_bytes().strip_prefix(b"@") {
+        Some(name) => UnixAddr::new_abstract(name)?,
+        None => UnixAddr::new(path)?,
+    };
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    sendto(
+        socket.as_raw_fd(),
+        state.as_bytes(),
+        &addr,
+        MsgFlags::empty(),
+    )?;
+    Ok(())
+}
+
+/// Sends a state notification to the socket `path`, the abstract namespace
+/// only exists on Linux.
+#[cfg(all(unix, not(any(target_os = "android", target_os = "linux"))))]
+fn send_notification(path: &std::ffi::OsStr, state: &str) -> IoResult<()> {
+    std::os::unix::net::UnixDatagram::unbound()?.send_to(state.as_bytes(), path)?;
+    Ok(())
+}
+
 async fn serve_connection(
     socket: impl AsyncRead + AsyncWrite + Send + Unpin + 'static,
     local_addr: LocalAddr,
     remote_addr: RemoteAddr,
     scheme: Scheme,
     ep: Arc<dyn Endpoint<Output = Response>>,
+    signal: ShutdownSignal,
+    options: ConnectionOptions,
 ) {
+    let conn_info = Arc::new(Mutex::new(ConnectionInfo::default()));
+
+    if options.fastcgi {
+        let socket = TimeoutIo::new(socket, options.read_timeout, options.write_timeout);
+        let conn = crate::fastcgi::serve_connection(
+            socket,
+            local_addr,
+            remote_addr,
+            scheme,
+            ep,
+            signal,
+            conn_info.clone(),
+            options.max_requests,
+        );
+        return connection_info::scope(conn_info, conn).await;
+    }
+
+    let requests_exhausted = Arc::new(Notify::new());
+    // dropped when the connection is closed
+    let (_disconnect_tx, disconnect_rx) = watch::channel(());
     let service = hyper::service::service_fn({
+        let signal = signal.clone();
+        let conn_info = conn_info.clone();
+        let requests_exhausted = requests_exhausted.clone();
+        let max_requests = options.max_requests;
+        let mut num_requests = 0;
         move |req: hyper::Request<hyper::Body>| {
             let ep = ep.clone();
-            let local_addr = local_addr.clone();
-            let remote_addr = remote_addr.clone();
-            let scheme = scheme.clone();
+            let signal = signal.clone();
+            let mut req: crate::Request =
+                (req, local_addr.clone(), remote_addr.clone(), scheme.clone()).into();
+            let version = req.version();
+            conn_info.lock().apply(&mut req);
+            req.extensions_mut().insert(signal.clone());
+            let (guard, request_rx) = RequestGuard::new();
+            req.extensions_mut()
+                .insert(ClientDisconnect::new(disconnect_rx.clone(), request_rx));
+
+            num_requests += 1;
+            let last_request = max_requests.map_or(false, |max| num_requests >= max);
+            if last_request {
+                requests_exhausted.notify_one();
+            }
+
             async move {
-                Ok::<http::Response<_>, Infallible>(
-                    ep.get_response((req, local_addr, remote_addr, scheme).into())
-                        .await
-                        .into(),
-                )
+                let mut resp = ep.get_response(req).await;
+                guard.complete();
+                if (signal.is_shutting_down() || last_request)
+                    && version < http::Version::HTTP_2
+                    && resp.status() != http::StatusCode::SWITCHING_PROTOCOLS
+                {
+                    resp.headers_mut().insert(
+                        http::header::CONNECTION,
+                        http::HeaderValue::from_static("close"),
+                    );
+                }
+                Ok::<http::Response<_>, Infallible>(resp.into())
             }
         }
     });
 
-    let conn = Http::new()
+    let socket = TimeoutIo::new(socket, options.read_timeout, options.write_timeout);
+    let conn = options
+        .http
         .serve_connection(socket, service)
         .with_upgrades();
-    let _ = conn.await;
+
+    connection_info::scope(conn_info, async move {
+        tokio::pin!(conn);
+
+        tokio::select! {
+            _ = &mut conn => return,
+            _ = signal.wait() => conn.as_mut().graceful_shutdown(),
+            _ = requests_exhausted.notified() => conn.as_mut().graceful_shutdown(),
+        }
+
+        let _ = conn.await;
+    })
+    .await;
+}
+
+/// An IO wrapper that fails the pending reads and writes after the respective
+/// timeout has elapsed.
+struct TimeoutIo<S> {
+    inner: S,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    read_deadline: Option<Pin<Box<Sleep>>>,
+    write_deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> TimeoutIo<S> {
+    fn new(inner: S, read_timeout: Option<Duration>, write_timeout: Option<Duration>) -> Self {
+        Self {
+            inner,
+            read_timeout,
+            write_timeout,
+            read_deadline: None,
+            write_deadline: None,
+        }
+    }
+}
+
+fn poll_timeout<T>(
+    res: Poll<IoResult<T>>,
+    timeout: Option<Duration>,
+    deadline: &mut Option<Pin<Box<Sleep>>>,
+    cx: &mut Context<'_>,
+) -> Poll<IoResult<T>> {
+    match (res, timeout) {
+        (Poll::Ready(res), _) => {
+            *deadline = None;
+            Poll::Ready(res)
+        }
+        (Poll::Pending, Some(timeout)) => {
+            let sleep = deadline.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+            match sleep.as_mut().poll(cx) {
+                Poll::Ready(()) => {
+                    *deadline = None;
+                    Poll::Ready(Err(IoError::new(
+                        ErrorKind::TimedOut,
+                        "connection timed out",
+                    )))
+                }
+                Poll::Pending => Poll::Pending,
+            }
+        }
+        (Poll::Pending, None) => Poll::Pending,
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TimeoutIo<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        poll_timeout(res, this.read_timeout, &mut this.read_deadline, cx)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimeoutIo<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        let this = &mut *self;
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        poll_timeout(res, this.write_timeout, &mut this.write_deadline, cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<IoResult<usize>> {
+        let this = &mut *self;
+        let res = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        poll_timeout(res, this.write_timeout, &mut this.write_deadline, cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        let res = Pin::new(&mut this.inner).poll_flush(cx);
+        poll_timeout(res, this.write_timeout, &mut this.write_deadline, cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        let res = Pin::new(&mut this.inner).poll_shutdown(cx);
+        poll_timeout(res, this.write_timeout, &mut this.write_deadline, cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::oneshot,
+    };
+
+    use super::*;
+    use crate::{handler, listener::TcpListener, web::Data};
+
+    #[tokio::test]
+    async fn graceful_shutdown_drains_connections() {
+        #[handler(internal)]
+        async fn index(signal: Data<&ShutdownSignal>) -> String {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            signal.is_shutting_down().to_string()
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor.local_addr().remove(0);
+        let server = Server::new_with_acceptor(acceptor);
+        let server_signal = server.shutdown_signal();
+        let (tx, rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(server.run_with_graceful_shutdown(
+            index,
+            async move {
+                let _ = rx.await;
+            },
+            Some(Duration::from_secs(5)),
+        ));
+
+        let mut stream = TcpStream::connect(*addr.as_socket_addr().unwrap())
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(()).unwrap();
+        server_signal.wait().await;
+        assert!(server_signal.is_shutting_down());
+
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(resp.to_lowercase().contains("connection: close\r\n"));
+        assert!(resp.ends_with("true"));
+
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown_waits_for_tasks() {
+        #[handler(internal)]
+        fn index() {}
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let server = Server::new_with_acceptor(acceptor);
+        let (done_tx, mut done_rx) = oneshot::channel();
+        server.tasks().spawn("worker", |signal| async move {
+            signal.wait().await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let _ = done_tx.send(());
+            Ok::<_, std::io::Error>(())
+        });
+
+        server
+            .run_with_graceful_shutdown(index, async {}, Some(Duration::from_secs(5)))
+            .await
+            .unwrap();
+        assert!(done_rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn lifecycle_hooks() {
+        #[handler(internal)]
+        fn index() {}
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let hook = |name: &'static str, fail: bool| {
+            let events = events.clone();
+            move || async move {
+                events.lock().push(name);
+                match fail {
+                    true => Err(crate::error::InternalServerError(IoError::new(
+                        ErrorKind::Other,
+                        "unavailable",
+                    ))),
+                    false => Ok(()),
+                }
+            }
+        };
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let server = Server::new_with_acceptor(acceptor)
+            .on_startup(hook("migrate", false))
+            .on_shutdown(hook("close pool", false))
+            .on_startup(hook("warm cache", false))
+            .on_shutdown(hook("flush metrics", true))
+            .on_shutdown(hook("flush logs", false));
+        let tasks_events = events.clone();
+        server.tasks().spawn("worker", |signal| async move {
+            signal.wait().await;
+            tasks_events.lock().push("worker");
+            Ok::<_, IoError>(())
+        });
+        server
+            .run_with_graceful_shutdown(index, async {}, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            *events.lock(),
+            vec![
+                "migrate",
+                "warm cache",
+                "worker",
+                "flush logs",
+                "flush metrics",
+                "close pool"
+            ]
+        );
+
+        events.lock().clear();
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let err = Server::new_with_acceptor(acceptor)
+            .on_startup(hook("migrate", true))
+            .on_startup(hook("warm cache", false))
+            .on_shutdown(hook("close pool", false))
+            .run(index)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "unavailable");
+        assert_eq!(*events.lock(), vec!["migrate"]);
+    }
+
+    #[tokio::test]
+    async fn warm_up() {
+        #[handler(internal)]
+        async fn report(gate: Data<&Arc<Notify>>) -> &'static str {
+            gate.notified().await;
+            "report"
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor.local_addr().remove(0);
+        let server = Server::new_with_acceptor(acceptor)
+            .warm_up(crate::Request::builder().uri_str("/report").finish());
+        let readiness = server.readiness();
+        let gate = Arc::new(Notify::new());
+        let app = crate::Route::new()
+            .at("/report", report)
+            .at("/readyz", readiness.clone())
+            .data(gate.clone());
+        let (tx, rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(server.run_with_graceful_shutdown(
+            app,
+            async move {
+                let _ = rx.await;
+            },
+            None,
+        ));
+
+        let readyz = || async {
+            let mut stream = TcpStream::connect(*addr.as_socket_addr().unwrap())
+                .await
+                .unwrap();
+            stream
+                .write_all(b"GET /readyz HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut resp = String::new();
+            stream.read_to_string(&mut resp).await.unwrap();
+            resp
+        };
+
+        assert!(readyz().await.starts_with("HTTP/1.1 503"));
+        assert!(!readiness.is_ready());
+
+        gate.notify_one();
+        readiness.wait().await;
+        assert!(readiness.is_ready());
+        assert!(readyz().await.starts_with("HTTP/1.1 200"));
+
+        tx.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+        assert!(!readiness.is_ready());
+    }
+
+    #[tokio::test]
+    async fn max_requests_per_connection() {
+        #[handler(internal)]
+        fn index() -> &'static str {
+            "hello"
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor.local_addr().remove(0);
+        tokio::spawn(
+            Server::new_with_acceptor(acceptor)
+                .max_requests_per_connection(2)
+                .run(index),
+        );
+
+        let mut stream = TcpStream::connect(*addr.as_socket_addr().unwrap())
+            .await
+            .unwrap();
+        stream
+            .write_all(
+                b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\nGET / HTTP/1.1\r\nhost: \
+                  localhost\r\n\r\nGET / HTTP/1.1\r\nhost: localhost\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert_eq!(resp.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(resp.to_lowercase().contains("connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn read_timeout() {
+        #[handler(internal)]
+        fn index() -> &'static str {
+            "hello"
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor.local_addr().remove(0);
+        tokio::spawn(
+            Server::new_with_acceptor(acceptor)
+                .read_timeout(Duration::from_millis(100))
+                .run(index),
+        );
+
+        let mut stream = TcpStream::connect(*addr.as_socket_addr().unwrap())
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        let res = tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut buf)).await;
+        assert!(matches!(res, Ok(Ok(0))));
+    }
+
+    #[tokio::test]
+    async fn client_disconnect() {
+        use tokio::sync::mpsc;
+
+        #[handler(internal)]
+        async fn index(disconnect: ClientDisconnect, tx: Data<&mpsc::UnboundedSender<()>>) {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                disconnect.wait().await;
+                let _ = tx.send(());
+            });
+            std::future::pending::<()>().await;
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor.local_addr().remove(0);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(index.data(tx)));
+
+        let mut stream = TcpStream::connect(*addr.as_socket_addr().unwrap())
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(rx.try_recv().is_err());
+
+        drop(stream);
+        let res = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await;
+        assert!(matches!(res, Ok(Some(()))));
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn notify_socket() {
+        use std::os::unix::{io::AsRawFd, net::UnixDatagram};
+
+        use nix::sys::socket::{bind, UnixAddr};
+
+        let mut buf = [0; 16];
+        let path = std::env::temp_dir().join(format!("poem-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        send_notification(path.as_os_str(), "READY=1").unwrap();
+        let n = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+        std::fs::remove_file(&path).unwrap();
+
+        // a socket in the abstract namespace
+        let name = format!("poem-notify-{}", std::process::id());
+        let socket = UnixDatagram::unbound().unwrap();
+        bind(
+            socket.as_raw_fd(),
+            &UnixAddr::new_abstract(name.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        send_notification(format!("@{name}").as_ref(), "STOPPING=1").unwrap();
+        let n = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"STOPPING=1");
+    }
 }
@@ -0,0 +1,452 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{error::SessionCollectionError, session::Session, Result};
+
+/// A change made to a [`SessionCollection`], that a [`SessionStorage`] can
+/// apply to a stored session without rewriting it.
+///
+/// [`SessionStorage`]: crate::session::SessionStorage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionChange {
+    /// An item appended to the collection.
+    Push {
+        /// The name of the collection.
+        name: String,
+        /// The item.
+        value: Value,
+    },
+
+    /// The item at the index removed from the collection.
+    Remove {
+        /// The name of the collection.
+        name: String,
+        /// The index of the item.
+        index: usize,
+    },
+
+    /// All the items removed from the collection.
+    Clear {
+        /// The name of the collection.
+        name: String,
+    },
+}
+
+impl CollectionChange {
+    /// Applies this change to the entries of a session.
+    ///
+    /// An empty collection is removed from the entries.
+    pub fn apply(&self, entries: &mut BTreeMap<String, Value>) {
+        let name = match self {
+            CollectionChange::Push { name, value } => {
+                match entries.get_mut(name) {
+                    Some(Value::Array(items)) => items.push(value.clone()),
+                    _ => {
+                        entries.insert(name.clone(), Value::Array(vec![value.clone()]));
+                    }
+                }
+                name
+            }
+            CollectionChange::Remove { name, index } => {
+                if let Some(Value::Array(items)) = entries.get_mut(name) {
+                    if *index < items.len() {
+                        items.remove(*index);
+                    }
+                }
+                name
+            }
+            CollectionChange::Clear { name } => name,
+        };
+        if matches!(self, CollectionChange::Clear { .. })
+            || matches!(entries.get(name), Some(Value::Array(items)) if items.is_empty())
+        {
+            entries.remove(name);
+        }
+    }
+}
+
+/// A typed list of items stored in the [`Session`], such as the items of a
+/// shopping cart.
+///
+/// The items are stored as a JSON array under the name of the collection, so
+/// they can also be read with [`Session::get`]. Each mutation only
+/// serializes or deserializes the items it touches, and is recorded as a
+/// [`CollectionChange`], so that the server-side storages supporting
+/// [`SessionStorage::update_collections`] apply it without rewriting the
+/// whole session.
+///
+/// The items that cannot be deserialized as `T` are skipped when reading the
+/// collection.
+///
+/// [`SessionStorage::update_collections`]: crate::session::SessionStorage::update_collections
+///
+/// # Errors
+///
+/// - [`SessionCollectionError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     session::{CookieConfig, CookieSession, Session},
+///     test::TestClient,
+///     web::Path,
+///     EndpointExt, Result, Route,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct CartItem {
+///     product: String,
+///     quantity: u32,
+/// }
+///
+/// #[handler]
+/// fn add(session: &Session, Path(product): Path<String>) -> Result<String> {
+///     let cart = session.collection::<CartItem>("cart").max_len(50);
+///     cart.push(&CartItem {
+///         product,
+///         quantity: 1,
+///     })?;
+///     Ok(cart.len().to_string())
+/// }
+///
+/// let app = Route::new()
+///     .at("/cart/:product", add)
+///     .with(CookieSession::new(CookieConfig::default()));
+/// let cli = TestClient::new(app).cookie_store();
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.post("/cart/apple").send().await.assert_text("1").await;
+/// cli.post("/cart/pear").send().await.assert_text("2").await;
+/// # });
+/// ```
+pub struct SessionCollection<T> {
+    session: Session,
+    name: String,
+    max_len: Option<usize>,
+    max_item_size: Option<usize>,
+    _mark: PhantomData<fn() -> T>,
+}
+
+impl<T> Debug for SessionCollection<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCollection")
+            .field("name", &self.name)
+            .field("max_len", &self.max_len)
+            .field("max_item_size", &self.max_item_size)
+            .finish()
+    }
+}
+
+impl<T> Clone for SessionCollection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            session: self.session.clone(),
+            name: self.name.clone(),
+            max_len: self.max_len,
+            max_item_size: self.max_item_size,
+            _mark: PhantomData,
+        }
+    }
+}
+
+impl<T> SessionCollection<T> {
+    pub(crate) fn new(session: &Session, name: impl Into<String>) -> Self {
+        Self {
+            session: session.clone(),
+            name: name.into(),
+            max_len: None,
+            max_item_size: None,
+            _mark: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of items in the collection.
+    ///
+    /// Default is unlimited.
+    #[must_use]
+    pub fn max_len(self, max_len: usize) -> Self {
+        Self {
+            max_len: Some(max_len),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size in bytes of an item serialized as JSON.
+    ///
+    /// Default is unlimited.
+    #[must_use]
+    pub fn max_item_size(self, max_item_size: usize) -> Self {
+        Self {
+            max_item_size: Some(max_item_size),
+            ..self
+        }
+    }
+
+    /// Returns the name of the collection.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of items in the collection.
+    pub fn len(&self) -> usize {
+        self.session
+            .read_collection(&self.name, |items| items.len())
+    }
+
+    /// Returns `true` if the collection contains no items.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all the items from the collection.
+    pub fn clear(&self) {
+        self.session
+            .update_collection(&self.name, |items, changes| {
+                if !items.is_empty() {
+                    items.clear();
+                    changes.push(CollectionChange::Clear {
+                        name: self.name.clone(),
+                    });
+                }
+            });
+    }
+}
+
+impl<T: Serialize> SessionCollection<T> {
+    /// Appends an item to the collection.
+    ///
+    /// Returns an error if the collection is full, or if the item is too
+    /// large. The item is ignored if the session is purged.
+    pub fn push(&self, item: &T) -> Result<()> {
+        let value = serde_json::to_value(item).map_err(SessionCollectionError::Serialize)?;
+        if let Some(max_item_size) = self.max_item_size {
+            let size = serde_json::to_vec(&value)
+                .map_err(SessionCollectionError::Serialize)?
+                .len();
+            if size > max_item_size {
+                return Err(SessionCollectionError::ItemTooLarge { max_item_size }.into());
+            }
+        }
+
+        self.session
+            .update_collection(&self.name, |items, changes| {
+                if let Some(max_len) = self.max_len {
+                    if items.len() >= max_len {
+                        return Err(SessionCollectionError::Full { max_len });
+                    }
+                }
+                items.push(value.clone());
+                changes.push(CollectionChange::Push {
+                    name: self.name.clone(),
+                    value,
+                });
+                Ok(())
+            })
+            .unwrap_or(Ok(()))?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> SessionCollection<T> {
+    /// Returns the item at the index.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.session.read_collection(&self.name, |items| {
+            items
+                .get(index)
+                .and_then(|value| serde_json::from_value(value.clone()).ok())
+        })
+    }
+
+    /// Returns all the items of the collection.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.session.read_collection(&self.name, |items| {
+            items
+                .iter()
+                .filter_map(|value| serde_json::from_value(value.clone()).ok())
+                .collect()
+        })
+    }
+
+    /// Returns an iterator over the items of the collection.
+    ///
+    /// The items are read when this method is called, so that the iterator
+    /// does not lock the session.
+    pub fn iter(&self) -> std::vec::IntoIter<T> {
+        self.to_vec().into_iter()
+    }
+
+    /// Removes the item at the index and returns it.
+    pub fn remove(&self, index: usize) -> Option<T> {
+        self.session
+            .update_collection(&self.name, |items, changes| {
+                if index >= items.len() {
+                    return None;
+                }
+                let value = items.remove(index);
+                changes.push(CollectionChange::Remove {
+                    name: self.name.clone(),
+                    index,
+                });
+                serde_json::from_value(value).ok()
+            })
+            .flatten()
+    }
+
+    /// Retains only the items for which `f` returns `true`.
+    ///
+    /// The items that cannot be deserialized as `T` are retained.
+    pub fn retain(&self, mut f: impl FnMut(&T) -> bool) {
+        self.session
+            .update_collection(&self.name, |items, changes| {
+                let mut index = 0;
+                items.retain(|value| {
+                    let retain = match serde_json::from_value(value.clone()) {
+                        Ok(item) => f(&item),
+                        Err(_) => true,
+                    };
+                    if retain {
+                        index += 1;
+                    } else {
+                        changes.push(CollectionChange::Remove {
+                            name: self.name.clone(),
+                            index,
+                        });
+                    }
+                    retain
+                });
+            });
+    }
+}
+
+impl<T: DeserializeOwned> IntoIterator for &SessionCollection<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::SessionStatus;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct CartItem {
+        product: String,
+        quantity: u32,
+    }
+
+    fn item(product: &str, quantity: u32) -> CartItem {
+        CartItem {
+            product: product.to_string(),
+            quantity,
+        }
+    }
+
+    #[test]
+    fn collection() {
+        let session = Session::default();
+        let cart = session.collection::<CartItem>("cart");
+        assert!(cart.is_empty());
+        assert_eq!(cart.remove(0), None);
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        assert!(session.is_empty());
+
+        cart.push(&item("apple", 1)).unwrap();
+        cart.push(&item("pear", 2)).unwrap();
+        cart.push(&item("plum", 3)).unwrap();
+        assert_eq!(session.status(), SessionStatus::Changed);
+        assert_eq!(cart.len(), 3);
+        assert_eq!(cart.get(1), Some(item("pear", 2)));
+        assert_eq!(
+            session.get::<Vec<CartItem>>("cart"),
+            Some(vec![item("apple", 1), item("pear", 2), item("plum", 3)])
+        );
+
+        assert_eq!(cart.remove(1), Some(item("pear", 2)));
+        cart.retain(|item| item.quantity > 1);
+        assert_eq!(
+            cart.iter().map(|item| item.product).collect::<Vec<_>>(),
+            vec!["plum"]
+        );
+
+        cart.clear();
+        assert!(cart.is_empty());
+        assert!(session.is_empty());
+    }
+
+    #[test]
+    fn limits() {
+        let session = Session::default();
+        let cart = session
+            .collection::<CartItem>("cart")
+            .max_len(2)
+            .max_item_size(40);
+
+        cart.push(&item("apple", 1)).unwrap();
+        cart.push(&item("pear", 1)).unwrap();
+        let err = cart.push(&item("plum", 1)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SessionCollectionError>(),
+            Some(SessionCollectionError::Full { max_len: 2 })
+        ));
+
+        cart.remove(0);
+        let err = cart.push(&item(&"a".repeat(20), 1)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SessionCollectionError>(),
+            Some(SessionCollectionError::ItemTooLarge { max_item_size: 40 })
+        ));
+        assert_eq!(cart.to_vec(), vec![item("pear", 1)]);
+    }
+
+    #[test]
+    fn changes() {
+        let mut entries = BTreeMap::new();
+        entries.insert("a".to_string(), json!(1));
+        entries.insert(
+            "cart".to_string(),
+            json!([{ "product": "apple", "quantity": 1 }]),
+        );
+        let session = Session::new(entries.clone());
+
+        let cart = session.collection::<CartItem>("cart");
+        cart.push(&item("pear", 1)).unwrap();
+        cart.push(&item("plum", 2)).unwrap();
+        cart.retain(|item| item.quantity > 1);
+        session.collection::<i32>("numbers").push(&1).unwrap();
+
+        let changes = session.collection_changes().unwrap();
+        for change in &changes {
+            change.apply(&mut entries);
+        }
+        assert_eq!(entries, session.entries());
+
+        session.set("b", 2);
+        assert_eq!(session.collection_changes(), None);
+    }
+
+    #[test]
+    fn purged() {
+        let session = Session::default();
+        session.purge();
+        let cart = session.collection::<CartItem>("cart");
+        cart.push(&item("apple", 1)).unwrap();
+        assert!(cart.is_empty());
+        assert_eq!(session.status(), SessionStatus::Purged);
+    }
+}
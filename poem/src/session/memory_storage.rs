@@ -9,17 +9,30 @@ use parking_lot::Mutex;
 use priority_queue::PriorityQueue;
 use serde_json::Value;
 
-use crate::{session::SessionStorage, Result};
+use crate::{
+    session::{CollectionChange, SessionStorage},
+    web::Clock,
+    Result,
+};
 
 struct InnerStorage {
     sessions: HashMap<String, BTreeMap<String, Value>>,
     timeout_queue: PriorityQueue<String, Reverse<Instant>>,
+    clock: Clock,
 }
 
 impl InnerStorage {
+    fn set_expires(&mut self, session_id: &str, expires: Option<Duration>) {
+        if let Some(expires) = expires {
+            let expire_at = self.clock.instant() + expires;
+            self.timeout_queue
+                .push(session_id.to_string(), Reverse(expire_at));
+        }
+    }
+
     fn cleanup(&mut self) {
         loop {
-            let now = Instant::now();
+            let now = self.clock.instant();
             if let Some((_, expire_at)) = self.timeout_queue.peek() {
                 if expire_at.0 > now {
                     break;
@@ -35,6 +48,7 @@ impl InnerStorage {
 }
 
 /// A session storage using memory.
+#[derive(Clone)]
 pub struct MemoryStorage {
     inner: Arc<Mutex<InnerStorage>>,
 }
@@ -44,6 +58,7 @@ impl Default for MemoryStorage {
         let inner = Arc::new(Mutex::new(InnerStorage {
             sessions: HashMap::new(),
             timeout_queue: PriorityQueue::new(),
+            clock: Clock::default(),
         }));
         tokio::spawn({
             let inner = Arc::downgrade(&inner);
@@ -66,12 +81,32 @@ impl MemoryStorage {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Sets the clock used for the expiration of the sessions.
+    ///
+    /// Default is the system clock.
+    #[must_use]
+    pub fn clock(self, clock: impl Into<Clock>) -> Self {
+        self.inner.lock().clock = clock.into();
+        self
+    }
+
+    /// Returns the number of sessions.
+    pub fn len(&self) -> usize {
+        self.inner.lock().sessions.len()
+    }
+
+    /// Returns `true` if there are no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().sessions.is_empty()
+    }
 }
 
 #[async_trait::async_trait]
 impl SessionStorage for MemoryStorage {
     async fn load_session(&self, session_id: &str) -> Result<Option<BTreeMap<String, Value>>> {
-        let inner = self.inner.lock();
+        let mut inner = self.inner.lock();
+        inner.cleanup();
         Ok(inner.sessions.get(session_id).cloned())
     }
 
@@ -86,11 +121,33 @@ impl SessionStorage for MemoryStorage {
         inner
             .sessions
             .insert(session_id.to_string(), entries.clone());
-        if let Some(expires) = expires {
-            inner
-                .timeout_queue
-                .push(session_id.to_string(), Reverse(Instant::now() + expires));
+        inner.set_expires(session_id, expires);
+        Ok(())
+    }
+
+    async fn update_collections(
+        &self,
+        session_id: &str,
+        changes: &[CollectionChange],
+        entries: &BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+        inner.cleanup();
+        match inner.sessions.get_mut(session_id) {
+            Some(stored) => {
+                for change in changes {
+                    change.apply(stored);
+                }
+            }
+            None => {
+                inner
+                    .sessions
+                    .insert(session_id.to_string(), entries.clone());
+            }
         }
+        inner.timeout_queue.remove(session_id);
+        inner.set_expires(session_id, expires);
         Ok(())
     }
 
@@ -104,12 +161,18 @@ impl SessionStorage for MemoryStorage {
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use super::*;
     use crate::{
+        handler,
+        http::StatusCode,
         session::{
-            test_harness::{index, TestClient},
-            CookieConfig, ServerSession,
+            test_harness::{index, TestClient as SessionTestClient},
+            CookieConfig, ServerSession, Session,
         },
+        test::TestClient,
+        web::{MockClock, Path},
         EndpointExt, Route,
     };
 
@@ -119,7 +182,7 @@ mod tests {
             CookieConfig::default(),
             MemoryStorage::new(),
         ));
-        let mut client = TestClient::default();
+        let mut client = SessionTestClient::default();
 
         client.call(&app, 0).await;
         client.assert_cookies(vec![]);
@@ -152,6 +215,7 @@ mod tests {
             .update_session("c", &values, Some(Duration::from_secs(3)))
             .await
             .unwrap();
+        assert_eq!(storage.len(), 3);
 
         assert_eq!(
             storage.load_session("a").await.unwrap(),
@@ -189,5 +253,62 @@ mod tests {
         assert_eq!(storage.load_session("a").await.unwrap(), None);
         assert_eq!(storage.load_session("b").await.unwrap(), None);
         assert_eq!(storage.load_session("c").await.unwrap(), None);
+        assert!(storage.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mock_clock() {
+        let clock = MockClock::new();
+        let storage = MemoryStorage::new().clock(clock.clone());
+        let mut values = BTreeMap::new();
+        values.insert("value".to_string(), "1".into());
+
+        storage
+            .update_session("a", &values, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        storage.update_session("b", &values, None).await.unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(
+            storage.load_session("a").await.unwrap(),
+            Some(values.clone())
+        );
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(storage.load_session("a").await.unwrap(), None);
+        assert_eq!(storage.load_session("b").await.unwrap(), Some(values));
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn collections() {
+        #[handler(internal)]
+        fn add(session: &Session, Path(item): Path<String>) -> Result<String> {
+            let items = session.collection::<String>("items").max_len(2);
+            items.push(&item)?;
+            Ok(items.to_vec().join(","))
+        }
+
+        let storage = MemoryStorage::new();
+        let cli = TestClient::new(
+            Route::new()
+                .at("/:item", add)
+                .with(ServerSession::new(CookieConfig::default(), storage.clone())),
+        )
+        .cookie_store();
+
+        cli.post("/a").send().await.assert_text("a").await;
+        cli.post("/b").send().await.assert_text("a,b").await;
+        cli.post("/c")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let sessions = storage.inner.lock().sessions.clone();
+        assert_eq!(
+            sessions.into_values().collect::<Vec<_>>(),
+            vec![[("items".to_string(), json!(["a", "b"]))].into()]
+        );
     }
 }
@@ -1,5 +1,6 @@
 //! Session management.
 
+mod collection;
 mod cookie_config;
 mod cookie_session;
 mod memory_storage;
@@ -11,7 +12,9 @@ mod session;
 mod session_storage;
 #[cfg(test)]
 pub(crate) mod test_harness;
+mod wizard;
 
+pub use collection::{CollectionChange, SessionCollection};
 pub use cookie_config::{CookieConfig, CookieSecurity};
 pub use cookie_session::{CookieSession, CookieSessionEndpoint};
 pub use memory_storage::MemoryStorage;
@@ -20,3 +23,4 @@ pub use redis_storage::RedisStorage;
 pub use server_session::{ServerSession, ServerSessionEndpoint};
 pub use session::{Session, SessionStatus};
 pub use session_storage::SessionStorage;
+pub use wizard::Wizard;
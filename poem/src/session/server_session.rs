@@ -79,11 +79,23 @@ where
 
         match session.status() {
             SessionStatus::Changed => match session_id {
-                Some(session_id) => {
-                    self.storage
-                        .update_session(&session_id, &session.entries(), self.config.ttl())
-                        .await?;
-                }
+                Some(session_id) => match session.collection_changes() {
+                    Some(changes) => {
+                        self.storage
+                            .update_collections(
+                                &session_id,
+                                &changes,
+                                &session.entries(),
+                                self.config.ttl(),
+                            )
+                            .await?;
+                    }
+                    None => {
+                        self.storage
+                            .update_session(&session_id, &session.entries(), self.config.ttl())
+                            .await?;
+                    }
+                },
                 None => {
                     let session_id = generate_session_id();
                     self.config.set_cookie_value(&cookie_jar, &session_id);
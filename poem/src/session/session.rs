@@ -8,7 +8,10 @@ use parking_lot::RwLock;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 
-use crate::{FromRequest, Request, RequestBody, Result};
+use crate::{
+    session::{CollectionChange, SessionCollection},
+    FromRequest, Request, RequestBody, Result,
+};
 
 /// Status of the Session.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -29,6 +32,9 @@ pub enum SessionStatus {
 struct SessionInner {
     status: SessionStatus,
     entries: BTreeMap<String, Value>,
+    /// The changes made by the collections, or `None` if the session is
+    /// changed otherwise.
+    changes: Option<Vec<CollectionChange>>,
 }
 
 /// Session
@@ -62,6 +68,7 @@ impl Session {
             inner: Arc::new(RwLock::new(SessionInner {
                 status: SessionStatus::Unchanged,
                 entries,
+                changes: Some(Vec::new()),
             })),
         }
     }
@@ -81,6 +88,7 @@ impl Session {
         if inner.status != SessionStatus::Purged {
             if let Ok(value) = serde_json::to_value(&value) {
                 inner.entries.insert(name.to_string(), value);
+                inner.changes = None;
                 if inner.status != SessionStatus::Renewed {
                     inner.status = SessionStatus::Changed;
                 }
@@ -93,6 +101,7 @@ impl Session {
         let mut inner = self.inner.write();
         if inner.status != SessionStatus::Purged {
             inner.entries.remove(name);
+            inner.changes = None;
             if inner.status != SessionStatus::Renewed {
                 inner.status = SessionStatus::Changed;
             }
@@ -117,6 +126,7 @@ impl Session {
         let mut inner = self.inner.write();
         if inner.status != SessionStatus::Purged {
             inner.entries.clear();
+            inner.changes = None;
             if inner.status != SessionStatus::Renewed {
                 inner.status = SessionStatus::Changed;
             }
@@ -145,6 +155,72 @@ impl Session {
         let inner = self.inner.read();
         inner.status
     }
+
+    /// Returns the collection of items of type `T` stored under `name`.
+    pub fn collection<T>(&self, name: impl Into<String>) -> SessionCollection<T> {
+        SessionCollection::new(self, name)
+    }
+
+    /// Returns the changes made by the collections, or `None` if the session
+    /// is changed otherwise, in which case it must be rewritten.
+    pub(crate) fn collection_changes(&self) -> Option<Vec<CollectionChange>> {
+        let inner = self.inner.read();
+        inner.changes.clone()
+    }
+
+    pub(crate) fn read_collection<R>(&self, name: &str, f: impl FnOnce(&[Value]) -> R) -> R {
+        let inner = self.inner.read();
+        match inner.entries.get(name) {
+            Some(Value::Array(items)) => f(items),
+            _ => f(&[]),
+        }
+    }
+
+    /// Updates the items of a collection, and records the changes reported by
+    /// `f`.
+    ///
+    /// Returns `None` if the session is purged.
+    pub(crate) fn update_collection<R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut Vec<Value>, &mut Vec<CollectionChange>) -> R,
+    ) -> Option<R> {
+        let mut inner = self.inner.write();
+        if inner.status == SessionStatus::Purged {
+            return None;
+        }
+
+        let inner = &mut *inner;
+        let (mut items, replaced) = match inner.entries.remove(name) {
+            Some(Value::Array(items)) => (items, None),
+            Some(value) => (Vec::new(), Some(value)),
+            None => (Vec::new(), None),
+        };
+        let mut changes = Vec::new();
+        let res = f(&mut items, &mut changes);
+
+        if changes.is_empty() {
+            if let Some(value) = replaced {
+                inner.entries.insert(name.to_string(), value);
+            } else if !items.is_empty() {
+                inner.entries.insert(name.to_string(), Value::Array(items));
+            }
+            return Some(res);
+        }
+
+        if !items.is_empty() {
+            inner.entries.insert(name.to_string(), Value::Array(items));
+        }
+        match &mut inner.changes {
+            // a value which is not a collection is replaced
+            Some(log) if replaced.is_none() => log.extend(changes),
+            _ => inner.changes = None,
+        }
+        if inner.status != SessionStatus::Renewed {
+            inner.status = SessionStatus::Changed;
+        }
+        Some(res)
+    }
 }
 
 #[async_trait::async_trait]
@@ -2,7 +2,7 @@ use std::{collections::BTreeMap, time::Duration};
 
 use serde_json::Value;
 
-use crate::Result;
+use crate::{session::CollectionChange, Result};
 
 /// Represents a back-end session storage.
 #[async_trait::async_trait]
@@ -18,6 +18,24 @@ pub trait SessionStorage: Send + Sync {
         expires: Option<Duration>,
     ) -> Result<()>;
 
+    /// Applies the changes made by the
+    /// [`SessionCollection`](crate::session::SessionCollection)s to a session,
+    /// when the session is not changed otherwise.
+    ///
+    /// `entries` are all the entries of the session after the changes. The
+    /// default implementation rewrites the session with them, the storages
+    /// which can apply the changes in place should override it.
+    async fn update_collections(
+        &self,
+        session_id: &str,
+        changes: &[CollectionChange],
+        entries: &BTreeMap<String, Value>,
+        expires: Option<Duration>,
+    ) -> Result<()> {
+        let _ = changes;
+        self.update_session(session_id, entries, expires).await
+    }
+
     /// Remove a session by session id.
     async fn remove_session(&self, session_id: &str) -> Result<()>;
 }
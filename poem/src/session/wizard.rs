@@ -0,0 +1,335 @@
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::session::Session;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WizardState {
+    step: usize,
+    data: Map<String, Value>,
+}
+
+/// The state of a multi-step form, such as a signup or a checkout flow,
+/// stored in the [`Session`].
+///
+/// The data submitted at each step is stored under the name of the step, and
+/// is kept when going [`back`](Wizard::back), so that the forms of the
+/// previous steps can be filled again. Once the last step is submitted, the
+/// data of all the steps is merged with [`values`](Wizard::values).
+///
+/// It implements [`Serialize`], so that it can be passed to a template, with
+/// the `name`, `steps`, `step`, `index`, `is_first`, `is_last` and `data`
+/// fields, `data` being the data already submitted at the current step.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use poem::{
+///     handler,
+///     session::{CookieConfig, CookieSession, Session, Wizard},
+///     test::TestClient,
+///     web::Form,
+///     EndpointExt, Route,
+/// };
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Signup {
+///     email: String,
+///     name: String,
+/// }
+///
+/// fn wizard(session: &Session) -> Wizard {
+///     Wizard::new(session, "signup", ["account", "profile"])
+/// }
+///
+/// #[handler]
+/// fn submit(session: &Session, Form(data): Form<HashMap<String, String>>) -> String {
+///     let wizard = wizard(session);
+///     if wizard.next(&data) {
+///         let signup = wizard.values::<Signup>().unwrap();
+///         wizard.clear();
+///         format!("welcome {}", signup.name)
+///     } else {
+///         wizard.current_step().to_string()
+///     }
+/// }
+///
+/// let app = Route::new()
+///     .at("/signup", submit)
+///     .with(CookieSession::new(CookieConfig::default()));
+/// let cli = TestClient::new(app).cookie_store();
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.post("/signup")
+///     .form(&[("email", "a@example.com")])
+///     .send()
+///     .await
+///     .assert_text("profile")
+///     .await;
+/// cli.post("/signup")
+///     .form(&[("name", "sunli")])
+///     .send()
+///     .await
+///     .assert_text("welcome sunli")
+///     .await;
+/// # });
+/// ```
+///
+/// The form of a step can then be filled with the data already submitted,
+/// in a template rendered with `ctx! { "wizard": &wizard }`:
+///
+/// ```text
+/// <form method="post">
+///   <input name="email" value="{{ wizard.data.email | default(value="") }}">
+///   {% if not wizard.is_first %}<button name="back">Back</button>{% endif %}
+///   <button>{% if wizard.is_last %}Finish{% else %}Next{% endif %}</button>
+/// </form>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Wizard {
+    session: Session,
+    key: String,
+    name: String,
+    steps: Arc<[String]>,
+}
+
+impl Wizard {
+    /// Create a wizard named `name` with the specified steps, whose state is
+    /// stored in `session`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `steps` is empty.
+    pub fn new(
+        session: &Session,
+        name: impl Into<String>,
+        steps: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let name = name.into();
+        let steps = steps.into_iter().map(Into::into).collect::<Arc<[String]>>();
+        assert!(!steps.is_empty(), "a wizard requires at least one step");
+        Self {
+            session: session.clone(),
+            key: format!("wizard:{name}"),
+            name,
+            steps,
+        }
+    }
+
+    fn state(&self) -> WizardState {
+        let mut state = self
+            .session
+            .get::<WizardState>(&self.key)
+            .unwrap_or_default();
+        state.step = state.step.min(self.steps.len() - 1);
+        state
+    }
+
+    /// Returns the name of the wizard.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the names of the steps.
+    pub fn steps(&self) -> &[String] {
+        &self.steps
+    }
+
+    /// Returns the index of the current step.
+    pub fn current_index(&self) -> usize {
+        self.state().step
+    }
+
+    /// Returns the name of the current step.
+    pub fn current_step(&self) -> &str {
+        &self.steps[self.current_index()]
+    }
+
+    /// Returns `true` if the current step is the first one.
+    pub fn is_first(&self) -> bool {
+        self.current_index() == 0
+    }
+
+    /// Returns `true` if the current step is the last one.
+    pub fn is_last(&self) -> bool {
+        self.current_index() == self.steps.len() - 1
+    }
+
+    /// Returns the data submitted at the specified step.
+    pub fn data<T: DeserializeOwned>(&self, step: &str) -> Option<T> {
+        self.state()
+            .data
+            .get(step)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Stores the validated data of the current step and moves to the next
+    /// step.
+    ///
+    /// Returns `true` if the current step is the last one, in which case the
+    /// wizard stays on it and is complete.
+    pub fn next(&self, data: &impl Serialize) -> bool {
+        let mut state = self.state();
+        if let Ok(value) = serde_json::to_value(data) {
+            state.data.insert(self.steps[state.step].clone(), value);
+        }
+        let complete = state.step == self.steps.len() - 1;
+        if !complete {
+            state.step += 1;
+        }
+        self.session.set(&self.key, &state);
+        complete
+    }
+
+    /// Moves to the previous step, keeping the data already submitted.
+    ///
+    /// Returns `false` if the current step is the first one.
+    pub fn back(&self) -> bool {
+        let mut state = self.state();
+        if state.step == 0 {
+            return false;
+        }
+        state.step -= 1;
+        self.session.set(&self.key, &state);
+        true
+    }
+
+    /// Moves to the specified step, which must precede the current step.
+    ///
+    /// Returns `false` if the step does not exist or is not reached yet.
+    pub fn go_to(&self, step: &str) -> bool {
+        let mut state = self.state();
+        match self.steps.iter().position(|name| name == step) {
+            Some(idx) if idx <= state.step => {
+                state.step = idx;
+                self.session.set(&self.key, &state);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Merges the data of all the steps into a single object, and
+    /// deserializes it.
+    ///
+    /// The fields of a step override those of the previous steps. Returns
+    /// `None` if the data of a step is not an object, or if it cannot be
+    /// deserialized as `T`, which is the case when a step is not submitted
+    /// yet and `T` requires its fields.
+    pub fn values<T: DeserializeOwned>(&self) -> Option<T> {
+        let state = self.state();
+        let mut values = Map::new();
+        for step in self.steps.iter() {
+            match state.data.get(step) {
+                Some(Value::Object(fields)) => values.extend(fields.clone()),
+                Some(_) => return None,
+                None => {}
+            }
+        }
+        serde_json::from_value(Value::Object(values)).ok()
+    }
+
+    /// Removes the state of the wizard from the session, so that it restarts
+    /// from the first step.
+    pub fn clear(&self) {
+        self.session.remove(&self.key);
+    }
+}
+
+impl Serialize for Wizard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let state = self.state();
+        let mut s = serializer.serialize_struct("Wizard", 7)?;
+        s.serialize_field("name", &self.name)?;
+        s.serialize_field("steps", &*self.steps)?;
+        s.serialize_field("step", &self.steps[state.step])?;
+        s.serialize_field("index", &state.step)?;
+        s.serialize_field("is_first", &(state.step == 0))?;
+        s.serialize_field("is_last", &(state.step == self.steps.len() - 1))?;
+        s.serialize_field(
+            "data",
+            state
+                .data
+                .get(&self.steps[state.step])
+                .unwrap_or(&Value::Null),
+        )?;
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn wizard(session: &Session) -> Wizard {
+        Wizard::new(session, "checkout", ["address", "payment", "confirm"])
+    }
+
+    #[test]
+    fn steps() {
+        let session = Session::default();
+        let wizard = wizard(&session);
+        assert_eq!(wizard.current_step(), "address");
+        assert!(wizard.is_first());
+        assert!(!wizard.back());
+
+        assert!(!wizard.next(&json!({ "city": "Paris" })));
+        assert!(!wizard.next(&json!({ "card": "4242" })));
+        assert_eq!(wizard.current_step(), "confirm");
+        assert!(wizard.is_last());
+
+        assert!(wizard.back());
+        assert_eq!(wizard.current_step(), "payment");
+        assert_eq!(
+            wizard.data::<Value>("payment"),
+            Some(json!({ "card": "4242" }))
+        );
+        assert!(!wizard.go_to("confirm"));
+        assert!(wizard.go_to("address"));
+        assert_eq!(wizard.current_index(), 0);
+
+        assert!(!wizard.next(&json!({ "city": "Lyon" })));
+        assert!(!wizard.next(&json!({ "card": "4343" })));
+        assert!(wizard.next(&json!({})));
+        assert_eq!(
+            wizard.values::<Value>(),
+            Some(json!({ "city": "Lyon", "card": "4343" }))
+        );
+
+        assert_eq!(
+            serde_json::to_value(&wizard).unwrap(),
+            json!({
+                "name": "checkout",
+                "steps": ["address", "payment", "confirm"],
+                "step": "confirm",
+                "index": 2,
+                "is_first": false,
+                "is_last": true,
+                "data": {},
+            })
+        );
+
+        wizard.clear();
+        assert!(session.is_empty());
+        assert_eq!(wizard.current_step(), "address");
+    }
+
+    #[test]
+    fn fewer_steps() {
+        let session = Session::default();
+        let wizard = wizard(&session);
+        wizard.next(&json!({}));
+        wizard.next(&json!({}));
+
+        let wizard = Wizard::new(&session, "checkout", ["address"]);
+        assert_eq!(wizard.current_step(), "address");
+        assert!(wizard.is_last());
+    }
+}
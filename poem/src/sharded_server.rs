@@ -0,0 +1,408 @@
+use std::{
+    future::Future,
+    io::{Error as IoError, ErrorKind, IoSlice},
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use http::uri::Scheme;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf, Result as IoResult},
+    net::{TcpSocket, TcpStream, ToSocketAddrs},
+    sync::{oneshot, watch},
+};
+
+use crate::{
+    listener::{Acceptor, TcpAcceptor},
+    web::{LocalAddr, RemoteAddr},
+    Endpoint, EndpointExt, IntoEndpoint, Request, Response, Result, Server,
+};
+
+/// The metrics of a shard of a [`ShardedServer`].
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+#[derive(Debug, Default)]
+pub struct ShardMetrics {
+    accepted: AtomicU64,
+    active: AtomicUsize,
+    requests: AtomicU64,
+}
+
+impl ShardMetrics {
+    /// Returns the number of connections accepted by the shard.
+    pub fn accepted_connections(&self) -> u64 {
+        self.accepted.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of open connections of the shard.
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests served by the shard.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+/// An HTTP server which runs its accept loop on several independent
+/// single-threaded Tokio runtimes, one per thread.
+///
+/// Each shard accepts the connections on its own socket bound to the same
+/// address with `SO_REUSEPORT`, so that the kernel balances the connections
+/// between the shards, and a connection is served by a single thread from the
+/// start to the end. This avoids the work stealing between the threads of a
+/// multi-threaded runtime, which improves the tail latency of the
+/// high-throughput deployments on many-core machines.
+///
+/// On the platforms without `SO_REUSEPORT`, the shards accept the connections
+/// on clones of the same socket.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{handler, Route, ShardedServer};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let server = ShardedServer::bind("0.0.0.0:3000", 4).await?;
+/// let metrics = server.metrics();
+/// server.run(Route::new().at("/", index)).await?;
+/// # Ok::<_, std::io::Error>(())
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub struct ShardedServer {
+    listeners: Vec<std::net::TcpListener>,
+    local_addr: SocketAddr,
+    name: Option<String>,
+    metrics: Vec<Arc<ShardMetrics>>,
+}
+
+impl ShardedServer {
+    /// Binds `shards` sockets to the address `addr`.
+    ///
+    /// If `shards` is `0`, the number of shards is the available
+    /// parallelism of the machine.
+    pub async fn bind(addr: impl ToSocketAddrs, shards: usize) -> IoResult<Self> {
+        let shards = match shards {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let addr = tokio::net::lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "no address to bind"))?;
+
+        let first = bind_socket(addr)?;
+        // the port of the first socket, if the port of `addr` is `0`
+        let local_addr = first.local_addr()?;
+        let mut listeners = vec![first];
+        for _ in 1..shards {
+            #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+            listeners.push(bind_socket(local_addr)?);
+            #[cfg(not(all(unix, not(target_os = "solaris"), not(target_os = "illumos"))))]
+            listeners.push(listeners[0].try_clone()?);
+        }
+
+        Ok(Self {
+            listeners,
+            local_addr,
+            name: None,
+            metrics: (0..shards).map(|_| Default::default()).collect(),
+        })
+    }
+
+    /// Specify the name of the server, it is only used for logs.
+    #[must_use]
+    pub fn name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Returns the local address that the shards are bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> usize {
+        self.listeners.len()
+    }
+
+    /// Returns the metrics of each shard.
+    pub fn metrics(&self) -> Vec<Arc<ShardMetrics>> {
+        self.metrics.clone()
+    }
+
+    /// Run this server.
+    pub async fn run<E>(self, ep: E) -> IoResult<()>
+    where
+        E: IntoEndpoint,
+        E::Endpoint: 'static,
+    {
+        self.run_with_graceful_shutdown(ep, futures_util::future::pending(), None)
+            .await
+    }
+
+    /// Run this server and a signal to initiate graceful shutdown.
+    ///
+    /// The `signal` initiates the graceful shutdown of all the shards, see
+    /// [`Server::run_with_graceful_shutdown`].
+    pub async fn run_with_graceful_shutdown<E>(
+        self,
+        ep: E,
+        signal: impl Future<Output = ()>,
+        timeout: Option<Duration>,
+    ) -> IoResult<()>
+    where
+        E: IntoEndpoint,
+        E::Endpoint: 'static,
+    {
+        let ep: Arc<dyn Endpoint<Output = Response>> =
+            Arc::new(ep.into_endpoint().map_to_response());
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let mut results = Vec::with_capacity(self.listeners.len());
+
+        for (idx, (listener, metrics)) in self.listeners.into_iter().zip(self.metrics).enumerate() {
+            let (tx, rx) = oneshot::channel();
+            results.push(rx);
+
+            let ep = ep.clone();
+            let mut shutdown_rx = shutdown_rx.clone();
+            let name = match &self.name {
+                Some(name) => format!("{name}-shard-{idx}"),
+                None => format!("shard-{idx}"),
+            };
+            std::thread::Builder::new()
+                .name(name.clone())
+                .spawn(move || {
+                    let res = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .and_then(|rt| {
+                            rt.block_on(async move {
+                                let acceptor = ShardAcceptor {
+                                    inner: TcpAcceptor::from_std(listener)?,
+                                    metrics: metrics.clone(),
+                                };
+                                Server::new_with_acceptor(acceptor)
+                                    .name(name)
+                                    .run_with_graceful_shutdown(
+                                        ShardEndpoint { inner: ep, metrics },
+                                        async move {
+                                            while !*shutdown_rx.borrow_and_update() {
+                                                if shutdown_rx.changed().await.is_err() {
+                                                    break;
+                                                }
+                                            }
+                                        },
+                                        timeout,
+                                    )
+                                    .await
+                            })
+                        });
+                    let _ = tx.send(res);
+                })?;
+        }
+
+        let done = futures_util::future::join_all(results);
+        tokio::pin!(done, signal);
+        let results = tokio::select! {
+            _ = &mut signal => {
+                shutdown.send_replace(true);
+                done.await
+            }
+            results = &mut done => results,
+        };
+
+        for res in results {
+            match res {
+                Ok(res) => res?,
+                Err(_) => return Err(IoError::new(ErrorKind::Other, "a shard panicked")),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn bind_socket(addr: SocketAddr) -> IoResult<std::net::TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
+    socket.set_reuseport(true)?;
+    socket.bind(addr)?;
+    socket.listen(1024)?.into_std()
+}
+
+struct ShardEndpoint {
+    inner: Arc<dyn Endpoint<Output = Response>>,
+    metrics: Arc<ShardMetrics>,
+}
+
+#[async_trait::async_trait]
+impl Endpoint for ShardEndpoint {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        self.metrics.requests.fetch_add(1, Ordering::Relaxed);
+        self.inner.call(req).await
+    }
+}
+
+struct ShardAcceptor {
+    inner: TcpAcceptor,
+    metrics: Arc<ShardMetrics>,
+}
+
+#[async_trait::async_trait]
+impl Acceptor for ShardAcceptor {
+    type Io = ShardIo;
+
+    fn local_addr(&self) -> Vec<LocalAddr> {
+        self.inner.local_addr()
+    }
+
+    async fn accept(&mut self) -> IoResult<(Self::Io, LocalAddr, RemoteAddr, Scheme)> {
+        let (io, local_addr, remote_addr, scheme) = self.inner.accept().await?;
+        self.metrics.accepted.fetch_add(1, Ordering::Relaxed);
+        self.metrics.active.fetch_add(1, Ordering::Relaxed);
+        let io = ShardIo {
+            inner: io,
+            metrics: self.metrics.clone(),
+        };
+        Ok((io, local_addr, remote_addr, scheme))
+    }
+}
+
+/// A connection of a shard, which is counted until it is dropped.
+struct ShardIo {
+    inner: TcpStream,
+    metrics: Arc<ShardMetrics>,
+}
+
+impl Drop for ShardIo {
+    fn drop(&mut self) {
+        self.metrics.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl AsyncRead for ShardIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ShardIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::handler;
+
+    #[handler(internal)]
+    fn index() -> String {
+        std::thread::current()
+            .name()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn sharded_server() {
+        let server = ShardedServer::bind("127.0.0.1:0", 3)
+            .await
+            .unwrap()
+            .name("test");
+        assert_eq!(server.shards(), 3);
+        let addr = server.local_addr();
+        let metrics = server.metrics();
+        let (tx, rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(server.run_with_graceful_shutdown(
+            index,
+            async move {
+                let _ = rx.await;
+            },
+            Some(Duration::from_secs(5)),
+        ));
+
+        for _ in 0..6 {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut resp = String::new();
+            stream.read_to_string(&mut resp).await.unwrap();
+            assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(resp.contains("test-shard-"));
+        }
+
+        tx.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+
+        assert_eq!(
+            metrics.iter().map(|m| m.requests()).sum::<u64>(),
+            6,
+            "{metrics:?}"
+        );
+        assert_eq!(
+            metrics
+                .iter()
+                .map(|m| m.accepted_connections())
+                .sum::<u64>(),
+            6
+        );
+        assert!(metrics.iter().all(|m| m.active_connections() == 0));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, future::Future};
+
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+/// Coalesces the concurrent calls with the same key, so that only one of
+/// them runs and the others share its result.
+pub(crate) struct SingleFlight<T> {
+    calls: Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            calls: Default::default(),
+        }
+    }
+}
+
+/// Removes the call when the leader completes or is cancelled.
+struct CallGuard<'a, T> {
+    calls: &'a Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+    key: &'a str,
+}
+
+impl<T> Drop for CallGuard<'_, T> {
+    fn drop(&mut self) {
+        self.calls.lock().remove(self.key);
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    /// Runs `f` unless a call with the same key is running, in which case
+    /// waits for it and returns its result.
+    ///
+    /// If the running call is cancelled, one of the waiting calls runs `f`
+    /// instead.
+    pub(crate) async fn run<F, Fut>(&self, key: &str, f: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        loop {
+            let running = {
+                let mut calls = self.calls.lock();
+                match calls.get(key) {
+                    Some(rx) => Err(rx.clone()),
+                    None => {
+                        let (tx, rx) = watch::channel(None);
+                        calls.insert(key.to_string(), rx);
+                        Ok(tx)
+                    }
+                }
+            };
+
+            match running {
+                Ok(tx) => {
+                    let _guard = CallGuard {
+                        calls: &self.calls,
+                        key,
+                    };
+                    let value = f().await;
+                    let _ = tx.send(Some(value.clone()));
+                    return value;
+                }
+                Err(mut rx) => loop {
+                    if let Some(value) = rx.borrow_and_update().clone() {
+                        return value;
+                    }
+                    if rx.changed().await.is_err() {
+                        if let Some(value) = rx.borrow().clone() {
+                            return value;
+                        }
+                        // the leader was cancelled
+                        break;
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn coalesce() {
+        let flights = Arc::new(SingleFlight::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let tasks = (0..10)
+            .map(|_| {
+                let flights = flights.clone();
+                let calls = calls.clone();
+                tokio::spawn(async move {
+                    flights
+                        .run("a", || async {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            42
+                        })
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 42);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(flights.run("a", || async { 1 }).await, 1);
+        assert!(flights.calls.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancelled_leader() {
+        let flights = Arc::new(SingleFlight::default());
+
+        let leader = tokio::spawn({
+            let flights = flights.clone();
+            async move { flights.run("a", std::future::pending::<i32>).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let follower = tokio::spawn({
+            let flights = flights.clone();
+            async move { flights.run("a", || async { 2 }).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        leader.abort();
+        assert_eq!(follower.await.unwrap(), 2);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    fs::Metadata,
+    io::{ErrorKind, Result as IoResult, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use percent_encoding::utf8_percent_encode;
+use ring::hmac;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use super::{sign_url, validate_key, ObjectMeta, Storage, StorageObject, KEY_ENCODE_SET};
+use crate::{error::StorageError, http::Method, Body, Result};
+
+static NEXT_TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A storage writing the objects to the files of a directory.
+///
+/// The content types of the objects are not stored, and are guessed from the
+/// extensions of the keys by the
+/// [`StorageFilesEndpoint`](crate::endpoint::StorageFilesEndpoint).
+///
+/// The presigned URLs are URLs of a `StorageFilesEndpoint` verifying their
+/// signatures, which are enabled by [`presign`](LocalStorage::presign).
+#[derive(Clone)]
+pub struct LocalStorage {
+    dir: PathBuf,
+    presign: Option<(String, Arc<hmac::Key>)>,
+}
+
+impl LocalStorage {
+    /// Create a `LocalStorage` writing the objects to `dir`, which is created
+    /// if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            presign: None,
+        }
+    }
+
+    /// Enables the presigned URLs, which are the URLs of the objects under
+    /// `base_url`, such as `https://example.com/files`, signed with `secret`.
+    ///
+    /// The objects must be served at `base_url` by a
+    /// [`StorageFilesEndpoint`](crate::endpoint::StorageFilesEndpoint) with
+    /// the same secret.
+    #[must_use]
+    pub fn presign(self, base_url: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        let base_url = base_url.into();
+        Self {
+            presign: Some((
+                base_url.trim_end_matches('/').to_string(),
+                Arc::new(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref())),
+            )),
+            ..self
+        }
+    }
+
+    /// Returns the path of the file of the object.
+    pub fn path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(self.dir.join(key))
+    }
+}
+
+fn object_meta(metadata: &Metadata) -> ObjectMeta {
+    let last_modified = metadata.modified().ok();
+    let etag = last_modified.map(|modified| {
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        format!(
+            "\"{:x}:{:x}:{:x}\"",
+            metadata.len(),
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos()
+        )
+    });
+    ObjectMeta {
+        size: metadata.len(),
+        etag,
+        last_modified,
+        content_type: None,
+    }
+}
+
+async fn metadata(path: &Path) -> Result<Option<Metadata>> {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => Ok(Some(metadata)),
+        Ok(_) => Ok(None),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(StorageError::Io(err).into()),
+    }
+}
+
+async fn write_file(path: &Path, tmp: &Path, body: Body) -> IoResult<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = tokio::fs::File::create(tmp).await?;
+    tokio::io::copy(&mut body.into_async_read(), &mut file).await?;
+    file.flush().await?;
+    drop(file);
+    tokio::fs::rename(tmp, path).await
+}
+
+#[async_trait::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, body: Body, _content_type: Option<&str>) -> Result<ObjectMeta> {
+        let path = self.path(key)?;
+        // the data is written to a temporary file, so that the object is never
+        // read partially written
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let tmp = path.with_file_name(format!(
+            ".{file_name}.{}.tmp",
+            NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        if let Err(err) = write_file(&path, &tmp, body).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(StorageError::Io(err).into());
+        }
+        let metadata = tokio::fs::metadata(&path).await.map_err(StorageError::Io)?;
+        Ok(object_meta(&metadata))
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>> {
+        Ok(metadata(&self.path(key)?).await?.as_ref().map(object_meta))
+    }
+
+    async fn stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<StorageObject>> {
+        let path = self.path(key)?;
+        let metadata = match metadata(&path).await? {
+            Some(metadata) => metadata,
+            None => return Ok(None),
+        };
+        let mut file = tokio::fs::File::open(&path)
+            .await
+            .map_err(StorageError::Io)?;
+
+        let body = match range {
+            Some(range) => {
+                let start = range.start.min(metadata.len());
+                let end = range.end.clamp(start, metadata.len());
+                file.seek(SeekFrom::Start(start))
+                    .await
+                    .map_err(StorageError::Io)?;
+                Body::from_async_read(file.take(end - start))
+            }
+            None => Body::from_async_read(file),
+        };
+        Ok(Some(StorageObject {
+            meta: object_meta(&metadata),
+            body,
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(StorageError::Io(err).into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn presigned_url(
+        &self,
+        key: &str,
+        method: Method,
+        expires_in: Duration,
+    ) -> Result<String> {
+        validate_key(key)?;
+        let (base_url, secret) = self
+            .presign
+            .as_ref()
+            .ok_or(StorageError::PresignUnsupported)?;
+        let expires = (SystemTime::now() + expires_in)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Ok(format!(
+            "{base_url}/{}?expires={expires}&signature={}",
+            utf8_percent_encode(key, KEY_ENCODE_SET),
+            sign_url(secret, &method, key, expires)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local_storage() {
+        let dir = std::env::temp_dir().join(format!("poem-storage-{}", std::process::id()));
+        let storage = LocalStorage::new(&dir);
+
+        assert!(storage.head("a/b.txt").await.unwrap().is_none());
+        let meta = storage
+            .put(
+                "a/b.txt",
+                Body::from_string("hello world".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(meta.size, 11);
+        assert!(meta.etag.is_some());
+        assert_eq!(storage.head("a/b.txt").await.unwrap(), Some(meta));
+        assert_eq!(
+            storage.get("a/b.txt").await.unwrap().unwrap(),
+            "hello world"
+        );
+
+        let object = storage
+            .stream("a/b.txt", Some(6..100))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(object.meta.size, 11);
+        assert_eq!(object.body.into_string().await.unwrap(), "world");
+
+        assert!(storage.head("a").await.unwrap().is_none());
+        assert!(storage.get("../b.txt").await.is_err());
+
+        storage.delete("a/b.txt").await.unwrap();
+        storage.delete("a/b.txt").await.unwrap();
+        assert!(storage.get("a/b.txt").await.unwrap().is_none());
+        assert_eq!(std::fs::read_dir(dir.join("a")).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn presigned_url() {
+        let storage = LocalStorage::new("files");
+        assert!(storage
+            .presigned_url("a.txt", Method::GET, Duration::from_secs(60))
+            .await
+            .is_err());
+
+        let storage = storage.presign("https://example.com/files/", b"secret");
+        let url = storage
+            .presigned_url("a b.txt", Method::GET, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(url.starts_with("https://example.com/files/a%20b.txt?expires="));
+        assert!(url.contains("&signature="));
+    }
+}
@@ -0,0 +1,226 @@
+//! Object storage for the uploads and the files served by an application.
+//!
+//! The [`Storage`] trait is implemented by [`LocalStorage`], which stores the
+//! objects in a directory, and by [`S3Storage`] with the `s3` feature, which
+//! stores them in an S3-compatible service.
+//!
+//! The objects are served by the
+//! [`StorageFilesEndpoint`](crate::endpoint::StorageFilesEndpoint), the
+//! multipart fields are stored with
+//! [`Field::store`](crate::web::Field::store), and the
+//! [`StorageTusStore`](crate::endpoint::StorageTusStore) stores the tus
+//! uploads with the `tus` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     endpoint::StorageFilesEndpoint,
+//!     handler,
+//!     http::StatusCode,
+//!     storage::LocalStorage,
+//!     web::{Data, Multipart},
+//!     EndpointExt, Error, Result, Route,
+//! };
+//!
+//! #[handler]
+//! async fn upload(storage: Data<&LocalStorage>, mut multipart: Multipart) -> Result<String> {
+//!     let field = multipart
+//!         .next_field()
+//!         .await?
+//!         .ok_or_else(|| Error::from_status(StatusCode::BAD_REQUEST))?;
+//!     let key = format!("avatars/{}", field.file_name().unwrap_or("avatar"));
+//!     let meta = field.store(*storage, &key).await?;
+//!     Ok(format!("{key}: {} bytes", meta.size))
+//! }
+//!
+//! let storage = LocalStorage::new("uploads");
+//! let app = Route::new()
+//!     .nest("/files", StorageFilesEndpoint::new(storage.clone()))
+//!     .at("/upload", upload)
+//!     .data(storage);
+//! ```
+
+mod local;
+#[cfg(feature = "s3")]
+mod s3;
+
+use std::{ops::Range, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytes::Bytes;
+pub use local::LocalStorage;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use ring::hmac;
+#[cfg(feature = "s3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "s3")))]
+pub use s3::S3Storage;
+
+use crate::{error::StorageError, http::Method, Body, Result};
+
+/// The characters of a key which are percent encoded in a URL.
+pub(crate) const KEY_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// The metadata of an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMeta {
+    /// The size of the object in bytes.
+    pub size: u64,
+    /// The entity tag of the object, a quoted string.
+    pub etag: Option<String>,
+    /// The time the object was last modified.
+    pub last_modified: Option<std::time::SystemTime>,
+    /// The content type of the object.
+    pub content_type: Option<String>,
+}
+
+/// An object returned by [`Storage::stream`].
+#[derive(Debug)]
+pub struct StorageObject {
+    /// The metadata of the whole object.
+    pub meta: ObjectMeta,
+    /// The data of the object, or of the requested range.
+    pub body: Body,
+}
+
+/// Represents an object storage.
+///
+/// The keys are paths separated by `/`, such as `avatars/1.png`, which must
+/// not be empty, start with `/`, or contain `.` or `..` segments.
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    /// Stores the data of `body` with the specified key, replacing the
+    /// object with the same key.
+    async fn put(&self, key: &str, body: Body, content_type: Option<&str>) -> Result<ObjectMeta>;
+
+    /// Returns the metadata of the object, or `None` if it does not exist.
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>>;
+
+    /// Returns the object, or the specified range of bytes of the object, or
+    /// `None` if it does not exist.
+    ///
+    /// The range is truncated to the size of the object.
+    async fn stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<StorageObject>>;
+
+    /// Returns the data of the object, or `None` if it does not exist.
+    async fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        match self.stream(key, None).await? {
+            Some(object) => Ok(Some(object.body.into_bytes().await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the object. Deleting an object which does not exist is not an
+    /// error.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Returns a URL allowing the clients to send a request with `method` to
+    /// the object without credentials, which expires after `expires_in`.
+    async fn presigned_url(
+        &self,
+        key: &str,
+        method: Method,
+        expires_in: Duration,
+    ) -> Result<String>;
+}
+
+#[async_trait::async_trait]
+impl<T: Storage + ?Sized> Storage for std::sync::Arc<T> {
+    async fn put(&self, key: &str, body: Body, content_type: Option<&str>) -> Result<ObjectMeta> {
+        self.as_ref().put(key, body, content_type).await
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>> {
+        self.as_ref().head(key).await
+    }
+
+    async fn stream(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<StorageObject>> {
+        self.as_ref().stream(key, range).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        self.as_ref().get(key).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.as_ref().delete(key).await
+    }
+
+    async fn presigned_url(
+        &self,
+        key: &str,
+        method: Method,
+        expires_in: Duration,
+    ) -> Result<String> {
+        self.as_ref().presigned_url(key, method, expires_in).await
+    }
+}
+
+pub(crate) fn validate_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && !key.contains('\\')
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+/// Returns the signature of a URL presigned by a [`LocalStorage`].
+pub(crate) fn sign_url(secret: &hmac::Key, method: &Method, key: &str, expires: u64) -> String {
+    let tag = hmac::sign(secret, format!("{method}\n{key}\n{expires}").as_bytes());
+    URL_SAFE_NO_PAD.encode(tag.as_ref())
+}
+
+/// Verifies the signature of a URL presigned by a [`LocalStorage`].
+pub(crate) fn verify_url(
+    secret: &hmac::Key,
+    method: &Method,
+    key: &str,
+    expires: u64,
+    signature: &str,
+) -> bool {
+    let signature = match URL_SAFE_NO_PAD.decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    hmac::verify(
+        secret,
+        format!("{method}\n{key}\n{expires}").as_bytes(),
+        &signature,
+    )
+    .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        assert!(validate_key("a.png").is_ok());
+        assert!(validate_key("avatars/1/a.png").is_ok());
+        for key in ["", "/a", "a/", "a//b", "a/../b", "./a", "a\\b"] {
+            assert!(validate_key(key).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn signatures() {
+        let secret = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let signature = sign_url(&secret, &Method::GET, "a.png", 100);
+        assert!(verify_url(&secret, &Method::GET, "a.png", 100, &signature));
+        assert!(!verify_url(&secret, &Method::PUT, "a.png", 100, &signature));
+        assert!(!verify_url(&secret, &Method::GET, "b.png", 100, &signature));
+        assert!(!verify_url(&secret, &Method::GET, "a.png", 200, &signature));
+        assert!(!verify_url(&secret, &Method::GET, "a.png", 100, "!"));
+    }
+}
@@ -0,0 +1,250 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+
+use crate::error::ParseCronError;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron expression with five fields: minute, hour, day of month, month and
+/// day of week, evaluated in UTC.
+///
+/// Each field is `*`, a value, a range `a-b` or a list `a,b`, optionally
+/// followed by a step `/n`. The months and the days of week can also be
+/// written with their three first letters, and `0` or `7` is Sunday. As with
+/// cron, if both the day of month and the day of week are restricted, a day
+/// matches if either of them matches.
+///
+/// The `@yearly`, `@monthly`, `@weekly`, `@daily` and `@hourly` shortcuts are
+/// supported.
+///
+/// # Example
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use poem::tasks::CronExpr;
+///
+/// // at 9:30 on weekdays
+/// let expr: CronExpr = "30 9 * * mon-fri".parse().unwrap();
+/// let friday = Utc.with_ymd_and_hms(2023, 6, 2, 10, 0, 0).unwrap();
+/// assert_eq!(
+///     expr.next_after(friday),
+///     Some(Utc.with_ymd_and_hms(2023, 6, 5, 9, 30, 0).unwrap())
+/// );
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for CronExpr {
+    type Err = ParseCronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = match s.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            s => s,
+        };
+        let err = || ParseCronError(s.to_string());
+
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(err());
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7, &WEEKDAYS).ok_or_else(err)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59, &[]).ok_or_else(err)?,
+            hours: parse_field(fields[1], 0, 23, &[]).ok_or_else(err)?,
+            days: parse_field(fields[2], 1, 31, &[]).ok_or_else(err)?,
+            months: parse_field(fields[3], 1, 12, &MONTHS).ok_or_else(err)?,
+            weekdays,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*'),
+        })
+    }
+}
+
+/// Parses a field to a bit set of the allowed values.
+///
+/// The names are the values from `min`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Option<u64> {
+    let parse_value = |s: &str| -> Option<u32> {
+        let value = match names.iter().position(|name| name.eq_ignore_ascii_case(s)) {
+            Some(idx) => idx as u32 + min,
+            None => s.parse().ok()?,
+        };
+        (min..=max).contains(&value).then_some(value)
+    };
+
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+                // `a/n` is `a-max/n`
+                None if step > 1 => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+impl CronExpr {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// Returns the first time matching this expression strictly after `time`,
+    /// or `None` if there is none in the next five years, such as for
+    /// February 30.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = time.naive_utc().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut t = start;
+
+        while t.year() <= start.year() + 5 {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.matches_day(t.date()) {
+                t = (t.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << t.hour()) == 0 {
+                t = NaiveDateTime::new(t.date(), t.time().with_minute(0)?) + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+                continue;
+            }
+            return Some(Utc.from_utc_datetime(&t));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(expr: &str, time: (i32, u32, u32, u32, u32)) -> Option<DateTime<Utc>> {
+        let (y, m, d, h, min) = time;
+        expr.parse::<CronExpr>()
+            .unwrap()
+            .next_after(Utc.with_ymd_and_hms(y, m, d, h, min, 30).unwrap())
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap())
+    }
+
+    #[test]
+    fn parse() {
+        for expr in [
+            "* * * * *",
+            "*/5 0-6,22 1,15 jan-mar/2 SUN",
+            "5/15 * * * 7",
+            "@daily",
+        ] {
+            assert!(expr.parse::<CronExpr>().is_ok(), "{expr}");
+        }
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * foo *",
+        ] {
+            assert_eq!(
+                expr.parse::<CronExpr>(),
+                Err(ParseCronError(expr.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn next_after() {
+        assert_eq!(next("* * * * *", (2023, 1, 1, 0, 0)), at(2023, 1, 1, 0, 1));
+        assert_eq!(
+            next("*/5 * * * *", (2023, 1, 1, 0, 59)),
+            at(2023, 1, 1, 1, 0)
+        );
+        assert_eq!(
+            next("5/15 * * * *", (2023, 1, 1, 0, 21)),
+            at(2023, 1, 1, 0, 35)
+        );
+        assert_eq!(
+            next("0 0 1 1 *", (2023, 6, 15, 12, 0)),
+            at(2024, 1, 1, 0, 0)
+        );
+        assert_eq!(
+            next("0 12 29 feb *", (2023, 1, 1, 0, 0)),
+            at(2024, 2, 29, 12, 0)
+        );
+        assert_eq!(next("0 0 30 2 *", (2023, 1, 1, 0, 0)), None);
+
+        // 2023-06-02 is a Friday
+        assert_eq!(
+            next("30 9 * * mon-fri", (2023, 6, 2, 10, 0)),
+            at(2023, 6, 5, 9, 30)
+        );
+        assert_eq!(next("0 0 * * 7", (2023, 6, 2, 10, 0)), at(2023, 6, 4, 0, 0));
+
+        // the day of month or the day of week
+        assert_eq!(
+            next("0 0 10 * fri", (2023, 6, 3, 0, 0)),
+            at(2023, 6, 9, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 5 * fri", (2023, 6, 3, 0, 0)),
+            at(2023, 6, 5, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,503 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    future::Future,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use futures_util::future::BoxFuture;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::sync::Notify;
+
+use super::Tasks;
+use crate::{error::InternalServerError, Result};
+
+/// A job stored in a [`JobStorage`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredJob {
+    /// The serialized job.
+    pub payload: Value,
+    /// The number of failed attempts.
+    pub attempts: u32,
+    /// The error of the last failed attempt.
+    pub last_error: Option<String>,
+}
+
+/// Represents a back-end storage for a [`JobQueue`].
+///
+/// A job returned by [`pop`](JobStorage::pop) is owned by the worker, which
+/// pushes it again if it must be retried. A storage which must not lose the
+/// jobs of a crashed process can lease them instead, and make them due again
+/// if they are not pushed or popped back within some time.
+#[async_trait::async_trait]
+pub trait JobStorage: Send + Sync {
+    /// Adds a job which is due at `run_at`.
+    async fn push(&self, job: StoredJob, run_at: SystemTime) -> Result<()>;
+
+    /// Removes and returns the job which is the most overdue at `now`, if
+    /// any.
+    async fn pop(&self, now: SystemTime) -> Result<Option<StoredJob>>;
+}
+
+/// A job storage using memory.
+///
+/// The pending jobs are lost when the process exits.
+#[derive(Default)]
+pub struct MemoryJobStorage {
+    jobs: Mutex<BTreeMap<(SystemTime, u64), StoredJob>>,
+    seq: AtomicU64,
+}
+
+impl MemoryJobStorage {
+    /// Create a `MemoryJobStorage`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl JobStorage for MemoryJobStorage {
+    async fn push(&self, job: StoredJob, run_at: SystemTime) -> Result<()> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        self.jobs.lock().insert((run_at, seq), job);
+        Ok(())
+    }
+
+    async fn pop(&self, now: SystemTime) -> Result<Option<StoredJob>> {
+        let mut jobs = self.jobs.lock();
+        let key = match jobs.keys().next() {
+            Some(key) if key.0 <= now => *key,
+            _ => return Ok(None),
+        };
+        Ok(jobs.remove(&key))
+    }
+}
+
+/// A queue of jobs of type `J`, which are processed in the background by the
+/// workers spawned with [`JobQueue::worker`].
+///
+/// The jobs are serialized to JSON in a [`JobStorage`], such as the
+/// [`MemoryJobStorage`] or one backed by Redis or a SQL database. The queue
+/// can be shared with the handlers with the [`Data`](crate::web::Data)
+/// extractor.
+///
+/// A job which fails is retried with an exponential backoff, and is passed to
+/// the [dead letter hook](JobWorker::on_dead_letter) after the last attempt.
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{
+///     handler, listener::TcpListener, tasks::{JobQueue, MemoryJobStorage}, web::Data,
+///     EndpointExt, Result, Route, Server,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Email {
+///     to: String,
+/// }
+///
+/// # async fn send_email(_: Email) -> std::io::Result<()> { Ok(()) }
+/// #[handler]
+/// async fn signup(emails: Data<&JobQueue<Email>>) -> Result<()> {
+///     emails
+///         .enqueue(Email {
+///             to: "user@example.com".to_string(),
+///         })
+///         .await
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let server = Server::new(TcpListener::bind("127.0.0.1:3000"));
+///
+/// let emails = JobQueue::new(MemoryJobStorage::new());
+/// emails
+///     .worker(send_email)
+///     .concurrency(4)
+///     .on_dead_letter(|job| async move {
+///         tracing::error!(payload = %job.payload, "failed to send an email");
+///     })
+///     .spawn(&server.tasks(), "emails");
+///
+/// let app = Route::new().at("/signup", signup).data(emails);
+/// server.run(app).await
+/// # });
+/// ```
+pub struct JobQueue<J> {
+    storage: Arc<dyn JobStorage>,
+    notify: Arc<Notify>,
+    _mark: PhantomData<fn(J)>,
+}
+
+impl<J> Clone for JobQueue<J> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: self.storage.clone(),
+            notify: self.notify.clone(),
+            _mark: PhantomData,
+        }
+    }
+}
+
+impl<J: Serialize + DeserializeOwned + Send + 'static> JobQueue<J> {
+    /// Create a queue with the specified storage.
+    pub fn new(storage: impl JobStorage + 'static) -> Self {
+        Self {
+            storage: Arc::new(storage),
+            notify: Default::default(),
+            _mark: PhantomData,
+        }
+    }
+
+    /// Adds a job to the queue.
+    pub async fn enqueue(&self, job: J) -> Result<()> {
+        self.enqueue_at(job, SystemTime::now()).await
+    }
+
+    /// Adds a job to the queue, which is processed after `delay`.
+    pub async fn enqueue_in(&self, job: J, delay: Duration) -> Result<()> {
+        self.enqueue_at(job, SystemTime::now() + delay).await
+    }
+
+    async fn enqueue_at(&self, job: J, run_at: SystemTime) -> Result<()> {
+        let job = StoredJob {
+            payload: serde_json::to_value(job).map_err(InternalServerError)?,
+            attempts: 0,
+            last_error: None,
+        };
+        self.storage.push(job, run_at).await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Returns a builder for workers which call `f` for each job.
+    pub fn worker<F, Fut, E>(&self, f: F) -> JobWorker<J, F>
+    where
+        F: Fn(J) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display + Send + 'static,
+    {
+        JobWorker {
+            queue: self.clone(),
+            f,
+            concurrency: 1,
+            max_attempts: 5,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60 * 60),
+            poll_interval: Duration::from_secs(1),
+            dead_letter: None,
+        }
+    }
+}
+
+type DeadLetterHook = Arc<dyn Fn(StoredJob) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A builder for the workers of a [`JobQueue`], returned by
+/// [`JobQueue::worker`].
+pub struct JobWorker<J, F> {
+    queue: JobQueue<J>,
+    f: F,
+    concurrency: usize,
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    poll_interval: Duration,
+    dead_letter: Option<DeadLetterHook>,
+}
+
+impl<J, F, Fut, E> JobWorker<J, F>
+where
+    J: Serialize + DeserializeOwned + Send + 'static,
+    F: Fn(J) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Display + Send + 'static,
+{
+    /// Sets the number of jobs processed at the same time.
+    ///
+    /// Default is `1`.
+    #[must_use]
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency,
+            ..self
+        }
+    }
+
+    /// Sets the number of attempts to process a job before it is passed to
+    /// the dead letter hook.
+    ///
+    /// Default is `5`.
+    #[must_use]
+    pub fn max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Sets the delay before the first retry, which doubles on each retry up
+    /// to `max`.
+    ///
+    /// Default is `1s` doubling up to `1h`.
+    #[must_use]
+    pub fn backoff(self, initial: Duration, max: Duration) -> Self {
+        Self {
+            backoff: initial,
+            max_backoff: max,
+            ..self
+        }
+    }
+
+    /// Sets the interval at which an idle worker checks the storage for the
+    /// delayed jobs and the jobs added by other processes.
+    ///
+    /// Default is `1s`.
+    #[must_use]
+    pub fn poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    /// Sets a function which is called with the jobs which failed on their
+    /// last attempt, or could not be deserialized.
+    ///
+    /// By default, these jobs are logged and dropped.
+    #[must_use]
+    pub fn on_dead_letter<H, HFut>(self, hook: H) -> Self
+    where
+        H: Fn(StoredJob) -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            dead_letter: Some(Arc::new(move |job| Box::pin(hook(job)))),
+            ..self
+        }
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+
+    /// Processes a job, and pushes it again if it must be retried.
+    async fn process(&self, mut job: StoredJob) -> Result<()> {
+        let (err, retry) = match serde_json::from_value::<J>(job.payload.clone()) {
+            Ok(payload) => match tokio::spawn((self.f)(payload)).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(err)) => (err.to_string(), true),
+                Err(_) => ("the job panicked".to_string(), true),
+            },
+            Err(err) => (format!("failed to deserialize the job: {}", err), false),
+        };
+
+        job.attempts = job.attempts.saturating_add(1);
+        job.last_error = Some(err);
+        if retry && job.attempts < self.max_attempts {
+            let delay = self.retry_delay(job.attempts);
+            tracing::warn!(
+                attempts = job.attempts,
+                error = job.last_error.as_deref().unwrap_or_default(),
+                "job failed, retrying in {:?}",
+                delay
+            );
+            self.queue
+                .storage
+                .push(job, SystemTime::now() + delay)
+                .await?;
+        } else {
+            tracing::error!(
+                attempts = job.attempts,
+                error = job.last_error.as_deref().unwrap_or_default(),
+                "job failed"
+            );
+            if let Some(hook) = &self.dead_letter {
+                hook(job).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawns the workers as tasks named `name`.
+    ///
+    /// When the shutdown signal is triggered, the workers finish the jobs
+    /// they are processing, and the pending jobs are left in the storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the concurrency is zero.
+    pub fn spawn(self, tasks: &Tasks, name: impl Into<String>) {
+        assert!(self.concurrency > 0, "the concurrency must not be zero");
+
+        let name = name.into();
+        let concurrency = self.concurrency;
+        let worker = Arc::new(self);
+        for idx in 0..concurrency {
+            let worker = worker.clone();
+            let name = if concurrency > 1 {
+                format!("{}#{}", name, idx)
+            } else {
+                name.clone()
+            };
+            tasks.spawn(name, move |signal| async move {
+                while !signal.is_shutting_down() {
+                    match worker.queue.storage.pop(SystemTime::now()).await {
+                        Ok(Some(job)) => {
+                            if let Err(err) = worker.process(job).await {
+                                tracing::error!(error = %err, "failed to retry a job");
+                            }
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(err) => tracing::error!(error = %err, "failed to fetch a job"),
+                    }
+                    tokio::select! {
+                        _ = worker.queue.notify.notified() => {}
+                        _ = tokio::time::sleep(worker.poll_interval) => {}
+                        _ = signal.wait() => {}
+                    }
+                }
+                Ok::<_, std::convert::Infallible>(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use tokio::sync::{mpsc, watch};
+
+    use super::*;
+    use crate::ShutdownSignal;
+
+    fn tasks() -> (watch::Sender<bool>, Tasks) {
+        let (tx, rx) = watch::channel(false);
+        (tx, Tasks::new(ShutdownSignal { rx }))
+    }
+
+    #[tokio::test]
+    async fn memory_storage() {
+        let storage = MemoryJobStorage::new();
+        let now = SystemTime::now();
+        let job = |n: i32| StoredJob {
+            payload: n.into(),
+            attempts: 0,
+            last_error: None,
+        };
+
+        storage.push(job(1), now).await.unwrap();
+        storage
+            .push(job(2), now + Duration::from_secs(10))
+            .await
+            .unwrap();
+        storage.push(job(3), now).await.unwrap();
+
+        assert_eq!(storage.pop(now).await.unwrap(), Some(job(1)));
+        assert_eq!(storage.pop(now).await.unwrap(), Some(job(3)));
+        assert_eq!(storage.pop(now).await.unwrap(), None);
+        assert_eq!(
+            storage.pop(now + Duration::from_secs(10)).await.unwrap(),
+            Some(job(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn process_jobs() {
+        let (shutdown, tasks) = tasks();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let queue = JobQueue::<String>::new(MemoryJobStorage::new());
+        queue
+            .worker(move |job| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(job).unwrap();
+                    Ok::<_, std::convert::Infallible>(())
+                }
+            })
+            .concurrency(2)
+            .spawn(&tasks, "jobs");
+
+        queue.enqueue("a".to_string()).await.unwrap();
+        queue.enqueue("b".to_string()).await.unwrap();
+        let mut jobs = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        jobs.sort();
+        assert_eq!(jobs, ["a", "b"]);
+
+        let names = tasks
+            .status()
+            .into_iter()
+            .map(|status| status.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["jobs#0", "jobs#1"]);
+
+        shutdown.send_replace(true);
+        tasks.join().await;
+        assert!(tasks.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn retry_and_dead_letter() {
+        let (shutdown, tasks) = tasks();
+        let attempts = Arc::new(AtomicU32::new(0));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let queue = JobQueue::<i32>::new(MemoryJobStorage::new());
+        queue
+            .worker({
+                let attempts = attempts.clone();
+                move |_| {
+                    let n = attempts.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if n == 1 {
+                            panic!("boom");
+                        }
+                        Err("boom")
+                    }
+                }
+            })
+            .max_attempts(3)
+            .backoff(Duration::from_millis(10), Duration::from_millis(20))
+            .poll_interval(Duration::from_millis(10))
+            .on_dead_letter(move |job| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(job).unwrap();
+                }
+            })
+            .spawn(&tasks, "jobs");
+
+        queue.enqueue(42).await.unwrap();
+        let job = rx.recv().await.unwrap();
+        assert_eq!(job.payload, 42);
+        assert_eq!(job.attempts, 3);
+        assert_eq!(job.last_error.as_deref(), Some("boom"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        shutdown.send_replace(true);
+        tasks.join().await;
+    }
+
+    #[test]
+    fn retry_delay() {
+        let queue = JobQueue::<i32>::new(MemoryJobStorage::new());
+        let worker = queue
+            .worker(|_| async { Ok::<_, std::convert::Infallible>(()) })
+            .backoff(Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(worker.retry_delay(1), Duration::from_secs(1));
+        assert_eq!(worker.retry_delay(2), Duration::from_secs(2));
+        assert_eq!(worker.retry_delay(4), Duration::from_secs(8));
+        assert_eq!(worker.retry_delay(5), Duration::from_secs(10));
+        assert_eq!(worker.retry_delay(100), Duration::from_secs(10));
+    }
+}
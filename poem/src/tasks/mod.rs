@@ -0,0 +1,388 @@
+//! Background tasks which are stopped with the server.
+//!
+//! The [`Tasks`] of a server is obtained with
+//! [`Server::tasks`](crate::Server::tasks). The tasks
+//! receive the [`ShutdownSignal`] of the server, and the server waits for
+//! them to finish after the connections have been closed, within the
+//! graceful shutdown timeout.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use poem::{
+//!     handler, listener::TcpListener, tasks::TaskQueue, web::Data, EndpointExt, Result, Route,
+//!     Server,
+//! };
+//!
+//! #[handler]
+//! async fn signup(mailer: Data<&TaskQueue<String>>) -> Result<()> {
+//!     mailer.enqueue("welcome@example.com".to_string()).await?;
+//!     Ok(())
+//! }
+//!
+//! # async fn send_mail(_: String) -> std::io::Result<()> { Ok(()) }
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let server = Server::new(TcpListener::bind("127.0.0.1:3000"));
+//! let tasks = server.tasks();
+//!
+//! let mailer = tasks.spawn_queue("mailer", 100, send_mail);
+//! tasks.spawn("cleanup", |signal| async move {
+//!     while !signal.is_shutting_down() {
+//!         // remove the expired rows...
+//!         tokio::select! {
+//!             _ = tokio::time::sleep(std::time::Duration::from_secs(60)) => {}
+//!             _ = signal.wait() => {}
+//!         }
+//!     }
+//!     Ok::<_, std::io::Error>(())
+//! });
+//!
+//! let app = Route::new()
+//!     .at("/signup", signup)
+//!     .at("/health", tasks.health_endpoint())
+//!     .data(mailer);
+//! server.run(app).await
+//! # });
+//! ```
+
+#[cfg(feature = "cron")]
+mod cron;
+mod job_queue;
+#[cfg(feature = "cron")]
+mod schedule;
+
+use std::{fmt::Display, future::Future, sync::Arc};
+
+use http::StatusCode;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+pub use self::job_queue::{JobQueue, JobStorage, JobWorker, MemoryJobStorage, StoredJob};
+#[cfg(feature = "cron")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cron")))]
+pub use self::{
+    cron::CronExpr,
+    schedule::{JobStats, Schedule, ScheduleHandle},
+};
+use crate::{
+    error::TaskQueueError, Endpoint, IntoResponse, Request, Response, Result, ShutdownSignal,
+};
+
+/// The state of a background task.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "snake_case")]
+pub enum TaskState {
+    /// The task is running.
+    Running,
+    /// The task has returned successfully.
+    Finished,
+    /// The task has returned an error.
+    Failed(String),
+    /// The task has panicked.
+    Panicked,
+}
+
+/// The name and state of a background task.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct TaskStatus {
+    /// The name of the task.
+    pub name: String,
+    /// The state of the task.
+    #[serde(flatten)]
+    pub state: TaskState,
+}
+
+struct Task {
+    status: Arc<Mutex<TaskStatus>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// A set of named background tasks, which share a [`ShutdownSignal`].
+#[derive(Clone)]
+pub struct Tasks {
+    signal: ShutdownSignal,
+    tasks: Arc<Mutex<Vec<Task>>>,
+}
+
+impl Tasks {
+    /// Create a set of tasks which are stopped with the specified signal.
+    ///
+    /// Use [`Server::tasks`](crate::Server::tasks) to get the tasks which are
+    /// stopped with a server.
+    pub fn new(signal: ShutdownSignal) -> Self {
+        Self {
+            signal,
+            tasks: Default::default(),
+        }
+    }
+
+    /// Spawns a task, which receives the shutdown signal and should return
+    /// soon after it is triggered.
+    pub fn spawn<F, Fut, E>(&self, name: impl Into<String>, f: F)
+    where
+        F: FnOnce(ShutdownSignal) -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display + Send + 'static,
+    {
+        let name = name.into();
+        let status = Arc::new(Mutex::new(TaskStatus {
+            name: name.clone(),
+            state: TaskState::Running,
+        }));
+        let task = tokio::spawn(f(self.signal.clone()));
+        let handle = tokio::spawn({
+            let status = status.clone();
+            async move {
+                let state = match task.await {
+                    Ok(Ok(())) => TaskState::Finished,
+                    Ok(Err(err)) => {
+                        tracing::error!(task = %name, error = %err, "background task failed");
+                        TaskState::Failed(err.to_string())
+                    }
+                    Err(_) => {
+                        tracing::error!(task = %name, "background task panicked");
+                        TaskState::Panicked
+                    }
+                };
+                status.lock().state = state;
+            }
+        });
+
+        self.tasks.lock().push(Task {
+            status,
+            handle: Some(handle),
+        });
+    }
+
+    /// Spawns a task which calls `f` for each job sent to the returned
+    /// queue, one at a time.
+    ///
+    /// The queue holds up to `capacity` jobs. When the shutdown signal is
+    /// triggered, it stops accepting jobs and the task returns once the
+    /// pending ones have been processed. The errors returned by `f` are
+    /// logged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn spawn_queue<T, F, Fut, E>(
+        &self,
+        name: impl Into<String>,
+        capacity: usize,
+        f: F,
+    ) -> TaskQueue<T>
+    where
+        T: Send + 'static,
+        F: Fn(T) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display + Send + 'static,
+    {
+        let name = name.into();
+        let (tx, mut rx) = mpsc::channel(capacity);
+
+        self.spawn(name.clone(), move |signal| async move {
+            loop {
+                let job = tokio::select! {
+                    job = rx.recv() => job,
+                    _ = signal.wait() => {
+                        rx.close();
+                        rx.recv().await
+                    }
+                };
+                match job {
+                    Some(job) => {
+                        if let Err(err) = f(job).await {
+                            tracing::error!(task = %name, error = %err, "failed to process job");
+                        }
+                    }
+                    None => return Ok::<_, std::convert::Infallible>(()),
+                }
+            }
+        });
+
+        TaskQueue { tx }
+    }
+
+    /// Returns the status of all the tasks.
+    pub fn status(&self) -> Vec<TaskStatus> {
+        self.tasks
+            .lock()
+            .iter()
+            .map(|task| task.status.lock().clone())
+            .collect()
+    }
+
+    /// Returns `true` if none of the tasks has failed or panicked.
+    pub fn is_healthy(&self) -> bool {
+        self.status()
+            .iter()
+            .all(|status| matches!(status.state, TaskState::Running | TaskState::Finished))
+    }
+
+    /// Returns an endpoint for health checks, which responds with the status
+    /// of the tasks as JSON, and `503 Service Unavailable` if any of them has
+    /// failed or panicked.
+    pub fn health_endpoint(&self) -> TasksHealthEndpoint {
+        TasksHealthEndpoint {
+            tasks: self.clone(),
+        }
+    }
+
+    /// Waits for all the tasks to return.
+    pub async fn join(&self) {
+        let handles = self
+            .tasks
+            .lock()
+            .iter_mut()
+            .filter_map(|task| task.handle.take())
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+/// A handle for sending jobs to a task spawned with [`Tasks::spawn_queue`].
+///
+/// It can be shared with the handlers with the [`Data`](crate::web::Data)
+/// extractor.
+pub struct TaskQueue<T> {
+    tx: mpsc::Sender<T>,
+}
+
+impl<T> Clone for TaskQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<T> TaskQueue<T> {
+    /// Sends a job, waiting while the queue is full.
+    pub async fn enqueue(&self, job: T) -> Result<(), TaskQueueError> {
+        self.tx.send(job).await.map_err(|_| TaskQueueError::Closed)
+    }
+
+    /// Sends a job if the queue is not full.
+    pub fn try_enqueue(&self, job: T) -> Result<(), TaskQueueError> {
+        self.tx.try_send(job).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => TaskQueueError::Full,
+            mpsc::error::TrySendError::Closed(_) => TaskQueueError::Closed,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct HealthReport {
+    healthy: bool,
+    tasks: Vec<TaskStatus>,
+}
+
+/// An endpoint responding with the status of the tasks, returned by
+/// [`Tasks::health_endpoint`].
+pub struct TasksHealthEndpoint {
+    tasks: Tasks,
+}
+
+#[async_trait::async_trait]
+impl Endpoint for TasksHealthEndpoint {
+    type Output = Response;
+
+    async fn call(&self, _req: Request) -> Result<Self::Output> {
+        let report = HealthReport {
+            healthy: self.tasks.is_healthy(),
+            tasks: self.tasks.status(),
+        };
+        let status = if report.healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        Ok(crate::web::Json(report).with_status(status).into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::test::TestClient;
+
+    fn signal() -> (watch::Sender<bool>, ShutdownSignal) {
+        let (tx, rx) = watch::channel(false);
+        (tx, ShutdownSignal { rx })
+    }
+
+    #[tokio::test]
+    async fn health() {
+        let (shutdown, signal) = signal();
+        let tasks = Tasks::new(signal);
+        tasks.spawn("worker", |signal| async move {
+            signal.wait().await;
+            Ok::<_, std::io::Error>(())
+        });
+
+        let cli = TestClient::new(tasks.health_endpoint());
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_json(json!({
+            "healthy": true,
+            "tasks": [{"name": "worker", "state": "running"}],
+        }))
+        .await;
+
+        tasks.spawn("failing", |_| async { Err("boom") });
+        tasks.spawn("panicking", |_| async {
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok::<_, String>(())
+        });
+        shutdown.send_replace(true);
+        tasks.join().await;
+
+        let resp = cli.get("/").send().await;
+        resp.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        resp.assert_json(json!({
+            "healthy": false,
+            "tasks": [
+                {"name": "worker", "state": "finished"},
+                {"name": "failing", "state": "failed", "error": "boom"},
+                {"name": "panicking", "state": "panicked"},
+            ],
+        }))
+        .await;
+    }
+
+    #[tokio::test]
+    async fn queue() {
+        let (shutdown, signal) = signal();
+        let tasks = Tasks::new(signal);
+        let processed = Arc::new(AtomicUsize::new(0));
+        let queue = tasks.spawn_queue("queue", 2, {
+            let processed = processed.clone();
+            move |n: usize| {
+                let processed = processed.clone();
+                async move {
+                    processed.fetch_add(n, Ordering::SeqCst);
+                    Ok::<_, std::io::Error>(())
+                }
+            }
+        });
+
+        queue.enqueue(1).await.unwrap();
+        queue.try_enqueue(2).unwrap();
+        shutdown.send_replace(true);
+        tasks.join().await;
+
+        // the pending jobs are processed before the task returns
+        assert_eq!(processed.load(Ordering::SeqCst), 3);
+        assert_eq!(queue.try_enqueue(3), Err(TaskQueueError::Closed));
+        assert_eq!(tasks.status()[0].state, TaskState::Finished);
+    }
+}
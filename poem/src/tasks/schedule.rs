@@ -0,0 +1,404 @@
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use super::{CronExpr, Tasks};
+use crate::{error::ParseCronError, lock::Lock};
+
+/// How long the lock of a run is held after the jitter.
+const LOCK_TTL: Duration = Duration::from_secs(60);
+
+type BoxJob = Box<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// The metrics of a job of a [`Schedule`].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct JobStats {
+    /// The cron expression of the job.
+    pub schedule: String,
+    /// The number of completed runs.
+    pub runs: u64,
+    /// The number of runs which returned an error or panicked.
+    pub failures: u64,
+    /// The number of runs skipped because the previous one was still running.
+    pub skipped: u64,
+    /// Whether the job is running.
+    pub running: bool,
+    /// The start time of the last completed run.
+    pub last_run: Option<DateTime<Utc>>,
+    /// The duration of the last completed run.
+    pub last_duration: Option<Duration>,
+}
+
+struct Job {
+    expr: CronExpr,
+    f: BoxJob,
+    stats: Arc<Mutex<JobStats>>,
+}
+
+impl Job {
+    /// Starts a run, unless the previous one is still running.
+    fn fire(&self) -> Option<JoinHandle<()>> {
+        {
+            let mut stats = self.stats.lock();
+            if stats.running {
+                stats.skipped += 1;
+                tracing::warn!(
+                    schedule = %stats.schedule,
+                    "skipped a scheduled job because the previous run is still running"
+                );
+                return None;
+            }
+            stats.running = true;
+        }
+
+        let fut = (self.f)();
+        let stats = self.stats.clone();
+        Some(tokio::spawn(async move {
+            let started_at = Utc::now();
+            let start = Instant::now();
+            let res = tokio::spawn(fut).await;
+
+            let mut stats = stats.lock();
+            stats.running = false;
+            stats.runs += 1;
+            stats.last_run = Some(started_at);
+            stats.last_duration = Some(start.elapsed());
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    stats.failures += 1;
+                    tracing::error!(schedule = %stats.schedule, error = %err, "scheduled job failed");
+                }
+                Err(_) => {
+                    stats.failures += 1;
+                    tracing::error!(schedule = %stats.schedule, "scheduled job panicked");
+                }
+            }
+        }))
+    }
+}
+
+/// A set of async jobs which run on cron expressions.
+///
+/// See [`CronExpr`] for the syntax of the expressions, which are evaluated in
+/// UTC. A run is skipped if the previous run of the same job is still
+/// running, and each run can be delayed by a random [jitter](Schedule::jitter)
+/// so that several instances of an application do not all start at once.
+/// With a [`lock`](Schedule::lock) shared by the instances, each run is
+/// started by only one of them.
+///
+/// The jobs are spawned as [`Tasks`], usually the ones of the server, and stop
+/// being scheduled when the server shuts down, which waits for the current
+/// runs to finish.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use poem::{listener::TcpListener, tasks::Schedule, Route, Server};
+///
+/// # async fn purge_sessions() -> std::io::Result<()> { Ok(()) }
+/// # async fn send_report() -> std::io::Result<()> { Ok(()) }
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let server = Server::new(TcpListener::bind("127.0.0.1:3000"));
+///
+/// let schedule = Schedule::new()
+///     .every("*/5 * * * *", purge_sessions)
+///     .every("0 8 * * mon", send_report)
+///     .jitter(Duration::from_secs(30))
+///     .spawn(&server.tasks());
+///
+/// server.run(Route::new()).await
+/// # });
+/// ```
+#[derive(Default)]
+pub struct Schedule {
+    jobs: Vec<Job>,
+    jitter: Duration,
+    lock: Option<Arc<dyn Lock>>,
+}
+
+impl Schedule {
+    /// Create an empty schedule.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a job which runs on the cron expression `expr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `expr` is not a valid cron expression.
+    #[must_use]
+    pub fn every<F, Fut, E>(self, expr: &str, job: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        match self.try_every(expr, job) {
+            Ok(schedule) => schedule,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Adds a job which runs on the cron expression `expr`, or returns an
+    /// error if it is not valid.
+    pub fn try_every<F, Fut, E>(mut self, expr: &str, job: F) -> Result<Self, ParseCronError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display,
+    {
+        let cron = expr.parse()?;
+        self.jobs.push(Job {
+            expr: cron,
+            f: Box::new(move || {
+                let fut = job();
+                Box::pin(async move { fut.await.map_err(|err| err.to_string()) })
+            }),
+            stats: Arc::new(Mutex::new(JobStats {
+                schedule: expr.to_string(),
+                runs: 0,
+                failures: 0,
+                skipped: 0,
+                running: false,
+                last_run: None,
+                last_duration: None,
+            })),
+        });
+        Ok(self)
+    }
+
+    /// Delays each run by a random duration up to `jitter`.
+    ///
+    /// Default is no jitter.
+    #[must_use]
+    pub fn jitter(self, jitter: Duration) -> Self {
+        Self { jitter, ..self }
+    }
+
+    /// Starts each run only if the lock with the key
+    /// `schedule:{index of the job}:{timestamp of the run}` is acquired, so
+    /// that the instances of an application sharing the lock do not start the
+    /// same run.
+    ///
+    /// The locks are not released, and expire after the jitter plus one
+    /// minute.
+    #[must_use]
+    pub fn lock(self, lock: impl Lock + 'static) -> Self {
+        Self {
+            lock: Some(Arc::new(lock)),
+            ..self
+        }
+    }
+
+    /// Spawns a task for each job, and returns a handle for reading their
+    /// metrics.
+    pub fn spawn(self, tasks: &Tasks) -> ScheduleHandle {
+        let handle = ScheduleHandle {
+            stats: self.jobs.iter().map(|job| job.stats.clone()).collect(),
+        };
+
+        for (index, job) in self.jobs.into_iter().enumerate() {
+            let jitter = self.jitter;
+            let lock = self.lock.clone();
+            let name = format!("schedule: {}", job.stats.lock().schedule);
+            tasks.spawn(name, move |signal| async move {
+                let mut after = Utc::now();
+                let mut last_run = None;
+                while let Some(next) = job.expr.next_after(after.max(Utc::now())) {
+                    let delay = (next - Utc::now()).to_std().unwrap_or_default();
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay + random_delay(jitter)) => {}
+                        _ = signal.wait() => break,
+                    }
+                    if acquire_run(lock.as_deref(), index, next, jitter).await {
+                        if let Some(run) = job.fire() {
+                            last_run = Some(run);
+                        }
+                    }
+                    after = next;
+                }
+                if let Some(run) = last_run {
+                    let _ = run.await;
+                }
+                Ok::<_, Infallible>(())
+            });
+        }
+
+        handle
+    }
+}
+
+/// Returns `true` if the run is not started by another instance sharing the
+/// lock.
+async fn acquire_run(
+    lock: Option<&dyn Lock>,
+    index: usize,
+    at: DateTime<Utc>,
+    jitter: Duration,
+) -> bool {
+    let lock = match lock {
+        Some(lock) => lock,
+        None => return true,
+    };
+    let key = format!("schedule:{index}:{}", at.timestamp());
+    match lock.try_acquire(&key, jitter + LOCK_TTL).await {
+        Ok(token) => token.is_some(),
+        Err(err) => {
+            tracing::error!(
+                key = %key,
+                error = %err,
+                "failed to acquire the lock of a scheduled job"
+            );
+            false
+        }
+    }
+}
+
+fn random_delay(jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return Duration::ZERO;
+    }
+    rand::thread_rng().gen_range(Duration::ZERO..=jitter)
+}
+
+/// A handle for reading the metrics of the jobs of a [`Schedule`], returned
+/// by [`Schedule::spawn`].
+#[derive(Clone)]
+pub struct ScheduleHandle {
+    stats: Vec<Arc<Mutex<JobStats>>>,
+}
+
+impl ScheduleHandle {
+    /// Returns the metrics of all the jobs, in the order they were added.
+    pub fn stats(&self) -> Vec<JobStats> {
+        self.stats
+            .iter()
+            .map(|stats| stats.lock().clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::sync::{watch, Semaphore};
+
+    use super::*;
+    use crate::ShutdownSignal;
+
+    #[tokio::test]
+    async fn overlap() {
+        let gate = Arc::new(Semaphore::new(0));
+        let schedule = Schedule::new().every("* * * * *", {
+            let gate = gate.clone();
+            move || {
+                let gate = gate.clone();
+                async move {
+                    let _ = gate.acquire().await;
+                    Ok::<_, Infallible>(())
+                }
+            }
+        });
+        let job = &schedule.jobs[0];
+
+        let run = job.fire().unwrap();
+        assert!(job.fire().is_none());
+        assert!(job.stats.lock().running);
+
+        gate.add_permits(1);
+        run.await.unwrap();
+        let stats = job.stats.lock().clone();
+        assert_eq!(stats.schedule, "* * * * *");
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(stats.skipped, 1);
+        assert!(!stats.running);
+        assert!(stats.last_run.is_some());
+        assert!(stats.last_duration.is_some());
+    }
+
+    #[tokio::test]
+    async fn failures() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let schedule = Schedule::new().every("@hourly", {
+            let count = count.clone();
+            move || {
+                let n = count.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match n {
+                        0 => Err("boom"),
+                        1 => panic!("boom"),
+                        _ => Ok(()),
+                    }
+                }
+            }
+        });
+        let job = &schedule.jobs[0];
+
+        for _ in 0..3 {
+            job.fire().unwrap().await.unwrap();
+        }
+        let stats = job.stats.lock().clone();
+        assert_eq!(stats.runs, 3);
+        assert_eq!(stats.failures, 2);
+    }
+
+    #[tokio::test]
+    async fn lock() {
+        let lock = crate::lock::MemoryLock::new();
+        let at = Utc::now();
+        let jitter = Duration::from_secs(5);
+
+        assert!(acquire_run(None, 0, at, jitter).await);
+        assert!(acquire_run(None, 0, at, jitter).await);
+        assert!(acquire_run(Some(&lock), 0, at, jitter).await);
+        assert!(!acquire_run(Some(&lock), 0, at, jitter).await);
+        assert!(acquire_run(Some(&lock), 1, at, jitter).await);
+        assert!(acquire_run(Some(&lock), 0, at + chrono::Duration::minutes(1), jitter).await);
+    }
+
+    #[test]
+    fn invalid_expr() {
+        assert_eq!(
+            Schedule::new()
+                .try_every("61 * * * *", || async { Ok::<_, Infallible>(()) })
+                .err(),
+            Some(ParseCronError("61 * * * *".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_on_shutdown() {
+        let (shutdown, rx) = watch::channel(false);
+        let tasks = Tasks::new(ShutdownSignal { rx });
+        let handle = Schedule::new()
+            .every("* * * * *", || async { Ok::<_, Infallible>(()) })
+            .every("@daily", || async { Ok::<_, Infallible>(()) })
+            .spawn(&tasks);
+
+        let stats = handle.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[1].schedule, "@daily");
+
+        shutdown.send_replace(true);
+        tokio::time::timeout(Duration::from_secs(5), tasks.join())
+            .await
+            .unwrap();
+    }
+}
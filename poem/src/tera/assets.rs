@@ -0,0 +1,123 @@
+use std::{collections::HashMap, sync::Arc};
+
+/// The fingerprinted URLs of the static assets, used by the
+/// [`asset`](crate::tera::functions::asset) template function.
+///
+/// The manifest maps the logical paths of the assets, such as `app.css`, to
+/// the file names produced by the bundler, such as `app.3f2a1b.css`, which
+/// can be cached forever.
+///
+/// The manifest must be added to the requests with
+/// [`EndpointExt::data`](crate::EndpointExt::data) outside of the
+/// `TeraTemplating` middleware.
+///
+/// # Example
+///
+/// ```
+/// use poem::tera::AssetManifest;
+///
+/// let manifest = AssetManifest::from_json(r#"{ "app.css": "app.3f2a1b.css" }"#)
+///     .unwrap()
+///     .prefix("/static");
+/// assert_eq!(manifest.url("app.css"), "/static/app.3f2a1b.css");
+/// assert_eq!(manifest.url("logo.png"), "/static/logo.png");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AssetManifest {
+    prefix: String,
+    assets: Arc<HashMap<String, String>>,
+    preload: bool,
+}
+
+impl Default for AssetManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssetManifest {
+    /// Create an empty manifest.
+    pub fn new() -> Self {
+        Self {
+            prefix: String::new(),
+            assets: Default::default(),
+            preload: true,
+        }
+    }
+
+    /// Create a manifest from a JSON object mapping the logical paths to the
+    /// fingerprinted paths, such as the manifests written by the bundlers.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            assets: Arc::new(serde_json::from_str(json)?),
+            ..Self::new()
+        })
+    }
+
+    /// Adds the asset `path`, served at `fingerprinted`.
+    #[must_use]
+    pub fn asset(mut self, path: impl Into<String>, fingerprinted: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.assets).insert(path.into(), fingerprinted.into());
+        self
+    }
+
+    /// Sets the URL prefix of the assets, such as `/static`.
+    #[must_use]
+    pub fn prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into().trim_end_matches('/').to_string(),
+            ..self
+        }
+    }
+
+    /// Sets whether the stylesheets and the scripts used by the templates are
+    /// hinted with `Link: rel=preload` headers.
+    ///
+    /// Default is `true`.
+    #[must_use]
+    pub fn preload(self, preload: bool) -> Self {
+        Self { preload, ..self }
+    }
+
+    pub(crate) fn is_preload(&self) -> bool {
+        self.preload
+    }
+
+    /// Returns the URL of the asset `path`, which is not fingerprinted if the
+    /// asset is not in the manifest.
+    pub fn url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        let path = self.assets.get(path).map(String::as_str).unwrap_or(path);
+        format!("{}/{}", self.prefix, path.trim_start_matches('/'))
+    }
+}
+
+/// Returns the `as` attribute of a preload hint for the asset at `url`, only
+/// for the stylesheets and the scripts.
+pub(crate) fn preload_destination(url: &str) -> Option<&'static str> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("css") => Some("style"),
+        Some("js" | "mjs") => Some("script"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest() {
+        let manifest = AssetManifest::new()
+            .asset("app.css", "app.123.css")
+            .prefix("/assets/");
+        assert_eq!(manifest.url("/app.css"), "/assets/app.123.css");
+        assert_eq!(manifest.url("app.js"), "/assets/app.js");
+        assert_eq!(AssetManifest::new().url("app.js"), "/app.js");
+
+        assert_eq!(preload_destination("/app.123.css?v=1"), Some("style"));
+        assert_eq!(preload_destination("/app.mjs"), Some("script"));
+        assert_eq!(preload_destination("/logo.png"), None);
+    }
+}
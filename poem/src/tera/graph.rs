@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use tera::{ast::Node, Tera};
+
+/// The templates a template depends on.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct TemplateDependencies {
+    /// The template extended with `{% extends %}`.
+    pub extends: Option<String>,
+    /// The templates included with `{% include %}`.
+    pub includes: BTreeSet<String>,
+    /// The macro files imported with `{% import %}`.
+    pub imports: BTreeSet<String>,
+}
+
+impl TemplateDependencies {
+    /// Returns the names of all the templates the template depends on.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.extends
+            .iter()
+            .chain(&self.includes)
+            .chain(&self.imports)
+            .map(String::as_str)
+    }
+}
+
+/// The graph of the dependencies between the templates of a [`Tera`]
+/// instance, through `{% extends %}`, `{% include %}` and `{% import %}`.
+///
+/// The live reloading of
+/// [`TeraTemplating`](crate::tera::TeraTemplating) uses it to only re-parse
+/// the templates affected by a change, and the
+/// [`AdminDashboard`](crate::endpoint::AdminDashboard) displays it, so that
+/// the authors see which pages an edit impacts.
+///
+/// # Example
+///
+/// ```
+/// use poem::tera::{TemplateGraph, Tera};
+///
+/// let mut tera = Tera::default();
+/// tera.add_raw_templates(vec![
+///     ("base.html", "<title>{% block title %}{% endblock %}</title>"),
+///     ("nav.html", "<nav></nav>"),
+///     (
+///         "index.html",
+///         r#"{% extends "base.html" %}{% block title %}{% include "nav.html" %}{% endblock %}"#,
+///     ),
+/// ])
+/// .unwrap();
+///
+/// let graph = TemplateGraph::new(&tera);
+/// assert_eq!(
+///     graph.dependencies("index.html").unwrap().extends.as_deref(),
+///     Some("base.html")
+/// );
+/// assert_eq!(
+///     graph.affected(["nav.html"]).into_iter().collect::<Vec<_>>(),
+///     vec!["index.html", "nav.html"]
+/// );
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct TemplateGraph(BTreeMap<String, TemplateDependencies>);
+
+impl TemplateGraph {
+    /// Create the graph of the templates of `tera`.
+    pub fn new(tera: &Tera) -> Self {
+        Self(
+            tera.templates
+                .iter()
+                .map(|(name, template)| {
+                    let mut dependencies = TemplateDependencies {
+                        extends: template.parent.clone(),
+                        includes: BTreeSet::new(),
+                        imports: template
+                            .imported_macro_files
+                            .iter()
+                            .map(|(file, _)| file.clone())
+                            .collect(),
+                    };
+                    collect_includes(&template.ast, &mut dependencies.includes);
+                    for definition in template.macros.values() {
+                        collect_includes(&definition.body, &mut dependencies.includes);
+                    }
+                    (name.clone(), dependencies)
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the dependencies of the template `name`, or `None` if it
+    /// doesn't exist.
+    pub fn dependencies(&self, name: &str) -> Option<&TemplateDependencies> {
+        self.0.get(name)
+    }
+
+    /// Returns the names of the templates which directly depend on the
+    /// template `name`.
+    pub fn dependents(&self, name: &str) -> BTreeSet<&str> {
+        self.0
+            .iter()
+            .filter(|(_, dependencies)| dependencies.iter().any(|dependency| dependency == name))
+            .map(|(dependent, _)| dependent.as_str())
+            .collect()
+    }
+
+    /// Returns the names of the templates affected by a change of the
+    /// templates `changed`: these templates and all the templates which
+    /// depend on them, directly or not.
+    pub fn affected<'a>(&self, changed: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+        let mut affected = BTreeSet::new();
+        let mut pending = changed.into_iter().collect::<Vec<_>>();
+        while let Some(name) = pending.pop() {
+            if affected.insert(name.to_string()) {
+                pending.extend(self.dependents(name));
+            }
+        }
+        affected
+    }
+
+    /// Returns the templates and their dependencies, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TemplateDependencies)> {
+        self.0
+            .iter()
+            .map(|(name, dependencies)| (name.as_str(), dependencies))
+    }
+}
+
+fn collect_includes(nodes: &[Node], includes: &mut BTreeSet<String>) {
+    for node in nodes {
+        match node {
+            Node::Include(_, names, _) => includes.extend(names.iter().cloned()),
+            Node::Block(_, block, _) => collect_includes(&block.body, includes),
+            Node::MacroDefinition(_, definition, _) => collect_includes(&definition.body, includes),
+            Node::FilterSection(_, section, _) => collect_includes(&section.body, includes),
+            Node::Forloop(_, forloop, _) => {
+                collect_includes(&forloop.body, includes);
+                if let Some(body) = &forloop.empty_body {
+                    collect_includes(body, includes);
+                }
+            }
+            Node::If(condition, _) => {
+                for (_, _, body) in &condition.conditions {
+                    collect_includes(body, includes);
+                }
+                if let Some((_, body)) = &condition.otherwise {
+                    collect_includes(body, includes);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graph() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base.html", "{% block content %}{% endblock %}"),
+            (
+                "macros.html",
+                "{% macro card() %}{% include \"card.html\" %}{% endmacro %}",
+            ),
+            ("card.html", "card"),
+            ("footer.html", "footer"),
+            (
+                "page.html",
+                "{% extends \"base.html\" %}{% import \"macros.html\" as macros %}{% block \
+                 content %}{% for i in [1] %}{{ macros::card() }}{% else %}{% include \
+                 \"footer.html\" %}{% endfor %}{% endblock %}",
+            ),
+            (
+                "article.html",
+                "{% extends \"page.html\" %}{% block content %}{% if true %}{% include \
+                 [\"missing.html\", \"card.html\"] ignore missing %}{% endif %}{% endblock %}",
+            ),
+        ])
+        .unwrap();
+
+        let graph = TemplateGraph::new(&tera);
+        assert_eq!(
+            graph.dependencies("page.html"),
+            Some(&TemplateDependencies {
+                extends: Some("base.html".to_string()),
+                includes: ["footer.html".to_string()].into_iter().collect(),
+                imports: ["macros.html".to_string()].into_iter().collect(),
+            })
+        );
+        assert_eq!(
+            graph.dependencies("article.html").unwrap().includes,
+            ["card.html".to_string(), "missing.html".to_string()]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            graph.dependencies("macros.html").unwrap().includes,
+            ["card.html".to_string()].into_iter().collect()
+        );
+        assert_eq!(
+            graph.dependents("card.html"),
+            ["article.html", "macros.html"].into_iter().collect()
+        );
+        assert_eq!(
+            graph.affected(["card.html"]),
+            ["article.html", "card.html", "macros.html", "page.html"]
+                .into_iter()
+                .map(ToString::to_string)
+                .collect()
+        );
+        assert_eq!(
+            graph.affected(["footer.html", "base.html"]),
+            ["article.html", "base.html", "footer.html", "page.html"]
+                .into_iter()
+                .map(ToString::to_string)
+                .collect()
+        );
+    }
+}
@@ -1,14 +1,25 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use parking_lot::{Mutex, RwLock};
 use tera::Tera;
 
+use super::TemplateGraph;
 use crate::{
-    error::{InternalServerError, IntoResult},
-    web::Html,
+    error::{DeadlineExceededError, InternalServerError, IntoResult, MissingMiddlewareError},
+    web::{Clock, Html},
     Endpoint, FromRequest, Middleware, Request, RequestBody, Result,
 };
 
 /// Tera Templating Middleware
 pub struct TeraTemplatingMiddleware {
-    tera: Tera,
+    tera: Arc<RwLock<Tera>>,
+    files: Arc<Mutex<TemplateFiles>>,
 }
 
 impl TeraTemplatingMiddleware {
@@ -22,16 +33,27 @@ impl TeraTemplatingMiddleware {
     /// let templating = TeraTemplating::from_glob("templates/**/*");
     /// ```
     pub fn from_glob(glob: &str) -> Self {
-        let tera = match Tera::new(glob) {
+        // the templates are checked once the built-in macros are added, since
+        // they can import them
+        let tera = match Tera::parse(glob).and_then(|mut tera| {
+            super::page_meta::register_macros(&mut tera)?;
+            Ok(tera)
+        }) {
             Ok(t) => t,
-            Err(e) => {
+            Err(err) => {
                 tracing::error!("Failed to parse Tera template: {err}");
                 tracing::debug!("Tera Parsing error: {err:?}");
                 ::std::process::exit(1);
             }
         };
 
-        Self { tera }
+        Self {
+            files: Arc::new(Mutex::new(TemplateFiles::new(
+                glob_root(glob).into_iter().collect(),
+                &tera,
+            ))),
+            tera: Arc::new(RwLock::new(tera)),
+        }
     }
 
     /// Create a new instance of TeraTemplating, containing all the parsed
@@ -44,7 +66,7 @@ impl TeraTemplatingMiddleware {
     /// let templating = TeraTemplating::from_glob("templates");
     /// ```
     pub fn from_directory(template_directory: &str) -> Self {
-        Self::from_glob(&format!("{template_directory}/**/*"));
+        Self::from_glob(&format!("{template_directory}/**/*"))
     }
 
     /// Create a new instance of TeraTemplating, using the provided Tera
@@ -63,8 +85,239 @@ impl TeraTemplatingMiddleware {
     /// tera.autoescape_on(vec![".html", ".sql"]);
     /// let templating = TeraTemplating::custom(tera);
     /// ```
-    pub fn custom(tera: Tera) -> Self {
-        Self { tera }
+    pub fn custom(mut tera: Tera) -> Self {
+        if let Err(err) = super::page_meta::register_macros(&mut tera) {
+            tracing::error!("Failed to add the built-in Tera macros: {err}");
+        }
+
+        Self {
+            files: Arc::new(Mutex::new(TemplateFiles::new(Vec::new(), &tera))),
+            tera: Arc::new(RwLock::new(tera)),
+        }
+    }
+
+    /// Returns a handle for reloading the templates while the server is
+    /// running.
+    ///
+    /// ```no_compile
+    /// use poem::tera::TeraTemplating;
+    ///
+    /// let templating = TeraTemplating::from_glob("templates/**/*");
+    /// let reloader = templating.reloader();
+    /// reloader.reload().unwrap();
+    /// ```
+    pub fn reloader(&self) -> TeraReloader {
+        TeraReloader {
+            tera: self.tera.clone(),
+            files: self.files.clone(),
+        }
+    }
+}
+
+/// A handle for reloading the templates of a [`TeraTemplatingMiddleware`].
+#[derive(Clone)]
+pub struct TeraReloader {
+    tera: Arc<RwLock<Tera>>,
+    files: Arc<Mutex<TemplateFiles>>,
+}
+
+impl TeraReloader {
+    /// Reloads the templates from the glob or the directory they were loaded
+    /// from.
+    ///
+    /// If a template fails to parse, the previous templates are kept.
+    pub fn reload(&self) -> tera::Result<()> {
+        let mut files = self.files.lock();
+        let mut tera = self.tera.read().clone();
+        tera.full_reload()?;
+        files.update(&tera);
+        *self.tera.write() = tera;
+        Ok(())
+    }
+
+    /// Reloads the templates whose files were modified since the last
+    /// reload, and returns the names of the reloaded templates.
+    ///
+    /// Only the modified templates and the templates which extend, include
+    /// or import them, directly or not, are parsed again. All the templates
+    /// are reloaded like [`reload`](TeraReloader::reload) when a file or a
+    /// directory was added or removed.
+    ///
+    /// The files are checked with blocking calls, so use
+    /// [`tokio::task::spawn_blocking`] to call it from an async context.
+    ///
+    /// If a template fails to parse, the previous templates are kept.
+    pub fn reload_changed(&self) -> tera::Result<BTreeSet<String>> {
+        let mut files = self.files.lock();
+        let changes = files.changes(&self.tera.read());
+        let changed = match changes {
+            Some(changed) if changed.is_empty() => {
+                files.refresh_dirs();
+                return Ok(BTreeSet::new());
+            }
+            Some(changed) => changed,
+            None => {
+                let mut tera = self.tera.read().clone();
+                tera.full_reload()?;
+                let reloaded = tera.templates.keys().cloned().collect();
+                files.update(&tera);
+                *self.tera.write() = tera;
+                return Ok(reloaded);
+            }
+        };
+
+        let mut tera = self.tera.read().clone();
+        let affected = TemplateGraph::new(&tera).affected(changed.iter().map(String::as_str));
+        let paths = affected
+            .iter()
+            .filter_map(|name| {
+                let path = tera.templates.get(name)?.path.clone()?;
+                Some((path, Some(name.as_str())))
+            })
+            .collect::<Vec<_>>();
+        tera.add_template_files(paths)?;
+        files.update(&tera);
+        *self.tera.write() = tera;
+        Ok(affected)
+    }
+
+    /// Returns the graph of the dependencies between the current templates.
+    pub fn graph(&self) -> TemplateGraph {
+        TemplateGraph::new(&self.tera.read())
+    }
+}
+
+/// The modification times of the template files and of the directories
+/// containing them, for finding the templates modified since the last reload.
+struct TemplateFiles {
+    /// The directories of the globs, whose subdirectories are watched even
+    /// if they contain no template yet.
+    roots: Vec<PathBuf>,
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    dirs: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl TemplateFiles {
+    fn new(roots: Vec<PathBuf>, tera: &Tera) -> Self {
+        let mut files = Self {
+            roots,
+            files: HashMap::new(),
+            dirs: HashMap::new(),
+        };
+        files.update(tera);
+        files
+    }
+
+    fn update(&mut self, tera: &Tera) {
+        self.files.clear();
+        self.dirs.clear();
+        for path in tera.templates.values().filter_map(|t| t.path.as_deref()) {
+            let path = Path::new(path);
+            self.files.insert(path.to_path_buf(), modified(path));
+            if let Some(dir) = path.parent() {
+                self.dirs
+                    .entry(dir.to_path_buf())
+                    .or_insert_with(|| modified(dir));
+            }
+        }
+        for root in &self.roots {
+            add_dirs(root, &mut self.dirs);
+        }
+    }
+
+    /// Returns the names of the modified templates, or `None` if a template
+    /// file or a directory was added or removed.
+    fn changes(&self, tera: &Tera) -> Option<Vec<String>> {
+        let mut changed = Vec::new();
+        for (name, template) in &tera.templates {
+            let path = match &template.path {
+                Some(path) => Path::new(path),
+                None => continue,
+            };
+            let modified = Some(modified(path)?);
+            if self.files.get(path) != Some(&modified) {
+                changed.push(name.clone());
+            }
+        }
+
+        // the directories are modified when files are added, but also when
+        // the editors write their swap files
+        for (dir, modified_at) in &self.dirs {
+            if modified(dir) != *modified_at && self.has_new_entries(dir) {
+                return None;
+            }
+        }
+        Some(changed)
+    }
+
+    fn has_new_entries(&self, dir: &Path) -> bool {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return true,
+        };
+        entries.flatten().any(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name.ends_with('~') {
+                return false;
+            }
+            match entry.file_type() {
+                Ok(ty) if ty.is_file() => !self.files.contains_key(&entry.path()),
+                Ok(ty) if ty.is_dir() => !self.dirs.contains_key(&entry.path()),
+                _ => false,
+            }
+        })
+    }
+
+    fn refresh_dirs(&mut self) {
+        for (dir, modified_at) in &mut self.dirs {
+            *modified_at = modified(dir);
+        }
+    }
+}
+
+/// Returns the directory of `glob` before its first wildcard, which Tera
+/// loads the templates from.
+fn glob_root(glob: &str) -> Option<PathBuf> {
+    let dir = &glob[..glob.find('*')?];
+    Some(std::fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir)))
+}
+
+/// Adds `dir` and its subdirectories, except the hidden ones.
+fn add_dirs(dir: &Path, dirs: &mut HashMap<PathBuf, Option<SystemTime>>) {
+    dirs.entry(dir.to_path_buf())
+        .or_insert_with(|| modified(dir));
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let is_dir = entry.file_type().map_or(false, |ty| ty.is_dir());
+        if is_dir && !entry.file_name().to_string_lossy().starts_with('.') {
+            add_dirs(&entry.path(), dirs);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl TeraTemplatingMiddleware {
+    /// Returns a hash of the current templates, which changes when a
+    /// template is modified, for versioning the caches of the rendered
+    /// pages such as [`RenderCache`](crate::middleware::RenderCache).
+    ///
+    /// The hash is computed from the parsed templates, so it also changes
+    /// when Tera or Rust is upgraded.
+    pub fn templates_hash(&self) -> String {
+        let tera = self.tera.read();
+        let templates = tera.templates.iter().collect::<BTreeMap<_, _>>();
+
+        let mut hasher = DefaultHasher::new();
+        for (name, template) in templates {
+            name.hash(&mut hasher);
+            format!("{:?}", template.ast).hash(&mut hasher);
+        }
+        format!("{:016x}", hasher.finish())
     }
 }
 
@@ -79,18 +332,39 @@ impl<E: Endpoint> Middleware<E> for TeraTemplatingMiddleware {
 
     fn transform(&self, inner: E) -> Self::Output {
         Self::Output {
-            tera: self.tera.clone(),
+            reloader: self.reloader(),
             inner,
             transformers: Vec::new(),
+            live_reloading: false,
+            live_reloading_interval: Duration::from_secs(1),
+            next_reload: Mutex::new(None),
         }
     }
 }
 
 /// Tera Templating Endpoint
 pub struct TeraTemplatingEndpoint<E> {
-    tera: Tera,
+    reloader: TeraReloader,
     inner: E,
     transformers: Vec<fn(&mut Tera, &mut Request)>,
+    live_reloading: bool,
+    live_reloading_interval: Duration,
+    next_reload: Mutex<Option<Instant>>,
+}
+
+impl<E> TeraTemplatingEndpoint<E> {
+    /// Returns `true` if the templates are due to be checked for changes,
+    /// at most once per interval.
+    fn is_reload_due(&self, now: Instant) -> bool {
+        let mut next_reload = self.next_reload.lock();
+        match *next_reload {
+            Some(next_reload) if now < next_reload => false,
+            _ => {
+                *next_reload = Some(now + self.live_reloading_interval);
+                true
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -98,7 +372,22 @@ impl<E: Endpoint> Endpoint for TeraTemplatingEndpoint<E> {
     type Output = E::Output;
 
     async fn call(&self, mut req: Request) -> Result<Self::Output> {
-        let mut tera = self.tera.clone();
+        if self.live_reloading && self.is_reload_due(Clock::of(&req).instant()) {
+            let reloader = self.reloader.clone();
+            match tokio::task::spawn_blocking(move || reloader.reload_changed()).await {
+                Ok(Ok(reloaded)) if !reloaded.is_empty() => {
+                    tracing::debug!(templates = ?reloaded, "Reloaded Tera templates");
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => {
+                    tracing::error!("Failed to reload Tera templates: {err}");
+                    tracing::debug!("Tera Parsing error: {err:?}");
+                }
+                Err(_) => tracing::error!("Failed to reload Tera templates"),
+            }
+        }
+
+        let mut tera = self.reloader.tera.read().clone();
 
         for transformer in &self.transformers {
             transformer(&mut tera, &mut req);
@@ -113,13 +402,17 @@ impl<E: Endpoint> Endpoint for TeraTemplatingEndpoint<E> {
 #[async_trait::async_trait]
 impl<'a> FromRequest<'a> for Tera {
     async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
-        let tera = req
-            .extensions()
-            .get::<Tera>()
-            .expect("To use the `Tera` extractor, the `TeraTemplating` endpoit is required.")
-            .clone();
-
-        Ok(tera)
+        match req.extensions().get::<Tera>() {
+            Some(tera) => Ok(tera.clone()),
+            None => {
+                let err = MissingMiddlewareError {
+                    extractor: "Tera",
+                    middleware: "TeraTemplating",
+                };
+                tracing::error!(error = %err, "failed to extract the templates");
+                Err(err.into())
+            }
+        }
     }
 }
 
@@ -129,6 +422,9 @@ pub type TeraTemplatingResult = tera::Result<String>;
 impl IntoResult<Html<String>> for TeraTemplatingResult {
     fn into_result(self) -> Result<Html<String>> {
         if let Err(err) = &self {
+            if super::is_deadline_exceeded(err) {
+                return Err(DeadlineExceededError.into());
+            }
             tracing::error!("Failed to render Tera template: {err}");
             tracing::debug!("Tera Rendering error: {err:?}");
         }
@@ -156,6 +452,12 @@ impl<E: Endpoint> TeraTemplatingEndpoint<E> {
 
     /// Enable live reloading only for debug mode (not for release)
     ///
+    /// Before the requests, the templates whose files were modified are
+    /// parsed again, along with the templates which extend, include or
+    /// import them, as with [`TeraReloader::reload_changed`]. The files are
+    /// checked at most once per second, which can be changed with
+    /// [`live_reloading_interval`](TeraTemplatingEndpoint::live_reloading_interval).
+    ///
     /// ```no_compile
     /// use poem::{Route, EndpointExt, tera::TeraTemplating};
     ///
@@ -164,10 +466,194 @@ impl<E: Endpoint> TeraTemplatingEndpoint<E> {
     ///     .with_live_reloading();
     /// ```
     pub fn with_live_reloading(self) -> Self {
-        #[cfg(debug_assertions)] {
+        #[cfg(debug_assertions)]
+        {
             tracing::debug!("Live Reloading for Tera Templating is enabled");
-        }    
-            
-        self
+        }
+
+        Self {
+            live_reloading: cfg!(debug_assertions),
+            ..self
+        }
+    }
+
+    /// Sets the minimum interval between two checks of the template files
+    /// for changes when the live reloading is enabled. Defaults to one
+    /// second.
+    ///
+    /// The requests are timed with the [`Clock`] of the request.
+    #[must_use]
+    pub fn live_reloading_interval(self, interval: Duration) -> Self {
+        Self {
+            live_reloading_interval: interval,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filetime::FileTime;
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, web::MockClock, EndpointExt};
+
+    #[tokio::test]
+    async fn missing_middleware() {
+        #[handler(internal)]
+        fn index(_tera: Tera) {}
+
+        TestClient::new(index)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn reload() {
+        let dir = std::env::temp_dir().join(format!("poem-tera-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "v1").unwrap();
+
+        let templating = TeraTemplatingMiddleware::from_directory(dir.to_str().unwrap());
+        let reloader = templating.reloader();
+        let render = || {
+            templating
+                .tera
+                .read()
+                .render("index.html", &tera::Context::new())
+                .unwrap()
+        };
+        assert_eq!(render(), "v1");
+        let hash = templating.templates_hash();
+        assert_eq!(templating.templates_hash(), hash);
+
+        std::fs::write(dir.join("index.html"), "v2").unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(render(), "v2");
+        assert_ne!(templating.templates_hash(), hash);
+
+        // the previous templates are kept on errors
+        std::fs::write(dir.join("index.html"), "{{ v3").unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(render(), "v2");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Writes the file and sets the modification times of the file and of
+    /// its directory, which may have a coarse resolution, to `secs`.
+    fn write(path: &Path, contents: &str, secs: i64) {
+        std::fs::write(path, contents).unwrap();
+        let time = FileTime::from_unix_time(secs, 0);
+        filetime::set_file_mtime(path, time).unwrap();
+        filetime::set_file_mtime(path.parent().unwrap(), time).unwrap();
+    }
+
+    #[test]
+    fn reload_changed() {
+        let dir =
+            std::env::temp_dir().join(format!("poem-tera-reload-changed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write(
+            &dir.join("base.html"),
+            "{% block content %}{% endblock %}",
+            1,
+        );
+        write(&dir.join("footer.html"), "v1", 1);
+        write(
+            &dir.join("page.html"),
+            r#"{% extends "base.html" %}{% block content %}{% include "footer.html" %}{% endblock %}"#,
+            1,
+        );
+        write(&dir.join("about.html"), "about", 1);
+
+        let templating = TeraTemplatingMiddleware::from_directory(dir.to_str().unwrap());
+        let reloader = templating.reloader();
+        let render = |name| {
+            templating
+                .tera
+                .read()
+                .render(name, &tera::Context::new())
+                .unwrap()
+        };
+        assert_eq!(render("page.html"), "v1");
+        assert!(reloader.reload_changed().unwrap().is_empty());
+        assert_eq!(
+            reloader.graph().dependents("footer.html"),
+            ["page.html"].into_iter().collect()
+        );
+
+        write(&dir.join("footer.html"), "v2", 2);
+        assert_eq!(
+            reloader.reload_changed().unwrap(),
+            ["footer.html".to_string(), "page.html".to_string()]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(render("page.html"), "v2");
+        assert!(reloader.reload_changed().unwrap().is_empty());
+
+        // the previous templates are kept on errors
+        write(&dir.join("footer.html"), "{{ v3", 3);
+        assert!(reloader.reload_changed().is_err());
+        assert_eq!(render("page.html"), "v2");
+
+        // all the templates are reloaded when a file is added
+        write(&dir.join("footer.html"), "v4", 4);
+        write(&dir.join("contact.html"), "contact", 4);
+        let reloaded = reloader.reload_changed().unwrap();
+        assert!(reloaded.contains("about.html") && reloaded.contains("contact.html"));
+        assert_eq!(render("page.html"), "v4");
+        assert_eq!(render("contact.html"), "contact");
+
+        // or a directory, whose new files are then found
+        std::fs::create_dir(dir.join("blog")).unwrap();
+        filetime::set_file_mtime(&dir, FileTime::from_unix_time(5, 0)).unwrap();
+        assert!(reloader.reload_changed().unwrap().contains("about.html"));
+        write(&dir.join("blog").join("post.html"), "post", 6);
+        assert!(reloader
+            .reload_changed()
+            .unwrap()
+            .contains("blog/post.html"));
+        assert_eq!(render("blog/post.html"), "post");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn live_reloading() {
+        #[handler(internal)]
+        fn index(tera: Tera) -> TeraTemplatingResult {
+            tera.render("index.html", &tera::Context::new())
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("poem-tera-live-reloading-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write(&dir.join("index.html"), "v1", 1);
+
+        let clock = MockClock::new();
+        let app = index
+            .with(TeraTemplatingMiddleware::from_directory(
+                dir.to_str().unwrap(),
+            ))
+            .with_live_reloading()
+            .live_reloading_interval(Duration::from_secs(2))
+            .data(Clock::from(clock.clone()));
+        let cli = TestClient::new(app);
+        cli.get("/").send().await.assert_text("v1").await;
+
+        // the files are only checked once per interval
+        write(&dir.join("index.html"), "v2", 2);
+        cli.get("/").send().await.assert_text("v1").await;
+        clock.advance(Duration::from_secs(1));
+        cli.get("/").send().await.assert_text("v1").await;
+        clock.advance(Duration::from_secs(1));
+        cli.get("/").send().await.assert_text("v2").await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -23,17 +23,31 @@
 //! }
 //! ```
 
+mod assets;
+mod graph;
 mod middleware;
+mod page_meta;
+mod pages;
 mod transformers;
 
+use std::io::{self, Write};
+
 pub use tera::{Context, Tera};
 
 pub use self::{
+    assets::AssetManifest,
+    graph::{TemplateDependencies, TemplateGraph},
     middleware::{
-        TeraTemplatingEndpoint, TeraTemplatingMiddleware as TeraTemplating,
+        TeraReloader, TeraTemplatingEndpoint, TeraTemplatingMiddleware as TeraTemplating,
         TeraTemplatingResult as TeraTemplate,
     },
-    transformers::filters,
+    page_meta::PageMeta,
+    pages::{Page, PageContext, Pages},
+    transformers::{filters, functions},
+};
+use crate::{
+    error::DeadlineExceededError,
+    web::{Deadline, PooledBuffer},
 };
 
 /// Macro for constructing a Tera Context
@@ -62,3 +76,148 @@ macro_rules! ctx {
         }
     };
 }
+
+/// Renders the template `name` with the `context` into a buffer of the pool
+/// of [`BufferPoolMetrics`](crate::web::BufferPoolMetrics), instead of
+/// growing a new `String` for each response.
+///
+/// ```no_compile
+/// use poem::{
+///     ctx, handler,
+///     tera::{self, Tera, TeraTemplate},
+///     web::Path,
+/// };
+///
+/// #[handler]
+/// fn hello(Path(name): Path<String>, tera: Tera) -> TeraTemplate {
+///     tera::render(&tera, "index.html.tera", &ctx! { "name": &name })
+/// }
+/// ```
+pub fn render(tera: &Tera, name: &str, context: &Context) -> TeraTemplate {
+    let mut buf = PooledBuffer::acquire();
+    tera.render_to(name, context, &mut *buf)?;
+    buf.into_string()
+        .ok_or_else(|| tera::Error::msg(format!("template `{name}` is not valid UTF-8")))
+}
+
+/// Renders the template `name` like [`render`], stopping when the
+/// [`Deadline`] of the request expires.
+///
+/// A template expiring before or while it is rendered fails with
+/// [`DeadlineExceededError`], which is returned by the handlers as a
+/// `504 Gateway Timeout`.
+///
+/// ```no_compile
+/// use poem::{
+///     ctx, handler,
+///     tera::{self, Tera, TeraTemplate},
+///     web::Deadline,
+/// };
+///
+/// #[handler]
+/// fn report(tera: Tera, deadline: Option<Deadline>) -> TeraTemplate {
+///     tera::render_within(&tera, "report.html.tera", &ctx! {}, deadline.as_ref())
+/// }
+/// ```
+pub fn render_within(
+    tera: &Tera,
+    name: &str,
+    context: &Context,
+    deadline: Option<&Deadline>,
+) -> TeraTemplate {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return render(tera, name, context),
+    };
+    let mut buf = PooledBuffer::acquire();
+    tera.render_to(
+        name,
+        context,
+        DeadlineWriter {
+            inner: &mut *buf,
+            deadline,
+        },
+    )?;
+    buf.into_string()
+        .ok_or_else(|| tera::Error::msg(format!("template `{name}` is not valid UTF-8")))
+}
+
+/// A writer failing once the deadline expires, which stops the rendering.
+struct DeadlineWriter<'a, W> {
+    inner: W,
+    deadline: &'a Deadline,
+}
+
+impl<'a, W: Write> Write for DeadlineWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.deadline.is_expired() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                DeadlineExceededError,
+            ));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns `true` if the rendering was stopped by [`render_within`].
+pub(crate) fn is_deadline_exceeded(err: &tera::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            if err
+                .get_ref()
+                .map_or(false, |err| err.is::<DeadlineExceededError>())
+            {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_pooled() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello.html", "<p>Hello {{ name }}</p>")
+            .unwrap();
+        let mut context = Context::new();
+        context.insert("name", "sunli");
+        assert_eq!(
+            render(&tera, "hello.html", &context).unwrap(),
+            "<p>Hello sunli</p>"
+        );
+        assert!(render(&tera, "missing.html", &context).is_err());
+    }
+
+    #[test]
+    fn render_within_deadline() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello.html", "<p>Hello {{ name }}</p>")
+            .unwrap();
+        let mut context = Context::new();
+        context.insert("name", "sunli");
+
+        let deadline = Deadline::after(std::time::Duration::from_secs(10));
+        assert_eq!(
+            render_within(&tera, "hello.html", &context, Some(&deadline)).unwrap(),
+            "<p>Hello sunli</p>"
+        );
+
+        let expired = Deadline::after(std::time::Duration::ZERO);
+        let err = render_within(&tera, "hello.html", &context, Some(&expired)).unwrap_err();
+        assert!(is_deadline_exceeded(&err));
+        assert!(!is_deadline_exceeded(
+            &render(&tera, "missing.html", &context).unwrap_err()
+        ));
+    }
+}
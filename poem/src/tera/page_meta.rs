@@ -0,0 +1,256 @@
+use serde::Serialize;
+use tera::Tera;
+
+use crate::{web::ForwardedInfo, Request};
+
+/// The name of the template defining the `page_meta` macro.
+const PAGE_META_TEMPLATE: &str = "poem/page_meta.html";
+
+const PAGE_META_MACROS: &str = r#"{% macro page_meta(meta) -%}
+{% if meta.title -%}
+<title>{{ meta.title }}</title>
+<meta property="og:title" content="{{ meta.title }}">
+{% endif -%}
+{% if meta.description -%}
+<meta name="description" content="{{ meta.description }}">
+<meta property="og:description" content="{{ meta.description }}">
+{% endif -%}
+{% if meta.canonical -%}
+<link rel="canonical" href="{{ meta.canonical }}">
+<meta property="og:url" content="{{ meta.canonical }}">
+{% endif -%}
+<meta property="og:type" content="{{ meta.og_type }}">
+{% if meta.image -%}
+<meta property="og:image" content="{{ meta.image }}">
+{% endif -%}
+{% if meta.site_name -%}
+<meta property="og:site_name" content="{{ meta.site_name }}">
+{% endif -%}
+<meta name="twitter:card" content="{{ meta.twitter_card }}">
+{% if meta.twitter_site -%}
+<meta name="twitter:site" content="{{ meta.twitter_site }}">
+{% endif -%}
+{% endmacro page_meta %}"#;
+
+/// Adds the template defining the `page_meta` macro to `tera`.
+///
+/// The template is added with [`Tera::extend`], so that it is kept when the
+/// templates are reloaded, and does not replace a template of the same name.
+pub(crate) fn register_macros(tera: &mut Tera) -> tera::Result<()> {
+    let mut macros = Tera::default();
+    macros.add_raw_template(PAGE_META_TEMPLATE, PAGE_META_MACROS)?;
+    tera.extend(&macros)?;
+    tera.build_inheritance_chains()
+}
+
+/// The title, the description and the Open Graph and Twitter card metadata of
+/// a page, rendered as the tags of its `<head>` by the `page_meta` Tera macro.
+///
+/// The macro is defined by the `poem/page_meta.html` template, which is added
+/// to the templates by the [`TeraTemplating`](crate::tera::TeraTemplating)
+/// middleware, and escapes all the values.
+///
+/// # Example
+///
+/// ```no_compile
+/// use poem::{
+///     ctx, handler,
+///     tera::{PageMeta, Tera, TeraTemplate},
+///     Request,
+/// };
+///
+/// #[handler]
+/// fn article(req: &Request, tera: Tera) -> TeraTemplate {
+///     let meta = PageMeta::new()
+///         .title("Hello")
+///         .description("The first article")
+///         .canonical_from(req)
+///         .image("https://example.com/hello.png");
+///     tera.render("article.html", &ctx! { "meta": &meta })
+/// }
+/// ```
+///
+/// With the template `article.html`:
+///
+/// ```text
+/// {% import "poem/page_meta.html" as poem %}
+/// <html>
+///   <head>{{ poem::page_meta(meta=meta) }}</head>
+/// </html>
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct PageMeta {
+    title: Option<String>,
+    description: Option<String>,
+    canonical: Option<String>,
+    og_type: String,
+    image: Option<String>,
+    site_name: Option<String>,
+    twitter_card: String,
+    twitter_site: Option<String>,
+}
+
+impl Default for PageMeta {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PageMeta {
+    /// Create an empty `PageMeta`, of the `website` Open Graph type with a
+    /// `summary` Twitter card.
+    pub fn new() -> Self {
+        Self {
+            title: None,
+            description: None,
+            canonical: None,
+            og_type: "website".to_string(),
+            image: None,
+            site_name: None,
+            twitter_card: "summary".to_string(),
+            twitter_site: None,
+        }
+    }
+
+    /// Sets the title of the page.
+    #[must_use]
+    pub fn title(self, title: impl Into<String>) -> Self {
+        Self {
+            title: Some(title.into()),
+            ..self
+        }
+    }
+
+    /// Sets the description of the page.
+    #[must_use]
+    pub fn description(self, description: impl Into<String>) -> Self {
+        Self {
+            description: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Sets the canonical URL of the page.
+    #[must_use]
+    pub fn canonical(self, url: impl Into<String>) -> Self {
+        Self {
+            canonical: Some(url.into()),
+            ..self
+        }
+    }
+
+    /// Sets the canonical URL of the page to the URL of the request, without
+    /// its query string.
+    ///
+    /// The host and the scheme are those requested by the client, as
+    /// resolved by the [`ForwardedHeaders`](crate::middleware::ForwardedHeaders)
+    /// middleware if it is applied. The canonical URL is unchanged if the host
+    /// is unknown.
+    #[must_use]
+    pub fn canonical_from(self, req: &Request) -> Self {
+        let info = req
+            .extensions()
+            .get::<ForwardedInfo>()
+            .cloned()
+            .unwrap_or_else(|| ForwardedInfo::direct(req));
+        match info.base_url() {
+            Some(base_url) => self.canonical(format!("{base_url}{}", req.original_uri().path())),
+            None => self,
+        }
+    }
+
+    /// Sets the Open Graph type of the page, such as `article`.
+    ///
+    /// Default is `website`.
+    #[must_use]
+    pub fn og_type(self, og_type: impl Into<String>) -> Self {
+        Self {
+            og_type: og_type.into(),
+            ..self
+        }
+    }
+
+    /// Sets the URL of the image shown when the page is shared.
+    #[must_use]
+    pub fn image(self, url: impl Into<String>) -> Self {
+        Self {
+            image: Some(url.into()),
+            ..self
+        }
+    }
+
+    /// Sets the name of the site.
+    #[must_use]
+    pub fn site_name(self, site_name: impl Into<String>) -> Self {
+        Self {
+            site_name: Some(site_name.into()),
+            ..self
+        }
+    }
+
+    /// Sets the type of the Twitter card, such as `summary_large_image`.
+    ///
+    /// Default is `summary`.
+    #[must_use]
+    pub fn twitter_card(self, card: impl Into<String>) -> Self {
+        Self {
+            twitter_card: card.into(),
+            ..self
+        }
+    }
+
+    /// Sets the Twitter account of the site, such as `@poem`.
+    #[must_use]
+    pub fn twitter_site(self, account: impl Into<String>) -> Self {
+        Self {
+            twitter_site: Some(account.into()),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler,
+        tera::{Context, TeraTemplating},
+        test::TestClient,
+        EndpointExt,
+    };
+
+    #[tokio::test]
+    async fn render() {
+        #[handler(internal)]
+        fn index(req: &Request, mut tera: Tera) -> tera::Result<String> {
+            tera.add_raw_template(
+                "index.html",
+                r#"{% import "poem/page_meta.html" as poem %}{{ poem::page_meta(meta=meta) }}"#,
+            )?;
+            let meta = PageMeta::new()
+                .title("Fish & Chips")
+                .canonical_from(req)
+                .twitter_card("summary_large_image");
+            let mut context = Context::new();
+            context.insert("meta", &meta);
+            tera.render("index.html", &context)
+        }
+
+        let app = index.with(TeraTemplating::custom(Tera::default()));
+        TestClient::new(app)
+            .get("/articles/1")
+            .header("host", "example.com")
+            .query("page", &2)
+            .send()
+            .await
+            .assert_text(
+                "<title>Fish &amp; Chips</title>\n\
+                 <meta property=\"og:title\" content=\"Fish &amp; Chips\">\n\
+                 <link rel=\"canonical\" href=\"http:&#x2F;&#x2F;example.com&#x2F;articles&#x2F;1\">\n\
+                 <meta property=\"og:url\" content=\"http:&#x2F;&#x2F;example.com&#x2F;articles&#x2F;1\">\n\
+                 <meta property=\"og:type\" content=\"website\">\n\
+                 <meta name=\"twitter:card\" content=\"summary_large_image\">\n",
+            )
+            .await;
+    }
+}
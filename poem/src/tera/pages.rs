@@ -0,0 +1,353 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use percent_encoding::percent_decode_str;
+use tera::{Context, Tera};
+
+use crate::{
+    error::{IntoResult, NotFoundError},
+    http::{Method, StatusCode},
+    web::Html,
+    Endpoint, FromRequest, IntoResponse, Request, Response, Result,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum SegmentKind {
+    Static,
+    Param,
+    CatchAll,
+}
+
+/// A page matched by [`Pages`].
+#[derive(Debug, Clone)]
+pub struct Page {
+    template: String,
+    params: Vec<(String, String)>,
+}
+
+impl Page {
+    /// Returns the name of the template of the page.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the value of the parameter `name`, captured by the `[name]`
+    /// or `[...name]` segments of the template path.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all the parameters.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+}
+
+/// Provides the context of the pages rendered by [`Pages`].
+///
+/// It is implemented for the functions returning the context synchronously,
+/// and can be implemented to load it asynchronously, such as from a
+/// database.
+#[async_trait::async_trait]
+pub trait PageContext: Send + Sync + 'static {
+    /// Returns the context of the `page` requested by `req`.
+    ///
+    /// An error is returned as the response, such as a
+    /// [`NotFoundError`] when the content of the page does not exist.
+    async fn context(&self, req: &Request, page: &Page) -> Result<Context>;
+}
+
+#[async_trait::async_trait]
+impl<F> PageContext for F
+where
+    F: Fn(&Request, &Page) -> Result<Context> + Send + Sync + 'static,
+{
+    async fn context(&self, req: &Request, page: &Page) -> Result<Context> {
+        (self)(req, page)
+    }
+}
+
+/// An endpoint that maps the templates of a directory to routes, so that a
+/// page can be added without declaring a handler.
+///
+/// The templates whose names start with the directory and end with the
+/// extension are served at their path relative to the directory, without
+/// the extension:
+///
+/// | Template                         | Route          |
+/// |----------------------------------|----------------|
+/// | `pages/index.html.tera`          | `/`            |
+/// | `pages/about.html.tera`          | `/about`       |
+/// | `pages/blog/index.html.tera`     | `/blog`        |
+/// | `pages/blog/[slug].html.tera`    | `/blog/:slug`  |
+/// | `pages/docs/[...path].html.tera` | `/docs/*path`  |
+///
+/// The static segments have priority over the `[name]` parameters, which
+/// have priority over the `[...name]` parameters, catching the rest of the
+/// path. The parameters are inserted in the context as `params`, which is
+/// built by the [`PageContext`] set with [`context`](Pages::context).
+///
+/// The templates are those of the
+/// [`TeraTemplating`](crate::tera::TeraTemplating) middleware, which must be
+/// applied outside of this endpoint, so the pages added by a reload are
+/// served without restarting the server.
+///
+/// # Example
+///
+/// ```no_compile
+/// use poem::{
+///     error::NotFoundError,
+///     tera::{Context, Page, Pages, TeraTemplating},
+///     EndpointExt, Request, Route,
+/// };
+///
+/// let pages = Pages::new("pages").context(|_req: &Request, page: &Page| {
+///     let mut context = Context::new();
+///     if let Some(slug) = page.param("slug") {
+///         let post = load_post(slug).ok_or(NotFoundError)?;
+///         context.insert("post", &post);
+///     }
+///     Ok(context)
+/// });
+///
+/// let app = Route::new()
+///     .nest("/", pages)
+///     .with(TeraTemplating::from_directory("templates"));
+/// ```
+#[derive(Clone)]
+pub struct Pages {
+    directory: String,
+    extension: String,
+    context: Option<Arc<dyn PageContext>>,
+}
+
+impl Pages {
+    /// Create a `Pages` endpoint serving the templates of `directory`.
+    pub fn new(directory: impl Into<String>) -> Self {
+        Self {
+            directory: directory.into().trim_matches('/').to_string(),
+            extension: ".html.tera".to_string(),
+            context: None,
+        }
+    }
+
+    /// Sets the extension of the templates of the pages.
+    ///
+    /// Default is `.html.tera`.
+    #[must_use]
+    pub fn extension(self, extension: impl Into<String>) -> Self {
+        Self {
+            extension: extension.into(),
+            ..self
+        }
+    }
+
+    /// Sets the provider of the context of the pages.
+    #[must_use]
+    pub fn context(self, context: impl PageContext) -> Self {
+        Self {
+            context: Some(Arc::new(context)),
+            ..self
+        }
+    }
+
+    /// Returns the segments of the route of the template `name`, or `None` if
+    /// it is not a page.
+    fn route<'a>(&self, name: &'a str) -> Option<Vec<(SegmentKind, &'a str)>> {
+        let path = match self.directory.as_str() {
+            "" => name,
+            directory => name.strip_prefix(directory)?.strip_prefix('/')?,
+        };
+        let path = path.strip_suffix(&self.extension)?;
+        let mut segments = path
+            .split('/')
+            .map(|segment| {
+                match segment
+                    .strip_prefix('[')
+                    .and_then(|segment| segment.strip_suffix(']'))
+                {
+                    Some(name) => match name.strip_prefix("...") {
+                        Some(name) => (SegmentKind::CatchAll, name),
+                        None => (SegmentKind::Param, name),
+                    },
+                    None => (SegmentKind::Static, segment),
+                }
+            })
+            .collect::<Vec<_>>();
+        if segments.last() == Some(&(SegmentKind::Static, "index")) {
+            segments.pop();
+        }
+        Some(segments)
+    }
+
+    /// Finds the page matching `path` among the templates of `tera`.
+    fn find(&self, tera: &Tera, path: &str) -> Option<Page> {
+        let path = path.trim_matches('/');
+        let path = if path.is_empty() {
+            Vec::new()
+        } else {
+            path.split('/').collect()
+        };
+        let mut found: Option<(Vec<SegmentKind>, Page)> = None;
+
+        for name in tera.get_template_names() {
+            let route = match self.route(name) {
+                Some(route) => route,
+                None => continue,
+            };
+            let params = match match_route(&route, &path) {
+                Some(params) => params,
+                None => continue,
+            };
+            let rank = route.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+            if found.as_ref().map_or(true, |(found, _)| rank < *found) {
+                found = Some((
+                    rank,
+                    Page {
+                        template: name.to_string(),
+                        params,
+                    },
+                ));
+            }
+        }
+
+        found.map(|(_, page)| page)
+    }
+}
+
+fn match_route(route: &[(SegmentKind, &str)], path: &[&str]) -> Option<Vec<(String, String)>> {
+    let decode = |segment: &str| percent_decode_str(segment).decode_utf8_lossy().into_owned();
+    let mut params = Vec::new();
+
+    for (idx, (kind, name)) in route.iter().enumerate() {
+        match kind {
+            SegmentKind::Static if path.get(idx) == Some(name) => {}
+            SegmentKind::Static => return None,
+            SegmentKind::Param => {
+                params.push((name.to_string(), decode(path.get(idx)?)));
+            }
+            SegmentKind::CatchAll if idx + 1 == route.len() && idx < path.len() => {
+                params.push((name.to_string(), decode(&path[idx..].join("/"))));
+                return Some(params);
+            }
+            SegmentKind::CatchAll => return None,
+        }
+    }
+
+    (route.len() == path.len()).then_some(params)
+}
+
+#[async_trait::async_trait]
+impl Endpoint for Pages {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into());
+        }
+
+        // fails with the error of the extractor when the middleware is missing
+        let tera = Tera::from_request_without_body(&req).await?;
+        let page = self.find(&tera, req.uri().path()).ok_or(NotFoundError)?;
+
+        let mut context = match &self.context {
+            Some(provider) => provider.context(&req, &page).await?,
+            None => Context::new(),
+        };
+        context.insert(
+            "params",
+            &page.params.iter().cloned().collect::<BTreeMap<_, _>>(),
+        );
+
+        let html: Html<String> = super::render(&tera, &page.template, &context).into_result()?;
+        Ok(html.into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tera::TeraTemplating, test::TestClient, EndpointExt, Route};
+
+    fn tera() -> Tera {
+        let mut tera = Tera::default();
+        tera.add_raw_templates([
+            ("pages/index.html.tera", "home"),
+            ("pages/about.html.tera", "about"),
+            ("pages/blog/index.html.tera", "blog"),
+            ("pages/blog/new.html.tera", "new post"),
+            ("pages/blog/[slug].html.tera", "post {{ params.slug }}"),
+            ("pages/docs/[...path].html.tera", "doc {{ params.path }}"),
+            ("pages/partial.html", "partial"),
+            ("layout.html.tera", "layout"),
+        ])
+        .unwrap();
+        tera
+    }
+
+    #[tokio::test]
+    async fn routes() {
+        let app = Route::new()
+            .nest("/site", Pages::new("pages"))
+            .with(TeraTemplating::custom(tera()));
+        let cli = TestClient::new(app);
+
+        for (path, text) in [
+            ("/site", "home"),
+            ("/site/about", "about"),
+            ("/site/blog/", "blog"),
+            ("/site/blog/new", "new post"),
+            ("/site/blog/hello%20world", "post hello world"),
+            ("/site/docs/a/b", "doc a/b"),
+        ] {
+            let resp = cli.get(path).send().await;
+            resp.assert_status_is_ok();
+            resp.assert_content_type("text/html; charset=utf-8");
+            resp.assert_text(text).await;
+        }
+
+        for path in [
+            "/site/partial",
+            "/site/layout",
+            "/site/docs",
+            "/site/blog/a/b",
+        ] {
+            cli.get(path)
+                .send()
+                .await
+                .assert_status(StatusCode::NOT_FOUND);
+        }
+        cli.post("/site/about")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn context() {
+        let pages = Pages::new("pages").context(|req: &Request, page: &Page| {
+            if page.param("slug") == Some("missing") {
+                return Err(NotFoundError.into());
+            }
+            let mut context = Context::new();
+            context.insert("path", req.uri().path());
+            Ok(context)
+        });
+        let mut tera = Tera::default();
+        tera.add_raw_template("pages/[slug].html", "{{ path }} {{ params.slug }}")
+            .unwrap();
+        let cli = TestClient::new(pages.extension(".html").with(TeraTemplating::custom(tera)));
+
+        cli.get("/hello")
+            .send()
+            .await
+            .assert_text("&#x2F;hello hello")
+            .await;
+        cli.get("/missing")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
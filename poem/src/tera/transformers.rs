@@ -35,7 +35,7 @@ pub mod filters {
 
         impl Filter for TranslateFilter {
             fn filter(&self, id: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
-                if args.len() == 0 {
+                if args.is_empty() {
                     self.locale.text(id.as_str().unwrap())
                 } else {
                     let mut fluent_args = HashMap::new();
@@ -56,8 +56,8 @@ pub mod filters {
                     self.locale
                         .text_with_args(id.as_str().unwrap(), fluent_args)
                 }
-                .map(|str| Value::String(str))
-                .map_err(|err| tera::Error::msg(err))
+                .map(Value::String)
+                .map_err(tera::Error::msg)
             }
         }
 
@@ -86,3 +86,553 @@ pub mod filters {
         }
     }
 }
+
+/// Tera Templating built-in functions
+pub mod functions {
+    use std::collections::HashMap;
+
+    use tera::{self, Function, Tera, Value};
+
+    #[cfg(feature = "cookie")]
+    use crate::web::Experiments;
+    use crate::{
+        endpoint::SiteIcons,
+        route::BreadcrumbsSlot,
+        tera::{assets::preload_destination, AssetManifest},
+        web::{Flags, ListParams, Preload, PreviewMode},
+        Request,
+    };
+
+    /// Tera Templating feature flag function
+    pub struct FeatureFunction {
+        flags: Flags,
+    }
+
+    impl Function for FeatureFunction {
+        fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+            match args.get("name").and_then(Value::as_str) {
+                Some(name) => Ok(Value::Bool(self.flags.is_enabled(name))),
+                None => Err(tera::Error::msg(
+                    "the `feature` function requires a `name` string argument",
+                )),
+            }
+        }
+
+        fn is_safe(&self) -> bool {
+            true
+        }
+    }
+
+    /// Registers the `feature` function, which returns `true` if a flag of
+    /// the [`FeatureFlags`](crate::middleware::FeatureFlags) middleware is
+    /// enabled, such as `{% if feature(name="new_ui") %}`.
+    ///
+    /// The `FeatureFlags` middleware must be applied outside of the
+    /// `TeraTemplating` middleware, otherwise all the flags are disabled.
+    ///
+    /// ```no_compile
+    /// use poem::{Route, EndpointExt, middleware::FeatureFlags, tera::{TeraTemplating, functions}};
+    ///
+    /// let app = Route::new()
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::feature)
+    ///     .with(FeatureFlags::new().flag("new_ui", true));
+    /// ```
+    pub fn feature(tera: &mut Tera, req: &mut Request) {
+        tera.register_function(
+            "feature",
+            FeatureFunction {
+                flags: req.extensions().get::<Flags>().cloned().unwrap_or_default(),
+            },
+        );
+    }
+
+    /// Tera Templating preview function
+    pub struct PreviewFunction {
+        enabled: bool,
+    }
+
+    impl Function for PreviewFunction {
+        fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+            Ok(Value::Bool(self.enabled))
+        }
+
+        fn is_safe(&self) -> bool {
+            true
+        }
+    }
+
+    /// Registers the `preview` function, which returns `true` if the
+    /// [`PreviewMode`] of the request is enabled, such as
+    /// `{% if preview() %}<div class="banner">Draft</div>{% endif %}`.
+    ///
+    /// The `Preview` middleware must be applied outside of the
+    /// `TeraTemplating` middleware, otherwise the preview mode is disabled.
+    ///
+    /// ```no_compile
+    /// use poem::{Route, EndpointExt, middleware::Preview, tera::{TeraTemplating, functions}};
+    ///
+    /// let app = Route::new()
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::preview)
+    ///     .with(Preview::new(b"secret"));
+    /// ```
+    pub fn preview(tera: &mut Tera, req: &mut Request) {
+        tera.register_function(
+            "preview",
+            PreviewFunction {
+                enabled: PreviewMode::of(req),
+            },
+        );
+    }
+
+    /// Tera Templating asset function
+    pub struct AssetFunction {
+        manifest: AssetManifest,
+        preload: Option<Preload>,
+    }
+
+    impl Function for AssetFunction {
+        fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+            let path = args.get("path").and_then(Value::as_str).ok_or_else(|| {
+                tera::Error::msg("the `asset` function requires a `path` string argument")
+            })?;
+            let url = self.manifest.url(path);
+
+            let preload = args
+                .get("preload")
+                .and_then(Value::as_bool)
+                .unwrap_or_else(|| self.manifest.is_preload());
+            if let (true, Some(hints)) = (preload, &self.preload) {
+                if let Some(destination) = preload_destination(&url) {
+                    hints.preload(&url, destination);
+                }
+            }
+
+            Ok(Value::String(url))
+        }
+
+        fn is_safe(&self) -> bool {
+            true
+        }
+    }
+
+    /// Registers the `asset` function, which returns the fingerprinted URL of
+    /// an asset of the [`AssetManifest`], such as `<link rel="stylesheet"
+    /// href="{{ asset(path="app.css") }}">`.
+    ///
+    /// If the [`EarlyHints`](crate::middleware::EarlyHints) middleware is
+    /// applied, the stylesheets and the scripts used by the template are also
+    /// hinted with `Link: rel=preload` headers, so that the clients start
+    /// fetching them before the page is parsed. The hints are disabled with
+    /// [`AssetManifest::preload`], for a single asset with
+    /// `asset(path="app.js", preload=false)`, or for a route with the
+    /// [`NoPreload`](crate::middleware::NoPreload) middleware.
+    ///
+    /// The manifest and the `EarlyHints` middleware must be applied outside of
+    /// the `TeraTemplating` middleware.
+    ///
+    /// ```no_compile
+    /// use poem::{Route, EndpointExt, middleware::EarlyHints, tera::{AssetManifest, TeraTemplating, functions}};
+    ///
+    /// let manifest = AssetManifest::from_json(include_str!("../dist/manifest.json"))?.prefix("/static");
+    /// let app = Route::new()
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::asset)
+    ///     .with(EarlyHints)
+    ///     .data(manifest);
+    /// ```
+    pub fn asset(tera: &mut Tera, req: &mut Request) {
+        tera.register_function(
+            "asset",
+            AssetFunction {
+                manifest: req
+                    .extensions()
+                    .get::<AssetManifest>()
+                    .cloned()
+                    .unwrap_or_default(),
+                preload: req.extensions().get::<Preload>().cloned(),
+            },
+        );
+    }
+
+    /// Tera Templating list URL function
+    pub struct ListUrlFunction {
+        path: String,
+        params: Option<ListParams>,
+    }
+
+    impl Function for ListUrlFunction {
+        fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+            let mut params = self.params.clone().ok_or_else(|| {
+                tera::Error::msg("the `list_url` function requires valid list parameters")
+            })?;
+            if let Some(field) = args.get("sort") {
+                let field = field.as_str().ok_or_else(|| {
+                    tera::Error::msg("the `sort` argument of `list_url` must be a string")
+                })?;
+                params = params.sort_by(field);
+            }
+            if let Some(page) = args.get("page") {
+                let page = page.as_u64().ok_or_else(|| {
+                    tera::Error::msg("the `page` argument of `list_url` must be an integer")
+                })?;
+                params = params.page(page.try_into().unwrap_or(u32::MAX));
+            }
+            Ok(Value::String(params.url(&self.path)))
+        }
+    }
+
+    /// Registers the `list_url` function, which returns the URL of the
+    /// current list with the [`ListParams`] of the request, changing the page
+    /// with `{{ list_url(page=2) }}`, or the order with `{{
+    /// list_url(sort="name") }}`, which toggles the direction of the field if
+    /// the list is already sorted by it.
+    ///
+    /// The [`ListParamsConfig`](crate::web::ListParamsConfig) must be applied
+    /// outside of the `TeraTemplating` middleware.
+    ///
+    /// ```no_compile
+    /// use poem::{get, Route, EndpointExt, web::ListParamsConfig, tera::{TeraTemplating, functions}};
+    ///
+    /// let app = Route::new()
+    ///     .at("/issues", get(issues))
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::list_url)
+    ///     .data(ListParamsConfig::new().sortable(["created_at", "title"]));
+    /// ```
+    pub fn list_url(tera: &mut Tera, req: &mut Request) {
+        tera.register_function(
+            "list_url",
+            ListUrlFunction {
+                path: req.uri().path().to_string(),
+                params: ListParams::from_request_without_body(req).ok(),
+            },
+        );
+    }
+
+    /// Tera Templating site icons function
+    pub struct SiteIconsFunction {
+        icons: SiteIcons,
+    }
+
+    impl Function for SiteIconsFunction {
+        fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+            Ok(Value::String(self.icons.link_tags()))
+        }
+
+        fn is_safe(&self) -> bool {
+            true
+        }
+    }
+
+    /// Registers the `site_icons` function, which returns the `<link>` tags
+    /// of the [`SiteIcons`] endpoint, such as `<head>{{ site_icons() }}</head>`.
+    ///
+    /// The `SiteIcons` must be added to the requests with
+    /// [`EndpointExt::data`](crate::EndpointExt::data) outside of the
+    /// `TeraTemplating` middleware.
+    ///
+    /// ```no_compile
+    /// use poem::{Route, EndpointExt, endpoint::SiteIcons, tera::{TeraTemplating, functions}};
+    ///
+    /// let icons = SiteIcons::new().favicon(include_bytes!("../static/favicon.ico").as_ref());
+    /// let app = Route::new()
+    ///     .at("/", get(index))
+    ///     .nest("/icons", icons.clone().prefix("/icons"))
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::site_icons)
+    ///     .data(icons.prefix("/icons"));
+    /// ```
+    pub fn site_icons(tera: &mut Tera, req: &mut Request) {
+        tera.register_function(
+            "site_icons",
+            SiteIconsFunction {
+                icons: req
+                    .extensions()
+                    .get::<SiteIcons>()
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+        );
+    }
+
+    /// Tera Templating breadcrumbs function
+    pub struct BreadcrumbsFunction {
+        slot: BreadcrumbsSlot,
+    }
+
+    impl Function for BreadcrumbsFunction {
+        fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+            Ok(Value::String(self.slot.0.lock().to_html()))
+        }
+
+        fn is_safe(&self) -> bool {
+            true
+        }
+    }
+
+    /// Registers the `breadcrumbs` function, which renders the
+    /// [`Breadcrumbs`](crate::Breadcrumbs) trail of the matched route as a
+    /// `<nav>` element, such as `{{ breadcrumbs() }}`.
+    ///
+    /// The breadcrumbs are declared with
+    /// [`Route::breadcrumb`](crate::Route::breadcrumb), the trail being filled
+    /// once the route is matched, so the `TeraTemplating` middleware can be
+    /// applied outside of the `Route`.
+    ///
+    /// ```no_compile
+    /// use poem::{get, Breadcrumb, Route, EndpointExt, tera::{TeraTemplating, functions}};
+    ///
+    /// let app = Route::new()
+    ///     .at_named("home", "/", get(index))
+    ///     .at_named("users", "/users", get(users))
+    ///     .breadcrumb("home", Breadcrumb::new("Home"))
+    ///     .breadcrumb("users", Breadcrumb::new("Users").parent("home"))
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::breadcrumbs);
+    /// ```
+    pub fn breadcrumbs(tera: &mut Tera, req: &mut Request) {
+        let slot = BreadcrumbsSlot::default();
+        req.set_data(slot.clone());
+        tera.register_function("breadcrumbs", BreadcrumbsFunction { slot });
+    }
+
+    /// Tera Templating experiment variant function
+    #[cfg(feature = "cookie")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
+    pub struct VariantFunction {
+        experiments: Experiments,
+    }
+
+    #[cfg(feature = "cookie")]
+    impl Function for VariantFunction {
+        fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+            match args.get("experiment").and_then(Value::as_str) {
+                Some(experiment) => Ok(self
+                    .experiments
+                    .variant(experiment)
+                    .map(|variant| Value::String(variant.to_string()))
+                    .unwrap_or(Value::Null)),
+                None => Err(tera::Error::msg(
+                    "the `variant` function requires an `experiment` string argument",
+                )),
+            }
+        }
+
+        fn is_safe(&self) -> bool {
+            true
+        }
+    }
+
+    /// Registers the `variant` function, which returns the variant of an
+    /// experiment of the [`Experiment`](crate::middleware::Experiment)
+    /// middleware, such as `{% if variant(experiment="checkout") == "one_page"
+    /// %}`.
+    ///
+    /// The `Experiment` middleware must be applied outside of the
+    /// `TeraTemplating` middleware, otherwise the function returns `null`.
+    ///
+    /// ```no_compile
+    /// use poem::{Route, EndpointExt, middleware::Experiment, tera::{TeraTemplating, functions}};
+    ///
+    /// let app = Route::new()
+    ///     .with(TeraTemplating::from_glob("templates/**/*"))
+    ///     .using(functions::variant)
+    ///     .with(Experiment::new("checkout").variant("control", 1).variant("one_page", 1));
+    /// ```
+    #[cfg(feature = "cookie")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
+    pub fn variant(tera: &mut Tera, req: &mut Request) {
+        tera.register_function(
+            "variant",
+            VariantFunction {
+                experiments: req
+                    .extensions()
+                    .get::<Experiments>()
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+        );
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{
+            handler, middleware::FeatureFlags, tera::TeraTemplating, test::TestClient, EndpointExt,
+        };
+
+        #[tokio::test]
+        async fn feature_function() {
+            #[handler(internal)]
+            fn index(mut tera: Tera) -> tera::Result<String> {
+                tera.render_str(
+                    r#"{{ feature(name="a") }},{{ feature(name="b") }}"#,
+                    &Default::default(),
+                )
+            }
+
+            let app = index
+                .with(TeraTemplating::custom(Tera::default()))
+                .using(feature)
+                .with(FeatureFlags::new().flag("a", true));
+            TestClient::new(app)
+                .get("/")
+                .send()
+                .await
+                .assert_text("true,false")
+                .await;
+        }
+
+        #[tokio::test]
+        async fn asset_function() {
+            use crate::{
+                middleware::{EarlyHints, NoPreload},
+                Route,
+            };
+
+            #[handler(internal)]
+            fn index(mut tera: Tera) -> tera::Result<String> {
+                tera.render_str(
+                    r#"{{ asset(path="app.css") }},{{ asset(path="app.js", preload=false) }},{{ asset(path="logo.png") }}"#,
+                    &Default::default(),
+                )
+            }
+
+            let app = Route::new()
+                .at("/", index)
+                .at("/plain", index.with(NoPreload))
+                .with(TeraTemplating::custom(Tera::default()))
+                .using(asset)
+                .with(EarlyHints)
+                .data(
+                    AssetManifest::new()
+                        .asset("app.css", "app.123.css")
+                        .asset("app.js", "app.456.js")
+                        .prefix("/static"),
+                );
+            let cli = TestClient::new(app);
+
+            let resp = cli.get("/").send().await;
+            resp.assert_header_all("link", ["</static/app.123.css>; rel=preload; as=style"]);
+            resp.assert_text("/static/app.123.css,/static/app.456.js,/static/logo.png")
+                .await;
+
+            cli.get("/plain")
+                .send()
+                .await
+                .assert_header_is_not_exist("link");
+        }
+
+        #[tokio::test]
+        async fn list_url_function() {
+            use crate::web::ListParamsConfig;
+
+            #[handler(internal)]
+            fn index(mut tera: Tera) -> tera::Result<String> {
+                tera.render_str(
+                    r#"{{ list_url(page=2) }} {{ list_url(sort="name") }}"#,
+                    &Default::default(),
+                )
+            }
+
+            let app = index
+                .with(TeraTemplating::custom(Tera::default()))
+                .using(list_url)
+                .data(
+                    ListParamsConfig::new()
+                        .sortable(["name", "id"])
+                        .filterable(["status"]),
+                );
+            let cli = TestClient::new(app);
+            cli.get("/issues")
+                .query("sort", &"name")
+                .query("filter[status]", &"open")
+                .send()
+                .await
+                .assert_text(
+                    "/issues?sort=name&filter%5Bstatus%5D=open&page=2&per_page=20 \
+                     /issues?sort=-name&filter%5Bstatus%5D=open&page=1&per_page=20",
+                )
+                .await;
+            cli.get("/issues")
+                .query("sort", &"secret")
+                .send()
+                .await
+                .assert_status(http::StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        #[tokio::test]
+        async fn site_icons_function() {
+            #[handler(internal)]
+            fn index(mut tera: Tera) -> tera::Result<String> {
+                tera.render_str("{{ site_icons() }}", &Default::default())
+            }
+
+            let app = index
+                .with(TeraTemplating::custom(Tera::default()))
+                .using(site_icons)
+                .data(SiteIcons::new().prefix("/icons").theme_color("#fff"));
+            TestClient::new(app)
+                .get("/")
+                .send()
+                .await
+                .assert_text(
+                    "<link rel=\"manifest\" href=\"/icons/site.webmanifest\">\n\
+                     <meta name=\"theme-color\" content=\"#fff\">",
+                )
+                .await;
+        }
+
+        #[tokio::test]
+        async fn breadcrumbs_function() {
+            use crate::{Breadcrumb, Route};
+
+            #[handler(internal)]
+            fn index(mut tera: Tera) -> tera::Result<String> {
+                tera.render_str("{{ breadcrumbs() }}", &Default::default())
+            }
+
+            let app = Route::new()
+                .at_named("home", "/", index)
+                .at_named("about", "/about", index)
+                .breadcrumb("home", Breadcrumb::new("Home"))
+                .breadcrumb("about", Breadcrumb::new("About").parent("home"))
+                .with(TeraTemplating::custom(Tera::default()))
+                .using(breadcrumbs);
+            TestClient::new(app)
+                .get("/about")
+                .send()
+                .await
+                .assert_text(
+                    "<nav aria-label=\"breadcrumb\"><ol><li><a href=\"/\">Home</a></li>\
+                     <li aria-current=\"page\">About</li></ol></nav>",
+                )
+                .await;
+        }
+
+        #[cfg(feature = "cookie")]
+        #[tokio::test]
+        async fn variant_function() {
+            #[handler(internal)]
+            fn index(mut tera: Tera) -> tera::Result<String> {
+                tera.render_str(
+                    r#"{{ variant(experiment="a") }},{{ variant(experiment="b") }}"#,
+                    &Default::default(),
+                )
+            }
+
+            let app = index
+                .with(TeraTemplating::custom(Tera::default()))
+                .using(variant)
+                .with(crate::middleware::Experiment::new("a").variant("x", 1));
+            TestClient::new(app)
+                .get("/")
+                .send()
+                .await
+                .assert_text("x,")
+                .await;
+        }
+    }
+}
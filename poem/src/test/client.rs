@@ -1,6 +1,13 @@
 use http::{header, header::HeaderName, HeaderMap, HeaderValue, Method};
 
-use crate::{test::TestRequestBuilder, Endpoint, IntoEndpoint};
+#[cfg(feature = "websocket")]
+use crate::test::TestWebSocket;
+#[cfg(feature = "cookie")]
+use crate::web::cookie::{Cookie, CookieJar};
+use crate::{
+    test::{TestLoad, TestRequestBuilder},
+    Endpoint, IntoEndpoint, Request, Response,
+};
 
 macro_rules! impl_methods {
     ($($(#[$docs:meta])* ($name:ident, $method:ident)),*) => {
@@ -17,6 +24,9 @@ macro_rules! impl_methods {
 pub struct TestClient<E> {
     pub(crate) ep: E,
     pub(crate) default_headers: HeaderMap,
+    #[cfg(feature = "cookie")]
+    pub(crate) cookie_jar: Option<CookieJar>,
+    pub(crate) max_redirects: usize,
 }
 
 impl<E: Endpoint> TestClient<E> {
@@ -28,6 +38,9 @@ impl<E: Endpoint> TestClient<E> {
         TestClient {
             ep: ep.into_endpoint(),
             default_headers: Default::default(),
+            #[cfg(feature = "cookie")]
+            cookie_jar: None,
+            max_redirects: 0,
         }
     }
 
@@ -77,6 +90,211 @@ impl<E: Endpoint> TestClient<E> {
         self.default_header(header::CONTENT_TYPE, content_type.as_ref())
     }
 
+    /// Stores the cookies set by the responses and sends them with the next
+    /// requests, like a browser.
+    ///
+    /// Only the names, values and expiration of the cookies are taken into
+    /// account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poem::{
+    ///     handler,
+    ///     middleware::CookieJarManager,
+    ///     test::TestClient,
+    ///     web::cookie::{Cookie, CookieJar},
+    ///     EndpointExt, Route,
+    /// };
+    ///
+    /// #[handler]
+    /// fn login(cookie_jar: &CookieJar) {
+    ///     cookie_jar.add(Cookie::new_with_str("user", "sunli"));
+    /// }
+    ///
+    /// #[handler]
+    /// fn index(cookie_jar: &CookieJar) -> String {
+    ///     cookie_jar
+    ///         .get("user")
+    ///         .map(|cookie| cookie.value_str().to_string())
+    ///         .unwrap_or_default()
+    /// }
+    ///
+    /// let app = Route::new()
+    ///     .at("/login", login)
+    ///     .at("/", index)
+    ///     .with(CookieJarManager::new());
+    /// let cli = TestClient::new(app).cookie_store();
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// cli.post("/login").send().await.assert_status_is_ok();
+    /// cli.get("/").send().await.assert_text("sunli").await;
+    /// assert!(cli.cookie_jar().unwrap().get("user").is_some());
+    /// # });
+    /// ```
+    #[cfg(feature = "cookie")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
+    #[must_use]
+    pub fn cookie_store(self) -> Self {
+        Self {
+            cookie_jar: Some(Default::default()),
+            ..self
+        }
+    }
+
+    /// Returns the cookies stored since [`TestClient::cookie_store`] has been
+    /// called.
+    #[cfg(feature = "cookie")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
+    pub fn cookie_jar(&self) -> Option<&CookieJar> {
+        self.cookie_jar.as_ref()
+    }
+
+    /// Follows the redirections of the responses, up to `max_redirects` times.
+    ///
+    /// The `301`, `302` and `303` redirections of the requests other than
+    /// `GET` and `HEAD` are followed with a `GET` request without body, the
+    /// others with the same method and body. Sending a request panics if it is
+    /// redirected more than `max_redirects` times.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poem::{handler, test::TestClient, web::Redirect, Route};
+    ///
+    /// #[handler]
+    /// fn login() -> Redirect {
+    ///     Redirect::see_other("/home")
+    /// }
+    ///
+    /// #[handler]
+    /// fn home() -> &'static str {
+    ///     "home"
+    /// }
+    ///
+    /// let app = Route::new().at("/login", login).at("/home", home);
+    /// let cli = TestClient::new(app).follow_redirects(5);
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// cli.post("/login").send().await.assert_text("home").await;
+    /// # });
+    /// ```
+    #[must_use]
+    pub fn follow_redirects(self, max_redirects: usize) -> Self {
+        Self {
+            max_redirects,
+            ..self
+        }
+    }
+
+    /// Sends a request with the stored cookies and stores the cookies of the
+    /// response.
+    pub(crate) async fn call(&self, req: Request) -> Response {
+        #[cfg(feature = "cookie")]
+        let req = self.add_cookies(req);
+        let resp = self.ep.get_response(req).await;
+        #[cfg(feature = "cookie")]
+        self.store_cookies(&resp);
+        resp
+    }
+
+    #[cfg(feature = "cookie")]
+    fn add_cookies(&self, mut req: Request) -> Request {
+        if let Some(cookie_jar) = &self.cookie_jar {
+            let cookies = cookie_jar.with_cookies(|cookies| {
+                cookies
+                    .map(|cookie| cookie.encoded().stripped().to_string())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            });
+            if !cookies.is_empty() {
+                if let Ok(value) = HeaderValue::from_str(&cookies) {
+                    req.headers_mut().append(header::COOKIE, value);
+                }
+            }
+        }
+        req
+    }
+
+    #[cfg(feature = "cookie")]
+    fn store_cookies(&self, resp: &Response) {
+        let cookie_jar = match &self.cookie_jar {
+            Some(cookie_jar) => cookie_jar,
+            None => return,
+        };
+        for value in resp.headers().get_all(header::SET_COOKIE) {
+            let cookie = match value.to_str().ok().and_then(|s| Cookie::parse(s).ok()) {
+                Some(cookie) => cookie,
+                None => continue,
+            };
+            let expired = cookie.max_age() == Some(std::time::Duration::ZERO)
+                || cookie
+                    .expires()
+                    .map_or(false, |expires| expires <= chrono::Utc::now());
+            if expired {
+                cookie_jar.remove(cookie.name());
+            } else {
+                cookie_jar.add(cookie);
+            }
+        }
+    }
+
+    /// Connects a WebSocket to the endpoint.
+    ///
+    /// Use [`TestRequestBuilder::websocket`] to set the headers or the query
+    /// string of the handshake request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint does not accept the WebSocket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures_util::{SinkExt, StreamExt};
+    /// use poem::{
+    ///     get, handler,
+    ///     test::TestClient,
+    ///     web::websocket::{Message, WebSocket},
+    ///     IntoResponse, Route,
+    /// };
+    ///
+    /// #[handler]
+    /// fn echo(ws: WebSocket) -> impl IntoResponse {
+    ///     ws.on_upgrade(|mut socket| async move {
+    ///         while let Some(Ok(Message::Text(text))) = socket.next().await {
+    ///             let _ = socket.send(Message::text(text)).await;
+    ///         }
+    ///     })
+    /// }
+    ///
+    /// let app = Route::new().at("/ws", get(echo));
+    /// let cli = TestClient::new(app);
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let mut ws = cli.websocket("/ws").await;
+    /// ws.send_text("hello").await;
+    /// ws.assert_text("hello").await;
+    /// ws.close().await;
+    /// # });
+    /// ```
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub async fn websocket(&self, uri: impl Into<String>) -> TestWebSocket {
+        self.get(uri).websocket().await
+    }
+
+    /// Create a [`TestLoad`] sending the concurrent requests created by
+    /// `make_request`, which receives the index of the request.
+    ///
+    /// See [`TestLoad`] for an example.
+    pub fn load<'a>(
+        &'a self,
+        make_request: impl for<'b> Fn(&'b TestClient<E>, usize) -> TestRequestBuilder<'b, E> + 'a,
+    ) -> TestLoad<'a, E> {
+        TestLoad::new(self, make_request)
+    }
+
     /// Create a [`TestRequestBuilder`].
     pub fn request(&self, method: Method, uri: impl Into<String>) -> TestRequestBuilder<'_, E> {
         TestRequestBuilder::new(self, method, uri.into())
@@ -103,3 +321,114 @@ impl<E: Endpoint> TestClient<E> {
         (trace, TRACE)
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, web::Redirect, Route};
+
+    #[handler(internal)]
+    fn echo(method: Method, body: String) -> String {
+        format!("{method} {body}")
+    }
+
+    #[handler(internal)]
+    fn temporary() -> Redirect {
+        Redirect::temporary("echo")
+    }
+
+    #[handler(internal)]
+    fn see_other() -> Redirect {
+        Redirect::see_other("/a/echo")
+    }
+
+    #[handler(internal)]
+    fn redirect_loop() -> Redirect {
+        Redirect::see_other("/loop")
+    }
+
+    #[tokio::test]
+    async fn follow_redirects() {
+        let app = Route::new()
+            .at("/a/echo", echo)
+            .at("/a/temporary", temporary)
+            .at("/see_other", see_other)
+            .at("/loop", redirect_loop);
+
+        let cli = TestClient::new(app).follow_redirects(2);
+        cli.post("/a/temporary")
+            .body("hello")
+            .send()
+            .await
+            .assert_text("POST hello")
+            .await;
+        cli.post("/see_other")
+            .body("hello")
+            .send()
+            .await
+            .assert_text("GET ")
+            .await;
+
+        let cli = TestClient {
+            max_redirects: 0,
+            ..cli
+        };
+        cli.post("/see_other")
+            .send()
+            .await
+            .assert_status(StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "too many redirections")]
+    async fn too_many_redirects() {
+        let cli = TestClient::new(Route::new().at("/loop", redirect_loop)).follow_redirects(3);
+        cli.get("/loop").send().await;
+    }
+
+    #[cfg(feature = "cookie")]
+    #[tokio::test]
+    async fn cookie_store() {
+        use crate::{
+            middleware::CookieJarManager,
+            web::cookie::{Cookie, CookieJar},
+            EndpointExt,
+        };
+
+        #[handler(internal)]
+        fn login(cookie_jar: &CookieJar) -> Redirect {
+            cookie_jar.add(Cookie::new_with_str("user", "sunli"));
+            Redirect::see_other("/")
+        }
+
+        #[handler(internal)]
+        fn logout(cookie_jar: &CookieJar) {
+            let mut cookie = Cookie::named("user");
+            cookie.make_removal();
+            cookie_jar.add(cookie);
+        }
+
+        #[handler(internal)]
+        fn index(cookie_jar: &CookieJar) -> String {
+            cookie_jar
+                .get("user")
+                .map(|cookie| cookie.value_str().to_string())
+                .unwrap_or_default()
+        }
+
+        let app = Route::new()
+            .at("/login", login)
+            .at("/logout", logout)
+            .at("/", index)
+            .with(CookieJarManager::new());
+        let cli = TestClient::new(app).cookie_store().follow_redirects(1);
+
+        cli.post("/login").send().await.assert_text("sunli").await;
+        cli.get("/").send().await.assert_text("sunli").await;
+        cli.post("/logout").send().await.assert_status_is_ok();
+        assert!(cli.cookie_jar().unwrap().get("user").is_none());
+        cli.get("/").send().await.assert_text("").await;
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use futures_util::StreamExt;
+use http::StatusCode;
+
+use crate::{
+    test::{TestClient, TestRequestBuilder},
+    Endpoint,
+};
+
+type MakeRequestFn<'a, E> =
+    Box<dyn for<'b> Fn(&'b TestClient<E>, usize) -> TestRequestBuilder<'b, E> + 'a>;
+
+/// A run of concurrent synthetic requests, created by
+/// [`TestClient::load`](crate::test::TestClient::load).
+///
+/// The latency of a request includes the time to read the whole response
+/// body.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{handler, test::TestClient};
+///
+/// #[handler]
+/// fn index() -> &'static str {
+///     "hello"
+/// }
+///
+/// let cli = TestClient::new(index);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let report = cli
+///     .load(|cli, _| cli.get("/"))
+///     .requests(1000)
+///     .concurrency(50)
+///     .run()
+///     .await;
+/// assert_eq!(report.errors(), 0);
+/// assert!(report.percentile(99.0) < Duration::from_secs(1));
+/// # });
+/// ```
+pub struct TestLoad<'a, E> {
+    cli: &'a TestClient<E>,
+    make_request: MakeRequestFn<'a, E>,
+    requests: usize,
+    concurrency: usize,
+}
+
+impl<'a, E: Endpoint> TestLoad<'a, E> {
+    pub(crate) fn new(
+        cli: &'a TestClient<E>,
+        make_request: impl for<'b> Fn(&'b TestClient<E>, usize) -> TestRequestBuilder<'b, E> + 'a,
+    ) -> Self {
+        Self {
+            cli,
+            make_request: Box::new(make_request),
+            requests: 100,
+            concurrency: 10,
+        }
+    }
+
+    /// Sets the total number of requests.
+    ///
+    /// Default is `100`.
+    #[must_use]
+    pub fn requests(self, requests: usize) -> Self {
+        Self { requests, ..self }
+    }
+
+    /// Sets the maximum number of requests in progress.
+    ///
+    /// Default is `10`.
+    #[must_use]
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    /// Sends the requests and returns the report.
+    pub async fn run(self) -> TestLoadReport {
+        let start = Instant::now();
+        let results = futures_util::stream::iter(0..self.requests)
+            .map(|idx| {
+                let req = (self.make_request)(self.cli, idx);
+                async move {
+                    let start = Instant::now();
+                    let resp = req.send().await;
+                    let status = resp.0.status();
+                    let _ = resp.0.into_body().into_bytes().await;
+                    (status, start.elapsed())
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut statuses = BTreeMap::new();
+        let mut latencies = Vec::with_capacity(results.len());
+        for (status, latency) in results {
+            *statuses.entry(status).or_default() += 1;
+            latencies.push(latency);
+        }
+        latencies.sort();
+
+        TestLoadReport {
+            elapsed: start.elapsed(),
+            statuses,
+            latencies,
+        }
+    }
+}
+
+/// The report of a [`TestLoad`].
+#[derive(Debug, Clone)]
+pub struct TestLoadReport {
+    elapsed: Duration,
+    statuses: BTreeMap<StatusCode, usize>,
+    latencies: Vec<Duration>,
+}
+
+impl TestLoadReport {
+    /// Returns the number of requests.
+    pub fn requests(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// Returns the duration of the run.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of requests per second.
+    pub fn throughput(&self) -> f64 {
+        self.requests() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Returns the number of responses with the status code `status`.
+    pub fn status_count(&self, status: StatusCode) -> usize {
+        self.statuses.get(&status).copied().unwrap_or_default()
+    }
+
+    /// Returns the number of responses for each status code.
+    pub fn statuses(&self) -> &BTreeMap<StatusCode, usize> {
+        &self.statuses
+    }
+
+    /// Returns the number of responses with a `4xx` or `5xx` status code.
+    pub fn errors(&self) -> usize {
+        self.statuses
+            .iter()
+            .filter(|(status, _)| status.is_client_error() || status.is_server_error())
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Returns the fraction of the responses with a `4xx` or `5xx` status
+    /// code.
+    pub fn error_rate(&self) -> f64 {
+        self.errors() as f64 / self.requests().max(1) as f64
+    }
+
+    /// Returns the latencies of the requests, sorted from the shortest.
+    pub fn latencies(&self) -> &[Duration] {
+        &self.latencies
+    }
+
+    /// Returns the mean latency.
+    pub fn mean(&self) -> Duration {
+        let total: Duration = self.latencies.iter().sum();
+        total / self.requests().max(1) as u32
+    }
+
+    /// Returns the latency below which the percentage `p` of the requests
+    /// completed, such as `99.0` for the 99th percentile.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.saturating_sub(1)]
+    }
+
+    /// Returns the number of requests in each bucket of a latency
+    /// histogram, whose upper bounds are `bounds` followed by an unbounded
+    /// bucket.
+    pub fn histogram(&self, bounds: &[Duration]) -> Vec<usize> {
+        let mut buckets = vec![0; bounds.len() + 1];
+        for latency in &self.latencies {
+            let idx = bounds
+                .iter()
+                .position(|bound| latency <= bound)
+                .unwrap_or(bounds.len());
+            buckets[idx] += 1;
+        }
+        buckets
+    }
+
+    /// Asserts that the fraction of the failed requests is at most `rate`.
+    pub fn assert_error_rate_at_most(&self, rate: f64) {
+        assert!(
+            self.error_rate() <= rate,
+            "error rate {:.4} is above {rate}: {:?}",
+            self.error_rate(),
+            self.statuses
+        );
+    }
+
+    /// Asserts that the percentile `p` of the latencies is at most `latency`.
+    pub fn assert_percentile_at_most(&self, p: f64, latency: Duration) {
+        let actual = self.percentile(p);
+        assert!(
+            actual <= latency,
+            "p{p} latency {actual:?} is above {latency:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, http::StatusCode, Error, Result};
+
+    #[handler(internal)]
+    async fn index(body: String) -> Result<&'static str> {
+        let n: u64 = body.parse().unwrap();
+        tokio::time::sleep(Duration::from_millis(n % 3 * 10)).await;
+        if n % 4 == 0 {
+            return Err(Error::from_status(StatusCode::SERVICE_UNAVAILABLE));
+        }
+        Ok("hello")
+    }
+
+    #[tokio::test]
+    async fn load() {
+        let cli = TestClient::new(index);
+        let report = cli
+            .load(|cli, idx| cli.post("/").body(idx.to_string()))
+            .requests(40)
+            .concurrency(8)
+            .run()
+            .await;
+
+        assert_eq!(report.requests(), 40);
+        assert_eq!(report.status_count(StatusCode::OK), 30);
+        assert_eq!(report.errors(), 10);
+        assert_eq!(report.error_rate(), 0.25);
+        report.assert_error_rate_at_most(0.25);
+
+        assert!(report.percentile(0.0) < Duration::from_millis(10));
+        assert!(report.percentile(100.0) >= Duration::from_millis(20));
+        assert!(report.mean() >= Duration::from_millis(9));
+        report.assert_percentile_at_most(50.0, report.percentile(100.0));
+        assert_eq!(
+            report
+                .histogram(&[Duration::from_millis(5), Duration::from_millis(15)])
+                .iter()
+                .sum::<usize>(),
+            40
+        );
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "error rate 0.2500 is above 0.1")]
+    async fn error_rate() {
+        let cli = TestClient::new(index);
+        cli.load(|cli, idx| cli.post("/").body(idx.to_string()))
+            .requests(4)
+            .run()
+            .await
+            .assert_error_rate_at_most(0.1);
+    }
+}
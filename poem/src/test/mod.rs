@@ -112,11 +112,23 @@
 mod client;
 mod form;
 mod json;
+mod load;
 mod request_builder;
 mod response;
+mod snapshot;
+#[cfg(feature = "tera")]
+mod template;
+#[cfg(feature = "websocket")]
+mod websocket;
 
 pub use client::TestClient;
 pub use form::{TestForm, TestFormField};
 pub use json::{TestJson, TestJsonArray, TestJsonObject, TestJsonValue};
+pub use load::{TestLoad, TestLoadReport};
 pub use request_builder::TestRequestBuilder;
 pub use response::TestResponse;
+pub use snapshot::TestSnapshot;
+#[cfg(feature = "tera")]
+pub use template::TestTera;
+#[cfg(feature = "websocket")]
+pub use websocket::TestWebSocket;
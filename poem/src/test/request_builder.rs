@@ -1,8 +1,13 @@
+use bytes::Bytes;
 use headers::{Header, HeaderMapExt};
-use http::{header, header::HeaderName, Extensions, HeaderMap, HeaderValue, Method};
+use http::{
+    header, header::HeaderName, Extensions, HeaderMap, HeaderValue, Method, StatusCode, Uri,
+};
 use serde::Serialize;
 use serde_json::Value;
 
+#[cfg(feature = "websocket")]
+use crate::{test::TestWebSocket, OnUpgrade};
 use crate::{
     test::{TestClient, TestForm, TestResponse},
     Body, Endpoint, Request,
@@ -206,14 +211,108 @@ impl<'a, E> TestRequestBuilder<'a, E> {
         self
     }
 
+    /// Sends this request as a WebSocket handshake, and returns the client
+    /// side of the connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint does not accept the WebSocket.
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub async fn websocket(self) -> TestWebSocket
+    where
+        E: Endpoint,
+    {
+        let cli = self.cli;
+        let mut req = self
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_VERSION, "13")
+            .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .make_request();
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        *req.state_mut().on_upgrade.get_mut() = Some(OnUpgrade::duplex(server_io));
+
+        let resp = cli.call(req).await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::SWITCHING_PROTOCOLS,
+            "the websocket handshake failed"
+        );
+        TestWebSocket::new(resp.headers().clone(), client_io).await
+    }
+
     /// Send this request to endpoint to get the response.
     pub async fn send(self) -> TestResponse
     where
         E: Endpoint,
     {
-        let ep = &self.cli.ep;
-        let req = self.make_request();
-        let resp = ep.get_response(req).await;
-        TestResponse::new(resp)
+        let cli = self.cli;
+        let mut req = self.make_request();
+        if cli.max_redirects == 0 {
+            return TestResponse::new(cli.call(req).await);
+        }
+
+        // the body is sent again for the `307` and `308` redirections
+        let mut body = req.take_body().into_bytes().await.expect("valid body");
+        req.set_body(body.clone());
+        let mut redirects = 0;
+        loop {
+            let (method, uri, mut headers) = (
+                req.method().clone(),
+                req.uri().clone(),
+                req.headers().clone(),
+            );
+            let resp = cli.call(req).await;
+            let location = match resp
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+            {
+                Some(location) if resp.status().is_redirection() => location,
+                _ => return TestResponse::new(resp),
+            };
+
+            redirects += 1;
+            assert!(
+                redirects <= cli.max_redirects,
+                "too many redirections, the last one to `{location}`"
+            );
+
+            let method = match resp.status() {
+                StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => method,
+                _ if method == Method::GET || method == Method::HEAD => method,
+                _ => {
+                    body = Bytes::new();
+                    for name in [
+                        header::CONTENT_TYPE,
+                        header::CONTENT_LENGTH,
+                        header::TRANSFER_ENCODING,
+                    ] {
+                        headers.remove(name);
+                    }
+                    Method::GET
+                }
+            };
+            req = Request::builder()
+                .method(method)
+                .uri(resolve_location(&uri, location))
+                .body(body.clone());
+            *req.headers_mut() = headers;
+        }
+    }
+}
+
+/// Resolves the `Location` of a redirection relatively to the uri of the
+/// request.
+fn resolve_location(uri: &Uri, location: &str) -> Uri {
+    let location = location.parse::<Uri>().expect("valid location");
+    if location.scheme().is_some() || location.path().starts_with('/') {
+        return location;
     }
+    let dir = match uri.path().rfind('/') {
+        Some(idx) => &uri.path()[..=idx],
+        None => "/",
+    };
+    format!("{dir}{location}").parse().expect("valid location")
 }
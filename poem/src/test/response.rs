@@ -6,7 +6,11 @@ use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
-use crate::{test::json::TestJson, web::sse::Event, Response};
+use crate::{
+    test::{json::TestJson, TestSnapshot},
+    web::sse::Event,
+    Response,
+};
 
 /// A response object for testing.
 pub struct TestResponse(pub Response);
@@ -138,6 +142,17 @@ impl TestResponse {
         );
     }
 
+    /// Asserts that the response body is a string and it matches the
+    /// `snapshot`.
+    ///
+    /// See [`TestSnapshot`](crate::test::TestSnapshot) for how the snapshots
+    /// are stored and normalized.
+    pub async fn assert_snapshot(self, snapshot: impl Into<TestSnapshot>) {
+        snapshot
+            .into()
+            .assert_eq(self.0.into_body().into_string().await.expect("expect body"));
+    }
+
     /// Asserts that the response body is bytes and it equals to `bytes`.
     pub async fn assert_bytes(self, bytes: impl AsRef<[u8]>) {
         assert_eq!(
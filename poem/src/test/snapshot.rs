@@ -0,0 +1,212 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+
+type NormalizeFn = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+/// An expected output stored in a file, to compare the rendered pages with
+/// [`TestResponse::assert_snapshot`](crate::test::TestResponse::assert_snapshot).
+///
+/// The snapshots are stored in the `tests/snapshots` directory of the crate
+/// by default. A missing snapshot is created by the first run of the test,
+/// except if the `CI` environment variable is set, and the snapshots are
+/// rewritten with the current output if the `POEM_UPDATE_SNAPSHOTS`
+/// environment variable is set.
+///
+/// The parts of the output which change at each run, such as the nonces or
+/// the timestamps, are replaced before the comparison with
+/// [`redact`](TestSnapshot::redact) or [`normalize`](TestSnapshot::normalize).
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{
+///     handler,
+///     test::{TestClient, TestSnapshot},
+///     web::Html,
+///     Route,
+/// };
+///
+/// #[handler]
+/// fn index() -> Html<String> {
+///     Html(format!("<p>{}</p>", std::process::id()))
+/// }
+///
+/// let cli = TestClient::new(Route::new().at("/", index));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// // compares with `tests/snapshots/index.html`
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_snapshot(TestSnapshot::new("index.html").redact(r"\d+", "[pid]"))
+///     .await;
+/// # });
+/// ```
+pub struct TestSnapshot {
+    path: PathBuf,
+    normalizers: Vec<NormalizeFn>,
+}
+
+impl Debug for TestSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestSnapshot")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl From<&str> for TestSnapshot {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for TestSnapshot {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl TestSnapshot {
+    /// Create a snapshot stored in the file `name` of the `tests/snapshots`
+    /// directory.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        let root = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        Self::from_path(root.join("tests").join("snapshots").join(name.as_ref()))
+    }
+
+    /// Create a snapshot stored in the file `path`.
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            normalizers: Vec::new(),
+        }
+    }
+
+    /// Returns the path of the file of this snapshot.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replaces the matches of the regular expression `pattern` with
+    /// `replacement` before the comparison.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    #[must_use]
+    pub fn redact(self, pattern: &str, replacement: impl Into<String>) -> Self {
+        let regex = Regex::new(pattern).expect("valid regular expression");
+        let replacement = replacement.into();
+        self.normalize(move |s| regex.replace_all(s, replacement.as_str()).into_owned())
+    }
+
+    /// Transforms the output with `f` before the comparison.
+    #[must_use]
+    pub fn normalize(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.normalizers.push(Box::new(f));
+        self
+    }
+
+    /// Asserts that `actual` equals to the snapshot, once normalized.
+    pub fn assert_eq(&self, actual: impl AsRef<str>) {
+        let mut actual = actual.as_ref().to_string();
+        for normalize in &self.normalizers {
+            actual = normalize(&actual);
+        }
+
+        let update = std::env::var_os("POEM_UPDATE_SNAPSHOTS").is_some();
+        let expected = match std::fs::read_to_string(&self.path) {
+            Ok(expected) if !update => expected,
+            Err(_) if !update && std::env::var_os("CI").is_some() => {
+                panic!("missing snapshot `{}`", self.path.display())
+            }
+            _ => {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir).expect("create snapshot directory");
+                }
+                std::fs::write(&self.path, &actual).expect("write snapshot");
+                return;
+            }
+        };
+
+        if actual != expected {
+            let line = actual
+                .lines()
+                .zip(expected.lines())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+            panic!(
+                "snapshot `{}` does not match at line {}\n\
+                 expected: {:?}\n  actual: {:?}\n\n{actual}\n\n\
+                 set `POEM_UPDATE_SNAPSHOTS=1` to update the snapshot",
+                self.path.display(),
+                line + 1,
+                expected.lines().nth(line).unwrap_or_default(),
+                actual.lines().nth(line).unwrap_or_default(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, test::TestClient, web::Html};
+
+    fn snapshot(name: &str) -> TestSnapshot {
+        let dir = std::env::temp_dir().join(format!("poem-snapshots-{}", std::process::id()));
+        TestSnapshot::from_path(dir.join(name))
+    }
+
+    #[test]
+    fn create_and_compare() {
+        let snapshot = snapshot("create.html").redact(r"nonce=\w+", "nonce=[nonce]");
+        let _ = std::fs::remove_file(snapshot.path());
+
+        if std::env::var_os("CI").is_none() {
+            snapshot.assert_eq("<script nonce=abc></script>");
+            assert_eq!(
+                std::fs::read_to_string(snapshot.path()).unwrap(),
+                "<script nonce=[nonce]></script>"
+            );
+            snapshot.assert_eq("<script nonce=def></script>");
+        }
+    }
+
+    #[tokio::test]
+    async fn response_snapshot() {
+        #[handler(internal)]
+        fn index() -> Html<String> {
+            Html(format!(
+                "<p>{}</p>",
+                std::time::SystemTime::now().elapsed().is_ok()
+            ))
+        }
+
+        let snapshot = snapshot("response.html").redact("true|false", "[bool]");
+        std::fs::create_dir_all(snapshot.path().parent().unwrap()).unwrap();
+        std::fs::write(snapshot.path(), "<p>[bool]</p>").unwrap();
+        TestClient::new(index)
+            .get("/")
+            .send()
+            .await
+            .assert_snapshot(snapshot)
+            .await;
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match at line 2")]
+    fn mismatch() {
+        let snapshot = snapshot("mismatch.html");
+        std::fs::create_dir_all(snapshot.path().parent().unwrap()).unwrap();
+        std::fs::write(snapshot.path(), "<p>\nhello\n</p>").unwrap();
+        snapshot.assert_eq("<p>\nworld\n</p>");
+    }
+}
@@ -0,0 +1,170 @@
+use tera::{Context, Tera};
+
+use crate::{
+    test::{TestResponse, TestSnapshot},
+    Request, Response,
+};
+
+/// A harness for rendering individual [Tera](https://keats.github.io/tera/)
+/// templates in unit tests.
+///
+/// The rendering errors are reported as panics, with the whole chain of
+/// causes.
+///
+/// # Example
+///
+/// ```
+/// use poem::{ctx, test::TestTera};
+///
+/// let mut tera = TestTera::default();
+/// tera.add_raw_template("hello.html", "<p>Hello {{ name }}</p>");
+/// tera.assert_render(
+///     "hello.html",
+///     &ctx! { "name": "sunli" },
+///     "<p>Hello sunli</p>",
+/// );
+/// ```
+#[derive(Default)]
+pub struct TestTera {
+    tera: Tera,
+}
+
+impl From<Tera> for TestTera {
+    fn from(tera: Tera) -> Self {
+        Self { tera }
+    }
+}
+
+impl TestTera {
+    /// Create a harness containing the templates found in the glob.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a template cannot be parsed.
+    pub fn from_glob(glob: &str) -> Self {
+        match Tera::new(glob) {
+            Ok(tera) => Self { tera },
+            Err(err) => panic!("parse templates: {}", error_chain(&err)),
+        }
+    }
+
+    /// Create a harness containing the templates found in the directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a template cannot be parsed.
+    pub fn from_directory(template_directory: &str) -> Self {
+        Self::from_glob(&format!("{template_directory}/**/*"))
+    }
+
+    /// Applies a transformer of
+    /// [`TeraTemplatingEndpoint::using`](crate::tera::TeraTemplatingEndpoint::using),
+    /// with the request `req`.
+    #[must_use]
+    pub fn using(mut self, transformer: fn(&mut Tera, &mut Request), mut req: Request) -> Self {
+        transformer(&mut self.tera, &mut req);
+        self
+    }
+
+    /// Adds a template from a string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the template cannot be parsed.
+    pub fn add_raw_template(&mut self, name: &str, content: &str) {
+        if let Err(err) = self.tera.add_raw_template(name, content) {
+            panic!("parse template `{name}`: {}", error_chain(&err));
+        }
+    }
+
+    /// Returns a mutable reference to the inner Tera instance, for example to
+    /// register filters or functions.
+    pub fn tera_mut(&mut self) -> &mut Tera {
+        &mut self.tera
+    }
+
+    /// Renders the template `name` with the `context`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the template cannot be rendered.
+    pub fn render(&self, name: &str, context: &Context) -> String {
+        match self.tera.render(name, context) {
+            Ok(output) => output,
+            Err(err) => panic!("render template `{name}`: {}", error_chain(&err)),
+        }
+    }
+
+    /// Renders the template `name` as a response, to use the assertions of
+    /// [`TestResponse`].
+    pub fn render_response(&self, name: &str, context: &Context) -> TestResponse {
+        TestResponse::new(
+            Response::builder()
+                .content_type("text/html; charset=utf-8")
+                .body(self.render(name, context)),
+        )
+    }
+
+    /// Asserts that the template `name` rendered with the `context` equals to
+    /// `expected`.
+    pub fn assert_render(&self, name: &str, context: &Context, expected: impl AsRef<str>) {
+        assert_eq!(self.render(name, context), expected.as_ref());
+    }
+
+    /// Asserts that the template `name` rendered with the `context` matches
+    /// the `snapshot`.
+    pub fn assert_snapshot(
+        &self,
+        name: &str,
+        context: &Context,
+        snapshot: impl Into<TestSnapshot>,
+    ) {
+        snapshot.into().assert_eq(self.render(name, context));
+    }
+}
+
+fn error_chain(err: &tera::Error) -> String {
+    let mut msg = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        msg.push_str(&format!(": {err}"));
+        source = err.source();
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upper(tera: &mut Tera, _req: &mut Request) {
+        tera.register_filter(
+            "upper_name",
+            |value: &tera::Value, _: &std::collections::HashMap<String, tera::Value>| {
+                Ok(value.as_str().unwrap_or_default().to_uppercase().into())
+            },
+        );
+    }
+
+    #[tokio::test]
+    async fn render() {
+        let mut tera = TestTera::default().using(upper, Request::default());
+        tera.add_raw_template("hello.html", "<p>{{ name | upper_name }}</p>");
+        let mut context = Context::new();
+        context.insert("name", "sunli");
+        tera.assert_render("hello.html", &context, "<p>SUNLI</p>");
+
+        context.insert("name", "poem");
+        let resp = tera.render_response("hello.html", &context);
+        resp.assert_content_type("text/html; charset=utf-8");
+        resp.assert_text("<p>POEM</p>").await;
+    }
+
+    #[test]
+    #[should_panic(expected = "Variable `name` not found")]
+    fn render_error() {
+        let mut tera = TestTera::default();
+        tera.add_raw_template("hello.html", "<p>{{ name }}</p>");
+        tera.render("hello.html", &Context::new());
+    }
+}
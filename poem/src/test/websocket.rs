@@ -0,0 +1,146 @@
+use futures_util::{SinkExt, StreamExt};
+use http::{header, HeaderMap};
+use tokio::io::DuplexStream;
+use tokio_tungstenite::{
+    tungstenite::{error::ProtocolError, protocol::Role, Error},
+    WebSocketStream,
+};
+
+use crate::web::websocket::Message;
+
+/// The client side of a WebSocket connected to an endpoint, returned by
+/// [`TestClient::websocket`](crate::test::TestClient::websocket).
+///
+/// The methods panic if the connection fails.
+pub struct TestWebSocket {
+    headers: HeaderMap,
+    stream: WebSocketStream<DuplexStream>,
+}
+
+impl TestWebSocket {
+    pub(crate) async fn new(headers: HeaderMap, io: DuplexStream) -> Self {
+        Self {
+            headers,
+            stream: WebSocketStream::from_raw_socket(io, Role::Client, None).await,
+        }
+    }
+
+    /// Returns the headers of the handshake response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Asserts that the server selected the subprotocol `protocol`.
+    pub fn assert_protocol(&self, protocol: &str) {
+        assert_eq!(
+            self.headers
+                .get(header::SEC_WEBSOCKET_PROTOCOL)
+                .and_then(|value| value.to_str().ok()),
+            Some(protocol)
+        );
+    }
+
+    /// Sends a message.
+    pub async fn send(&mut self, msg: Message) {
+        self.stream.send(msg.into()).await.expect("send message");
+    }
+
+    /// Sends a text message.
+    pub async fn send_text(&mut self, text: impl Into<String>) {
+        self.send(Message::text(text)).await;
+    }
+
+    /// Sends a binary message.
+    pub async fn send_binary(&mut self, data: impl Into<Vec<u8>>) {
+        self.send(Message::binary(data)).await;
+    }
+
+    /// Receives the next message, or returns `None` if the connection has
+    /// been closed.
+    pub async fn recv(&mut self) -> Option<Message> {
+        match self.stream.next().await? {
+            Ok(msg) => Some(msg.into()),
+            Err(
+                Error::ConnectionClosed
+                | Error::AlreadyClosed
+                | Error::Protocol(ProtocolError::ResetWithoutClosingHandshake),
+            ) => None,
+            Err(err) => panic!("receive message: {err}"),
+        }
+    }
+
+    /// Receives the next message and asserts that it is the text `text`.
+    pub async fn assert_text(&mut self, text: impl AsRef<str>) {
+        match self.recv().await {
+            Some(Message::Text(msg)) => assert_eq!(msg, text.as_ref()),
+            msg => panic!("expect a text message, received {msg:?}"),
+        }
+    }
+
+    /// Receives the next message and asserts that it is the binary `data`.
+    pub async fn assert_binary(&mut self, data: impl AsRef<[u8]>) {
+        match self.recv().await {
+            Some(Message::Binary(msg)) => assert_eq!(msg, data.as_ref()),
+            msg => panic!("expect a binary message, received {msg:?}"),
+        }
+    }
+
+    /// Asserts that the server closes the connection, skipping the remaining
+    /// messages.
+    pub async fn assert_closed(&mut self) {
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while self.recv().await.is_some() {}
+        })
+        .await;
+        assert!(closed.is_ok(), "the connection is not closed");
+    }
+
+    /// Closes the connection.
+    pub async fn close(mut self) {
+        let _ = self.stream.close(None).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        handler,
+        test::TestClient,
+        web::websocket::{Message, WebSocket},
+        IntoResponse,
+    };
+
+    #[handler(internal)]
+    fn reverse(ws: WebSocket) -> impl IntoResponse {
+        ws.protocols(["v1", "v2"])
+            .on_upgrade(|mut socket| async move {
+                use futures_util::{SinkExt, StreamExt};
+
+                if let Some(Ok(Message::Binary(mut data))) = socket.next().await {
+                    data.reverse();
+                    let _ = socket.send(Message::binary(data)).await;
+                }
+            })
+    }
+
+    #[tokio::test]
+    async fn websocket() {
+        let cli = TestClient::new(reverse);
+        let mut ws = cli
+            .get("/")
+            .header("Sec-WebSocket-Protocol", "v2, v3")
+            .websocket()
+            .await;
+        ws.assert_protocol("v2");
+        ws.send_binary([1, 2, 3]).await;
+        ws.assert_binary([3, 2, 1]).await;
+        ws.assert_closed().await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "the websocket handshake failed")]
+    async fn not_websocket() {
+        let cli = TestClient::new(reverse);
+        cli.post("/").websocket().await;
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use crate::{http::StatusCode, Error, FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the API key of the request, validated by the
+/// [`ApiKeyAuth`](crate::middleware::ApiKeyAuth) middleware.
+///
+/// # Errors
+///
+/// - `401 Unauthorized` if the request has no valid API key. Use
+///   `Option<ApiKey>` for the routes allowing the anonymous clients.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ApiKey {
+    /// The identifier of the key, which is not the secret key itself.
+    pub id: String,
+    /// The owner of the key, such as an account or a service.
+    pub owner: String,
+    /// The scopes granted to the key.
+    pub scopes: BTreeSet<String>,
+}
+
+impl ApiKey {
+    /// Create an `ApiKey` without scopes.
+    pub fn new(id: impl Into<String>, owner: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            owner: owner.into(),
+            scopes: BTreeSet::new(),
+        }
+    }
+
+    /// Grants a scope to the key.
+    #[must_use]
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.insert(scope.into());
+        self
+    }
+
+    /// Returns `true` if the scope is granted to the key.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for ApiKey {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        req.extensions()
+            .get::<ApiKey>()
+            .cloned()
+            .ok_or_else(|| Error::from_status(StatusCode::UNAUTHORIZED))
+    }
+}
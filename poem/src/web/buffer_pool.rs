@@ -0,0 +1,185 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bytes::Bytes;
+
+/// The maximum number of idle buffers kept in the pool.
+#[cfg(feature = "buffer-pool")]
+const MAX_IDLE: usize = 64;
+
+/// The buffers which grew above this capacity are not returned to the pool,
+/// so that only the small responses are pooled.
+#[cfg(feature = "buffer-pool")]
+const MAX_CAPACITY: usize = 64 * 1024;
+
+#[cfg(feature = "buffer-pool")]
+const INITIAL_CAPACITY: usize = 1024;
+
+#[cfg(feature = "buffer-pool")]
+static POOL: parking_lot::Mutex<Vec<Vec<u8>>> = parking_lot::const_mutex(Vec::new());
+
+static ACQUIRED: AtomicU64 = AtomicU64::new(0);
+static REUSED: AtomicU64 = AtomicU64::new(0);
+static DISCARDED: AtomicU64 = AtomicU64::new(0);
+
+/// The metrics of the buffer pool used to serialize the JSON responses and
+/// to render the templates.
+///
+/// The pool is enabled by the `buffer-pool` feature. The responses are
+/// serialized into the pooled buffers and then copied into exactly sized
+/// ones, which is faster for the small responses and on par for the large
+/// ones, as measured by the `buffer_pool` benchmark. All the metrics are zero
+/// when it is disabled.
+///
+/// # Example
+///
+/// ```
+/// use poem::{web::BufferPoolMetrics, IntoResponse};
+/// use serde_json::json;
+///
+/// let before = BufferPoolMetrics::current();
+/// let _ = poem::web::Json(json!({ "a": 1 })).into_response();
+/// let after = BufferPoolMetrics::current();
+/// # #[cfg(feature = "buffer-pool")]
+/// assert!(after.acquired > before.acquired);
+/// ```
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct BufferPoolMetrics {
+    /// The number of buffers taken from the pool.
+    pub acquired: u64,
+    /// The number of buffers which were reused instead of allocated.
+    pub reused: u64,
+    /// The number of buffers which were dropped instead of returned to the
+    /// pool, because they were too large or the pool was full.
+    pub discarded: u64,
+    /// The number of idle buffers in the pool.
+    pub idle: usize,
+}
+
+impl BufferPoolMetrics {
+    /// Returns the current metrics of the pool.
+    pub fn current() -> Self {
+        Self {
+            acquired: ACQUIRED.load(Ordering::Relaxed),
+            reused: REUSED.load(Ordering::Relaxed),
+            discarded: DISCARDED.load(Ordering::Relaxed),
+            #[cfg(feature = "buffer-pool")]
+            idle: POOL.lock().len(),
+            #[cfg(not(feature = "buffer-pool"))]
+            idle: 0,
+        }
+    }
+
+    /// Returns the fraction of the acquired buffers which were reused.
+    pub fn hit_rate(&self) -> f64 {
+        self.reused as f64 / self.acquired.max(1) as f64
+    }
+}
+
+/// A buffer taken from the pool, which is returned to the pool when dropped.
+pub(crate) struct PooledBuffer(Vec<u8>);
+
+impl PooledBuffer {
+    pub(crate) fn acquire() -> Self {
+        #[cfg(feature = "buffer-pool")]
+        {
+            ACQUIRED.fetch_add(1, Ordering::Relaxed);
+            if let Some(buf) = POOL.lock().pop() {
+                REUSED.fetch_add(1, Ordering::Relaxed);
+                return Self(buf);
+            }
+            Self(Vec::with_capacity(INITIAL_CAPACITY))
+        }
+
+        #[cfg(not(feature = "buffer-pool"))]
+        Self(Vec::new())
+    }
+
+    /// Copies the content of the buffer to an exactly sized `Bytes`, and
+    /// returns the buffer to the pool.
+    pub(crate) fn into_bytes(mut self) -> Bytes {
+        if cfg!(feature = "buffer-pool") {
+            Bytes::copy_from_slice(&self.0)
+        } else {
+            std::mem::take(&mut self.0).into()
+        }
+    }
+
+    /// Copies the content of the buffer to an exactly sized `String`, and
+    /// returns the buffer to the pool.
+    ///
+    /// Returns `None` if the content is not valid UTF-8.
+    #[cfg(feature = "tera")]
+    pub(crate) fn into_string(mut self) -> Option<String> {
+        if cfg!(feature = "buffer-pool") {
+            std::str::from_utf8(&self.0).ok().map(ToString::to_string)
+        } else {
+            String::from_utf8(std::mem::take(&mut self.0)).ok()
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "buffer-pool")]
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let mut buf = std::mem::take(&mut self.0);
+        if buf.capacity() > MAX_CAPACITY {
+            DISCARDED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        buf.clear();
+        let mut pool = POOL.lock();
+        if pool.len() < MAX_IDLE {
+            pool.push(buf);
+        } else {
+            DISCARDED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "buffer-pool"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let mut buf = PooledBuffer::acquire();
+        buf.extend_from_slice(b"hello");
+        assert_eq!(buf.into_bytes(), Bytes::from_static(b"hello"));
+
+        let before = BufferPoolMetrics::current();
+        let buf = PooledBuffer::acquire();
+        assert!(buf.is_empty());
+        assert!(buf.capacity() >= INITIAL_CAPACITY);
+        drop(buf);
+        let after = BufferPoolMetrics::current();
+        assert!(after.acquired > before.acquired);
+        assert!(after.reused > before.reused);
+    }
+
+    #[test]
+    fn discard_large() {
+        let before = BufferPoolMetrics::current();
+        let mut buf = PooledBuffer::acquire();
+        buf.resize(MAX_CAPACITY + 1, 0);
+        drop(buf);
+        assert!(BufferPoolMetrics::current().discarded > before.discarded);
+    }
+}
@@ -0,0 +1,224 @@
+use std::ops::{Deref, DerefMut};
+
+use http::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::ParseCborError, http::header, web::RequestBody, FromRequest, IntoResponse, Request,
+    Response, Result,
+};
+
+/// [CBOR](https://cbor.io/) extractor and response.
+///
+/// To extract the specified type of CBOR from the body, `T` must
+/// implement [`serde::Deserialize`]. The `Content-Type` must be
+/// `application/cbor` or a type with the `+cbor` suffix.
+///
+/// # Errors
+///
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
+/// - [`ParseCborError`]
+///
+/// ```
+/// use poem::{handler, http::header, post, test::TestClient, web::Cbor, Route};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// #[handler]
+/// async fn index(Cbor(user): Cbor<User>) -> String {
+///     format!("welcome {}!", user.name)
+/// }
+///
+/// let app = Route::new().at("/", post(index));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .post("/")
+///     .header(header::CONTENT_TYPE, "application/cbor")
+///     // {"name": "foo"}
+///     .body(b"\xa1\x64name\x63foo".to_vec())
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("welcome foo!").await;
+/// # });
+/// ```
+///
+/// # Response
+///
+/// To serialize the specified type to CBOR, `T` must implement
+/// [`serde::Serialize`].
+///
+/// The [`Accept`](crate::web::Accept) extractor can be used to respond with
+/// CBOR only to the clients that ask for it:
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     web::{Accept, Cbor, Json},
+///     IntoResponse, Response, Route,
+/// };
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// #[handler]
+/// async fn index(accept: Accept) -> Response {
+///     let user = User {
+///         name: "foo".to_string(),
+///     };
+///     match accept.0.first() {
+///         Some(mime) if mime.essence_str() == "application/cbor" => Cbor(user).into_response(),
+///         _ => Json(user).into_response(),
+///     }
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(docsrs, doc(cfg(feature = "cbor")))]
+pub struct Cbor<T>(pub T);
+
+impl<T> Deref for Cbor<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Cbor<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: DeserializeOwned> FromRequest<'a> for Cbor<T> {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .ok_or(ParseCborError::ContentTypeRequired)?;
+        if !is_cbor_content_type(content_type) {
+            return Err(ParseCborError::InvalidContentType(content_type.into()).into());
+        }
+
+        Ok(Self(
+            ciborium::de::from_reader(body.take()?.into_bytes().await?.as_ref())
+                .map_err(ParseCborError::Parse)?,
+        ))
+    }
+}
+
+fn is_cbor_content_type(content_type: &str) -> bool {
+    matches!(content_type.parse::<mime::Mime>(),
+        Ok(content_type) if content_type.type_() == "application"
+        && (content_type.subtype() == "cbor"
+        || content_type
+            .suffix()
+            .map_or(false, |v| v == "cbor")))
+}
+
+impl<T: Serialize + Send> IntoResponse for Cbor<T> {
+    fn into_response(self) -> Response {
+        let mut data = Vec::new();
+        match ciborium::ser::into_writer(&self.0, &mut data) {
+            Ok(()) => {}
+            Err(err) => {
+                return Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(err.to_string())
+            }
+        }
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .body(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{handler, test::TestClient};
+
+    #[derive(Deserialize, Serialize, Debug, Eq, PartialEq)]
+    struct CreateResource {
+        name: String,
+        value: i32,
+    }
+
+    #[tokio::test]
+    async fn cbor_extractor() {
+        #[handler(internal)]
+        async fn index(query: Cbor<CreateResource>) {
+            assert_eq!(query.name, "abc");
+            assert_eq!(query.value, 100);
+        }
+
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(
+            &CreateResource {
+                name: "abc".to_string(),
+                value: 100,
+            },
+            &mut data,
+        )
+        .unwrap();
+        let cli = TestClient::new(index);
+        cli.post("/")
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .body(data.clone())
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        cli.post("/")
+            .body(data)
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        cli.post("/")
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .body(b"\xff".to_vec())
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn cbor_response() {
+        #[handler(internal)]
+        async fn index() -> Cbor<CreateResource> {
+            Cbor(CreateResource {
+                name: "abc".to_string(),
+                value: 100,
+            })
+        }
+
+        let cli = TestClient::new(index);
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("application/cbor");
+        let data = resp.0.into_body().into_vec().await.unwrap();
+        assert_eq!(
+            ciborium::de::from_reader::<CreateResource, _>(data.as_slice()).unwrap(),
+            CreateResource {
+                name: "abc".to_string(),
+                value: 100,
+            }
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::{net::IpAddr, sync::Arc};
+
+use x509_parser::{
+    extensions::GeneralName,
+    prelude::{FromDer, X509Certificate},
+};
+
+use crate::{error::ClientCertRequiredError, FromRequest, Request, RequestBody, Result};
+
+/// An extractor that can extract the verified certificate presented by the
+/// client during the TLS handshake.
+///
+/// Client authentication must be enabled on the listener with
+/// [`RustlsConfig::client_auth_required`](crate::listener::RustlsConfig::client_auth_required)
+/// or
+/// [`RustlsConfig::client_auth_optional`](crate::listener::RustlsConfig::client_auth_optional).
+/// If the client did not present a certificate, the extractor fails with
+/// [`ClientCertRequiredError`]; use `Option<ClientCert>` when client
+/// authentication is optional.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, web::ClientCert};
+///
+/// #[handler]
+/// fn index(cert: ClientCert) -> String {
+///     format!("hello {}", cert.subject())
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+#[derive(Debug, Clone)]
+pub struct ClientCert(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    chain: Vec<Vec<u8>>,
+    subject: String,
+    subject_alt_names: Vec<String>,
+    fingerprint: [u8; 32],
+}
+
+impl ClientCert {
+    /// Parses the certificate chain presented by the client, with the end
+    /// entity certificate first.
+    pub(crate) fn from_chain(chain: Vec<Vec<u8>>) -> Option<Self> {
+        let der = chain.first()?;
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+
+        let subject = cert.subject().to_string();
+        let subject_alt_names = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|ext| {
+                ext.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(name)
+                        | GeneralName::RFC822Name(name)
+                        | GeneralName::URI(name) => Some(name.to_string()),
+                        GeneralName::IPAddress(addr) => match addr.len() {
+                            4 => Some(IpAddr::from(<[u8; 4]>::try_from(*addr).ok()?).to_string()),
+                            16 => Some(IpAddr::from(<[u8; 16]>::try_from(*addr).ok()?).to_string()),
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut fingerprint = [0; 32];
+        fingerprint.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, der).as_ref());
+
+        Some(Self(Arc::new(Inner {
+            chain,
+            subject,
+            subject_alt_names,
+            fingerprint,
+        })))
+    }
+
+    /// Returns the DER-encoded end entity certificate.
+    pub fn der(&self) -> &[u8] {
+        &self.0.chain[0]
+    }
+
+    /// Returns the DER-encoded certificate chain presented by the client, with
+    /// the end entity certificate first.
+    pub fn chain(&self) -> &[Vec<u8>] {
+        &self.0.chain
+    }
+
+    /// Returns the subject of the certificate, for example
+    /// `CN=client.example.com`.
+    pub fn subject(&self) -> &str {
+        &self.0.subject
+    }
+
+    /// Returns the DNS names, email addresses, URIs and IP addresses in the
+    /// subject alternative name extension of the certificate.
+    pub fn subject_alt_names(&self) -> &[String] {
+        &self.0.subject_alt_names
+    }
+
+    /// Returns the SHA-256 fingerprint of the certificate.
+    pub fn fingerprint(&self) -> &[u8; 32] {
+        &self.0.fingerprint
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for ClientCert {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<ClientCert>()
+            .cloned()
+            .ok_or(ClientCertRequiredError)?)
+    }
+}
@@ -0,0 +1,173 @@
+use std::future::Future;
+
+use tokio::sync::watch;
+
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// An extractor resolving when the client of the request disconnects, so
+/// that the long-running work, such as the exports or the streamed
+/// responses, can stop early instead of computing a response nobody reads.
+///
+/// The client is disconnected when its connection is closed, or when the
+/// server stops serving the request before the response is sent, for
+/// instance when an HTTP/2 stream is reset. A `ClientDisconnect` is usually
+/// moved into the spawned tasks and the streams of the response body, since
+/// the handler itself is dropped when the request is cancelled.
+///
+/// Outside of a [`Server`](crate::Server), such as with the
+/// [`TestClient`](crate::test::TestClient), the client never disconnects.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use futures_util::stream;
+/// use poem::{
+///     handler,
+///     web::{ClientDisconnect, StreamJson},
+/// };
+///
+/// #[handler]
+/// fn export(disconnect: ClientDisconnect) -> StreamJson {
+///     StreamJson::new(stream::unfold(0, move |n| {
+///         let disconnect = disconnect.clone();
+///         async move {
+///             // stop producing the rows once the client is gone
+///             disconnect
+///                 .run(tokio::time::sleep(Duration::from_millis(10)))
+///                 .await?;
+///             Some((n, n + 1))
+///         }
+///     }))
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClientDisconnect {
+    /// Closed when the connection is closed.
+    connection: Option<watch::Receiver<()>>,
+    /// Set to `true` when the request is cancelled before its response.
+    request: Option<watch::Receiver<bool>>,
+}
+
+impl ClientDisconnect {
+    pub(crate) fn new(connection: watch::Receiver<()>, request: watch::Receiver<bool>) -> Self {
+        Self {
+            connection: Some(connection),
+            request: Some(request),
+        }
+    }
+
+    /// Returns `true` if the client has disconnected.
+    pub fn is_disconnected(&self) -> bool {
+        self.request.as_ref().map_or(false, |rx| *rx.borrow())
+            || self
+                .connection
+                .as_ref()
+                .map_or(false, |rx| rx.has_changed().is_err())
+    }
+
+    /// Waits until the client disconnects.
+    pub async fn wait(&self) {
+        if let Some(mut rx) = self.request.clone() {
+            // the sender is dropped once the response is sent
+            while !*rx.borrow_and_update() {
+                if rx.changed().await.is_err() {
+                    break;
+                }
+            }
+            if *rx.borrow() {
+                return;
+            }
+        }
+        match self.connection.clone() {
+            Some(mut rx) => while rx.changed().await.is_ok() {},
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Runs `fut`, returning `None` if the client disconnects before it
+    /// completes.
+    pub async fn run<F: Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            output = fut => Some(output),
+            _ = self.wait() => None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for ClientDisconnect {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<ClientDisconnect>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// Notifies the [`ClientDisconnect`] of a request if it is dropped before the
+/// response is sent.
+pub(crate) struct RequestGuard {
+    tx: watch::Sender<bool>,
+    completed: bool,
+}
+
+impl RequestGuard {
+    pub(crate) fn new() -> (Self, watch::Receiver<bool>) {
+        let (tx, rx) = watch::channel(false);
+        (
+            Self {
+                tx,
+                completed: false,
+            },
+            rx,
+        )
+    }
+
+    pub(crate) fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            let _ = self.tx.send(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn disconnect() {
+        let never = ClientDisconnect::default();
+        assert!(!never.is_disconnected());
+        assert_eq!(never.run(async { 1 }).await, Some(1));
+
+        let (conn_tx, conn_rx) = watch::channel(());
+        let (guard, rx) = RequestGuard::new();
+        let disconnect = ClientDisconnect::new(conn_rx.clone(), rx);
+        assert!(!disconnect.is_disconnected());
+        drop(guard);
+        assert!(disconnect.is_disconnected());
+        assert_eq!(disconnect.run(std::future::pending::<()>()).await, None);
+
+        // a completed request is disconnected with its connection
+        let (guard, rx) = RequestGuard::new();
+        let disconnect = ClientDisconnect::new(conn_rx, rx);
+        guard.complete();
+        assert!(!disconnect.is_disconnected());
+        let wait = tokio::spawn(async move { disconnect.wait().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!wait.is_finished());
+        drop(conn_tx);
+        wait.await.unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use parking_lot::Mutex;
+
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// A source of the current time, used for the expiration of the sessions and
+/// the idempotency keys.
+///
+/// The default clock is the system clock. A [`MockClock`] can replace it in
+/// the tests, so that the expiration is tested without sleeping: it is
+/// passed to the storages with their `clock` method, and to the middlewares
+/// as the [`Data`](crate::web::Data) of the requests.
+///
+/// As an extractor, it returns the clock added to the request with
+/// [`EndpointExt::data`](crate::EndpointExt::data), or the system clock.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{
+///     handler,
+///     test::TestClient,
+///     web::{Clock, MockClock},
+///     EndpointExt,
+/// };
+///
+/// #[handler]
+/// fn index(clock: Clock) -> String {
+///     let elapsed = clock.now().duration_since(std::time::UNIX_EPOCH).unwrap();
+///     elapsed.as_secs().to_string()
+/// }
+///
+/// let clock = MockClock::at(std::time::UNIX_EPOCH);
+/// let cli = TestClient::new(index.data(Clock::from(clock.clone())));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/").send().await.assert_text("0").await;
+/// clock.advance(Duration::from_secs(60));
+/// cli.get("/").send().await.assert_text("60").await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Clock(Option<MockClock>);
+
+impl Clock {
+    /// Returns the system clock.
+    pub fn system() -> Self {
+        Self(None)
+    }
+
+    /// Returns the current time.
+    pub fn now(&self) -> SystemTime {
+        match &self.0 {
+            Some(mock) => mock.now(),
+            None => SystemTime::now(),
+        }
+    }
+
+    /// Returns the current instant, for measuring the durations.
+    pub fn instant(&self) -> Instant {
+        match &self.0 {
+            Some(mock) => mock.instant(),
+            None => Instant::now(),
+        }
+    }
+
+    /// Returns the clock of the request, or the system clock.
+    pub(crate) fn of(req: &Request) -> Self {
+        req.data::<Clock>().cloned().unwrap_or_default()
+    }
+}
+
+impl From<MockClock> for Clock {
+    fn from(mock: MockClock) -> Self {
+        Self(Some(mock))
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Clock {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(Self::of(req))
+    }
+}
+
+#[derive(Debug)]
+struct MockState {
+    system: SystemTime,
+    instant: Instant,
+    elapsed: Duration,
+}
+
+/// A clock which only moves forward when it is
+/// [`advanced`](MockClock::advance), for the tests.
+///
+/// The clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Mutex<MockState>>);
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    /// Create a clock stopped at the current time.
+    pub fn new() -> Self {
+        Self::at(SystemTime::now())
+    }
+
+    /// Create a clock stopped at `time`.
+    pub fn at(time: SystemTime) -> Self {
+        Self(Arc::new(Mutex::new(MockState {
+            system: time,
+            instant: Instant::now(),
+            elapsed: Duration::ZERO,
+        })))
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.0.lock().elapsed += duration;
+    }
+
+    /// Returns the current time of the clock.
+    pub fn now(&self) -> SystemTime {
+        let state = self.0.lock();
+        state.system + state.elapsed
+    }
+
+    /// Returns the current instant of the clock.
+    pub fn instant(&self) -> Instant {
+        let state = self.0.lock();
+        state.instant + state.elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock() {
+        let mock = MockClock::at(SystemTime::UNIX_EPOCH);
+        let clock = Clock::from(mock.clone());
+        let start = clock.instant();
+
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH);
+        mock.advance(Duration::from_secs(10));
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(10)
+        );
+        assert_eq!(clock.instant() - start, Duration::from_secs(10));
+
+        assert!(Clock::system().now() > SystemTime::UNIX_EPOCH);
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    io::{Error as IoError, ErrorKind, Write},
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use http::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::ParseCsvError, http::header, web::RequestBody, Body, FromRequest, IntoResponse, Request,
+    Response, Result,
+};
+
+/// CSV extractor and response.
+///
+/// To extract the rows from the body, `T` must implement
+/// [`serde::Deserialize`]. The `Content-Type` must be `text/csv` or
+/// `text/tab-separated-values`, and the first row is treated as the headers
+/// unless the `header=absent` parameter is specified.
+///
+/// # Errors
+///
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
+/// - [`ParseCsvError`]
+///
+/// ```
+/// use poem::{
+///     handler,
+///     http::{header, StatusCode},
+///     post,
+///     test::TestClient,
+///     web::Csv,
+///     Endpoint, Request, Route,
+/// };
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// #[handler]
+/// async fn index(Csv(users): Csv<User>) -> String {
+///     format!("imported {} users", users.len())
+/// }
+///
+/// let app = Route::new().at("/", post(index));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .post("/")
+///     .header(header::CONTENT_TYPE, "text/csv")
+///     .body("name,age\nfoo,20\nbar,30\n")
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("imported 2 users").await;
+/// # });
+/// ```
+///
+/// # Response
+///
+/// To serialize the rows to CSV, `T` must implement [`serde::Serialize`].
+/// Use [`CsvStream`] for large exports.
+///
+/// ```
+/// use poem::{get, handler, test::TestClient, web::Csv, Route};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// #[handler]
+/// async fn index() -> Csv<User> {
+///     Csv(vec![User {
+///         name: "foo".to_string(),
+///         age: 20,
+///     }])
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("name,age\nfoo,20\n").await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Csv<T>(pub Vec<T>);
+
+impl<T> Deref for Csv<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Csv<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: DeserializeOwned> FromRequest<'a> for Csv<T> {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .ok_or(ParseCsvError::ContentTypeRequired)?;
+        let (delimiter, has_headers) = parse_csv_content_type(content_type)
+            .ok_or_else(|| ParseCsvError::InvalidContentType(content_type.into()))?;
+
+        let data = body.take()?.into_bytes().await?;
+        let rows = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(has_headers)
+            .from_reader(&*data)
+            .deserialize()
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(ParseCsvError::Parse)?;
+        Ok(Self(rows))
+    }
+}
+
+/// Returns the delimiter, and whether the first row is the headers.
+fn parse_csv_content_type(content_type: &str) -> Option<(u8, bool)> {
+    let mime = content_type.parse::<mime::Mime>().ok()?;
+    let delimiter = match (mime.type_().as_str(), mime.subtype().as_str()) {
+        ("text" | "application", "csv") => b',',
+        ("text", "tab-separated-values") => b'\t',
+        _ => return None,
+    };
+    let has_headers = mime
+        .get_param("header")
+        .map_or(true, |value| value != "absent");
+    Some((delimiter, has_headers))
+}
+
+impl<T: Serialize + Send> IntoResponse for Csv<T> {
+    fn into_response(self) -> Response {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in &self.0 {
+            if let Err(err) = writer.serialize(row) {
+                return Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(err.to_string());
+            }
+        }
+        let data = match writer.into_inner() {
+            Ok(data) => data,
+            Err(err) => {
+                return Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(err.to_string())
+            }
+        };
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+            .body(data)
+    }
+}
+
+/// A CSV response that writes the rows of a stream as they are produced, so
+/// that large exports do not need to be kept in memory.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::stream;
+/// use poem::{get, handler, test::TestClient, web::CsvStream, IntoResponse, Route};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Row {
+///     id: u32,
+///     name: &'static str,
+/// }
+///
+/// #[handler]
+/// async fn export() -> impl IntoResponse {
+///     CsvStream::new(stream::iter(vec![
+///         Row { id: 1, name: "foo" },
+///         Row { id: 2, name: "bar" },
+///     ]))
+///     .delimiter(b';')
+///     .attachment("export.csv")
+/// }
+///
+/// let cli = TestClient::new(Route::new().at("/", get(export)));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_header("content-disposition", "attachment; filename=\"export.csv\"");
+/// resp.assert_text("id;name\n1;foo\n2;bar\n").await;
+/// # });
+/// ```
+pub struct CsvStream<S> {
+    stream: S,
+    delimiter: u8,
+    has_headers: bool,
+    filename: Option<String>,
+}
+
+impl<S> CsvStream<S>
+where
+    S: Stream + Send + 'static,
+    S::Item: Serialize,
+{
+    /// Create a `CsvStream` response from a stream of rows.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            delimiter: b',',
+            has_headers: true,
+            filename: None,
+        }
+    }
+
+    /// Sets the field delimiter. Defaults to `b','`.
+    #[must_use]
+    pub fn delimiter(self, delimiter: u8) -> Self {
+        Self { delimiter, ..self }
+    }
+
+    /// Sets whether the field names of the first row are written as a header.
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn has_headers(self, has_headers: bool) -> Self {
+        Self {
+            has_headers,
+            ..self
+        }
+    }
+
+    /// Sets the `Content-Disposition` header, so that the browser downloads
+    /// the response as a file with the specified name.
+    #[must_use]
+    pub fn attachment(self, filename: impl Into<String>) -> Self {
+        Self {
+            filename: Some(filename.into()),
+            ..self
+        }
+    }
+}
+
+impl<S> IntoResponse for CsvStream<S>
+where
+    S: Stream + Send + 'static,
+    S::Item: Serialize,
+{
+    fn into_response(self) -> Response {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(self.has_headers)
+            .from_writer(SharedBuf::default());
+        let buf = writer.get_ref().clone();
+        let stream = self.stream.map(move |row| {
+            writer
+                .serialize(row)
+                .and_then(|()| writer.flush().map_err(Into::into))
+                .map_err(|err| {
+                    tracing::error!(error = %err, "failed to serialize csv row");
+                    IoError::new(ErrorKind::Other, err)
+                })?;
+            Ok::<_, IoError>(Bytes::from(std::mem::take(&mut *buf.0.lock().unwrap())))
+        });
+
+        let mut resp = Response::builder()
+            .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+            .body(Body::from_bytes_stream(stream));
+        if let Some(filename) = self.filename {
+            if let Ok(value) =
+                format!("attachment; filename=\"{}\"", filename.replace('"', "")).parse()
+            {
+                resp.headers_mut()
+                    .insert(header::CONTENT_DISPOSITION, value);
+            }
+        }
+        resp
+    }
+}
+
+/// The output of the writer of [`CsvStream`], which is taken after each row.
+#[derive(Default, Clone)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{handler, test::TestClient};
+
+    #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+    struct Row {
+        id: i32,
+        name: String,
+    }
+
+    #[test]
+    fn content_type() {
+        assert_eq!(parse_csv_content_type("text/csv"), Some((b',', true)));
+        assert_eq!(
+            parse_csv_content_type("text/csv; charset=utf-8; header=absent"),
+            Some((b',', false))
+        );
+        assert_eq!(
+            parse_csv_content_type("text/tab-separated-values"),
+            Some((b'\t', true))
+        );
+        assert_eq!(parse_csv_content_type("application/json"), None);
+    }
+
+    #[tokio::test]
+    async fn csv_extractor() {
+        #[handler(internal)]
+        async fn index(rows: Csv<Row>) -> String {
+            format!("{:?}", rows.0)
+        }
+
+        let cli = TestClient::new(index);
+        let resp = cli
+            .post("/")
+            .header(header::CONTENT_TYPE, "text/tab-separated-values")
+            .body("id\tname\n1\tfoo\n")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_text(r#"[Row { id: 1, name: "foo" }]"#).await;
+
+        let resp = cli
+            .post("/")
+            .header(header::CONTENT_TYPE, "text/csv; header=absent")
+            .body("1,foo\n2,bar\n")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_text(r#"[Row { id: 1, name: "foo" }, Row { id: 2, name: "bar" }]"#)
+            .await;
+
+        let resp = cli
+            .post("/")
+            .header(header::CONTENT_TYPE, "text/csv")
+            .body("id,name\nabc,foo\n")
+            .send()
+            .await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+
+        let resp = cli.post("/").body("id,name\n1,foo\n").send().await;
+        resp.assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn csv_stream() {
+        let rows = (1..=2).map(|id| Row {
+            id,
+            name: format!("name{id}"),
+        });
+        let resp = CsvStream::new(futures_util::stream::iter(rows))
+            .has_headers(false)
+            .into_response();
+        assert_eq!(resp.content_type(), Some("text/csv; charset=utf-8"));
+        assert_eq!(
+            resp.into_body().into_string().await.unwrap(),
+            "1,name1\n2,name2\n"
+        );
+    }
+}
@@ -48,6 +48,51 @@ impl<'a, T: Send + Sync + 'static> FromRequest<'a> for Data<&'a T> {
     }
 }
 
+/// An extractor for the optional data of the request extension, which
+/// yields `None` instead of failing when the data is missing.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, test::TestClient, web::OptionalData, EndpointExt};
+///
+/// #[handler]
+/// async fn index(data: OptionalData<&i32>) -> String {
+///     data.0.copied().unwrap_or_default().to_string()
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// TestClient::new(index)
+///     .get("/")
+///     .send()
+///     .await
+///     .assert_text("0")
+///     .await;
+/// TestClient::new(index.data(10i32))
+///     .get("/")
+///     .send()
+///     .await
+///     .assert_text("10")
+///     .await;
+/// # });
+/// ```
+pub struct OptionalData<T>(pub Option<T>);
+
+impl<T> Deref for OptionalData<T> {
+    type Target = Option<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: Send + Sync + 'static> FromRequest<'a> for OptionalData<&'a T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(OptionalData(req.extensions().get::<T>()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use http::StatusCode;
@@ -97,4 +142,25 @@ mod tests {
             .await
             .assert_status_is_ok();
     }
+
+    #[tokio::test]
+    async fn test_optional_data_extractor() {
+        #[handler(internal)]
+        async fn index(value: OptionalData<&i32>) -> String {
+            format!("{:?}", value.0)
+        }
+
+        TestClient::new(index)
+            .get("/")
+            .send()
+            .await
+            .assert_text("None")
+            .await;
+        TestClient::new(index.with(AddData::new(100i32)))
+            .get("/")
+            .send()
+            .await
+            .assert_text("Some(100)")
+            .await;
+    }
 }
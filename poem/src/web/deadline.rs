@@ -0,0 +1,148 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use http::HeaderName;
+
+use crate::{
+    error::{DeadlineExceededError, GetDataError},
+    web::Clock,
+    FromRequest, Request, RequestBody, Result,
+};
+
+/// The header of the timeouts propagated between the services, in
+/// milliseconds.
+pub(crate) const REQUEST_TIMEOUT: HeaderName = HeaderName::from_static("x-request-timeout");
+
+/// The time by which the response to a request must be sent, set by the
+/// [`RequestDeadline`](crate::middleware::RequestDeadline) middleware.
+///
+/// The downstream operations are shortened to the remaining time: the
+/// [`Proxy`](crate::endpoint::Proxy) endpoint times out the upstream
+/// requests and forwards the remaining time in the `X-Request-Timeout`
+/// header, and [`tera::render_within`](crate::tera::render_within) stops
+/// rendering the expired templates.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if no `RequestDeadline` middleware is applied, use
+///   `Option<Deadline>` for the optional deadlines.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use poem::{handler, middleware::RequestDeadline, web::Deadline, EndpointExt};
+///
+/// #[handler]
+/// async fn index(deadline: Deadline) -> String {
+///     let remaining = deadline.remaining();
+///     match deadline.run(tokio::time::sleep(remaining / 2)).await {
+///         Ok(()) => "done".to_string(),
+///         Err(err) => err.to_string(),
+///     }
+/// }
+///
+/// let app = index.with(RequestDeadline::new(Duration::from_secs(10)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    at: Instant,
+    clock: Clock,
+}
+
+impl Deadline {
+    /// Create a deadline expiring after `timeout`.
+    pub fn after(timeout: Duration) -> Self {
+        Self::after_with_clock(timeout, Clock::system())
+    }
+
+    pub(crate) fn after_with_clock(timeout: Duration, clock: Clock) -> Self {
+        Self {
+            at: clock.instant() + timeout,
+            clock,
+        }
+    }
+
+    /// Returns the instant at which the deadline expires.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+
+    /// Returns the remaining time, which is zero once the deadline expired.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(self.clock.instant())
+    }
+
+    /// Returns `true` if the deadline expired.
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Returns the earliest of the two deadlines.
+    #[must_use]
+    pub fn min(self, other: Deadline) -> Self {
+        if other.at < self.at {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Runs `fut`, cancelling it when the deadline expires.
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output, DeadlineExceededError> {
+        if self.is_expired() {
+            return Err(DeadlineExceededError);
+        }
+        tokio::time::timeout(self.remaining(), fut)
+            .await
+            .map_err(|_| DeadlineExceededError)
+    }
+
+    /// Returns the deadline of the request.
+    pub(crate) fn of(req: &Request) -> Option<&Deadline> {
+        req.data::<Deadline>()
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Deadline {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(Deadline::of(req)
+            .cloned()
+            .ok_or_else(|| GetDataError(std::any::type_name::<Deadline>()))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::MockClock;
+
+    #[tokio::test]
+    async fn deadline() {
+        let clock = MockClock::at(std::time::UNIX_EPOCH);
+        let deadline = Deadline::after_with_clock(Duration::from_secs(10), clock.clone().into());
+        assert_eq!(deadline.remaining(), Duration::from_secs(10));
+
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(deadline.remaining(), Duration::from_secs(6));
+        assert_eq!(deadline.run(async { 1 }).await, Ok(1));
+
+        let earlier = Deadline::after_with_clock(Duration::from_secs(1), clock.clone().into());
+        assert_eq!(deadline.clone().min(earlier.clone()).at(), earlier.at());
+
+        clock.advance(Duration::from_secs(6));
+        assert!(deadline.is_expired());
+        assert_eq!(deadline.remaining(), Duration::ZERO);
+        assert_eq!(deadline.run(async { 1 }).await, Err(DeadlineExceededError));
+
+        let deadline = Deadline::after(Duration::from_millis(10));
+        assert_eq!(
+            deadline.run(std::future::pending::<()>()).await,
+            Err(DeadlineExceededError)
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{error::GetDataError, FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the variants assigned to the request by the
+/// [`Experiment`](crate::middleware::Experiment) middleware, which must be
+/// applied to the endpoint.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if no `Experiment` middleware is applied.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Experiments(pub(crate) Arc<BTreeMap<String, String>>);
+
+impl Experiments {
+    /// Returns the variant assigned for an experiment.
+    pub fn variant(&self, experiment: &str) -> Option<&str> {
+        self.0.get(experiment).map(String::as_str)
+    }
+
+    /// Returns the experiments and their assigned variants, in alphabetical
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(experiment, variant)| (experiment.as_str(), variant.as_str()))
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Experiments {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<Experiments>()
+            .cloned()
+            .ok_or_else(|| GetDataError(std::any::type_name::<Experiments>()))?)
+    }
+}
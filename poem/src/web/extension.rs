@@ -0,0 +1,154 @@
+use std::{marker::PhantomData, ops::Deref};
+
+use crate::{error::GetDataError, FromRequest, Request, RequestBody, Result};
+
+/// A compile-time key of a typed request extension, which maps a marker
+/// type to the type of its value.
+///
+/// Unlike [`Request::data`], which is keyed by the type of the value, several
+/// values of the same type can be stored with different keys.
+///
+/// The keys are usually implemented with the derive macro, which uses the
+/// name of the marker type as [`ExtensionKey::NAME`].
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     test::TestClient,
+///     web::{Ext, ExtensionKey},
+///     Endpoint, EndpointExt, Request,
+/// };
+///
+/// #[derive(ExtensionKey)]
+/// #[extension(value = "String")]
+/// struct RequestId;
+///
+/// #[derive(ExtensionKey)]
+/// #[extension(value = "String")]
+/// struct TenantId;
+///
+/// #[handler]
+/// fn index(req: &Request, tenant_id: Ext<'_, TenantId>) -> String {
+///     format!("{}/{}", req.ext::<RequestId>(), *tenant_id)
+/// }
+///
+/// let app = index.before(|mut req| async move {
+///     req.set_ext::<RequestId>("42".to_string());
+///     req.set_ext::<TenantId>("acme".to_string());
+///     Ok(req)
+/// });
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// TestClient::new(app)
+///     .get("/")
+///     .send()
+///     .await
+///     .assert_text("42/acme")
+///     .await;
+/// # });
+/// ```
+pub trait ExtensionKey: 'static {
+    /// The type of the value.
+    type Value: Send + Sync + 'static;
+
+    /// The name of the key, used in the error messages.
+    const NAME: &'static str;
+}
+
+/// The value of an extension, stored in the extensions of the requests
+/// under the type of its key.
+pub(crate) struct Keyed<K: ExtensionKey> {
+    pub(crate) value: K::Value,
+    _mark: PhantomData<fn() -> K>,
+}
+
+impl<K: ExtensionKey> Keyed<K> {
+    pub(crate) fn new(value: K::Value) -> Self {
+        Self {
+            value,
+            _mark: PhantomData,
+        }
+    }
+}
+
+/// An extractor for the value of the typed extension with the key `K`.
+///
+/// # Errors
+///
+/// - [`GetDataError`]
+pub struct Ext<'a, K: ExtensionKey>(pub &'a K::Value);
+
+impl<'a, K: ExtensionKey> Deref for Ext<'a, K> {
+    type Target = K::Value;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, K: ExtensionKey> FromRequest<'a> for Ext<'a, K> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(Ext(req.try_ext::<K>().ok_or(GetDataError(K::NAME))?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, web::ExtensionKey, EndpointExt};
+
+    #[derive(ExtensionKey)]
+    #[extension(internal, value = "u32")]
+    struct Left;
+
+    #[derive(ExtensionKey)]
+    #[extension(internal, value = "u32")]
+    struct Right;
+
+    #[test]
+    fn typed_extensions() {
+        let mut req = Request::default();
+        assert_eq!(req.try_ext::<Left>(), None);
+
+        req.set_ext::<Left>(1);
+        req.set_ext::<Right>(2);
+        assert_eq!(req.ext::<Left>(), &1);
+        assert_eq!(req.ext::<Right>(), &2);
+        assert_eq!(req.data::<u32>(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the extension `Left` is not set")]
+    fn missing_extension() {
+        Request::default().ext::<Left>();
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        #[handler(internal)]
+        fn index(left: Ext<'_, Left>) -> String {
+            left.to_string()
+        }
+
+        TestClient::new(index.before(|mut req| async move {
+            req.set_ext::<Left>(7);
+            Ok(req)
+        }))
+        .get("/")
+        .send()
+        .await
+        .assert_text("7")
+        .await;
+
+        TestClient::new(index)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
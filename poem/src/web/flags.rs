@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{error::GetDataError, FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the feature flags of the request, evaluated by the
+/// [`FeatureFlags`](crate::middleware::FeatureFlags) middleware, which must be
+/// applied to the endpoint.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if the `FeatureFlags` middleware is not applied.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::FeatureFlags, test::TestClient, web::Flags, EndpointExt};
+///
+/// #[handler]
+/// fn index(flags: Flags) -> &'static str {
+///     if flags.is_enabled("new_ui") {
+///         "new"
+///     } else {
+///         "old"
+///     }
+/// }
+///
+/// let cli = TestClient::new(index.with(FeatureFlags::new().flag("new_ui", true)));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/").send().await.assert_text("new").await;
+/// # });
+/// ```
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Flags(pub(crate) Arc<BTreeMap<String, bool>>);
+
+impl Flags {
+    /// Returns `true` if the flag is enabled.
+    ///
+    /// The unknown flags are disabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.0.get(name).copied().unwrap_or_default()
+    }
+
+    /// Returns the names of the enabled flags, in alphabetical order.
+    pub fn enabled(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .iter()
+            .filter(|(_, enabled)| **enabled)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Flags {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<Flags>()
+            .cloned()
+            .ok_or_else(|| GetDataError(std::any::type_name::<Flags>()))?)
+    }
+}
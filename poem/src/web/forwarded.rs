@@ -0,0 +1,205 @@
+use std::{net::IpAddr, str::FromStr};
+
+use http::{
+    header,
+    uri::{Authority, Scheme},
+};
+use rfc7239::{NodeIdentifier, NodeName};
+
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the scheme, host and port requested by the client and
+/// the address of the client, which differ from the ones of the incoming
+/// request behind a reverse proxy.
+///
+/// The `Forwarded` and `X-Forwarded-*` headers can be set by anyone, so they
+/// are only used after the [`ForwardedHeaders`](crate::middleware::ForwardedHeaders)
+/// middleware has checked that the request comes from a trusted proxy.
+/// Otherwise, the information is taken from the incoming request.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler, http::StatusCode, middleware::ForwardedHeaders, test::TestClient,
+///     web::ForwardedInfo, EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index(info: ForwardedInfo) -> String {
+///     info.base_url().unwrap_or_default()
+/// }
+///
+/// let app = Route::new()
+///     .at("/", get(index))
+///     .with(ForwardedHeaders::new().trust_all());
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .get("/")
+///     .header("forwarded", "for=192.0.2.60;proto=https;host=example.com")
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("https://example.com").await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ForwardedInfo {
+    /// The scheme requested by the client.
+    pub scheme: Scheme,
+    /// The host requested by the client, without the port.
+    pub host: Option<String>,
+    /// The port requested by the client, if it was specified.
+    pub port: Option<u16>,
+    /// The ip address of the client.
+    pub client_ip: Option<IpAddr>,
+}
+
+#[derive(Default)]
+struct Hop {
+    for_ip: Option<IpAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+}
+
+fn split_header<'a>(req: &'a Request, name: &str) -> Vec<&'a str> {
+    req.headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn parse_host(host: &str) -> (Option<String>, Option<u16>) {
+    match Authority::from_str(host) {
+        Ok(authority) => (Some(authority.host().to_string()), authority.port_u16()),
+        Err(_) => (None, None),
+    }
+}
+
+impl ForwardedInfo {
+    /// Returns the information of the incoming request, ignoring the
+    /// forwarding headers.
+    pub(crate) fn direct(req: &Request) -> Self {
+        let (host, port) = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| req.uri().authority().map(Authority::as_str))
+            .map(parse_host)
+            .unwrap_or_default();
+
+        Self {
+            scheme: req.scheme().clone(),
+            host,
+            port,
+            client_ip: req.remote_addr().as_socket_addr().map(|addr| addr.ip()),
+        }
+    }
+
+    /// Returns the information of the original request from the forwarding
+    /// headers, skipping the proxies that are trusted.
+    pub(crate) fn from_headers(req: &Request, is_trusted: impl Fn(&IpAddr) -> bool) -> Self {
+        let mut hops: Vec<Hop> = match req
+            .headers()
+            .get(header::FORWARDED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| rfc7239::parse(value).collect::<Result<Vec<_>, _>>().ok())
+        {
+            Some(forwarded) => forwarded
+                .into_iter()
+                .map(|item| Hop {
+                    for_ip: match item.forwarded_for {
+                        Some(NodeIdentifier {
+                            name: NodeName::Ip(ip),
+                            ..
+                        }) => Some(ip),
+                        _ => None,
+                    },
+                    proto: item.protocol.map(ToString::to_string),
+                    host: item.host.map(ToString::to_string),
+                    port: None,
+                })
+                .collect(),
+            None => {
+                // `X-Forwarded-Proto`, `X-Forwarded-Host` and `X-Forwarded-Port`
+                // are often only set by the first proxy, and their leftmost
+                // values may have been sent by the client, so the rightmost
+                // values are used whatever the hop
+                let last = |name| split_header(req, name).last().map(ToString::to_string);
+                let proto = last("x-forwarded-proto");
+                let host = last("x-forwarded-host");
+                let port = last("x-forwarded-port").and_then(|port| port.parse().ok());
+                split_header(req, "x-forwarded-for")
+                    .into_iter()
+                    .map(|for_ip| Hop {
+                        for_ip: for_ip.parse().ok(),
+                        proto: proto.clone(),
+                        host: host.clone(),
+                        port,
+                    })
+                    .collect()
+            }
+        };
+
+        let mut info = Self::direct(req);
+        if hops.is_empty() {
+            return info;
+        }
+        let idx = hops
+            .iter()
+            .rposition(|hop| hop.for_ip.as_ref().map_or(true, |ip| !is_trusted(ip)))
+            .unwrap_or(0);
+        let hop = hops.swap_remove(idx);
+
+        info.client_ip = hop.for_ip;
+        if let Some(scheme) = hop.proto.and_then(|proto| proto.parse().ok()) {
+            info.scheme = scheme;
+        }
+        if let Some(host) = hop.host {
+            let (host, port) = parse_host(&host);
+            info.host = host;
+            info.port = port;
+        }
+        if let Some(port) = hop.port {
+            info.port = Some(port);
+        }
+        info
+    }
+
+    /// Returns the scheme, host and port requested by the client, such as
+    /// `https://example.com:8443`, or `None` if the host is unknown.
+    ///
+    /// The port is omitted if it is the default port of the scheme.
+    pub fn base_url(&self) -> Option<String> {
+        let host = self.host.as_ref()?;
+        let default_port = match self.scheme.as_str() {
+            "http" | "ws" => Some(80),
+            "https" | "wss" => Some(443),
+            _ => None,
+        };
+        Some(match self.port {
+            Some(port) if Some(port) != default_port => {
+                format!("{}://{}:{}", self.scheme, host, port)
+            }
+            _ => format!("{}://{}", self.scheme, host),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for ForwardedInfo {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<ForwardedInfo>()
+            .cloned()
+            .unwrap_or_else(|| ForwardedInfo::direct(req)))
+    }
+}
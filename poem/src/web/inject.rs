@@ -0,0 +1,242 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{error::InjectError, FromRequest, Request, RequestBody, Result};
+
+pub(crate) type AnyService = Arc<dyn Any + Send + Sync>;
+type Factory = Arc<dyn Fn(&Resolver<'_>) -> Result<AnyService> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Registration {
+    type_name: &'static str,
+    factory: Factory,
+    /// The instance of a singleton, shared by all the requests.
+    singleton: Option<Arc<Mutex<Option<AnyService>>>>,
+}
+
+impl Registration {
+    pub(crate) fn singleton<T, F>(factory: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Resolver<'_>) -> Result<T> + Send + Sync + 'static,
+    {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            factory: Arc::new(move |resolver| Ok(Arc::new(factory(resolver)?))),
+            singleton: Some(Default::default()),
+        }
+    }
+
+    pub(crate) fn instance<T: Send + Sync + 'static>(service: T) -> Self {
+        let service: AnyService = Arc::new(service);
+        Self {
+            type_name: std::any::type_name::<T>(),
+            singleton: Some(Arc::new(Mutex::new(Some(service.clone())))),
+            factory: Arc::new(move |_| Ok(service.clone())),
+        }
+    }
+
+    pub(crate) fn per_request<T, F>(factory: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Resolver<'_>) -> Result<T> + Send + Sync + 'static,
+    {
+        Self {
+            singleton: None,
+            ..Self::singleton(factory)
+        }
+    }
+}
+
+pub(crate) type Registrations = HashMap<TypeId, Registration>;
+
+/// The services constructed for a request.
+pub(crate) struct Injector {
+    registrations: Arc<Registrations>,
+    /// The injector of an outer `Services` middleware.
+    parent: Option<Arc<Injector>>,
+    instances: Mutex<HashMap<TypeId, AnyService>>,
+    resolving: Mutex<Vec<TypeId>>,
+}
+
+impl Injector {
+    pub(crate) fn new(registrations: Arc<Registrations>, parent: Option<Arc<Injector>>) -> Self {
+        Self {
+            registrations,
+            parent,
+            instances: Default::default(),
+            resolving: Default::default(),
+        }
+    }
+
+    fn get<T: Send + Sync + 'static>(&self, req: &Request) -> Result<Arc<T>> {
+        let type_id = TypeId::of::<T>();
+        let type_name = std::any::type_name::<T>();
+        let registration = match (self.registrations.get(&type_id), &self.parent) {
+            (Some(registration), _) => registration,
+            (None, Some(parent)) => return parent.get(req),
+            (None, None) => return Err(InjectError::NotRegistered(type_name).into()),
+        };
+
+        let cached = match &registration.singleton {
+            Some(singleton) => singleton.lock().clone(),
+            None => self.instances.lock().get(&type_id).cloned(),
+        };
+        let service = match cached {
+            Some(service) => service,
+            None => {
+                let service = self.construct(req, type_id, registration)?;
+                match &registration.singleton {
+                    // keep the first instance if two requests construct it concurrently
+                    Some(singleton) => singleton.lock().get_or_insert(service).clone(),
+                    None => self
+                        .instances
+                        .lock()
+                        .entry(type_id)
+                        .or_insert(service)
+                        .clone(),
+                }
+            }
+        };
+
+        Ok(service
+            .downcast()
+            .expect("the service has the type of its registration"))
+    }
+
+    fn construct(
+        &self,
+        req: &Request,
+        type_id: TypeId,
+        registration: &Registration,
+    ) -> Result<AnyService> {
+        {
+            let mut resolving = self.resolving.lock();
+            if resolving.contains(&type_id) {
+                return Err(InjectError::Cycle(registration.type_name).into());
+            }
+            resolving.push(type_id);
+        }
+        let res = (registration.factory)(&Resolver {
+            req,
+            injector: self,
+        });
+        self.resolving.lock().retain(|id| *id != type_id);
+        res
+    }
+}
+
+/// A handle for resolving the dependencies of a service while it is
+/// constructed by a factory of the [`Services`](crate::middleware::Services) middleware.
+pub struct Resolver<'a> {
+    req: &'a Request,
+    injector: &'a Injector,
+}
+
+impl<'a> Resolver<'a> {
+    /// Returns the request which the service is constructed for.
+    ///
+    /// The singletons are constructed for the first request which injects
+    /// them.
+    pub fn request(&self) -> &'a Request {
+        self.req
+    }
+
+    /// Returns the service of type `T`, constructing it if needed.
+    ///
+    /// # Errors
+    ///
+    /// - [`InjectError`]
+    pub fn get<T: Send + Sync + 'static>(&self) -> Result<Arc<T>> {
+        self.injector.get(self.req)
+    }
+}
+
+/// An extractor for the services registered in the [`Services`](crate::middleware::Services) middleware.
+///
+/// # Errors
+///
+/// - [`InjectError`]
+/// - The errors of the factory of the service.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler, middleware::Services, test::TestClient, web::Inject, EndpointExt, Route,
+/// };
+///
+/// struct Config {
+///     greeting: String,
+/// }
+///
+/// struct Greeter {
+///     greeting: String,
+///     name: String,
+/// }
+///
+/// #[handler]
+/// fn index(greeter: Inject<Greeter>) -> String {
+///     format!("{} {}", greeter.greeting, greeter.name)
+/// }
+///
+/// let app = Route::new().at("/", index).with(
+///     Services::new()
+///         .singleton(Config {
+///             greeting: "hello".to_string(),
+///         })
+///         .per_request(|resolver| {
+///             Ok(Greeter {
+///                 greeting: resolver.get::<Config>()?.greeting.clone(),
+///                 name: resolver
+///                     .request()
+///                     .header("x-name")
+///                     .unwrap_or("world")
+///                     .to_string(),
+///             })
+///         }),
+/// );
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .header("x-name", "poem")
+///     .send()
+///     .await
+///     .assert_text("hello poem")
+///     .await;
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Inject<T>(pub Arc<T>);
+
+impl<T> Clone for Inject<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for Inject<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: Send + Sync + 'static> FromRequest<'a> for Inject<T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        let injector = req
+            .extensions()
+            .get::<Arc<Injector>>()
+            .ok_or(InjectError::MissingMiddleware(std::any::type_name::<T>()))?;
+        injector.get(req).map(Inject)
+    }
+}
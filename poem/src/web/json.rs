@@ -4,8 +4,10 @@ use http::StatusCode;
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
-    error::ParseJsonError, http::header, web::RequestBody, FromRequest, IntoResponse, Request,
-    Response, Result,
+    error::ParseJsonError,
+    http::header,
+    web::{PooledBuffer, RequestBody},
+    FromRequest, IntoResponse, Request, Response, Result,
 };
 
 /// JSON extractor and response.
@@ -133,17 +135,15 @@ fn is_json_content_type(content_type: &str) -> bool {
 
 impl<T: Serialize + Send> IntoResponse for Json<T> {
     fn into_response(self) -> Response {
-        let data = match serde_json::to_vec(&self.0) {
-            Ok(data) => data,
-            Err(err) => {
-                return Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(err.to_string())
-            }
-        };
+        let mut data = PooledBuffer::acquire();
+        if let Err(err) = serde_json::to_writer(&mut *data, &self.0) {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(err.to_string());
+        }
         Response::builder()
             .header(header::CONTENT_TYPE, "application/json; charset=utf-8")
-            .body(data)
+            .body(data.into_bytes())
     }
 }
 
@@ -0,0 +1,461 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{error::ParseListParamsError, FromRequest, Request, RequestBody, Result};
+
+/// The direction of a [`SortField`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SortDirection {
+    /// Ascending order.
+    Asc,
+    /// Descending order, written with a `-` prefix.
+    Desc,
+}
+
+/// A field of the `sort` parameter of a [`ListParams`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SortField {
+    /// The name of the field.
+    pub field: String,
+    /// The direction of the field.
+    pub direction: SortDirection,
+}
+
+impl SortField {
+    fn to_param(&self) -> String {
+        match self.direction {
+            SortDirection::Asc => self.field.clone(),
+            SortDirection::Desc => format!("-{}", self.field),
+        }
+    }
+}
+
+/// The configuration of the [`ListParams`] extractor, added to the requests
+/// of a list endpoint with [`EndpointExt::data`](crate::EndpointExt::data).
+///
+/// Only the fields of the allowlists can be sorted and filtered on, so that
+/// they can be mapped to the columns of a database query.
+#[derive(Debug, Clone)]
+pub struct ListParamsConfig {
+    sortable: Arc<[String]>,
+    filterable: Arc<[String]>,
+    default_sort: Vec<SortField>,
+    default_per_page: u32,
+    max_per_page: u32,
+}
+
+impl Default for ListParamsConfig {
+    fn default() -> Self {
+        Self {
+            sortable: Arc::new([]),
+            filterable: Arc::new([]),
+            default_sort: Vec::new(),
+            default_per_page: 20,
+            max_per_page: 100,
+        }
+    }
+}
+
+impl ListParamsConfig {
+    /// Create a `ListParamsConfig` without any sortable or filterable field.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the fields which can be sorted on.
+    #[must_use]
+    pub fn sortable<I, T>(self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            sortable: fields.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sets the fields which can be filtered on.
+    #[must_use]
+    pub fn filterable<I, T>(self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        Self {
+            filterable: fields.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sets the order used when the request has no `sort` parameter, such as
+    /// `-created_at`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sort` is not a valid `sort` parameter.
+    #[must_use]
+    pub fn default_sort(self, sort: &str) -> Self {
+        Self {
+            default_sort: parse_sort(sort).expect("invalid default sort"),
+            ..self
+        }
+    }
+
+    /// Sets the number of items per page when the request has no `per_page`
+    /// parameter.
+    ///
+    /// Default is `20`.
+    #[must_use]
+    pub fn default_per_page(self, default_per_page: u32) -> Self {
+        Self {
+            default_per_page,
+            ..self
+        }
+    }
+
+    /// Sets the maximum of the `per_page` parameter.
+    ///
+    /// Default is `100`.
+    #[must_use]
+    pub fn max_per_page(self, max_per_page: u32) -> Self {
+        Self {
+            max_per_page,
+            ..self
+        }
+    }
+}
+
+/// An extractor for the standard parameters of the list endpoints: the
+/// order, such as `sort=-created_at,name`, the filters, such as
+/// `filter[status]=open`, and the pagination, with `page` and `per_page`.
+///
+/// The parameters are validated against the allowlists of the
+/// [`ListParamsConfig`] of the request, or the default configuration, which
+/// doesn't allow any sort or filter.
+///
+/// The links to the other pages or orders of the list are built with
+/// [`ListParams::page`], [`ListParams::sort_by`] and [`ListParams::url`], or
+/// with the [`list_url`](crate::tera::functions::list_url) function in the
+/// templates.
+///
+/// # Errors
+///
+/// - [`ParseListParamsError`]
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     test::TestClient,
+///     web::{ListParams, ListParamsConfig},
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn issues(params: ListParams) -> String {
+///     format!(
+///         "{:?} {:?} offset={} limit={} next={}",
+///         params.sort().first().map(|sort| &sort.field),
+///         params.filter("status"),
+///         params.offset(),
+///         params.limit(),
+///         params.page(params.current_page() + 1).url("/issues"),
+///     )
+/// }
+///
+/// let app = Route::new().at(
+///     "/issues",
+///     get(issues).data(
+///         ListParamsConfig::new()
+///             .sortable(["created_at", "title"])
+///             .filterable(["status"]),
+///     ),
+/// );
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let cli = TestClient::new(app);
+/// cli.get("/issues?sort=-created_at&filter[status]=open&page=3")
+///     .send()
+///     .await
+///     .assert_text(
+///         "Some(\"created_at\") Some(\"open\") offset=40 limit=20 \
+///          next=/issues?sort=-created_at&filter%5Bstatus%5D=open&page=4&per_page=20",
+///     )
+///     .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ListParams {
+    sort: Vec<SortField>,
+    filters: BTreeMap<String, String>,
+    page: u32,
+    per_page: u32,
+}
+
+impl ListParams {
+    /// Parses the parameters of the query string `query`.
+    pub fn parse(query: &str, config: &ListParamsConfig) -> Result<Self, ParseListParamsError> {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(query).map_err(|_| ParseListParamsError::InvalidQuery)?;
+        let mut params = Self {
+            sort: config.default_sort.clone(),
+            filters: BTreeMap::new(),
+            page: 1,
+            per_page: config.default_per_page,
+        };
+
+        for (key, value) in pairs {
+            match key.as_str() {
+                "sort" => {
+                    params.sort = parse_sort(&value)?;
+                    if let Some(sort) = params
+                        .sort
+                        .iter()
+                        .find(|sort| !config.sortable.contains(&sort.field))
+                    {
+                        return Err(ParseListParamsError::UnknownSortField(sort.field.clone()));
+                    }
+                }
+                "page" => {
+                    params.page = match value.parse() {
+                        Ok(page) if page > 0 => page,
+                        _ => return Err(ParseListParamsError::InvalidPage(value)),
+                    }
+                }
+                "per_page" => {
+                    params.per_page = match value.parse() {
+                        Ok(per_page) if (1..=config.max_per_page).contains(&per_page) => per_page,
+                        _ => return Err(ParseListParamsError::InvalidPageSize(value)),
+                    }
+                }
+                _ => {
+                    if let Some(field) = key
+                        .strip_prefix("filter[")
+                        .and_then(|key| key.strip_suffix(']'))
+                    {
+                        if !config.filterable.iter().any(|name| name == field) {
+                            return Err(ParseListParamsError::UnknownFilterField(
+                                field.to_string(),
+                            ));
+                        }
+                        params.filters.insert(field.to_string(), value);
+                    }
+                }
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// Returns the order of the list, with the most significant field first.
+    pub fn sort(&self) -> &[SortField] {
+        &self.sort
+    }
+
+    /// Returns the filters of the list.
+    pub fn filters(&self) -> &BTreeMap<String, String> {
+        &self.filters
+    }
+
+    /// Returns the value of the filter of `field`.
+    pub fn filter(&self, field: &str) -> Option<&str> {
+        self.filters.get(field).map(String::as_str)
+    }
+
+    /// Returns the current page, starting from `1`.
+    pub fn current_page(&self) -> u32 {
+        self.page
+    }
+
+    /// Returns the number of items per page.
+    pub fn per_page(&self) -> u32 {
+        self.per_page
+    }
+
+    /// Returns the number of items before the current page.
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+
+    /// Returns the maximum number of items of the current page.
+    pub fn limit(&self) -> u64 {
+        self.per_page.into()
+    }
+
+    /// Returns the parameters of the page `page` of the list.
+    #[must_use]
+    pub fn page(&self, page: u32) -> Self {
+        Self {
+            page: page.max(1),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the parameters of the first page of the list sorted by
+    /// `field`, toggling the direction if the list is already sorted by it.
+    #[must_use]
+    pub fn sort_by(&self, field: &str) -> Self {
+        let direction = match self.sort.first() {
+            Some(sort) if sort.field == field && sort.direction == SortDirection::Asc => {
+                SortDirection::Desc
+            }
+            _ => SortDirection::Asc,
+        };
+        Self {
+            sort: vec![SortField {
+                field: field.to_string(),
+                direction,
+            }],
+            page: 1,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the equivalent query string.
+    pub fn to_query_string(&self) -> String {
+        let mut pairs = Vec::new();
+        if !self.sort.is_empty() {
+            let sort = self
+                .sort
+                .iter()
+                .map(SortField::to_param)
+                .collect::<Vec<_>>();
+            pairs.push(("sort".to_string(), sort.join(",")));
+        }
+        for (field, value) in &self.filters {
+            pairs.push((format!("filter[{field}]"), value.clone()));
+        }
+        pairs.push(("page".to_string(), self.page.to_string()));
+        pairs.push(("per_page".to_string(), self.per_page.to_string()));
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+
+    /// Returns the URL of the list at `path` with these parameters.
+    pub fn url(&self, path: &str) -> String {
+        format!("{path}?{}", self.to_query_string())
+    }
+
+    pub(crate) fn from_request_without_body(
+        req: &Request,
+    ) -> Result<ListParams, ParseListParamsError> {
+        let default_config = ListParamsConfig::default();
+        let config = req.data::<ListParamsConfig>().unwrap_or(&default_config);
+        Self::parse(req.uri().query().unwrap_or_default(), config)
+    }
+}
+
+fn parse_sort(value: &str) -> Result<Vec<SortField>, ParseListParamsError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| {
+            let (field, direction) = match field.strip_prefix('-') {
+                Some(field) => (field, SortDirection::Desc),
+                None => (field.strip_prefix('+').unwrap_or(field), SortDirection::Asc),
+            };
+            if field.is_empty() {
+                return Err(ParseListParamsError::InvalidSort(value.to_string()));
+            }
+            Ok(SortField {
+                field: field.to_string(),
+                direction,
+            })
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for ListParams {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(Self::from_request_without_body(req)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ListParamsConfig {
+        ListParamsConfig::new()
+            .sortable(["created_at", "name"])
+            .filterable(["status"])
+            .default_sort("-created_at")
+            .max_per_page(50)
+    }
+
+    #[test]
+    fn parse() {
+        let params = ListParams::parse(
+            "sort=-created_at,name&filter%5Bstatus%5D=open&page=2&per_page=10&q=x",
+            &config(),
+        )
+        .unwrap();
+        assert_eq!(
+            params.sort(),
+            &[
+                SortField {
+                    field: "created_at".to_string(),
+                    direction: SortDirection::Desc,
+                },
+                SortField {
+                    field: "name".to_string(),
+                    direction: SortDirection::Asc,
+                },
+            ]
+        );
+        assert_eq!(params.filter("status"), Some("open"));
+        assert_eq!((params.offset(), params.limit()), (10, 10));
+
+        let params = ListParams::parse("", &config()).unwrap();
+        assert_eq!(params.sort()[0].to_param(), "-created_at");
+        assert_eq!((params.current_page(), params.per_page()), (1, 20));
+    }
+
+    #[test]
+    fn validation() {
+        let err = |query| ListParams::parse(query, &config()).unwrap_err();
+        assert_eq!(
+            err("sort=password"),
+            ParseListParamsError::UnknownSortField("password".to_string())
+        );
+        assert_eq!(
+            err("sort=-"),
+            ParseListParamsError::InvalidSort("-".to_string())
+        );
+        assert_eq!(
+            err("filter[owner]=me"),
+            ParseListParamsError::UnknownFilterField("owner".to_string())
+        );
+        assert_eq!(
+            err("page=0"),
+            ParseListParamsError::InvalidPage("0".to_string())
+        );
+        assert_eq!(
+            err("per_page=51"),
+            ParseListParamsError::InvalidPageSize("51".to_string())
+        );
+    }
+
+    #[test]
+    fn links() {
+        let params = ListParams::parse("sort=name&filter[status]=open&page=3", &config()).unwrap();
+        assert_eq!(
+            params.page(4).url("/users"),
+            "/users?sort=name&filter%5Bstatus%5D=open&page=4&per_page=20"
+        );
+        assert_eq!(
+            params.sort_by("name").to_query_string(),
+            "sort=-name&filter%5Bstatus%5D=open&page=1&per_page=20"
+        );
+        assert_eq!(
+            params.sort_by("created_at").to_query_string(),
+            "sort=created_at&filter%5Bstatus%5D=open&page=1&per_page=20"
+        );
+        assert_eq!(
+            ListParams::parse(&params.to_query_string(), &config()).unwrap(),
+            params
+        );
+    }
+}
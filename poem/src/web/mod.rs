@@ -2,36 +2,67 @@
 
 mod accept;
 mod addr;
+mod api_key;
+mod buffer_pool;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "rustls")]
+mod client_cert;
+pub(crate) mod client_disconnect;
+mod clock;
 #[cfg(feature = "compression")]
 mod compress;
 #[cfg(feature = "cookie")]
 #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
 pub mod cookie;
+#[cfg(feature = "csv")]
+mod csv;
 mod data;
+pub(crate) mod deadline;
+mod experiments;
+pub(crate) mod extension;
+mod flags;
 mod form;
+mod forwarded;
+pub(crate) mod inject;
 mod json;
+mod list_params;
+#[cfg(feature = "msgpack")]
+mod msgpack;
 #[cfg(feature = "multipart")]
 mod multipart;
+mod ndjson;
 mod path;
+mod permissions;
+mod preload;
+mod preview;
+mod principal;
 mod query;
 mod real_ip;
 mod redirect;
+mod sized_body;
 #[cfg(feature = "sse")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
 pub mod sse;
 #[cfg(feature = "static-files")]
 mod static_file;
+#[cfg(feature = "qs")]
+mod structured_query;
 #[cfg(feature = "tempfile")]
 mod tempfile;
+pub(crate) mod tx;
 #[cfg(feature = "xml")]
 mod xml;
 #[cfg(feature = "yaml")]
 mod yaml;
 #[doc(inline)]
 pub use headers;
+pub use poem_derive::ExtensionKey;
 #[cfg(feature = "csrf")]
 mod csrf;
 mod typed_header;
+#[cfg(feature = "usage")]
+mod usage;
 #[cfg(feature = "websocket")]
 #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
 pub mod websocket;
@@ -43,31 +74,66 @@ pub use async_compression::Level as CompressionLevel;
 use bytes::Bytes;
 use http::header;
 
+#[cfg(feature = "cbor")]
+pub use self::cbor::Cbor;
+#[cfg(feature = "rustls")]
+pub use self::client_cert::ClientCert;
 #[cfg(feature = "compression")]
 pub use self::compress::{Compress, CompressionAlgo};
 #[cfg(feature = "csrf")]
 pub use self::csrf::{CsrfToken, CsrfVerifier};
+#[cfg(feature = "csv")]
+pub use self::csv::{Csv, CsvStream};
+#[cfg(feature = "msgpack")]
+pub use self::msgpack::Msgpack;
 #[cfg(feature = "multipart")]
 pub use self::multipart::{Field, Multipart};
-pub(crate) use self::path::PathDeserializer;
+#[cfg(feature = "cookie")]
+pub use self::redirect::Flash;
 #[cfg(feature = "static-files")]
 pub use self::static_file::{StaticFileRequest, StaticFileResponse};
+#[cfg(feature = "qs")]
+pub use self::structured_query::{StructuredQuery, StructuredQueryConfig};
 #[cfg(feature = "tempfile")]
 pub use self::tempfile::TempFile;
+#[cfg(feature = "usage")]
+pub use self::usage::CurrentUsage;
 #[cfg(feature = "xml")]
 pub use self::xml::Xml;
 #[cfg(feature = "yaml")]
 pub use self::yaml::Yaml;
+pub(crate) use self::{
+    accept::parse_accept, buffer_pool::PooledBuffer, path::PathDeserializer,
+    redirect::NamedRedirect,
+};
 pub use self::{
     accept::Accept,
     addr::{LocalAddr, RemoteAddr},
-    data::Data,
+    api_key::ApiKey,
+    buffer_pool::BufferPoolMetrics,
+    client_disconnect::ClientDisconnect,
+    clock::{Clock, MockClock},
+    data::{Data, OptionalData},
+    deadline::Deadline,
+    experiments::Experiments,
+    extension::{Ext, ExtensionKey},
+    flags::Flags,
     form::Form,
+    forwarded::ForwardedInfo,
+    inject::{Inject, Resolver},
     json::Json,
+    list_params::{ListParams, ListParamsConfig, SortDirection, SortField},
+    ndjson::{NdJson, StreamJson},
     path::Path,
+    permissions::Permissions,
+    preload::Preload,
+    preview::PreviewMode,
+    principal::Principal,
     query::Query,
     real_ip::RealIp,
     redirect::Redirect,
+    sized_body::SizedBody,
+    tx::Tx,
     typed_header::TypedHeader,
 };
 use crate::{
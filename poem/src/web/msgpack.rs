@@ -0,0 +1,220 @@
+use std::ops::{Deref, DerefMut};
+
+use http::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::ParseMsgpackError, http::header, web::RequestBody, FromRequest, IntoResponse, Request,
+    Response, Result,
+};
+
+/// [MessagePack](https://msgpack.org/) extractor and response.
+///
+/// To extract the specified type of MessagePack from the body, `T` must
+/// implement [`serde::Deserialize`]. The `Content-Type` must be
+/// `application/msgpack`, `application/x-msgpack` or
+/// `application/vnd.msgpack`.
+///
+/// # Errors
+///
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
+/// - [`ParseMsgpackError`]
+///
+/// ```
+/// use poem::{handler, http::header, post, test::TestClient, web::Msgpack, Route};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// #[handler]
+/// async fn index(Msgpack(user): Msgpack<User>) -> String {
+///     format!("welcome {}!", user.name)
+/// }
+///
+/// let app = Route::new().at("/", post(index));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .post("/")
+///     .header(header::CONTENT_TYPE, "application/msgpack")
+///     // {"name": "foo"}
+///     .body(b"\x81\xa4name\xa3foo".to_vec())
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("welcome foo!").await;
+/// # });
+/// ```
+///
+/// # Response
+///
+/// To serialize the specified type to MessagePack, `T` must implement
+/// [`serde::Serialize`]. Structs are serialized as maps, so that the clients
+/// can read the fields by name.
+///
+/// The [`Accept`](crate::web::Accept) extractor can be used to respond with
+/// MessagePack only to the clients that ask for it:
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     web::{Accept, Json, Msgpack},
+///     IntoResponse, Response, Route,
+/// };
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// #[handler]
+/// async fn index(accept: Accept) -> Response {
+///     let user = User {
+///         name: "foo".to_string(),
+///     };
+///     match accept.0.first() {
+///         Some(mime) if mime.essence_str() == "application/msgpack" => {
+///             Msgpack(user).into_response()
+///         }
+///         _ => Json(user).into_response(),
+///     }
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(docsrs, doc(cfg(feature = "msgpack")))]
+pub struct Msgpack<T>(pub T);
+
+impl<T> Deref for Msgpack<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Msgpack<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: DeserializeOwned> FromRequest<'a> for Msgpack<T> {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .ok_or(ParseMsgpackError::ContentTypeRequired)?;
+        if !is_msgpack_content_type(content_type) {
+            return Err(ParseMsgpackError::InvalidContentType(content_type.into()).into());
+        }
+
+        Ok(Self(
+            rmp_serde::from_slice(&body.take()?.into_bytes().await?)
+                .map_err(ParseMsgpackError::Parse)?,
+        ))
+    }
+}
+
+fn is_msgpack_content_type(content_type: &str) -> bool {
+    matches!(content_type.parse::<mime::Mime>(),
+        Ok(content_type) if content_type.type_() == "application"
+        && matches!(content_type.subtype().as_str(), "msgpack" | "x-msgpack" | "vnd.msgpack"))
+}
+
+impl<T: Serialize + Send> IntoResponse for Msgpack<T> {
+    fn into_response(self) -> Response {
+        let data = match rmp_serde::to_vec_named(&self.0) {
+            Ok(data) => data,
+            Err(err) => {
+                return Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(err.to_string())
+            }
+        };
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .body(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{handler, test::TestClient};
+
+    #[derive(Deserialize, Serialize, Debug, Eq, PartialEq)]
+    struct CreateResource {
+        name: String,
+        value: i32,
+    }
+
+    #[tokio::test]
+    async fn msgpack_extractor() {
+        #[handler(internal)]
+        async fn index(query: Msgpack<CreateResource>) {
+            assert_eq!(query.name, "abc");
+            assert_eq!(query.value, 100);
+        }
+
+        let data = rmp_serde::to_vec_named(&CreateResource {
+            name: "abc".to_string(),
+            value: 100,
+        })
+        .unwrap();
+        let cli = TestClient::new(index);
+        cli.post("/")
+            .header(header::CONTENT_TYPE, "application/x-msgpack")
+            .body(data.clone())
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        cli.post("/")
+            .body(data)
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        cli.post("/")
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .body(b"\xc1".to_vec())
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn msgpack_response() {
+        #[handler(internal)]
+        async fn index() -> Msgpack<CreateResource> {
+            Msgpack(CreateResource {
+                name: "abc".to_string(),
+                value: 100,
+            })
+        }
+
+        let cli = TestClient::new(index);
+        let resp = cli.get("/").send().await;
+        resp.assert_status_is_ok();
+        resp.assert_content_type("application/msgpack");
+        let data = resp.0.into_body().into_vec().await.unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<CreateResource>(&data).unwrap(),
+            CreateResource {
+                name: "abc".to_string(),
+                value: 100,
+            }
+        );
+    }
+}
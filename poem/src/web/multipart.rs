@@ -12,6 +12,11 @@ use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::io::{AsyncSeekExt, SeekFrom};
 
 use crate::{error::ParseMultipartError, http::header, FromRequest, Request, RequestBody, Result};
+#[cfg(feature = "storage")]
+use crate::{
+    storage::{ObjectMeta, Storage},
+    Body,
+};
 
 /// A single field in a multipart stream.
 #[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
@@ -90,6 +95,21 @@ impl Field {
         Ok(file)
     }
 
+    /// Stores the field data in `storage` with the specified key, and returns
+    /// the metadata of the object.
+    #[cfg(feature = "storage")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "storage")))]
+    pub async fn store<S: Storage + ?Sized>(self, storage: &S, key: &str) -> Result<ObjectMeta> {
+        let content_type = self.content_type().map(ToString::to_string);
+        storage
+            .put(
+                key,
+                Body::from_async_read(self.into_async_read()),
+                content_type.as_deref(),
+            )
+            .await
+    }
+
     /// Consume this field to return a reader.
     pub fn into_async_read(self) -> impl AsyncRead + Send {
         tokio_util::io::StreamReader::new(
@@ -0,0 +1,339 @@
+use std::{
+    io::{Error as IoError, ErrorKind},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::{stream::BoxStream, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::ParseJsonError, web::RequestBody, Body, FromRequest, IntoResponse, Request, Response,
+    Result,
+};
+
+/// A response that writes a stream of values as
+/// [newline-delimited JSON](http://ndjson.org/).
+///
+/// Each value is serialized to a single line. The stream is only polled when
+/// the client reads the response, so a slow client applies backpressure to
+/// the producer. By default every value is sent as soon as it is ready, use
+/// [`StreamJson::batch`] to combine the values that are ready at the same time
+/// into one chunk.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::stream;
+/// use poem::{handler, test::TestClient, web::StreamJson};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Log {
+///     level: &'static str,
+/// }
+///
+/// #[handler]
+/// fn logs() -> StreamJson {
+///     StreamJson::new(stream::iter(vec![
+///         Log { level: "info" },
+///         Log { level: "warn" },
+///     ]))
+/// }
+///
+/// let cli = TestClient::new(logs);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_content_type("application/x-ndjson");
+/// resp.assert_text("{\"level\":\"info\"}\n{\"level\":\"warn\"}\n")
+///     .await;
+/// # });
+/// ```
+pub struct StreamJson {
+    stream: BoxStream<'static, serde_json::Result<Vec<u8>>>,
+    batch: usize,
+}
+
+impl StreamJson {
+    /// Create a `StreamJson` response from a stream of values.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream + Send + 'static,
+        S::Item: Serialize,
+    {
+        Self {
+            stream: stream
+                .map(|value| {
+                    let mut line = serde_json::to_vec(&value)?;
+                    line.push(b'\n');
+                    Ok(line)
+                })
+                .boxed(),
+            batch: 1,
+        }
+    }
+
+    /// Sets the maximum number of values written in a single chunk.
+    ///
+    /// The values are never delayed to fill a chunk, only those which are
+    /// already available are combined. Defaults to `1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch` is zero.
+    #[must_use]
+    pub fn batch(self, batch: usize) -> Self {
+        assert!(batch > 0, "batch must be greater than zero");
+        Self { batch, ..self }
+    }
+}
+
+impl IntoResponse for StreamJson {
+    fn into_response(self) -> Response {
+        let stream = self.stream.ready_chunks(self.batch).map(|lines| {
+            let mut chunk = Vec::new();
+            for line in lines {
+                match line {
+                    Ok(line) => chunk.extend_from_slice(&line),
+                    Err(err) => {
+                        tracing::error!(error = %err, "failed to serialize ndjson value");
+                        return Err(IoError::new(ErrorKind::Other, err));
+                    }
+                }
+            }
+            Ok(Bytes::from(chunk))
+        });
+
+        Response::builder()
+            .content_type("application/x-ndjson")
+            .header("X-Accel-Buffering", "no")
+            .body(Body::from_bytes_stream(stream))
+    }
+}
+
+/// An extractor that reads the request body as a stream of
+/// [newline-delimited JSON](http://ndjson.org/) values.
+///
+/// The body is parsed incrementally while the stream is consumed, and empty
+/// lines are skipped. Unlike [`Json`](crate::web::Json), the `Content-Type`
+/// of the request is not checked, because the clients shipping logs use a
+/// variety of them.
+///
+/// # Errors
+///
+/// The stream yields [`ParseJsonError`] if a line is not a valid value and
+/// then continues with the next line, or
+/// [`ReadBodyError`](crate::error::ReadBodyError) if the body cannot be read
+/// or a line is longer than the
+/// [`max_line_length`](NdJson::max_line_length) and then ends.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::StreamExt;
+/// use poem::{handler, post, test::TestClient, web::NdJson, Result, Route};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Log {
+///     level: String,
+/// }
+///
+/// #[handler]
+/// async fn ingest(mut logs: NdJson<Log>) -> Result<String> {
+///     let mut count = 0;
+///     while let Some(log) = logs.next().await {
+///         log?;
+///         count += 1;
+///     }
+///     Ok(count.to_string())
+/// }
+///
+/// let cli = TestClient::new(Route::new().at("/", post(ingest)));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli
+///     .post("/")
+///     .body("{\"level\":\"info\"}\n\n{\"level\":\"warn\"}")
+///     .send()
+///     .await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("2").await;
+/// # });
+/// ```
+pub struct NdJson<T> {
+    body: BoxStream<'static, Result<Bytes, IoError>>,
+    buf: BytesMut,
+    eof: bool,
+    max_line_length: usize,
+    _mark: std::marker::PhantomData<fn() -> T>,
+}
+
+#[async_trait::async_trait]
+impl<'a, T: DeserializeOwned> FromRequest<'a> for NdJson<T> {
+    async fn from_request(_req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        Ok(Self {
+            body: body.take()?.into_bytes_stream().boxed(),
+            buf: BytesMut::new(),
+            eof: false,
+            max_line_length: 1024 * 1024,
+            _mark: std::marker::PhantomData,
+        })
+    }
+}
+
+impl<T: DeserializeOwned> NdJson<T> {
+    /// Sets the maximum length of a line in bytes, so that a body without
+    /// newlines is not buffered in memory. A longer line ends the stream with
+    /// [`ReadBodyError::PayloadTooLarge`](crate::error::ReadBodyError::PayloadTooLarge).
+    ///
+    /// Defaults to 1 MiB.
+    #[must_use]
+    pub fn max_line_length(self, max_line_length: usize) -> Self {
+        Self {
+            max_line_length,
+            ..self
+        }
+    }
+
+    fn parse_line(&mut self) -> Option<Result<T>> {
+        loop {
+            let newline = self.buf.iter().position(|b| *b == b'\n');
+            if newline.unwrap_or(self.buf.len()) > self.max_line_length {
+                self.eof = true;
+                self.buf.clear();
+                return Some(Err(crate::error::ReadBodyError::PayloadTooLarge.into()));
+            }
+
+            let line = match newline {
+                Some(idx) => {
+                    let line = self.buf.split_to(idx);
+                    self.buf.advance(1);
+                    line
+                }
+                None if self.eof && !self.buf.is_empty() => self.buf.split(),
+                None => return None,
+            };
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return Some(
+                serde_json::from_slice(&line).map_err(|err| ParseJsonError::Parse(err).into()),
+            );
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Stream for NdJson<T> {
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(item) = this.parse_line() {
+                return Poll::Ready(Some(item));
+            }
+            if this.eof {
+                return Poll::Ready(None);
+            }
+
+            match this.body.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(data))) => this.buf.extend_from_slice(&data),
+                Poll::Ready(Some(Err(err))) => {
+                    this.eof = true;
+                    this.buf.clear();
+                    return Poll::Ready(Some(Err(crate::error::ReadBodyError::Io(err).into())));
+                }
+                Poll::Ready(None) => this.eof = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{handler, test::TestClient};
+
+    #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+    struct Item {
+        n: i32,
+    }
+
+    #[tokio::test]
+    async fn stream_json() {
+        let resp = StreamJson::new(futures_util::stream::iter((0..3).map(|n| Item { n })))
+            .batch(2)
+            .into_response();
+        assert_eq!(resp.content_type(), Some("application/x-ndjson"));
+
+        let chunks = resp
+            .into_body()
+            .into_bytes_stream()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            chunks,
+            vec![
+                Bytes::from_static(b"{\"n\":0}\n{\"n\":1}\n"),
+                Bytes::from_static(b"{\"n\":2}\n"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ndjson_body() {
+        async fn collect(body: NdJson<Item>) -> String {
+            body.map(|item| match item {
+                Ok(item) => item.n.to_string(),
+                Err(err) => err.status().as_u16().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .await
+            .join(",")
+        }
+
+        #[handler(internal)]
+        async fn index(body: NdJson<Item>) -> String {
+            collect(body).await
+        }
+
+        let cli = TestClient::new(index);
+        let body = Body::from_bytes_stream(futures_util::stream::iter(
+            ["{\"n\":", "1}\n\r\n{\"n\":2}\nnot json\n", "{\"n\":3}"]
+                .into_iter()
+                .map(|s| Ok::<_, IoError>(Bytes::from_static(s.as_bytes()))),
+        ));
+        let resp = cli.post("/").body(body).send().await;
+        resp.assert_status_is_ok();
+        resp.assert_text("1,2,400,3").await;
+
+        #[handler(internal)]
+        async fn limited(body: NdJson<Item>) -> String {
+            collect(body.max_line_length(8)).await
+        }
+
+        let cli = TestClient::new(limited);
+        for (body, expected) in [
+            ("{\"n\":1}\n{\"n\":  2}\n{\"n\":3}", "1,413"),
+            ("{\"n\":1}\n{\"n\":22}", "1,22"),
+            ("{\"n\":1}\n           ", "1,413"),
+        ] {
+            cli.post("/")
+                .body(body)
+                .send()
+                .await
+                .assert_text(expected)
+                .await;
+        }
+    }
+}
@@ -1,5 +1,10 @@
-use std::fmt::{self, Display};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+    sync::Arc,
+};
 
+use percent_encoding::percent_decode_str;
 use serde::{
     de::{self, DeserializeSeed, EnumAccess, Error, MapAccess, SeqAccess, VariantAccess, Visitor},
     forward_to_deserialize_any, Deserializer,
@@ -62,25 +67,45 @@ macro_rules! parse_single_value {
                 ));
             }
 
-            let value = self.url_params[0].1.parse().map_err(|_| {
-                PathDeserializerError::custom(format!(
-                    "can not parse `{:?}` to a `{}`",
-                    self.url_params[0].1.as_str(),
-                    $tp
-                ))
+            let value = decode(&self.url_params[0].1)?;
+            let value = value.parse().map_err(|_| {
+                PathDeserializerError::custom(format!("can not parse `{:?}` to a `{}`", value, $tp))
             })?;
             visitor.$visit_fn(value)
         }
     };
 }
 
+/// Percent-decodes a path parameter, which borrows it unless it contains an
+/// escaped character.
+fn decode(value: &str) -> Result<Cow<'_, str>, PathDeserializerError> {
+    percent_decode_str(value)
+        .decode_utf8()
+        .map_err(|_| PathDeserializerError::custom(format!("invalid utf-8 in `{value}`")))
+}
+
+fn visit_cow_str<'de, V>(
+    value: Cow<'de, str>,
+    visitor: V,
+) -> Result<V::Value, PathDeserializerError>
+where
+    V: Visitor<'de>,
+{
+    match value {
+        Cow::Borrowed(value) => visitor.visit_borrowed_str(value),
+        Cow::Owned(value) => visitor.visit_string(value),
+    }
+}
+
+/// Deserializes the raw path parameters matched by the router, and only
+/// percent-decodes the values when they are deserialized.
 pub(crate) struct PathDeserializer<'de> {
-    url_params: &'de [(String, String)],
+    url_params: &'de [(Arc<str>, String)],
 }
 
 impl<'de> PathDeserializer<'de> {
     #[inline]
-    pub(crate) fn new(url_params: &'de [(String, String)]) -> Self {
+    pub(crate) fn new(url_params: &'de [(Arc<str>, String)]) -> Self {
         PathDeserializer { url_params }
     }
 }
@@ -119,7 +144,7 @@ impl<'de> Deserializer<'de> for PathDeserializer<'de> {
                 self.url_params.len()
             )));
         }
-        visitor.visit_str(&self.url_params[0].1)
+        visit_cow_str(decode(&self.url_params[0].1)?, visitor)
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -242,13 +267,13 @@ impl<'de> Deserializer<'de> for PathDeserializer<'de> {
         }
 
         visitor.visit_enum(EnumDeserializer {
-            value: &self.url_params[0].1,
+            value: decode(&self.url_params[0].1)?,
         })
     }
 }
 
 struct MapDeserializer<'de> {
-    params: &'de [(String, String)],
+    params: &'de [(Arc<str>, String)],
     value: Option<&'de str>,
 }
 
@@ -263,7 +288,10 @@ impl<'de> MapAccess<'de> for MapDeserializer<'de> {
             Some(((key, value), tail)) => {
                 self.value = Some(value);
                 self.params = tail;
-                seed.deserialize(KeyDeserializer { key }).map(Some)
+                seed.deserialize(KeyDeserializer {
+                    key: Cow::Borrowed(key),
+                })
+                .map(Some)
             }
             None => Ok(None),
         }
@@ -274,14 +302,16 @@ impl<'de> MapAccess<'de> for MapDeserializer<'de> {
         V: DeserializeSeed<'de>,
     {
         match self.value.take() {
-            Some(value) => seed.deserialize(ValueDeserializer { value }),
+            Some(value) => seed.deserialize(ValueDeserializer {
+                value: decode(value)?,
+            }),
             None => Err(serde::de::Error::custom("value is missing")),
         }
     }
 }
 
 struct KeyDeserializer<'de> {
-    key: &'de str,
+    key: Cow<'de, str>,
 }
 
 macro_rules! parse_key {
@@ -290,7 +320,7 @@ macro_rules! parse_key {
         where
             V: Visitor<'de>,
         {
-            visitor.visit_str(self.key)
+            visit_cow_str(self.key, visitor)
         }
     };
 }
@@ -334,7 +364,7 @@ macro_rules! parse_value {
 }
 
 struct ValueDeserializer<'de> {
-    value: &'de str,
+    value: Cow<'de, str>,
 }
 
 impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
@@ -364,14 +394,17 @@ impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.value)
+        visit_cow_str(self.value, visitor)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(self.value.as_bytes())
+        match self.value {
+            Cow::Borrowed(value) => visitor.visit_borrowed_bytes(value.as_bytes()),
+            Cow::Owned(value) => visitor.visit_byte_buf(value.into_bytes()),
+        }
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -464,7 +497,7 @@ impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
 }
 
 struct EnumDeserializer<'de> {
-    value: &'de str,
+    value: Cow<'de, str>,
 }
 
 impl<'de> EnumAccess<'de> for EnumDeserializer<'de> {
@@ -518,7 +551,7 @@ impl<'de> VariantAccess<'de> for UnitVariant {
 }
 
 struct SeqDeserializer<'de> {
-    params: &'de [(String, String)],
+    params: &'de [(Arc<str>, String)],
 }
 
 impl<'de> SeqAccess<'de> for SeqDeserializer<'de> {
@@ -531,7 +564,9 @@ impl<'de> SeqAccess<'de> for SeqDeserializer<'de> {
         match self.params.split_first() {
             Some(((_, value), tail)) => {
                 self.params = tail;
-                Ok(Some(seed.deserialize(ValueDeserializer { value })?))
+                Ok(Some(seed.deserialize(ValueDeserializer {
+                    value: decode(value)?,
+                })?))
             }
             None => Ok(None),
         }
@@ -566,7 +601,7 @@ mod tests {
     fn create_url_params<I, K, V>(values: I) -> PathParams
     where
         I: IntoIterator<Item = (K, V)>,
-        K: Into<String>,
+        K: Into<Arc<str>>,
         V: Into<String>,
     {
         values
@@ -601,6 +636,8 @@ mod tests {
         check_single_value!(f32, "123", 123.0);
         check_single_value!(f64, "123", 123.0);
         check_single_value!(String, "abc", "abc");
+        check_single_value!(String, "a%20b", "a b");
+        check_single_value!(u32, "%31%32", 12);
         check_single_value!(char, "a", 'a');
 
         let url_params = create_url_params(vec![("a", "B")]);
@@ -616,6 +653,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_invalid_utf8() {
+        let url_params = create_url_params(vec![("a", "%FF")]);
+        assert_eq!(
+            String::deserialize(PathDeserializer::new(&url_params)).unwrap_err(),
+            PathDeserializerError::custom("invalid utf-8 in `%FF`".to_string())
+        );
+    }
+
     #[test]
     fn test_parse_seq() {
         let url_params = create_url_params(vec![("a", "1"), ("b", "true"), ("c", "abc")]);
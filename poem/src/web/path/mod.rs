@@ -3,7 +3,7 @@ mod de;
 use std::ops::{Deref, DerefMut};
 
 pub(crate) use de::PathDeserializer;
-use serde::de::DeserializeOwned;
+use serde::Deserialize;
 
 use crate::{error::ParsePathError, FromRequest, Request, RequestBody, Result};
 
@@ -66,6 +66,39 @@ use crate::{error::ParsePathError, FromRequest, Request, RequestBody, Result};
 /// # });
 /// ```
 ///
+/// The parameters are borrowed from the request when possible, and are only
+/// percent-decoded when they are deserialized: a segment containing escaped
+/// characters is decoded into a new `String`, so it can't be deserialized
+/// into a `&str`, but can be into a `Cow<str>` marked with `#[serde(borrow)]`.
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use poem::{get, handler, test::TestClient, web::Path, Route};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct File<'a> {
+///     dir: &'a str,
+///     #[serde(borrow)]
+///     name: Cow<'a, str>,
+/// }
+///
+/// #[handler]
+/// async fn file(Path(file): Path<File<'_>>) -> String {
+///     format!("{}:{}", file.dir, file.name)
+/// }
+///
+/// let app = Route::new().at("/files/:dir/:name", get(file));
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/files/docs/hello%20world.txt").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("docs:hello world.txt").await;
+/// # });
+/// ```
+///
 /// Path segments also can be deserialized into any type that implements [`serde::Deserialize`](https://docs.rs/serde/1.0.127/serde/trait.Deserialize.html).
 /// Path segment labels will be matched with struct field names.
 ///
@@ -116,8 +149,8 @@ impl<T> DerefMut for Path<T> {
     }
 }
 
-impl<T: DeserializeOwned> Path<T> {
-    async fn internal_from_request(req: &Request) -> Result<Self, ParsePathError> {
+impl<'a, T: Deserialize<'a>> Path<T> {
+    async fn internal_from_request(req: &'a Request) -> Result<Self, ParsePathError> {
         Ok(Path(
             T::deserialize(de::PathDeserializer::new(&req.state().match_params))
                 .map_err(|_| ParsePathError)?,
@@ -126,8 +159,76 @@ impl<T: DeserializeOwned> Path<T> {
 }
 
 #[async_trait::async_trait]
-impl<'a, T: DeserializeOwned> FromRequest<'a> for Path<T> {
+impl<'a, T: Deserialize<'a>> FromRequest<'a> for Path<T> {
     async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
         Self::internal_from_request(req).await.map_err(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{handler, http::StatusCode, test::TestClient, Route};
+
+    #[tokio::test]
+    async fn borrowed() {
+        #[derive(Deserialize)]
+        struct Params<'a> {
+            #[serde(borrow)]
+            name: Cow<'a, str>,
+            id: u32,
+        }
+
+        #[handler(internal)]
+        fn single(Path(name): Path<&str>) -> String {
+            name.to_string()
+        }
+
+        #[handler(internal)]
+        fn owned(Path(name): Path<String>) -> String {
+            name
+        }
+
+        #[handler(internal)]
+        fn params(Path(params): Path<Params<'_>>) -> String {
+            let borrowed = matches!(params.name, Cow::Borrowed(_));
+            format!("{}:{}:{}", params.name, params.id, borrowed)
+        }
+
+        let cli = TestClient::new(
+            Route::new()
+                .at("/single/:name", single)
+                .at("/owned/:name", owned)
+                .at("/params/:name/:id", params),
+        );
+        cli.get("/single/abc").send().await.assert_text("abc").await;
+        cli.get("/single/a%2Fb")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.get("/owned/a%2Fb")
+            .send()
+            .await
+            .assert_text("a/b")
+            .await;
+        cli.get("/params/abc/100")
+            .send()
+            .await
+            .assert_text("abc:100:true")
+            .await;
+        cli.get("/params/a%2Fb/100")
+            .send()
+            .await
+            .assert_text("a/b:100:false")
+            .await;
+        cli.get("/params/abc/%31%30%30")
+            .send()
+            .await
+            .assert_text("abc:100:true")
+            .await;
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{btree_set, BTreeSet};
+
+use serde_json::Value;
+
+use crate::{web::ApiKey, FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the scopes granted to the client of the request.
+///
+/// The scopes are collected from:
+///
+/// - the `Permissions` inserted into the extensions of the request by the
+///   authentication middlewares, such as [`Permissions::from_claims`] for
+///   the claims of a JWT,
+/// - the scopes of the [`ApiKey`] validated by the
+///   [`ApiKeyAuth`](crate::middleware::ApiKeyAuth) middleware,
+/// - with the `session` feature, the [`Session`](crate::session::Session)
+///   entry named [`SESSION_KEY`](Permissions::SESSION_KEY), holding the
+///   scopes of the user logged in.
+///
+/// The [`RequireScope`](crate::guard::RequireScope) guard rejects the
+/// requests without the required scopes.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, test::TestClient, web::Permissions, EndpointExt, Request};
+/// use serde_json::json;
+///
+/// #[handler]
+/// fn index(permissions: Permissions) -> String {
+///     permissions.iter().collect::<Vec<_>>().join(",")
+/// }
+///
+/// let app = index.before(|mut req: Request| async move {
+///     let claims = json!({ "sub": "sunli", "scope": "orders:read orders:write" });
+///     req.extensions_mut().insert(Permissions::from_claims(&claims));
+///     Ok(req)
+/// });
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_text("orders:read,orders:write")
+///     .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Permissions(BTreeSet<String>);
+
+impl Permissions {
+    /// The name of the session entry holding the scopes, as an array of
+    /// strings.
+    pub const SESSION_KEY: &'static str = "scopes";
+
+    /// Create a `Permissions` without scopes.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a `Permissions` from the claims of a token.
+    ///
+    /// The scopes are read from the `scope` claim, as a space-delimited
+    /// string, and from the `scp` and `scopes` claims, as a string or an
+    /// array of strings.
+    pub fn from_claims(claims: &Value) -> Self {
+        let mut permissions = Self::new();
+        for name in ["scope", "scp", "scopes"] {
+            match claims.get(name) {
+                Some(Value::String(scopes)) => permissions.extend(scopes.split_whitespace()),
+                Some(Value::Array(scopes)) => {
+                    permissions.extend(scopes.iter().filter_map(Value::as_str))
+                }
+                _ => {}
+            }
+        }
+        permissions
+    }
+
+    /// Returns the scopes granted to the client of the request.
+    pub fn of(req: &Request) -> Self {
+        let mut permissions = req
+            .extensions()
+            .get::<Permissions>()
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(api_key) = req.extensions().get::<ApiKey>() {
+            permissions.extend(api_key.scopes.iter().cloned());
+        }
+
+        #[cfg(feature = "session")]
+        if let Some(scopes) = req
+            .extensions()
+            .get::<crate::session::Session>()
+            .and_then(|session| session.get::<Vec<String>>(Self::SESSION_KEY))
+        {
+            permissions.extend(scopes);
+        }
+
+        permissions
+    }
+
+    /// Grants a scope.
+    pub fn insert(&mut self, scope: impl Into<String>) {
+        self.0.insert(scope.into());
+    }
+
+    /// Returns `true` if the scope is granted.
+    pub fn contains(&self, scope: &str) -> bool {
+        self.0.contains(scope)
+    }
+
+    /// Returns the number of scopes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if no scope is granted.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the scopes, in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl<T: Into<String>> Extend<T> for Permissions {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.0.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+impl<T: Into<String>> FromIterator<T> for Permissions {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut permissions = Self::new();
+        permissions.extend(iter);
+        permissions
+    }
+}
+
+impl IntoIterator for Permissions {
+    type Item = String;
+    type IntoIter = btree_set::IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Permissions {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(Permissions::of(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn claims() {
+        let permissions = Permissions::from_claims(&json!({
+            "scope": "a  b",
+            "scp": ["c", 1],
+            "scopes": "d",
+        }));
+        assert_eq!(permissions.iter().collect::<Vec<_>>(), ["a", "b", "c", "d"]);
+        assert!(Permissions::from_claims(&json!({ "sub": "a" })).is_empty());
+    }
+
+    #[tokio::test]
+    async fn sources() {
+        let mut req = Request::default();
+        assert!(Permissions::from_request_without_body(&req)
+            .await
+            .unwrap()
+            .is_empty());
+
+        req.extensions_mut()
+            .insert(Permissions::from_iter(["orders:read"]));
+        req.extensions_mut()
+            .insert(ApiKey::new("key-1", "sunli").scope("orders:write"));
+        #[cfg(feature = "session")]
+        {
+            let session = crate::session::Session::default();
+            session.set(Permissions::SESSION_KEY, ["admin"]);
+            req.extensions_mut().insert(session);
+        }
+
+        let permissions = Permissions::of(&req);
+        assert!(permissions.contains("orders:read"));
+        assert!(permissions.contains("orders:write"));
+        assert_eq!(permissions.contains("admin"), cfg!(feature = "session"));
+    }
+}
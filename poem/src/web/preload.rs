@@ -0,0 +1,123 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use http::{header, HeaderMap, HeaderValue};
+
+use crate::{error::GetDataError, FromRequest, Request, RequestBody, Result};
+
+/// An extractor for declaring the resources a page needs, so that the client
+/// can start fetching them early.
+///
+/// The hints are collected by the
+/// [`EarlyHints`](crate::middleware::EarlyHints) middleware, which must be
+/// applied to the endpoint.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if the `EarlyHints` middleware is not applied.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, middleware::EarlyHints, test::TestClient, web::Preload, EndpointExt};
+///
+/// #[handler]
+/// fn index(preload: Preload) -> &'static str {
+///     preload
+///         .preload("/app.css", "style")
+///         .preconnect("https://fonts.example.com");
+///     "<html>...</html>"
+/// }
+///
+/// let cli = TestClient::new(index.with(EarlyHints));
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").send().await;
+/// let links = resp.0.headers().get_all("link").iter().collect::<Vec<_>>();
+/// assert_eq!(
+///     links,
+///     [
+///         "</app.css>; rel=preload; as=style",
+///         "<https://fonts.example.com>; rel=preconnect"
+///     ]
+/// );
+/// # });
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Preload(Arc<PreloadInner>);
+
+#[derive(Debug, Default)]
+struct PreloadInner {
+    links: Mutex<Vec<HeaderValue>>,
+    disabled: AtomicBool,
+}
+
+impl Preload {
+    /// Hints that the resource at `href` will be used by the page, `as_` is
+    /// its destination, such as `style`, `script`, `font` or `image`.
+    pub fn preload(&self, href: impl AsRef<str>, as_: impl AsRef<str>) -> &Self {
+        self.link(format!(
+            "<{}>; rel=preload; as={}",
+            href.as_ref(),
+            as_.as_ref()
+        ))
+    }
+
+    /// Hints that the page will fetch resources from the origin `href`.
+    pub fn preconnect(&self, href: impl AsRef<str>) -> &Self {
+        self.link(format!("<{}>; rel=preconnect", href.as_ref()))
+    }
+
+    /// Adds a raw `Link` header value, the invalid values are ignored.
+    pub fn link(&self, value: impl AsRef<str>) -> &Self {
+        if let Ok(value) = HeaderValue::from_str(value.as_ref()) {
+            let mut links = self.0.links.lock().unwrap();
+            if !links.contains(&value) {
+                links.push(value);
+            }
+        }
+        self
+    }
+
+    /// Returns `true` if no hints have been added.
+    pub fn is_empty(&self) -> bool {
+        self.0.links.lock().unwrap().is_empty()
+    }
+
+    /// Stops sending the hints of this request, including the hints added
+    /// automatically by the [`asset`](crate::tera::functions::asset)
+    /// template function.
+    ///
+    /// See also [`NoPreload`](crate::middleware::NoPreload) for opting a
+    /// whole route out.
+    pub fn disable(&self) {
+        self.0.disabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the hints of this request are not sent.
+    pub fn is_disabled(&self) -> bool {
+        self.0.disabled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn append_to_headers(&self, headers: &mut HeaderMap) {
+        if self.is_disabled() {
+            return;
+        }
+        for value in self.0.links.lock().unwrap().iter() {
+            headers.append(header::LINK, value.clone());
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Preload {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<Preload>()
+            .cloned()
+            .ok_or_else(|| GetDataError(std::any::type_name::<Preload>()))?)
+    }
+}
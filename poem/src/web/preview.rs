@@ -0,0 +1,45 @@
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the preview mode of the request, enabled by the
+/// [`Preview`](crate::middleware::Preview) middleware when the request
+/// carries a valid preview token.
+///
+/// The preview mode is disabled when the middleware is not applied.
+///
+/// # Example
+///
+/// ```
+/// use poem::{handler, web::PreviewMode};
+///
+/// #[handler]
+/// fn article(preview: PreviewMode) -> &'static str {
+///     if preview.is_enabled() {
+///         "draft"
+///     } else {
+///         "published"
+///     }
+/// }
+/// ```
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct PreviewMode(pub(crate) bool);
+
+impl PreviewMode {
+    /// Returns `true` if the unpublished content can be shown.
+    pub fn is_enabled(&self) -> bool {
+        self.0
+    }
+
+    /// Returns `true` if the preview mode of `req` is enabled.
+    pub(crate) fn of(req: &Request) -> bool {
+        req.extensions()
+            .get::<PreviewMode>()
+            .map_or(false, PreviewMode::is_enabled)
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for PreviewMode {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(PreviewMode(PreviewMode::of(req)))
+    }
+}
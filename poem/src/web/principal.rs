@@ -0,0 +1,123 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{http::StatusCode, Error, FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the identity of the authenticated client of the request,
+/// such as the subject of a JWT or the id of the user logged in.
+///
+/// The authentication middlewares insert the `Principal` into the extensions
+/// of the request. Otherwise, with the `session` feature, it is read from
+/// the [`Session`](crate::session::Session) entry named
+/// [`SESSION_KEY`](Principal::SESSION_KEY).
+///
+/// Use `Option<Principal>` for the routes allowing the anonymous clients.
+///
+/// # Errors
+///
+/// - `401 Unauthorized` if the request is not authenticated.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler, http::StatusCode, test::TestClient, web::Principal, Endpoint, EndpointExt,
+///     Request,
+/// };
+///
+/// #[handler]
+/// fn index(principal: Principal) -> String {
+///     format!("hello {principal}")
+/// }
+///
+/// let app = index.before(|mut req: Request| async move {
+///     if let Some(user) = req.header("x-user").map(ToString::to_string) {
+///         req.extensions_mut().insert(Principal(user));
+///     }
+///     Ok(req)
+/// });
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// cli.get("/")
+///     .header("x-user", "sunli")
+///     .send()
+///     .await
+///     .assert_text("hello sunli")
+///     .await;
+/// cli.get("/")
+///     .send()
+///     .await
+///     .assert_status(StatusCode::UNAUTHORIZED);
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Principal(pub String);
+
+impl Principal {
+    /// The name of the session entry holding the principal.
+    pub const SESSION_KEY: &'static str = "principal";
+
+    /// Returns the principal of the request, or `None` if the request is not
+    /// authenticated.
+    pub fn of(req: &Request) -> Option<Principal> {
+        if let Some(principal) = req.extensions().get::<Principal>() {
+            return Some(principal.clone());
+        }
+
+        #[cfg(feature = "session")]
+        if let Some(principal) = req
+            .extensions()
+            .get::<crate::session::Session>()
+            .and_then(|session| session.get::<String>(Self::SESSION_KEY))
+        {
+            return Some(Principal(principal));
+        }
+
+        None
+    }
+
+    /// Returns the identity as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Principal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for Principal {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Principal::of(req).ok_or_else(|| Error::from_status(StatusCode::UNAUTHORIZED))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn extension() {
+        let mut req = Request::default();
+        assert!(Principal::from_request_without_body(&req).await.is_err());
+
+        req.extensions_mut().insert(Principal("a".to_string()));
+        assert_eq!(
+            Principal::from_request_without_body(&req).await.unwrap(),
+            Principal("a".to_string())
+        );
+    }
+
+    #[cfg(feature = "session")]
+    #[tokio::test]
+    async fn session() {
+        let session = crate::session::Session::default();
+        session.set(Principal::SESSION_KEY, "b");
+        let mut req = Request::default();
+        req.extensions_mut().insert(session);
+        assert_eq!(Principal::of(&req), Some(Principal("b".to_string())));
+    }
+}
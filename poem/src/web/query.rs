@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
-use serde::de::DeserializeOwned;
+use serde::Deserialize;
 
 use crate::{error::ParseQueryError, FromRequest, Request, RequestBody, Result};
 
@@ -47,6 +47,38 @@ use crate::{error::ParseQueryError, FromRequest, Request, RequestBody, Result};
 /// resp.assert_text("foo:bar").await;
 /// # });
 /// ```
+///
+/// The values are borrowed from the query string when possible, and only the
+/// percent-encoded values are decoded into a new `String`, when they are
+/// deserialized into a `Cow<str>` marked with `#[serde(borrow)]`.
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use poem::{get, handler, test::TestClient, web::Query, Route};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Search<'a> {
+///     #[serde(borrow)]
+///     q: Cow<'a, str>,
+///     page: Option<u32>,
+/// }
+///
+/// #[handler]
+/// fn search(Query(search): Query<Search<'_>>) -> String {
+///     format!("{}:{}", search.q, search.page.unwrap_or(1))
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let app = Route::new().at("/", get(search));
+/// let cli = TestClient::new(app);
+///
+/// let resp = cli.get("/").query("q", &"hello world").send().await;
+/// resp.assert_status_is_ok();
+/// resp.assert_text("hello world:1").await;
+/// # });
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 pub struct Query<T>(pub T);
 
@@ -64,14 +96,14 @@ impl<T> DerefMut for Query<T> {
     }
 }
 
-impl<T: DeserializeOwned> Query<T> {
-    async fn internal_from_request(req: &Request) -> Result<Self, ParseQueryError> {
+impl<'a, T: Deserialize<'a>> Query<T> {
+    async fn internal_from_request(req: &'a Request) -> Result<Self, ParseQueryError> {
         Ok(serde_urlencoded::from_str(req.uri().query().unwrap_or_default()).map(Self)?)
     }
 }
 
 #[async_trait::async_trait]
-impl<'a, T: DeserializeOwned> FromRequest<'a> for Query<T> {
+impl<'a, T: Deserialize<'a>> FromRequest<'a> for Query<T> {
     async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
         Self::internal_from_request(req).await.map_err(Into::into)
     }
@@ -79,6 +111,8 @@ impl<'a, T: DeserializeOwned> FromRequest<'a> for Query<T> {
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use serde::Deserialize;
 
     use super::*;
@@ -106,4 +140,36 @@ mod tests {
             .await
             .assert_status_is_ok();
     }
+
+    #[tokio::test]
+    async fn borrowed() {
+        #[derive(Deserialize)]
+        struct Search<'a> {
+            #[serde(borrow)]
+            q: Cow<'a, str>,
+            lang: &'a str,
+        }
+
+        #[handler(internal)]
+        fn index(Query(search): Query<Search<'_>>) -> String {
+            let borrowed = matches!(search.q, Cow::Borrowed(_));
+            format!("{}:{}:{}", search.q, search.lang, borrowed)
+        }
+
+        let cli = TestClient::new(index);
+        cli.get("/")
+            .query("q", &"poem")
+            .query("lang", &"en")
+            .send()
+            .await
+            .assert_text("poem:en:true")
+            .await;
+        cli.get("/")
+            .query("q", &"hello world")
+            .query("lang", &"en")
+            .send()
+            .await
+            .assert_text("hello world:en:false")
+            .await;
+    }
 }
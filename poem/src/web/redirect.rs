@@ -1,10 +1,24 @@
 use std::fmt::Display;
 
+use http::Uri;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
 use crate::{
     http::{header, StatusCode},
-    IntoResponse, Response,
+    IntoResponse, Request, Response,
 };
 
+/// The characters that are percent-encoded in the path parameters of a named
+/// route, all but the unreserved characters.
+const PATH_PARAM: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[cfg(feature = "cookie")]
+const FLASH_COOKIE: &str = "poem-flash";
+
 /// A redirect response.
 ///
 /// # Example
@@ -30,51 +44,302 @@ use crate::{
 /// resp.assert_header(header::LOCATION, "https://www.google.com");
 /// # });
 /// ```
+///
+/// # Named routes
+///
+/// [`Redirect::to_named`] redirects to a route added with
+/// [`Route::at_named`](crate::Route::at_named). The location is resolved by
+/// the innermost [`Route`](crate::Route) enclosing the handler that knows the
+/// name, taking account of the prefix it is nested under.
+///
+/// ```
+/// use poem::{handler, http::StatusCode, post, test::TestClient, web::Redirect, Route};
+///
+/// #[handler]
+/// fn user() {}
+///
+/// #[handler]
+/// fn create_user() -> Redirect {
+///     Redirect::to_named("user", [("id", 42)])
+/// }
+///
+/// let app = Route::new().nest(
+///     "/admin",
+///     Route::new()
+///         .at_named("user", "/users/:id", user)
+///         .at("/users", post(create_user)),
+/// );
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = TestClient::new(app).post("/admin/users").send().await;
+/// resp.assert_status(StatusCode::SEE_OTHER);
+/// resp.assert_header("location", "/admin/users/42");
+/// # });
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Redirect {
     status: StatusCode,
-    uri: String,
+    target: Target,
+    #[cfg(feature = "cookie")]
+    flash: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Target {
+    Uri(String),
+    Named(NamedRedirect),
+}
+
+/// A redirect to a named route that has not been resolved yet, which is
+/// stored in the response.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct NamedRedirect {
+    pub(crate) name: String,
+    pub(crate) params: Vec<(String, String)>,
+}
+
+impl NamedRedirect {
+    /// Fills the parameters of the specified route pattern, returns `None` if
+    /// a parameter is missing or the pattern contains an anonymous regex.
+    pub(crate) fn resolve(&self, pattern: &str) -> Option<String> {
+        let param = |name: &str| {
+            self.params
+                .iter()
+                .find(|(param_name, _)| param_name == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let mut path = String::new();
+        for segment in pattern.split('/').skip(1) {
+            path.push('/');
+            if let Some(name) = segment.strip_prefix(':') {
+                let name = name.split('<').next().unwrap_or_default();
+                path.extend(utf8_percent_encode(param(name)?, PATH_PARAM));
+            } else if let Some(name) = segment.strip_prefix('*') {
+                let value = param(name)?;
+                let value = value.strip_prefix('/').unwrap_or(value);
+                for (idx, part) in value.split('/').enumerate() {
+                    if idx > 0 {
+                        path.push('/');
+                    }
+                    path.extend(utf8_percent_encode(part, PATH_PARAM));
+                }
+            } else if segment.contains('<') {
+                return None;
+            } else {
+                path.push_str(segment);
+            }
+        }
+        Some(path)
+    }
 }
 
 impl Redirect {
-    /// A simple `308` permanent redirect to a different location.
-    pub fn permanent(uri: impl Display) -> Self {
+    fn new(status: StatusCode, target: Target) -> Self {
         Self {
-            status: StatusCode::PERMANENT_REDIRECT,
-            uri: uri.to_string(),
+            status,
+            target,
+            #[cfg(feature = "cookie")]
+            flash: Vec::new(),
         }
     }
 
+    /// A simple `308` permanent redirect to a different location.
+    pub fn permanent(uri: impl Display) -> Self {
+        Self::new(StatusCode::PERMANENT_REDIRECT, Target::Uri(uri.to_string()))
+    }
+
     /// A simple `301` permanent redirect to a different location.
     pub fn moved_permanent(uri: impl Display) -> Self {
-        Self {
-            status: StatusCode::MOVED_PERMANENTLY,
-            uri: uri.to_string(),
-        }
+        Self::new(StatusCode::MOVED_PERMANENTLY, Target::Uri(uri.to_string()))
     }
 
     /// A simple `303` redirect to a different location.
     pub fn see_other(uri: impl Display) -> Self {
-        Self {
-            status: StatusCode::SEE_OTHER,
-            uri: uri.to_string(),
-        }
+        Self::new(StatusCode::SEE_OTHER, Target::Uri(uri.to_string()))
     }
 
     /// A simple `307` temporary redirect to a different location.
     pub fn temporary(uri: impl Display) -> Self {
-        Self {
-            status: StatusCode::TEMPORARY_REDIRECT,
-            uri: uri.to_string(),
+        Self::new(StatusCode::TEMPORARY_REDIRECT, Target::Uri(uri.to_string()))
+    }
+
+    /// A `303` redirect to the page that the request came from, or to `/` if
+    /// it is unknown.
+    ///
+    /// See [`Redirect::back_or`] for more details.
+    pub fn back(req: &Request) -> Self {
+        Self::back_or(req, "/")
+    }
+
+    /// A `303` redirect to the page that the request came from, or to
+    /// `fallback` if it is unknown.
+    ///
+    /// The page is read from the `Referer` header. To prevent open
+    /// redirects, it is only used if it is on the same host as the request,
+    /// and the redirect always uses its path.
+    pub fn back_or(req: &Request, fallback: impl Display) -> Self {
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| req.uri().authority().map(|authority| authority.as_str()));
+        let referer = req
+            .headers()
+            .get(header::REFERER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Uri>().ok())
+            .filter(|uri| match uri.authority() {
+                Some(authority) => Some(authority.as_str()) == host,
+                None => uri.scheme().is_none(),
+            })
+            .and_then(|uri| uri.path_and_query().map(ToString::to_string))
+            .filter(|path| path.starts_with('/') && !path.starts_with("//"));
+
+        match referer {
+            Some(path) => Self::see_other(path),
+            None => Self::see_other(fallback),
         }
     }
+
+    /// A `303` redirect to the route added with the specified name by
+    /// [`Route::at_named`](crate::Route::at_named), filling its path
+    /// parameters with `params`.
+    ///
+    /// If the name cannot be resolved, the response is
+    /// `500 Internal Server Error`.
+    pub fn to_named<K, V>(name: impl Into<String>, params: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Display,
+    {
+        Self::new(
+            StatusCode::SEE_OTHER,
+            Target::Named(NamedRedirect {
+                name: name.into(),
+                params: params
+                    .into_iter()
+                    .map(|(name, value)| (name.into(), value.to_string()))
+                    .collect(),
+            }),
+        )
+    }
+
+    /// Adds a flash message, which can be read once by the next request with
+    /// the [`Flash`](crate::web::Flash) extractor.
+    ///
+    /// The messages are stored in a cookie, so the
+    /// [`CookieJarManager`](crate::middleware::CookieJarManager) middleware is
+    /// required to read them.
+    #[cfg(feature = "cookie")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
+    #[must_use]
+    pub fn with_flash(mut self, message: impl Into<String>) -> Self {
+        self.flash.push(message.into());
+        self
+    }
 }
 
 impl IntoResponse for Redirect {
     fn into_response(self) -> Response {
-        self.status
-            .with_header(header::LOCATION, self.uri)
-            .into_response()
+        let resp = match self.target {
+            Target::Uri(uri) => self
+                .status
+                .with_header(header::LOCATION, uri)
+                .into_response(),
+            Target::Named(named) => {
+                let mut resp = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                resp.set_data((self.status, named));
+                resp
+            }
+        };
+
+        #[cfg(feature = "cookie")]
+        let resp = add_flash(resp, &self.flash);
+        resp
+    }
+}
+
+#[cfg(feature = "cookie")]
+fn add_flash(mut resp: Response, flash: &[String]) -> Response {
+    if !flash.is_empty() {
+        let mut cookie = crate::web::cookie::Cookie::new(FLASH_COOKIE, flash);
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+        if let Ok(value) = cookie.to_string().parse() {
+            resp.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    resp
+}
+
+/// An extractor for the flash messages added by [`Redirect::with_flash`].
+///
+/// The messages are removed once they have been extracted. The
+/// [`CookieJarManager`](crate::middleware::CookieJarManager) middleware is
+/// required.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     middleware::CookieJarManager,
+///     test::TestClient,
+///     web::{Flash, Redirect},
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn index(flash: Flash) -> String {
+///     flash.0.join(", ")
+/// }
+///
+/// #[handler]
+/// fn save() -> Redirect {
+///     Redirect::see_other("/").with_flash("saved")
+/// }
+///
+/// let app = Route::new()
+///     .at("/", get(index).post(save))
+///     .with(CookieJarManager::new());
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.post("/").send().await;
+/// let cookie = resp
+///     .0
+///     .headers()
+///     .get("set-cookie")
+///     .unwrap()
+///     .to_str()
+///     .unwrap();
+/// let cookie = cookie.split(';').next().unwrap().to_string();
+///
+/// let resp = cli.get("/").header("cookie", cookie).send().await;
+/// resp.assert_text("saved").await;
+/// # });
+/// ```
+#[cfg(feature = "cookie")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cookie")))]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Flash(pub Vec<String>);
+
+#[cfg(feature = "cookie")]
+#[async_trait::async_trait]
+impl<'a> crate::FromRequest<'a> for Flash {
+    async fn from_request(req: &'a Request, _body: &mut crate::RequestBody) -> crate::Result<Self> {
+        let cookie_jar = req.cookie();
+        let messages = match cookie_jar.get(FLASH_COOKIE) {
+            Some(cookie) => cookie.value::<Vec<String>>().unwrap_or_default(),
+            None => return Ok(Flash::default()),
+        };
+
+        let mut removal = crate::web::cookie::Cookie::named(FLASH_COOKIE);
+        removal.set_path("/");
+        removal.make_removal();
+        cookie_jar.add(removal);
+        Ok(Flash(messages))
     }
 }
 
@@ -102,4 +367,87 @@ mod tests {
     test_redirect!(moved_permanent, MOVED_PERMANENTLY);
     test_redirect!(see_other, SEE_OTHER);
     test_redirect!(temporary, TEMPORARY_REDIRECT);
+
+    #[test]
+    fn resolve_named() {
+        let named = NamedRedirect {
+            name: "a".to_string(),
+            params: vec![
+                ("id".to_string(), "a b".to_string()),
+                ("path".to_string(), "c/d e".to_string()),
+            ],
+        };
+        assert_eq!(named.resolve("/users/:id").as_deref(), Some("/users/a%20b"));
+        assert_eq!(
+            named.resolve("/users/:id<\\w+>/*path").as_deref(),
+            Some("/users/a%20b/c/d%20e")
+        );
+        assert_eq!(named.resolve("/users/:name"), None);
+        assert_eq!(named.resolve("/users/<\\d+>"), None);
+    }
+
+    #[tokio::test]
+    async fn back() {
+        let redirect_location = |referer: Option<&str>| {
+            let mut req = Request::builder().header(header::HOST, "example.com");
+            if let Some(referer) = referer {
+                req = req.header(header::REFERER, referer);
+            }
+            let resp = Redirect::back_or(&req.finish(), "/home").into_response();
+            assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+            resp.headers()
+                .get(header::LOCATION)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(redirect_location(None), "/home");
+        assert_eq!(
+            redirect_location(Some("https://example.com/form?a=1")),
+            "/form?a=1"
+        );
+        assert_eq!(redirect_location(Some("/form")), "/form");
+        assert_eq!(redirect_location(Some("https://evil.com/form")), "/home");
+        assert_eq!(redirect_location(Some("//evil.com/form")), "/home");
+        assert_eq!(redirect_location(Some("not a uri")), "/home");
+    }
+
+    #[cfg(feature = "cookie")]
+    #[tokio::test]
+    async fn flash() {
+        use crate::{
+            get, handler, middleware::CookieJarManager, test::TestClient, EndpointExt, Route,
+        };
+
+        #[handler(internal)]
+        fn index(flash: Flash) -> String {
+            flash.0.join(",")
+        }
+
+        #[handler(internal)]
+        fn save() -> Redirect {
+            Redirect::see_other("/").with_flash("a").with_flash("b")
+        }
+
+        let app = Route::new()
+            .at("/", get(index).post(save))
+            .with(CookieJarManager::new());
+        let cli = TestClient::new(app);
+
+        let resp = cli.post("/").send().await;
+        resp.assert_status(StatusCode::SEE_OTHER);
+        let cookie = resp.0.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.contains("Path=/"));
+        let cookie = cookie.split(';').next().unwrap().to_string();
+
+        let resp = cli.get("/").header(header::COOKIE, &cookie).send().await;
+        let removal = resp.0.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(removal.starts_with("poem-flash=;"));
+        assert!(removal.contains("Max-Age=0"));
+        resp.assert_text("a,b").await;
+
+        cli.get("/").send().await.assert_text("").await;
+    }
 }
@@ -0,0 +1,160 @@
+use std::time::SystemTime;
+
+use bytes::Bytes;
+use futures_util::Stream;
+use headers::{ETag, LastModified};
+use http::header;
+use tokio::io::AsyncRead;
+
+use crate::{Body, IntoResponse, Response};
+
+/// A response whose body is read from an [`AsyncRead`] or a stream, with a
+/// known length and optional validators.
+///
+/// Unlike [`Body::from_async_read`] and [`Body::from_bytes_stream`], the
+/// response declares its `Content-Length` and `Accept-Ranges: bytes`, and can
+/// carry the `ETag` and `Last-Modified` headers. The
+/// [`Conditional`](crate::middleware::Conditional) middleware uses them to
+/// answer conditional and `Range` requests, as is done for static files.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler, http::StatusCode, middleware::Conditional, test::TestClient, web::SizedBody,
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn download() -> SizedBody {
+///     SizedBody::from_async_read(&b"hello world"[..], 11)
+///         .content_type("text/plain")
+///         .etag("\"v1\"")
+/// }
+///
+/// let app = Route::new().at("/", get(download)).with(Conditional);
+/// let cli = TestClient::new(app);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let resp = cli.get("/").header("range", "bytes=6-").send().await;
+/// resp.assert_status(StatusCode::PARTIAL_CONTENT);
+/// resp.assert_header("content-range", "bytes 6-10/11");
+/// resp.assert_text("world").await;
+///
+/// let resp = cli.get("/").header("if-none-match", "\"v1\"").send().await;
+/// resp.assert_status(StatusCode::NOT_MODIFIED);
+/// # });
+/// ```
+pub struct SizedBody {
+    body: Body,
+    content_length: u64,
+    content_type: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<SystemTime>,
+}
+
+impl SizedBody {
+    /// Create a `SizedBody` from a reader which yields `content_length`
+    /// bytes.
+    pub fn from_async_read(reader: impl AsyncRead + Send + 'static, content_length: u64) -> Self {
+        Self::new(Body::from_async_read(reader), content_length)
+    }
+
+    /// Create a `SizedBody` from a bytes stream which yields
+    /// `content_length` bytes.
+    pub fn from_bytes_stream<S, O, E>(stream: S, content_length: u64) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Self::new(Body::from_bytes_stream(stream), content_length)
+    }
+
+    fn new(body: Body, content_length: u64) -> Self {
+        Self {
+            body,
+            content_length,
+            content_type: None,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// Sets the `Content-Type` header.
+    #[must_use]
+    pub fn content_type(self, content_type: impl Into<String>) -> Self {
+        Self {
+            content_type: Some(content_type.into()),
+            ..self
+        }
+    }
+
+    /// Sets the `ETag` header, which must be a quoted string such as
+    /// `"\"v1\""` or `"W/\"v1\""`.
+    #[must_use]
+    pub fn etag(self, etag: impl Into<String>) -> Self {
+        Self {
+            etag: Some(etag.into()),
+            ..self
+        }
+    }
+
+    /// Sets the `Last-Modified` header.
+    #[must_use]
+    pub fn last_modified(self, last_modified: SystemTime) -> Self {
+        Self {
+            last_modified: Some(last_modified),
+            ..self
+        }
+    }
+}
+
+impl IntoResponse for SizedBody {
+    fn into_response(self) -> Response {
+        let mut builder = Response::builder()
+            .header(header::ACCEPT_RANGES, "bytes")
+            .header(header::CONTENT_LENGTH, self.content_length);
+
+        if let Some(content_type) = self.content_type {
+            builder = builder.content_type(content_type);
+        }
+        if let Some(etag) = self.etag.and_then(|etag| etag.parse::<ETag>().ok()) {
+            builder = builder.typed_header(etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            builder = builder.typed_header(LastModified::from(last_modified));
+        }
+
+        builder.body(self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Error as IoError, time::Duration};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn headers() {
+        let resp = SizedBody::from_bytes_stream(
+            futures_util::stream::iter([Ok::<_, IoError>("abc"), Ok("de")]),
+            5,
+        )
+        .content_type("text/plain")
+        .etag("\"abc\"")
+        .last_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+        .into_response();
+
+        assert_eq!(resp.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(resp.headers()[header::CONTENT_LENGTH], "5");
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(resp.headers()[header::ETAG], "\"abc\"");
+        assert_eq!(
+            resp.headers()[header::LAST_MODIFIED],
+            "Thu, 01 Jan 1970 00:00:01 GMT"
+        );
+        assert_eq!(resp.into_body().into_string().await.unwrap(), "abcde");
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::{Event, SSE};
+
+struct History {
+    events: VecDeque<Event>,
+    capacity: usize,
+    next_id: u64,
+}
+
+/// A handle for broadcasting server-sent events to every request that
+/// subscribes to it.
+///
+/// Messages sent without an id are given an increasing numeric id, and the
+/// last `capacity` messages are retained, so that a client which reconnects
+/// with a [`LastEventId`](super::LastEventId) receives the messages it has
+/// missed. Subscribers which fall more than `capacity` messages behind skip
+/// the oldest ones.
+///
+/// By default, the subscriptions send a keep-alive comment every 15 seconds.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     get, handler,
+///     web::{
+///         sse::{Event, LastEventId, SseChannel, SSE},
+///         Data,
+///     },
+///     EndpointExt, Route,
+/// };
+///
+/// #[handler]
+/// fn events(channel: Data<&SseChannel>, last_event_id: LastEventId) -> SSE {
+///     match last_event_id.0 {
+///         Some(id) => channel.subscribe_from(&id),
+///         None => channel.subscribe(),
+///     }
+/// }
+///
+/// #[handler]
+/// fn publish(channel: Data<&SseChannel>, body: String) {
+///     channel.send(Event::message(body));
+/// }
+///
+/// let app = Route::new()
+///     .at("/events", get(events).post(publish))
+///     .data(SseChannel::new(100));
+/// ```
+#[derive(Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+pub struct SseChannel {
+    sender: broadcast::Sender<Event>,
+    history: Arc<Mutex<History>>,
+    keep_alive: Option<Duration>,
+    retry: Option<Duration>,
+}
+
+impl SseChannel {
+    /// Create an `SseChannel` that retains the specified number of messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            history: Arc::new(Mutex::new(History {
+                events: VecDeque::with_capacity(capacity),
+                capacity,
+                next_id: 1,
+            })),
+            keep_alive: Some(Duration::from_secs(15)),
+            retry: None,
+        }
+    }
+
+    /// Set the keep alive interval of the subscriptions.
+    #[must_use]
+    pub fn keep_alive(self, duration: Duration) -> Self {
+        Self {
+            keep_alive: Some(duration),
+            ..self
+        }
+    }
+
+    /// Disable the keep-alive comments.
+    #[must_use]
+    pub fn no_keep_alive(self) -> Self {
+        Self {
+            keep_alive: None,
+            ..self
+        }
+    }
+
+    /// Set the reconnection time sent to the clients when they subscribe.
+    #[must_use]
+    pub fn retry(self, duration: Duration) -> Self {
+        Self {
+            retry: Some(duration),
+            ..self
+        }
+    }
+
+    /// Sends an event to all subscribers, and returns the number of
+    /// subscribers that it was sent to.
+    pub fn send(&self, event: Event) -> usize {
+        let mut history = self.history.lock().unwrap();
+        let event = match event {
+            Event::Message { id, event, data } if id.is_empty() => {
+                let id = history.next_id.to_string();
+                history.next_id += 1;
+                Event::Message { id, event, data }
+            }
+            event => event,
+        };
+
+        if matches!(event, Event::Message { .. }) {
+            if history.events.len() == history.capacity {
+                history.events.pop_front();
+            }
+            history.events.push_back(event.clone());
+        }
+
+        // sent while holding the lock, so that a new subscriber either finds
+        // the event in the history or receives it from the channel
+        self.sender.send(event).unwrap_or_default()
+    }
+
+    /// Returns the number of active subscriptions.
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Subscribe to the events sent after this call.
+    pub fn subscribe(&self) -> SSE {
+        let receiver = self.sender.subscribe();
+        self.create_sse(Vec::new(), receiver)
+    }
+
+    /// Subscribe to the events sent after the event with the specified id.
+    ///
+    /// If that event is no longer retained, all of the retained messages are
+    /// sent first.
+    pub fn subscribe_from(&self, last_event_id: &str) -> SSE {
+        let history = self.history.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let start = history
+            .events
+            .iter()
+            .position(|event| matches!(event, Event::Message { id, .. } if id == last_event_id))
+            .map(|idx| idx + 1)
+            .unwrap_or_default();
+        let missed = history.events.iter().skip(start).cloned().collect();
+        drop(history);
+        self.create_sse(missed, receiver)
+    }
+
+    fn create_sse(&self, missed: Vec<Event>, receiver: broadcast::Receiver<Event>) -> SSE {
+        let stream = futures_util::stream::iter(missed).chain(futures_util::stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => return Some((event, receiver)),
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        ));
+        let mut sse = SSE::new(stream);
+        if let Some(duration) = self.keep_alive {
+            sse = sse.keep_alive(duration);
+        }
+        if let Some(duration) = self.retry {
+            sse = sse.retry(duration);
+        }
+        sse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::IntoResponse;
+
+    async fn read_chunk(body: &mut (impl tokio::io::AsyncRead + Unpin)) -> String {
+        let mut buf = [0; 256];
+        let n = body.read(&mut buf).await.unwrap();
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn broadcast() {
+        let channel = SseChannel::new(8).no_keep_alive();
+        let mut a = channel
+            .subscribe()
+            .into_response()
+            .into_body()
+            .into_async_read();
+        let mut b = channel
+            .subscribe()
+            .into_response()
+            .into_body()
+            .into_async_read();
+        assert_eq!(channel.subscriber_count(), 2);
+
+        assert_eq!(channel.send(Event::message("hello")), 2);
+        assert_eq!(read_chunk(&mut a).await, "id: 1\ndata: hello\n\n");
+        assert_eq!(read_chunk(&mut b).await, "id: 1\ndata: hello\n\n");
+
+        channel.send(Event::message("world").id("custom"));
+        assert_eq!(read_chunk(&mut a).await, "id: custom\ndata: world\n\n");
+    }
+
+    #[tokio::test]
+    async fn resume() {
+        let channel = SseChannel::new(2).no_keep_alive();
+        for i in 0..3 {
+            channel.send(Event::message(i.to_string()));
+        }
+
+        let collect = |sse: SSE| async move {
+            let mut body = sse.into_response().into_body().into_bytes_stream();
+            let mut data = String::new();
+            while !data.ends_with("data: 3\n\n") {
+                let chunk = body.next().await.unwrap().unwrap();
+                data.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+            data
+        };
+
+        let resumed = tokio::spawn(collect(channel.subscribe_from("2")));
+        let evicted = tokio::spawn(collect(channel.subscribe_from("1")));
+        while channel.subscriber_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+        channel.send(Event::message("3"));
+
+        assert_eq!(
+            resumed.await.unwrap(),
+            "id: 3\ndata: 2\n\nid: 4\ndata: 3\n\n"
+        );
+        assert_eq!(
+            evicted.await.unwrap(),
+            "id: 2\ndata: 1\n\nid: 3\ndata: 2\n\nid: 4\ndata: 3\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        let channel = SseChannel::new(2)
+            .no_keep_alive()
+            .retry(Duration::from_secs(3));
+        let mut body = channel
+            .subscribe()
+            .into_response()
+            .into_body()
+            .into_async_read();
+        assert_eq!(read_chunk(&mut body).await, "retry: 3000\n\n");
+    }
+}
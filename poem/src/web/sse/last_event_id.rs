@@ -0,0 +1,38 @@
+use crate::{FromRequest, Request, RequestBody, Result};
+
+/// An extractor for the `Last-Event-ID` header, which is sent by the browser
+/// when it reconnects to an event stream, so that the server can resume from
+/// the last event the client has received.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     web::sse::{LastEventId, SseChannel, SSE},
+/// };
+///
+/// #[handler]
+/// fn events(last_event_id: LastEventId, channel: poem::web::Data<&SseChannel>) -> SSE {
+///     match last_event_id.0 {
+///         Some(id) => channel.subscribe_from(&id),
+///         None => channel.subscribe(),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+pub struct LastEventId(pub Option<String>);
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for LastEventId {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(LastEventId(
+            req.headers()
+                .get("last-event-id")
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(ToString::to_string),
+        ))
+    }
+}
@@ -1,10 +1,16 @@
 //! Server-Sent Events (SSE) types.
 
+mod channel;
 mod event;
+mod last_event_id;
 mod response;
+mod stream;
 
+pub use channel::SseChannel;
 pub use event::Event;
+pub use last_event_id::LastEventId;
 pub use response::SSE;
+pub use stream::{EventSender, EventStream};
 
 #[cfg(test)]
 mod tests {
@@ -13,7 +19,7 @@ mod tests {
     use tokio::{io::AsyncReadExt, time::Instant};
 
     use super::*;
-    use crate::IntoResponse;
+    use crate::{FromRequest, IntoResponse};
 
     #[tokio::test]
     async fn sse() {
@@ -53,6 +59,32 @@ data: 5
         );
     }
 
+    #[tokio::test]
+    async fn retry() {
+        let sse = SSE::new(futures_util::stream::iter(vec![Event::message("a")]))
+            .retry(Duration::from_millis(1500));
+        let data = sse.into_response().into_body().into_string().await.unwrap();
+        assert_eq!(data, "retry: 1500\n\ndata: a\n\n");
+    }
+
+    #[tokio::test]
+    async fn last_event_id() {
+        let req = crate::Request::builder()
+            .header("last-event-id", "42")
+            .finish();
+        let (req, mut body) = req.split();
+        assert_eq!(
+            LastEventId::from_request(&req, &mut body).await.unwrap(),
+            LastEventId(Some("42".to_string()))
+        );
+
+        let (req, mut body) = crate::Request::default().split();
+        assert_eq!(
+            LastEventId::from_request(&req, &mut body).await.unwrap(),
+            LastEventId(None)
+        );
+    }
+
     #[tokio::test]
     async fn keep_alive() {
         let sse = SSE::new(futures_util::stream::pending()).keep_alive(Duration::from_secs(1));
@@ -41,6 +41,7 @@ use crate::{Body, IntoResponse, Response};
 pub struct SSE {
     stream: BoxStream<'static, Event>,
     keep_alive: Option<Duration>,
+    retry: Option<Duration>,
 }
 
 impl SSE {
@@ -49,6 +50,7 @@ impl SSE {
         Self {
             stream: stream.boxed(),
             keep_alive: None,
+            retry: None,
         }
     }
 
@@ -60,12 +62,25 @@ impl SSE {
             ..self
         }
     }
+
+    /// Set the reconnection time, which is sent to the client before the
+    /// events.
+    #[must_use]
+    pub fn retry(self, duration: Duration) -> Self {
+        Self {
+            retry: Some(duration),
+            ..self
+        }
+    }
 }
 
 impl IntoResponse for SSE {
     fn into_response(self) -> Response {
-        let mut stream = self
-            .stream
+        let retry = self
+            .retry
+            .map(|duration| Event::retry(duration.as_millis() as u64));
+        let mut stream = futures_util::stream::iter(retry)
+            .chain(self.stream)
             .map(|event| Ok::<_, std::io::Error>(Bytes::from(event.to_string())))
             .boxed();
         if let Some(duration) = self.keep_alive {
@@ -0,0 +1,305 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::{future::BoxFuture, FutureExt, Stream};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use super::{Event, SSE};
+use crate::{
+    error::{InternalServerError, StreamClosedError},
+    IntoResponse, Response, Result,
+};
+
+/// Sends the events of an [`EventStream`].
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+pub struct EventSender<T> {
+    sender: mpsc::Sender<Event>,
+    _mark: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize> EventSender<T> {
+    /// Sends an event whose data is `item` serialized to JSON.
+    ///
+    /// Waits while the client is reading the previous events slower than they
+    /// are sent, and returns [`StreamClosedError`] if the client
+    /// disconnected.
+    pub async fn send(&self, item: T) -> Result<()> {
+        let data = serde_json::to_string(&item).map_err(InternalServerError)?;
+        self.send_event(Event::message(data)).await
+    }
+
+    /// Sends an event as it is, such as an event with a type or an id.
+    pub async fn send_event(&self, event: Event) -> Result<()> {
+        self.sender
+            .send(event)
+            .await
+            .map_err(|_| StreamClosedError.into())
+    }
+}
+
+/// A long-lived SSE response, whose events are sent by an async function,
+/// such as the tokens generated by a language model.
+///
+/// The function receives an [`EventSender`] sending the events of type `T`,
+/// which are serialized to JSON. It runs while the response is streamed, and
+/// waits while the client is reading the events slower than they are sent,
+/// so that no more than [`buffer`](EventStream::buffer) events are pending.
+/// If the client disconnects, the function is cancelled at the next
+/// `.await`.
+///
+/// If the function returns an error, an event of type `error` with the
+/// message of the error is sent before the response ends.
+///
+/// By default, a keep-alive comment is sent every 15 seconds.
+///
+/// # Example
+///
+/// ```
+/// use poem::{
+///     handler,
+///     test::TestClient,
+///     web::sse::{EventSender, EventStream},
+/// };
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// #[serde(tag = "type", rename_all = "snake_case")]
+/// enum Chunk {
+///     Token { text: String },
+///     Done,
+/// }
+///
+/// #[handler]
+/// fn chat() -> EventStream {
+///     EventStream::new(|sender: EventSender<Chunk>| async move {
+///         for text in ["Hello", " world"] {
+///             sender
+///                 .send(Chunk::Token {
+///                     text: text.to_string(),
+///                 })
+///                 .await?;
+///         }
+///         sender.send(Chunk::Done).await
+///     })
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// TestClient::new(chat)
+///     .get("/")
+///     .send()
+///     .await
+///     .assert_text(concat!(
+///         "data: {\"type\":\"token\",\"text\":\"Hello\"}\n\n",
+///         "data: {\"type\":\"token\",\"text\":\" world\"}\n\n",
+///         "data: {\"type\":\"done\"}\n\n",
+///     ))
+///     .await;
+/// # });
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+pub struct EventStream {
+    producer: Box<dyn FnOnce(mpsc::Sender<Event>) -> BoxFuture<'static, ()> + Send>,
+    buffer: usize,
+    keep_alive: Option<Duration>,
+    retry: Option<Duration>,
+}
+
+impl EventStream {
+    /// Create an `EventStream` whose events are sent by `f`.
+    pub fn new<T, F, Fut>(f: F) -> Self
+    where
+        T: Serialize + 'static,
+        F: FnOnce(EventSender<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Self {
+            producer: Box::new(move |sender| {
+                async move {
+                    let res = f(EventSender {
+                        sender: sender.clone(),
+                        _mark: PhantomData,
+                    })
+                    .await;
+                    if let Err(err) = res {
+                        if err.is::<StreamClosedError>() {
+                            return;
+                        }
+                        tracing::debug!(error = %err, "event stream failed");
+                        let event = Event::message(err.to_string()).event_type("error");
+                        let _ = sender.send(event).await;
+                    }
+                }
+                .boxed()
+            }),
+            buffer: 16,
+            keep_alive: Some(Duration::from_secs(15)),
+            retry: None,
+        }
+    }
+
+    /// Sets the number of events which can be pending before the sender
+    /// waits for the client. Default is `16`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is zero.
+    #[must_use]
+    pub fn buffer(self, buffer: usize) -> Self {
+        assert!(buffer > 0, "the buffer must not be empty");
+        Self { buffer, ..self }
+    }
+
+    /// Sets the keep alive interval, or `None` to disable it. Default is 15
+    /// seconds.
+    #[must_use]
+    pub fn keep_alive(self, duration: Option<Duration>) -> Self {
+        Self {
+            keep_alive: duration,
+            ..self
+        }
+    }
+
+    /// Sets the reconnection time, which is sent to the client before the
+    /// events.
+    #[must_use]
+    pub fn retry(self, duration: Duration) -> Self {
+        Self {
+            retry: Some(duration),
+            ..self
+        }
+    }
+}
+
+/// Drives the producer while the events are read, so that it is dropped with
+/// the response body when the client disconnects.
+struct Generator {
+    producer: Option<BoxFuture<'static, ()>>,
+    receiver: mpsc::Receiver<Event>,
+}
+
+impl Stream for Generator {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(producer) = &mut self.producer {
+            if producer.poll_unpin(cx).is_ready() {
+                self.producer = None;
+            }
+        }
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl IntoResponse for EventStream {
+    fn into_response(self) -> Response {
+        let (sender, receiver) = mpsc::channel(self.buffer);
+        let generator = Generator {
+            producer: Some((self.producer)(sender)),
+            receiver,
+        };
+
+        let mut sse = SSE::new(generator);
+        if let Some(duration) = self.keep_alive {
+            sse = sse.keep_alive(duration);
+        }
+        if let Some(duration) = self.retry {
+            sse = sse.retry(duration);
+        }
+        sse.into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::Error;
+
+    #[tokio::test]
+    async fn events() {
+        let stream = EventStream::new(|sender: EventSender<i32>| async move {
+            for i in 0..3 {
+                sender.send(i).await?;
+            }
+            sender
+                .send_event(Event::message("end").event_type("end"))
+                .await
+        })
+        .buffer(1);
+        let data = stream.into_response().into_body().into_string().await;
+        assert_eq!(
+            data.unwrap(),
+            "data: 0\n\ndata: 1\n\ndata: 2\n\nevent: end\ndata: end\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn error() {
+        let stream = EventStream::new(|sender: EventSender<i32>| async move {
+            sender.send(1).await?;
+            Err(Error::from_string(
+                "quota exceeded",
+                http::StatusCode::TOO_MANY_REQUESTS,
+            ))
+        });
+        let data = stream.into_response().into_body().into_string().await;
+        assert_eq!(
+            data.unwrap(),
+            "data: 1\n\nevent: error\ndata: quota exceeded\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn backpressure_and_cancellation() {
+        struct Running(Arc<AtomicBool>);
+
+        impl Drop for Running {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::SeqCst);
+            }
+        }
+
+        let sent = Arc::new(AtomicUsize::new(0));
+        let running = Arc::new(AtomicBool::new(true));
+        let stream = EventStream::new({
+            let sent = sent.clone();
+            let running = Running(running.clone());
+            move |sender: EventSender<usize>| async move {
+                let _running = running;
+                for i in 0.. {
+                    sender.send(i).await?;
+                    sent.fetch_add(1, Ordering::SeqCst);
+                }
+                Ok(())
+            }
+        })
+        .buffer(2);
+
+        let (sender, receiver) = mpsc::channel(stream.buffer);
+        let mut generator = Generator {
+            producer: Some((stream.producer)(sender)),
+            receiver,
+        };
+        assert_eq!(generator.next().await, Some(Event::message("0")));
+        // the producer waits once the buffer is full
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert_eq!(generator.next().await, Some(Event::message("1")));
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+
+        // the producer is cancelled when the client disconnects
+        drop(generator);
+        assert!(!running.load(Ordering::SeqCst));
+    }
+}
@@ -277,6 +277,97 @@ impl StaticFileRequest {
             content_range,
         })
     }
+
+    /// Create a response for an object of a
+    /// [`Storage`](crate::storage::Storage).
+    ///
+    /// The content type is guessed from the extension of the key if the
+    /// storage does not return it.
+    #[cfg(feature = "storage")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "storage")))]
+    pub async fn create_response_from_storage<S: crate::storage::Storage + ?Sized>(
+        self,
+        storage: &S,
+        key: &str,
+    ) -> Result<StaticFileResponse> {
+        let meta = storage.head(key).await?.ok_or(StaticFileError::NotFound)?;
+
+        if let Some(etag) = meta
+            .etag
+            .as_deref()
+            .and_then(|etag| ETag::from_str(etag).ok())
+        {
+            if let Some(if_match) = &self.if_match {
+                if !if_match.precondition_passes(&etag) {
+                    return Err(StaticFileError::PreconditionFailed.into());
+                }
+            }
+            if let Some(if_none_match) = &self.if_none_match {
+                if !if_none_match.precondition_passes(&etag) {
+                    return Ok(StaticFileResponse::NotModified);
+                }
+            }
+        }
+        if let Some(modified) = meta.last_modified {
+            if let Some(if_unmodified_since) = self.if_unmodified_since {
+                if !if_unmodified_since.precondition_passes(modified) {
+                    return Err(StaticFileError::PreconditionFailed.into());
+                }
+            }
+            if let (None, Some(if_modified_since)) = (&self.if_none_match, self.if_modified_since) {
+                if !if_modified_since.is_modified(modified) {
+                    return Ok(StaticFileResponse::NotModified);
+                }
+            }
+        }
+
+        let mut content_range = None;
+        let mut content_length = meta.size;
+        let range = match self.range.and_then(|range| range.iter().next()) {
+            Some((start, end)) => {
+                let start = match start {
+                    Bound::Included(n) => n,
+                    Bound::Excluded(n) => n + 1,
+                    Bound::Unbounded => 0,
+                };
+                let end = match end {
+                    Bound::Included(n) => n + 1,
+                    Bound::Excluded(n) => n,
+                    Bound::Unbounded => meta.size,
+                };
+                if end < start || end > meta.size {
+                    return Err(StaticFileError::RangeNotSatisfiable { size: meta.size }.into());
+                }
+                if start != 0 || end != meta.size {
+                    content_range = Some((start..end, meta.size));
+                }
+                content_length = end - start;
+                Some(start..end)
+            }
+            None => None,
+        };
+
+        let object = storage
+            .stream(key, range)
+            .await?
+            .ok_or(StaticFileError::NotFound)?;
+        let content_type = meta.content_type.clone().or_else(|| {
+            mime_guess::from_path(key)
+                .first()
+                .map(|mime| mime.to_string())
+        });
+
+        Ok(StaticFileResponse::Ok {
+            body: object.body,
+            content_length,
+            content_type,
+            etag: meta.etag,
+            last_modified: meta
+                .last_modified
+                .map(|modified| HttpDate::from(modified).to_string()),
+            content_range,
+        })
+    }
 }
 
 fn equiv_utf8_text(ct: Mime) -> Mime {
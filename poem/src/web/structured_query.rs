@@ -0,0 +1,222 @@
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{error::ParseStructuredQueryError, FromRequest, Request, RequestBody, Result};
+
+/// An extractor that can deserialize some type from a query string with
+/// nested arrays and maps, such as `?filter[status]=open&ids[]=1&ids[]=2`.
+///
+/// Unlike [`Query`](crate::web::Query), which only supports flat keys, the
+/// brackets of the keys are parsed with
+/// [`serde_qs`](https://crates.io/crates/serde_qs), whether they are
+/// percent-encoded or not. The parsing is configured with a
+/// [`StructuredQueryConfig`] added to the requests with
+/// [`EndpointExt::data`](crate::EndpointExt::data).
+///
+/// # Errors
+///
+/// - [`ParseStructuredQueryError`]
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use poem::{get, handler, test::TestClient, web::StructuredQuery, Route};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Search {
+///     filter: HashMap<String, String>,
+///     #[serde(default)]
+///     ids: Vec<u32>,
+/// }
+///
+/// #[handler]
+/// fn search(StructuredQuery(search): StructuredQuery<Search>) -> String {
+///     format!("{:?} {:?}", search.filter.get("status"), search.ids)
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let cli = TestClient::new(Route::new().at("/", get(search)));
+/// cli.get("/?filter[status]=open&ids[]=1&ids[]=2")
+///     .send()
+///     .await
+///     .assert_text(r#"Some("open") [1, 2]"#)
+///     .await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct StructuredQuery<T>(pub T);
+
+impl<T> Deref for StructuredQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for StructuredQuery<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// The configuration of the [`StructuredQuery`] extractor.
+#[derive(Debug, Clone, Copy)]
+pub struct StructuredQueryConfig {
+    max_depth: usize,
+    strict: bool,
+}
+
+impl Default for StructuredQueryConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            strict: false,
+        }
+    }
+}
+
+impl StructuredQueryConfig {
+    /// Create a `StructuredQueryConfig`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum depth of the nested keys, the deeper brackets being
+    /// kept in the key of the last level, which limits the nesting of the
+    /// untrusted query strings.
+    ///
+    /// Default is `5`.
+    #[must_use]
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Sets whether the keys that are not deserialized by the type are
+    /// rejected with [`ParseStructuredQueryError::UnknownKeys`], instead of
+    /// being ignored.
+    ///
+    /// Default is `false`.
+    #[must_use]
+    pub fn strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+}
+
+/// The value and the ignored keys of a query string.
+struct Tracked<T> {
+    value: T,
+    ignored: Vec<String>,
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tracked<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut ignored = Vec::new();
+        let value =
+            serde_ignored::deserialize(deserializer, |path| ignored.push(path.to_string()))?;
+        Ok(Self { value, ignored })
+    }
+}
+
+impl<'a, T: Deserialize<'a>> StructuredQuery<T> {
+    fn internal_from_request(req: &'a Request) -> Result<Self, ParseStructuredQueryError> {
+        let config = req
+            .data::<StructuredQueryConfig>()
+            .copied()
+            .unwrap_or_default();
+        // the brackets are allowed to be percent-encoded, as the browsers do
+        let tracked: Tracked<T> = serde_qs::Config::new(config.max_depth, false)
+            .deserialize_str(req.uri().query().unwrap_or_default())?;
+        if config.strict && !tracked.ignored.is_empty() {
+            return Err(ParseStructuredQueryError::UnknownKeys(tracked.ignored));
+        }
+        Ok(Self(tracked.value))
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: Deserialize<'a>> FromRequest<'a> for StructuredQuery<T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Self::internal_from_request(req).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{handler, test::TestClient, EndpointExt};
+
+    #[derive(Deserialize)]
+    struct Filter {
+        #[serde(default)]
+        filter: BTreeMap<String, String>,
+        #[serde(default)]
+        ids: Vec<u32>,
+    }
+
+    #[handler(internal)]
+    fn index(StructuredQuery(query): StructuredQuery<Filter>) -> String {
+        format!("{:?} {:?}", query.filter, query.ids)
+    }
+
+    #[tokio::test]
+    async fn structured_query() {
+        let cli = TestClient::new(index);
+        cli.get("/?filter[status]=open&filter[owner]=me&ids[]=1&ids[]=2")
+            .send()
+            .await
+            .assert_text(r#"{"owner": "me", "status": "open"} [1, 2]"#)
+            .await;
+        cli.get("/?filter%5Bstatus%5D=open&ids%5B%5D=3")
+            .send()
+            .await
+            .assert_text(r#"{"status": "open"} [3]"#)
+            .await;
+        cli.get("/?ids[]=x")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        cli.get("/?page=2").send().await.assert_text("{} []").await;
+    }
+
+    #[tokio::test]
+    async fn strict() {
+        let cli = TestClient::new(index.data(StructuredQueryConfig::new().strict(true)));
+        cli.get("/?ids[]=1")
+            .send()
+            .await
+            .assert_text("{} [1]")
+            .await;
+        let resp = cli.get("/?ids[]=1&page=2").send().await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+        resp.assert_text("unknown query keys: page").await;
+    }
+
+    #[tokio::test]
+    async fn max_depth() {
+        #[derive(Deserialize)]
+        struct Nested {
+            a: BTreeMap<String, String>,
+        }
+
+        #[handler(internal)]
+        fn nested(StructuredQuery(query): StructuredQuery<Nested>) -> String {
+            format!("{:?}", query.a)
+        }
+
+        let cli = TestClient::new(nested.data(StructuredQueryConfig::new().max_depth(1)));
+        cli.get("/?a[b][c]=1")
+            .send()
+            .await
+            .assert_text(r#"{"b][c]": "1"}"#)
+            .await;
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::{error::TransactionError, FromRequest, Request, RequestBody, Result};
+
+/// The transaction of a request, added to the extensions by the
+/// [`Transactional`](crate::middleware::Transactional) middleware and taken
+/// back once the endpoint has returned.
+pub(crate) struct TransactionSlot<T>(pub(crate) Arc<Mutex<Option<T>>>);
+
+/// An extractor for the transaction begun by the
+/// [`Transactional`](crate::middleware::Transactional) middleware, which
+/// dereferences to the transaction.
+///
+/// The transaction is committed or rolled back by the middleware once the
+/// handler has returned, so the handlers only use it to run their queries.
+///
+/// # Errors
+///
+/// - [`TransactionError`]
+///
+/// # Example
+///
+/// ```ignore
+/// use poem::{handler, middleware::Transactional, web::Tx, EndpointExt, Result};
+/// use sqlx::{Postgres, Transaction};
+///
+/// #[handler]
+/// async fn transfer(mut tx: Tx<Transaction<'static, Postgres>>) -> Result<()> {
+///     sqlx::query("update accounts set balance = balance - 10 where id = 1")
+///         .execute(&mut *tx)
+///         .await
+///         .map_err(poem::error::InternalServerError)?;
+///     sqlx::query("update accounts set balance = balance + 10 where id = 2")
+///         .execute(&mut *tx)
+///         .await
+///         .map_err(poem::error::InternalServerError)?;
+///     Ok(())
+/// }
+///
+/// let app = transfer.with(Transactional::new(pool));
+/// ```
+pub struct Tx<T>(OwnedMutexGuard<Option<T>>);
+
+impl<T> Deref for Tx<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref().expect("transaction")
+    }
+}
+
+impl<T> DerefMut for Tx<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut().expect("transaction")
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, T: Send + 'static> FromRequest<'a> for Tx<T> {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        let slot = req
+            .extensions()
+            .get::<TransactionSlot<T>>()
+            .ok_or(TransactionError::Missing)?;
+        match slot.0.clone().try_lock_owned() {
+            Ok(tx) if tx.is_some() => Ok(Tx(tx)),
+            _ => Err(TransactionError::InUse.into()),
+        }
+    }
+}
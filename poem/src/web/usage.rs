@@ -0,0 +1,49 @@
+use crate::{
+    error::GetDataError,
+    middleware::{Usage, UsageCap},
+    FromRequest, Request, RequestBody, Result,
+};
+
+/// An extractor for the usage of the API by the client of the request in the
+/// current month, recorded by the
+/// [`UsageMeter`](crate::middleware::UsageMeter) middleware, which must be
+/// applied to the endpoint.
+///
+/// The usage does not include the current request.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if the `UsageMeter` middleware is not applied, or if
+///   the request is not authenticated. Use `Option<CurrentUsage>` for the
+///   routes allowing the anonymous clients.
+#[cfg_attr(docsrs, doc(cfg(feature = "usage")))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CurrentUsage {
+    /// The current month, formatted as `YYYY-MM`.
+    pub period: String,
+    /// The usage in the current month.
+    pub usage: Usage,
+    /// The monthly cap of the client.
+    pub cap: Option<UsageCap>,
+}
+
+impl CurrentUsage {
+    /// Returns the number of requests remaining in the current month, or
+    /// `None` if the number of requests is not limited.
+    pub fn remaining_requests(&self) -> Option<u64> {
+        self.cap
+            .and_then(|cap| cap.requests)
+            .map(|requests| requests.saturating_sub(self.usage.requests))
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for CurrentUsage {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(req
+            .extensions()
+            .get::<CurrentUsage>()
+            .cloned()
+            .ok_or_else(|| GetDataError(std::any::type_name::<CurrentUsage>()))?)
+    }
+}
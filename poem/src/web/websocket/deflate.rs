@@ -0,0 +1,622 @@
+use std::{
+    io::{Cursor, Error as IoError, ErrorKind, Result as IoResult},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::protocol::frame::{
+    coding::{Data, OpCode},
+    FrameHeader,
+};
+
+use super::utils::tungstenite_error_to_io_error;
+
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+const MAX_PENDING_WRITE: usize = 64 << 10;
+
+/// The configuration of the
+/// [permessage-deflate](https://www.rfc-editor.org/rfc/rfc7692) extension,
+/// which compresses the WebSocket messages.
+///
+/// The extension is only used when the client offers it, and the server
+/// always uses a LZ77 sliding window of 32kb, so the offers that limit the
+/// window of the server with `server_max_window_bits` are declined.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::{SinkExt, StreamExt};
+/// use poem::{
+///     get, handler,
+///     web::websocket::{DeflateConfig, WebSocket},
+///     IntoResponse, Route,
+/// };
+///
+/// #[handler]
+/// async fn index(ws: WebSocket) -> impl IntoResponse {
+///     ws.deflate(DeflateConfig::new().server_no_context_takeover(true))
+///         .on_upgrade(|socket| async move {
+///             // ...
+///         })
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct DeflateConfig {
+    level: u32,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    client_max_window_bits: Option<u8>,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self {
+            level: 6,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            client_max_window_bits: None,
+        }
+    }
+}
+
+impl DeflateConfig {
+    /// Create a `DeflateConfig` with the default settings.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the compression level, from `0` to `9`. Defaults to `6`.
+    #[must_use]
+    pub fn level(self, level: u32) -> Self {
+        Self {
+            level: level.min(9),
+            ..self
+        }
+    }
+
+    /// Reset the compression context of the server after each message, which
+    /// uses less memory per connection but compresses less. Defaults to
+    /// `false`.
+    #[must_use]
+    pub fn server_no_context_takeover(self, enable: bool) -> Self {
+        Self {
+            server_no_context_takeover: enable,
+            ..self
+        }
+    }
+
+    /// Ask the client to reset its compression context after each message.
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn client_no_context_takeover(self, enable: bool) -> Self {
+        Self {
+            client_no_context_takeover: enable,
+            ..self
+        }
+    }
+
+    /// Ask the client to use a LZ77 sliding window of `2^bits` bytes, if it
+    /// supports it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is not between `8` and `15`.
+    #[must_use]
+    pub fn client_max_window_bits(self, bits: u8) -> Self {
+        assert!(
+            (8..=15).contains(&bits),
+            "window bits must be between 8 and 15"
+        );
+        Self {
+            client_max_window_bits: Some(bits),
+            ..self
+        }
+    }
+
+    /// Selects the first acceptable offer of the `Sec-WebSocket-Extensions`
+    /// header, and returns the value of the response header and the
+    /// negotiated parameters.
+    pub(crate) fn negotiate(&self, offers: &str) -> Option<(String, Negotiated)> {
+        offers.split(',').find_map(|offer| self.accept(offer))
+    }
+
+    fn accept(&self, offer: &str) -> Option<(String, Negotiated)> {
+        let mut params = offer.split(';').map(str::trim);
+        if params.next() != Some("permessage-deflate") {
+            return None;
+        }
+
+        let mut server_no_context_takeover = self.server_no_context_takeover;
+        let mut client_max_window_bits = None;
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+            match (name, value) {
+                ("server_no_context_takeover", None) => server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => {}
+                ("server_max_window_bits", Some("15")) => {}
+                ("client_max_window_bits", None) => {
+                    client_max_window_bits = self.client_max_window_bits;
+                }
+                ("client_max_window_bits", Some(bits)) => {
+                    let bits = bits
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|bits| (8..=15).contains(bits))?;
+                    client_max_window_bits = Some(
+                        self.client_max_window_bits
+                            .map_or(bits, |max_bits| max_bits.min(bits)),
+                    );
+                }
+                _ => return None,
+            }
+        }
+
+        let mut response = "permessage-deflate".to_string();
+        if server_no_context_takeover {
+            response.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            response.push_str("; client_no_context_takeover");
+        }
+        if let Some(bits) = client_max_window_bits {
+            response.push_str(&format!("; client_max_window_bits={bits}"));
+        }
+
+        Some((
+            response,
+            Negotiated {
+                level: self.level,
+                server_no_context_takeover,
+            },
+        ))
+    }
+}
+
+/// The parameters of the extension after the negotiation.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Negotiated {
+    level: u32,
+    server_no_context_takeover: bool,
+}
+
+struct Deflate {
+    server_no_context_takeover: bool,
+    compress: Compress,
+    decompress: Decompress,
+    read_in: Vec<u8>,
+    read_out: Vec<u8>,
+    read_pos: usize,
+    reading_compressed: bool,
+    read_message_size: usize,
+    write_in: Vec<u8>,
+    write_out: Vec<u8>,
+    write_pos: usize,
+    writing_compressed: bool,
+}
+
+/// A stream between the connection and the WebSocket protocol, which
+/// decompresses the frames from the client and compresses the data frames
+/// sent to it when the extension has been negotiated.
+pub(crate) struct DeflateIo<S> {
+    inner: S,
+    deflate: Option<Box<Deflate>>,
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (idx, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[idx % 4];
+    }
+}
+
+fn invalid_data(msg: &'static str) -> IoError {
+    IoError::new(ErrorKind::InvalidData, msg)
+}
+
+/// Reads a complete frame from the beginning of `buf`.
+fn take_frame(buf: &mut Vec<u8>) -> IoResult<Option<(FrameHeader, Vec<u8>)>> {
+    let mut cursor = Cursor::new(&buf[..]);
+    let (header, len) =
+        match FrameHeader::parse(&mut cursor).map_err(tungstenite_error_to_io_error)? {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+    let header_len = cursor.position() as usize;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| *len <= MAX_MESSAGE_SIZE)
+        .ok_or_else(|| invalid_data("frame too large"))?;
+    if buf.len() < header_len + len {
+        return Ok(None);
+    }
+
+    let payload = buf[header_len..header_len + len].to_vec();
+    buf.drain(..header_len + len);
+    Ok(Some((header, payload)))
+}
+
+impl Deflate {
+    fn new(params: Negotiated) -> Self {
+        Self {
+            server_no_context_takeover: params.server_no_context_takeover,
+            compress: Compress::new(Compression::new(params.level), false),
+            decompress: Decompress::new(false),
+            read_in: Vec::new(),
+            read_out: Vec::new(),
+            read_pos: 0,
+            reading_compressed: false,
+            read_message_size: 0,
+            write_in: Vec::new(),
+            write_out: Vec::new(),
+            write_pos: 0,
+            writing_compressed: false,
+        }
+    }
+
+    fn inflate(&mut self, mut input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        loop {
+            out.reserve(input.len().max(1024));
+            let total_in = self.decompress.total_in();
+            let status = self
+                .decompress
+                .decompress_vec(input, out, FlushDecompress::Sync)
+                .map_err(|_| invalid_data("invalid compressed data"))?;
+            input = &input[(self.decompress.total_in() - total_in) as usize..];
+
+            if self.read_message_size + out.len() > MAX_MESSAGE_SIZE {
+                return Err(invalid_data("message too large"));
+            }
+            if (input.is_empty() && out.len() < out.capacity()) || status == Status::StreamEnd {
+                return Ok(());
+            }
+        }
+    }
+
+    fn deflate(&mut self, mut input: &[u8], out: &mut Vec<u8>) -> IoResult<()> {
+        loop {
+            out.reserve(input.len() / 2 + 64);
+            let total_in = self.compress.total_in();
+            self.compress
+                .compress_vec(input, out, FlushCompress::Sync)
+                .map_err(|err| IoError::new(ErrorKind::Other, err))?;
+            input = &input[(self.compress.total_in() - total_in) as usize..];
+
+            if input.is_empty() && out.len() < out.capacity() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Decompresses the next frame received from the client, returns `false`
+    /// if it has not been received completely.
+    fn decode_frame(&mut self) -> IoResult<bool> {
+        let (mut header, mut payload) = match take_frame(&mut self.read_in)? {
+            Some(frame) => frame,
+            None => return Ok(false),
+        };
+
+        let compressed = match header.opcode {
+            OpCode::Data(Data::Continue) => self.reading_compressed,
+            OpCode::Data(_) => {
+                self.reading_compressed = header.rsv1;
+                self.read_message_size = 0;
+                header.rsv1
+            }
+            OpCode::Control(_) => false,
+        };
+
+        if compressed {
+            if let Some(mask) = header.mask {
+                apply_mask(&mut payload, mask);
+            }
+            let mut data = Vec::new();
+            self.inflate(&payload, &mut data)?;
+            if header.is_final {
+                self.inflate(&DEFLATE_TRAILER, &mut data)?;
+            }
+            self.read_message_size += data.len();
+            if let Some(mask) = header.mask {
+                apply_mask(&mut data, mask);
+            }
+            header.rsv1 = false;
+            payload = data;
+        }
+
+        if header.is_final && matches!(header.opcode, OpCode::Data(_)) {
+            self.reading_compressed = false;
+        }
+
+        header
+            .format(payload.len() as u64, &mut self.read_out)
+            .map_err(tungstenite_error_to_io_error)?;
+        self.read_out.extend_from_slice(&payload);
+        Ok(true)
+    }
+
+    /// Compresses the data frames written by the WebSocket protocol.
+    fn encode_frames(&mut self) -> IoResult<()> {
+        while let Some((mut header, payload)) = take_frame(&mut self.write_in)? {
+            let compress = match header.opcode {
+                OpCode::Data(Data::Continue) => self.writing_compressed,
+                OpCode::Data(_) => {
+                    self.writing_compressed = true;
+                    header.rsv1 = true;
+                    true
+                }
+                OpCode::Control(_) => false,
+            };
+
+            let payload = if compress {
+                let mut data = Vec::new();
+                self.deflate(&payload, &mut data)?;
+                if header.is_final {
+                    if data.ends_with(&DEFLATE_TRAILER) {
+                        data.truncate(data.len() - DEFLATE_TRAILER.len());
+                    }
+                    self.writing_compressed = false;
+                    if self.server_no_context_takeover {
+                        self.compress.reset();
+                    }
+                }
+                data
+            } else {
+                payload
+            };
+
+            header
+                .format(payload.len() as u64, &mut self.write_out)
+                .map_err(tungstenite_error_to_io_error)?;
+            self.write_out.extend_from_slice(&payload);
+        }
+        Ok(())
+    }
+}
+
+impl<S> DeflateIo<S> {
+    pub(crate) fn new(inner: S, params: Option<Negotiated>) -> Self {
+        Self {
+            inner,
+            deflate: params.map(|params| Box::new(Deflate::new(params))),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> DeflateIo<S> {
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        if let Some(deflate) = &mut self.deflate {
+            while deflate.write_pos < deflate.write_out.len() {
+                let n = futures_util::ready!(Pin::new(&mut self.inner)
+                    .poll_write(cx, &deflate.write_out[deflate.write_pos..]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+                }
+                deflate.write_pos += n;
+            }
+            deflate.write_out.clear();
+            deflate.write_pos = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateIo<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        let deflate = match &mut this.deflate {
+            Some(deflate) => deflate,
+            None => return Pin::new(&mut this.inner).poll_read(cx, buf),
+        };
+
+        loop {
+            if deflate.read_pos < deflate.read_out.len() {
+                let data = &deflate.read_out[deflate.read_pos..];
+                let n = data.len().min(buf.remaining());
+                buf.put_slice(&data[..n]);
+                deflate.read_pos += n;
+                if deflate.read_pos == deflate.read_out.len() {
+                    deflate.read_out.clear();
+                    deflate.read_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            if deflate.decode_frame()? {
+                continue;
+            }
+
+            let mut data = [0; 8192];
+            let mut read_buf = ReadBuf::new(&mut data);
+            futures_util::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+            if read_buf.filled().is_empty() {
+                // pass the incomplete frame to the protocol, which reports
+                // the error
+                deflate.read_out = std::mem::take(&mut deflate.read_in);
+                if deflate.read_out.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+            deflate.read_in.extend_from_slice(read_buf.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateIo<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        let this = &mut *self;
+        if this.deflate.is_none() {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+
+        if this.deflate.as_ref().map_or(false, |deflate| {
+            deflate.write_out.len() >= MAX_PENDING_WRITE
+        }) {
+            futures_util::ready!(this.poll_drain(cx))?;
+        }
+
+        if let Some(deflate) = &mut this.deflate {
+            deflate.write_in.extend_from_slice(buf);
+            deflate.encode_frames()?;
+        }
+        // the frames are sent on flush if the connection is not ready
+        let _ = this.poll_drain(cx)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        futures_util::ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        futures_util::ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_tungstenite::tungstenite::protocol::{frame::Frame, Role};
+
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let config = DeflateConfig::new();
+        assert_eq!(
+            config.negotiate("permessage-deflate").unwrap().0,
+            "permessage-deflate"
+        );
+        assert_eq!(
+            config
+                .negotiate("permessage-deflate; client_max_window_bits")
+                .unwrap()
+                .0,
+            "permessage-deflate"
+        );
+        assert_eq!(
+            config
+                .negotiate("permessage-deflate; server_no_context_takeover")
+                .unwrap()
+                .0,
+            "permessage-deflate; server_no_context_takeover"
+        );
+        assert!(config.negotiate("x-webkit-deflate-frame").is_none());
+
+        let config = DeflateConfig::new()
+            .client_no_context_takeover(true)
+            .client_max_window_bits(10);
+        assert_eq!(
+            config
+                .negotiate(
+                    "permessage-deflate; server_max_window_bits=10, permessage-deflate; \
+                     client_max_window_bits=12"
+                )
+                .unwrap()
+                .0,
+            "permessage-deflate; client_no_context_takeover; client_max_window_bits=10"
+        );
+        assert_eq!(
+            config
+                .negotiate("permessage-deflate; client_max_window_bits")
+                .unwrap()
+                .0,
+            "permessage-deflate; client_no_context_takeover; client_max_window_bits=10"
+        );
+        assert!(config.negotiate("permessage-deflate; unknown").is_none());
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut out = Vec::with_capacity(data.len() + 64);
+        compress
+            .compress_vec(data, &mut out, FlushCompress::Sync)
+            .unwrap();
+        assert!(out.ends_with(&DEFLATE_TRAILER));
+        out.truncate(out.len() - 4);
+        out
+    }
+
+    fn decompress(decompress: &mut Decompress, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1024);
+        decompress
+            .decompress_vec(
+                &[data, &DEFLATE_TRAILER].concat(),
+                &mut out,
+                FlushDecompress::Sync,
+            )
+            .unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn deflate_io() {
+        let (client, server) = tokio::io::duplex(4096);
+        let params = DeflateConfig::new()
+            .negotiate("permessage-deflate")
+            .unwrap()
+            .1;
+        let mut server = tokio_tungstenite::WebSocketStream::from_raw_socket(
+            DeflateIo::new(server, Some(params)),
+            Role::Server,
+            None,
+        )
+        .await;
+        tokio::spawn(async move {
+            while let Some(Ok(msg)) = server.next().await {
+                if msg.is_text() {
+                    let text = msg.into_text().unwrap().repeat(2);
+                    server.send(text.into()).await.unwrap();
+                }
+            }
+        });
+
+        let (mut reader, mut writer) = tokio::io::split(client);
+        let mask = [1, 2, 3, 4];
+        let mut decompressor = Decompress::new(false);
+        for text in ["hello", "world"] {
+            // the payload is masked by `Frame::format`
+            let payload = compress(text.as_bytes());
+            let mut header = FrameHeader {
+                rsv1: true,
+                mask: Some(mask),
+                opcode: OpCode::Data(Data::Text),
+                ..FrameHeader::default()
+            };
+            header.is_final = true;
+            let mut frame = Vec::new();
+            Frame::from_payload(header, payload)
+                .format(&mut frame)
+                .unwrap();
+            writer.write_all(&frame).await.unwrap();
+
+            let mut head = [0; 2];
+            reader.read_exact(&mut head).await.unwrap();
+            // FIN, RSV1 and the text opcode
+            assert_eq!(head[0], 0xc1);
+            let mut payload = vec![0; head[1] as usize];
+            reader.read_exact(&mut payload).await.unwrap();
+            assert_eq!(
+                String::from_utf8(decompress(&mut decompressor, &payload)).unwrap(),
+                text.repeat(2)
+            );
+        }
+    }
+}
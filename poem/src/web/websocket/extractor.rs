@@ -1,10 +1,14 @@
-use std::{borrow::Cow, future::Future};
+use std::{borrow::Cow, future::Future, time::Duration};
 
 use futures_util::{future::BoxFuture, FutureExt};
 use headers::HeaderMapExt;
 use tokio_tungstenite::tungstenite::protocol::Role;
 
-use super::{utils::sign, WebSocketStream};
+use super::{
+    deflate::{DeflateConfig, DeflateIo},
+    utils::sign,
+    WebSocketStream,
+};
 use crate::{
     error::WebSocketError,
     http::{
@@ -24,6 +28,9 @@ pub struct WebSocket {
     on_upgrade: OnUpgrade,
     protocols: Option<Box<[Cow<'static, str>]>>,
     sec_websocket_protocol: Option<HeaderValue>,
+    sec_websocket_extensions: Option<HeaderValue>,
+    heartbeat: Option<(Duration, Duration)>,
+    deflate: Option<DeflateConfig>,
 }
 
 impl WebSocket {
@@ -52,12 +59,16 @@ impl WebSocket {
             .ok_or(WebSocketError::InvalidProtocol)?;
 
         let sec_websocket_protocol = req.headers().get(header::SEC_WEBSOCKET_PROTOCOL).cloned();
+        let sec_websocket_extensions = req.headers().get(header::SEC_WEBSOCKET_EXTENSIONS).cloned();
 
         Ok(Self {
             key,
             on_upgrade: req.take_upgrade()?,
             protocols: None,
             sec_websocket_protocol,
+            sec_websocket_extensions,
+            heartbeat: None,
+            deflate: None,
         })
     }
 }
@@ -106,6 +117,53 @@ impl WebSocket {
         self
     }
 
+    /// Send a ping to the peer every `interval` and close the connection if no
+    /// frame is received from it within `timeout`.
+    ///
+    /// The heartbeat runs while the stream is being read. When the deadline
+    /// elapses, the stream ends and
+    /// [`WebSocketStream::disconnect_reason`] returns
+    /// [`DisconnectReason::Timeout`](super::DisconnectReason::Timeout).
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use futures_util::StreamExt;
+    /// use poem::{get, handler, web::websocket::WebSocket, IntoResponse, Route};
+    ///
+    /// #[handler]
+    /// async fn index(ws: WebSocket) -> impl IntoResponse {
+    ///     ws.heartbeat(Duration::from_secs(15), Duration::from_secs(60))
+    ///         .on_upgrade(|mut socket| async move {
+    ///             while let Some(Ok(msg)) = socket.next().await {
+    ///                 // ...
+    ///             }
+    ///             tracing::info!(reason = ?socket.disconnect_reason(), "disconnected");
+    ///         })
+    /// }
+    ///
+    /// let app = Route::new().at("/", get(index));
+    /// ```
+    #[must_use]
+    pub fn heartbeat(self, interval: Duration, timeout: Duration) -> Self {
+        Self {
+            heartbeat: Some((interval, timeout)),
+            ..self
+        }
+    }
+
+    /// Compress the messages with the `permessage-deflate` extension if the
+    /// client supports it.
+    ///
+    /// See [`DeflateConfig`] for an example.
+    #[must_use]
+    pub fn deflate(self, config: DeflateConfig) -> Self {
+        Self {
+            deflate: Some(config),
+            ..self
+        }
+    }
+
     /// Finalize upgrading the connection and call the provided `callback` with
     /// the stream.
     ///
@@ -187,6 +245,23 @@ where
             );
         }
 
+        let deflate = self.websocket.deflate.and_then(|config| {
+            let offers = self
+                .websocket
+                .sec_websocket_extensions
+                .as_ref()?
+                .to_str()
+                .ok()?;
+            config.negotiate(offers)
+        });
+        let params = match deflate {
+            Some((extensions, params)) => {
+                builder = builder.header(header::SEC_WEBSOCKET_EXTENSIONS, extensions);
+                Some(params)
+            }
+            None => None,
+        };
+
         let resp = builder.body(Body::empty());
 
         tokio::spawn(async move {
@@ -195,10 +270,13 @@ where
                 Err(_) => return,
             };
 
-            let stream =
-                tokio_tungstenite::WebSocketStream::from_raw_socket(upgraded, Role::Server, None)
-                    .await;
-            (self.callback)(WebSocketStream::new(stream)).await;
+            let stream = tokio_tungstenite::WebSocketStream::from_raw_socket(
+                DeflateIo::new(upgraded, params),
+                Role::Server,
+                None,
+            )
+            .await;
+            (self.callback)(WebSocketStream::new(stream, self.websocket.heartbeat)).await;
         });
 
         resp
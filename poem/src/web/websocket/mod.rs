@@ -22,14 +22,20 @@
 //! let app = Route::new().at("/", get(index));
 //! ```
 
+mod deflate;
 mod extractor;
 mod message;
+mod rooms;
 mod stream;
+mod typed;
 mod utils;
 
+pub use deflate::DeflateConfig;
 pub use extractor::{BoxWebSocketUpgraded, WebSocket, WebSocketUpgraded};
 pub use message::{CloseCode, Message};
-pub use stream::WebSocketStream;
+pub use rooms::{MemberId, RoomMember, Rooms};
+pub use stream::{DisconnectReason, WebSocketStream};
+pub use typed::TypedWebSocket;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,420 @@
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::Stream;
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+
+use super::Message;
+
+/// The identifier of a [`RoomMember`], which is unique within a [`Rooms`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct MemberId(u64);
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    members: HashMap<MemberId, mpsc::Sender<Message>>,
+    rooms: HashMap<String, HashSet<MemberId>>,
+}
+
+/// A registry of WebSocket connections grouped in named rooms, for features
+/// such as chats and live updates.
+///
+/// `Rooms` can be cloned and shared between the handlers with
+/// [`Data`](crate::web::Data). Each connection registers itself with
+/// [`Rooms::member`] and receives the messages sent to it or to the rooms it
+/// joined from the returned [`RoomMember`], which leaves all its rooms when
+/// it is dropped. Empty rooms are removed automatically.
+///
+/// Every member has a queue of [`Rooms::with_capacity`] messages, and
+/// [`Rooms::broadcast`] waits for the queues of the members to have room, so
+/// that a fast sender cannot make the memory grow without bound. The messages
+/// are sent to the members concurrently, and a member whose queue is still
+/// full after the [`send_timeout`](Rooms::send_timeout) is disconnected: it
+/// is removed from its rooms, and its stream ends after the queued messages,
+/// so that a lagging member cannot stall the whole room. The members that
+/// have disconnected are skipped.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::{SinkExt, StreamExt};
+/// use poem::{
+///     get, handler,
+///     web::{
+///         websocket::{Message, Rooms, WebSocket},
+///         Data, Path,
+///     },
+///     EndpointExt, IntoResponse, Route,
+/// };
+///
+/// #[handler]
+/// fn chat(Path(room): Path<String>, ws: WebSocket, rooms: Data<&Rooms>) -> impl IntoResponse {
+///     let rooms = rooms.0.clone();
+///     ws.on_upgrade(move |socket| async move {
+///         let (mut sink, mut stream) = socket.split();
+///         let mut member = rooms.member();
+///         member.join(&room);
+///         let id = member.id();
+///
+///         tokio::spawn(async move {
+///             while let Some(msg) = member.next().await {
+///                 if sink.send(msg).await.is_err() {
+///                     break;
+///                 }
+///             }
+///         });
+///
+///         while let Some(Ok(Message::Text(text))) = stream.next().await {
+///             rooms.broadcast_except(&room, id, Message::Text(text)).await;
+///         }
+///     })
+/// }
+///
+/// let app = Route::new().at("/chat/:room", get(chat)).data(Rooms::new());
+/// ```
+#[derive(Clone)]
+pub struct Rooms {
+    inner: Arc<Mutex<Inner>>,
+    capacity: usize,
+    send_timeout: Duration,
+}
+
+impl Default for Rooms {
+    fn default() -> Self {
+        Self::with_capacity(32)
+    }
+}
+
+impl Rooms {
+    /// Create an empty `Rooms`, with a queue of 32 messages for each member.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create an empty `Rooms`, with a queue of `capacity` messages for each
+    /// member.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        Self {
+            inner: Default::default(),
+            capacity,
+            send_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Sets how long [`Rooms::broadcast`] waits for the queue of a member to
+    /// have room before disconnecting it.
+    ///
+    /// Default is 5 seconds.
+    #[must_use]
+    pub fn send_timeout(self, send_timeout: Duration) -> Self {
+        Self {
+            send_timeout,
+            ..self
+        }
+    }
+
+    /// Register a new member, which is not in any room yet.
+    pub fn member(&self) -> RoomMember {
+        let (tx, rx) = mpsc::channel(self.capacity);
+        let mut inner = self.inner.lock();
+        let id = MemberId(inner.next_id);
+        inner.next_id += 1;
+        inner.members.insert(id, tx);
+
+        RoomMember {
+            id,
+            rooms: self.clone(),
+            joined: HashSet::new(),
+            rx,
+        }
+    }
+
+    fn senders(
+        &self,
+        room: &str,
+        except: Option<MemberId>,
+    ) -> Vec<(MemberId, mpsc::Sender<Message>)> {
+        let inner = self.inner.lock();
+        inner
+            .rooms
+            .get(room)
+            .into_iter()
+            .flatten()
+            .filter(|id| Some(**id) != except)
+            .filter_map(|id| Some((*id, inner.members.get(id)?.clone())))
+            .collect()
+    }
+
+    async fn send_all(
+        &self,
+        senders: Vec<(MemberId, mpsc::Sender<Message>)>,
+        msg: Message,
+    ) -> usize {
+        let results = futures_util::future::join_all(senders.into_iter().map(|(id, tx)| {
+            let msg = msg.clone();
+            async move {
+                (
+                    id,
+                    tokio::time::timeout(self.send_timeout, tx.send(msg)).await,
+                )
+            }
+        }))
+        .await;
+
+        let mut count = 0;
+        for (id, res) in results {
+            match res {
+                Ok(Ok(())) => count += 1,
+                Ok(Err(_)) => {}
+                Err(_) => {
+                    tracing::debug!(member = id.0, "disconnect a lagging room member");
+                    self.disconnect(id);
+                }
+            }
+        }
+        count
+    }
+
+    /// Removes a member from its rooms and drops its sender, so that its
+    /// stream ends.
+    fn disconnect(&self, id: MemberId) {
+        let mut inner = self.inner.lock();
+        inner.members.remove(&id);
+        inner.rooms.retain(|_, members| {
+            members.remove(&id);
+            !members.is_empty()
+        });
+    }
+
+    /// Send a message to all the members of a room, and returns the number of
+    /// members that received it.
+    pub async fn broadcast(&self, room: &str, msg: Message) -> usize {
+        self.send_all(self.senders(room, None), msg).await
+    }
+
+    /// Send a message to all the members of a room except one, usually the
+    /// sender of the message.
+    pub async fn broadcast_except(&self, room: &str, except: MemberId, msg: Message) -> usize {
+        self.send_all(self.senders(room, Some(except)), msg).await
+    }
+
+    /// Send a message to all the members of a room without waiting, and
+    /// returns the number of members that received it. The message is
+    /// dropped for the members whose queue is full.
+    pub fn try_broadcast(&self, room: &str, msg: Message) -> usize {
+        self.senders(room, None)
+            .into_iter()
+            .filter(|(_, tx)| tx.try_send(msg.clone()).is_ok())
+            .count()
+    }
+
+    /// Send a message to the specified member, and returns `false` if the
+    /// member has disconnected.
+    pub async fn send(&self, member: MemberId, msg: Message) -> bool {
+        let tx = self.inner.lock().members.get(&member).cloned();
+        match tx {
+            Some(tx) => tx.send(msg).await.is_ok(),
+            None => false,
+        }
+    }
+
+    /// Returns the number of members in a room.
+    pub fn count(&self, room: &str) -> usize {
+        self.inner.lock().rooms.get(room).map_or(0, HashSet::len)
+    }
+
+    /// Returns the members of a room.
+    pub fn members(&self, room: &str) -> Vec<MemberId> {
+        self.inner
+            .lock()
+            .rooms
+            .get(room)
+            .map(|members| members.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the names of the rooms that have members.
+    pub fn rooms(&self) -> Vec<String> {
+        self.inner.lock().rooms.keys().cloned().collect()
+    }
+}
+
+/// A member of [`Rooms`], which is a [`Stream`] of the messages sent to it.
+///
+/// It is created with [`Rooms::member`], and leaves all its rooms when it is
+/// dropped.
+pub struct RoomMember {
+    id: MemberId,
+    rooms: Rooms,
+    joined: HashSet<String>,
+    rx: mpsc::Receiver<Message>,
+}
+
+impl RoomMember {
+    /// Returns the identifier of this member.
+    #[inline]
+    pub fn id(&self) -> MemberId {
+        self.id
+    }
+
+    /// Join a room. A member can be in several rooms at the same time.
+    pub fn join(&mut self, room: impl Into<String>) {
+        let room = room.into();
+        self.rooms
+            .inner
+            .lock()
+            .rooms
+            .entry(room.clone())
+            .or_default()
+            .insert(self.id);
+        self.joined.insert(room);
+    }
+
+    /// Leave a room.
+    pub fn leave(&mut self, room: &str) {
+        if self.joined.remove(room) {
+            let mut inner = self.rooms.inner.lock();
+            remove_from_room(&mut inner, room, self.id);
+        }
+    }
+
+    /// Returns the rooms this member has joined.
+    pub fn joined(&self) -> impl Iterator<Item = &str> {
+        self.joined.iter().map(String::as_str)
+    }
+
+    /// Receives the next message sent to this member.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.rx.recv().await
+    }
+}
+
+fn remove_from_room(inner: &mut Inner, room: &str, id: MemberId) {
+    if let Some(members) = inner.rooms.get_mut(room) {
+        members.remove(&id);
+        if members.is_empty() {
+            inner.rooms.remove(room);
+        }
+    }
+}
+
+impl Stream for RoomMember {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Drop for RoomMember {
+    fn drop(&mut self) {
+        let mut inner = self.rooms.inner.lock();
+        inner.members.remove(&self.id);
+        for room in &self.joined {
+            remove_from_room(&mut inner, room, self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rooms() {
+        let rooms = Rooms::new();
+        let mut a = rooms.member();
+        let mut b = rooms.member();
+        a.join("chat");
+        b.join("chat");
+        b.join("news");
+        assert_eq!(rooms.count("chat"), 2);
+        assert_eq!(rooms.count("news"), 1);
+        assert_eq!(rooms.count("other"), 0);
+
+        assert_eq!(rooms.broadcast("chat", Message::text("hello")).await, 2);
+        assert_eq!(a.next().await, Some(Message::text("hello")));
+        assert_eq!(b.next().await, Some(Message::text("hello")));
+
+        assert_eq!(
+            rooms
+                .broadcast_except("chat", a.id(), Message::text("from a"))
+                .await,
+            1
+        );
+        assert_eq!(b.recv().await, Some(Message::text("from a")));
+
+        assert!(rooms.send(a.id(), Message::text("private")).await);
+        assert_eq!(a.recv().await, Some(Message::text("private")));
+
+        b.leave("chat");
+        assert_eq!(rooms.members("chat"), vec![a.id()]);
+
+        let b_id = b.id();
+        drop(b);
+        assert_eq!(rooms.count("news"), 0);
+        assert_eq!(rooms.rooms(), vec!["chat".to_string()]);
+        assert!(!rooms.send(b_id, Message::text("gone")).await);
+
+        drop(a);
+        assert!(rooms.rooms().is_empty());
+    }
+
+    #[tokio::test]
+    async fn backpressure() {
+        let rooms = Rooms::with_capacity(1);
+        let mut a = rooms.member();
+        a.join("chat");
+
+        assert_eq!(rooms.try_broadcast("chat", Message::text("1")), 1);
+        assert_eq!(rooms.try_broadcast("chat", Message::text("2")), 0);
+
+        let broadcast = rooms.broadcast("chat", Message::text("3"));
+        tokio::pin!(broadcast);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut broadcast)
+                .await
+                .is_err()
+        );
+        assert_eq!(a.recv().await, Some(Message::text("1")));
+        assert_eq!(broadcast.await, 1);
+        assert_eq!(a.recv().await, Some(Message::text("3")));
+    }
+
+    #[tokio::test]
+    async fn lagging() {
+        let rooms = Rooms::with_capacity(1).send_timeout(Duration::from_millis(50));
+        let mut a = rooms.member();
+        let mut b = rooms.member();
+        a.join("chat");
+        b.join("chat");
+
+        assert_eq!(rooms.try_broadcast("chat", Message::text("1")), 2);
+        assert_eq!(b.recv().await, Some(Message::text("1")));
+
+        // `b` receives the message without waiting for `a`, which is
+        // disconnected after the timeout
+        let (sent, received) = tokio::join!(rooms.broadcast("chat", Message::text("2")), b.recv());
+        assert_eq!(received, Some(Message::text("2")));
+        assert_eq!(sent, 1);
+        assert_eq!(rooms.members("chat"), vec![b.id()]);
+        assert_eq!(a.recv().await, Some(Message::text("1")));
+        assert_eq!(a.recv().await, None);
+    }
+}
@@ -1,23 +1,100 @@
 use std::{
+    future::Future,
     io::{Error as IoError, Result as IoResult},
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use tokio::time::{Instant, Interval, Sleep};
 
-use super::{utils::tungstenite_error_to_io_error, Message};
+use super::{deflate::DeflateIo, utils::tungstenite_error_to_io_error, CloseCode, Message};
 use crate::Upgraded;
 
+/// The reason why a [`WebSocketStream`] has ended.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DisconnectReason {
+    /// The peer sent a close frame.
+    Closed(Option<(CloseCode, String)>),
+    /// No frame has been received from the peer within the liveness deadline
+    /// set with [`WebSocket::heartbeat`](super::WebSocket::heartbeat).
+    Timeout,
+    /// The connection was lost without a close frame.
+    ConnectionLost,
+}
+
+struct Heartbeat {
+    interval: Interval,
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+    pending_ping: bool,
+    flushing: bool,
+}
+
 /// A `WebSocket` stream, which implements [`Stream<Message>`] and
 /// [`Sink<Message>`].
 pub struct WebSocketStream {
-    inner: tokio_tungstenite::WebSocketStream<Upgraded>,
+    inner: tokio_tungstenite::WebSocketStream<DeflateIo<Upgraded>>,
+    heartbeat: Option<Heartbeat>,
+    disconnect_reason: Option<DisconnectReason>,
 }
 
 impl WebSocketStream {
-    pub(crate) fn new(inner: tokio_tungstenite::WebSocketStream<Upgraded>) -> Self {
-        Self { inner }
+    pub(crate) fn new(
+        inner: tokio_tungstenite::WebSocketStream<DeflateIo<Upgraded>>,
+        heartbeat: Option<(Duration, Duration)>,
+    ) -> Self {
+        Self {
+            inner,
+            heartbeat: heartbeat.map(|(interval, timeout)| Heartbeat {
+                interval: tokio::time::interval_at(Instant::now() + interval, interval),
+                timeout,
+                deadline: Box::pin(tokio::time::sleep(timeout)),
+                pending_ping: false,
+                flushing: false,
+            }),
+            disconnect_reason: None,
+        }
+    }
+
+    /// Returns the reason why the stream has ended, or `None` if it has not
+    /// ended yet.
+    pub fn disconnect_reason(&self) -> Option<&DisconnectReason> {
+        self.disconnect_reason.as_ref()
+    }
+
+    /// Sends the pings and checks the liveness deadline, returns `true` if it
+    /// has elapsed.
+    fn poll_heartbeat(&mut self, cx: &mut Context<'_>) -> bool {
+        let heartbeat = match &mut self.heartbeat {
+            Some(heartbeat) => heartbeat,
+            None => return false,
+        };
+
+        if heartbeat.deadline.as_mut().poll(cx).is_ready() {
+            return true;
+        }
+
+        while heartbeat.interval.poll_tick(cx).is_ready() {
+            heartbeat.pending_ping = true;
+        }
+
+        // the pings are sent on a best effort basis, without blocking the reads
+        if heartbeat.pending_ping {
+            if let Poll::Ready(Ok(())) = self.inner.poll_ready_unpin(cx) {
+                heartbeat.pending_ping = false;
+                heartbeat.flushing = self
+                    .inner
+                    .start_send_unpin(tokio_tungstenite::tungstenite::Message::Ping(Vec::new()))
+                    .is_ok();
+            }
+        }
+        if heartbeat.flushing && self.inner.poll_flush_unpin(cx).is_ready() {
+            heartbeat.flushing = false;
+        }
+
+        false
     }
 }
 
@@ -25,14 +102,42 @@ impl Stream for WebSocketStream {
     type Item = IoResult<Message>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.inner.poll_next_unpin(cx) {
-            Poll::Ready(Some(Ok(msg))) => Poll::Ready(Some(Ok(msg.into()))),
+        if self.disconnect_reason == Some(DisconnectReason::Timeout) {
+            return Poll::Ready(None);
+        }
+
+        if self.poll_heartbeat(cx) {
+            self.disconnect_reason = Some(DisconnectReason::Timeout);
+            return Poll::Ready(None);
+        }
+
+        let res = match self.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(msg))) => Poll::Ready(Some(Ok(Message::from(msg)))),
             Poll::Ready(Some(Err(err))) => {
                 Poll::Ready(Some(Err(tungstenite_error_to_io_error(err))))
             }
             Poll::Ready(None) => Poll::Ready(None),
             Poll::Pending => Poll::Pending,
+        };
+
+        match &res {
+            Poll::Ready(Some(Ok(msg))) => {
+                if let Some(heartbeat) = &mut self.heartbeat {
+                    let deadline = Instant::now() + heartbeat.timeout;
+                    heartbeat.deadline.as_mut().reset(deadline);
+                }
+                if let Message::Close(frame) = msg {
+                    self.disconnect_reason = Some(DisconnectReason::Closed(frame.clone()));
+                }
+            }
+            Poll::Ready(Some(Err(_)) | None) => {
+                self.disconnect_reason
+                    .get_or_insert(DisconnectReason::ConnectionLost);
+            }
+            Poll::Pending => {}
         }
+
+        res
     }
 }
 
@@ -63,3 +168,67 @@ impl Sink<Message> for WebSocketStream {
             .map_err(tungstenite_error_to_io_error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        web::{websocket::WebSocket, Data},
+        EndpointExt, IntoResponse, Server,
+    };
+
+    #[tokio::test]
+    async fn heartbeat() {
+        #[handler(internal)]
+        async fn index(
+            ws: WebSocket,
+            tx: Data<&mpsc::UnboundedSender<Option<DisconnectReason>>>,
+        ) -> impl IntoResponse {
+            let tx = tx.0.clone();
+            ws.heartbeat(Duration::from_millis(50), Duration::from_millis(200))
+                .on_upgrade(move |mut socket| async move {
+                    while let Some(Ok(_)) = socket.next().await {}
+                    let _ = tx.send(socket.disconnect_reason().cloned());
+                })
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Option<DisconnectReason>>();
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(index.data(tx)));
+
+        // the client replies to the pings while it is reading
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}"))
+            .await
+            .unwrap();
+        let reader = tokio::spawn(async move {
+            let mut pings = 0;
+            while let Some(Ok(msg)) = client.next().await {
+                if msg.is_ping() {
+                    pings += 1;
+                    if pings == 8 {
+                        client.close(None).await.unwrap();
+                    }
+                }
+            }
+        });
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            Some(DisconnectReason::Closed(None))
+        );
+        reader.await.unwrap();
+
+        // the client does not reply to the pings
+        let (_client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}"))
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap(), Some(DisconnectReason::Timeout));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{Message, WebSocketStream};
+
+/// A `WebSocket` stream that exchanges JSON messages, which implements
+/// [`Stream<In>`] and [`Sink<Out>`].
+///
+/// Both text and binary frames are deserialized into `In`, and the values of
+/// `Out` are sent as text frames. The ping and pong frames are handled
+/// automatically and the stream ends when a close frame is received.
+///
+/// When a frame cannot be deserialized, it is skipped and a text frame such
+/// as `{"error":"invalid message: ..."}` is sent back to the client.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::{SinkExt, StreamExt};
+/// use poem::{
+///     get, handler,
+///     web::websocket::{TypedWebSocket, WebSocket},
+///     IntoResponse, Route,
+/// };
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize)]
+/// struct Request {
+///     a: i32,
+///     b: i32,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Response {
+///     sum: i32,
+/// }
+///
+/// #[handler]
+/// async fn index(ws: WebSocket) -> impl IntoResponse {
+///     ws.on_upgrade(|socket| async move {
+///         let mut socket: TypedWebSocket<Request, Response> = socket.typed();
+///         while let Some(Ok(req)) = socket.next().await {
+///             let _ = socket.send(Response { sum: req.a + req.b }).await;
+///         }
+///     })
+/// }
+///
+/// let app = Route::new().at("/", get(index));
+/// ```
+pub struct TypedWebSocket<In, Out> {
+    inner: WebSocketStream,
+    pending_error: Option<Message>,
+    flushing: bool,
+    _mark: PhantomData<fn(Out) -> In>,
+}
+
+impl<In, Out> TypedWebSocket<In, Out> {
+    /// Create a `TypedWebSocket` from a `WebSocket` stream.
+    pub fn new(inner: WebSocketStream) -> Self {
+        Self {
+            inner,
+            pending_error: None,
+            flushing: false,
+            _mark: PhantomData,
+        }
+    }
+
+    /// Consumes this `TypedWebSocket`, returning the underlying stream.
+    pub fn into_inner(self) -> WebSocketStream {
+        self.inner
+    }
+}
+
+impl WebSocketStream {
+    /// Converts this stream into a [`TypedWebSocket`] that exchanges JSON
+    /// messages.
+    pub fn typed<In, Out>(self) -> TypedWebSocket<In, Out> {
+        TypedWebSocket::new(self)
+    }
+}
+
+fn error_message(err: serde_json::Error) -> Message {
+    Message::text(serde_json::json!({ "error": format!("invalid message: {err}") }).to_string())
+}
+
+impl<In: DeserializeOwned, Out> TypedWebSocket<In, Out> {
+    /// Sends the pending error frame, if any.
+    fn poll_send_error(&mut self, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        if let Some(msg) = self.pending_error.take() {
+            match self.inner.poll_ready_unpin(cx) {
+                Poll::Ready(Ok(())) => {
+                    self.inner.start_send_unpin(msg)?;
+                    self.flushing = true;
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => {
+                    self.pending_error = Some(msg);
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        if self.flushing {
+            futures_util::ready!(self.inner.poll_flush_unpin(cx))?;
+            self.flushing = false;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<In: DeserializeOwned, Out> Stream for TypedWebSocket<In, Out> {
+    type Item = IoResult<In>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Err(err) = futures_util::ready!(self.poll_send_error(cx)) {
+                return Poll::Ready(Some(Err(err)));
+            }
+
+            let res = match futures_util::ready!(self.inner.poll_next_unpin(cx)) {
+                Some(Ok(Message::Text(text))) => serde_json::from_str(&text),
+                Some(Ok(Message::Binary(data))) => serde_json::from_slice(&data),
+                Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                Some(Ok(Message::Close(_))) | None => return Poll::Ready(None),
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+            };
+
+            match res {
+                Ok(value) => return Poll::Ready(Some(Ok(value))),
+                Err(err) => self.pending_error = Some(error_message(err)),
+            }
+        }
+    }
+}
+
+impl<In, Out: Serialize> Sink<Out> for TypedWebSocket<In, Out> {
+    type Error = IoError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Out) -> Result<(), Self::Error> {
+        let text = serde_json::to_string(&item)
+            .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+        self.inner.start_send_unpin(Message::Text(text))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        web::websocket::WebSocket,
+        IntoResponse, Server,
+    };
+
+    #[tokio::test]
+    async fn typed_websocket() {
+        #[derive(Deserialize)]
+        struct Request {
+            a: i32,
+            b: i32,
+        }
+
+        #[derive(Serialize)]
+        struct Response {
+            sum: i32,
+        }
+
+        #[handler(internal)]
+        async fn index(ws: WebSocket) -> impl IntoResponse {
+            ws.on_upgrade(|socket| async move {
+                let mut socket = socket.typed::<Request, Response>();
+                while let Some(Ok(req)) = socket.next().await {
+                    let _ = socket.send(Response { sum: req.a + req.b }).await;
+                }
+            })
+        }
+
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(index));
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}"))
+            .await
+            .unwrap();
+        let cases = [
+            (Message::text(r#"{"a":1,"b":2}"#), r#"{"sum":3}"#),
+            (
+                Message::binary(br#"{"a":3,"b":4}"#.to_vec()),
+                r#"{"sum":7}"#,
+            ),
+            (
+                Message::text("{}"),
+                r#"{"error":"invalid message: missing field `a` at line 1 column 2"}"#,
+            ),
+            (Message::text(r#"{"a":5,"b":6}"#), r#"{"sum":11}"#),
+        ];
+        for (msg, expected) in cases {
+            client.send(msg.into()).await.unwrap();
+            let resp = client.next().await.unwrap().unwrap();
+            assert_eq!(resp.into_text().unwrap(), expected);
+        }
+    }
+}
@@ -11,7 +11,8 @@ use crate::{
 /// XML extractor and response.
 ///
 /// To extract the specified type of XML from the body, `T` must implement
-/// [`serde::Deserialize`].
+/// [`serde::Deserialize`]. The `Content-Type` must be `application/xml`,
+/// `text/xml`, or a type with the `+xml` suffix.
 ///
 /// # Errors
 ///
@@ -123,9 +124,12 @@ impl<'a, T: DeserializeOwned> FromRequest<'a> for Xml<T> {
     }
 }
 
+/// Accepts `application/xml`, `text/xml` (used by SOAP 1.1 and many legacy
+/// services) and the types with the `+xml` suffix, such as
+/// `application/soap+xml`.
 fn is_xml_content_type(content_type: &str) -> bool {
-    matches!(content_type.parse::<mime::Mime>(), 
-        Ok(content_type) if content_type.type_() == "application" 
+    matches!(content_type.parse::<mime::Mime>(),
+        Ok(content_type) if (content_type.type_() == "application" || content_type.type_() == "text")
         && (content_type.subtype() == "xml"
         || content_type
             .suffix()
@@ -219,4 +223,39 @@ mod tests {
         })
         .await;
     }
+
+    #[test]
+    fn xml_content_type() {
+        assert!(is_xml_content_type("application/xml"));
+        assert!(is_xml_content_type("text/xml; charset=utf-8"));
+        assert!(is_xml_content_type("application/soap+xml"));
+        assert!(is_xml_content_type("application/atom+xml"));
+        assert!(!is_xml_content_type("application/json"));
+        assert!(!is_xml_content_type("image/svg"));
+    }
+
+    #[tokio::test]
+    async fn test_xml_extractor_text_xml() {
+        #[handler(internal)]
+        async fn index(query: Xml<CreateResource>) -> String {
+            format!("{}={}", query.name, query.value)
+        }
+
+        let cli = TestClient::new(index);
+        let resp = cli
+            .post("/")
+            .header(header::CONTENT_TYPE, "text/xml; charset=utf-8")
+            .body("<CreateResource><name>abc</name><value>100</value></CreateResource>")
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        resp.assert_text("abc=100").await;
+
+        cli.post("/")
+            .header(header::CONTENT_TYPE, "text/xml")
+            .body("<CreateResource><name>abc</name></CreateResource>")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,537 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures_util::future::BoxFuture;
+use http::{header, Method, StatusCode};
+use hyper::client::connect::Connect;
+use parking_lot::{Mutex, RwLock};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::WebhookVerifier;
+use crate::{
+    error::InternalServerError,
+    tasks::{JobQueue, JobStorage, Tasks},
+    Result,
+};
+
+type HttpSender = Arc<
+    dyn Fn(
+            http::Request<hyper::Body>,
+        ) -> BoxFuture<'static, Result<http::Response<hyper::Body>, hyper::Error>>
+        + Send
+        + Sync,
+>;
+
+type DeadLetterHook = Arc<dyn Fn(FailedDelivery) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A webhook event to deliver to a URL, stored in the [`JobStorage`] of a
+/// [`WebhookDispatcher`] until it is delivered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// The unique identifier of the delivery, sent in the
+    /// `X-Webhook-Delivery` header.
+    pub id: String,
+    /// The URL of the receiver.
+    pub url: String,
+    /// The name of the event, sent in the `X-Webhook-Event` header.
+    pub event: String,
+    /// The JSON payload of the event.
+    pub payload: Value,
+}
+
+/// A delivery which failed on its last attempt, passed to the
+/// [dead letter hook](WebhookDispatcher::on_dead_letter).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedDelivery {
+    /// The delivery, or `None` if the stored job could not be deserialized.
+    pub delivery: Option<WebhookDelivery>,
+    /// The number of attempts.
+    pub attempts: u32,
+    /// The error of the last attempt.
+    pub last_error: Option<String>,
+}
+
+/// An attempt to deliver a webhook, recorded in the logs of a
+/// [`WebhookDispatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryLog {
+    /// The identifier of the delivery.
+    pub id: String,
+    /// The URL of the receiver.
+    pub url: String,
+    /// The name of the event.
+    pub event: String,
+    /// The time of the attempt.
+    pub at: SystemTime,
+    /// The status of the response, if any.
+    pub status: Option<StatusCode>,
+    /// The error of the attempt, if it failed.
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Shared {
+    receivers: RwLock<HashMap<String, WebhookVerifier>>,
+    logs: Mutex<VecDeque<DeliveryLog>>,
+}
+
+/// Delivers the signed JSON payloads of the events to the registered URLs in
+/// the background, shared with the handlers with the
+/// [`Data`](crate::web::Data) extractor.
+///
+/// The deliveries are the jobs of a [`JobQueue`]: a delivery which fails,
+/// because the receiver is unreachable or doesn't answer with a success
+/// status, is retried with an exponential backoff, and is passed to the
+/// [dead letter hook](WebhookDispatcher::on_dead_letter) after the last
+/// attempt. Each request is signed when it is sent with the
+/// [`WebhookVerifier`] of its URL, so that the receivers check it with the
+/// same scheme.
+///
+/// The default client only supports the `http` URLs, a client with a TLS
+/// connector is set with [`WebhookDispatcher::client`].
+///
+/// # Example
+///
+/// ```no_run
+/// use poem::{
+///     handler,
+///     listener::TcpListener,
+///     tasks::MemoryJobStorage,
+///     web::Data,
+///     webhook::{WebhookDispatcher, WebhookVerifier},
+///     EndpointExt, Result, Route, Server,
+/// };
+/// use serde_json::json;
+///
+/// #[handler]
+/// async fn create_order(webhooks: Data<&WebhookDispatcher>) -> Result<()> {
+///     webhooks
+///         .dispatch("order.created", &json!({ "id": 1 }))
+///         .await
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let server = Server::new(TcpListener::bind("127.0.0.1:3000"));
+///
+/// let webhooks =
+///     WebhookDispatcher::new(MemoryJobStorage::new()).on_dead_letter(|failed| async move {
+///         tracing::error!(error = ?failed.last_error, "failed to deliver a webhook");
+///     });
+/// webhooks.register("http://example.com/hooks", WebhookVerifier::github("secret"));
+/// webhooks.spawn(&server.tasks());
+///
+/// let app = Route::new().at("/orders", create_order).data(webhooks);
+/// server.run(app).await
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    queue: JobQueue<WebhookDelivery>,
+    shared: Arc<Shared>,
+    sender: HttpSender,
+    timeout: Duration,
+    concurrency: usize,
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    poll_interval: Duration,
+    max_logs: usize,
+    dead_letter: Option<DeadLetterHook>,
+}
+
+impl WebhookDispatcher {
+    /// Create a dispatcher storing the pending deliveries in `storage`.
+    pub fn new(storage: impl JobStorage + 'static) -> Self {
+        Self {
+            queue: JobQueue::new(storage),
+            shared: Default::default(),
+            sender: sender(hyper::Client::new()),
+            timeout: Duration::from_secs(10),
+            concurrency: 4,
+            max_attempts: 8,
+            backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60 * 60),
+            poll_interval: Duration::from_secs(1),
+            max_logs: 1000,
+            dead_letter: None,
+        }
+    }
+
+    /// Sets the client sending the requests, such as one with a TLS
+    /// connector.
+    #[must_use]
+    pub fn client<C>(self, client: hyper::Client<C>) -> Self
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        Self {
+            sender: sender(client),
+            ..self
+        }
+    }
+
+    /// Sets the timeout of a delivery attempt.
+    ///
+    /// Default is `10s`.
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Sets the number of deliveries sent at the same time.
+    ///
+    /// Default is `4`.
+    #[must_use]
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency,
+            ..self
+        }
+    }
+
+    /// Sets the number of attempts to deliver an event before it is passed
+    /// to the dead letter hook.
+    ///
+    /// Default is `8`.
+    #[must_use]
+    pub fn max_attempts(self, max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Sets the delay before the first retry, which doubles on each retry up
+    /// to `max`.
+    ///
+    /// Default is `10s` doubling up to `1h`.
+    #[must_use]
+    pub fn backoff(self, initial: Duration, max: Duration) -> Self {
+        Self {
+            backoff: initial,
+            max_backoff: max,
+            ..self
+        }
+    }
+
+    /// Sets the interval at which an idle worker checks the storage for the
+    /// retried deliveries.
+    ///
+    /// Default is `1s`.
+    #[must_use]
+    pub fn poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    /// Sets the number of the most recent attempts kept in the
+    /// [logs](WebhookDispatcher::logs).
+    ///
+    /// Default is `1000`.
+    #[must_use]
+    pub fn max_logs(self, max_logs: usize) -> Self {
+        Self { max_logs, ..self }
+    }
+
+    /// Sets a function which is called with the deliveries which failed on
+    /// their last attempt.
+    ///
+    /// By default, these deliveries are logged and dropped.
+    #[must_use]
+    pub fn on_dead_letter<H, HFut>(self, hook: H) -> Self
+    where
+        H: Fn(FailedDelivery) -> HFut + Send + Sync + 'static,
+        HFut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            dead_letter: Some(Arc::new(move |failed| Box::pin(hook(failed)))),
+            ..self
+        }
+    }
+
+    /// Registers a receiver of the events at `url`, whose requests are signed
+    /// with `signer`.
+    pub fn register(&self, url: impl Into<String>, signer: WebhookVerifier) {
+        self.shared.receivers.write().insert(url.into(), signer);
+    }
+
+    /// Unregisters the receiver at `url`, its pending deliveries are dropped.
+    pub fn unregister(&self, url: &str) {
+        self.shared.receivers.write().remove(url);
+    }
+
+    /// Queues the delivery of an event to all the registered receivers.
+    pub async fn dispatch(&self, event: &str, payload: &impl Serialize) -> Result<()> {
+        let payload = serde_json::to_value(payload).map_err(InternalServerError)?;
+        let urls = self
+            .shared
+            .receivers
+            .read()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for url in urls {
+            self.queue
+                .enqueue(WebhookDelivery {
+                    id: delivery_id()?,
+                    url,
+                    event: event.to_string(),
+                    payload: payload.clone(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the most recent delivery attempts, the oldest first.
+    pub fn logs(&self) -> Vec<DeliveryLog> {
+        self.shared.logs.lock().iter().cloned().collect()
+    }
+
+    /// Spawns the workers delivering the events as tasks named `webhooks`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the concurrency is zero.
+    pub fn spawn(&self, tasks: &Tasks) {
+        let dispatcher = self.clone();
+        let dead_letter = self.dead_letter.clone();
+        self.queue
+            .worker(move |delivery| {
+                let dispatcher = dispatcher.clone();
+                async move { dispatcher.deliver(delivery).await }
+            })
+            .concurrency(self.concurrency)
+            .max_attempts(self.max_attempts)
+            .backoff(self.backoff, self.max_backoff)
+            .poll_interval(self.poll_interval)
+            .on_dead_letter(move |job| {
+                let dead_letter = dead_letter.clone();
+                async move {
+                    if let Some(hook) = dead_letter {
+                        hook(FailedDelivery {
+                            delivery: serde_json::from_value(job.payload).ok(),
+                            attempts: job.attempts,
+                            last_error: job.last_error,
+                        })
+                        .await;
+                    }
+                }
+            })
+            .spawn(tasks, "webhooks");
+    }
+
+    async fn deliver(&self, delivery: WebhookDelivery) -> Result<(), String> {
+        let signer = match self.shared.receivers.read().get(&delivery.url) {
+            Some(signer) => signer.clone(),
+            None => return Ok(()),
+        };
+
+        let res = self.send(&delivery, &signer).await;
+        let (status, error) = match &res {
+            Ok(status) if status.is_success() => (Some(*status), None),
+            Ok(status) => (Some(*status), Some(format!("unexpected status {status}"))),
+            Err(err) => (None, Some(err.clone())),
+        };
+        self.log(DeliveryLog {
+            id: delivery.id,
+            url: delivery.url,
+            event: delivery.event,
+            at: SystemTime::now(),
+            status,
+            error: error.clone(),
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    async fn send(
+        &self,
+        delivery: &WebhookDelivery,
+        signer: &WebhookVerifier,
+    ) -> Result<StatusCode, String> {
+        let body = serde_json::to_vec(&delivery.payload).map_err(|err| err.to_string())?;
+        let mut req = http::Request::builder()
+            .method(Method::POST)
+            .uri(&delivery.url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-webhook-event", &delivery.event)
+            .header("x-webhook-delivery", &delivery.id);
+        for (name, value) in signer.sign(&body, SystemTime::now()) {
+            req = req.header(name, value);
+        }
+        let req = req
+            .body(hyper::Body::from(body))
+            .map_err(|err| err.to_string())?;
+
+        match tokio::time::timeout(self.timeout, (self.sender)(req)).await {
+            Ok(Ok(resp)) => Ok(resp.status()),
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(_) => Err("timed out".to_string()),
+        }
+    }
+
+    fn log(&self, log: DeliveryLog) {
+        if self.max_logs == 0 {
+            return;
+        }
+        let mut logs = self.shared.logs.lock();
+        while logs.len() >= self.max_logs {
+            logs.pop_front();
+        }
+        logs.push_back(log);
+    }
+}
+
+fn sender<C>(client: hyper::Client<C>) -> HttpSender
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    Arc::new(move |req| {
+        let client = client.clone();
+        Box::pin(async move { client.request(req).await })
+    })
+}
+
+fn delivery_id() -> Result<String> {
+    let mut id = [0; 16];
+    SystemRandom::new()
+        .fill(&mut id)
+        .map_err(|_| InternalServerError(std::io::Error::from(std::io::ErrorKind::Other)))?;
+    Ok(hex::encode(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::sync::{mpsc, watch};
+
+    use super::*;
+    use crate::{
+        handler,
+        listener::{Acceptor, Listener, TcpListener},
+        tasks::MemoryJobStorage,
+        web::Data,
+        webhook::Webhook,
+        Endpoint, EndpointExt, Request, Server, ShutdownSignal,
+    };
+
+    async fn serve<E>(ep: E) -> SocketAddr
+    where
+        E: Endpoint + 'static,
+    {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = *acceptor.local_addr()[0].as_socket_addr().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(ep));
+        addr
+    }
+
+    #[tokio::test]
+    async fn dispatch() {
+        #[handler(internal)]
+        async fn receiver(
+            req: &Request,
+            Webhook(payload): Webhook<Value>,
+            tx: Data<&mpsc::UnboundedSender<(String, Value)>>,
+        ) -> StatusCode {
+            let event = req
+                .header("x-webhook-event")
+                .unwrap_or_default()
+                .to_string();
+            tx.send((event, payload)).unwrap();
+            StatusCode::NO_CONTENT
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<(String, Value)>();
+        let verifier = WebhookVerifier::stripe("whsec_test");
+        let addr = serve(receiver.data(verifier.clone()).data(tx)).await;
+
+        let (shutdown, signal) = watch::channel(false);
+        let tasks = Tasks::new(ShutdownSignal { rx: signal });
+        let dispatcher = WebhookDispatcher::new(MemoryJobStorage::new());
+        dispatcher.register(format!("http://{addr}/"), verifier);
+        dispatcher.spawn(&tasks);
+
+        dispatcher
+            .dispatch("order.created", &serde_json::json!({ "id": 1 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            ("order.created".to_string(), serde_json::json!({ "id": 1 }))
+        );
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let logs = dispatcher.logs();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].status, Some(StatusCode::NO_CONTENT));
+        assert_eq!(logs[0].error, None);
+        assert_eq!(logs[0].id.len(), 32);
+
+        shutdown.send_replace(true);
+        tasks.join().await;
+    }
+
+    #[tokio::test]
+    async fn retry_and_dead_letter() {
+        #[handler(internal)]
+        fn receiver() -> StatusCode {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+
+        let addr = serve(receiver).await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (shutdown, signal) = watch::channel(false);
+        let tasks = Tasks::new(ShutdownSignal { rx: signal });
+        let dispatcher = WebhookDispatcher::new(MemoryJobStorage::new())
+            .max_attempts(3)
+            .backoff(Duration::from_millis(10), Duration::from_millis(20))
+            .poll_interval(Duration::from_millis(10))
+            .max_logs(2)
+            .on_dead_letter(move |failed| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(failed).unwrap();
+                }
+            });
+        let url = format!("http://{addr}/hooks");
+        dispatcher.register(&url, WebhookVerifier::github("secret"));
+        dispatcher.spawn(&tasks);
+
+        dispatcher.dispatch("ping", &()).await.unwrap();
+        let failed = rx.recv().await.unwrap();
+        assert_eq!(failed.attempts, 3);
+        assert_eq!(
+            failed.last_error.as_deref(),
+            Some("unexpected status 503 Service Unavailable")
+        );
+        let delivery = failed.delivery.unwrap();
+        assert_eq!(
+            (delivery.url.as_str(), delivery.event.as_str()),
+            (url.as_str(), "ping")
+        );
+
+        let logs = dispatcher.logs();
+        assert_eq!(logs.len(), 2);
+        assert!(logs
+            .iter()
+            .all(|log| log.status == Some(StatusCode::SERVICE_UNAVAILABLE)));
+
+        shutdown.send_replace(true);
+        tasks.join().await;
+    }
+}
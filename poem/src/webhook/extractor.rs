@@ -0,0 +1,111 @@
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+
+use super::WebhookVerifier;
+use crate::{
+    error::{GetDataError, ParseJsonError},
+    web::Clock,
+    FromRequest, Request, RequestBody, Result,
+};
+
+/// An extractor for the raw body of a webhook request, after the
+/// verification of its signature by the [`WebhookVerifier`] of the request.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if no `WebhookVerifier` is added to the request.
+/// - [`WebhookError`](crate::error::WebhookError)
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WebhookBody(pub Bytes);
+
+#[async_trait::async_trait]
+impl<'a> FromRequest<'a> for WebhookBody {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let verifier = req
+            .data::<WebhookVerifier>()
+            .ok_or_else(|| GetDataError(std::any::type_name::<WebhookVerifier>()))?;
+        let data = body.take()?.into_bytes().await?;
+        verifier.verify(req.headers(), &data, Clock::of(req).now())?;
+        Ok(Self(data))
+    }
+}
+
+/// An extractor deserializing the JSON payload of a webhook request, after
+/// the verification of its signature by the [`WebhookVerifier`] of the
+/// request.
+///
+/// See the [`webhook`](super) module for an example.
+///
+/// # Errors
+///
+/// - [`GetDataError`] if no `WebhookVerifier` is added to the request.
+/// - [`WebhookError`](crate::error::WebhookError)
+/// - [`ParseJsonError`]
+/// - [`ReadBodyError`](crate::error::ReadBodyError)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Webhook<T>(pub T);
+
+#[async_trait::async_trait]
+impl<'a, T: DeserializeOwned> FromRequest<'a> for Webhook<T> {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> Result<Self> {
+        let WebhookBody(data) = WebhookBody::from_request(req, body).await?;
+        Ok(Self(
+            serde_json::from_slice(&data).map_err(ParseJsonError::Parse)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http::StatusCode;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{handler, test::TestClient, web::MockClock, EndpointExt};
+
+    #[derive(Deserialize)]
+    struct Event {
+        id: String,
+    }
+
+    #[handler(internal)]
+    fn index(Webhook(event): Webhook<Event>) -> String {
+        event.id
+    }
+
+    #[tokio::test]
+    async fn webhook() {
+        let clock = MockClock::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let verifier = WebhookVerifier::stripe("whsec_test");
+        let cli = TestClient::new(
+            index
+                .data(verifier.clone())
+                .data(Clock::from(clock.clone())),
+        );
+
+        let send = |body: &'static str| {
+            let mut req = cli.post("/").body(body);
+            for (name, value) in verifier.sign(body.as_bytes(), clock.now()) {
+                req = req.header(name, value);
+            }
+            req.send()
+        };
+        send(r#"{"id":"evt_1"}"#).await.assert_text("evt_1").await;
+        send("{}").await.assert_status(StatusCode::BAD_REQUEST);
+
+        let resp = cli.post("/").body("{}").send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        resp.assert_text("missing webhook signature header `stripe-signature`")
+            .await;
+
+        TestClient::new(index)
+            .post("/")
+            .body("{}")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
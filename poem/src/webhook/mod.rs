@@ -0,0 +1,65 @@
+//! Webhook receivers verifying the signatures of the requests.
+//!
+//! A [`WebhookVerifier`] added to the webhook route with
+//! [`EndpointExt::data`](crate::EndpointExt::data) checks the HMAC signature
+//! of the raw body before the [`Webhook`] extractor deserializes it, in the
+//! style of GitHub, Stripe or Slack. The signatures are compared in constant
+//! time, and the signed timestamps of Stripe and Slack must be within a
+//! replay window.
+//!
+//! The [`WebhookDispatcher`] sends the signed events to the receivers of
+//! other services, retrying the failed deliveries in the background.
+//!
+//! # Example
+//!
+//! ```
+//! use poem::{
+//!     handler,
+//!     http::StatusCode,
+//!     post,
+//!     test::TestClient,
+//!     webhook::{Webhook, WebhookVerifier},
+//!     EndpointExt, Route,
+//! };
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Push {
+//!     r#ref: String,
+//! }
+//!
+//! #[handler]
+//! fn push(Webhook(push): Webhook<Push>) -> String {
+//!     push.r#ref
+//! }
+//!
+//! let verifier = WebhookVerifier::github("It's a Secret to Everybody");
+//! let app = Route::new().at("/github", post(push).data(verifier.clone()));
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let cli = TestClient::new(app);
+//! let body = r#"{"ref":"refs/heads/main"}"#;
+//! let mut req = cli.post("/github").body(body);
+//! for (name, value) in verifier.sign(body.as_bytes(), std::time::SystemTime::now()) {
+//!     req = req.header(name, value);
+//! }
+//! req.send().await.assert_text("refs/heads/main").await;
+//! cli.post("/github")
+//!     .header("x-hub-signature-256", "sha256=00")
+//!     .body(body)
+//!     .send()
+//!     .await
+//!     .assert_status(StatusCode::UNAUTHORIZED);
+//! # });
+//! ```
+
+#[cfg(feature = "server")]
+mod dispatcher;
+mod extractor;
+mod verifier;
+
+#[cfg(feature = "server")]
+#[cfg_attr(docsrs, doc(cfg(feature = "server")))]
+pub use dispatcher::{DeliveryLog, FailedDelivery, WebhookDelivery, WebhookDispatcher};
+pub use extractor::{Webhook, WebhookBody};
+pub use verifier::WebhookVerifier;
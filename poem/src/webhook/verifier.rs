@@ -0,0 +1,316 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::{HeaderMap, HeaderName};
+use ring::{constant_time::verify_slices_are_equal, hmac};
+
+use crate::error::WebhookError;
+
+const GITHUB_SIGNATURE: &str = "x-hub-signature-256";
+const STRIPE_SIGNATURE: &str = "stripe-signature";
+const SLACK_SIGNATURE: &str = "x-slack-signature";
+const SLACK_TIMESTAMP: &str = "x-slack-request-timestamp";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Scheme {
+    GitHub,
+    Stripe,
+    Slack,
+}
+
+/// Verifies the HMAC-SHA256 signatures of the webhook requests, added to the
+/// webhook routes with [`EndpointExt::data`](crate::EndpointExt::data) for
+/// the [`Webhook`](super::Webhook) and [`WebhookBody`](super::WebhookBody)
+/// extractors.
+#[derive(Debug, Clone)]
+pub struct WebhookVerifier {
+    scheme: Scheme,
+    key: hmac::Key,
+    tolerance: Duration,
+}
+
+impl WebhookVerifier {
+    fn new(scheme: Scheme, secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            scheme,
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref()),
+            tolerance: Duration::from_secs(5 * 60),
+        }
+    }
+
+    /// Create a verifier of the GitHub webhooks, signing the body in the
+    /// `X-Hub-Signature-256: sha256=<hex>` header.
+    ///
+    /// GitHub doesn't sign a timestamp, so the replay window doesn't apply.
+    pub fn github(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Scheme::GitHub, secret)
+    }
+
+    /// Create a verifier of the Stripe webhooks, signing `<timestamp>.<body>`
+    /// in the `Stripe-Signature: t=<timestamp>,v1=<hex>` header.
+    ///
+    /// The header can contain several `v1` signatures while the secret is
+    /// rolled, one of them must match.
+    pub fn stripe(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Scheme::Stripe, secret)
+    }
+
+    /// Create a verifier of the Slack requests, signing
+    /// `v0:<timestamp>:<body>` in the `X-Slack-Signature: v0=<hex>` header,
+    /// with the timestamp of the `X-Slack-Request-Timestamp` header.
+    pub fn slack(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Scheme::Slack, secret)
+    }
+
+    /// Sets the maximum difference between the signed timestamp and the
+    /// current time, which rejects the replayed requests.
+    ///
+    /// Default is `5 minutes`.
+    #[must_use]
+    pub fn tolerance(self, tolerance: Duration) -> Self {
+        Self { tolerance, ..self }
+    }
+
+    /// Verifies the signature of `body` in the `headers`, at the time `now`.
+    pub fn verify(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        match self.scheme {
+            Scheme::GitHub => {
+                let signature = header(headers, GITHUB_SIGNATURE)?
+                    .strip_prefix("sha256=")
+                    .ok_or(WebhookError::MalformedSignature)?;
+                self.check(&[body], [signature])
+            }
+            Scheme::Stripe => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for (key, value) in header(headers, STRIPE_SIGNATURE)?
+                    .split(',')
+                    .filter_map(|item| item.trim().split_once('='))
+                {
+                    match key {
+                        "t" => timestamp = Some(value),
+                        "v1" => signatures.push(value),
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.ok_or(WebhookError::MalformedSignature)?;
+                if signatures.is_empty() {
+                    return Err(WebhookError::MalformedSignature);
+                }
+                self.check_timestamp(timestamp, now)?;
+                self.check(&[timestamp.as_bytes(), b".", body], signatures)
+            }
+            Scheme::Slack => {
+                let signature = header(headers, SLACK_SIGNATURE)?
+                    .strip_prefix("v0=")
+                    .ok_or(WebhookError::MalformedSignature)?;
+                let timestamp = header(headers, SLACK_TIMESTAMP)?;
+                self.check_timestamp(timestamp, now)?;
+                self.check(&[b"v0:", timestamp.as_bytes(), b":", body], [signature])
+            }
+        }
+    }
+
+    /// Returns the headers signing `body` at the time `timestamp`, for
+    /// testing the webhook handlers.
+    pub fn sign(&self, body: &[u8], timestamp: SystemTime) -> Vec<(HeaderName, String)> {
+        let timestamp = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        match self.scheme {
+            Scheme::GitHub => vec![(
+                HeaderName::from_static(GITHUB_SIGNATURE),
+                format!("sha256={}", self.signature(&[body])),
+            )],
+            Scheme::Stripe => vec![(
+                HeaderName::from_static(STRIPE_SIGNATURE),
+                format!(
+                    "t={},v1={}",
+                    timestamp,
+                    self.signature(&[timestamp.as_bytes(), b".", body])
+                ),
+            )],
+            Scheme::Slack => {
+                let signature = self.signature(&[b"v0:", timestamp.as_bytes(), b":", body]);
+                vec![
+                    (
+                        HeaderName::from_static(SLACK_SIGNATURE),
+                        format!("v0={signature}"),
+                    ),
+                    (HeaderName::from_static(SLACK_TIMESTAMP), timestamp),
+                ]
+            }
+        }
+    }
+
+    fn signature(&self, parts: &[&[u8]]) -> String {
+        let mut ctx = hmac::Context::with_key(&self.key);
+        for part in parts {
+            ctx.update(part);
+        }
+        hex::encode(ctx.sign())
+    }
+
+    fn check<'a>(
+        &self,
+        parts: &[&[u8]],
+        signatures: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), WebhookError> {
+        let mut ctx = hmac::Context::with_key(&self.key);
+        for part in parts {
+            ctx.update(part);
+        }
+        let expected = ctx.sign();
+
+        let mut matched = false;
+        for signature in signatures {
+            let signature = hex::decode(signature).map_err(|_| WebhookError::MalformedSignature)?;
+            matched |= verify_slices_are_equal(expected.as_ref(), &signature).is_ok();
+        }
+        if matched {
+            Ok(())
+        } else {
+            Err(WebhookError::InvalidSignature)
+        }
+    }
+
+    fn check_timestamp(&self, timestamp: &str, now: SystemTime) -> Result<(), WebhookError> {
+        let timestamp = timestamp
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| WebhookError::MalformedSignature)?;
+        let timestamp = UNIX_EPOCH
+            .checked_add(Duration::from_secs(timestamp))
+            .ok_or(WebhookError::MalformedSignature)?;
+        let skew = match now.duration_since(timestamp) {
+            Ok(elapsed) => elapsed,
+            Err(err) => err.duration(),
+        };
+        if skew > self.tolerance {
+            return Err(WebhookError::Expired);
+        }
+        Ok(())
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &'static str) -> Result<&'a str, WebhookError> {
+    headers
+        .get(name)
+        .ok_or(WebhookError::MissingSignature(name))?
+        .to_str()
+        .map_err(|_| WebhookError::MalformedSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: Vec<(HeaderName, String)>) -> HeaderMap {
+        pairs
+            .into_iter()
+            .map(|(name, value)| (name, value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn github() {
+        // the example of the GitHub documentation
+        let verifier = WebhookVerifier::github("It's a Secret to Everybody");
+        let signed = headers(verifier.sign(b"Hello, World!", UNIX_EPOCH));
+        assert_eq!(
+            signed[GITHUB_SIGNATURE],
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+        );
+        assert_eq!(
+            verifier.verify(&signed, b"Hello, World!", SystemTime::now()),
+            Ok(())
+        );
+        assert_eq!(
+            verifier.verify(&signed, b"Hello, World?", SystemTime::now()),
+            Err(WebhookError::InvalidSignature)
+        );
+        assert_eq!(
+            verifier.verify(&HeaderMap::new(), b"", SystemTime::now()),
+            Err(WebhookError::MissingSignature("x-hub-signature-256"))
+        );
+    }
+
+    #[test]
+    fn stripe() {
+        let verifier = WebhookVerifier::stripe("whsec_test");
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let signed = headers(verifier.sign(b"{}", now));
+        assert_eq!(verifier.verify(&signed, b"{}", now), Ok(()));
+
+        // one of the signatures of a rolled secret matches
+        let rolled = format!(
+            "{},v1={},v0=ignored",
+            signed[STRIPE_SIGNATURE].to_str().unwrap(),
+            "00".repeat(32)
+        );
+        let mut rolled_headers = HeaderMap::new();
+        rolled_headers.insert(STRIPE_SIGNATURE, rolled.parse().unwrap());
+        assert_eq!(verifier.verify(&rolled_headers, b"{}", now), Ok(()));
+
+        assert_eq!(
+            verifier.verify(&signed, b"{}", now + Duration::from_secs(301)),
+            Err(WebhookError::Expired)
+        );
+        assert_eq!(
+            verifier.tolerance(Duration::from_secs(600)).verify(
+                &signed,
+                b"{}",
+                now + Duration::from_secs(301)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            WebhookVerifier::stripe("other").verify(&signed, b"{}", now),
+            Err(WebhookError::InvalidSignature)
+        );
+
+        let mut huge = HeaderMap::new();
+        huge.insert(
+            STRIPE_SIGNATURE,
+            format!("t={},v1={}", u64::MAX, "00".repeat(32))
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            WebhookVerifier::stripe("whsec_test").verify(&huge, b"{}", now),
+            Err(WebhookError::MalformedSignature)
+        );
+    }
+
+    #[test]
+    fn slack() {
+        let verifier = WebhookVerifier::slack("8f742231b10e8888abcd99yyyzzz85a5");
+        let now = UNIX_EPOCH + Duration::from_secs(1_531_420_618);
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&command=%2Fweather";
+        let signed = headers(verifier.sign(body, now));
+        assert_eq!(signed[SLACK_TIMESTAMP], "1531420618");
+        assert_eq!(verifier.verify(&signed, body, now), Ok(()));
+        assert_eq!(
+            verifier.verify(&signed, body, now - Duration::from_secs(301)),
+            Err(WebhookError::Expired)
+        );
+
+        let mut tampered = signed.clone();
+        tampered.insert(SLACK_TIMESTAMP, "1531420619".parse().unwrap());
+        assert_eq!(
+            verifier.verify(&tampered, body, now),
+            Err(WebhookError::InvalidSignature)
+        );
+        tampered.insert(SLACK_SIGNATURE, "v0=xyz".parse().unwrap());
+        assert_eq!(
+            verifier.verify(&tampered, body, now),
+            Err(WebhookError::MalformedSignature)
+        );
+    }
+}